@@ -0,0 +1,752 @@
+//! Streaming parsing of Unix ar archives in the common, GNU and BSD variants,
+//! which Debian packages and static libraries use.
+//!
+//! Each member of the archive is a file of its own: its data is returned
+//! as output, and the next member as `State::NextFile`, like the entries
+//! of a tar archive. Symbol tables and the table of GNU long names aren't
+//! returned as members. The archive has no end marker, so after each
+//! member that ends with the input, `State::NeedsInputOrEof` is returned.
+//!
+//! Debian packages are read with `start_deb_stream`, which chains the
+//! parser with the tar parser: members that are tar archives, compressed
+//! with gzip or, with the `xz` feature, xz, return each of their entries
+//! as a file of its own.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::chain::{self, Chain};
+use crate::gzip::GZipFile;
+use crate::input_helper::{Input, InputHandler};
+use crate::tar::{self, TarFile};
+#[cfg(feature = "xz")]
+use crate::xz::{self, XzFile};
+use crate::{gzip, Compression, Error, RatioLimit, SizeLimits, State};
+
+pub mod headers;
+
+use self::headers::{Header, Name, HEADER_SIZE, SIGNATURE};
+
+const OUTPUT_BUFFER_SIZE: usize = 32 * 1024;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum ArError {
+    InvalidSignature,
+    InvalidHeader,
+    /// A GNU long name referred to a name outside the table of long names.
+    InvalidName,
+    /// The size of a member in its header is more than `Options::max_member_size`.
+    MemberTooLarge,
+}
+
+impl std::error::Error for ArError {
+    fn description(&self) -> &str {
+        "ar parsing error"
+    }
+}
+
+impl std::fmt::Display for ArError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        use self::ArError::*;
+        match self {
+            InvalidSignature => write!(f, "invalid signature"),
+            InvalidHeader => write!(f, "invalid header"),
+            InvalidName => write!(f, "invalid long name reference"),
+            MemberTooLarge => write!(f, "member exceeds the size limit"),
+        }
+    }
+}
+
+/// Options for parsing an ar archive, set with the builder methods.
+/// The options are inherited by the files returned in `State::NextFile`
+/// and `State::NeedsInputOrEof`.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Options {
+    pub(crate) limits: SizeLimits,
+}
+
+impl Options {
+    /// Limits the size of each member to `size` bytes. A larger member fails the stream
+    /// with `ArError::MemberTooLarge` once its header has been parsed, before any
+    /// of its data is returned. In a `DebFile`, the limit applies to the decompressed
+    /// size of the compressed tar archives as well, which fail the stream with
+    /// `GZipError::MemberTooLarge` or `XzError::StreamTooLarge` as soon as the
+    /// output that exceeds the limit has been decompressed.
+    pub fn max_member_size(mut self, size: u64) -> Self {
+        self.limits.max_size = Some(size);
+        self
+    }
+
+    /// Limits the ratio of the decompressed size of the compressed tar archives
+    /// in the members of a `DebFile` to their compressed size. Once a tar archive
+    /// has decompressed to more than `min_size` bytes, the stream fails with
+    /// `GZipError::CompressionRatioExceeded` or `XzError::CompressionRatioExceeded`
+    /// if its output so far is more than `ratio` times the compressed data that
+    /// produced it. The members themselves aren't compressed, so the limit
+    /// doesn't apply to them.
+    pub fn max_compression_ratio(mut self, ratio: u64, min_size: u64) -> Self {
+        self.limits.max_compression_ratio = Some(RatioLimit { ratio, min_size });
+        self
+    }
+}
+
+pub struct ArFile {
+    state: InternalState,
+    unparsed: Vec<u8>,
+    header: Option<Header>,
+    /// The table of GNU long names, handed over to the next member.
+    long_names: Vec<u8>,
+    output: Vec<u8>,
+    options: Options,
+}
+
+impl std::fmt::Debug for ArFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("ArFile")
+            .field("state", &self.state)
+            .field("unparsed", &self.unparsed)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum InternalState {
+    Signature,
+    Header,
+    /// Reading the table of GNU long names.
+    LongNames {
+        remaining: u64,
+        padding: u64,
+    },
+    /// Reading a BSD long name that precedes the data.
+    Name {
+        remaining: u64,
+        padding: u64,
+    },
+    Data {
+        remaining: u64,
+        padding: u64,
+    },
+    EntryEnd,
+    /// The next member has been returned.
+    End,
+    Skip {
+        remaining: u64,
+        then: Box<InternalState>,
+    },
+    Sentinel,
+    /// The stream failed with the error, which later reads return again.
+    Error(ArError),
+}
+
+impl InternalState {
+    /// Returns a state that skips `bytes` bytes of input before continuing with `then`.
+    fn skip_then(bytes: u64, then: InternalState) -> InternalState {
+        if bytes == 0 {
+            then
+        } else {
+            InternalState::Skip {
+                remaining: bytes,
+                then: Box::new(then),
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
+enum ParseResult {
+    Continue,
+    NeedsInput,
+    Output,
+    Error(ArError),
+    EndOfFile,
+    NextFile(ArFile),
+    NeedsInputOrEof(ArFile),
+}
+
+impl ArFile {
+    pub fn get_output(&self) -> &[u8] {
+        &self.output
+    }
+
+    /// The header of the member, once it has been parsed.
+    pub fn header(&self) -> Option<&Header> {
+        self.header.as_ref()
+    }
+
+    pub fn filename(&self) -> Option<&[u8]> {
+        self.header.as_ref().map(|header| &header.name[..])
+    }
+
+    pub fn read<'i, 's>(&'s mut self, input: &'i [u8]) -> Result<State<'i, 's, ArFile>, ArError> {
+        let mut ihandler = InputHandler::take_storage(&mut self.unparsed, input);
+        let mut unparsed = ihandler.get_unparsed();
+
+        loop {
+            let mut state = InternalState::Sentinel;
+            std::mem::swap(&mut self.state, &mut state);
+            let (bytes_consumed, new_state, res) = self.parse_step(state, unparsed);
+            unparsed = ihandler.consumed(bytes_consumed);
+            self.state = new_state;
+            match res {
+                ParseResult::Continue => (),
+                ParseResult::NeedsInput => {
+                    let extended_len = ihandler.extend_input();
+                    // Nothing in input left to extend, so we need the user to provide more
+                    if extended_len == 0 {
+                        ihandler.return_storage(&mut self.unparsed);
+                        return Ok(State::NeedsInput);
+                    }
+                    unparsed = ihandler.get_unparsed();
+                }
+                ParseResult::Output => {
+                    let unparsed_input = ihandler.take_rest(&mut self.unparsed);
+                    return Ok(State::HasOutput {
+                        unparsed_input,
+                        output: &self.output,
+                    });
+                }
+                ParseResult::NextFile(next_file) => {
+                    let unparsed_input = ihandler.take_rest(&mut self.unparsed);
+                    return Ok(State::NextFile {
+                        unparsed_input,
+                        next_file,
+                    });
+                }
+                ParseResult::NeedsInputOrEof(next_file) => {
+                    ihandler.return_storage(&mut self.unparsed);
+                    return Ok(State::NeedsInputOrEof(next_file));
+                }
+                ParseResult::EndOfFile => {
+                    ihandler.return_storage(&mut self.unparsed);
+                    return Ok(State::EndOfFile);
+                }
+                ParseResult::Error(err) => return Err(err),
+            };
+            // At the end of a member, running out of input may also end the archive.
+            if unparsed.is_empty() && self.state != InternalState::EntryEnd {
+                ihandler.return_storage(&mut self.unparsed);
+                return Ok(State::NeedsInput);
+            }
+        }
+    }
+
+    pub fn read_with<'i>(
+        &mut self,
+        mut input: &'i [u8],
+        mut callback: impl FnMut(&[u8]),
+    ) -> Result<State<'i, 'i, ArFile>, ArError> {
+        loop {
+            match self.read(input)?.try_no_output() {
+                Err((unparsed_input, output)) => {
+                    input = unparsed_input;
+                    callback(output);
+                }
+                Ok(state) => return Ok(state),
+            }
+        }
+    }
+
+    fn parse_step<'long, 'short>(
+        &'short mut self,
+        state: InternalState,
+        input: Input<'long, 'short>,
+    ) -> (usize, InternalState, ParseResult) {
+        match state {
+            InternalState::Signature => ArFile::check_signature(input),
+            InternalState::Header => self.parse_header(input),
+            InternalState::LongNames { remaining, padding } => {
+                self.read_long_names(input, remaining, padding)
+            }
+            InternalState::Name { remaining, padding } => self.read_name(input, remaining, padding),
+            InternalState::Data { remaining, padding } => self.read_data(input, remaining, padding),
+            InternalState::EntryEnd => self.end(input),
+            InternalState::End => (0, InternalState::End, ParseResult::EndOfFile),
+            InternalState::Skip { remaining, then } => ArFile::skip(input, remaining, then),
+            InternalState::Sentinel => unreachable!("parse_step is never called with Sentinel"),
+            InternalState::Error(error) => {
+                (0, InternalState::Error(error), ParseResult::Error(error))
+            }
+        }
+    }
+
+    fn check_signature<'long, 'short>(
+        input: Input<'long, 'short>,
+    ) -> (usize, InternalState, ParseResult) {
+        if input.len() < SIGNATURE.len() {
+            return (0, InternalState::Signature, ParseResult::NeedsInput);
+        }
+        if &input[..SIGNATURE.len()] != SIGNATURE {
+            return (
+                0,
+                InternalState::Error(ArError::InvalidSignature),
+                ParseResult::Error(ArError::InvalidSignature),
+            );
+        }
+        (
+            SIGNATURE.len(),
+            InternalState::Header,
+            ParseResult::Continue,
+        )
+    }
+
+    fn parse_header<'long, 'short>(
+        &mut self,
+        input: Input<'long, 'short>,
+    ) -> (usize, InternalState, ParseResult) {
+        if input.len() < HEADER_SIZE {
+            return (0, InternalState::Header, ParseResult::NeedsInput);
+        }
+        let (mut header, name) = match Header::parse(&input[..HEADER_SIZE]) {
+            Ok(parsed) => parsed,
+            Err(e) => return (0, InternalState::Error(e), ParseResult::Error(e)),
+        };
+        let padding = headers::padding(header.size);
+        let state = match name {
+            Name::SymbolTable => {
+                InternalState::skip_then(header.size + padding, InternalState::Header)
+            }
+            Name::LongNames => {
+                if header.size > headers::MAX_LONG_NAMES_SIZE {
+                    return (
+                        0,
+                        InternalState::Error(ArError::InvalidHeader),
+                        ParseResult::Error(ArError::InvalidHeader),
+                    );
+                }
+                self.long_names.clear();
+                InternalState::LongNames {
+                    remaining: header.size,
+                    padding,
+                }
+            }
+            Name::LongName(offset) => match headers::long_name(&self.long_names, offset) {
+                Ok(name) => {
+                    header.name = name;
+                    InternalState::Data {
+                        remaining: header.size,
+                        padding,
+                    }
+                }
+                Err(e) => return (0, InternalState::Error(e), ParseResult::Error(e)),
+            },
+            Name::Bsd(len) => {
+                if len > header.size || len > headers::MAX_NAME_SIZE {
+                    return (
+                        0,
+                        InternalState::Error(ArError::InvalidHeader),
+                        ParseResult::Error(ArError::InvalidHeader),
+                    );
+                }
+                header.size -= len;
+                InternalState::Name {
+                    remaining: len,
+                    padding,
+                }
+            }
+            Name::Plain(name) => {
+                header.name = name;
+                InternalState::Data {
+                    remaining: header.size,
+                    padding,
+                }
+            }
+        };
+        if let InternalState::Data { .. } | InternalState::Name { .. } = state {
+            if self.options.limits.exceeds_size(header.size) {
+                return (
+                    0,
+                    InternalState::Error(ArError::MemberTooLarge),
+                    ParseResult::Error(ArError::MemberTooLarge),
+                );
+            }
+            self.header = Some(header);
+        }
+        (HEADER_SIZE, state, ParseResult::Continue)
+    }
+
+    fn read_long_names<'long, 'short>(
+        &mut self,
+        input: Input<'long, 'short>,
+        remaining: u64,
+        padding: u64,
+    ) -> (usize, InternalState, ParseResult) {
+        let available = std::cmp::min(remaining, input.len() as u64) as usize;
+        self.long_names.extend_from_slice(&input[..available]);
+        let remaining = remaining - available as u64;
+        let state = if remaining == 0 {
+            InternalState::skip_then(padding, InternalState::Header)
+        } else {
+            InternalState::LongNames { remaining, padding }
+        };
+        (available, state, ParseResult::Continue)
+    }
+
+    fn read_name<'long, 'short>(
+        &mut self,
+        input: Input<'long, 'short>,
+        remaining: u64,
+        padding: u64,
+    ) -> (usize, InternalState, ParseResult) {
+        let header = self
+            .header
+            .as_mut()
+            .expect("The header is parsed before the name");
+        let available = std::cmp::min(remaining, input.len() as u64) as usize;
+        header.name.extend_from_slice(&input[..available]);
+        let remaining = remaining - available as u64;
+        let state = if remaining == 0 {
+            // The name is padded with NULs by some archivers.
+            while let Some(0) = header.name.last() {
+                header.name.pop();
+            }
+            InternalState::Data {
+                remaining: header.size,
+                padding,
+            }
+        } else {
+            InternalState::Name { remaining, padding }
+        };
+        (available, state, ParseResult::Continue)
+    }
+
+    fn read_data<'long, 'short>(
+        &mut self,
+        input: Input<'long, 'short>,
+        remaining: u64,
+        padding: u64,
+    ) -> (usize, InternalState, ParseResult) {
+        if remaining == 0 {
+            return (
+                0,
+                InternalState::skip_then(padding, InternalState::EntryEnd),
+                ParseResult::Continue,
+            );
+        }
+        if input.is_empty() {
+            return (
+                0,
+                InternalState::Data { remaining, padding },
+                ParseResult::NeedsInput,
+            );
+        }
+
+        let available = std::cmp::min(remaining, input.len() as u64) as usize;
+        let available = std::cmp::min(available, OUTPUT_BUFFER_SIZE);
+        self.output.clear();
+        self.output.extend_from_slice(&input[..available]);
+        (
+            available,
+            InternalState::Data {
+                remaining: remaining - available as u64,
+                padding,
+            },
+            ParseResult::Output,
+        )
+    }
+
+    /// Checks whether the input ends with the member or continues with the next one.
+    fn end<'long, 'short>(
+        &mut self,
+        input: Input<'long, 'short>,
+    ) -> (usize, InternalState, ParseResult) {
+        let mut next_file = ArFile {
+            state: InternalState::Header,
+            unparsed: Vec::new(),
+            header: None,
+            long_names: Vec::new(),
+            output: Vec::new(),
+            options: self.options.clone(),
+        };
+        if input.is_empty() {
+            next_file.long_names = std::mem::take(&mut self.long_names);
+            return (
+                0,
+                InternalState::End,
+                ParseResult::NeedsInputOrEof(next_file),
+            );
+        }
+        if input.len() < HEADER_SIZE {
+            return (0, InternalState::EntryEnd, ParseResult::NeedsInput);
+        }
+        // The next member parses its own header. The header may have been
+        // buffered from several inputs, so it's handed over with the member.
+        next_file.unparsed = input[..HEADER_SIZE].to_vec();
+        next_file.long_names = std::mem::take(&mut self.long_names);
+        (
+            HEADER_SIZE,
+            InternalState::End,
+            ParseResult::NextFile(next_file),
+        )
+    }
+
+    /// Consumes input until `remaining` bytes are skipped, then continues with `then`.
+    fn skip<'long, 'short>(
+        input: Input<'long, 'short>,
+        remaining: u64,
+        then: Box<InternalState>,
+    ) -> (usize, InternalState, ParseResult) {
+        let skipped = std::cmp::min(remaining, input.len() as u64);
+        let remaining = remaining - skipped;
+        let state = if remaining == 0 {
+            *then
+        } else {
+            InternalState::Skip { remaining, then }
+        };
+        (skipped as usize, state, ParseResult::Continue)
+    }
+}
+
+impl chain::Stream for ArFile {
+    type Error = ArError;
+
+    fn read<'i, 's>(&'s mut self, input: &'i [u8]) -> Result<State<'i, 's, ArFile>, ArError> {
+        ArFile::read(self, input)
+    }
+
+    fn get_output(&self) -> &[u8] {
+        ArFile::get_output(self)
+    }
+
+    fn name(&self) -> Option<&[u8]> {
+        self.filename()
+    }
+}
+
+/// A member that is a tar archive, compressed or not.
+#[allow(clippy::large_enum_variant)]
+enum TarMember {
+    Tar(TarFile),
+    Gz(Chain<GZipFile, TarFile>),
+    #[cfg(feature = "xz")]
+    Xz(Chain<XzFile, TarFile>),
+}
+
+impl TarMember {
+    fn tar(&self) -> Option<&TarFile> {
+        match self {
+            TarMember::Tar(file) => Some(file),
+            TarMember::Gz(chain) => chain.inner(),
+            #[cfg(feature = "xz")]
+            TarMember::Xz(chain) => chain.inner(),
+        }
+    }
+}
+
+impl chain::Stream for TarMember {
+    type Error = Error;
+
+    fn read<'i, 's>(&'s mut self, input: &'i [u8]) -> Result<State<'i, 's, TarMember>, Error> {
+        Ok(match self {
+            TarMember::Tar(file) => file.read(input)?.map_file(TarMember::Tar),
+            TarMember::Gz(chain) => chain.read(input)?.map_file(TarMember::Gz),
+            #[cfg(feature = "xz")]
+            TarMember::Xz(chain) => chain.read(input)?.map_file(TarMember::Xz),
+        })
+    }
+
+    fn get_output(&self) -> &[u8] {
+        match self {
+            TarMember::Tar(file) => file.get_output(),
+            TarMember::Gz(chain) => chain.get_output(),
+            #[cfg(feature = "xz")]
+            TarMember::Xz(chain) => chain.get_output(),
+        }
+    }
+
+    fn name(&self) -> Option<&[u8]> {
+        match self {
+            TarMember::Tar(file) => file.filename(),
+            TarMember::Gz(chain) => chain.name(),
+            #[cfg(feature = "xz")]
+            TarMember::Xz(chain) => chain.name(),
+        }
+    }
+}
+
+/// The magic number of gzip, followed by the deflate compression method.
+const GZIP_MAGIC: &[u8] = b"\x1f\x8b\x08";
+
+impl chain::Container for TarMember {
+    type Options = Options;
+
+    const PROBE_LEN: usize = tar::headers::BLOCK_SIZE;
+
+    fn is_start(output: &[u8]) -> bool {
+        #[cfg(feature = "xz")]
+        {
+            if xz::is_xz_header(output) {
+                return true;
+            }
+        }
+        output.starts_with(GZIP_MAGIC) || tar::headers::is_tar_header(output)
+    }
+
+    fn start(probe: &[u8], options: &Options) -> TarMember {
+        #[cfg(feature = "xz")]
+        {
+            if xz::is_xz_header(probe) {
+                let xz_options = xz::Options {
+                    limits: options.limits,
+                };
+                return TarMember::Xz(Chain::new(xz::start_stream_with(xz_options)));
+            }
+        }
+        if probe.starts_with(GZIP_MAGIC) {
+            let mut gzip_options = gzip::Options::default();
+            if let Some(size) = options.limits.max_size {
+                gzip_options = gzip_options.max_member_size(size);
+            }
+            if let Some(RatioLimit { ratio, min_size }) = options.limits.max_compression_ratio {
+                gzip_options = gzip_options.max_compression_ratio(ratio, min_size);
+            }
+            TarMember::Gz(Chain::new(gzip::start_stream_with(gzip_options)))
+        } else {
+            TarMember::Tar(tar::start_stream())
+        }
+    }
+}
+
+/// An ar archive whose members may be tar archives, like the members
+/// of a Debian package. The entries of the tar archives, which may be
+/// compressed with gzip or, with the `xz` feature, xz, are files of their own,
+/// like with `TarFile`. The other members are returned as they are,
+/// like with `ArFile`.
+pub struct DebFile(Box<Chain<ArFile, TarMember>>);
+
+impl std::fmt::Debug for DebFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("DebFile")
+            .field("ar", &self.0.outer())
+            .field("tar", &self.0.inner().and_then(TarMember::tar))
+            .finish()
+    }
+}
+
+impl DebFile {
+    pub fn get_output(&self) -> &[u8] {
+        self.0.get_output()
+    }
+
+    /// Whether the ar member is a tar archive. This is known
+    /// once the first output of the member has been returned.
+    pub fn is_tar(&self) -> bool {
+        self.0.is_piped()
+    }
+
+    /// The header of the ar member.
+    pub fn ar_header(&self) -> Option<&Header> {
+        self.0.outer().and_then(ArFile::header)
+    }
+
+    /// The header of the tar entry, once it has been parsed.
+    pub fn tar_header(&self) -> Option<&tar::headers::Header> {
+        self.0
+            .inner()
+            .and_then(TarMember::tar)
+            .and_then(TarFile::header)
+    }
+
+    /// The name of the tar entry or, if the member isn't a tar archive,
+    /// the name of the ar member.
+    pub fn filename(&self) -> Option<&[u8]> {
+        self.0.name()
+    }
+
+    /// The modification time of the tar entry or, if the member isn't
+    /// a tar archive, that of the ar member.
+    pub fn last_modified(&self) -> Option<SystemTime> {
+        let mtime = match self.0.inner() {
+            Some(member) => member.tar()?.header()?.mtime,
+            None => self.ar_header()?.mtime,
+        };
+        UNIX_EPOCH.checked_add(Duration::from_secs(mtime))
+    }
+
+    /// The size of the tar entry or, if the member isn't a tar archive,
+    /// that of the ar member.
+    pub fn size(&self) -> Option<u64> {
+        match self.0.inner() {
+            Some(member) => Some(member.tar()?.header()?.size),
+            None => Some(self.ar_header()?.size),
+        }
+    }
+
+    /// How the tar archive in the ar member is compressed.
+    pub(crate) fn compression(&self) -> Compression {
+        match self.0.inner() {
+            Some(TarMember::Gz(_)) => Compression::Deflate,
+            #[cfg(feature = "xz")]
+            Some(TarMember::Xz(_)) => Compression::Xz,
+            Some(TarMember::Tar(_)) | None => Compression::None,
+        }
+    }
+
+    pub fn read<'i, 's>(&'s mut self, input: &'i [u8]) -> Result<State<'i, 's, DebFile>, Error> {
+        Ok(self.0.read(input)?.map_file(|next| DebFile(Box::new(next))))
+    }
+
+    pub fn read_with<'i>(
+        &mut self,
+        mut input: &'i [u8],
+        mut callback: impl FnMut(&[u8]),
+    ) -> Result<State<'i, 'i, DebFile>, Error> {
+        loop {
+            match self.read(input)?.try_no_output() {
+                Err((unparsed_input, output)) => {
+                    input = unparsed_input;
+                    callback(output);
+                }
+                Ok(state) => return Ok(state),
+            }
+        }
+    }
+}
+
+/// Starts a stream of an ar archive. The returned file is the first member.
+pub fn start_stream() -> ArFile {
+    start_stream_with(Options::default())
+}
+
+/// Starts a stream of an ar archive that is parsed according to `options`.
+pub fn start_stream_with(options: Options) -> ArFile {
+    ArFile {
+        state: InternalState::Signature,
+        unparsed: Vec::new(),
+        header: None,
+        long_names: Vec::new(),
+        output: Vec::new(),
+        options,
+    }
+}
+
+/// Starts a stream whose first bytes, `prefix`, were already read from the input
+/// to detect the format. They are parsed together with the input of the next `read`.
+pub(crate) fn start_stream_with_options_and_prefix(options: Options, prefix: &[u8]) -> ArFile {
+    let mut file = start_stream_with(options);
+    file.unparsed = prefix.to_vec();
+    file
+}
+
+/// Starts a stream of an ar archive whose members may be tar archives, like a Debian package.
+pub fn start_deb_stream() -> DebFile {
+    start_deb_stream_with(Options::default())
+}
+
+/// Starts a stream of an ar archive whose members may be tar archives, like a Debian package,
+/// that is parsed according to `options`.
+pub fn start_deb_stream_with(options: Options) -> DebFile {
+    DebFile(Box::new(Chain::with_options(
+        start_stream_with(options.clone()),
+        options,
+    )))
+}
+
+/// Starts a stream of an ar archive whose first bytes, `prefix`, were already read from
+/// the input to detect the format. They are parsed together with the input of the next `read`.
+pub(crate) fn start_deb_stream_with_options_and_prefix(options: Options, prefix: &[u8]) -> DebFile {
+    DebFile(Box::new(Chain::with_options(
+        start_stream_with_options_and_prefix(options.clone(), prefix),
+        options,
+    )))
+}
@@ -0,0 +1,144 @@
+use std::convert::TryFrom;
+
+use super::ArError;
+
+/// The global header that starts an archive.
+pub const SIGNATURE: &[u8] = b"!<arch>\n";
+/// The size of the header of a member.
+pub const HEADER_SIZE: usize = 60;
+/// Longer tables of long names are rejected instead of being buffered.
+pub const MAX_LONG_NAMES_SIZE: u64 = 1024 * 1024;
+/// Longer BSD-style names are rejected instead of being buffered.
+pub const MAX_NAME_SIZE: u64 = 4096;
+
+const HEADER_END: &[u8] = b"`\n";
+const BSD_NAME_PREFIX: &[u8] = b"#1/";
+
+/// Whether `input` starts like an ar archive. The first 6 bytes are enough to tell.
+pub fn is_ar_header(input: &[u8]) -> bool {
+    let len = std::cmp::min(input.len(), SIGNATURE.len());
+    len >= 6 && input[..len] == SIGNATURE[..len]
+}
+
+/// The number of padding bytes after `len` bytes of data.
+/// The data of the members is padded to an even length.
+pub fn padding(len: u64) -> u64 {
+    len % 2
+}
+
+/// The name field of a header, which may refer to a name stored elsewhere.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Name {
+    Plain(Vec<u8>),
+    /// The symbol table of a GNU or System V archive of object files, `/` or `/SYM64/`.
+    SymbolTable,
+    /// The table of long names of a GNU archive, `//`.
+    LongNames,
+    /// A GNU long name, `/` followed by its offset in the table of long names.
+    LongName(u64),
+    /// A BSD long name, `#1/` followed by its length.
+    /// The name is stored at the start of the data.
+    Bsd(u64),
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Header {
+    /// The name of the member. GNU archives end short names with a `/`,
+    /// which isn't a part of the name.
+    pub name: Vec<u8>,
+    /// The last modification time, in seconds since the Unix epoch.
+    pub mtime: u64,
+    pub uid: u32,
+    pub gid: u32,
+    /// The file type and the permissions.
+    pub mode: u32,
+    /// The size of the data. Doesn't include a BSD long name stored before the data.
+    pub size: u64,
+}
+
+/// Parses a numeric field, which is left-aligned and padded with spaces.
+/// The symbol table of some archives leaves fields blank, which is read as 0.
+fn field(input: &[u8], radix: u32) -> Result<u64, ArError> {
+    let end = input
+        .iter()
+        .position(|&byte| byte == b' ')
+        .unwrap_or(input.len());
+    let (digits, padding) = input.split_at(end);
+    if digits.is_empty() {
+        return Ok(0);
+    }
+    if padding.iter().any(|&byte| byte != b' ') {
+        return Err(ArError::InvalidHeader);
+    }
+    std::str::from_utf8(digits)
+        .ok()
+        .and_then(|digits| u64::from_str_radix(digits, radix).ok())
+        .ok_or(ArError::InvalidHeader)
+}
+
+fn trim_end(mut input: &[u8]) -> &[u8] {
+    while let Some((b' ', rest)) = input.split_last() {
+        input = rest;
+    }
+    input
+}
+
+impl Name {
+    fn parse(input: &[u8]) -> Result<Name, ArError> {
+        let name = trim_end(input);
+        let number = |digits: &[u8]| {
+            std::str::from_utf8(digits)
+                .ok()
+                .and_then(|digits| digits.parse().ok())
+                .ok_or(ArError::InvalidHeader)
+        };
+        Ok(match name {
+            b"/" | b"/SYM64/" => Name::SymbolTable,
+            b"//" => Name::LongNames,
+            _ if name.starts_with(BSD_NAME_PREFIX) => {
+                Name::Bsd(number(&name[BSD_NAME_PREFIX.len()..])?)
+            }
+            [b'/', offset @ ..] => Name::LongName(number(offset)?),
+            [name @ .., b'/'] => Name::Plain(name.to_vec()),
+            _ => Name::Plain(name.to_vec()),
+        })
+    }
+}
+
+impl Header {
+    /// Parses the header of a member, returning it with an empty name
+    /// and the name field, which is resolved by the caller.
+    /// The size includes a BSD long name, if there is one.
+    /// `input` must be `HEADER_SIZE` bytes long.
+    pub fn parse(input: &[u8]) -> Result<(Header, Name), ArError> {
+        debug_assert_eq!(input.len(), HEADER_SIZE);
+        if &input[58..60] != HEADER_END {
+            return Err(ArError::InvalidHeader);
+        }
+        let name = Name::parse(&input[..16])?;
+        let header = Header {
+            name: Vec::new(),
+            mtime: field(&input[16..28], 10)?,
+            uid: field(&input[28..34], 10)? as u32,
+            gid: field(&input[34..40], 10)? as u32,
+            mode: field(&input[40..48], 8)? as u32,
+            size: field(&input[48..58], 10)?,
+        };
+        Ok((header, name))
+    }
+}
+
+/// Looks up the name at `offset` in a GNU table of long names,
+/// where each name ends with `/\n`.
+pub fn long_name(long_names: &[u8], offset: u64) -> Result<Vec<u8>, ArError> {
+    let names = usize::try_from(offset)
+        .ok()
+        .and_then(|offset| long_names.get(offset..))
+        .ok_or(ArError::InvalidName)?;
+    let end = names
+        .iter()
+        .position(|&byte| byte == b'\n')
+        .unwrap_or(names.len());
+    let name = &names[..end];
+    Ok(name.strip_suffix(b"/").unwrap_or(name).to_vec())
+}
@@ -0,0 +1,330 @@
+//! A pull-based reader of archives and compressed files over `io::Read`.
+//!
+//! `Archive` drives the same autodetecting state machine as `start_stream`,
+//! reading input from the reader as it's needed, and hands out its entries
+//! one at a time: `Archive::next_entry` skips what's left of the current entry
+//! and returns the next one, whose metadata is known once it's returned,
+//! and whose decompressed content is then pulled with `Entry::next_chunk`,
+//! or read through the `io::Read` and `io::BufRead` implementations of `Entry`.
+//! Formats without entries, such as gzip, have one entry per file or member.
+
+use std::io::{self, BufRead, Read};
+use std::task::Poll;
+
+use crate::{start_stream_with, DriveError, EntryMetadata, Error, File, Options, State};
+
+/// The size of the input buffer of `Archive`.
+const INPUT_BUFFER_SIZE: usize = 64 * 1024;
+
+/// What `Core::poll_pull` got from the stream.
+enum Pulled {
+    /// The current file has output, which is in `File::get_output`.
+    Output,
+    /// The current file ended, with this metadata.
+    EntryEnd(EntryMetadata),
+    /// The stream ended.
+    End,
+}
+
+/// The state of an archive that is read entry by entry, independent of
+/// where its input comes from. The input is read with a function that fills
+/// the buffer it's given like `io::Read::read`, except that it may also
+/// return `Poll::Pending`, in which case the operation can be retried later.
+/// This is shared by `Archive` and the asynchronous readers.
+pub(crate) struct Core {
+    /// The file of the current entry, or `None` once the stream has ended.
+    file: Option<File>,
+    input: Vec<u8>,
+    input_pos: usize,
+    input_len: usize,
+    /// Whether the current file has parsed all of the input, so more is read.
+    needs_input: bool,
+    /// Whether the input may end at the current position.
+    may_end: bool,
+    metadata: EntryMetadata,
+    /// Whether the content of the current entry may continue.
+    in_entry: bool,
+    /// The position in the output of `file` that hasn't been returned yet, if any.
+    chunk: Option<usize>,
+}
+
+impl Core {
+    pub(crate) fn new(options: Options) -> Core {
+        Core::from_file(start_stream_with(options))
+    }
+
+    /// Continues reading from `file`, whose input hasn't been read yet.
+    pub(crate) fn from_file(file: File) -> Core {
+        Core {
+            metadata: file.metadata(),
+            file: Some(file),
+            input: vec![0; INPUT_BUFFER_SIZE],
+            input_pos: 0,
+            input_len: 0,
+            needs_input: true,
+            may_end: false,
+            in_entry: false,
+            chunk: None,
+        }
+    }
+
+    /// The metadata of the current entry, as it was when the entry was started.
+    pub(crate) fn metadata(&self) -> &EntryMetadata {
+        &self.metadata
+    }
+
+    /// Skips what's left of the content of the current entry and starts the next one.
+    /// Returns `false` if the archive has ended instead.
+    pub(crate) fn poll_next_entry<F>(&mut self, read: &mut F) -> Poll<Result<bool, DriveError>>
+    where
+        F: FnMut(&mut [u8]) -> Poll<io::Result<usize>>,
+    {
+        self.chunk = None;
+        while self.in_entry {
+            match std::task::ready!(self.poll_pull(read))? {
+                Pulled::Output => (),
+                Pulled::EntryEnd(_) | Pulled::End => self.in_entry = false,
+            }
+        }
+
+        // The header of an entry has been parsed by the time it has output or it ends.
+        match std::task::ready!(self.poll_pull(read))? {
+            Pulled::Output => {
+                if let Some(file) = &self.file {
+                    self.metadata = file.metadata();
+                }
+                self.in_entry = true;
+                self.chunk = Some(0);
+            }
+            Pulled::EntryEnd(metadata) => self.metadata = metadata,
+            Pulled::End => return Poll::Ready(Ok(false)),
+        }
+        Poll::Ready(Ok(true))
+    }
+
+    /// Makes sure that there's unread output of the current entry in `pending`,
+    /// pulling more from the stream if needed. Returns `false` if the content
+    /// of the entry has ended instead.
+    pub(crate) fn poll_fill<F>(&mut self, read: &mut F) -> Poll<Result<bool, DriveError>>
+    where
+        F: FnMut(&mut [u8]) -> Poll<io::Result<usize>>,
+    {
+        loop {
+            if !self.pending().is_empty() {
+                return Poll::Ready(Ok(true));
+            }
+            self.chunk = None;
+            if !self.in_entry {
+                return Poll::Ready(Ok(false));
+            }
+            match std::task::ready!(self.poll_pull(read))? {
+                Pulled::Output => self.chunk = Some(0),
+                Pulled::EntryEnd(_) | Pulled::End => self.in_entry = false,
+            }
+        }
+    }
+
+    /// The output of the current entry that hasn't been read yet.
+    pub(crate) fn pending(&self) -> &[u8] {
+        match (self.chunk, &self.file) {
+            (Some(pos), Some(file)) => &file.get_output()[pos..],
+            _ => &[],
+        }
+    }
+
+    /// Marks the first `amt` bytes of `pending` as read.
+    pub(crate) fn consume(&mut self, amt: usize) {
+        if let Some(pos) = &mut self.chunk {
+            *pos += amt;
+        }
+    }
+
+    /// Returns `pending` and marks all of it as read.
+    pub(crate) fn take_pending(&mut self) -> &[u8] {
+        match (self.chunk.take(), &self.file) {
+            (Some(pos), Some(file)) => &file.get_output()[pos..],
+            _ => &[],
+        }
+    }
+
+    /// Feeds input to the current file until it has output or it ends,
+    /// reading more input with `read` as needed.
+    fn poll_pull<F>(&mut self, read: &mut F) -> Poll<Result<Pulled, DriveError>>
+    where
+        F: FnMut(&mut [u8]) -> Poll<io::Result<usize>>,
+    {
+        loop {
+            let file = match &mut self.file {
+                Some(file) => file,
+                None => return Poll::Ready(Ok(Pulled::End)),
+            };
+            if self.needs_input {
+                let len = std::task::ready!(read(&mut self.input))?;
+                if len == 0 {
+                    if self.may_end {
+                        self.file = None;
+                        return Poll::Ready(Ok(Pulled::End));
+                    }
+                    if file.passes_through_at_eof() {
+                        // The stream ends once it has returned what it had buffered.
+                        self.input_pos = 0;
+                        self.input_len = 0;
+                        self.needs_input = false;
+                        self.may_end = true;
+                        continue;
+                    }
+                    if let File::Zip(zip_file) = file {
+                        zip_file.finish().map_err(Error::from)?;
+                    }
+                    return Poll::Ready(Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()));
+                }
+                self.input_pos = 0;
+                self.input_len = len;
+                self.needs_input = false;
+                self.may_end = false;
+            }
+
+            let input = &self.input[self.input_pos..self.input_len];
+            match file.read(input)? {
+                State::HasOutput { unparsed_input, .. } => {
+                    self.input_pos = self.input_len - unparsed_input.len();
+                    return Poll::Ready(Ok(Pulled::Output));
+                }
+                State::NeedsInput => self.needs_input = true,
+                State::NeedsInputOrEof(next_file) => {
+                    let metadata = file.metadata();
+                    self.needs_input = true;
+                    self.file = Some(next_file);
+                    self.may_end = true;
+                    return Poll::Ready(Ok(Pulled::EntryEnd(metadata)));
+                }
+                State::NextFile {
+                    unparsed_input,
+                    next_file,
+                }
+                | State::NextArchive {
+                    unparsed_input,
+                    next_file,
+                } => {
+                    let metadata = file.metadata();
+                    self.input_pos = self.input_len - unparsed_input.len();
+                    self.file = Some(next_file);
+                    return Poll::Ready(Ok(Pulled::EntryEnd(metadata)));
+                }
+                State::EndOfFile | State::Stopped => {
+                    let metadata = file.metadata();
+                    self.file = None;
+                    return Poll::Ready(Ok(Pulled::EntryEnd(metadata)));
+                }
+            }
+        }
+    }
+}
+
+/// Reads from a blocking reader, for `Core`.
+pub(crate) fn read_blocking(reader: &mut impl Read, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+    loop {
+        match reader.read(buf) {
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            result => return Poll::Ready(result),
+        }
+    }
+}
+
+/// Unwraps the result of an operation of `Core` that only read with `read_blocking`.
+pub(crate) fn ready<T>(poll: Poll<T>) -> T {
+    match poll {
+        Poll::Ready(result) => result,
+        Poll::Pending => unreachable!("Blocking reads are always ready."),
+    }
+}
+
+/// An archive or a compressed file read from `R`. See the module documentation.
+pub struct Archive<R> {
+    reader: R,
+    core: Core,
+}
+
+impl<R: Read> Archive<R> {
+    /// Reads the archive in `reader`, which is parsed with the default options.
+    pub fn new(reader: R) -> Archive<R> {
+        Archive::with_options(reader, Options::default())
+    }
+
+    /// Reads the archive in `reader`, which is parsed according to `options`.
+    pub fn with_options(reader: R, options: Options) -> Archive<R> {
+        Archive {
+            reader,
+            core: Core::new(options),
+        }
+    }
+
+    /// Returns the next entry, skipping what's left of the content of the current one,
+    /// or `None` if the archive has ended. The archive ends when the end of the archive
+    /// is reached, or when the reader runs out of input at a point where the stream
+    /// may end. Running out of input anywhere else fails like in `drive`.
+    pub fn next_entry(&mut self) -> Result<Option<Entry<'_, R>>, DriveError> {
+        let reader = &mut self.reader;
+        let mut read = |buf: &mut [u8]| read_blocking(reader, buf);
+        if ready(self.core.poll_next_entry(&mut read))? {
+            Ok(Some(Entry { archive: self }))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// See `Core::poll_fill`.
+    fn fill(&mut self) -> Result<bool, DriveError> {
+        let reader = &mut self.reader;
+        ready(
+            self.core
+                .poll_fill(&mut |buf: &mut [u8]| read_blocking(reader, buf)),
+        )
+    }
+}
+
+/// An entry of an `Archive`, returned by `Archive::next_entry`.
+/// Its decompressed content can also be read with `io::Read` and `io::BufRead`.
+pub struct Entry<'a, R> {
+    archive: &'a mut Archive<R>,
+}
+
+impl<R: Read> Entry<'_, R> {
+    /// The metadata of the entry, as it was when the entry was returned.
+    pub fn metadata(&self) -> &EntryMetadata {
+        self.archive.core.metadata()
+    }
+
+    /// Returns the next chunk of the decompressed content of the entry,
+    /// or `None` once the content has ended.
+    pub fn next_chunk(&mut self) -> Result<Option<&[u8]>, DriveError> {
+        if self.archive.fill()? {
+            Ok(Some(self.archive.core.take_pending()))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl<R: Read> Read for Entry<'_, R> {
+    /// Errors of the stream are returned as `io::ErrorKind::InvalidData`.
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let pending = self.fill_buf()?;
+        let len = pending.len().min(buf.len());
+        buf[..len].copy_from_slice(&pending[..len]);
+        self.consume(len);
+        Ok(len)
+    }
+}
+
+impl<R: Read> BufRead for Entry<'_, R> {
+    /// Returns the unread part of the latest output of the stream, without copying it.
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.archive.fill()?;
+        Ok(self.archive.core.pending())
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.archive.core.consume(amt);
+    }
+}
@@ -0,0 +1,115 @@
+//! Decompression of a stream of `bytes::Bytes`, in the shape of the body of
+//! an HTTP response in hyper or reqwest, so that an archive can be decompressed
+//! while it's being downloaded.
+//!
+//! `BytesStream` is `futures::DecompressStream` for such bodies: the errors of
+//! the body are passed through, and the content is yielded as `Bytes`.
+
+use std::fmt;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use bytes_crate::Bytes;
+use futures_core::Stream;
+
+use crate::futures::{DecompressStream, OutputChunk};
+use crate::{DriveError, File};
+
+/// An error returned by `BytesStream`: either the body failed,
+/// or decompressing it did.
+#[derive(Debug)]
+pub enum BodyError<E> {
+    Body(E),
+    Stream(DriveError),
+}
+
+impl<E: fmt::Display> fmt::Display for BodyError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BodyError::Body(e) => write!(f, "body error: {}", e),
+            BodyError::Stream(e) => e.fmt(f),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for BodyError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BodyError::Body(e) => Some(e),
+            BodyError::Stream(e) => Some(e),
+        }
+    }
+}
+
+/// The chunks of a body, which ends at its first error. The error is kept for `BytesStream`.
+struct Body<S, E> {
+    body: S,
+    error: Option<E>,
+}
+
+// The error is never pinned.
+impl<S: Unpin, E> Unpin for Body<S, E> {}
+
+impl<S, E> Stream for Body<S, E>
+where
+    S: Stream<Item = Result<Bytes, E>> + Unpin,
+{
+    type Item = Bytes;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Bytes>> {
+        let this = self.get_mut();
+        if this.error.is_some() {
+            return Poll::Ready(None);
+        }
+        match std::task::ready!(Pin::new(&mut this.body).poll_next(cx)) {
+            Some(Ok(bytes)) => Poll::Ready(Some(bytes)),
+            Some(Err(e)) => {
+                this.error = Some(e);
+                Poll::Ready(None)
+            }
+            None => Poll::Ready(None),
+        }
+    }
+}
+
+/// A stream of the decompressed output of `body`, whose content is yielded
+/// as `Bytes`. See `futures::DecompressStream`. An error of the body is returned
+/// as `BodyError::Body`, after which the stream ends.
+pub struct BytesStream<S, E> {
+    inner: DecompressStream<Body<S, E>, Bytes>,
+}
+
+impl<S, E> BytesStream<S, E>
+where
+    S: Stream<Item = Result<Bytes, E>> + Unpin,
+{
+    /// Decompresses `body` with `file`, which is usually a stream that has just been
+    /// started with `start_stream` or `start_stream_with`.
+    pub fn new(file: File, body: S) -> BytesStream<S, E> {
+        BytesStream {
+            inner: DecompressStream::new(file, Body { body, error: None }),
+        }
+    }
+}
+
+impl<S, E> Stream for BytesStream<S, E>
+where
+    S: Stream<Item = Result<Bytes, E>> + Unpin,
+{
+    type Item = Result<OutputChunk<Bytes>, BodyError<E>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let inner = &mut self.get_mut().inner;
+        let item = std::task::ready!(Pin::new(&mut *inner).poll_next(cx));
+        // The decompressing stream only sees the end of the body.
+        if let Some(e) = inner.get_mut().error.take() {
+            return Poll::Ready(Some(Err(BodyError::Body(e))));
+        }
+        Poll::Ready(item.map(|result| match result {
+            Ok(OutputChunk::EntryStart(metadata)) => Ok(OutputChunk::EntryStart(metadata)),
+            Ok(OutputChunk::Data(data)) => Ok(OutputChunk::Data(Bytes::from(data))),
+            Ok(OutputChunk::EntryEnd) => Ok(OutputChunk::EntryEnd),
+            Err(e) => Err(BodyError::Stream(e)),
+        }))
+    }
+}
@@ -0,0 +1,430 @@
+//! Streaming decompression of bzip2 data, both in zip entries
+//! (compression method 12) and in standalone .bz2 files.
+//!
+//! `Stream` decompresses the raw data of a zip entry. Its API mirrors
+//! `deflate::Stream` and returns the same `deflate::State`.
+//! `Bzip2File` reads a standalone file, with the same API as `gzip::GZipFile`.
+//! Such a file may consist of several concatenated streams, which are
+//! returned as separate files, like the members of a gzip file.
+
+use bzip2_codec::{Decompress, Status};
+
+use crate::deflate::{self, State};
+use crate::input_helper::{Input, InputHandler};
+use crate::{RatioLimit, SizeLimits};
+
+const OUTPUT_BUFFER_SIZE: usize = 32 * 1024;
+
+/// The bzip2 stream contained invalid data.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct InvalidStream;
+
+pub struct Stream {
+    decomp: Decompress,
+    output: Vec<u8>,
+    output_len: usize,
+    ended: bool,
+}
+
+impl Default for Stream {
+    fn default() -> Stream {
+        Self::new()
+    }
+}
+
+impl Stream {
+    pub fn new() -> Stream {
+        Stream {
+            decomp: Decompress::new(false),
+            output: vec![0; OUTPUT_BUFFER_SIZE],
+            output_len: 0,
+            ended: false,
+        }
+    }
+
+    pub fn feed_input<'i, 'o>(
+        &'o mut self,
+        input: &'i [u8],
+    ) -> Result<State<'i, 'o>, InvalidStream> {
+        if self.ended {
+            // The end of the stream was reached during the previous call,
+            // which also returned the last output.
+            self.output_len = 0;
+            return Ok(State::Stop {
+                unparsed_input: input,
+            });
+        }
+
+        let in_before = self.decomp.total_in();
+        let out_before = self.decomp.total_out();
+        let status = self
+            .decomp
+            .decompress(input, &mut self.output)
+            .map_err(|_| InvalidStream)?;
+        // The bzip2 decoder doesn't read past the end of the stream,
+        // so the input it didn't consume belongs to whatever follows.
+        let in_consumed = (self.decomp.total_in() - in_before) as usize;
+        self.output_len = (self.decomp.total_out() - out_before) as usize;
+        self.ended = status == Status::StreamEnd;
+        let unparsed_input = &input[in_consumed..];
+
+        if self.output_len > 0 {
+            Ok(State::HasOutput {
+                unparsed_input,
+                output: self.get_output(),
+            })
+        } else if self.ended {
+            Ok(State::Stop { unparsed_input })
+        } else {
+            Ok(State::NeedsInput { unparsed_input })
+        }
+    }
+
+    pub fn get_output(&self) -> &[u8] {
+        &self.output[..self.output_len]
+    }
+
+    pub fn uncompressed_size(&self) -> u64 {
+        self.decomp.total_out()
+    }
+
+    pub fn compressed_size(&self) -> u64 {
+        self.decomp.total_in()
+    }
+}
+
+/// The magic bytes that start a bzip2 stream, followed by the block size digit.
+const MAGIC: &[u8] = b"BZh";
+const HEADER_SIZE: usize = 4;
+
+/// Whether `input` starts with a bzip2 stream header.
+pub fn is_bzip2_header(input: &[u8]) -> bool {
+    match input {
+        [b'B', b'Z', b'h', level, ..] => (b'1'..=b'9').contains(level),
+        _ => false,
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Bzip2Error {
+    InvalidHeader,
+    InvalidStream,
+    /// A stream decompresses to more than `Options::max_size`.
+    StreamTooLarge,
+    /// A stream decompresses to more than `Options::max_compression_ratio`
+    /// times its compressed size.
+    CompressionRatioExceeded,
+}
+
+impl std::error::Error for Bzip2Error {
+    fn description(&self) -> &str {
+        "bzip2 uncompressing error"
+    }
+}
+
+impl std::fmt::Display for Bzip2Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        use self::Bzip2Error::*;
+        match self {
+            InvalidHeader => write!(f, "invalid stream header"),
+            InvalidStream => write!(f, "invalid bzip2 stream"),
+            StreamTooLarge => write!(f, "stream exceeds the size limit"),
+            CompressionRatioExceeded => write!(f, "stream exceeds the compression ratio limit"),
+        }
+    }
+}
+
+/// Options for parsing bzip2 streams, set with the builder methods.
+/// The options are inherited by the streams returned in `State::NextFile`
+/// and `State::NeedsInputOrEof`.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Options {
+    pub(crate) limits: SizeLimits,
+}
+
+impl Options {
+    /// Limits the decompressed size of each stream to `size` bytes. A stream that
+    /// decompresses to more fails with `Bzip2Error::StreamTooLarge` as soon as
+    /// the output that exceeds the limit has been decompressed, before it's returned.
+    pub fn max_size(mut self, size: u64) -> Self {
+        self.limits.max_size = Some(size);
+        self
+    }
+
+    /// Limits the ratio of the decompressed size of each stream to its compressed size.
+    /// Once a stream has decompressed to more than `min_size` bytes, it fails
+    /// with `Bzip2Error::CompressionRatioExceeded` if its output so far is more than
+    /// `ratio` times the compressed data that produced it.
+    pub fn max_compression_ratio(mut self, ratio: u64, min_size: u64) -> Self {
+        self.limits.max_compression_ratio = Some(RatioLimit { ratio, min_size });
+        self
+    }
+}
+
+pub struct Bzip2File {
+    state: InternalState,
+    unparsed: Vec<u8>,
+    decompressor: Stream,
+    options: Options,
+}
+
+impl std::fmt::Debug for Bzip2File {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Bzip2File")
+            .field("state", &self.state)
+            .field("unparsed", &self.unparsed)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum InternalState {
+    Init,
+    Decompressing,
+    End,
+    Eof,
+    Sentinel,
+    /// The stream failed with the error, which later reads return again.
+    Error(Bzip2Error),
+}
+
+#[derive(Debug)]
+enum ParseResult {
+    Continue,
+    NeedsInput,
+    Output,
+    Error(Bzip2Error),
+    NextFile(Bzip2File),
+    EndOfFile,
+}
+
+impl Bzip2File {
+    pub fn get_output(&self) -> &[u8] {
+        self.decompressor.get_output()
+    }
+
+    pub fn read<'i, 's>(
+        &'s mut self,
+        input: &'i [u8],
+    ) -> Result<crate::State<'i, 's, Bzip2File>, Bzip2Error> {
+        let mut ihandler = InputHandler::take_storage(&mut self.unparsed, input);
+        let mut unparsed = ihandler.get_unparsed();
+
+        loop {
+            let mut state = InternalState::Sentinel;
+            std::mem::swap(&mut self.state, &mut state);
+            let (bytes_consumed, new_state, res) = self.parse_step(state, unparsed);
+            unparsed = ihandler.consumed(bytes_consumed);
+            self.state = new_state;
+            match res {
+                ParseResult::Continue => (),
+                ParseResult::NeedsInput => {
+                    let extended_len = ihandler.extend_input();
+                    // Nothing in input left to extend, so we need the user to provide more
+                    if extended_len == 0 {
+                        ihandler.return_storage(&mut self.unparsed);
+                        return Ok(crate::State::NeedsInput);
+                    }
+                    unparsed = ihandler.get_unparsed();
+                }
+                ParseResult::Output => {
+                    let unparsed_input = ihandler.take_rest(&mut self.unparsed);
+                    return Ok(crate::State::HasOutput {
+                        unparsed_input,
+                        output: self.decompressor.get_output(),
+                    });
+                }
+                ParseResult::NextFile(next_file) => {
+                    let unparsed_input = ihandler.take_rest(&mut self.unparsed);
+                    return Ok(crate::State::NextFile {
+                        unparsed_input,
+                        next_file,
+                    });
+                }
+                ParseResult::EndOfFile => {
+                    if self.state == InternalState::Eof {
+                        return Ok(crate::State::EndOfFile);
+                    } else {
+                        return Ok(crate::State::NeedsInputOrEof(start_stream_with(
+                            self.options.clone(),
+                        )));
+                    }
+                }
+                ParseResult::Error(err) => return Err(err),
+            };
+            if unparsed.is_empty() {
+                return Ok(crate::State::NeedsInput);
+            }
+        }
+    }
+
+    fn parse_step<'long, 'short>(
+        &'short mut self,
+        state: InternalState,
+        input: Input<'long, 'short>,
+    ) -> (usize, InternalState, ParseResult) {
+        match state {
+            InternalState::Init => Bzip2File::check_header(input),
+            InternalState::Decompressing => self.decompress(input),
+            InternalState::End => (0, InternalState::Eof, ParseResult::EndOfFile),
+            InternalState::Eof => (0, InternalState::Eof, ParseResult::EndOfFile),
+            InternalState::Sentinel => unreachable!("parse_step is never called with Sentinel"),
+            InternalState::Error(error) => {
+                (0, InternalState::Error(error), ParseResult::Error(error))
+            }
+        }
+    }
+
+    /// Checks the header without consuming it,
+    /// because it's a part of the data that the decompressor reads.
+    fn check_header<'long, 'short>(
+        input: Input<'long, 'short>,
+    ) -> (usize, InternalState, ParseResult) {
+        if is_bzip2_header(*input) {
+            (0, InternalState::Decompressing, ParseResult::Continue)
+        } else if input.len() < HEADER_SIZE && MAGIC.starts_with(&input[..input.len().min(3)]) {
+            (0, InternalState::Init, ParseResult::NeedsInput)
+        } else {
+            (
+                0,
+                InternalState::Error(Bzip2Error::InvalidHeader),
+                ParseResult::Error(Bzip2Error::InvalidHeader),
+            )
+        }
+    }
+
+    fn decompress<'long, 'short>(
+        &mut self,
+        input: Input<'long, 'short>,
+    ) -> (usize, InternalState, ParseResult) {
+        match self.decompressor.feed_input(*input) {
+            Ok(deflate::State::NeedsInput { unparsed_input }) => (
+                input.len() - unparsed_input.len(),
+                InternalState::Decompressing,
+                ParseResult::Continue,
+            ),
+            Ok(deflate::State::HasOutput { unparsed_input, .. }) => {
+                let consumed_bytes = input.len() - unparsed_input.len();
+                if self
+                    .options
+                    .limits
+                    .exceeds_size(self.decompressor.uncompressed_size())
+                {
+                    return (
+                        consumed_bytes,
+                        InternalState::Error(Bzip2Error::StreamTooLarge),
+                        ParseResult::Error(Bzip2Error::StreamTooLarge),
+                    );
+                }
+                if self.options.limits.exceeds_ratio(
+                    self.decompressor.compressed_size(),
+                    self.decompressor.uncompressed_size(),
+                ) {
+                    return (
+                        consumed_bytes,
+                        InternalState::Error(Bzip2Error::CompressionRatioExceeded),
+                        ParseResult::Error(Bzip2Error::CompressionRatioExceeded),
+                    );
+                }
+                (
+                    consumed_bytes,
+                    InternalState::Decompressing,
+                    ParseResult::Output,
+                )
+            }
+            Ok(deflate::State::Stop { unparsed_input }) => {
+                let consumed = input.len() - unparsed_input.len();
+                if unparsed_input.is_empty() {
+                    return (consumed, InternalState::End, ParseResult::EndOfFile);
+                }
+                match peek_stream_with(unparsed_input, &self.options) {
+                    Ok((rest, next_file)) => (
+                        input.len() - rest.len(),
+                        InternalState::End,
+                        ParseResult::NextFile(next_file),
+                    ),
+                    Err(err) => (consumed, InternalState::Error(err), ParseResult::Error(err)),
+                }
+            }
+            Err(_) => (
+                0,
+                InternalState::Error(Bzip2Error::InvalidStream),
+                ParseResult::Error(Bzip2Error::InvalidStream),
+            ),
+        }
+    }
+
+    pub fn read_with<'i>(
+        &mut self,
+        mut input: &'i [u8],
+        mut callback: impl FnMut(&[u8]),
+    ) -> Result<crate::State<'i, 'i, Bzip2File>, Bzip2Error> {
+        loop {
+            match self.read(input)?.try_no_output() {
+                Err((unparsed_input, output)) => {
+                    input = unparsed_input;
+                    callback(output);
+                }
+                Ok(state) => return Ok(state),
+            }
+        }
+    }
+}
+
+/// Starts a standalone bzip2 stream.
+pub fn start_stream() -> Bzip2File {
+    start_stream_with(Options::default())
+}
+
+/// Starts a standalone bzip2 stream that is parsed according to `options`.
+pub fn start_stream_with(options: Options) -> Bzip2File {
+    Bzip2File {
+        state: InternalState::Init,
+        unparsed: Vec::new(),
+        decompressor: Stream::new(),
+        options,
+    }
+}
+
+/// Starts a stream whose first bytes, `prefix`, were already read from the input
+/// to detect the format. They are parsed together with the input of the next `read`.
+pub(crate) fn start_stream_with_options_and_prefix(options: Options, prefix: &[u8]) -> Bzip2File {
+    let mut file = start_stream_with(options);
+    file.unparsed = prefix.to_vec();
+    file
+}
+
+/// Starts the stream that follows a finished one in `input`.
+/// The header is left in the input, because the decompressor reads it.
+pub fn peek_stream(input: &[u8]) -> Result<(&[u8], Bzip2File), Bzip2Error> {
+    peek_stream_with(input, &Options::default())
+}
+
+fn peek_stream_with<'i>(
+    input: &'i [u8],
+    options: &Options,
+) -> Result<(&'i [u8], Bzip2File), Bzip2Error> {
+    if is_bzip2_header(input) {
+        Ok((
+            input,
+            Bzip2File {
+                state: InternalState::Decompressing,
+                unparsed: Vec::new(),
+                decompressor: Stream::new(),
+                options: options.clone(),
+            },
+        ))
+    } else if input.len() < HEADER_SIZE && MAGIC.starts_with(&input[..input.len().min(3)]) {
+        Ok((
+            &b""[..],
+            Bzip2File {
+                state: InternalState::Init,
+                unparsed: input.to_vec(),
+                decompressor: Stream::new(),
+                options: options.clone(),
+            },
+        ))
+    } else {
+        Err(Bzip2Error::InvalidHeader)
+    }
+}
@@ -0,0 +1,332 @@
+//! Chaining of streams: the output of an outer stream, such as a gzip file,
+//! is parsed by an inner stream, such as a tar archive.
+//!
+//! The outer stream is only chained if its output starts like the inner
+//! format. Until enough output has been seen to tell, it's buffered.
+//! If the output turns out to be something else, the buffered output is
+//! returned first and the rest of the output is passed through as is.
+
+use crate::{Error, State};
+
+/// A stream that can be on either side of a chain.
+pub(crate) trait Stream: Sized {
+    type Error: Into<Error>;
+
+    fn read<'i, 's>(&'s mut self, input: &'i [u8]) -> Result<State<'i, 's, Self>, Self::Error>;
+
+    fn get_output(&self) -> &[u8];
+
+    fn name(&self) -> Option<&[u8]>;
+
+    /// Whether the output of this stream continues that of the stream
+    /// that returned it with `State::NeedsInputOrEof`, like the members
+    /// of a gzip file that are read as one.
+    fn continues_output(&self) -> bool {
+        false
+    }
+}
+
+/// A stream that can be the inner side of a chain.
+pub(crate) trait Container: Stream {
+    /// The options that the container is started with.
+    type Options: Clone + Default;
+
+    /// The amount of output that is needed to tell whether it starts a container.
+    const PROBE_LEN: usize;
+
+    /// Whether `output` starts a container.
+    fn is_start(output: &[u8]) -> bool;
+
+    /// Starts the container. `probe` is the output that `is_start` accepted.
+    fn start(probe: &[u8], options: &Self::Options) -> Self;
+}
+
+enum Inner<I> {
+    /// Buffering the output to tell what it contains.
+    Probing,
+    /// The output isn't in the inner format, so it's passed through.
+    /// `replayed` tells whether the buffered output was returned already.
+    Plain {
+        replayed: bool,
+    },
+    Piped(I),
+    /// The inner stream ended; the rest of the output is ignored.
+    /// The ended stream is kept for its metadata.
+    Ended(I),
+}
+
+/// How the outer stream ended.
+enum OuterEnd<O> {
+    /// `State::NeedsInputOrEof`
+    NeedsInputOrEof(O),
+    /// `State::NextFile`
+    NextFile(O),
+    EndOfFile,
+    /// `State::Stopped`
+    Stopped,
+}
+
+pub(crate) struct Chain<O, I: Container> {
+    /// Handed over to the next inner file when the current one ends.
+    outer: Option<O>,
+    /// The options of the inner streams, handed over to the next chain.
+    options: I::Options,
+    inner: Inner<I>,
+    /// Output of the outer stream that the inner stream hasn't parsed yet.
+    pending: Vec<u8>,
+    /// The end of the outer stream, to be returned once the pending output is parsed.
+    outer_end: Option<OuterEnd<O>>,
+}
+
+/// What to do after feeding the inner stream.
+enum InnerStep<I> {
+    Output,
+    NeedsInput,
+    NextFile(I),
+    EndOfFile,
+    Stopped,
+}
+
+/// What to do after feeding the outer stream.
+enum OuterStep {
+    Continue,
+    NeedsInput,
+    PassOutput,
+}
+
+impl<O: Stream, I: Container> Chain<O, I> {
+    pub fn new(outer: O) -> Chain<O, I> {
+        Chain::with_options(outer, I::Options::default())
+    }
+
+    /// Starts a chain whose inner streams are started with `options`.
+    pub fn with_options(outer: O, options: I::Options) -> Chain<O, I> {
+        Chain {
+            outer: Some(outer),
+            options,
+            inner: Inner::Probing,
+            pending: Vec::new(),
+            outer_end: None,
+        }
+    }
+
+    /// Starts the chain of the outer stream that follows this one.
+    fn next_chain(&self, outer: O) -> Chain<O, I> {
+        Chain::with_options(outer, self.options.clone())
+    }
+
+    /// Whether the output is parsed by the inner stream. Only known
+    /// after the first output of the outer stream has been seen.
+    pub fn is_piped(&self) -> bool {
+        matches!(self.inner, Inner::Piped(_) | Inner::Ended(_))
+    }
+
+    pub fn outer(&self) -> Option<&O> {
+        self.outer.as_ref()
+    }
+
+    pub fn inner(&self) -> Option<&I> {
+        match self.inner {
+            Inner::Piped(ref inner) | Inner::Ended(ref inner) => Some(inner),
+            Inner::Probing | Inner::Plain { .. } => None,
+        }
+    }
+
+    pub fn name(&self) -> Option<&[u8]> {
+        match self.inner {
+            Inner::Piped(ref inner) | Inner::Ended(ref inner) => inner.name(),
+            Inner::Probing | Inner::Plain { .. } => self.outer.as_ref().and_then(O::name),
+        }
+    }
+
+    pub fn get_output(&self) -> &[u8] {
+        match self.inner {
+            Inner::Piped(ref inner) => inner.get_output(),
+            Inner::Plain { replayed: true } if !self.pending.is_empty() => &self.pending,
+            Inner::Plain { .. } => self.outer.as_ref().map_or(&[], O::get_output),
+            Inner::Probing | Inner::Ended(_) => &[],
+        }
+    }
+
+    pub fn read<'i, 's>(
+        &'s mut self,
+        mut input: &'i [u8],
+    ) -> Result<State<'i, 's, Chain<O, I>>, Error> {
+        loop {
+            if let Inner::Probing = self.inner {
+                if self.pending.len() >= I::PROBE_LEN || self.outer_end.is_some() {
+                    self.inner = if I::is_start(&self.pending) {
+                        Inner::Piped(I::start(&self.pending, &self.options))
+                    } else {
+                        Inner::Plain { replayed: false }
+                    };
+                }
+            }
+
+            match self.inner {
+                // Once the outer stream has ended, the inner stream is read even without
+                // pending output, since it may still hold output it buffered itself.
+                Inner::Piped(ref mut inner)
+                    if !self.pending.is_empty() || self.outer_end.is_some() =>
+                {
+                    let (consumed, step) = match inner.read(&self.pending).map_err(Into::into)? {
+                        State::HasOutput { unparsed_input, .. } => {
+                            (self.pending.len() - unparsed_input.len(), InnerStep::Output)
+                        }
+                        // The inner stream stores the input it can't parse yet.
+                        State::NeedsInput | State::NeedsInputOrEof(_) => {
+                            (self.pending.len(), InnerStep::NeedsInput)
+                        }
+                        State::NextFile {
+                            unparsed_input,
+                            next_file,
+                        }
+                        | State::NextArchive {
+                            unparsed_input,
+                            next_file,
+                        } => (
+                            self.pending.len() - unparsed_input.len(),
+                            InnerStep::NextFile(next_file),
+                        ),
+                        State::EndOfFile => (self.pending.len(), InnerStep::EndOfFile),
+                        State::Stopped => (self.pending.len(), InnerStep::Stopped),
+                    };
+                    self.pending.drain(..consumed);
+                    match step {
+                        InnerStep::Output => {
+                            return Ok(State::HasOutput {
+                                unparsed_input: input,
+                                output: self.get_output(),
+                            });
+                        }
+                        InnerStep::NextFile(next_inner) => {
+                            let next_file = Chain {
+                                outer: self.outer.take(),
+                                options: self.options.clone(),
+                                inner: Inner::Piped(next_inner),
+                                pending: std::mem::take(&mut self.pending),
+                                outer_end: self.outer_end.take(),
+                            };
+                            return Ok(State::NextFile {
+                                unparsed_input: input,
+                                next_file,
+                            });
+                        }
+                        InnerStep::EndOfFile => {
+                            if let Inner::Piped(inner) =
+                                std::mem::replace(&mut self.inner, Inner::Probing)
+                            {
+                                self.inner = Inner::Ended(inner);
+                            }
+                        }
+                        InnerStep::Stopped => return Ok(State::Stopped),
+                        InnerStep::NeedsInput => (),
+                    }
+                }
+                Inner::Plain { ref mut replayed } if !self.pending.is_empty() => {
+                    if *replayed {
+                        self.pending.clear();
+                    } else {
+                        *replayed = true;
+                        return Ok(State::HasOutput {
+                            unparsed_input: input,
+                            output: &self.pending,
+                        });
+                    }
+                }
+                Inner::Ended(_) => self.pending.clear(),
+                _ => (),
+            }
+
+            if let Some(end) = self.outer_end.take() {
+                return Ok(match end {
+                    // The output goes on in the next outer stream. If the inner stream
+                    // hasn't ended, the input can't end here either.
+                    OuterEnd::NeedsInputOrEof(next_outer) if next_outer.continues_output() => {
+                        match self.inner {
+                            Inner::Piped(_) => {
+                                self.outer = Some(next_outer);
+                                return Ok(State::NeedsInput);
+                            }
+                            Inner::Plain { .. } => State::NeedsInputOrEof(Chain {
+                                outer: Some(next_outer),
+                                options: self.options.clone(),
+                                inner: Inner::Plain { replayed: true },
+                                pending: Vec::new(),
+                                outer_end: None,
+                            }),
+                            Inner::Probing | Inner::Ended(_) => {
+                                State::NeedsInputOrEof(self.next_chain(next_outer))
+                            }
+                        }
+                    }
+                    OuterEnd::NeedsInputOrEof(next_outer) => {
+                        State::NeedsInputOrEof(self.next_chain(next_outer))
+                    }
+                    OuterEnd::NextFile(next_outer) => State::NextFile {
+                        unparsed_input: input,
+                        next_file: self.next_chain(next_outer),
+                    },
+                    OuterEnd::EndOfFile => State::EndOfFile,
+                    OuterEnd::Stopped => State::Stopped,
+                });
+            }
+
+            let outer = self
+                .outer
+                .as_mut()
+                .expect("The outer stream is only handed over with State::NextFile");
+            let step = match outer.read(input).map_err(Into::into)? {
+                State::HasOutput {
+                    unparsed_input,
+                    output,
+                } => {
+                    input = unparsed_input;
+                    match self.inner {
+                        Inner::Plain { .. } => OuterStep::PassOutput,
+                        Inner::Ended(_) => OuterStep::Continue,
+                        Inner::Probing | Inner::Piped(_) => {
+                            self.pending.extend_from_slice(output);
+                            OuterStep::Continue
+                        }
+                    }
+                }
+                State::NeedsInput => OuterStep::NeedsInput,
+                State::NeedsInputOrEof(next_outer) => {
+                    self.outer_end = Some(OuterEnd::NeedsInputOrEof(next_outer));
+                    OuterStep::Continue
+                }
+                State::NextFile {
+                    unparsed_input,
+                    next_file,
+                }
+                | State::NextArchive {
+                    unparsed_input,
+                    next_file,
+                } => {
+                    input = unparsed_input;
+                    self.outer_end = Some(OuterEnd::NextFile(next_file));
+                    OuterStep::Continue
+                }
+                State::EndOfFile => {
+                    self.outer_end = Some(OuterEnd::EndOfFile);
+                    OuterStep::Continue
+                }
+                State::Stopped => {
+                    self.outer_end = Some(OuterEnd::Stopped);
+                    OuterStep::Continue
+                }
+            };
+            match step {
+                OuterStep::PassOutput => {
+                    return Ok(State::HasOutput {
+                        unparsed_input: input,
+                        output: self.get_output(),
+                    });
+                }
+                OuterStep::NeedsInput => return Ok(State::NeedsInput),
+                OuterStep::Continue => (),
+            }
+        }
+    }
+}
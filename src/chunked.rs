@@ -0,0 +1,195 @@
+//! Reading input that is split into several chunks without copying it
+//! into a contiguous slice first: from a `&mut [IoSlice]`, such as a ring buffer
+//! that wraps around, with the `read_vectored` methods of `File` and the files
+//! of the format modules, and from a `bytes::Buf`, which may be non-contiguous,
+//! like a `Chain` of buffers, with the `read_buf` methods. The chunks are parsed
+//! in place and the input is advanced past what was parsed.
+
+use std::io::IoSlice;
+
+#[cfg(feature = "bytes")]
+use bytes_crate::Buf;
+
+use crate::State;
+
+/// Input that is split into chunks.
+trait Chunks {
+    /// The first chunk, which is empty only if the input has run out.
+    fn chunk(&self) -> &[u8];
+    fn advance(&mut self, len: usize);
+    fn has_remaining(&self) -> bool;
+}
+
+struct Slices<'a, 'b, 'c>(&'a mut &'b mut [IoSlice<'c>]);
+
+impl Chunks for Slices<'_, '_, '_> {
+    fn chunk(&self) -> &[u8] {
+        self.0
+            .iter()
+            .find(|slice| !slice.is_empty())
+            .map_or(&[], |slice| &slice[..])
+    }
+
+    fn advance(&mut self, len: usize) {
+        IoSlice::advance_slices(self.0, len);
+    }
+
+    fn has_remaining(&self) -> bool {
+        self.0.iter().any(|slice| !slice.is_empty())
+    }
+}
+
+#[cfg(feature = "bytes")]
+struct BufChunks<'a, B>(&'a mut B);
+
+#[cfg(feature = "bytes")]
+impl<B: Buf> Chunks for BufChunks<'_, B> {
+    fn chunk(&self) -> &[u8] {
+        self.0.chunk()
+    }
+
+    fn advance(&mut self, len: usize) {
+        self.0.advance(len);
+    }
+
+    fn has_remaining(&self) -> bool {
+        self.0.has_remaining()
+    }
+}
+
+/// The state with the input and the output that it borrows left out,
+/// and how much of the input of length `input_len` was parsed.
+fn detach<F>(state: State<'_, '_, F>, input_len: usize) -> (usize, State<'static, 'static, F>) {
+    use State::*;
+    match state {
+        HasOutput { unparsed_input, .. } => (
+            input_len - unparsed_input.len(),
+            HasOutput {
+                unparsed_input: &[],
+                output: &[],
+            },
+        ),
+        NeedsInput => (input_len, NeedsInput),
+        NeedsInputOrEof(next_file) => (input_len, NeedsInputOrEof(next_file)),
+        NextFile {
+            unparsed_input,
+            next_file,
+        } => (
+            input_len - unparsed_input.len(),
+            NextFile {
+                unparsed_input: &[],
+                next_file,
+            },
+        ),
+        NextArchive {
+            unparsed_input,
+            next_file,
+        } => (
+            input_len - unparsed_input.len(),
+            NextArchive {
+                unparsed_input: &[],
+                next_file,
+            },
+        ),
+        // It isn't known where the stream ended, so the input is consumed as a whole.
+        EndOfFile => (input_len, EndOfFile),
+        Stopped => (input_len, Stopped),
+    }
+}
+
+/// Feeds the chunks of `input` to `file` with `read` until there is output or the state
+/// changes, advancing `input` past the parsed input. The output is then got with `get_output`.
+fn read_chunks<'s, T, F, E>(
+    file: &'s mut T,
+    input: &mut impl Chunks,
+    mut read: impl FnMut(&mut T, &[u8]) -> Result<(usize, State<'static, 'static, F>), E>,
+    get_output: fn(&T) -> &[u8],
+) -> Result<State<'static, 's, F>, E> {
+    loop {
+        let (consumed, state) = read(file, input.chunk())?;
+        input.advance(consumed);
+        match state {
+            State::HasOutput { .. } => {
+                return Ok(State::HasOutput {
+                    unparsed_input: &[],
+                    output: get_output(file),
+                })
+            }
+            State::NeedsInput if input.has_remaining() => (),
+            state => return Ok(state),
+        }
+    }
+}
+
+macro_rules! impl_read_chunks {
+    ($($file:ty => $error:ty,)*) => {
+        $(
+            impl $file {
+                /// Like `read`, but the input is read from `bufs`, which are advanced
+                /// past the input that was parsed like with `IoSlice::advance_slices`.
+                /// The input that wasn't parsed is left in `bufs` instead of
+                /// `unparsed_input`, which is always empty.
+                pub fn read_vectored<'s>(
+                    &'s mut self,
+                    bufs: &mut &mut [IoSlice],
+                ) -> Result<State<'static, 's, $file>, $error> {
+                    read_chunks(
+                        self,
+                        &mut Slices(bufs),
+                        |file: &mut $file, input: &[u8]| {
+                            file.read(input).map(|state| detach(state, input.len()))
+                        },
+                        <$file>::get_output,
+                    )
+                }
+
+                /// Like `read`, but the input is read from `buf`, which is advanced past
+                /// the input that was parsed. The input that wasn't parsed is left in `buf`
+                /// instead of `unparsed_input`, which is always empty.
+                #[cfg(feature = "bytes")]
+                pub fn read_buf<'s>(
+                    &'s mut self,
+                    buf: &mut impl Buf,
+                ) -> Result<State<'static, 's, $file>, $error> {
+                    read_chunks(
+                        self,
+                        &mut BufChunks(buf),
+                        |file: &mut $file, input: &[u8]| {
+                            file.read(input).map(|state| detach(state, input.len()))
+                        },
+                        <$file>::get_output,
+                    )
+                }
+            }
+        )*
+    };
+}
+
+impl_read_chunks! {
+    crate::File => crate::Error,
+    crate::zip::ZipFile => crate::zip::ZipError,
+    crate::gzip::GZipFile => crate::gzip::GZipError,
+    crate::tar::TarFile => crate::tar::TarError,
+    crate::tar::TarGzFile => crate::Error,
+    crate::zlib::ZlibFile => crate::zlib::ZlibError,
+    crate::lzw::LzwFile => crate::lzw::LzwError,
+    crate::cpio::CpioFile => crate::cpio::CpioError,
+    crate::cpio::CpioGzFile => crate::Error,
+    crate::ar::ArFile => crate::ar::ArError,
+    crate::ar::DebFile => crate::Error,
+}
+
+#[cfg(feature = "bzip2")]
+impl_read_chunks! {
+    crate::bzip2::Bzip2File => crate::bzip2::Bzip2Error,
+}
+
+#[cfg(feature = "xz")]
+impl_read_chunks! {
+    crate::xz::XzFile => crate::xz::XzError,
+}
+
+#[cfg(feature = "sevenz")]
+impl_read_chunks! {
+    crate::sevenz::SevenZFile => crate::sevenz::SevenZError,
+}
@@ -0,0 +1,502 @@
+//! Streaming parsing of cpio archives in the "newc" format of SVR4,
+//! which initramfs images and RPM payloads use, and in its variant with checksums.
+//!
+//! Each entry of the archive is a file of its own: its data is returned
+//! as output, and the next entry as `State::NextFile`, like the entries
+//! of a tar archive. The entry named `TRAILER!!!` ends the archive and
+//! isn't returned; the padding after it is ignored.
+//!
+//! Gzipped archives are read with `start_cpio_gz_stream`, which chains
+//! a gzip stream with the parser.
+
+use crate::chain::{self, Chain};
+use crate::gzip::{self, GZipFile};
+use crate::input_helper::{Input, InputHandler};
+use crate::{SizeLimits, State};
+
+pub mod headers;
+
+use self::headers::{Format, Header, HEADER_SIZE};
+
+const OUTPUT_BUFFER_SIZE: usize = 32 * 1024;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum CpioError {
+    InvalidHeader,
+    /// The checksum of the data of an entry didn't match the one in its header.
+    ChecksumMismatch,
+    /// The size of an entry in its header is more than `Options::max_entry_size`.
+    EntryTooLarge,
+}
+
+impl std::error::Error for CpioError {
+    fn description(&self) -> &str {
+        "cpio parsing error"
+    }
+}
+
+impl std::fmt::Display for CpioError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        use self::CpioError::*;
+        match self {
+            InvalidHeader => write!(f, "invalid header"),
+            ChecksumMismatch => write!(f, "data checksum mismatch"),
+            EntryTooLarge => write!(f, "entry exceeds the size limit"),
+        }
+    }
+}
+
+/// Options for parsing a cpio archive, set with the builder methods.
+/// The options are inherited by the files returned in `State::NextFile`.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Options {
+    pub(crate) limits: SizeLimits,
+}
+
+impl Options {
+    /// Limits the size of each entry to `size` bytes. A larger entry fails the stream
+    /// with `CpioError::EntryTooLarge` once its header has been parsed, before any
+    /// of its data is returned.
+    pub fn max_entry_size(mut self, size: u64) -> Self {
+        self.limits.max_size = Some(size);
+        self
+    }
+}
+
+pub struct CpioFile {
+    state: InternalState,
+    unparsed: Vec<u8>,
+    header: Option<Header>,
+    output: Vec<u8>,
+    options: Options,
+}
+
+impl std::fmt::Debug for CpioFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("CpioFile")
+            .field("state", &self.state)
+            .field("unparsed", &self.unparsed)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum InternalState {
+    Init,
+    Data {
+        remaining: u64,
+        checksum: u32,
+    },
+    Padding {
+        remaining: u64,
+    },
+    EntryEnd,
+    End,
+    Sentinel,
+    /// The stream failed with the error, which later reads return again.
+    Error(CpioError),
+}
+
+#[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
+enum ParseResult {
+    Continue,
+    NeedsInput,
+    Output,
+    Error(CpioError),
+    NextFile(CpioFile),
+    EndOfFile,
+}
+
+/// The result of reading the header of an entry.
+enum HeaderResult {
+    NeedsInput,
+    Entry(usize, Header),
+    Trailer(usize),
+}
+
+impl CpioFile {
+    pub fn get_output(&self) -> &[u8] {
+        &self.output
+    }
+
+    /// The header of the entry, once it has been parsed.
+    pub fn header(&self) -> Option<&Header> {
+        self.header.as_ref()
+    }
+
+    pub fn filename(&self) -> Option<&[u8]> {
+        self.header.as_ref().map(|header| &header.name[..])
+    }
+
+    pub fn read<'i, 's>(
+        &'s mut self,
+        input: &'i [u8],
+    ) -> Result<State<'i, 's, CpioFile>, CpioError> {
+        let mut ihandler = InputHandler::take_storage(&mut self.unparsed, input);
+        let mut unparsed = ihandler.get_unparsed();
+
+        loop {
+            let mut state = InternalState::Sentinel;
+            std::mem::swap(&mut self.state, &mut state);
+            let (bytes_consumed, new_state, res) = self.parse_step(state, unparsed);
+            unparsed = ihandler.consumed(bytes_consumed);
+            self.state = new_state;
+            match res {
+                ParseResult::Continue => (),
+                ParseResult::NeedsInput => {
+                    let extended_len = ihandler.extend_input();
+                    // Nothing in input left to extend, so we need the user to provide more
+                    if extended_len == 0 {
+                        ihandler.return_storage(&mut self.unparsed);
+                        return Ok(State::NeedsInput);
+                    }
+                    unparsed = ihandler.get_unparsed();
+                }
+                ParseResult::Output => {
+                    let unparsed_input = ihandler.take_rest(&mut self.unparsed);
+                    return Ok(State::HasOutput {
+                        unparsed_input,
+                        output: &self.output,
+                    });
+                }
+                ParseResult::NextFile(next_file) => {
+                    let unparsed_input = ihandler.take_rest(&mut self.unparsed);
+                    return Ok(State::NextFile {
+                        unparsed_input,
+                        next_file,
+                    });
+                }
+                ParseResult::EndOfFile => return Ok(State::EndOfFile),
+                ParseResult::Error(err) => return Err(err),
+            };
+            if unparsed.is_empty() {
+                ihandler.return_storage(&mut self.unparsed);
+                return Ok(State::NeedsInput);
+            }
+        }
+    }
+
+    pub fn read_with<'i>(
+        &mut self,
+        mut input: &'i [u8],
+        mut callback: impl FnMut(&[u8]),
+    ) -> Result<State<'i, 'i, CpioFile>, CpioError> {
+        loop {
+            match self.read(input)?.try_no_output() {
+                Err((unparsed_input, output)) => {
+                    input = unparsed_input;
+                    callback(output);
+                }
+                Ok(state) => return Ok(state),
+            }
+        }
+    }
+
+    fn parse_step<'long, 'short>(
+        &'short mut self,
+        state: InternalState,
+        input: Input<'long, 'short>,
+    ) -> (usize, InternalState, ParseResult) {
+        match state {
+            InternalState::Init => self.parse_first_header(input),
+            InternalState::Data {
+                remaining,
+                checksum,
+            } => self.read_data(input, remaining, checksum),
+            InternalState::Padding { remaining } => CpioFile::skip_padding(input, remaining),
+            InternalState::EntryEnd => self.end(input),
+            InternalState::End => (0, InternalState::End, ParseResult::EndOfFile),
+            InternalState::Sentinel => unreachable!("parse_step is never called with Sentinel"),
+            InternalState::Error(error) => {
+                (0, InternalState::Error(error), ParseResult::Error(error))
+            }
+        }
+    }
+
+    /// Reads a header with the name that follows it.
+    fn read_header(input: &[u8]) -> Result<HeaderResult, CpioError> {
+        if input.len() < HEADER_SIZE {
+            return Ok(HeaderResult::NeedsInput);
+        }
+        let (mut header, name_size) = Header::parse(&input[..HEADER_SIZE])?;
+        let len = Header::len_with_name(name_size);
+        if input.len() < len {
+            return Ok(HeaderResult::NeedsInput);
+        }
+        let name = &input[HEADER_SIZE..HEADER_SIZE + name_size as usize];
+        match name.split_last() {
+            Some((0, name)) => header.name = name.to_vec(),
+            _ => return Err(CpioError::InvalidHeader),
+        }
+        if header.is_trailer() {
+            Ok(HeaderResult::Trailer(len))
+        } else {
+            Ok(HeaderResult::Entry(len, header))
+        }
+    }
+
+    /// The state that reads the data of the entry of `header`.
+    fn data_state(header: &Header) -> InternalState {
+        InternalState::Data {
+            remaining: u64::from(header.size),
+            checksum: 0,
+        }
+    }
+
+    /// Whether the entry of `header` is larger than the limit of the options.
+    fn is_too_large(&self, header: &Header) -> bool {
+        self.options.limits.exceeds_size(u64::from(header.size))
+    }
+
+    fn parse_first_header<'long, 'short>(
+        &mut self,
+        input: Input<'long, 'short>,
+    ) -> (usize, InternalState, ParseResult) {
+        match CpioFile::read_header(*input) {
+            Ok(HeaderResult::NeedsInput) => (0, InternalState::Init, ParseResult::NeedsInput),
+            Ok(HeaderResult::Entry(_, ref header)) if self.is_too_large(header) => (
+                0,
+                InternalState::Error(CpioError::EntryTooLarge),
+                ParseResult::Error(CpioError::EntryTooLarge),
+            ),
+            Ok(HeaderResult::Entry(len, header)) => {
+                let state = CpioFile::data_state(&header);
+                self.header = Some(header);
+                (len, state, ParseResult::Continue)
+            }
+            // An archive without entries.
+            Ok(HeaderResult::Trailer(len)) => (len, InternalState::End, ParseResult::EndOfFile),
+            Err(e) => (0, InternalState::Error(e), ParseResult::Error(e)),
+        }
+    }
+
+    fn read_data<'long, 'short>(
+        &mut self,
+        input: Input<'long, 'short>,
+        remaining: u64,
+        checksum: u32,
+    ) -> (usize, InternalState, ParseResult) {
+        if remaining == 0 {
+            let header = self
+                .header
+                .as_ref()
+                .expect("The header is parsed before the data");
+            if header.format == Format::Crc && header.check != checksum {
+                return (
+                    0,
+                    InternalState::Error(CpioError::ChecksumMismatch),
+                    ParseResult::Error(CpioError::ChecksumMismatch),
+                );
+            }
+            let padding = headers::padding(u64::from(header.size));
+            return (
+                0,
+                InternalState::Padding { remaining: padding },
+                ParseResult::Continue,
+            );
+        }
+        if input.is_empty() {
+            return (
+                0,
+                InternalState::Data {
+                    remaining,
+                    checksum,
+                },
+                ParseResult::NeedsInput,
+            );
+        }
+
+        let available = std::cmp::min(remaining, input.len() as u64) as usize;
+        let available = std::cmp::min(available, OUTPUT_BUFFER_SIZE);
+        self.output.clear();
+        self.output.extend_from_slice(&input[..available]);
+        let checksum = self
+            .output
+            .iter()
+            .fold(checksum, |sum, &byte| sum.wrapping_add(u32::from(byte)));
+        (
+            available,
+            InternalState::Data {
+                remaining: remaining - available as u64,
+                checksum,
+            },
+            ParseResult::Output,
+        )
+    }
+
+    fn skip_padding<'long, 'short>(
+        input: Input<'long, 'short>,
+        remaining: u64,
+    ) -> (usize, InternalState, ParseResult) {
+        let skipped = std::cmp::min(remaining, input.len() as u64);
+        let remaining = remaining - skipped;
+        let state = if remaining == 0 {
+            InternalState::EntryEnd
+        } else {
+            InternalState::Padding { remaining }
+        };
+        (skipped as usize, state, ParseResult::Continue)
+    }
+
+    /// Reads the next header, which either starts another entry or ends the archive.
+    fn end<'long, 'short>(
+        &self,
+        input: Input<'long, 'short>,
+    ) -> (usize, InternalState, ParseResult) {
+        match CpioFile::read_header(*input) {
+            Ok(HeaderResult::NeedsInput) => (0, InternalState::EntryEnd, ParseResult::NeedsInput),
+            Ok(HeaderResult::Entry(_, ref header)) if self.is_too_large(header) => (
+                0,
+                InternalState::Error(CpioError::EntryTooLarge),
+                ParseResult::Error(CpioError::EntryTooLarge),
+            ),
+            Ok(HeaderResult::Entry(len, header)) => {
+                // The header may have been buffered from several inputs,
+                // so it's handed over to the next entry already parsed.
+                let next_file = CpioFile {
+                    state: CpioFile::data_state(&header),
+                    unparsed: Vec::new(),
+                    header: Some(header),
+                    output: Vec::new(),
+                    options: self.options.clone(),
+                };
+                (len, InternalState::End, ParseResult::NextFile(next_file))
+            }
+            Ok(HeaderResult::Trailer(len)) => (len, InternalState::End, ParseResult::EndOfFile),
+            Err(e) => (0, InternalState::Error(e), ParseResult::Error(e)),
+        }
+    }
+}
+
+impl chain::Stream for CpioFile {
+    type Error = CpioError;
+
+    fn read<'i, 's>(&'s mut self, input: &'i [u8]) -> Result<State<'i, 's, CpioFile>, CpioError> {
+        CpioFile::read(self, input)
+    }
+
+    fn get_output(&self) -> &[u8] {
+        CpioFile::get_output(self)
+    }
+
+    fn name(&self) -> Option<&[u8]> {
+        self.filename()
+    }
+}
+
+impl chain::Container for CpioFile {
+    type Options = Options;
+
+    const PROBE_LEN: usize = HEADER_SIZE;
+
+    fn is_start(output: &[u8]) -> bool {
+        headers::is_cpio_header(output)
+    }
+
+    fn start(_probe: &[u8], options: &Options) -> CpioFile {
+        start_stream_with(options.clone())
+    }
+}
+
+/// A gzip stream that may contain a cpio archive. If the decompressed data
+/// starts with a cpio header, each entry of the archive is a file of its own,
+/// like with `CpioFile`. Otherwise the gzip members are returned as they are,
+/// like with `GZipFile`.
+pub struct CpioGzFile(Box<Chain<GZipFile, CpioFile>>);
+
+impl std::fmt::Debug for CpioGzFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("CpioGzFile")
+            .field("gzip", &self.0.outer())
+            .field("cpio", &self.0.inner())
+            .finish()
+    }
+}
+
+impl CpioGzFile {
+    pub fn get_output(&self) -> &[u8] {
+        self.0.get_output()
+    }
+
+    /// Whether the gzip stream contains a cpio archive. This is known
+    /// once the first output of the stream has been returned.
+    pub fn is_cpio(&self) -> bool {
+        self.0.is_piped()
+    }
+
+    /// The header of the cpio entry, once it has been parsed.
+    pub fn header(&self) -> Option<&Header> {
+        self.0.inner().and_then(CpioFile::header)
+    }
+
+    /// The name of the cpio entry or, if the stream doesn't contain
+    /// a cpio archive, the name of the gzip member.
+    pub fn filename(&self) -> Option<&[u8]> {
+        self.0.name()
+    }
+
+    pub fn read<'i, 's>(
+        &'s mut self,
+        input: &'i [u8],
+    ) -> Result<State<'i, 's, CpioGzFile>, crate::Error> {
+        Ok(self
+            .0
+            .read(input)?
+            .map_file(|next| CpioGzFile(Box::new(next))))
+    }
+
+    pub fn read_with<'i>(
+        &mut self,
+        mut input: &'i [u8],
+        mut callback: impl FnMut(&[u8]),
+    ) -> Result<State<'i, 'i, CpioGzFile>, crate::Error> {
+        loop {
+            match self.read(input)?.try_no_output() {
+                Err((unparsed_input, output)) => {
+                    input = unparsed_input;
+                    callback(output);
+                }
+                Ok(state) => return Ok(state),
+            }
+        }
+    }
+}
+
+/// Starts a stream of a cpio archive. The returned file is the first entry.
+pub fn start_stream() -> CpioFile {
+    start_stream_with(Options::default())
+}
+
+/// Starts a stream of a cpio archive that is parsed according to `options`.
+pub fn start_stream_with(options: Options) -> CpioFile {
+    CpioFile {
+        state: InternalState::Init,
+        unparsed: Vec::new(),
+        header: None,
+        output: Vec::new(),
+        options,
+    }
+}
+
+/// Starts a stream whose first bytes, `prefix`, were already read from the input
+/// to detect the format. They are parsed together with the input of the next `read`.
+pub(crate) fn start_stream_with_options_and_prefix(options: Options, prefix: &[u8]) -> CpioFile {
+    let mut file = start_stream_with(options);
+    file.unparsed = prefix.to_vec();
+    file
+}
+
+/// Starts a gzip stream that may contain a cpio archive.
+pub fn start_cpio_gz_stream() -> CpioGzFile {
+    CpioGzFile(Box::new(Chain::new(gzip::start_stream())))
+}
+
+/// Starts a gzip stream that may contain a cpio archive. The gzip stream is parsed
+/// according to `gzip_options`, and the cpio archive according to `options`.
+pub fn start_cpio_gz_stream_with(gzip_options: gzip::Options, options: Options) -> CpioGzFile {
+    CpioGzFile(Box::new(Chain::with_options(
+        gzip::start_stream_with(gzip_options),
+        options,
+    )))
+}
@@ -0,0 +1,121 @@
+use super::CpioError;
+
+/// The size of the fixed part of a header, which is followed by the name.
+pub const HEADER_SIZE: usize = 110;
+/// Longer names are rejected instead of being buffered.
+pub const MAX_NAME_SIZE: u32 = 64 * 1024;
+/// The name of the entry that ends the archive.
+pub const TRAILER_NAME: &[u8] = b"TRAILER!!!";
+
+const NEWC_MAGIC: &[u8] = b"070701";
+const CRC_MAGIC: &[u8] = b"070702";
+
+/// Whether `input` starts with the magic of the newc format or of its variant with checksums.
+pub fn is_cpio_header(input: &[u8]) -> bool {
+    input.starts_with(NEWC_MAGIC) || input.starts_with(CRC_MAGIC)
+}
+
+/// The number of padding bytes after `len` bytes of a header or of data.
+/// Both are padded to a multiple of four bytes.
+pub fn padding(len: u64) -> u64 {
+    (4 - len % 4) % 4
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Format {
+    Newc,
+    /// The newc format with a checksum of the data in the header.
+    Crc,
+}
+
+/// The file type bits of `Header::mode`.
+pub const MODE_TYPE_MASK: u32 = 0o170_000;
+pub const MODE_DIRECTORY: u32 = 0o040_000;
+pub const MODE_REGULAR: u32 = 0o100_000;
+/// The data of a symlink is the path that it points to.
+pub const MODE_SYMLINK: u32 = 0o120_000;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Header {
+    pub format: Format,
+    pub inode: u32,
+    /// The file type and the permissions.
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub nlink: u32,
+    /// The last modification time, in seconds since the Unix epoch.
+    pub mtime: u32,
+    /// The size of the data. Of hard links to the same inode, only
+    /// the last one has the data; the others have a size of 0.
+    pub size: u32,
+    pub dev_major: u32,
+    pub dev_minor: u32,
+    pub rdev_major: u32,
+    pub rdev_minor: u32,
+    /// The sum of the bytes of the data in the `Crc` format, 0 otherwise.
+    pub check: u32,
+    pub name: Vec<u8>,
+}
+
+impl Header {
+    /// Parses the fixed part of a header, returning it with an empty name
+    /// and the size of the name including its terminating NUL.
+    /// `input` must be `HEADER_SIZE` bytes long.
+    pub fn parse(input: &[u8]) -> Result<(Header, u32), CpioError> {
+        debug_assert_eq!(input.len(), HEADER_SIZE);
+        let format = match &input[..6] {
+            magic if magic == NEWC_MAGIC => Format::Newc,
+            magic if magic == CRC_MAGIC => Format::Crc,
+            _ => return Err(CpioError::InvalidHeader),
+        };
+        let field = |index: usize| {
+            let digits = &input[6 + 8 * index..6 + 8 * (index + 1)];
+            std::str::from_utf8(digits)
+                .ok()
+                .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+                .ok_or(CpioError::InvalidHeader)
+        };
+
+        let name_size = field(11)?;
+        if name_size == 0 || name_size > MAX_NAME_SIZE {
+            return Err(CpioError::InvalidHeader);
+        }
+        let header = Header {
+            format,
+            inode: field(0)?,
+            mode: field(1)?,
+            uid: field(2)?,
+            gid: field(3)?,
+            nlink: field(4)?,
+            mtime: field(5)?,
+            size: field(6)?,
+            dev_major: field(7)?,
+            dev_minor: field(8)?,
+            rdev_major: field(9)?,
+            rdev_minor: field(10)?,
+            check: field(12)?,
+            name: Vec::new(),
+        };
+        Ok((header, name_size))
+    }
+
+    /// The length of the header with the name and its padding.
+    pub fn len_with_name(name_size: u32) -> usize {
+        let len = HEADER_SIZE as u64 + u64::from(name_size);
+        (len + padding(len)) as usize
+    }
+
+    /// Whether the entry is the one that ends the archive.
+    pub fn is_trailer(&self) -> bool {
+        self.name == TRAILER_NAME
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.mode & MODE_TYPE_MASK == MODE_DIRECTORY
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        self.mode & MODE_TYPE_MASK == MODE_SYMLINK
+    }
+}
@@ -1,5 +1,6 @@
 use std::io::Cursor;
 
+use miniz_oxide::deflate::core::{CompressorOxide, TDEFLFlush, TDEFLStatus};
 use miniz_oxide::inflate::core::DecompressorOxide;
 use miniz_oxide::inflate::TINFLStatus;
 
@@ -7,11 +8,12 @@ struct InnerState {
     output: Vec<u8>,
     out_pos: usize,
     last_out_pos: usize,
-    decomp: DecompressorOxide,
+    decomp: Box<DecompressorOxide>,
     flags: u32,
-    uncomp_size: usize,
-    comp_size: usize,
+    uncomp_size: u64,
+    comp_size: u64,
     had_output: bool,
+    output_chunks: u64,
 }
 
 impl InnerState {
@@ -24,6 +26,26 @@ impl InnerState {
     }
 }
 
+/// Statistics of a `Stream`, for profiling inputs and tuning the sizes
+/// of the input chunks and of the output buffer. The decompressor doesn't
+/// expose the Deflate blocks it decodes, so they aren't counted.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct Stats {
+    /// The input consumed so far.
+    pub compressed_size: u64,
+    /// The output produced so far.
+    pub uncompressed_size: u64,
+    /// The number of chunks of output returned with `State::HasOutput`.
+    pub output_chunks: u64,
+    /// How much of the 32 KiB window that matches refer back to is filled.
+    pub window_fill: usize,
+    /// The output written to the output buffer since it last wrapped around
+    /// to its start, which happens whenever it's full.
+    pub buffer_fill: usize,
+    /// The size of the output buffer.
+    pub buffer_size: usize,
+}
+
 /*
 pub struct ChunkIter<'a> {
     input: &'a [u8],
@@ -52,7 +74,7 @@ pub enum State<'i, 'o> {
 
 fn consume_input<'i, 'o>(
     input: &'i [u8],
-    mut state: &'o mut InnerState,
+    state: &'o mut InnerState,
 ) -> Result<State<'i, 'o>, TINFLStatus> {
     use miniz_oxide::inflate::core::decompress;
     use miniz_oxide::inflate::TINFLStatus::*;
@@ -67,11 +89,13 @@ fn consume_input<'i, 'o>(
         // decompressed data for matches.
         let mut c = Cursor::new(state.output.as_mut_slice());
         c.set_position(state.out_pos as u64);
-        decompress(&mut state.decomp, &input[..], &mut c, state.flags)
+        decompress(&mut state.decomp, input, &mut c, state.flags)
     };
 
-    state.comp_size += in_consumed;
-    state.uncomp_size += out_consumed;
+    // The counters are 64-bit even on 32-bit targets,
+    // because streams over 4 GiB are nothing unusual.
+    state.comp_size += in_consumed as u64;
+    state.uncomp_size += out_consumed as u64;
     state.out_pos += out_consumed;
     let unparsed_input = &input[in_consumed..];
 
@@ -83,35 +107,43 @@ fn consume_input<'i, 'o>(
     match status {
         Done => {
             if out_consumed == 0 {
-                return Ok(State::Stop { unparsed_input });
+                Ok(State::Stop { unparsed_input })
             } else {
                 state.had_output = true;
+                state.output_chunks += 1;
                 let output = state.get_output();
-                return Ok(State::HasOutput {
+                Ok(State::HasOutput {
                     unparsed_input,
                     output,
-                });
+                })
             }
         }
         NeedsMoreInput => {
-            //if out_consumed == 0 {
-            return Ok(State::NeedsInput { unparsed_input });
-            /*    } else {
-                return Ok(State::HasOutput {
+            if out_consumed == 0 {
+                Ok(State::NeedsInput { unparsed_input })
+            } else {
+                // The input ran out in the middle of the stream, but the input that
+                // was there already produced some output; if we don't pass it on now,
+                // it's lost when the rest of the stream produces no further output.
+                state.had_output = true;
+                state.output_chunks += 1;
+                let output = state.get_output();
+                Ok(State::HasOutput {
                     unparsed_input,
                     output,
-                });
-            }*/
+                })
+            }
         }
         HasMoreOutput => {
             state.had_output = true;
+            state.output_chunks += 1;
             let output = state.get_output();
-            return Ok(State::HasOutput {
+            Ok(State::HasOutput {
                 unparsed_input,
                 output,
-            });
+            })
         }
-        _ => return Err(status),
+        _ => Err(status),
     }
 }
 
@@ -119,11 +151,31 @@ pub fn start_deflate_stream() -> Stream {
     Stream::new()
 }
 
+impl Default for Stream {
+    fn default() -> Stream {
+        Self::new()
+    }
+}
+
 impl Stream {
     pub fn new() -> Stream {
         Self::with(0, 0)
     }
 
+    /// A stream of the zlib format (RFC 1950) instead of raw Deflate:
+    /// the two-byte header is parsed, and the Adler-32 checksum at the end
+    /// is verified, failing with `TINFLStatus::Adler32Mismatch`. This is what
+    /// HTTP bodies with `Content-Encoding: deflate` usually contain.
+    /// `Stop` is returned after the checksum.
+    pub fn zlib() -> Stream {
+        use miniz_oxide::inflate::core::inflate_flags;
+
+        Self::with(
+            0,
+            inflate_flags::TINFL_FLAG_PARSE_ZLIB_HEADER | inflate_flags::TINFL_FLAG_COMPUTE_ADLER32,
+        )
+    }
+
     pub fn with(size: usize, flags: u32) -> Self {
         use miniz_oxide::inflate::core::inflate_flags;
         use miniz_oxide::inflate::core::TINFL_LZ_DICT_SIZE;
@@ -133,10 +185,9 @@ impl Stream {
 
         let size = max(TINFL_LZ_DICT_SIZE, size);
 
-        let mut output = Vec::with_capacity(size);
-        output.resize(size, 0);
+        let output = vec![0; size];
 
-        let mut decomp = DecompressorOxide::new();
+        let mut decomp = Box::new(DecompressorOxide::new());
         decomp.init();
 
         Self {
@@ -149,10 +200,24 @@ impl Stream {
                 uncomp_size: 0,
                 comp_size: 0,
                 had_output: false,
+                output_chunks: 0,
             },
         }
     }
 
+    /// Resets the stream to decompress a new stream with the same flags.
+    /// The output buffer is kept, so no memory is allocated.
+    pub fn reset(&mut self) {
+        let state = &mut self.state;
+        state.decomp.init();
+        state.out_pos = 0;
+        state.last_out_pos = 0;
+        state.uncomp_size = 0;
+        state.comp_size = 0;
+        state.had_output = false;
+        state.output_chunks = 0;
+    }
+
     pub fn feed_input<'i, 'o>(&'o mut self, input: &'i [u8]) -> Result<State<'i, 'o>, TINFLStatus> {
         consume_input(input, &mut self.state)
     }
@@ -161,16 +226,31 @@ impl Stream {
         self.state.get_output()
     }
 
-    pub fn uncompressed_size(&self) -> usize {
+    pub fn uncompressed_size(&self) -> u64 {
         self.state.uncomp_size
     }
 
-    pub fn compressed_size(&self) -> usize {
+    pub fn compressed_size(&self) -> u64 {
         self.state.comp_size
     }
 
-    pub fn inner_iter<'i, 'o>(
-        &'o mut self,
+    pub fn stats(&self) -> Stats {
+        use miniz_oxide::inflate::core::TINFL_LZ_DICT_SIZE;
+        use std::cmp::min;
+
+        let state = &self.state;
+        Stats {
+            compressed_size: state.comp_size,
+            uncompressed_size: state.uncomp_size,
+            output_chunks: state.output_chunks,
+            window_fill: min(state.uncomp_size, TINFL_LZ_DICT_SIZE as u64) as usize,
+            buffer_fill: state.out_pos,
+            buffer_size: state.output.len(),
+        }
+    }
+
+    pub fn inner_iter<'i>(
+        &mut self,
         mut input: &'i [u8],
         mut callback: impl FnMut(&[u8]),
     ) -> Result<State<'i, 'static>, TINFLStatus> {
@@ -230,6 +310,144 @@ impl<E> From<TINFLStatus> for InnerIterError<E> {
         InnerIterError::IterErr(from)
     }
 }
+
+/// A raw Deflate compressor with the same push-based model as `Stream`:
+/// `feed_input` returns `HasOutput` as long as the compressed output
+/// fills the output buffer, and `NeedsInput` when all of the input is consumed.
+/// The compressor keeps some of the input to itself until it has enough
+/// to emit a block, so once all of the input is fed, `finish` must be called
+/// until it returns `Stop` to get the rest of the stream.
+pub struct Encoder {
+    comp: Box<CompressorOxide>,
+    output: Vec<u8>,
+    out_len: usize,
+    uncomp_size: u64,
+    comp_size: u64,
+    finished: bool,
+}
+
+impl Default for Encoder {
+    fn default() -> Encoder {
+        Self::new()
+    }
+}
+
+impl Encoder {
+    pub fn new() -> Encoder {
+        Self::with(0, 6)
+    }
+
+    /// An encoder with an output buffer of at least `size` bytes,
+    /// compressing at `level`, from 0 (stored blocks only) to 10 (slowest).
+    pub fn with(size: usize, level: u8) -> Encoder {
+        use miniz_oxide::deflate::core::create_comp_flags_from_zip_params;
+        use miniz_oxide::inflate::core::TINFL_LZ_DICT_SIZE;
+        use std::cmp::max;
+
+        // Negative window bits mean raw Deflate without the zlib header.
+        let flags = create_comp_flags_from_zip_params(i32::from(level), -15, 0);
+
+        let size = max(TINFL_LZ_DICT_SIZE, size);
+
+        Self {
+            comp: Box::new(CompressorOxide::new(flags)),
+            output: vec![0; size],
+            out_len: 0,
+            uncomp_size: 0,
+            comp_size: 0,
+            finished: false,
+        }
+    }
+
+    fn compress<'i, 'o>(
+        &'o mut self,
+        input: &'i [u8],
+        flush: TDEFLFlush,
+    ) -> Result<State<'i, 'o>, TDEFLStatus> {
+        use miniz_oxide::deflate::core::compress;
+
+        if self.finished {
+            return Ok(State::Stop {
+                unparsed_input: input,
+            });
+        }
+
+        let (status, in_consumed, out_consumed) =
+            compress(&mut self.comp, input, &mut self.output, flush);
+
+        self.uncomp_size += in_consumed as u64;
+        self.comp_size += out_consumed as u64;
+        self.out_len = out_consumed;
+        let unparsed_input = &input[in_consumed..];
+
+        match status {
+            TDEFLStatus::Okay | TDEFLStatus::Done => {
+                self.finished = status == TDEFLStatus::Done;
+                if out_consumed > 0 {
+                    Ok(State::HasOutput {
+                        unparsed_input,
+                        output: &self.output[..out_consumed],
+                    })
+                } else if self.finished {
+                    Ok(State::Stop { unparsed_input })
+                } else {
+                    Ok(State::NeedsInput { unparsed_input })
+                }
+            }
+            _ => Err(status),
+        }
+    }
+
+    pub fn feed_input<'i, 'o>(&'o mut self, input: &'i [u8]) -> Result<State<'i, 'o>, TDEFLStatus> {
+        self.compress(input, TDEFLFlush::None)
+    }
+
+    /// Ends the stream. Returns `HasOutput` until the rest of the stream
+    /// is out, and `Stop` after that.
+    pub fn finish(&mut self) -> Result<State<'static, '_>, TDEFLStatus> {
+        self.compress(&[], TDEFLFlush::Finish)
+    }
+
+    pub fn get_output(&self) -> &[u8] {
+        &self.output[..self.out_len]
+    }
+
+    pub fn uncompressed_size(&self) -> u64 {
+        self.uncomp_size
+    }
+
+    pub fn compressed_size(&self) -> u64 {
+        self.comp_size
+    }
+
+    /// Feeds all of `input` to the encoder, passing the output to `callback`.
+    pub fn inner_iter(
+        &mut self,
+        mut input: &[u8],
+        mut callback: impl FnMut(&[u8]),
+    ) -> Result<(), TDEFLStatus> {
+        loop {
+            match self.feed_input(input)? {
+                State::HasOutput {
+                    unparsed_input,
+                    output,
+                } => {
+                    input = unparsed_input;
+                    callback(output);
+                }
+                State::NeedsInput { .. } | State::Stop { .. } => return Ok(()),
+            }
+        }
+    }
+
+    /// Ends the stream, passing the rest of the output to `callback`.
+    pub fn finish_iter(&mut self, mut callback: impl FnMut(&[u8])) -> Result<(), TDEFLStatus> {
+        while let State::HasOutput { output, .. } = self.finish()? {
+            callback(output);
+        }
+        Ok(())
+    }
+}
 /*
 impl<'a> ChunkIter<'a> {
     pub fn get(&self) -> &[u8]
@@ -349,10 +567,12 @@ mod tests {
                     })
                     .unwrap();
                 match state {
-                    State::NeedsInput { unparsed_input } if unparsed_input.is_empty() => (),
-                    State::Stop { unparsed_input } if unparsed_input.is_empty() => {
-                        last_state = Some(state)
-                    }
+                    State::NeedsInput {
+                        unparsed_input: &[],
+                    } => (),
+                    State::Stop {
+                        unparsed_input: &[],
+                    } => last_state = Some(state),
                     state => panic!("Un-expected parser state: {:?}", state),
                 }
             }
@@ -403,8 +623,10 @@ mod tests {
                 })
                 .expect("Should be valid DEFLATE");
             match state {
-                State::NeedsInput { unparsed_input } if unparsed_input.is_empty() => (),
-                State::Stop { unparsed_input } => last_state = Some(state),
+                State::NeedsInput {
+                    unparsed_input: &[],
+                } => (),
+                State::Stop { .. } => last_state = Some(state),
                 state => panic!("Un-expected parser state: {:?}", state),
             }
         }
@@ -449,4 +671,167 @@ mod tests {
             panic!("That should be all, folks!");
         }
     }
+
+    #[test]
+    fn test_zlib() {
+        use zip;
+
+        let data_zlib = fs::read("tests/assets/zlib/short_data.txt.zlib").unwrap();
+        let expected = fs::read("tests/assets/uncompressed/short_data.txt").unwrap();
+
+        // Feeds the stream in chunks of `size` bytes until it stops.
+        fn inflate(data: &[u8], size: usize) -> Result<Vec<u8>, TINFLStatus> {
+            let mut stream = Stream::zlib();
+            let mut output = Vec::new();
+            for chunk in data.chunks(size) {
+                if let State::Stop { unparsed_input } =
+                    stream.inner_iter(chunk, |out| output.extend_from_slice(out))?
+                {
+                    assert!(unparsed_input.is_empty());
+                    return Ok(output);
+                }
+            }
+            panic!("The stream didn't stop");
+        }
+
+        for size in 1..=data_zlib.len() {
+            assert_eq!(inflate(&data_zlib, size), Ok(expected.clone()));
+        }
+
+        // The last byte is a part of the Adler-32 checksum.
+        let mut corrupted = data_zlib.clone();
+        *corrupted.last_mut().unwrap() ^= 1;
+        assert_eq!(
+            inflate(&corrupted, corrupted.len()),
+            Err(TINFLStatus::Adler32Mismatch)
+        );
+
+        // A reset stream is a zlib stream again.
+        let mut stream = Stream::zlib();
+        stream.inner_iter(&data_zlib[..10], |_| ()).unwrap();
+        stream.reset();
+        let mut output = Vec::new();
+        let state = stream
+            .inner_iter(&data_zlib, |out| output.extend_from_slice(out))
+            .unwrap();
+        assert_eq!(
+            state,
+            State::Stop {
+                unparsed_input: &[]
+            }
+        );
+        assert_eq!(output, expected);
+        assert_eq!(stream.compressed_size(), data_zlib.len() as u64);
+        assert_eq!(stream.uncompressed_size(), expected.len() as u64);
+
+        // Raw Deflate has no zlib header.
+        let data_zip = fs::read("tests/assets/zip/short_data.txt.zip").unwrap();
+        let (unparsed, _) = zip::headers::LocalFileHeader::parse(&data_zip).unwrap();
+        assert!(Stream::zlib().feed_input(unparsed).is_err());
+    }
+
+    #[test]
+    fn test_stats() {
+        use zip;
+
+        let data_zip = fs::read("tests/assets/zip/ultra_repetitive_data.txt.zip").unwrap();
+        let (unparsed, _) = zip::headers::LocalFileHeader::parse(&data_zip).unwrap();
+        let expected_len = fs::metadata("tests/assets/uncompressed/ultra_repetitive_data.txt")
+            .unwrap()
+            .len();
+
+        let mut stream = Stream::with(64 * 1024, 0);
+        assert_eq!(
+            stream.stats(),
+            Stats {
+                buffer_size: 64 * 1024,
+                ..Stats::default()
+            }
+        );
+
+        let mut chunks = 0;
+        let state = stream.inner_iter(unparsed, |_| chunks += 1).unwrap();
+        let unparsed_len = match state {
+            State::Stop { unparsed_input } => unparsed_input.len(),
+            state => panic!("Un-expected parser state: {:?}", state),
+        };
+
+        let stats = stream.stats();
+        assert_eq!(
+            stats.compressed_size,
+            (unparsed.len() - unparsed_len) as u64
+        );
+        assert_eq!(stats.uncompressed_size, expected_len);
+        assert_eq!(stats.output_chunks, chunks);
+        assert!(stats.output_chunks > 1);
+        assert_eq!(stats.window_fill, 32 * 1024);
+        assert_eq!(stats.buffer_fill as u64, expected_len % (64 * 1024));
+        assert_eq!(stats.buffer_size, 64 * 1024);
+
+        stream.reset();
+        assert_eq!(
+            stream.stats(),
+            Stats {
+                buffer_size: 64 * 1024,
+                ..Stats::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_encoder() {
+        let text = fs::read("tests/assets/uncompressed/ultra_repetitive_data.txt").unwrap();
+        // Incompressible data ends up in stored blocks.
+        let noise: Vec<u8> = (0..100_000u32)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8)
+            .collect();
+
+        fn deflate(data: &[u8], size: usize, level: u8) -> Vec<u8> {
+            let mut encoder = Encoder::with(0, level);
+            let mut compressed = Vec::new();
+            for chunk in data.chunks(size) {
+                encoder
+                    .inner_iter(chunk, |out| compressed.extend_from_slice(out))
+                    .unwrap();
+            }
+            encoder
+                .finish_iter(|out| compressed.extend_from_slice(out))
+                .unwrap();
+            assert_eq!(
+                encoder.finish(),
+                Ok(State::Stop {
+                    unparsed_input: &[]
+                })
+            );
+            assert_eq!(encoder.uncompressed_size(), data.len() as u64);
+            assert_eq!(encoder.compressed_size(), compressed.len() as u64);
+            compressed
+        }
+
+        fn inflate(data: &[u8]) -> Vec<u8> {
+            let mut output = Vec::new();
+            let state = start_deflate_stream()
+                .inner_iter(data, |out| output.extend_from_slice(out))
+                .unwrap();
+            assert_eq!(
+                state,
+                State::Stop {
+                    unparsed_input: &[]
+                }
+            );
+            output
+        }
+
+        for &size in &[1000, 65536, 1_000_000] {
+            for &level in &[0, 1, 6, 10] {
+                let compressed = deflate(&text, size, level);
+                if level > 0 {
+                    assert!(compressed.len() < text.len() / 10);
+                }
+                assert_eq!(inflate(&compressed), text);
+                assert_eq!(inflate(&deflate(&noise, size, level)), noise);
+            }
+        }
+        assert_eq!(inflate(&deflate(&[], 1, 6)), b"");
+    }
 }
@@ -0,0 +1,104 @@
+//! Streaming decompression of Deflate64, also known as Enhanced Deflate
+//! (zip compression method 9). Deflate64 extends Deflate with a 64 KiB window
+//! and longer matches; Windows Explorer uses it for large files.
+//!
+//! The API mirrors `deflate::Stream` and returns the same `deflate::State`.
+
+use deflate64_codec::InflaterManaged;
+
+use crate::deflate::State;
+
+const OUTPUT_BUFFER_SIZE: usize = 32 * 1024;
+
+/// The Deflate64 stream contained invalid data.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct InvalidStream;
+
+pub struct Stream {
+    inflater: Box<InflaterManaged>,
+    output: Vec<u8>,
+    output_len: usize,
+    remaining_input: Option<u64>,
+    uncomp_size: u64,
+    comp_size: u64,
+}
+
+impl Default for Stream {
+    fn default() -> Stream {
+        Self::new()
+    }
+}
+
+impl Stream {
+    /// Starts a stream whose compressed length isn't known.
+    /// The decompressor may read up to a few bytes past the end of the stream,
+    /// so anything that follows the stream in the input can't be parsed reliably.
+    pub fn new() -> Stream {
+        Stream {
+            inflater: Box::new(InflaterManaged::new()),
+            output: vec![0; OUTPUT_BUFFER_SIZE],
+            output_len: 0,
+            remaining_input: None,
+            uncomp_size: 0,
+            comp_size: 0,
+        }
+    }
+
+    /// Starts a stream that is `compressed_size` bytes long.
+    /// No input past that is consumed.
+    pub fn with_compressed_size(compressed_size: u64) -> Stream {
+        Stream {
+            remaining_input: Some(compressed_size),
+            ..Stream::new()
+        }
+    }
+
+    pub fn feed_input<'i, 'o>(
+        &'o mut self,
+        input: &'i [u8],
+    ) -> Result<State<'i, 'o>, InvalidStream> {
+        let available = match self.remaining_input {
+            Some(remaining) => std::cmp::min(remaining, input.len() as u64) as usize,
+            None => input.len(),
+        };
+
+        let result = self.inflater.inflate(&input[..available], &mut self.output);
+        if result.data_error {
+            return Err(InvalidStream);
+        }
+
+        self.comp_size += result.bytes_consumed as u64;
+        self.uncomp_size += result.bytes_written as u64;
+        self.output_len = result.bytes_written;
+        if let Some(remaining) = self.remaining_input.as_mut() {
+            *remaining -= result.bytes_consumed as u64;
+        }
+        let unparsed_input = &input[result.bytes_consumed..];
+
+        if result.bytes_written > 0 {
+            Ok(State::HasOutput {
+                unparsed_input,
+                output: self.get_output(),
+            })
+        } else if self.inflater.finished() {
+            Ok(State::Stop { unparsed_input })
+        } else if self.remaining_input == Some(0) {
+            // All of the compressed data was consumed but the stream didn't end.
+            Err(InvalidStream)
+        } else {
+            Ok(State::NeedsInput { unparsed_input })
+        }
+    }
+
+    pub fn get_output(&self) -> &[u8] {
+        &self.output[..self.output_len]
+    }
+
+    pub fn uncompressed_size(&self) -> u64 {
+        self.uncomp_size
+    }
+
+    pub fn compressed_size(&self) -> u64 {
+        self.comp_size
+    }
+}
@@ -0,0 +1,259 @@
+//! Extraction of archives into a directory on the filesystem.
+//!
+//! The format of the input is autodetected. Directories are created as needed
+//! and files are written as their data streams by. Entry names are checked
+//! with `sanitize::is_safe_name` before anything is written: a name that is absolute, that starts with
+//! a drive letter or that climbs out of the destination with `..` fails the
+//! extraction with `ExtractError::UnsafePath`, so a malicious archive can't
+//! write outside the destination ("zip slip").
+//!
+//! Symbolic links, hard links and special files are never created; such
+//! entries of tar, cpio and ar archives are skipped. Zip archives only mark
+//! symbolic links in the central directory, which comes after the data,
+//! so when streaming, their entries are written as regular files that
+//! contain the link target.
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::{sanitize, start_stream, tar, Error, File, State, Summary};
+
+/// An error returned by extraction: either the filesystem or the reader
+/// failed, the stream itself was invalid, or an entry had an unsafe name.
+#[derive(Debug)]
+pub enum ExtractError {
+    Io(io::Error),
+    Stream(Error),
+    /// The name of an entry would be written outside the destination directory,
+    /// or the entry has no name.
+    UnsafePath(Vec<u8>),
+}
+
+impl std::fmt::Display for ExtractError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ExtractError::Io(e) => write!(f, "i/o error: {}", e),
+            ExtractError::Stream(e) => write!(f, "stream error: {}", e),
+            ExtractError::UnsafePath(name) => {
+                write!(f, "unsafe entry name: {}", String::from_utf8_lossy(name))
+            }
+        }
+    }
+}
+
+impl std::error::Error for ExtractError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ExtractError::Io(e) => Some(e),
+            ExtractError::Stream(e) => Some(e),
+            ExtractError::UnsafePath(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for ExtractError {
+    fn from(err: io::Error) -> ExtractError {
+        ExtractError::Io(err)
+    }
+}
+
+impl From<Error> for ExtractError {
+    fn from(err: Error) -> ExtractError {
+        ExtractError::Stream(err)
+    }
+}
+
+/// The size of the input buffer of `extract_to_dir`.
+const INPUT_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Extracts everything `reader` provides into the directory `dest`, which is
+/// created if it doesn't exist. Reading stops when the end of the archive
+/// is reached or when the reader runs out of input at a point where
+/// the stream may end. See the module documentation for what is extracted.
+pub fn extract_to_dir<R: Read>(
+    mut reader: R,
+    dest: impl AsRef<Path>,
+) -> Result<Summary, ExtractError> {
+    let mut extractor = Extractor::new(dest)?;
+    let mut buf = vec![0; INPUT_BUFFER_SIZE];
+    while !extractor.has_ended() {
+        let len = match reader.read(&mut buf) {
+            Ok(len) => len,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        if len == 0 {
+            break;
+        }
+        extractor.read(&buf[..len])?;
+    }
+    extractor.finish()
+}
+
+/// Extracts a stream that is fed in chunks into a directory. This is the
+/// streaming counterpart of `extract_to_dir`, for input that isn't available
+/// as a reader, such as the body of a request that arrives in chunks.
+pub struct Extractor {
+    dest: PathBuf,
+    file: File,
+    /// The output file of the current entry, once its first output has been written.
+    output: Option<fs::File>,
+    /// Whether the input may end at the current position.
+    may_end: bool,
+    ended: bool,
+    summary: Summary,
+}
+
+impl Extractor {
+    /// Starts extracting into `dest`, which is created if it doesn't exist.
+    pub fn new(dest: impl AsRef<Path>) -> Result<Extractor, ExtractError> {
+        let dest = dest.as_ref().to_path_buf();
+        fs::create_dir_all(&dest)?;
+        Ok(Extractor {
+            dest,
+            file: start_stream(),
+            output: None,
+            may_end: false,
+            ended: false,
+            summary: Summary::default(),
+        })
+    }
+
+    /// Whether the end of the archive has been reached. The input after it is ignored.
+    pub fn has_ended(&self) -> bool {
+        self.ended
+    }
+
+    /// Extracts what can be extracted from `input`, which continues the input
+    /// of the previous calls.
+    pub fn read(&mut self, mut input: &[u8]) -> Result<(), ExtractError> {
+        if self.ended {
+            return Ok(());
+        }
+        self.summary.compressed_bytes += input.len() as u64;
+        self.may_end = false;
+        loop {
+            match self.file.read(input)? {
+                State::HasOutput { unparsed_input, .. } => {
+                    input = unparsed_input;
+                    self.write_output()?;
+                }
+                State::NeedsInput => return Ok(()),
+                State::NeedsInputOrEof(next_file) => {
+                    self.end_entry()?;
+                    self.file = next_file;
+                    self.may_end = true;
+                    return Ok(());
+                }
+                State::NextFile {
+                    unparsed_input,
+                    next_file,
+                }
+                | State::NextArchive {
+                    unparsed_input,
+                    next_file,
+                } => {
+                    self.end_entry()?;
+                    self.file = next_file;
+                    input = unparsed_input;
+                }
+                State::EndOfFile | State::Stopped => {
+                    self.end_entry()?;
+                    self.ended = true;
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Signals that the input has ended. Fails if the archive isn't complete;
+    /// the entries extracted so far are left in place.
+    pub fn finish(self) -> Result<Summary, ExtractError> {
+        if self.ended || self.may_end {
+            return Ok(self.summary);
+        }
+        if let File::Zip(zip_file) = &self.file {
+            zip_file.finish().map_err(Error::from)?;
+        }
+        Err(io::Error::from(io::ErrorKind::UnexpectedEof).into())
+    }
+
+    /// The path the current entry is extracted to.
+    fn entry_path(&self) -> Result<PathBuf, ExtractError> {
+        let name = self.file.name().unwrap_or_default();
+        match sanitize::entry_path(name) {
+            Some(path) if sanitize::is_safe_name(name) => Ok(self.dest.join(path)),
+            _ => Err(ExtractError::UnsafePath(name.to_vec())),
+        }
+    }
+
+    fn write_output(&mut self) -> Result<(), ExtractError> {
+        if self.output.is_none() {
+            if is_special(&self.file) || self.file.is_dir() {
+                return Ok(());
+            }
+            self.output = Some(self.create_file()?);
+        }
+        let output = self.file.get_output();
+        self.output
+            .as_mut()
+            .expect("The output file was just created")
+            .write_all(output)?;
+        self.summary.uncompressed_bytes += output.len() as u64;
+        Ok(())
+    }
+
+    fn create_file(&self) -> Result<fs::File, ExtractError> {
+        let path = self.entry_path()?;
+        if path == self.dest {
+            return Err(ExtractError::UnsafePath(
+                self.file.name().unwrap_or_default().to_vec(),
+            ));
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        Ok(fs::File::create(path)?)
+    }
+
+    /// Finishes the current entry once it has ended. Directories and empty files
+    /// are created here, since they have no output.
+    fn end_entry(&mut self) -> Result<(), ExtractError> {
+        self.summary.files += 1;
+        if let Some(mut output) = self.output.take() {
+            output.flush()?;
+            return Ok(());
+        }
+        if is_special(&self.file) {
+            return Ok(());
+        }
+        if self.file.is_dir() {
+            fs::create_dir_all(self.entry_path()?)?;
+        } else {
+            self.create_file()?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether the current entry is a link or a special file, which isn't extracted.
+fn is_special(file: &File) -> bool {
+    let is_tar_special = |header: &tar::headers::Header| {
+        !matches!(
+            header.entry_type,
+            tar::headers::EntryType::Regular
+                | tar::headers::EntryType::Contiguous
+                | tar::headers::EntryType::Directory
+        )
+    };
+    match file {
+        File::TarGz(file) => file.header().is_some_and(is_tar_special),
+        File::Ar(file) => file.tar_header().is_some_and(is_tar_special),
+        File::Cpio(file) => file.header().is_some_and(|header| {
+            let file_type = header.mode & 0o170000;
+            file_type != 0o100000 && file_type != 0o040000
+        }),
+        _ => false,
+    }
+}
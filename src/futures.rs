@@ -0,0 +1,159 @@
+//! Decompression of a `futures_core::Stream` of input chunks into
+//! a stream of output chunks.
+//!
+//! `DecompressStream` reads the input chunks as they arrive and yields
+//! the decompressed output of every entry between an `OutputChunk::EntryStart`
+//! and an `OutputChunk::EntryEnd`, so it composes with stream combinators.
+//! The input is copied into an input buffer before it's parsed, and the output
+//! is copied out of the output buffer of the stream, as the items are owned.
+
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_core::Stream;
+
+use crate::archive::Core;
+use crate::{DriveError, EntryMetadata, File};
+
+/// An item of `DecompressStream`. The content is in `D`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum OutputChunk<D = Vec<u8>> {
+    /// An entry starts. Its metadata is as it was when its first output
+    /// was decompressed, or when it ended if it has no content.
+    EntryStart(EntryMetadata),
+    /// Decompressed content of the current entry.
+    Data(D),
+    /// The current entry ended.
+    EntryEnd,
+}
+
+/// The input chunks of a stream, read like `io::Read`.
+struct ChunkReader<S, B> {
+    input: S,
+    chunk: Option<B>,
+    pos: usize,
+}
+
+// The chunks are never pinned.
+impl<S: Unpin, B> Unpin for ChunkReader<S, B> {}
+
+impl<S, B> ChunkReader<S, B>
+where
+    S: Stream<Item = B> + Unpin,
+    B: AsRef<[u8]>,
+{
+    fn new(input: S) -> ChunkReader<S, B> {
+        ChunkReader {
+            input,
+            chunk: None,
+            pos: 0,
+        }
+    }
+
+    /// Copies the rest of the current chunk into `buf`, as much as fits,
+    /// polling the next chunk when it runs out. Returns 0 when the input has ended.
+    fn poll_read(&mut self, cx: &mut Context, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        loop {
+            if let Some(chunk) = &self.chunk {
+                let rest = &chunk.as_ref()[self.pos..];
+                if !rest.is_empty() {
+                    let len = rest.len().min(buf.len());
+                    buf[..len].copy_from_slice(&rest[..len]);
+                    self.pos += len;
+                    return Poll::Ready(Ok(len));
+                }
+            }
+            match std::task::ready!(Pin::new(&mut self.input).poll_next(cx)) {
+                Some(chunk) => {
+                    self.chunk = Some(chunk);
+                    self.pos = 0;
+                }
+                None => {
+                    self.chunk = None;
+                    return Poll::Ready(Ok(0));
+                }
+            }
+        }
+    }
+}
+
+/// A stream of the decompressed output of the input stream `S`, whose items are
+/// chunks of input. Running out of input where the stream may not end is reported
+/// like in `drive`. After an error, or after the archive has ended, the stream ends.
+pub struct DecompressStream<S, B> {
+    reader: ChunkReader<S, B>,
+    core: Core,
+    /// Whether the content of an entry has been started and not ended.
+    in_entry: bool,
+    ended: bool,
+}
+
+impl<S, B> DecompressStream<S, B>
+where
+    S: Stream<Item = B> + Unpin,
+    B: AsRef<[u8]>,
+{
+    /// Decompresses `input` with `file`, which is usually a stream that has just been
+    /// started with `start_stream` or `start_stream_with`.
+    pub fn new(file: File, input: S) -> DecompressStream<S, B> {
+        DecompressStream {
+            reader: ChunkReader::new(input),
+            core: Core::from_file(file),
+            in_entry: false,
+            ended: false,
+        }
+    }
+
+    /// The input stream.
+    pub fn get_ref(&self) -> &S {
+        &self.reader.input
+    }
+
+    /// The input stream.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.reader.input
+    }
+
+    fn poll_chunk(&mut self, cx: &mut Context) -> Poll<Result<Option<OutputChunk>, DriveError>> {
+        let reader = &mut self.reader;
+        let mut read = |buf: &mut [u8]| reader.poll_read(cx, buf);
+        if !self.in_entry {
+            if !std::task::ready!(self.core.poll_next_entry(&mut read))? {
+                return Poll::Ready(Ok(None));
+            }
+            self.in_entry = true;
+            return Poll::Ready(Ok(Some(OutputChunk::EntryStart(
+                self.core.metadata().clone(),
+            ))));
+        }
+        if std::task::ready!(self.core.poll_fill(&mut read))? {
+            Poll::Ready(Ok(Some(OutputChunk::Data(
+                self.core.take_pending().to_vec(),
+            ))))
+        } else {
+            self.in_entry = false;
+            Poll::Ready(Ok(Some(OutputChunk::EntryEnd)))
+        }
+    }
+}
+
+impl<S, B> Stream for DecompressStream<S, B>
+where
+    S: Stream<Item = B> + Unpin,
+    B: AsRef<[u8]>,
+{
+    type Item = Result<OutputChunk, DriveError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if this.ended {
+            return Poll::Ready(None);
+        }
+        let result = std::task::ready!(this.poll_chunk(cx));
+        if !matches!(result, Ok(Some(_))) {
+            this.ended = true;
+        }
+        Poll::Ready(result.transpose())
+    }
+}
@@ -1,21 +1,76 @@
 use deflate;
 
 use std;
+use std::sync::Arc;
+use std::time::SystemTime;
 
 use nom;
 
-use gzip::headers::MemberHeader;
+use gzip::headers::{MemberHeader, PendingFields};
 use State;
 
+use crate::chain;
 use crate::input_helper::{Input, InputHandler};
+use crate::utils::update_crc32;
+use crate::{
+    CompressedStream, Compression, EntryMetadata, EntryTotals, ErrorContext, FieldLimits,
+    HeaderField, HookProgress, Hooks, RatioLimit, StopConditions, Summary, TryReadError,
+};
 
+mod encoder;
 pub mod headers;
 
+pub use self::encoder::Encoder;
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub enum GZipError {
-    InvalidMemberHeader,
+    /// The input doesn't have a valid member header where one should start.
+    InvalidMemberHeader(ErrorContext),
     InvalidDeflateStream,
     InvalidFooter,
+    /// The CRC-32 in the footer of a member doesn't match its data.
+    ChecksumMismatch {
+        expected: u32,
+        actual: u32,
+    },
+    /// The size in the footer of a member doesn't match the size of its data.
+    /// The footer has the size modulo 2^32, which is `expected`.
+    SizeMismatch {
+        expected: u32,
+        actual: u64,
+    },
+    /// The input ended in the middle of a member. Returned by `GZipFile::finish`.
+    TruncatedMember,
+    /// The data of a member decompresses to more than `Options::max_member_size`.
+    MemberTooLarge,
+    /// The data of a member decompresses to more than `Options::max_compression_ratio`
+    /// allows for its compressed size.
+    CompressionRatioExceeded,
+    /// The filename or the comment of a member header is longer than its limit.
+    /// See `Options::max_filename_len`.
+    FieldTooLong(HeaderField),
+}
+
+impl GZipError {
+    /// Where in the input the error was detected, and what was there,
+    /// if the error is about a header.
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            GZipError::InvalidMemberHeader(context) => Some(context),
+            _ => None,
+        }
+    }
+
+    /// Moves the context of the error `by` bytes further in the input,
+    /// from the start of a parse step to the start of the stream.
+    fn shifted(self, by: u64) -> Self {
+        match self {
+            GZipError::InvalidMemberHeader(context) => {
+                GZipError::InvalidMemberHeader(context.shifted(by))
+            }
+            _ => self,
+        }
+    }
 }
 
 impl std::error::Error for GZipError {
@@ -28,17 +83,195 @@ impl std::fmt::Display for GZipError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         use self::GZipError::*;
         match self {
-            InvalidMemberHeader => write!(f, "invalid member header"),
+            InvalidMemberHeader(context) if context.expected_magic().is_empty() => {
+                write!(f, "invalid member header")
+            }
+            InvalidMemberHeader(context) => write!(f, "invalid member header {}", context),
             InvalidDeflateStream => write!(f, "invalid deflate stream"),
             InvalidFooter => write!(f, "invalid footer"),
+            ChecksumMismatch { expected, actual } => write!(
+                f,
+                "data checksum mismatch: expected {:08x}, got {:08x}",
+                expected, actual
+            ),
+            SizeMismatch { expected, actual } => write!(
+                f,
+                "data size mismatch: expected {} bytes, got {}",
+                expected, actual
+            ),
+            TruncatedMember => write!(f, "truncated member"),
+            MemberTooLarge => write!(f, "member exceeds the size limit"),
+            CompressionRatioExceeded => write!(f, "member exceeds the compression ratio limit"),
+            FieldTooLong(HeaderField::Comment) => write!(f, "comment exceeds the length limit"),
+            FieldTooLong(_) => write!(f, "filename exceeds the length limit"),
         }
     }
 }
 
+/// Options for parsing a gzip stream, set with the builder methods.
+/// The options are inherited by the files returned in `State::NextFile`
+/// and `State::NeedsInputOrEof`.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Options {
+    allow_trailing_data: bool,
+    concatenate_members: bool,
+    field_limits: FieldLimits,
+    pub(crate) hooks: Hooks,
+    max_compression_ratio: Option<RatioLimit>,
+    max_member_size: Option<u64>,
+    output_buffer_size: Option<usize>,
+    pub(crate) stop: StopConditions,
+}
+
+impl Options {
+    /// Some producers pad gzip files, for example to the block size of a tape.
+    /// By default, the bytes after a member must start another member, or the
+    /// stream fails with `GZipError::InvalidMemberHeader`. When enabled,
+    /// bytes that don't start like a member end the stream with
+    /// `State::EndOfFile`, and are available from `GZipFile::trailing_data`.
+    pub fn allow_trailing_data(mut self, enabled: bool) -> Self {
+        self.allow_trailing_data = enabled;
+        self
+    }
+
+    /// A gzip file may consist of several members, for example when it's
+    /// compressed in parallel by pigz or in blocks by bgzip. By default,
+    /// each member is a file of its own that is returned with `State::NextFile`.
+    /// When enabled, the members are read as one logical file like the `gzip`
+    /// tool does, so the output continues across the member boundaries.
+    /// The header of the file is that of the current member. If the input runs
+    /// out right after a member, `State::NeedsInputOrEof` is still returned,
+    /// and the output continues in the file returned with it. A tar archive
+    /// in a `TarGzFile` is read across the member boundaries as well.
+    pub fn concatenate_members(mut self, enabled: bool) -> Self {
+        self.concatenate_members = enabled;
+        self
+    }
+
+    /// Sets a hook that is called with the metadata of each member once its
+    /// header has been parsed, before any of its output is returned.
+    pub fn on_entry_start(mut self, hook: impl Fn(&EntryMetadata) + Send + Sync + 'static) -> Self {
+        self.hooks.on_entry_start(Arc::new(hook));
+        self
+    }
+
+    /// Sets a hook that is called with the metadata, the sizes and the CRC-32
+    /// of each member once its footer has been checked. With `concatenate_members`,
+    /// the members are one file, which ends with the last member.
+    pub fn on_entry_end(
+        mut self,
+        hook: impl Fn(&EntryMetadata, &EntryTotals) + Send + Sync + 'static,
+    ) -> Self {
+        self.hooks.on_entry_end(Arc::new(hook));
+        self
+    }
+
+    /// Sets a hook that is called with `GZipFile::progress` each time another
+    /// `interval` bytes of input have been consumed.
+    pub fn on_progress(
+        mut self,
+        interval: u64,
+        hook: impl Fn(&Summary) + Send + Sync + 'static,
+    ) -> Self {
+        self.hooks.on_progress(interval, Arc::new(hook));
+        self
+    }
+
+    /// Stops the stream with `State::Stopped` once `members` members have ended,
+    /// counted from the start of the stream.
+    pub fn stop_after_entries(mut self, members: usize) -> Self {
+        self.stop.entries = Some(members);
+        self
+    }
+
+    /// Stops the stream with `State::Stopped` once the member named `name` has ended.
+    pub fn stop_after_entry(mut self, name: impl Into<Vec<u8>>) -> Self {
+        self.stop.entry_name = Some(name.into());
+        self
+    }
+
+    /// Stops the stream with `State::Stopped` once it has output `bytes` bytes,
+    /// counted from the start of the stream. The output that reaches the limit
+    /// is returned in full.
+    pub fn stop_after_bytes(mut self, bytes: u64) -> Self {
+        self.stop.uncompressed_bytes = Some(bytes);
+        self
+    }
+
+    /// Limits the decompressed size of each member to `size` bytes. A member whose data
+    /// decompresses to more fails the stream with `GZipError::MemberTooLarge` as soon
+    /// as the output that exceeds the limit has been decompressed, before it's returned.
+    /// With `concatenate_members`, the limit applies to each member separately.
+    pub fn max_member_size(mut self, size: u64) -> Self {
+        self.max_member_size = Some(size);
+        self
+    }
+
+    /// Limits the ratio of the decompressed size of each member to its compressed size.
+    /// Once a member has decompressed to more than `min_size` bytes, the stream fails
+    /// with `GZipError::CompressionRatioExceeded` if its output so far is more than
+    /// `ratio` times the compressed data that produced it.
+    pub fn max_compression_ratio(mut self, ratio: u64, min_size: u64) -> Self {
+        self.max_compression_ratio = Some(RatioLimit { ratio, min_size });
+        self
+    }
+
+    /// Limits the length of the filename of each member header to `len` bytes.
+    /// A longer filename fails the stream with `GZipError::FieldTooLong`, unless
+    /// `truncate_long_fields` is enabled. The filename is zero-terminated, so
+    /// at most `len + 1` bytes of it are buffered while it's parsed.
+    pub fn max_filename_len(mut self, len: usize) -> Self {
+        self.field_limits.filename = Some(len);
+        self
+    }
+
+    /// Limits the length of the comment of each member header to `len` bytes.
+    /// See `max_filename_len`.
+    pub fn max_comment_len(mut self, len: usize) -> Self {
+        self.field_limits.comment = Some(len);
+        self
+    }
+
+    /// When enabled, a filename or a comment that is longer than its limit is
+    /// truncated to it, and the rest of it is skipped without being buffered,
+    /// rather than failing the stream.
+    pub fn truncate_long_fields(mut self, enabled: bool) -> Self {
+        self.field_limits.truncate = enabled;
+        self
+    }
+
+    /// Sets the size of the output buffer of the Deflate decompressor, which is
+    /// the most output a single `State::HasOutput` can have. A larger buffer
+    /// means fewer, larger chunks of output. The buffer is at least 32 KiB,
+    /// the size of the Deflate window, which is also the default.
+    pub fn output_buffer_size(mut self, size: usize) -> Self {
+        self.output_buffer_size = Some(size);
+        self
+    }
+
+    fn new_deflate_stream(&self) -> deflate::Stream {
+        deflate::Stream::with(self.output_buffer_size.unwrap_or(0), 0)
+    }
+}
+
+/// The magic number and the compression method (Deflate) that start a member.
+const MEMBER_MAGIC: &[u8] = b"\x1f\x8b\x08";
+
 pub struct GZipFile {
     state: InternalState,
     unparsed: Vec<u8>,
     inflater: deflate::Stream,
+    options: Options,
+    /// The CRC-32 of the output of the member so far.
+    crc_32: u32,
+    /// Whether the stream starts after the end of a member, where trailing data may start.
+    follows_member: bool,
+    trailing_data: Vec<u8>,
+    hook_progress: HookProgress,
+    /// The progress of the stream, counted from the start of its first file.
+    progress: Summary,
+    /// Whether a stop condition of the options has stopped the stream.
+    stopped: bool,
 }
 
 impl std::fmt::Debug for GZipFile {
@@ -46,6 +279,11 @@ impl std::fmt::Debug for GZipFile {
         f.debug_struct("GZipFile")
             .field("state", &self.state)
             .field("unparsed", &self.unparsed)
+            .field("options", &self.options)
+            .field("crc_32", &self.crc_32)
+            .field("follows_member", &self.follows_member)
+            .field("trailing_data", &self.trailing_data)
+            .field("hook_progress", &self.hook_progress)
             .finish()
     }
 }
@@ -55,25 +293,124 @@ struct HeaderParsed {
     header: headers::MemberHeader,
 }
 
+/// A member header whose fixed part has been parsed, and whose
+/// zero-terminated fields are parsed as the input arrives.
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct HeaderFields {
+    header: headers::MemberHeader,
+    pending: PendingFields,
+    /// Whether the rest of a truncated field is being skipped.
+    skipping: bool,
+}
+
+/// A step of parsing a zero-terminated field. See `HeaderFields::parse`.
+enum FieldStep {
+    /// The field, and the number of bytes consumed.
+    Parsed(Vec<u8>, usize),
+    /// The field was truncated to its limit, whose length is consumed.
+    Truncated(Vec<u8>),
+    NeedsInput,
+}
+
+impl HeaderFields {
+    /// Parses the fields in `input` as far as it goes, keeping them within `limits`.
+    /// Returns the number of bytes consumed, and whether the header is complete.
+    fn parse(&mut self, input: &[u8], limits: &FieldLimits) -> Result<(usize, bool), GZipError> {
+        let mut consumed = 0;
+        loop {
+            let rest = &input[consumed..];
+            if self.skipping {
+                match rest.iter().position(|&b| b == 0) {
+                    Some(pos) => {
+                        consumed += pos + 1;
+                        self.skipping = false;
+                    }
+                    None => return Ok((input.len(), false)),
+                }
+            } else if self.pending.filename || self.pending.comment {
+                let field = if self.pending.filename {
+                    HeaderField::Filename
+                } else {
+                    HeaderField::Comment
+                };
+                let value = match Self::parse_field(rest, field, limits)? {
+                    FieldStep::Parsed(value, len) => {
+                        consumed += len;
+                        value
+                    }
+                    FieldStep::Truncated(value) => {
+                        consumed += value.len();
+                        self.skipping = true;
+                        value
+                    }
+                    FieldStep::NeedsInput => return Ok((consumed, false)),
+                };
+                if self.pending.filename {
+                    self.pending.filename = false;
+                    self.header.filename = Some(value);
+                } else {
+                    self.pending.comment = false;
+                    self.header.fcomment = Some(value);
+                }
+            } else if self.pending.header_crc {
+                if rest.len() < 2 {
+                    return Ok((consumed, false));
+                }
+                consumed += 2;
+                self.pending.header_crc = false;
+            } else {
+                return Ok((consumed, true));
+            }
+        }
+    }
+
+    /// Parses a zero-terminated `field` from the start of `input`, looking
+    /// no further for its end than one byte past its limit.
+    fn parse_field(
+        input: &[u8],
+        field: HeaderField,
+        limits: &FieldLimits,
+    ) -> Result<FieldStep, GZipError> {
+        let limit = limits.limit(field);
+        let window = &input[..limit.map_or(input.len(), |limit| input.len().min(limit + 1))];
+        match window.iter().position(|&b| b == 0) {
+            Some(pos) => Ok(FieldStep::Parsed(input[..pos].to_vec(), pos + 1)),
+            None => match limit {
+                Some(limit) if window.len() > limit => {
+                    if limits.truncate {
+                        Ok(FieldStep::Truncated(input[..limit].to_vec()))
+                    } else {
+                        Err(GZipError::FieldTooLong(field))
+                    }
+                }
+                _ => Ok(FieldStep::NeedsInput),
+            },
+        }
+    }
+}
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 struct Inflated {
     header: headers::MemberHeader,
-    comp_size: usize,
-    uncomp_size: usize,
+    comp_size: u64,
+    uncomp_size: u64,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 enum InternalState {
     Init,
+    HeaderFields(HeaderFields),
     HeaderParsed(HeaderParsed),
     Inflated(Inflated),
     End(Inflated),
     Eof,
     Sentinel,
-    Error,
+    /// The stream failed with the error, which later reads return again.
+    Error(GZipError),
 }
 
 #[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
 enum ParseResult {
     Continue,
     NeedsInput,
@@ -81,6 +418,8 @@ enum ParseResult {
     Error(GZipError),
     NextFile(GZipFile),
     EndOfFile,
+    /// The stream ended with trailing data, which is the rest of the input.
+    TrailingData,
 }
 
 impl GZipFile {
@@ -92,15 +431,31 @@ impl GZipFile {
         &'s mut self,
         input: &'i [u8],
     ) -> Result<State<'i, 's, GZipFile>, GZipError> {
+        if let InternalState::Error(error) = self.state {
+            return Err(error);
+        }
+        if self.stopped || self.options.stop.are_met(&self.progress, None) {
+            self.stopped = true;
+            return Ok(State::Stopped);
+        }
         let mut ihandler = InputHandler::take_storage(&mut self.unparsed, input);
         let mut unparsed = ihandler.get_unparsed();
 
         loop {
+            let ended = self.has_ended();
             let mut state = InternalState::Sentinel;
             std::mem::swap(&mut self.state, &mut state);
+            // The offset of the input of the step from the start of the stream.
+            let step_offset = self.progress.compressed_bytes;
             let (bytes_consumed, new_state, res) = self.parse_step(state, unparsed);
             unparsed = ihandler.consumed(bytes_consumed);
             self.state = new_state;
+            let entry_ended = self.count_progress(bytes_consumed, ended, &res);
+            self.call_hooks();
+            if self.should_stop(entry_ended, &res) {
+                self.stopped = true;
+                return Ok(State::Stopped);
+            }
             match res {
                 ParseResult::Continue => (),
                 ParseResult::NeedsInput => {
@@ -123,17 +478,30 @@ impl GZipFile {
                     let unparsed_input = unparsed.assert_take_long();
                     return Ok(State::NextFile {
                         unparsed_input,
-                        next_file,
+                        next_file: self.pass_progress(next_file),
                     });
                 }
                 ParseResult::EndOfFile => {
                     if self.state == InternalState::Eof {
                         return Ok(State::EndOfFile);
                     } else {
-                        return Ok(State::NeedsInputOrEof(start_stream()));
+                        let mut next_file = start_stream_with(self.options.clone());
+                        next_file.follows_member = true;
+                        return Ok(State::NeedsInputOrEof(self.pass_progress(next_file)));
                     }
                 }
-                ParseResult::Error(err) => return Err(err),
+                ParseResult::TrailingData => {
+                    let rest = ihandler.take_rest(&mut self.unparsed);
+                    self.trailing_data = std::mem::take(&mut self.unparsed);
+                    self.trailing_data.extend_from_slice(rest);
+                    return Ok(State::EndOfFile);
+                }
+                ParseResult::Error(err) => {
+                    let err = err.shifted(step_offset);
+                    // Kept with the offset, so that later reads return it as it is.
+                    self.state = InternalState::Error(err);
+                    return Err(err);
+                }
             };
             if unparsed.is_empty() {
                 return Ok(State::NeedsInput);
@@ -147,41 +515,75 @@ impl GZipFile {
         input: Input<'long, 'short>,
     ) -> (usize, InternalState, ParseResult) {
         match state {
-            InternalState::Init => GZipFile::parse_header(input),
+            InternalState::Init => self.parse_header(input),
+            InternalState::HeaderFields(state) => self.parse_header_fields(input, state),
             InternalState::HeaderParsed(state) => self.inflate(input, state),
-            InternalState::Inflated(state) => GZipFile::parse_footer(input, state),
+            InternalState::Inflated(state) => self.parse_footer(input, state),
             InternalState::End { .. } => (0, InternalState::Eof, ParseResult::EndOfFile),
-            InternalState::Eof => {
-                panic!("Don't call read after Eof!");
-            }
+            InternalState::Eof => (0, InternalState::Eof, ParseResult::EndOfFile),
             InternalState::Sentinel => unreachable!("parse_step is never called with Sentinel"),
-            InternalState::Error => panic!("don't call parse_step with Error"),
+            InternalState::Error(error) => {
+                (0, InternalState::Error(error), ParseResult::Error(error))
+            }
         }
     }
 
     fn parse_header<'long, 'short>(
+        &self,
         input: Input<'long, 'short>,
     ) -> (usize, InternalState, ParseResult) {
-        match MemberHeader::parse(*input) {
-            Ok((unparsed, header)) => {
+        if self.follows_member {
+            match self.is_trailing_data(*input) {
+                Some(true) => return (0, InternalState::Eof, ParseResult::TrailingData),
+                Some(false) => (),
+                None => return (0, InternalState::Init, ParseResult::NeedsInput),
+            }
+        }
+        match MemberHeader::parse_fixed(*input) {
+            Ok((unparsed, (header, pending))) => {
                 let consumed = input.len() - unparsed.len();
                 (
                     consumed,
-                    InternalState::HeaderParsed(HeaderParsed { header }),
+                    InternalState::HeaderFields(HeaderFields {
+                        header,
+                        pending,
+                        skipping: false,
+                    }),
                     ParseResult::Continue,
                 )
             }
             Err(nom::Err::Incomplete(_need)) => (0, InternalState::Init, ParseResult::NeedsInput),
-            Err(nom::Err::Error(_e)) => (
-                0,
-                InternalState::Error,
-                ParseResult::Error(GZipError::InvalidMemberHeader),
+            Err(nom::Err::Error(_e)) | Err(nom::Err::Failure(_e)) => {
+                let error = GZipError::InvalidMemberHeader(ErrorContext::new(MEMBER_MAGIC, *input));
+                (0, InternalState::Error(error), ParseResult::Error(error))
+            }
+        }
+    }
+
+    fn parse_header_fields<'long, 'short>(
+        &self,
+        input: Input<'long, 'short>,
+        mut state: HeaderFields,
+    ) -> (usize, InternalState, ParseResult) {
+        match state.parse(*input, &self.options.field_limits) {
+            Ok((consumed, true)) => (
+                consumed,
+                InternalState::HeaderParsed(HeaderParsed {
+                    header: state.header,
+                }),
+                ParseResult::Continue,
             ),
-            Err(nom::Err::Failure(_e)) => (
+            Ok((0, false)) => (
                 0,
-                InternalState::Error,
-                ParseResult::Error(GZipError::InvalidMemberHeader),
+                InternalState::HeaderFields(state),
+                ParseResult::NeedsInput,
+            ),
+            Ok((consumed, false)) => (
+                consumed,
+                InternalState::HeaderFields(state),
+                ParseResult::Continue,
             ),
+            Err(err) => (0, InternalState::Error(err), ParseResult::Error(err)),
         }
     }
 
@@ -200,7 +602,29 @@ impl GZipFile {
                 unparsed_input,
                 output,
             }) => {
+                self.crc_32 = update_crc32(self.crc_32, output);
                 let consumed_bytes = input.len() - unparsed_input.len();
+                let uncomp_size = self.inflater.uncompressed_size();
+                if self
+                    .options
+                    .max_member_size
+                    .is_some_and(|max| uncomp_size > max)
+                {
+                    return (
+                        consumed_bytes,
+                        InternalState::Error(GZipError::MemberTooLarge),
+                        ParseResult::Error(GZipError::MemberTooLarge),
+                    );
+                }
+                if let Some(limit) = &self.options.max_compression_ratio {
+                    if limit.is_exceeded(self.inflater.compressed_size(), uncomp_size) {
+                        return (
+                            consumed_bytes,
+                            InternalState::Error(GZipError::CompressionRatioExceeded),
+                            ParseResult::Error(GZipError::CompressionRatioExceeded),
+                        );
+                    }
+                }
                 (
                     consumed_bytes,
                     InternalState::HeaderParsed(state),
@@ -224,24 +648,86 @@ impl GZipFile {
         }
     }
 
+    /// Whether `input`, which follows a member, is trailing data that
+    /// `Options::allow_trailing_data` allows. `None` if there's too little
+    /// input to tell whether another member starts.
+    fn is_trailing_data(&self, input: &[u8]) -> Option<bool> {
+        if !self.options.allow_trailing_data {
+            return Some(false);
+        }
+        let len = std::cmp::min(input.len(), MEMBER_MAGIC.len());
+        if input[..len] != MEMBER_MAGIC[..len] {
+            Some(true)
+        } else if len < MEMBER_MAGIC.len() {
+            None
+        } else {
+            Some(false)
+        }
+    }
+
     fn parse_footer<'long, 'short>(
+        &mut self,
         input: Input<'long, 'short>,
         state: Inflated,
     ) -> (usize, InternalState, ParseResult) {
         match headers::parse_footer(*input) {
-            Ok((mut unparsed, footer)) => {
+            Ok((mut unparsed, (crc_32, size))) => {
+                // The footer has the size modulo 2^32.
+                let error = if crc_32 != self.crc_32 {
+                    Some(GZipError::ChecksumMismatch {
+                        expected: crc_32,
+                        actual: self.crc_32,
+                    })
+                } else if size != state.uncomp_size as u32 {
+                    Some(GZipError::SizeMismatch {
+                        expected: size,
+                        actual: state.uncomp_size,
+                    })
+                } else {
+                    None
+                };
+                if let Some(error) = error {
+                    return (0, InternalState::Error(error), ParseResult::Error(error));
+                }
+
                 if unparsed.is_empty() {
                     let consumed = input.len() - unparsed.len();
                     return (consumed, InternalState::End(state), ParseResult::EndOfFile);
                 }
 
-                let res = match peek_stream(unparsed) {
+                match self.is_trailing_data(unparsed) {
+                    Some(true) => {
+                        let consumed = input.len() - unparsed.len();
+                        return (
+                            consumed,
+                            InternalState::End(state),
+                            ParseResult::TrailingData,
+                        );
+                    }
+                    Some(false) => (),
+                    None => return (0, InternalState::Inflated(state), ParseResult::NeedsInput),
+                }
+
+                if self.options.concatenate_members {
+                    // The next member continues the output of this one.
+                    self.inflater.reset();
+                    self.crc_32 = 0;
+                    let consumed = input.len() - unparsed.len();
+                    return (consumed, InternalState::Init, ParseResult::Continue);
+                }
+
+                let res = match peek_stream_with(unparsed, &self.options) {
                     Ok((unparsed_input, next_file)) => {
                         unparsed = unparsed_input;
                         ParseResult::NextFile(next_file)
                     }
                     Err(err) => {
-                        return (0, InternalState::Inflated(state), ParseResult::Error(err));
+                        let footer_len = (input.len() - unparsed.len()) as u64;
+                        return (
+                            0,
+                            InternalState::Inflated(state),
+                            ParseResult::Error(err.shifted(footer_len)),
+                        );
                     }
                 };
 
@@ -264,16 +750,123 @@ impl GZipFile {
         }
     }
 
-    pub fn filename(&self) -> Option<&[u8]> {
+    /// The header of the current member, once it has been parsed. It stays
+    /// available until the next member starts.
+    pub fn header(&self) -> Option<&MemberHeader> {
         match &self.state {
-            InternalState::HeaderParsed(HeaderParsed { header }) => header,
-            InternalState::Inflated(Inflated { header, .. }) => header,
-            InternalState::End(Inflated { header, .. }) => header,
-            _ => return None,
+            InternalState::HeaderParsed(HeaderParsed { header }) => Some(header),
+            InternalState::Inflated(Inflated { header, .. }) => Some(header),
+            InternalState::End(Inflated { header, .. }) => Some(header),
+            _ => None,
+        }
+    }
+
+    pub fn filename(&self) -> Option<&[u8]> {
+        self.header()?.filename.as_deref()
+    }
+
+    /// The bytes that followed the end of the stream in the input, when it
+    /// ended with trailing data that `Options::allow_trailing_data` allows.
+    /// Only the trailing data in the input that was passed to `read` is known.
+    pub fn trailing_data(&self) -> &[u8] {
+        &self.trailing_data
+    }
+
+    /// The modification time of the original file, once the header has been parsed.
+    /// See `MemberHeader::last_modified`.
+    pub fn last_modified(&self) -> Option<SystemTime> {
+        self.header()?.last_modified()
+    }
+
+    /// The input consumed, the output produced and the members completed so far,
+    /// counted from the start of the stream over all the files that it has
+    /// continued in. The output of the latest `State::HasOutput` is included.
+    pub fn progress(&self) -> Summary {
+        self.progress
+    }
+
+    fn has_ended(&self) -> bool {
+        matches!(self.state, InternalState::End(_))
+    }
+
+    /// Counts a parse step that consumed `bytes_consumed` bytes and resulted
+    /// in `res`, and the member if the step ended it, and reports the progress.
+    /// Returns whether the step ended the member.
+    fn count_progress(&mut self, bytes_consumed: usize, ended: bool, res: &ParseResult) -> bool {
+        let before = self.progress;
+        self.progress.compressed_bytes += bytes_consumed as u64;
+        if let ParseResult::Output = res {
+            self.progress.uncompressed_bytes += self.get_output().len() as u64;
+        }
+        let entry_ended = !ended && self.has_ended();
+        if entry_ended {
+            self.progress.files += 1;
+        }
+        self.options.hooks.report_progress(&before, &self.progress);
+        entry_ended
+    }
+
+    /// Whether a stop condition of the options is met after a parse step that
+    /// resulted in `res`. The output of a step is returned before stopping.
+    fn should_stop(&self, entry_ended: bool, res: &ParseResult) -> bool {
+        if let ParseResult::Output | ParseResult::Error(_) = res {
+            return false;
+        }
+        let ended_entry = if entry_ended {
+            Some(self.filename())
+        } else {
+            None
+        };
+        self.options.stop.are_met(&self.progress, ended_entry)
+    }
+
+    /// Continues the progress of this file in `next_file`, which parses
+    /// its unparsed input again.
+    fn pass_progress(&self, mut next_file: GZipFile) -> GZipFile {
+        next_file.progress = Summary {
+            compressed_bytes: self.progress.compressed_bytes - next_file.unparsed.len() as u64,
+            ..self.progress
+        };
+        next_file
+    }
+
+    /// Calls the hooks of the options that are due. See `Options::on_entry_start`.
+    fn call_hooks(&mut self) {
+        let totals = match &self.state {
+            InternalState::End(state) => Some(EntryTotals {
+                compressed_size: state.comp_size,
+                uncompressed_size: state.uncomp_size,
+                crc_32: self.crc_32,
+            }),
+            _ => None,
+        };
+        let mut progress = self.hook_progress;
+        self.options
+            .hooks
+            .call(&mut progress, self.header().is_some(), totals, || {
+                CompressedStream::metadata(self)
+            });
+        self.hook_progress = progress;
+    }
+
+    /// Signals that the input has ended. Returns `GZipError::TruncatedMember`
+    /// unless the stream has ended or has been stopped, or it follows a member
+    /// and nothing of another member has been read. Without this, a truncated
+    /// member would just keep returning `State::NeedsInput`.
+    pub fn finish(&self) -> Result<(), GZipError> {
+        if self.stopped {
+            return Ok(());
+        }
+        match self.state {
+            InternalState::End(_) | InternalState::Eof => Ok(()),
+            InternalState::Init
+                if self.follows_member
+                    && (self.unparsed.is_empty() || self.options.allow_trailing_data) =>
+            {
+                Ok(())
+            }
+            _ => Err(GZipError::TruncatedMember),
         }
-        .filename
-        .as_ref()
-        .map(|f| f.as_slice())
     }
 
     pub fn read_with<'i>(
@@ -282,49 +875,157 @@ impl GZipFile {
         mut callback: impl FnMut(&[u8]),
     ) -> Result<crate::State<'i, 'i, GZipFile>, GZipError> {
         loop {
-            let state = self.read(input)?;
-            if let State::HasOutput {
-                unparsed_input,
-                output,
-            } = state
-            {
-                input = unparsed_input;
-                callback(output);
-            } else {
-                return Ok(state.assert_no_output());
+            match self.read(input)?.try_no_output() {
+                Err((unparsed_input, output)) => {
+                    input = unparsed_input;
+                    callback(output);
+                }
+                Ok(state) => return Ok(state),
             }
         }
     }
+
+    /// Like `read_with`, but `callback` may fail, which stops
+    /// the reading and returns its error as `TryReadError::Callback`.
+    pub fn try_read_with<'i, E>(
+        &mut self,
+        input: &'i [u8],
+        callback: impl FnMut(&[u8]) -> Result<(), E>,
+    ) -> Result<State<'i, 'i, GZipFile>, TryReadError<GZipError, E>> {
+        crate::try_read_with(self, input, callback)
+    }
+}
+
+impl CompressedStream for GZipFile {
+    type Error = GZipError;
+
+    fn read<'i, 's>(&'s mut self, input: &'i [u8]) -> Result<State<'i, 's, GZipFile>, GZipError> {
+        GZipFile::read(self, input)
+    }
+
+    fn get_output(&self) -> &[u8] {
+        GZipFile::get_output(self)
+    }
+
+    fn metadata(&self) -> EntryMetadata {
+        EntryMetadata {
+            name: self.filename().map(<[u8]>::to_vec),
+            size_hint: None,
+            mtime: self.last_modified(),
+            is_dir: false,
+            compression: Compression::Deflate,
+        }
+    }
+
+    fn finish(&self) -> Result<(), GZipError> {
+        GZipFile::finish(self)
+    }
+}
+
+impl chain::Stream for GZipFile {
+    type Error = GZipError;
+
+    fn read<'i, 's>(&'s mut self, input: &'i [u8]) -> Result<State<'i, 's, GZipFile>, GZipError> {
+        GZipFile::read(self, input)
+    }
+
+    fn get_output(&self) -> &[u8] {
+        GZipFile::get_output(self)
+    }
+
+    fn name(&self) -> Option<&[u8]> {
+        self.filename()
+    }
+
+    fn continues_output(&self) -> bool {
+        self.options.concatenate_members
+    }
 }
 
 /// Stats a gzip stream.
 pub fn start_stream() -> GZipFile {
+    start_stream_with(Options::default())
+}
+
+/// Starts a gzip stream that is parsed according to `options`.
+pub fn start_stream_with(options: Options) -> GZipFile {
     GZipFile {
         state: InternalState::Init,
         unparsed: Vec::new(),
-        inflater: deflate::Stream::new(),
+        inflater: options.new_deflate_stream(),
+        options,
+        crc_32: 0,
+        follows_member: false,
+        trailing_data: Vec::new(),
+        hook_progress: HookProgress::default(),
+        progress: Summary::default(),
+        stopped: false,
     }
 }
 
+/// Starts a stream whose first bytes, `prefix`, were already read from the input
+/// to detect the format. They are parsed together with the input of the next `read`.
+pub(crate) fn start_stream_with_options_and_prefix(options: Options, prefix: &[u8]) -> GZipFile {
+    let mut file = start_stream_with(options);
+    file.unparsed = prefix.to_vec();
+    file
+}
+
 pub fn peek_stream(input: &[u8]) -> Result<(&[u8], GZipFile), GZipError> {
-    match MemberHeader::parse(input) {
+    peek_stream_with(input, &Options::default())
+}
+
+fn peek_stream_with<'i>(
+    input: &'i [u8],
+    options: &Options,
+) -> Result<(&'i [u8], GZipFile), GZipError> {
+    let parsed = match MemberHeader::parse_fixed(input) {
+        Ok((unparsed, (header, pending))) => {
+            let mut fields = HeaderFields {
+                header,
+                pending,
+                skipping: false,
+            };
+            match fields.parse(unparsed, &options.field_limits)? {
+                (consumed, true) => Ok((&unparsed[consumed..], fields.header)),
+                (_, false) => Err(nom::Err::Incomplete(nom::Needed::Unknown)),
+            }
+        }
+        Err(err) => Err(err),
+    };
+    match parsed {
         Ok((unparsed, header)) => Ok((
             unparsed,
             GZipFile {
-                state: InternalState::Init,
+                state: InternalState::HeaderParsed(HeaderParsed { header }),
                 unparsed: Vec::new(),
-                inflater: deflate::Stream::new(),
+                inflater: options.new_deflate_stream(),
+                options: options.clone(),
+                crc_32: 0,
+                follows_member: false,
+                trailing_data: Vec::new(),
+                hook_progress: HookProgress::default(),
+                progress: Summary::default(),
+                stopped: false,
             },
         )),
         Err(nom::Err::Incomplete(_need)) => Ok((
             &b""[..],
             GZipFile {
                 state: InternalState::Init,
-                unparsed: Vec::new(),
-                inflater: deflate::Stream::new(),
+                unparsed: input.to_vec(),
+                inflater: options.new_deflate_stream(),
+                options: options.clone(),
+                crc_32: 0,
+                follows_member: false,
+                trailing_data: Vec::new(),
+                hook_progress: HookProgress::default(),
+                progress: Summary::default(),
+                stopped: false,
             },
         )),
-        Err(nom::Err::Error(_e)) => Err(GZipError::InvalidMemberHeader),
-        Err(nom::Err::Failure(_e)) => Err(GZipError::InvalidMemberHeader),
+        Err(nom::Err::Error(_e)) | Err(nom::Err::Failure(_e)) => Err(
+            GZipError::InvalidMemberHeader(ErrorContext::new(MEMBER_MAGIC, input)),
+        ),
     }
 }
@@ -0,0 +1,73 @@
+use miniz_oxide::deflate::core::TDEFLStatus;
+
+use crate::deflate;
+use crate::gzip::headers::{self, MemberHeader};
+use crate::gzip::GZipError;
+use crate::utils::update_crc32;
+use crate::ErrorContext;
+
+/// Compresses data into a gzip member. The output is passed to the callbacks
+/// of `write` and `finish` as it's produced: first the member header,
+/// then the Deflate stream, and after it, the footer with the CRC-32
+/// and the size of the data.
+pub struct Encoder {
+    deflate: deflate::Encoder,
+    header: Option<Vec<u8>>,
+    crc_32: u32,
+}
+
+impl Encoder {
+    /// An encoder that compresses at the default level.
+    pub fn new(header: &MemberHeader) -> Result<Encoder, GZipError> {
+        Self::with_level(header, 6)
+    }
+
+    /// An encoder that compresses at `level`, from 0 (no compression)
+    /// to 10 (slowest). Fails with `GZipError::InvalidMemberHeader`, whose context
+    /// is empty, if the filename or the comment of the header contains a zero byte.
+    pub fn with_level(header: &MemberHeader, level: u8) -> Result<Encoder, GZipError> {
+        let header = header
+            .to_bytes()
+            .ok_or(GZipError::InvalidMemberHeader(ErrorContext::default()))?;
+        Ok(Encoder {
+            deflate: deflate::Encoder::with(0, level),
+            header: Some(header),
+            crc_32: 0,
+        })
+    }
+
+    fn write_header(&mut self, callback: &mut impl FnMut(&[u8])) {
+        if let Some(header) = self.header.take() {
+            callback(&header);
+        }
+    }
+
+    /// Compresses `input`. The output doesn't necessarily contain all of
+    /// the input until the member is finished.
+    pub fn write(
+        &mut self,
+        input: &[u8],
+        mut callback: impl FnMut(&[u8]),
+    ) -> Result<(), TDEFLStatus> {
+        self.write_header(&mut callback);
+        self.crc_32 = update_crc32(self.crc_32, input);
+        self.deflate.inner_iter(input, callback)
+    }
+
+    /// Ends the member with the rest of the Deflate stream and the footer.
+    pub fn finish(mut self, mut callback: impl FnMut(&[u8])) -> Result<(), TDEFLStatus> {
+        self.write_header(&mut callback);
+        self.deflate.finish_iter(&mut callback)?;
+
+        callback(&headers::footer(
+            self.crc_32,
+            self.deflate.uncompressed_size(),
+        ));
+        Ok(())
+    }
+
+    /// The size of the data written so far.
+    pub fn uncompressed_size(&self) -> u64 {
+        self.deflate.uncompressed_size()
+    }
+}
@@ -4,6 +4,8 @@ use nom::number::streaming::{le_u16, le_u32, le_u8};
 use nom::sequence::{pair, terminated, tuple};
 use nom::IResult;
 
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
 use nom::error::ErrorKind;
 
 use nom::bits::{bits, streaming::take as take_bits};
@@ -34,38 +36,221 @@ fn extra_data(i: &[u8]) -> IResult<&[u8], &[u8]> {
     take(len)(i)
 }
 
+/// The operating system or the filesystem a member was compressed on,
+/// as listed in RFC 1952.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Os {
+    Fat,
+    Amiga,
+    Vms,
+    Unix,
+    VmCms,
+    AtariTos,
+    Hpfs,
+    Macintosh,
+    ZSystem,
+    CpM,
+    Tops20,
+    Ntfs,
+    Qdos,
+    AcornRiscos,
+    /// The value 255, which stands for an unknown system, or a value
+    /// that isn't listed in RFC 1952.
+    Unknown(u8),
+}
+
+impl From<u8> for Os {
+    fn from(os: u8) -> Os {
+        use self::Os::*;
+        match os {
+            0 => Fat,
+            1 => Amiga,
+            2 => Vms,
+            3 => Unix,
+            4 => VmCms,
+            5 => AtariTos,
+            6 => Hpfs,
+            7 => Macintosh,
+            8 => ZSystem,
+            9 => CpM,
+            10 => Tops20,
+            11 => Ntfs,
+            12 => Qdos,
+            13 => AcornRiscos,
+            os => Unknown(os),
+        }
+    }
+}
+
+impl From<Os> for u8 {
+    fn from(os: Os) -> u8 {
+        use self::Os::*;
+        match os {
+            Fat => 0,
+            Amiga => 1,
+            Vms => 2,
+            Unix => 3,
+            VmCms => 4,
+            AtariTos => 5,
+            Hpfs => 6,
+            Macintosh => 7,
+            ZSystem => 8,
+            CpM => 9,
+            Tops20 => 10,
+            Ntfs => 11,
+            Qdos => 12,
+            AcornRiscos => 13,
+            Unknown(os) => os,
+        }
+    }
+}
+
+/// The hint about how hard the compressor tried, which some compressors
+/// set in the extra flags of the header.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum CompressionHint {
+    /// The compressor used its slowest method for the best compression.
+    Best,
+    /// The compressor used its fastest method.
+    Fastest,
+}
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct MemberHeader {
+    /// The FTEXT flag, which the compressor may set if it guesses
+    /// that the data is text.
+    pub text: bool,
     pub mtime: u32,
+    /// The XFL byte. See `MemberHeader::compression_hint`.
+    pub extra_flags: u8,
     pub os: u8,
     pub filename: Option<Vec<u8>>,
     pub fcomment: Option<Vec<u8>>,
 }
 
+/// A header with no metadata, from an unknown system.
+impl Default for MemberHeader {
+    fn default() -> MemberHeader {
+        MemberHeader {
+            text: false,
+            mtime: 0,
+            extra_flags: 0,
+            os: 255,
+            filename: None,
+            fcomment: None,
+        }
+    }
+}
+
 impl MemberHeader {
+    /// The modification time of the original file, or `None` if the header
+    /// has none, which it marks with a time of 0.
+    pub fn last_modified(&self) -> Option<SystemTime> {
+        match self.mtime {
+            0 => None,
+            secs => Some(UNIX_EPOCH + Duration::from_secs(u64::from(secs))),
+        }
+    }
+
+    /// The hint in the extra flags, if any. Like the `gzip` tool, tools
+    /// may use it for heuristics, but it doesn't affect decompression.
+    pub fn compression_hint(&self) -> Option<CompressionHint> {
+        match self.extra_flags {
+            2 => Some(CompressionHint::Best),
+            4 => Some(CompressionHint::Fastest),
+            _ => None,
+        }
+    }
+
+    /// The system the member was compressed on.
+    pub fn operating_system(&self) -> Os {
+        Os::from(self.os)
+    }
+
+    /// Serializes the header. Returns `None` if the filename or the comment
+    /// contains a zero byte, which would end it early.
+    pub fn to_bytes(&self) -> Option<Vec<u8>> {
+        let fields = [&self.filename, &self.fcomment];
+        if fields
+            .iter()
+            .any(|field| field.as_ref().is_some_and(|f| f.contains(&0)))
+        {
+            return None;
+        }
+
+        let flags = u8::from(self.text)
+            | u8::from(self.filename.is_some()) << 3
+            | u8::from(self.fcomment.is_some()) << 4;
+        let mut bytes = vec![0x1f, 0x8b, 0x08, flags];
+        bytes.extend_from_slice(&self.mtime.to_le_bytes());
+        bytes.extend_from_slice(&[self.extra_flags, self.os]);
+        for field in fields.iter().filter_map(|field| field.as_ref()) {
+            bytes.extend_from_slice(field);
+            bytes.push(0);
+        }
+        Some(bytes)
+    }
+
     pub fn parse(i: &[u8]) -> IResult<&[u8], MemberHeader> {
+        let (i, (mut header, pending)) = MemberHeader::parse_fixed(i)?;
+        let (i, (filename, fcomment, _header_crc)) = tuple((
+            cond(pending.filename, zero_terminated),
+            cond(pending.comment, zero_terminated),
+            cond(pending.header_crc, le_u16),
+        ))(i)?;
+        header.filename = filename.map(ToOwned::to_owned);
+        header.fcomment = fcomment.map(ToOwned::to_owned);
+        Ok((i, header))
+    }
+
+    /// Parses the header up to the zero-terminated filename and comment,
+    /// skipping the extra field. Returns the header without them, and
+    /// the fields that follow in the input.
+    pub(crate) fn parse_fixed(i: &[u8]) -> IResult<&[u8], (MemberHeader, PendingFields)> {
         let (i, (_tag, _compression, bit_flags)) =
             tuple((tag(b"\x1f\x8b"), tag(b"\x08"), parse_bitflags))(i)?;
-        let (i, (mtime, _xtra_flags, os)) = tuple((le_u32, le_u8, le_u8))(i)?;
-        let (i, (_extra, filename, fcomment, _header_crc)) = tuple((
-            cond(bit_flags.2, extra_data),
-            cond(bit_flags.3, zero_terminated),
-            cond(bit_flags.4, zero_terminated),
-            cond(bit_flags.1, le_u16),
-        ))(i)?;
+        let (i, (mtime, extra_flags, os)) = tuple((le_u32, le_u8, le_u8))(i)?;
+        let (i, _extra) = cond(bit_flags.2, extra_data)(i)?;
 
         Ok((
             i,
-            MemberHeader {
-                mtime,
-                os,
-                filename: filename.map(ToOwned::to_owned),
-                fcomment: fcomment.map(ToOwned::to_owned),
-            },
+            (
+                MemberHeader {
+                    text: bit_flags.0,
+                    mtime,
+                    extra_flags,
+                    os,
+                    filename: None,
+                    fcomment: None,
+                },
+                PendingFields {
+                    filename: bit_flags.3,
+                    comment: bit_flags.4,
+                    header_crc: bit_flags.1,
+                },
+            ),
         ))
     }
 }
 
+/// The fields of a header that follow its fixed part, in this order,
+/// and haven't been parsed yet.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub(crate) struct PendingFields {
+    pub(crate) filename: bool,
+    pub(crate) comment: bool,
+    pub(crate) header_crc: bool,
+}
+
+/// Serializes a footer of a member with `size` bytes of data,
+/// which the footer has modulo 2^32.
+pub fn footer(crc_32: u32, size: u64) -> [u8; 8] {
+    let mut footer = [0; 8];
+    footer[..4].copy_from_slice(&crc_32.to_le_bytes());
+    footer[4..].copy_from_slice(&(size as u32).to_le_bytes());
+    footer
+}
+
 pub fn parse_footer(i: &[u8]) -> IResult<&[u8], (u32, u32)> {
     let (size, crc32) = pair(le_u32, le_u32)(i)?;
     Ok((size, crc32))
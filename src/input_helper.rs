@@ -17,8 +17,8 @@ where
 
     fn deref(&self) -> &&'short [u8] {
         match self {
-            Input::Long(long) => &long,
-            Input::Short(short) => &short,
+            Input::Long(long) => long,
+            Input::Short(short) => short,
         }
     }
 }
@@ -52,13 +52,13 @@ impl<'l> InputHandler<'l> {
             orig_input,
         };
 
-        let unparsed = if orig_stored > 0 {
+        if orig_stored > 0 {
             // There was too little input to progress the parser state during
             // the previous call so the input of last call was stored to self.unparsed.
             // Appending the current input to existing unparsed input
             // for them to form a continuous buffer.
             ihandler.extend_input();
-        };
+        }
 
         ihandler
     }
@@ -67,8 +67,20 @@ impl<'l> InputHandler<'l> {
         std::mem::swap(reader_storage, &mut self.storage);
     }
 
+    /// Returns the part of the input that hasn't been consumed or stored yet.
+    /// Unlike `Input::assert_take_long`, this may be called while the parser
+    /// is still working through stored data: the rest of the stored data
+    /// is returned to `reader_storage` to be parsed during the next call.
+    pub fn take_rest(mut self, reader_storage: &mut Vec<u8>) -> &'l [u8] {
+        let input_stored_consumed = (self.storage.len() - self.orig_stored)
+            .saturating_add(self.input_consumed)
+            .min(self.orig_input.len());
+        std::mem::swap(reader_storage, &mut self.storage);
+        &self.orig_input[input_stored_consumed..]
+    }
+
     pub fn get_unparsed<'s>(&'s self) -> Input<'l, 's> {
-        if self.storage.len() > 0 {
+        if !self.storage.is_empty() {
             Input::Short(self.storage.as_slice())
         } else {
             Input::Long(self.orig_input)
@@ -77,20 +89,32 @@ impl<'l> InputHandler<'l> {
 
     pub fn consumed<'s>(&'s mut self, bytes: usize) -> Input<'l, 's> {
         if bytes == 0 {
-            if self.storage.len() > 0 {
+            if !self.storage.is_empty() {
                 return Input::Short(&self.storage[..]);
             } else {
                 return Input::Long(&self.orig_input[self.input_consumed..]);
             }
         }
 
-        // Either no bytes are consumed or then
-        // so much is consumed that it exceeds the stored amount.
-        // Consuming only a part of the stored amount does not happen,
-        // because it was enough to be parsed, it wouldn't have been
-        // stored at first place.
-        debug_assert!(self.orig_stored < bytes);
-        self.input_consumed += bytes - self.orig_stored;
+        if bytes <= self.orig_stored {
+            // Only a part of the stored data was consumed, such as the first bytes
+            // of a stream that were stored for the format detection.
+            self.storage.drain(..bytes);
+            self.orig_stored -= bytes;
+            return Input::Short(&self.storage[..]);
+        }
+
+        // A parser never consumes more than it was given. Clamping to the input
+        // length keeps a misbehaving one from indexing past the end of the input
+        // in a release build.
+        debug_assert!(
+            self.input_consumed + (bytes - self.orig_stored) <= self.orig_input.len(),
+            "consumed more than the input"
+        );
+        self.input_consumed = self
+            .input_consumed
+            .saturating_add(bytes - self.orig_stored)
+            .min(self.orig_input.len());
         // Consumed data successfully so the stored data isn't needed anymore.
         self.storage.truncate(0);
         self.orig_stored = 0;
@@ -110,10 +134,21 @@ impl<'l> InputHandler<'l> {
         // By indexing this amount as the lower bound, we get the slice
         // of input that hasn't been processed any way yet.
         // We call that "extension".
-        let input_stored_consumed = self.storage.len() - self.orig_stored + self.input_consumed;
-        let upper_bound = std::cmp::min(input_stored_consumed + 80, self.orig_input.len());
-        let extension = &self.orig_input[input_stored_consumed..upper_bound];
+        let input_stored_consumed = (self.storage.len() - self.orig_stored)
+            .saturating_add(self.input_consumed)
+            .min(self.orig_input.len());
         let was_empty = self.storage.is_empty();
+        let upper_bound = if was_empty {
+            // The parser has already seen the rest of the input,
+            // so all of it must be stored, not just the next 80 bytes.
+            self.orig_input.len()
+        } else {
+            std::cmp::min(
+                input_stored_consumed.saturating_add(80),
+                self.orig_input.len(),
+            )
+        };
+        let extension = &self.orig_input[input_stored_consumed..upper_bound];
         self.storage.extend(extension);
         if was_empty {
             // The storage was empty, which means that even if we extended it,
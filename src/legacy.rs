@@ -0,0 +1,164 @@
+//! Decompression of the legacy PKZIP compression methods:
+//! Shrink (zip method 1), Reduce (methods 2–5) and Implode (method 6).
+//!
+//! The API mirrors `deflate::Stream` and returns the same `deflate::State`.
+//! None of these methods mark the end of the compressed data, so the stream
+//! needs to know both the compressed and the uncompressed length up front.
+//! The entries they were used for are small, so the stream buffers all of
+//! the compressed data and decodes it in one go before returning any output.
+
+mod implode;
+mod reduce;
+mod shrink;
+
+use std::convert::TryFrom;
+
+use crate::deflate::State;
+
+const OUTPUT_BUFFER_SIZE: usize = 32 * 1024;
+
+/// The compressed data was invalid.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct InvalidStream;
+
+/// A legacy compression method and its parameters.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Method {
+    Shrink,
+    /// Reduce with a compression factor from 1 to 4 (methods 2 to 5).
+    Reduce {
+        factor: u8,
+    },
+    /// Implode, with either a 4 KiB or an 8 KiB sliding dictionary,
+    /// and with or without a Shannon-Fano tree for the literals.
+    Implode {
+        large_window: bool,
+        literal_tree: bool,
+    },
+}
+
+pub struct Stream {
+    method: Method,
+    input: Vec<u8>,
+    output: Vec<u8>,
+    output_start: usize,
+    output_len: usize,
+    decoded: bool,
+    remaining_input: u64,
+    uncomp_size: u64,
+}
+
+impl Stream {
+    /// Starts a stream that is `compressed_size` bytes long
+    /// and decompresses to `uncompressed_size` bytes.
+    /// No input past that is consumed.
+    pub fn new(method: Method, compressed_size: u64, uncompressed_size: u64) -> Stream {
+        Stream {
+            method,
+            input: Vec::new(),
+            output: Vec::new(),
+            output_start: 0,
+            output_len: 0,
+            decoded: false,
+            remaining_input: compressed_size,
+            uncomp_size: uncompressed_size,
+        }
+    }
+
+    pub fn feed_input<'i, 'o>(
+        &'o mut self,
+        input: &'i [u8],
+    ) -> Result<State<'i, 'o>, InvalidStream> {
+        let available = std::cmp::min(self.remaining_input, input.len() as u64) as usize;
+        self.input.extend_from_slice(&input[..available]);
+        self.remaining_input -= available as u64;
+        let unparsed_input = &input[available..];
+
+        if !self.decoded {
+            if self.remaining_input > 0 {
+                return Ok(State::NeedsInput { unparsed_input });
+            }
+            self.output = self.decode()?;
+            self.decoded = true;
+        }
+
+        self.output_start += self.output_len;
+        self.output_len = std::cmp::min(OUTPUT_BUFFER_SIZE, self.output.len() - self.output_start);
+        if self.output_len > 0 {
+            Ok(State::HasOutput {
+                unparsed_input,
+                output: self.get_output(),
+            })
+        } else {
+            Ok(State::Stop { unparsed_input })
+        }
+    }
+
+    fn decode(&self) -> Result<Vec<u8>, InvalidStream> {
+        let size = usize::try_from(self.uncomp_size).map_err(|_| InvalidStream)?;
+        match self.method {
+            Method::Shrink => shrink::decode(&self.input, size),
+            Method::Reduce { factor } => reduce::decode(&self.input, size, factor),
+            Method::Implode {
+                large_window,
+                literal_tree,
+            } => implode::decode(&self.input, size, large_window, literal_tree),
+        }
+    }
+
+    pub fn get_output(&self) -> &[u8] {
+        &self.output[self.output_start..self.output_start + self.output_len]
+    }
+
+    pub fn uncompressed_size(&self) -> u64 {
+        (self.output_start + self.output_len) as u64
+    }
+
+    pub fn compressed_size(&self) -> u64 {
+        self.input.len() as u64
+    }
+}
+
+/// Reads bit fields starting from the least significant bit of each byte.
+struct BitReader<'a> {
+    input: &'a [u8],
+    buffer: u32,
+    buffered: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(input: &'a [u8]) -> BitReader<'a> {
+        BitReader {
+            input,
+            buffer: 0,
+            buffered: 0,
+        }
+    }
+
+    /// Reads `count` bits, at most 16. Returns `None` if the input runs out.
+    fn bits(&mut self, count: u32) -> Option<u32> {
+        while self.buffered < count {
+            let (&byte, rest) = self.input.split_first()?;
+            self.buffer |= u32::from(byte) << self.buffered;
+            self.buffered += 8;
+            self.input = rest;
+        }
+        let value = self.buffer & ((1 << count) - 1);
+        self.buffer >>= count;
+        self.buffered -= count;
+        Some(value)
+    }
+}
+
+/// Copies `length` bytes from `distance` bytes back in `output`, stopping at `size`.
+/// Reduce and Implode treat the bytes before the start of the output as zeros.
+fn copy_match(output: &mut Vec<u8>, distance: usize, length: usize, size: usize) {
+    for _ in 0..std::cmp::min(length, size - output.len()) {
+        let byte = if distance <= output.len() {
+            output[output.len() - distance]
+        } else {
+            0
+        };
+        output.push(byte);
+    }
+}
@@ -0,0 +1,118 @@
+//! Implode is LZ77 with a 4 KiB or 8 KiB sliding dictionary. Match lengths,
+//! the high bits of match distances and optionally the literals are coded with
+//! Shannon-Fano trees, which are sent at the start of the data as lists of
+//! code lengths. The codes are the bitwise complements of the canonical
+//! Huffman codes for the same lengths.
+
+use super::{copy_match, BitReader, InvalidStream};
+
+const MAX_CODE_LENGTH: usize = 16;
+
+struct Tree {
+    /// The number of codes of each length.
+    counts: [u16; MAX_CODE_LENGTH + 1],
+    /// The symbols ordered by code length, and by value within a length.
+    symbols: Vec<u16>,
+}
+
+impl Tree {
+    /// Reads a tree of `size` symbols. The tree is sent as a byte count
+    /// followed by bytes that each hold a code length and the number
+    /// of consecutive symbols that have it.
+    fn read(reader: &mut BitReader, size: usize) -> Result<Tree, InvalidStream> {
+        let mut lengths = Vec::with_capacity(size);
+        let bytes = reader.bits(8).ok_or(InvalidStream)? + 1;
+        for _ in 0..bytes {
+            let byte = reader.bits(8).ok_or(InvalidStream)?;
+            let length = (byte & 0x0f) as usize + 1;
+            let repeat = (byte >> 4) as usize + 1;
+            lengths.extend(std::iter::repeat_n(length, repeat));
+        }
+        if lengths.len() != size {
+            return Err(InvalidStream);
+        }
+
+        let mut counts = [0; MAX_CODE_LENGTH + 1];
+        for &length in &lengths {
+            counts[length] += 1;
+        }
+        // Reject trees that have more codes than the lengths allow.
+        let mut left = 1i32;
+        for &count in &counts[1..] {
+            left = left * 2 - i32::from(count);
+            if left < 0 {
+                return Err(InvalidStream);
+            }
+        }
+
+        let mut symbols = Vec::with_capacity(size);
+        for length in 1..=MAX_CODE_LENGTH {
+            for (symbol, _) in lengths.iter().enumerate().filter(|&(_, &l)| l == length) {
+                symbols.push(symbol as u16);
+            }
+        }
+        Ok(Tree { counts, symbols })
+    }
+
+    /// Reads a code bit by bit and returns its symbol.
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, InvalidStream> {
+        let mut code = 0i32;
+        let mut first = 0i32;
+        let mut index = 0i32;
+        for &count in &self.counts[1..] {
+            code |= (reader.bits(1).ok_or(InvalidStream)? ^ 1) as i32;
+            let count = i32::from(count);
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(InvalidStream)
+    }
+}
+
+pub fn decode(
+    input: &[u8],
+    size: usize,
+    large_window: bool,
+    literal_tree: bool,
+) -> Result<Vec<u8>, InvalidStream> {
+    let mut reader = BitReader::new(input);
+    let literals = if literal_tree {
+        Some(Tree::read(&mut reader, 256)?)
+    } else {
+        None
+    };
+    let lengths = Tree::read(&mut reader, 64)?;
+    let distances = Tree::read(&mut reader, 64)?;
+    let min_length = if literal_tree { 3 } else { 2 };
+    let distance_low_bits = if large_window { 7 } else { 6 };
+
+    let mut output = Vec::new();
+    while output.len() < size {
+        match reader.bits(1) {
+            Some(1) => {
+                let literal = match literals {
+                    Some(ref tree) => tree.decode(&mut reader)?,
+                    None => reader.bits(8).ok_or(InvalidStream)? as u16,
+                };
+                output.push(literal as u8);
+            }
+            Some(_) => {
+                let low = reader.bits(distance_low_bits).ok_or(InvalidStream)? as usize;
+                let high = distances.decode(&mut reader)? as usize;
+                let distance = (high << distance_low_bits | low) + 1;
+                let symbol = lengths.decode(&mut reader)? as usize;
+                let mut length = symbol + min_length;
+                if symbol == 63 {
+                    length += reader.bits(8).ok_or(InvalidStream)? as usize;
+                }
+                copy_match(&mut output, distance, length, size);
+            }
+            None => return Err(InvalidStream),
+        }
+    }
+    Ok(output)
+}
@@ -0,0 +1,117 @@
+//! Reduce compresses in two stages. The first stage encodes each byte
+//! using a set of likely followers of the previous byte. The second stage
+//! is a simple LZ77 scheme where the byte 144 (DLE) introduces a match,
+//! with the compression factor deciding how many bits go to the distance.
+
+use super::{copy_match, BitReader, InvalidStream};
+
+const DLE: u8 = 144;
+const MAX_FOLLOWERS: usize = 32;
+
+/// Where the second stage is in the current sequence of bytes.
+enum Expect {
+    Byte,
+    /// A byte after DLE: either 0 for a literal DLE, or the start of a match.
+    MatchStart,
+    /// An extra length byte, for matches whose length field is all ones.
+    ExtraLength {
+        start: u8,
+        length: usize,
+    },
+    DistanceLow {
+        start: u8,
+        length: usize,
+    },
+}
+
+pub fn decode(input: &[u8], size: usize, factor: u8) -> Result<Vec<u8>, InvalidStream> {
+    if !(1..=4).contains(&factor) {
+        return Err(InvalidStream);
+    }
+    let length_mask = 0x7f >> (factor - 1);
+
+    let mut reader = BitReader::new(input);
+    let mut followers = vec![Vec::new(); 256];
+    for set in followers.iter_mut().rev() {
+        let count = reader.bits(6).ok_or(InvalidStream)? as usize;
+        if count > MAX_FOLLOWERS {
+            return Err(InvalidStream);
+        }
+        for _ in 0..count {
+            set.push(reader.bits(8).ok_or(InvalidStream)? as u8);
+        }
+    }
+
+    let mut output = Vec::new();
+    let mut last = 0u8;
+    let mut expect = Expect::Byte;
+    while output.len() < size {
+        let set = &followers[last as usize];
+        let byte = if set.is_empty() {
+            reader.bits(8)
+        } else {
+            match reader.bits(1) {
+                Some(1) => reader.bits(8),
+                Some(_) => match reader.bits(index_bits(set.len())) {
+                    Some(index) => Some(u32::from(*set.get(index as usize).ok_or(InvalidStream)?)),
+                    None => None,
+                },
+                None => None,
+            }
+        };
+        let byte = match byte {
+            Some(byte) => byte as u8,
+            None => break,
+        };
+        last = byte;
+
+        expect = match expect {
+            Expect::Byte if byte == DLE => Expect::MatchStart,
+            Expect::Byte => {
+                output.push(byte);
+                Expect::Byte
+            }
+            Expect::MatchStart if byte == 0 => {
+                output.push(DLE);
+                Expect::Byte
+            }
+            Expect::MatchStart => {
+                let length = usize::from(byte & length_mask);
+                if length == usize::from(length_mask) {
+                    Expect::ExtraLength {
+                        start: byte,
+                        length,
+                    }
+                } else {
+                    Expect::DistanceLow {
+                        start: byte,
+                        length,
+                    }
+                }
+            }
+            Expect::ExtraLength { start, length } => Expect::DistanceLow {
+                start,
+                length: length + usize::from(byte),
+            },
+            Expect::DistanceLow { start, length } => {
+                let distance = (usize::from(start) >> (8 - factor)) * 256 + usize::from(byte) + 1;
+                copy_match(&mut output, distance, length + 3, size);
+                Expect::Byte
+            }
+        };
+    }
+
+    if output.len() < size {
+        return Err(InvalidStream);
+    }
+    Ok(output)
+}
+
+/// The number of bits used for an index into a follower set of `len` bytes.
+fn index_bits(len: usize) -> u32 {
+    if len <= 2 {
+        1
+    } else {
+        32 - (len as u32 - 1).leading_zeros()
+    }
+}
@@ -0,0 +1,128 @@
+//! Shrink is LZW with code sizes from 9 to 13 bits. Code 256 is followed by
+//! a control code that either grows the code size or partially clears the table.
+//! New codes take the lowest free code, so after a partial clear,
+//! the freed codes get reused in order.
+
+use super::{BitReader, InvalidStream};
+
+const CONTROL_CODE: u16 = 256;
+const FIRST_CODE: u16 = 257;
+const MAX_CODE_SIZE: u32 = 13;
+const TABLE_SIZE: usize = 1 << MAX_CODE_SIZE;
+
+struct Table {
+    /// The code whose string is extended by `suffixes[code]`,
+    /// or `None` for the free codes.
+    prefixes: Vec<Option<u16>>,
+    suffixes: Vec<u8>,
+    free: Vec<u16>,
+    next_free: usize,
+}
+
+impl Table {
+    fn new() -> Table {
+        Table {
+            prefixes: vec![None; TABLE_SIZE],
+            suffixes: (0..TABLE_SIZE).map(|code| code as u8).collect(),
+            free: (FIRST_CODE..TABLE_SIZE as u16).collect(),
+            next_free: 0,
+        }
+    }
+
+    fn peek_free(&self) -> Option<u16> {
+        self.free.get(self.next_free).cloned()
+    }
+
+    fn add(&mut self, prefix: u16, suffix: u8) {
+        if let Some(code) = self.peek_free() {
+            self.prefixes[code as usize] = Some(prefix);
+            self.suffixes[code as usize] = suffix;
+            self.next_free += 1;
+        }
+    }
+
+    /// Frees the codes that aren't a prefix of another code.
+    fn partial_clear(&mut self) {
+        let mut is_prefix = vec![false; TABLE_SIZE];
+        for prefix in self.prefixes[FIRST_CODE as usize..].iter().flatten() {
+            is_prefix[*prefix as usize] = true;
+        }
+        self.free.clear();
+        self.next_free = 0;
+        for code in FIRST_CODE..TABLE_SIZE as u16 {
+            if !is_prefix[code as usize] {
+                self.prefixes[code as usize] = None;
+                self.free.push(code);
+            }
+        }
+    }
+
+    /// Writes the string of `code` to `string`, replacing its contents.
+    fn write_string(&self, mut code: u16, string: &mut Vec<u8>) -> Result<(), InvalidStream> {
+        string.clear();
+        while code >= FIRST_CODE {
+            // A chain longer than the table must loop.
+            if string.len() >= TABLE_SIZE {
+                return Err(InvalidStream);
+            }
+            string.push(self.suffixes[code as usize]);
+            code = self.prefixes[code as usize].ok_or(InvalidStream)?;
+        }
+        if code == CONTROL_CODE {
+            return Err(InvalidStream);
+        }
+        string.push(code as u8);
+        string.reverse();
+        Ok(())
+    }
+}
+
+pub fn decode(input: &[u8], size: usize) -> Result<Vec<u8>, InvalidStream> {
+    let mut reader = BitReader::new(input);
+    let mut table = Table::new();
+    let mut code_size = 9;
+    let mut output = Vec::new();
+    let mut string = Vec::new();
+
+    let mut prev = match reader.bits(code_size) {
+        Some(code) if code < u32::from(CONTROL_CODE) => code as u16,
+        Some(_) => return Err(InvalidStream),
+        None => return Ok(output),
+    };
+    output.push(prev as u8);
+
+    while output.len() < size {
+        let code = match reader.bits(code_size) {
+            Some(code) => code as u16,
+            None => break,
+        };
+
+        if code == CONTROL_CODE {
+            match reader.bits(code_size) {
+                Some(1) if code_size < MAX_CODE_SIZE => code_size += 1,
+                Some(2) => table.partial_clear(),
+                _ => return Err(InvalidStream),
+            }
+            continue;
+        }
+
+        if table.peek_free() == Some(code) {
+            // The code is the one about to be added: the previous string
+            // extended with its own first byte.
+            table.write_string(prev, &mut string)?;
+            let first = string[0];
+            string.push(first);
+        } else {
+            table.write_string(code, &mut string)?;
+        }
+        output.extend_from_slice(&string);
+        table.add(prev, string[0]);
+        prev = code;
+    }
+
+    if output.len() < size {
+        return Err(InvalidStream);
+    }
+    output.truncate(size);
+    Ok(output)
+}
@@ -1,15 +1,148 @@
+// The dependencies are aliased so that they don't clash with
+// the modules of the same name.
+#[cfg(feature = "aes")]
+extern crate aes as aes_cipher;
+#[cfg(feature = "bytes")]
+extern crate bytes as bytes_crate;
+#[cfg(feature = "bzip2")]
+extern crate bzip2 as bzip2_codec;
 extern crate core;
+#[cfg(feature = "deflate64")]
+extern crate deflate64 as deflate64_codec;
+#[cfg(feature = "futures")]
+extern crate futures_core;
+#[cfg(feature = "aes")]
+extern crate hmac;
 extern crate miniz_oxide;
 extern crate nom;
+#[cfg(feature = "aes")]
+extern crate pbkdf2;
+#[cfg(feature = "ppmd")]
+extern crate ppmd_rust;
+#[cfg(feature = "aes")]
+extern crate sha1;
+#[cfg(feature = "tokio")]
+extern crate tokio as tokio_rt;
+#[cfg(any(feature = "xz", feature = "sevenz"))]
+extern crate xz2;
+#[cfg(feature = "zstd")]
+extern crate zstd_safe;
 
+pub mod ar;
+pub mod archive;
+#[cfg(feature = "bytes")]
+pub mod bytes;
+#[cfg(feature = "bzip2")]
+pub mod bzip2;
+mod chain;
+mod chunked;
+pub mod cpio;
 pub mod deflate;
+#[cfg(feature = "deflate64")]
+pub mod deflate64;
+pub mod extract;
+#[cfg(feature = "futures")]
+pub mod futures;
 pub mod gzip;
 pub mod input_helper;
+pub mod legacy;
+pub mod lzw;
+pub mod nested;
+pub mod passthrough;
+#[cfg(feature = "ppmd")]
+pub mod ppmd;
+pub mod sanitize;
+#[cfg(feature = "sevenz")]
+pub mod sevenz;
+pub mod tar;
+#[cfg(feature = "test-util")]
+pub mod test_util;
+#[cfg(feature = "tokio")]
+pub mod tokio;
 pub mod utils;
+#[cfg(feature = "xz")]
+pub mod xz;
 pub mod zip;
+pub mod zlib;
+pub mod zstd;
 
-trait CompressedStream: Sized {
-    fn feed_input(&mut self, input: &[u8]) -> State<Self>;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A stream that is fed with input and hands out its output through `State`.
+/// Implemented by `File` and by the streams of the zip and gzip formats,
+/// so that code can be written generically over the type of the stream.
+pub trait CompressedStream: Sized {
+    type Error: Into<Error> + std::error::Error;
+
+    /// Feeds `input` to the stream. See `File::read`.
+    fn read<'i, 's>(&'s mut self, input: &'i [u8]) -> Result<State<'i, 's, Self>, Self::Error>;
+
+    /// The output of the latest `State::HasOutput`.
+    fn get_output(&self) -> &[u8];
+
+    /// The metadata of the current entry or file. See `File::metadata`.
+    fn metadata(&self) -> EntryMetadata;
+
+    /// Signals that the input has ended. Returns an error if the stream
+    /// was cut short at the point where it was fed last.
+    fn finish(&self) -> Result<(), Self::Error>;
+}
+
+/// Feeds `input` to `stream`, passing all of the output to `callback`,
+/// until `stream` needs more input or `callback` fails.
+/// Shared by the `try_read_with` methods of the streams.
+pub(crate) fn try_read_with<'i, S, E>(
+    stream: &mut S,
+    mut input: &'i [u8],
+    mut callback: impl FnMut(&[u8]) -> Result<(), E>,
+) -> Result<State<'i, 'i, S>, TryReadError<S::Error, E>>
+where
+    S: CompressedStream,
+{
+    loop {
+        match stream
+            .read(input)
+            .map_err(TryReadError::Stream)?
+            .try_no_output()
+        {
+            Err((unparsed_input, output)) => {
+                callback(output).map_err(TryReadError::Callback)?;
+                input = unparsed_input;
+            }
+            Ok(state) => return Ok(state),
+        }
+    }
+}
+
+/// An error returned by the `try_read_with` methods: either the stream
+/// was invalid, or the callback failed.
+#[derive(Debug)]
+pub enum TryReadError<S, E> {
+    Stream(S),
+    Callback(E),
+}
+
+impl<S: std::fmt::Display, E: std::fmt::Display> std::fmt::Display for TryReadError<S, E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TryReadError::Stream(e) => e.fmt(f),
+            TryReadError::Callback(e) => write!(f, "callback error: {}", e),
+        }
+    }
+}
+
+impl<S, E> std::error::Error for TryReadError<S, E>
+where
+    S: std::error::Error + 'static,
+    E: std::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TryReadError::Stream(e) => Some(e),
+            TryReadError::Callback(e) => Some(e),
+        }
+    }
 }
 
 /// Represents a state of a compressed input stream.
@@ -18,7 +151,7 @@ pub enum State<'i, 's, File>
 where
     'i: 's,
 {
-    NeedsInputOrEof(gzip::GZipFile),
+    NeedsInputOrEof(File),
     NeedsInput,
     HasOutput {
         unparsed_input: &'i [u8],
@@ -28,24 +161,125 @@ where
         unparsed_input: &'i [u8],
         next_file: File,
     },
+    /// The previous archive ended and another one starts
+    /// in the same input stream. Only returned by zip streams
+    /// that have the concatenated archives mode enabled.
+    NextArchive {
+        unparsed_input: &'i [u8],
+        next_file: File,
+    },
     EndOfFile,
+    /// The stream was stopped early by a stop condition of the options, such as
+    /// `Options::stop_after_entries`. The rest of the input isn't parsed, and
+    /// reading the stream again returns `Stopped` again.
+    Stopped,
 }
 
 impl<'i, 's, F> State<'i, 's, F> {
+    /// Like `try_no_output`, but panics if the state is `HasOutput`.
+    #[deprecated(note = "use `try_no_output`, which doesn't panic")]
     pub fn assert_no_output(self) -> State<'i, 'i, F> {
+        match self.try_no_output() {
+            Ok(state) => state,
+            Err(_) => panic!("Assertion failed: self was HasOutput"),
+        }
+    }
+
+    /// Returns the state without the borrow of the output buffer of the stream,
+    /// or the unparsed input and the output if the state is `HasOutput`.
+    pub fn try_no_output(self) -> Result<State<'i, 'i, F>, (&'i [u8], &'s [u8])> {
         use State::*;
         match self {
-            NeedsInputOrEof(f) => NeedsInputOrEof(f),
+            NeedsInputOrEof(f) => Ok(NeedsInputOrEof(f)),
+            NeedsInput => Ok(NeedsInput),
+            HasOutput {
+                unparsed_input,
+                output,
+            } => Err((unparsed_input, output)),
+            NextFile {
+                unparsed_input,
+                next_file,
+            } => Ok(NextFile {
+                unparsed_input,
+                next_file,
+            }),
+            NextArchive {
+                unparsed_input,
+                next_file,
+            } => Ok(NextArchive {
+                unparsed_input,
+                next_file,
+            }),
+            EndOfFile => Ok(EndOfFile),
+            Stopped => Ok(Stopped),
+        }
+    }
+
+    /// Returns the unparsed input and the output of a `HasOutput` state,
+    /// or `None` if the state is something else.
+    pub fn into_output(self) -> Option<(&'i [u8], &'s [u8])> {
+        match self {
+            State::HasOutput {
+                unparsed_input,
+                output,
+            } => Some((unparsed_input, output)),
+            _ => None,
+        }
+    }
+
+    /// Like `into_output`, but panics if the state isn't `HasOutput`.
+    #[deprecated(note = "use `into_output`, which doesn't panic")]
+    pub fn expect_output(self) -> (&'i [u8], &'s [u8]) {
+        self.into_output()
+            .expect("Expected the state to be State::HasOutput")
+    }
+
+    /// Returns the file that continues the stream, if the state carries one:
+    /// the `next_file` of `NextFile` and `NextArchive`, or the file of `NeedsInputOrEof`.
+    pub fn into_next_file(self) -> Option<F> {
+        use State::*;
+        match self {
+            NeedsInputOrEof(f) => Some(f),
+            NextFile { next_file, .. } | NextArchive { next_file, .. } => Some(next_file),
+            NeedsInput | HasOutput { .. } | EndOfFile | Stopped => None,
+        }
+    }
+
+    /// Whether the stream has ended or has been stopped. `NeedsInputOrEof`
+    /// isn't an end, as the stream continues if more input is provided.
+    pub fn is_end(&self) -> bool {
+        matches!(self, State::EndOfFile | State::Stopped)
+    }
+
+    /// Converts the file that the state carries, if any.
+    pub fn map_file<G>(self, map: impl FnOnce(F) -> G) -> State<'i, 's, G> {
+        use State::*;
+        match self {
+            NeedsInputOrEof(f) => NeedsInputOrEof(map(f)),
             NeedsInput => NeedsInput,
-            HasOutput { .. } => panic!("Assertion failed: self was HasOutput"),
+            HasOutput {
+                unparsed_input,
+                output,
+            } => HasOutput {
+                unparsed_input,
+                output,
+            },
             NextFile {
                 unparsed_input,
                 next_file,
             } => NextFile {
+                unparsed_input,
+                next_file: map(next_file),
+            },
+            NextArchive {
                 unparsed_input,
                 next_file,
+            } => NextArchive {
+                unparsed_input,
+                next_file: map(next_file),
             },
             EndOfFile => EndOfFile,
+            Stopped => Stopped,
         }
     }
 }
@@ -58,78 +292,849 @@ impl<'i, 's, File> std::fmt::Debug for State<'i, 's, File> {
             NeedsInput => writeln!(f, "State::NeedsInput"),
             HasOutput { .. } => writeln!(f, "State::HasOutput"),
             NextFile { .. } => writeln!(f, "State::NextFile"),
+            NextArchive { .. } => writeln!(f, "State::NextArchive"),
             EndOfFile => writeln!(f, "State::EndOfFile"),
+            Stopped => writeln!(f, "State::Stopped"),
+        }
+    }
+}
+
+pub enum ReadHeadersResult<'i> {
+    NeedsInput,
+    Done { unparsed: &'i [u8] },
+}
+
+impl<'i, 's> From<State<'i, 's, zip::ZipFile>> for State<'i, 's, File> {
+    fn from(from: State<'i, 's, zip::ZipFile>) -> State<'i, 's, File> {
+        from.map_file(File::from)
+    }
+}
+
+impl<'i, 's> From<State<'i, 's, gzip::GZipFile>> for State<'i, 's, File> {
+    fn from(from: State<'i, 's, gzip::GZipFile>) -> State<'i, 's, File> {
+        from.map_file(File::from)
+    }
+}
+
+impl<'i, 's> From<State<'i, 's, tar::TarGzFile>> for State<'i, 's, File> {
+    fn from(from: State<'i, 's, tar::TarGzFile>) -> State<'i, 's, File> {
+        from.map_file(File::from)
+    }
+}
+
+impl<'i, 's> From<State<'i, 's, zlib::ZlibFile>> for State<'i, 's, File> {
+    fn from(from: State<'i, 's, zlib::ZlibFile>) -> State<'i, 's, File> {
+        from.map_file(File::from)
+    }
+}
+
+impl<'i, 's> From<State<'i, 's, lzw::LzwFile>> for State<'i, 's, File> {
+    fn from(from: State<'i, 's, lzw::LzwFile>) -> State<'i, 's, File> {
+        from.map_file(File::from)
+    }
+}
+
+impl<'i, 's> From<State<'i, 's, cpio::CpioFile>> for State<'i, 's, File> {
+    fn from(from: State<'i, 's, cpio::CpioFile>) -> State<'i, 's, File> {
+        from.map_file(File::from)
+    }
+}
+
+impl<'i, 's> From<State<'i, 's, ar::DebFile>> for State<'i, 's, File> {
+    fn from(from: State<'i, 's, ar::DebFile>) -> State<'i, 's, File> {
+        from.map_file(File::from)
+    }
+}
+
+#[cfg(feature = "bzip2")]
+impl<'i, 's> From<State<'i, 's, bzip2::Bzip2File>> for State<'i, 's, File> {
+    fn from(from: State<'i, 's, bzip2::Bzip2File>) -> State<'i, 's, File> {
+        from.map_file(File::from)
+    }
+}
+
+#[cfg(feature = "xz")]
+impl<'i, 's> From<State<'i, 's, xz::XzFile>> for State<'i, 's, File> {
+    fn from(from: State<'i, 's, xz::XzFile>) -> State<'i, 's, File> {
+        from.map_file(File::from)
+    }
+}
+
+#[cfg(feature = "sevenz")]
+impl<'i, 's> From<State<'i, 's, sevenz::SevenZFile>> for State<'i, 's, File> {
+    fn from(from: State<'i, 's, sevenz::SevenZFile>) -> State<'i, 's, File> {
+        from.map_file(File::from)
+    }
+}
+
+/// Corresponds to a zipped, gzipped, zlib-wrapped, `compress`ed, bzip2 or xz-compressed
+/// file/stream, or to a file of a 7z archive. Can be in one of these states:
+/// not-yet-detected type, a zip file, a gzip file, an entry of a gzipped tar archive,
+/// a zlib stream, a `.Z` file, an entry of a cpio archive, a member of an ar archive or,
+/// with the `bzip2`, `xz` and `sevenz` features, a bzip2 file, an xz file or a file
+/// of a 7z archive.
+///
+/// Autodetected gzip streams are `TarGz`: if they contain a tar archive,
+/// each of its entries is a file of its own. Likewise, autodetected ar archives
+/// are `Ar`, whose members that are tar archives are read like those of a Debian package.
+/// Streams that start like an executable are read as self-extracting zip archives,
+/// skipping a stub of up to `zip::MAX_AUTODETECTED_STUB_LEN` bytes.
+#[allow(clippy::large_enum_variant)]
+pub enum File {
+    Zip(zip::ZipFile),
+    GZip(gzip::GZipFile),
+    TarGz(tar::TarGzFile),
+    Zlib(zlib::ZlibFile),
+    Lzw(lzw::LzwFile),
+    Cpio(cpio::CpioFile),
+    Ar(ar::DebFile),
+    #[cfg(feature = "bzip2")]
+    Bzip2(bzip2::Bzip2File),
+    #[cfg(feature = "xz")]
+    Xz(xz::XzFile),
+    #[cfg(feature = "sevenz")]
+    SevenZ(sevenz::SevenZFile),
+    /// Input that isn't in a known format, with `Options::passthrough_unknown`.
+    Passthrough(passthrough::PassthroughFile),
+    Init(Vec<u8>, Options),
+}
+
+/// Options for streams whose format is autodetected, set with the builder methods.
+/// See `start_stream_with`.
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    gzip: gzip::Options,
+    zip: zip::Options,
+    /// The hooks of both formats, set over those of `gzip` and `zip` when the stream starts.
+    hooks: Hooks,
+    /// The stop conditions of both formats, set like `hooks`.
+    stop: StopConditions,
+    output_buffer_size: Option<usize>,
+    max_entry_size: Option<u64>,
+    max_compression_ratio: Option<RatioLimit>,
+    max_filename_len: Option<usize>,
+    max_extra_fields_len: Option<usize>,
+    max_comment_len: Option<usize>,
+    truncate_long_fields: Option<bool>,
+    format: Option<Format>,
+    passthrough_unknown: bool,
+}
+
+impl Options {
+    /// Sets the options that gzip streams are parsed with. The settings of both
+    /// formats, like `Options::on_entry_start`, are applied over them when the stream
+    /// starts, whether they were set before or after this.
+    pub fn gzip(mut self, options: gzip::Options) -> Self {
+        self.gzip = options;
+        self
+    }
+
+    /// Sets the options that zip streams are parsed with. Self-extracting
+    /// archives are parsed with `zip::Options::scan_for_start` added to them.
+    /// The settings of both formats are applied over them like in `Options::gzip`.
+    pub fn zip(mut self, options: zip::Options) -> Self {
+        self.zip = options;
+        self
+    }
+
+    /// Starts the stream in `format` instead of autodetecting it. The first bytes
+    /// aren't buffered for the detection, and input in another format fails
+    /// with the errors of `format` right away. See `Format::from_extension`.
+    ///
+    /// The other options apply as they would to an autodetected stream of `format`:
+    /// `max_entry_size` and `max_compression_ratio` are honoured by every format,
+    /// but the hooks, the stop conditions, the field limits, `output_buffer_size`
+    /// and `truncate_long_fields` are only honoured by zip and gzip streams
+    /// (a tar.gz stream uses them for its gzip members), and ignored otherwise.
+    pub fn format(mut self, format: Format) -> Self {
+        self.format = Some(format);
+        self
+    }
+
+    /// Sets a hook that is called with the metadata of each zip entry and gzip member
+    /// once its header has been parsed, before any of its output is returned.
+    /// See `zip::Options::on_entry_start` and `gzip::Options::on_entry_start`.
+    ///
+    /// Only zip and gzip streams, including the gzip members of a tar.gz stream,
+    /// call the hook. It isn't called for the entries and members of the other
+    /// formats, such as cpio and ar archives, Debian packages and 7z archives.
+    pub fn on_entry_start(mut self, hook: impl Fn(&EntryMetadata) + Send + Sync + 'static) -> Self {
+        self.hooks.on_entry_start(Arc::new(hook));
+        self
+    }
+
+    /// Sets a hook that is called with the metadata, the sizes and the CRC-32 of
+    /// each zip entry and gzip member once it has ended and its data descriptor
+    /// or footer has been checked. See `zip::Options::on_entry_end`
+    /// and `gzip::Options::on_entry_end`.
+    ///
+    /// Like `on_entry_start`, the hook is only called by zip and gzip streams.
+    pub fn on_entry_end(
+        mut self,
+        hook: impl Fn(&EntryMetadata, &EntryTotals) + Send + Sync + 'static,
+    ) -> Self {
+        self.hooks.on_entry_end(Arc::new(hook));
+        self
+    }
+
+    /// Sets a hook that is called with the progress of the zip and gzip streams
+    /// each time another `interval` bytes of input have been consumed, so that
+    /// a progress bar can be updated without counting the input. The counters
+    /// are those of `ZipFile::progress` and `GZipFile::progress`.
+    pub fn on_progress(
+        mut self,
+        interval: u64,
+        hook: impl Fn(&Summary) + Send + Sync + 'static,
+    ) -> Self {
+        self.hooks.on_progress(interval, Arc::new(hook));
+        self
+    }
+
+    /// Stops the zip and gzip streams with `State::Stopped` once `entries` entries
+    /// or members have ended, instead of parsing the rest of the input.
+    pub fn stop_after_entries(mut self, entries: usize) -> Self {
+        self.stop.entries = Some(entries);
+        self
+    }
+
+    /// Stops the zip and gzip streams with `State::Stopped` once the entry
+    /// or member named `name` has ended, so that a single file can be looked up
+    /// without decompressing the rest of the stream.
+    pub fn stop_after_entry(mut self, name: impl Into<Vec<u8>>) -> Self {
+        self.stop.entry_name = Some(name.into());
+        self
+    }
+
+    /// Stops the zip and gzip streams with `State::Stopped` once they have output
+    /// `bytes` bytes. The output that reaches the limit is returned in full.
+    pub fn stop_after_bytes(mut self, bytes: u64) -> Self {
+        self.stop.uncompressed_bytes = Some(bytes);
+        self
+    }
+
+    /// Instead of failing with `Error::UnknownFileFormat`, passes input that isn't
+    /// in a known format through as it is, as the output of a single file without
+    /// a name. See `passthrough::PassthroughFile`. This allows reading a mix of
+    /// compressed and plain files the same way. Input that ends before its format
+    /// could be detected is passed through as well.
+    pub fn passthrough_unknown(mut self, enabled: bool) -> Self {
+        self.passthrough_unknown = enabled;
+        self
+    }
+
+    /// Limits the decompressed size of each file of the stream to `size` bytes:
+    /// of each zip entry, gzip member, cpio entry, ar member and 7z file, and of the
+    /// data of zlib, LZW, bzip2 and xz streams. In a Debian package, the limit applies
+    /// to the decompressed tar archives as well. See `zip::Options::max_entry_size`,
+    /// `gzip::Options::max_member_size` and the options of the other formats.
+    pub fn max_entry_size(mut self, size: u64) -> Self {
+        self.max_entry_size = Some(size);
+        self
+    }
+
+    /// Limits the ratio of the decompressed size of each file of the stream to its
+    /// compressed size, once it has decompressed to more than `min_size` bytes:
+    /// of each zip entry, gzip member and 7z folder, and of the data of zlib, LZW,
+    /// bzip2 and xz streams. In a Debian package, the limit applies to the compressed
+    /// tar archives. The entries of cpio and ar archives aren't compressed, so it
+    /// doesn't apply to them. See `zip::Options::max_compression_ratio` and the
+    /// options of the other formats.
+    pub fn max_compression_ratio(mut self, ratio: u64, min_size: u64) -> Self {
+        self.max_compression_ratio = Some(RatioLimit { ratio, min_size });
+        self
+    }
+
+    /// Limits the length of the filenames of zip entries and gzip members to `len`
+    /// bytes. See `zip::Options::max_filename_len` and `gzip::Options::max_filename_len`.
+    pub fn max_filename_len(mut self, len: usize) -> Self {
+        self.max_filename_len = Some(len);
+        self
+    }
+
+    /// Limits the total length of the extra fields of each zip header to `len` bytes.
+    /// See `zip::Options::max_extra_fields_len`.
+    pub fn max_extra_fields_len(mut self, len: usize) -> Self {
+        self.max_extra_fields_len = Some(len);
+        self
+    }
+
+    /// Limits the length of the comments of zip headers and gzip members to `len`
+    /// bytes. See `zip::Options::max_comment_len` and `gzip::Options::max_comment_len`.
+    pub fn max_comment_len(mut self, len: usize) -> Self {
+        self.max_comment_len = Some(len);
+        self
+    }
+
+    /// Truncates header fields of zip and gzip streams that are longer than their
+    /// limits instead of failing. See `zip::Options::truncate_long_fields`.
+    pub fn truncate_long_fields(mut self, enabled: bool) -> Self {
+        self.truncate_long_fields = Some(enabled);
+        self
+    }
+
+    /// Sets the size of the output buffer of the Deflate decompressor
+    /// of gzip and zip streams. See `zip::Options::output_buffer_size`.
+    pub fn output_buffer_size(mut self, size: usize) -> Self {
+        self.output_buffer_size = Some(size);
+        self
+    }
+
+    /// The options that gzip streams are started with: those set with `Options::gzip`,
+    /// with the settings of both formats applied over them.
+    fn gzip_options(&self) -> gzip::Options {
+        let mut gzip = self.gzip.clone();
+        gzip.hooks.set_from(&self.hooks);
+        gzip.stop.set_from(&self.stop);
+        if let Some(size) = self.output_buffer_size {
+            gzip = gzip.output_buffer_size(size);
+        }
+        if let Some(size) = self.max_entry_size {
+            gzip = gzip.max_member_size(size);
+        }
+        if let Some(RatioLimit { ratio, min_size }) = self.max_compression_ratio {
+            gzip = gzip.max_compression_ratio(ratio, min_size);
+        }
+        if let Some(len) = self.max_filename_len {
+            gzip = gzip.max_filename_len(len);
+        }
+        if let Some(len) = self.max_comment_len {
+            gzip = gzip.max_comment_len(len);
+        }
+        if let Some(enabled) = self.truncate_long_fields {
+            gzip = gzip.truncate_long_fields(enabled);
+        }
+        gzip
+    }
+
+    /// The options that zip streams are started with, like `Options::gzip_options`.
+    fn zip_options(&self) -> zip::Options {
+        let mut zip = self.zip.clone();
+        zip.hooks.set_from(&self.hooks);
+        zip.stop.set_from(&self.stop);
+        if let Some(size) = self.output_buffer_size {
+            zip = zip.output_buffer_size(size);
+        }
+        if let Some(size) = self.max_entry_size {
+            zip = zip.max_entry_size(size);
+        }
+        if let Some(RatioLimit { ratio, min_size }) = self.max_compression_ratio {
+            zip = zip.max_compression_ratio(ratio, min_size);
+        }
+        if let Some(len) = self.max_filename_len {
+            zip = zip.max_filename_len(len);
+        }
+        if let Some(len) = self.max_extra_fields_len {
+            zip = zip.max_extra_fields_len(len);
+        }
+        if let Some(len) = self.max_comment_len {
+            zip = zip.max_comment_len(len);
+        }
+        if let Some(enabled) = self.truncate_long_fields {
+            zip = zip.truncate_long_fields(enabled);
+        }
+        zip
+    }
+
+    /// The limits that the streams of the other formats are started with.
+    fn size_limits(&self) -> SizeLimits {
+        SizeLimits {
+            max_size: self.max_entry_size,
+            max_compression_ratio: self.max_compression_ratio,
+        }
+    }
+}
+
+/// The sizes and the checksum of an entry that has ended, passed to the hook
+/// set with `Options::on_entry_end`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct EntryTotals {
+    /// The size of the compressed data.
+    pub compressed_size: u64,
+    pub uncompressed_size: u64,
+    /// The CRC-32 of the data: the computed one if the data was decompressed,
+    /// and the one in the headers otherwise.
+    pub crc_32: u32,
+}
+
+type EntryStartHook = Arc<dyn Fn(&EntryMetadata) + Send + Sync>;
+type EntryEndHook = Arc<dyn Fn(&EntryMetadata, &EntryTotals) + Send + Sync>;
+type ProgressHook = Arc<dyn Fn(&Summary) + Send + Sync>;
+
+/// The hooks set with `Options::on_entry_start`, `Options::on_entry_end`
+/// and `Options::on_progress`, which are kept in the options of the formats
+/// that call them, and in `Options` until the stream starts.
+#[derive(Clone, Default)]
+pub(crate) struct Hooks {
+    entry_start: Option<EntryStartHook>,
+    entry_end: Option<EntryEndHook>,
+    /// The progress hook and the number of input bytes between its calls.
+    progress: Option<(u64, ProgressHook)>,
+}
+
+impl std::fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Hooks")
+            .field("entry_start", &self.entry_start.as_ref().map(|_| ".."))
+            .field("entry_end", &self.entry_end.as_ref().map(|_| ".."))
+            .field(
+                "progress",
+                &self.progress.as_ref().map(|(interval, _)| interval),
+            )
+            .finish()
+    }
+}
+
+/// Hooks are equal if they are the same closures.
+impl PartialEq for Hooks {
+    fn eq(&self, other: &Hooks) -> bool {
+        fn same<T: ?Sized>(a: &Option<Arc<T>>, b: &Option<Arc<T>>) -> bool {
+            match (a, b) {
+                (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+                (None, None) => true,
+                _ => false,
+            }
+        }
+        let same_progress = match (&self.progress, &other.progress) {
+            (Some((a_interval, a)), Some((b_interval, b))) => {
+                a_interval == b_interval && Arc::ptr_eq(a, b)
+            }
+            (None, None) => true,
+            _ => false,
+        };
+        same(&self.entry_start, &other.entry_start)
+            && same(&self.entry_end, &other.entry_end)
+            && same_progress
+    }
+}
+
+impl Eq for Hooks {}
+
+/// The conditions set with `Options::stop_after_entries`, `Options::stop_after_entry`
+/// and `Options::stop_after_bytes`, which are kept in the options of the formats
+/// that check them, and in `Options` until the stream starts.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub(crate) struct StopConditions {
+    pub(crate) entries: Option<usize>,
+    pub(crate) entry_name: Option<Vec<u8>>,
+    pub(crate) uncompressed_bytes: Option<u64>,
+}
+
+impl StopConditions {
+    /// Sets the conditions that are set in `other`, keeping the others.
+    fn set_from(&mut self, other: &StopConditions) {
+        if let Some(entries) = other.entries {
+            self.entries = Some(entries);
+        }
+        if let Some(name) = &other.entry_name {
+            self.entry_name = Some(name.clone());
+        }
+        if let Some(bytes) = other.uncompressed_bytes {
+            self.uncompressed_bytes = Some(bytes);
+        }
+    }
+
+    /// Whether the stream should stop with `progress`, after the entry named
+    /// `ended_entry` has ended if an entry ended in the latest step.
+    pub(crate) fn are_met(&self, progress: &Summary, ended_entry: Option<Option<&[u8]>>) -> bool {
+        self.entries
+            .is_some_and(|entries| progress.files >= entries)
+            || self
+                .uncompressed_bytes
+                .is_some_and(|bytes| progress.uncompressed_bytes >= bytes)
+            || match (&self.entry_name, ended_entry) {
+                (Some(name), Some(Some(ended))) => name[..] == *ended,
+                _ => false,
+            }
+    }
+}
+
+/// The most bytes of the input that an `ErrorContext` keeps.
+const ERROR_SNIPPET_LEN: usize = 16;
+
+/// Where a header that failed to parse was in the input, and what was there
+/// instead. Carried by the errors of headers that start with a magic number,
+/// such as `zip::ZipError::NotLocalFileHeader`.
+#[derive(Copy, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct ErrorContext {
+    offset: u64,
+    expected: &'static [u8],
+    found: [u8; ERROR_SNIPPET_LEN],
+    found_len: u8,
+}
+
+impl ErrorContext {
+    /// The context of a header that should start with `expected`
+    /// at the start of `input`.
+    pub(crate) fn new(expected: &'static [u8], input: &[u8]) -> ErrorContext {
+        let mut found = [0; ERROR_SNIPPET_LEN];
+        let found_len = input.len().min(ERROR_SNIPPET_LEN);
+        found[..found_len].copy_from_slice(&input[..found_len]);
+        ErrorContext {
+            offset: 0,
+            expected,
+            found,
+            found_len: found_len as u8,
+        }
+    }
+
+    /// The context of a header `by` bytes further in the input.
+    pub(crate) fn shifted(self, by: u64) -> ErrorContext {
+        ErrorContext {
+            offset: self.offset + by,
+            ..self
+        }
+    }
+
+    /// The offset of the header from the start of the stream. When an error is returned
+    /// by a function that parses a slice, such as `zip::peek_stream`, the offset is
+    /// from the start of the slice.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// The magic number, or signature, that the header should start with.
+    pub fn expected_magic(&self) -> &'static [u8] {
+        self.expected
+    }
+
+    /// The first bytes of the input at the offset, at most 16 of them. Fewer if the input
+    /// ended, or if the error was detected before more of it was read.
+    pub fn found(&self) -> &[u8] {
+        &self.found[..usize::from(self.found_len)]
+    }
+}
+
+/// Bytes formatted as space-separated hex, like `50 4b 03 04`.
+struct HexBytes<'a>(&'a [u8]);
+
+impl<'a> std::fmt::Debug for HexBytes<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        std::fmt::Display::fmt(self, f)
+    }
+}
+
+impl<'a> std::fmt::Display for HexBytes<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (i, byte) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for ErrorContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("ErrorContext")
+            .field("offset", &self.offset)
+            .field("expected", &HexBytes(self.expected))
+            .field("found", &HexBytes(self.found()))
+            .finish()
+    }
+}
+
+impl std::fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "at offset {}: expected {}, found {}",
+            self.offset,
+            HexBytes(self.expected),
+            HexBytes(self.found())
+        )
+    }
+}
+
+/// A variable-length field of a header, which is longer than its limit
+/// in a `FieldTooLong` error. See `Options::max_filename_len`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum HeaderField {
+    Filename,
+    /// The extra fields of a zip header, which are limited together.
+    ExtraFields,
+    Comment,
+}
+
+/// The limits set with `Options::max_filename_len`, `Options::max_extra_fields_len`
+/// and `Options::max_comment_len`, which are kept in the options of the formats
+/// that check them.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub(crate) struct FieldLimits {
+    pub(crate) filename: Option<usize>,
+    pub(crate) extra_fields: Option<usize>,
+    pub(crate) comment: Option<usize>,
+    /// Whether fields that are too long are truncated, rather than failing the stream.
+    pub(crate) truncate: bool,
+}
+
+impl FieldLimits {
+    pub(crate) fn limit(&self, field: HeaderField) -> Option<usize> {
+        match field {
+            HeaderField::Filename => self.filename,
+            HeaderField::ExtraFields => self.extra_fields,
+            HeaderField::Comment => self.comment,
+        }
+    }
+
+    /// Whether a field of `len` bytes fails the stream: it's too long,
+    /// and isn't truncated.
+    pub(crate) fn rejects(&self, field: HeaderField, len: usize) -> bool {
+        !self.truncate && self.limit(field).is_some_and(|limit| len > limit)
+    }
+
+    /// Truncates `value` to the limit of `field`, if it has one.
+    pub(crate) fn truncate(&self, field: HeaderField, value: &mut Vec<u8>) {
+        if let Some(limit) = self.limit(field) {
+            value.truncate(limit);
+        }
+    }
+}
+
+/// The limit set with `Options::max_compression_ratio`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub(crate) struct RatioLimit {
+    pub(crate) ratio: u64,
+    pub(crate) min_size: u64,
+}
+
+impl RatioLimit {
+    /// Whether `uncompressed` bytes of output from `compressed` bytes of input exceed the limit.
+    pub(crate) fn is_exceeded(&self, compressed: u64, uncompressed: u64) -> bool {
+        uncompressed > self.min_size && uncompressed / self.ratio.max(1) > compressed
+    }
+}
+
+/// The limits on the decompressed data of each file, kept in the options of
+/// the formats whose options have no other settings for it than these.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub(crate) struct SizeLimits {
+    pub(crate) max_size: Option<u64>,
+    pub(crate) max_compression_ratio: Option<RatioLimit>,
+}
+
+impl SizeLimits {
+    /// Whether `size` bytes of output exceed the limit on the size.
+    pub(crate) fn exceeds_size(&self, size: u64) -> bool {
+        self.max_size.is_some_and(|max| size > max)
+    }
+
+    /// Whether `uncompressed` bytes of output from `compressed` bytes of input
+    /// exceed the limit on the compression ratio.
+    pub(crate) fn exceeds_ratio(&self, compressed: u64, uncompressed: u64) -> bool {
+        self.max_compression_ratio
+            .is_some_and(|limit| limit.is_exceeded(compressed, uncompressed))
+    }
+}
+
+/// How far the hooks of an entry have been called.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub(crate) enum HookProgress {
+    #[default]
+    Pending,
+    Started,
+    Ended,
+}
+
+impl Hooks {
+    pub(crate) fn on_entry_start(&mut self, hook: EntryStartHook) {
+        self.entry_start = Some(hook);
+    }
+
+    pub(crate) fn on_entry_end(&mut self, hook: EntryEndHook) {
+        self.entry_end = Some(hook);
+    }
+
+    pub(crate) fn on_progress(&mut self, interval: u64, hook: ProgressHook) {
+        self.progress = Some((interval.max(1), hook));
+    }
+
+    /// Sets the hooks that are set in `other`, keeping the others.
+    fn set_from(&mut self, other: &Hooks) {
+        if let Some(hook) = &other.entry_start {
+            self.entry_start = Some(hook.clone());
+        }
+        if let Some(hook) = &other.entry_end {
+            self.entry_end = Some(hook.clone());
+        }
+        if let Some(progress) = &other.progress {
+            self.progress = Some(progress.clone());
+        }
+    }
+
+    /// Calls the progress hook if the input consumed has crossed a multiple
+    /// of its interval between `before` and `after`.
+    pub(crate) fn report_progress(&self, before: &Summary, after: &Summary) {
+        if let Some((interval, hook)) = &self.progress {
+            if before.compressed_bytes / interval != after.compressed_bytes / interval {
+                hook(after);
+            }
+        }
+    }
+
+    /// Calls the hooks of an entry that are due: the start once its header has been
+    /// parsed, and the end once it has ended with `totals`. The metadata is only
+    /// computed if a hook is called.
+    pub(crate) fn call(
+        &self,
+        progress: &mut HookProgress,
+        header_parsed: bool,
+        totals: Option<EntryTotals>,
+        metadata: impl Fn() -> EntryMetadata,
+    ) {
+        if self.entry_start.is_none() && self.entry_end.is_none() {
+            return;
+        }
+        if *progress == HookProgress::Pending && header_parsed {
+            *progress = HookProgress::Started;
+            if let Some(hook) = &self.entry_start {
+                hook(&metadata());
+            }
+        }
+        if let (HookProgress::Started, Some(totals)) = (*progress, totals) {
+            *progress = HookProgress::Ended;
+            if let Some(hook) = &self.entry_end {
+                hook(&metadata(), &totals);
+            }
         }
     }
 }
 
-pub enum ReadHeadersResult<'i> {
-    NeedsInput,
-    Done { unparsed: &'i [u8] },
+/// The metadata of an entry or a file, whatever its format. See `File::metadata`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct EntryMetadata {
+    pub name: Option<Vec<u8>>,
+    /// The uncompressed size, if it's known before the data, like in the headers
+    /// of tar archives. Zip entries only have it in the local file header if
+    /// the sizes aren't deferred to the data descriptor.
+    pub size_hint: Option<u64>,
+    /// The last modification time.
+    pub mtime: Option<SystemTime>,
+    pub is_dir: bool,
+    pub compression: Compression,
 }
 
-impl<'i, 's> From<State<'i, 's, zip::ZipFile>> for State<'i, 's, File> {
-    fn from(from: State<'i, 's, zip::ZipFile>) -> State<'i, 's, File> {
-        use State::*;
+/// How the data of an entry or a file is compressed in the stream.
+/// The entries of a compressed tar archive have the compression of the archive.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Compression {
+    /// The data isn't compressed.
+    None,
+    Deflate,
+    Deflate64,
+    Bzip2,
+    Lzma,
+    Xz,
+    Zstd,
+    Ppmd,
+    Lzw,
+    /// Another method, such as the legacy methods of zip and the coders of 7z,
+    /// or one that isn't known yet.
+    Other,
+}
 
-        match from {
-            NeedsInputOrEof(_) => unreachable!(
-                "Zip files have always directory at end so we know if we have reached the end."
-            ),
-            NeedsInput => NeedsInput,
-            HasOutput {
-                unparsed_input,
-                output,
-            } => HasOutput {
-                unparsed_input,
-                output,
-            },
-            NextFile {
-                unparsed_input,
-                next_file,
-            } => NextFile {
-                unparsed_input,
-                next_file: next_file.into(),
-            },
-            EndOfFile => EndOfFile,
-        }
-    }
+/// The format of a stream, as detected by `start_stream`. See `File::format`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Format {
+    Zip,
+    GZip,
+    /// A gzipped tar archive.
+    TarGz,
+    Zlib,
+    /// The `compress` format of `.Z` files.
+    Lzw,
+    Cpio,
+    /// An ar archive, such as a Debian package.
+    Ar,
+    #[cfg(feature = "bzip2")]
+    Bzip2,
+    #[cfg(feature = "xz")]
+    Xz,
+    #[cfg(feature = "sevenz")]
+    SevenZ,
+    /// Input that isn't compressed, passed through as it is.
+    Passthrough,
 }
 
-impl<'i, 's> From<State<'i, 's, gzip::GZipFile>> for State<'i, 's, File> {
-    fn from(from: State<'i, 's, gzip::GZipFile>) -> State<'i, 's, File> {
-        use State::*;
+impl Format {
+    /// Guesses the format from the extension of a file name, such as `"data.tar.gz"`,
+    /// or from an extension alone, such as `".gz"`. The case of the name is ignored.
+    /// Returns `None` for extensions of unknown formats.
+    pub fn from_extension(name: &str) -> Option<Format> {
+        use Format::*;
 
-        match from {
-            NeedsInputOrEof(file) => NeedsInputOrEof(file),
-            NeedsInput => NeedsInput,
-            HasOutput {
-                unparsed_input,
-                output,
-            } => HasOutput {
-                unparsed_input,
-                output,
-            },
-            NextFile {
-                unparsed_input,
-                next_file,
-            } => NextFile {
-                unparsed_input,
-                next_file: next_file.into(),
-            },
-            EndOfFile => EndOfFile,
+        let name = name.to_ascii_lowercase();
+        if name == "tar.gz" || name.ends_with(".tar.gz") {
+            return Some(TarGz);
         }
+        let extension = name.rsplit('.').next().unwrap_or_default();
+        Some(match extension {
+            "zip" => Zip,
+            "gz" => GZip,
+            "tgz" => TarGz,
+            "zz" | "zlib" => Zlib,
+            "z" => Lzw,
+            "cpio" => Cpio,
+            "a" | "ar" | "deb" => Ar,
+            #[cfg(feature = "bzip2")]
+            "bz2" => Bzip2,
+            #[cfg(feature = "xz")]
+            "xz" => Xz,
+            #[cfg(feature = "sevenz")]
+            "7z" => SevenZ,
+            _ => return None,
+        })
     }
 }
 
-/// Corresponds to a zipped or gzipped file/stream.
-/// Can be in one of three states: not-yet-detected type, a zip file or a gzip file.
-pub enum File {
-    Zip(zip::ZipFile),
-    GZip(gzip::GZipFile),
-    Init(Vec<u8>),
+impl std::fmt::Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        use Format::*;
+        let name = match self {
+            Zip => "zip",
+            GZip => "gzip",
+            TarGz => "tar.gz",
+            Zlib => "zlib",
+            Lzw => "compress",
+            Cpio => "cpio",
+            Ar => "ar",
+            #[cfg(feature = "bzip2")]
+            Bzip2 => "bzip2",
+            #[cfg(feature = "xz")]
+            Xz => "xz",
+            #[cfg(feature = "sevenz")]
+            SevenZ => "7z",
+            Passthrough => "passthrough",
+        };
+        f.write_str(name)
+    }
+}
+
+/// The compression of a zip entry. For entries encrypted with AES,
+/// it's the method in the AES extra field.
+fn zip_compression(header: &zip::headers::LocalFileHeader) -> Compression {
+    use zip::headers::CompressionMethod::*;
+
+    let method = match header.compression_method {
+        AesEncrypted => header
+            .aes()
+            .map_or(AesEncrypted, |field| field.compression_method),
+        method => method,
+    };
+    match method {
+        Stored => Compression::None,
+        Deflated => Compression::Deflate,
+        EnhancedDeflated => Compression::Deflate64,
+        Bzip2 => Compression::Bzip2,
+        Lzma => Compression::Lzma,
+        Zstd => Compression::Zstd,
+        PpmdVer1Rev1 => Compression::Ppmd,
+        _ => Compression::Other,
+    }
 }
 
+#[allow(clippy::large_enum_variant)]
 enum AutodetectResult {
     NeedsMoreData,
     UnknownFormat,
@@ -137,25 +1142,177 @@ enum AutodetectResult {
 }
 
 impl File {
+    /// The format of the stream, or `None` if it hasn't been detected yet.
+    /// A gzip stream is `Format::GZip` until its output shows that it
+    /// contains a tar archive, after which it's `Format::TarGz`.
+    pub fn format(&self) -> Option<Format> {
+        use File::*;
+
+        Some(match self {
+            Zip(_) => Format::Zip,
+            GZip(_) => Format::GZip,
+            TarGz(file) if file.is_tar() => Format::TarGz,
+            TarGz(_) => Format::GZip,
+            Zlib(_) => Format::Zlib,
+            Lzw(_) => Format::Lzw,
+            Cpio(_) => Format::Cpio,
+            Ar(_) => Format::Ar,
+            #[cfg(feature = "bzip2")]
+            Bzip2(_) => Format::Bzip2,
+            #[cfg(feature = "xz")]
+            Xz(_) => Format::Xz,
+            #[cfg(feature = "sevenz")]
+            SevenZ(_) => Format::SevenZ,
+            Passthrough(_) => Format::Passthrough,
+            Init(..) => return None,
+        })
+    }
+
+    /// The progress of the stream so far, counted from its start: the input
+    /// consumed, the output produced and the entries completed. Only zip and gzip
+    /// streams count their progress; for a tar.gz file, these are the counters
+    /// of the gzip stream around the tar archive.
+    pub fn progress(&self) -> Option<Summary> {
+        match self {
+            File::Zip(zip) => Some(zip.progress()),
+            File::GZip(gzip) => Some(gzip.progress()),
+            File::TarGz(file) => file.gzip_progress(),
+            _ => None,
+        }
+    }
+
     pub fn name(&self) -> Option<&[u8]> {
         use File::*;
 
         match self {
             Zip(zip) => zip.filename(),
             GZip(gzip) => gzip.filename(),
-            Init(_) => None,
+            TarGz(file) => file.filename(),
+            Zlib(_) => None,
+            Lzw(_) => None,
+            Cpio(file) => file.filename(),
+            Ar(file) => file.filename(),
+            #[cfg(feature = "bzip2")]
+            Bzip2(_) => None,
+            #[cfg(feature = "xz")]
+            Xz(_) => None,
+            #[cfg(feature = "sevenz")]
+            SevenZ(file) => file.filename(),
+            Passthrough(_) => None,
+            Init(..) => None,
         }
     }
 
-    /// Reads the fist 4 bytes of the input and tries to autodetect the stream format.
+    /// The modification time of the current entry or file, once its header
+    /// has been parsed, for formats that have one.
+    pub fn last_modified(&self) -> Option<SystemTime> {
+        use File::*;
+
+        let unix_time = |secs: u64| UNIX_EPOCH.checked_add(Duration::from_secs(secs));
+        match self {
+            Zip(zip) => zip.last_modified(),
+            GZip(gzip) => gzip.last_modified(),
+            TarGz(file) => file.last_modified(),
+            Cpio(file) => unix_time(u64::from(file.header()?.mtime)),
+            Ar(file) => file.last_modified(),
+            #[cfg(feature = "sevenz")]
+            SevenZ(file) => zip::headers::windows_time(file.entry()?.modified?),
+            _ => None,
+        }
+    }
+
+    /// The metadata of the current entry or file, in the same shape for every format.
+    /// What isn't known yet, such as the name before the header has been parsed,
+    /// or what the format doesn't have, is left empty.
+    pub fn metadata(&self) -> EntryMetadata {
+        use File::*;
+
+        let (size_hint, compression) = match self {
+            Zip(zip) => return CompressedStream::metadata(zip),
+            GZip(gzip) => return CompressedStream::metadata(gzip),
+            Zlib(_) => (None, Compression::Deflate),
+            TarGz(file) => (file.size(), Compression::Deflate),
+            Lzw(_) => (None, Compression::Lzw),
+            Cpio(file) => (
+                file.header().map(|header| u64::from(header.size)),
+                Compression::None,
+            ),
+            Ar(file) => (file.size(), file.compression()),
+            #[cfg(feature = "bzip2")]
+            Bzip2(_) => (None, Compression::Bzip2),
+            #[cfg(feature = "xz")]
+            Xz(_) => (None, Compression::Xz),
+            #[cfg(feature = "sevenz")]
+            SevenZ(file) => (file.entry().map(|entry| entry.size), Compression::Other),
+            Passthrough(_) => (None, Compression::None),
+            Init(..) => (None, Compression::Other),
+        };
+        EntryMetadata {
+            name: self.name().map(<[u8]>::to_vec),
+            size_hint,
+            mtime: self.last_modified(),
+            is_dir: self.is_dir(),
+            compression,
+        }
+    }
+
+    /// The index of the current entry in its archive, counting from 0.
+    /// Only zip streams track it; see `ZipFile::entry_index`.
+    pub fn entry_index(&self) -> Option<u64> {
+        match self {
+            File::Zip(zip) => Some(zip.entry_index()),
+            _ => None,
+        }
+    }
+
+    /// Whether the current entry is a directory, which has no content,
+    /// once its header has been parsed. Streams of formats without directories
+    /// never are.
+    pub fn is_dir(&self) -> bool {
+        use File::*;
+
+        let is_tar_dir =
+            |header: &tar::headers::Header| header.entry_type == tar::headers::EntryType::Directory;
+        match self {
+            Zip(zip) => zip.is_dir(),
+            TarGz(file) => file.header().is_some_and(is_tar_dir),
+            Cpio(file) => file
+                .header()
+                .is_some_and(|header| header.mode & 0o170000 == 0o040000),
+            Ar(file) => file.tar_header().is_some_and(is_tar_dir),
+            #[cfg(feature = "sevenz")]
+            SevenZ(file) => file.entry().is_some_and(|entry| entry.is_dir),
+            _ => false,
+        }
+    }
+
+    /// Called by `archive::Core` when the input has ended where the stream didn't say
+    /// it may end. Returns whether the stream passes its input through, in which
+    /// case it ends there, once the input that was read to detect the format
+    /// has been returned by reading empty input.
+    pub(crate) fn passes_through_at_eof(&mut self) -> bool {
+        if let File::Init(unparsed, options) = self {
+            if options.passthrough_unknown {
+                *self = File::Passthrough(passthrough::start_stream_with_prefix(unparsed));
+            }
+        }
+        matches!(self, File::Passthrough(_))
+    }
+
+    /// Reads the first 6 bytes of the input and tries to autodetect the stream format.
     /// Consumes and retains the amount of bytes read from input in `unparsed` buffer.
     /// Once the detection succeeds, constructs a stream object of the detected format
-    /// and feeds it the consumed first bytes.
+    /// that holds the consumed first bytes, to be parsed with the rest of the input.
     /// In case where there is no enough data for detection,
     /// it consumes the input it can and returns,
     /// expecting to be called again with more data.
-    fn autodetect_format(unparsed: &mut Vec<u8>, input: &mut &[u8]) -> AutodetectResult {
-        const NEEDED_BYTES: usize = 4;
+    fn autodetect_format(
+        unparsed: &mut Vec<u8>,
+        options: &Options,
+        input: &mut &[u8],
+    ) -> AutodetectResult {
+        // The longest magic numbers, those of xz and 7z, are 6 bytes.
+        const NEEDED_BYTES: usize = 6;
         if unparsed.len() + input.len() < NEEDED_BYTES {
             unparsed.extend_from_slice(input);
             *input = &[][..];
@@ -168,36 +1325,125 @@ impl File {
         }
 
         // Bytes needed for detection are now in `unparsed`!
+        let limits = options.size_limits();
 
-        // Start a stream according to a detected stream type
-        // and feed in the first bytes
-        // that where used for detection.
+        // Start a stream according to a detected stream type.
+        // The bytes that were used for detection may already contain
+        // compressed data, so they are parsed together with the rest
+        // of the input instead of being fed in separately.
         if unparsed.starts_with(b"\x50\x4b\x03\x04") {
-            let mut stream = zip::start_stream();
-            stream
-                .read(unparsed)
-                .expect("No errors will happen with the 4 first input bytes.");
-            AutodetectResult::Detected(File::Zip(stream))
+            AutodetectResult::Detected(File::Zip(zip::start_stream_with_options_and_prefix(
+                options.zip_options(),
+                unparsed,
+            )))
         } else if unparsed.starts_with(b"\x1f\x8b\x08") {
-            let mut stream = gzip::start_stream();
-            stream
-                .read(unparsed)
-                .expect("No errors will happen with the 4 first input bytes.");
-            AutodetectResult::Detected(File::GZip(stream))
+            AutodetectResult::Detected(File::TarGz(
+                tar::start_tar_gz_stream_with_options_and_prefix(options.gzip_options(), unparsed),
+            ))
+        } else if lzw::is_lzw_header(unparsed) {
+            let lzw_options = lzw::Options { limits };
+            AutodetectResult::Detected(File::Lzw(lzw::start_stream_with_options_and_prefix(
+                lzw_options,
+                unparsed,
+            )))
+        } else if cpio::headers::is_cpio_header(unparsed) {
+            let cpio_options = cpio::Options { limits };
+            AutodetectResult::Detected(File::Cpio(cpio::start_stream_with_options_and_prefix(
+                cpio_options,
+                unparsed,
+            )))
+        } else if ar::headers::is_ar_header(unparsed) {
+            let ar_options = ar::Options { limits };
+            AutodetectResult::Detected(File::Ar(ar::start_deb_stream_with_options_and_prefix(
+                ar_options, unparsed,
+            )))
+        } else if let Some(file) = Self::detect_optional_format(unparsed, limits) {
+            AutodetectResult::Detected(file)
+        } else if zip::is_executable_header(unparsed) {
+            // Possibly a self-extracting zip archive.
+            let options = options
+                .zip_options()
+                .scan_for_start(zip::MAX_AUTODETECTED_STUB_LEN);
+            AutodetectResult::Detected(File::Zip(zip::start_stream_with_options_and_prefix(
+                options, unparsed,
+            )))
+        } else if zlib::headers::is_zlib_header(unparsed) {
+            // The zlib header has no magic number, just a checksum,
+            // so it's tried last.
+            let zlib_options = zlib::Options { limits };
+            AutodetectResult::Detected(File::Zlib(zlib::start_stream_with_options_and_prefix(
+                zlib_options,
+                unparsed,
+            )))
+        } else if options.passthrough_unknown {
+            AutodetectResult::Detected(File::Passthrough(passthrough::start_stream_with_prefix(
+                unparsed,
+            )))
         } else {
-            return AutodetectResult::UnknownFormat;
+            AutodetectResult::UnknownFormat
+        }
+    }
+
+    /// Detects the formats whose support is enabled with a crate feature.
+    #[allow(unused_variables)]
+    fn detect_optional_format(unparsed: &[u8], limits: SizeLimits) -> Option<File> {
+        #[cfg(feature = "bzip2")]
+        {
+            if bzip2::is_bzip2_header(unparsed) {
+                let options = bzip2::Options { limits };
+                return Some(File::Bzip2(bzip2::start_stream_with_options_and_prefix(
+                    options, unparsed,
+                )));
+            }
+        }
+        #[cfg(feature = "xz")]
+        {
+            if xz::is_xz_header(unparsed) {
+                let options = xz::Options { limits };
+                return Some(File::Xz(xz::start_stream_with_options_and_prefix(
+                    options, unparsed,
+                )));
+            }
+        }
+        #[cfg(feature = "sevenz")]
+        {
+            if sevenz::headers::is_7z_header(unparsed) {
+                let options = sevenz::Options { limits };
+                return Some(File::SevenZ(sevenz::start_stream_with_options_and_prefix(
+                    options, unparsed,
+                )));
+            }
         }
+        None
     }
 
+    /// The output of the last `State::HasOutput`. Before the format
+    /// has been detected there's no output, so it's empty.
     pub fn get_output(&self) -> &[u8] {
         use File::*;
         match self {
             Zip(file) => file.get_output(),
             GZip(file) => file.get_output(),
-            Init(file) => panic!("This shouldn't be called before autodetect!"),
+            TarGz(file) => file.get_output(),
+            Zlib(file) => file.get_output(),
+            Lzw(file) => file.get_output(),
+            Cpio(file) => file.get_output(),
+            Ar(file) => file.get_output(),
+            #[cfg(feature = "bzip2")]
+            Bzip2(file) => file.get_output(),
+            #[cfg(feature = "xz")]
+            Xz(file) => file.get_output(),
+            #[cfg(feature = "sevenz")]
+            SevenZ(file) => file.get_output(),
+            Passthrough(file) => file.get_output(),
+            Init(..) => &[],
         }
     }
 
+    /// Reads the headers of a zip entry without reading its data, so that its
+    /// metadata is available before the first output. Streams of the other formats
+    /// fail with `Error::ReadHeadersUnsupported` once their format is detected,
+    /// and plain data passes through.
     pub fn read_headers<'i>(
         &mut self,
         mut input: &'i [u8],
@@ -205,9 +1451,9 @@ impl File {
         use File::*;
 
         // Format detection will run only when the stream has started (the Init state)
-        if let Init(ref mut unparsed) = self {
+        if let Init(ref mut unparsed, ref options) = self {
             // Set self to the corresponding format
-            *self = match Self::autodetect_format(unparsed, &mut input) {
+            *self = match Self::autodetect_format(unparsed, options, &mut input) {
                 AutodetectResult::NeedsMoreData => return Ok(ReadHeadersResult::NeedsInput),
                 AutodetectResult::UnknownFormat => return Err(Error::UnknownFileFormat),
                 AutodetectResult::Detected(file) => file,
@@ -215,9 +1461,18 @@ impl File {
         };
 
         match self {
-            Zip(ref mut file) => Ok(file.read_headers(input)?.into()),
-            GZip(ref mut file) => unimplemented!("TODO"),
-            Init(_) => {
+            Zip(ref mut file) => Ok(file.read_headers(input)?),
+            GZip(_) | TarGz(_) | Zlib(_) | Lzw(_) | Cpio(_) | Ar(_) => {
+                Err(Error::ReadHeadersUnsupported)
+            }
+            #[cfg(feature = "bzip2")]
+            Bzip2(_) => Err(Error::ReadHeadersUnsupported),
+            #[cfg(feature = "xz")]
+            Xz(_) => Err(Error::ReadHeadersUnsupported),
+            #[cfg(feature = "sevenz")]
+            SevenZ(_) => Err(Error::ReadHeadersUnsupported),
+            Passthrough(_) => Ok(ReadHeadersResult::Done { unparsed: input }),
+            Init(..) => {
                 unreachable!("The File::Init state is never set after autodetect has succeeded.")
             }
         }
@@ -229,27 +1484,140 @@ impl File {
         mut callback: impl FnMut(&[u8]),
     ) -> Result<State<'i, 'i, File>, Error> {
         loop {
-            let state = self.read(input)?;
-            if let State::HasOutput {
-                unparsed_input,
-                output,
-            } = state
+            match self.read(input)?.try_no_output() {
+                Err((unparsed_input, output)) => {
+                    input = unparsed_input;
+                    callback(output);
+                }
+                Ok(state) => return Ok(state),
+            }
+        }
+    }
+
+    /// Like `read_internal_iter`, but `callback` may fail, which stops
+    /// the reading and returns its error as `TryReadError::Callback`.
+    pub fn try_read_internal_iter<'i, E>(
+        &mut self,
+        input: &'i [u8],
+        callback: impl FnMut(&[u8]) -> Result<(), E>,
+    ) -> Result<State<'i, 'i, File>, TryReadError<Error, E>> {
+        try_read_with(self, input, callback)
+    }
+
+    /// Like `read_internal_iter`, but continues with the next file on
+    /// `State::NextFile` and `State::NextArchive`, replacing `self` with it,
+    /// and passes the file that the output belongs to to `callback` with it,
+    /// so that its name, index or metadata can be looked up.
+    pub fn read_entries<'i>(
+        &mut self,
+        mut input: &'i [u8],
+        mut callback: impl FnMut(&File, &[u8]),
+    ) -> Result<State<'i, 'i, File>, Error> {
+        loop {
+            let unparsed_len = match self.read(input)?.try_no_output() {
+                Err((unparsed_input, _)) => unparsed_input.len(),
+                Ok(State::NextFile {
+                    unparsed_input,
+                    next_file,
+                })
+                | Ok(State::NextArchive {
+                    unparsed_input,
+                    next_file,
+                }) => {
+                    *self = next_file;
+                    input = unparsed_input;
+                    continue;
+                }
+                Ok(state) => return Ok(state),
+            };
+            // The output is looked up again, as the state borrows `self` mutably.
+            callback(self, self.get_output());
+            input = &input[input.len() - unparsed_len..];
+        }
+    }
+
+    /// Like `read_internal_iter`, but writes all of the output to `writer`.
+    /// Errors of the writer are returned as they are, and errors of the stream
+    /// as `std::io::ErrorKind::InvalidData` errors that wrap the `Error`.
+    pub fn copy_to<'i>(
+        &mut self,
+        mut input: &'i [u8],
+        writer: &mut impl std::io::Write,
+    ) -> std::io::Result<State<'i, 'i, File>> {
+        loop {
+            match self
+                .read(input)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?
+                .try_no_output()
             {
-                input = unparsed_input;
-                callback(output);
-            } else {
-                return Ok(state.assert_no_output());
+                Err((unparsed_input, output)) => {
+                    input = unparsed_input;
+                    writer.write_all(output)?;
+                }
+                Ok(state) => return Ok(state),
+            }
+        }
+    }
+
+    /// Signals that the input has ended. Zip and gzip streams, including gzipped
+    /// tar archives, return an error unless they may end where they are,
+    /// like `ZipFile::finish` and `GZipFile::finish`.
+    /// The streams of the other formats can't tell, so the input may only end
+    /// where they returned `State::NeedsInputOrEof` or `State::EndOfFile`.
+    /// A stream whose format hasn't been detected yet can't end.
+    pub fn finish(&self) -> Result<(), Error> {
+        match self {
+            File::Zip(zip) => Ok(zip.finish()?),
+            File::GZip(gzip) => Ok(gzip.finish()?),
+            File::TarGz(file) => file.finish(),
+            File::Init(..) => Err(Error::UnknownFileFormat),
+            _ => Ok(()),
+        }
+    }
+
+    /// Drives the stream to completion with input read from `reader`, passing the
+    /// entries and their decompressed output to `callback` as `Event`s. The input
+    /// is read into an internal buffer, and the stream ends like in `drive`.
+    pub fn read_from(
+        self,
+        reader: &mut impl std::io::Read,
+        mut callback: impl FnMut(&Event),
+    ) -> Result<Summary, DriveError> {
+        let mut compressed_bytes = 0;
+        let mut core = archive::Core::from_file(self);
+        let mut read = |buf: &mut [u8]| {
+            let result = archive::read_blocking(reader, buf);
+            if let std::task::Poll::Ready(Ok(len)) = result {
+                compressed_bytes += len as u64;
+            }
+            result
+        };
+        let mut files = 0;
+        let mut uncompressed_bytes = 0;
+        while archive::ready(core.poll_next_entry(&mut read))? {
+            callback(&Event::EntryStart(core.metadata()));
+            while archive::ready(core.poll_fill(&mut read))? {
+                let output = core.take_pending();
+                uncompressed_bytes += output.len() as u64;
+                callback(&Event::Data(output));
             }
+            files += 1;
+            callback(&Event::EntryEnd);
         }
+        Ok(Summary {
+            files,
+            compressed_bytes,
+            uncompressed_bytes,
+        })
     }
 
     pub fn read<'i, 's>(&'s mut self, mut input: &'i [u8]) -> Result<State<'i, 's, File>, Error> {
         use File::*;
 
         // Format detection will run only when the stream has started (the Init state)
-        if let Init(ref mut unparsed) = self {
+        if let Init(ref mut unparsed, ref options) = self {
             // Set self to the corresponding format
-            *self = match Self::autodetect_format(unparsed, &mut input) {
+            *self = match Self::autodetect_format(unparsed, options, &mut input) {
                 AutodetectResult::NeedsMoreData => return Ok(State::NeedsInput),
                 AutodetectResult::UnknownFormat => return Err(Error::UnknownFileFormat),
                 AutodetectResult::Detected(file) => file,
@@ -259,13 +1627,45 @@ impl File {
         match self {
             Zip(ref mut file) => Ok(file.read(input)?.into()),
             GZip(ref mut file) => Ok(file.read(input)?.into()),
-            Init(_) => {
+            TarGz(ref mut file) => Ok(file.read(input)?.into()),
+            Zlib(ref mut file) => Ok(file.read(input)?.into()),
+            Lzw(ref mut file) => Ok(file.read(input)?.into()),
+            Cpio(ref mut file) => Ok(file.read(input)?.into()),
+            Ar(ref mut file) => Ok(file.read(input)?.into()),
+            #[cfg(feature = "bzip2")]
+            Bzip2(ref mut file) => Ok(file.read(input)?.into()),
+            #[cfg(feature = "xz")]
+            Xz(ref mut file) => Ok(file.read(input)?.into()),
+            #[cfg(feature = "sevenz")]
+            SevenZ(ref mut file) => Ok(file.read(input)?.into()),
+            Passthrough(ref mut file) => Ok(file.read(input).map_file(Passthrough)),
+            Init(..) => {
                 unreachable!("The File::Init state is never set after autodetect has succeeded.")
             }
         }
     }
 }
 
+impl CompressedStream for File {
+    type Error = Error;
+
+    fn read<'i, 's>(&'s mut self, input: &'i [u8]) -> Result<State<'i, 's, File>, Error> {
+        File::read(self, input)
+    }
+
+    fn get_output(&self) -> &[u8] {
+        File::get_output(self)
+    }
+
+    fn metadata(&self) -> EntryMetadata {
+        File::metadata(self)
+    }
+
+    fn finish(&self) -> Result<(), Error> {
+        File::finish(self)
+    }
+}
+
 impl From<zip::ZipFile> for File {
     fn from(f: zip::ZipFile) -> File {
         File::Zip(f)
@@ -278,6 +1678,57 @@ impl From<gzip::GZipFile> for File {
     }
 }
 
+impl From<tar::TarGzFile> for File {
+    fn from(f: tar::TarGzFile) -> File {
+        File::TarGz(f)
+    }
+}
+
+impl From<zlib::ZlibFile> for File {
+    fn from(f: zlib::ZlibFile) -> File {
+        File::Zlib(f)
+    }
+}
+
+impl From<lzw::LzwFile> for File {
+    fn from(f: lzw::LzwFile) -> File {
+        File::Lzw(f)
+    }
+}
+
+impl From<cpio::CpioFile> for File {
+    fn from(f: cpio::CpioFile) -> File {
+        File::Cpio(f)
+    }
+}
+
+impl From<ar::DebFile> for File {
+    fn from(f: ar::DebFile) -> File {
+        File::Ar(f)
+    }
+}
+
+#[cfg(feature = "bzip2")]
+impl From<bzip2::Bzip2File> for File {
+    fn from(f: bzip2::Bzip2File) -> File {
+        File::Bzip2(f)
+    }
+}
+
+#[cfg(feature = "xz")]
+impl From<xz::XzFile> for File {
+    fn from(f: xz::XzFile) -> File {
+        File::Xz(f)
+    }
+}
+
+#[cfg(feature = "sevenz")]
+impl From<sevenz::SevenZFile> for File {
+    fn from(f: sevenz::SevenZFile) -> File {
+        File::SevenZ(f)
+    }
+}
+
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         use Error::*;
@@ -290,7 +1741,46 @@ impl std::fmt::Display for Error {
                 write!(f, "gzip error:")?;
                 e.fmt(f)?
             }
-            UnknownFileFormat => write!(f, "no known fileformat (zip or gzip) detected")?,
+            Tar(e) => {
+                write!(f, "tar error:")?;
+                e.fmt(f)?
+            }
+            Zlib(e) => {
+                write!(f, "zlib error:")?;
+                e.fmt(f)?
+            }
+            Lzw(e) => {
+                write!(f, "LZW error:")?;
+                e.fmt(f)?
+            }
+            Cpio(e) => {
+                write!(f, "cpio error:")?;
+                e.fmt(f)?
+            }
+            Ar(e) => {
+                write!(f, "ar error:")?;
+                e.fmt(f)?
+            }
+            #[cfg(feature = "bzip2")]
+            Bzip2(e) => {
+                write!(f, "bzip2 error:")?;
+                e.fmt(f)?
+            }
+            #[cfg(feature = "xz")]
+            Xz(e) => {
+                write!(f, "xz error:")?;
+                e.fmt(f)?
+            }
+            #[cfg(feature = "sevenz")]
+            SevenZ(e) => {
+                write!(f, "7z error:")?;
+                e.fmt(f)?
+            }
+            UnknownFileFormat => write!(f, "no known fileformat detected")?,
+            ReadHeadersUnsupported => write!(
+                f,
+                "reading only the headers isn't supported for this format"
+            )?,
         }
         Ok(())
     }
@@ -301,19 +1791,43 @@ impl std::error::Error for Error {
         match self {
             Self::Zip(e) => Some(e),
             Self::GZip(e) => Some(e),
-            Self::UnknownFileFormat => None,
+            Self::Tar(e) => Some(e),
+            Self::Zlib(e) => Some(e),
+            Self::Lzw(e) => Some(e),
+            Self::Cpio(e) => Some(e),
+            Self::Ar(e) => Some(e),
+            #[cfg(feature = "bzip2")]
+            Self::Bzip2(e) => Some(e),
+            #[cfg(feature = "xz")]
+            Self::Xz(e) => Some(e),
+            #[cfg(feature = "sevenz")]
+            Self::SevenZ(e) => Some(e),
+            Self::UnknownFileFormat | Self::ReadHeadersUnsupported => None,
         }
     }
 }
 
-/// An error type that delegates to ZipError or GzipError.
-/// In case the file format detection fails, there's a third
+/// An error type that delegates to the error type of each format.
+/// In case the file format detection fails, there's another
 /// error state for that.
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub enum Error {
     Zip(zip::ZipError),
     GZip(gzip::GZipError),
+    Tar(tar::TarError),
+    Zlib(zlib::ZlibError),
+    Lzw(lzw::LzwError),
+    Cpio(cpio::CpioError),
+    Ar(ar::ArError),
+    #[cfg(feature = "bzip2")]
+    Bzip2(bzip2::Bzip2Error),
+    #[cfg(feature = "xz")]
+    Xz(xz::XzError),
+    #[cfg(feature = "sevenz")]
+    SevenZ(sevenz::SevenZError),
     UnknownFileFormat,
+    /// `File::read_headers` was called on a stream of a format other than zip.
+    ReadHeadersUnsupported,
 }
 
 impl From<zip::ZipError> for Error {
@@ -328,11 +1842,247 @@ impl From<gzip::GZipError> for Error {
     }
 }
 
+impl From<tar::TarError> for Error {
+    fn from(err: tar::TarError) -> Error {
+        Error::Tar(err)
+    }
+}
+
+impl From<zlib::ZlibError> for Error {
+    fn from(err: zlib::ZlibError) -> Error {
+        Error::Zlib(err)
+    }
+}
+
+impl From<lzw::LzwError> for Error {
+    fn from(err: lzw::LzwError) -> Error {
+        Error::Lzw(err)
+    }
+}
+
+impl From<cpio::CpioError> for Error {
+    fn from(err: cpio::CpioError) -> Error {
+        Error::Cpio(err)
+    }
+}
+
+impl From<ar::ArError> for Error {
+    fn from(err: ar::ArError) -> Error {
+        Error::Ar(err)
+    }
+}
+
+#[cfg(feature = "bzip2")]
+impl From<bzip2::Bzip2Error> for Error {
+    fn from(err: bzip2::Bzip2Error) -> Error {
+        Error::Bzip2(err)
+    }
+}
+
+#[cfg(feature = "xz")]
+impl From<xz::XzError> for Error {
+    fn from(err: xz::XzError) -> Error {
+        Error::Xz(err)
+    }
+}
+
+#[cfg(feature = "sevenz")]
+impl From<sevenz::SevenZError> for Error {
+    fn from(err: sevenz::SevenZError) -> Error {
+        Error::SevenZ(err)
+    }
+}
+
 /// Initialises a File that starts in a state that is agnostic
 /// about the whether the input
-/// stream is in zip format or gzip format.
+/// stream is in zip, gzip, zlib, `compress`, cpio, ar, bzip2, xz or 7z format.
 /// Use this function to initialise the stream if you want to
 /// auto-detect the input format.
 pub fn start_stream() -> File {
-    File::Init(Vec::new())
+    start_stream_with(Options::default())
+}
+
+/// Starts a stream whose format is autodetected, and that is parsed according to `options`.
+/// If the format is set with `Options::format`, the stream is started in that format.
+pub fn start_stream_with(options: Options) -> File {
+    use Format::*;
+
+    let format = match options.format {
+        Some(format) => format,
+        None => return File::Init(Vec::new(), options),
+    };
+    let limits = options.size_limits();
+    match format {
+        Zip => File::Zip(zip::start_stream_with(options.zip_options())),
+        GZip => File::GZip(gzip::start_stream_with(options.gzip_options())),
+        TarGz => File::TarGz(tar::start_tar_gz_stream_with(options.gzip_options())),
+        Zlib => File::Zlib(zlib::start_stream_with(zlib::Options { limits })),
+        Lzw => File::Lzw(lzw::start_stream_with(lzw::Options { limits })),
+        Cpio => File::Cpio(cpio::start_stream_with(cpio::Options { limits })),
+        Ar => File::Ar(ar::start_deb_stream_with(ar::Options { limits })),
+        #[cfg(feature = "bzip2")]
+        Bzip2 => File::Bzip2(bzip2::start_stream_with(bzip2::Options { limits })),
+        #[cfg(feature = "xz")]
+        Xz => File::Xz(xz::start_stream_with(xz::Options { limits })),
+        #[cfg(feature = "sevenz")]
+        SevenZ => File::SevenZ(sevenz::start_stream_with(sevenz::Options { limits })),
+        Passthrough => File::Passthrough(passthrough::start_stream()),
+    }
+}
+
+/// Starts a stream in `format`, skipping the autodetection. See `Options::format`.
+pub fn start_stream_with_format(format: Format) -> File {
+    start_stream_with(Options::default().format(format))
+}
+
+/// Totals of a stream processed by `drive`, `File::read_from` or `extract::extract_to_dir`,
+/// or the progress of a stream so far. See `File::progress`.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct Summary {
+    /// The number of files (zip entries or gzip members) that were completed.
+    pub files: usize,
+    /// The number of bytes read from the reader, or consumed by the stream.
+    pub compressed_bytes: u64,
+    /// The number of decompressed bytes passed to the sink, or output by the stream.
+    pub uncompressed_bytes: u64,
+}
+
+/// An event of a stream that is driven by `File::read_from`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Event<'a> {
+    /// An entry starts. Its metadata is as it was when its first output
+    /// was decompressed, or when it ended if it has no content.
+    EntryStart(&'a EntryMetadata),
+    /// Decompressed content of the current entry.
+    Data(&'a [u8]),
+    /// The current entry ended.
+    EntryEnd,
+}
+
+/// An error returned by `drive` and `archive::Archive`: either the reader or the sink
+/// failed, or the stream itself was invalid.
+#[derive(Debug)]
+pub enum DriveError {
+    Io(std::io::Error),
+    Stream(Error),
+}
+
+impl std::fmt::Display for DriveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DriveError::Io(e) => write!(f, "i/o error: {}", e),
+            DriveError::Stream(e) => write!(f, "stream error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for DriveError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DriveError::Io(e) => Some(e),
+            DriveError::Stream(e) => Some(e),
+        }
+    }
+}
+
+impl From<std::io::Error> for DriveError {
+    fn from(err: std::io::Error) -> DriveError {
+        DriveError::Io(err)
+    }
+}
+
+impl From<Error> for DriveError {
+    fn from(err: Error) -> DriveError {
+        DriveError::Stream(err)
+    }
+}
+
+/// Errors of the stream become `std::io::ErrorKind::InvalidData` errors.
+impl From<DriveError> for std::io::Error {
+    fn from(err: DriveError) -> std::io::Error {
+        match err {
+            DriveError::Io(e) => e,
+            DriveError::Stream(e) => std::io::Error::new(std::io::ErrorKind::InvalidData, e),
+        }
+    }
+}
+
+/// Decompresses everything `reader` provides, autodetecting the format,
+/// and passes the decompressed output of every file to `sink`.
+/// The input is read in chunks into `buf`, which is the only input
+/// buffer used, so its size decides how much is read at a time.
+/// Reading stops when the end of the archive is reached or
+/// when the reader runs out of input at a point where the stream may end.
+/// Running out of input anywhere else is reported as an `UnexpectedEof` error,
+/// or, in the middle of a zip archive, as `zip::ZipError::TruncatedArchive`.
+pub fn drive<R: std::io::Read>(
+    mut reader: R,
+    buf: &mut [u8],
+    mut sink: impl FnMut(&[u8]) -> std::io::Result<()>,
+) -> Result<Summary, DriveError> {
+    use std::io::ErrorKind;
+
+    if buf.is_empty() {
+        return Err(std::io::Error::new(ErrorKind::InvalidInput, "empty input buffer").into());
+    }
+
+    let mut summary = Summary::default();
+    let mut file = start_stream();
+    let mut may_end = false;
+
+    loop {
+        let len = match reader.read(buf) {
+            Ok(len) => len,
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        if len == 0 {
+            if may_end {
+                return Ok(summary);
+            }
+            if let File::Zip(zip_file) = &file {
+                zip_file.finish().map_err(Error::from)?;
+            }
+            return Err(std::io::Error::from(ErrorKind::UnexpectedEof).into());
+        }
+        summary.compressed_bytes += len as u64;
+        may_end = false;
+
+        let mut input = &buf[..len];
+        loop {
+            match file.read(input)? {
+                State::HasOutput {
+                    unparsed_input,
+                    output,
+                } => {
+                    sink(output)?;
+                    summary.uncompressed_bytes += output.len() as u64;
+                    input = unparsed_input;
+                }
+                State::NeedsInput => break,
+                State::NeedsInputOrEof(next_file) => {
+                    summary.files += 1;
+                    file = next_file;
+                    may_end = true;
+                    break;
+                }
+                State::NextFile {
+                    unparsed_input,
+                    next_file,
+                }
+                | State::NextArchive {
+                    unparsed_input,
+                    next_file,
+                } => {
+                    summary.files += 1;
+                    file = next_file;
+                    input = unparsed_input;
+                }
+                State::EndOfFile | State::Stopped => {
+                    summary.files += 1;
+                    return Ok(summary);
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,526 @@
+//! Streaming decompression of files made by the Unix `compress` utility (`.Z`),
+//! which use adaptive LZW coding.
+//!
+//! `LzwFile` has the same API as `gzip::GZipFile`. The format has no end marker
+//! and no checksum, so the stream never ends by itself: once the input runs out
+//! it returns `NeedsInput`, and the end of the input is the end of the file.
+//! `Stream` is the raw decompressor, whose API mirrors `deflate::Stream`.
+
+use nom::bytes::streaming::tag;
+use nom::combinator::verify;
+use nom::number::streaming::be_u8;
+use nom::IResult;
+
+use crate::deflate::{self, State};
+use crate::input_helper::{Input, InputHandler};
+use crate::{RatioLimit, SizeLimits};
+
+const OUTPUT_BUFFER_SIZE: usize = 32 * 1024;
+
+/// The magic bytes that start a compressed file.
+const MAGIC: &[u8] = b"\x1f\x9d";
+const MIN_CODE_BITS: u32 = 9;
+const MAX_CODE_BITS: u8 = 16;
+/// In block mode, this code clears the string table.
+const CLEAR_CODE: u32 = 256;
+
+/// Whether `input` starts with the magic bytes of a compressed file.
+pub fn is_lzw_header(input: &[u8]) -> bool {
+    input.starts_with(MAGIC)
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct StreamHeader {
+    /// The largest code width, from 9 to 16 bits.
+    pub max_bits: u8,
+    /// Whether the compressor may clear the string table when the
+    /// compression ratio drops. Every `compress` since 1986 sets this.
+    pub block_mode: bool,
+}
+
+impl StreamHeader {
+    pub fn parse(i: &[u8]) -> IResult<&[u8], StreamHeader> {
+        let (i, _) = tag(MAGIC)(i)?;
+        // Bits 5 and 6 are reserved.
+        let (i, flags) = verify(be_u8, |flags| {
+            flags & 0x60 == 0 && (MIN_CODE_BITS as u8..=MAX_CODE_BITS).contains(&(flags & 0x1f))
+        })(i)?;
+
+        Ok((
+            i,
+            StreamHeader {
+                max_bits: flags & 0x1f,
+                block_mode: flags & 0x80 != 0,
+            },
+        ))
+    }
+}
+
+/// The LZW data contained an undefined code.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct InvalidStream;
+
+/// An LZW decompressor. The codes are packed starting from the least
+/// significant bit, in groups of eight codes of the same width. When the width
+/// changes or the table is cleared, the rest of the current group is skipped.
+pub struct Stream {
+    block_mode: bool,
+    /// The code that the string of each code extends.
+    prefixes: Vec<u16>,
+    /// The last byte of the string of each code.
+    suffixes: Vec<u8>,
+    code_bits: u32,
+    max_bits: u32,
+    /// When the next free code exceeds this, the codes get a bit wider.
+    max_code: u32,
+    free_code: u32,
+    previous_code: Option<u32>,
+    /// The first byte of the string of the previous code.
+    first_byte: u8,
+    bit_buffer: u32,
+    buffered_bits: u32,
+    /// Bits read since the start of the current group of codes.
+    group_bits: u32,
+    /// Padding bits left to skip before the next code.
+    skip_bits: u32,
+    /// The string of a code, in reverse order.
+    string: Vec<u8>,
+    output: Vec<u8>,
+    comp_size: u64,
+    uncomp_size: u64,
+}
+
+impl Stream {
+    pub fn new(header: &StreamHeader) -> Stream {
+        let table_size = 1 << header.max_bits;
+        Stream {
+            block_mode: header.block_mode,
+            prefixes: vec![0; table_size],
+            suffixes: vec![0; table_size],
+            code_bits: MIN_CODE_BITS,
+            max_bits: u32::from(header.max_bits),
+            max_code: (1 << MIN_CODE_BITS) - 1,
+            free_code: if header.block_mode {
+                CLEAR_CODE + 1
+            } else {
+                CLEAR_CODE
+            },
+            previous_code: None,
+            first_byte: 0,
+            bit_buffer: 0,
+            buffered_bits: 0,
+            group_bits: 0,
+            skip_bits: 0,
+            string: Vec::new(),
+            output: Vec::with_capacity(OUTPUT_BUFFER_SIZE),
+            comp_size: 0,
+            uncomp_size: 0,
+        }
+    }
+
+    /// Decodes the codes in `input`. As the data has no end marker,
+    /// this never returns `State::Stop`.
+    pub fn feed_input<'i, 'o>(
+        &'o mut self,
+        input: &'i [u8],
+    ) -> Result<State<'i, 'o>, InvalidStream> {
+        self.output.clear();
+        let mut bytes = input.iter();
+
+        while self.output.len() < OUTPUT_BUFFER_SIZE {
+            if self.free_code > self.max_code {
+                self.skip_to_next_group();
+                self.code_bits += 1;
+                self.max_code = if self.code_bits == self.max_bits {
+                    1 << self.max_bits
+                } else {
+                    (1 << self.code_bits) - 1
+                };
+            }
+            if !self.fill_bits(&mut bytes) {
+                break;
+            }
+            let code = self.bit_buffer & ((1 << self.code_bits) - 1);
+            self.bit_buffer >>= self.code_bits;
+            self.buffered_bits -= self.code_bits;
+            self.group_bits = (self.group_bits + self.code_bits) % (self.code_bits * 8);
+            self.decode(code)?;
+        }
+
+        let unparsed_input = bytes.as_slice();
+        self.comp_size += (input.len() - unparsed_input.len()) as u64;
+        self.uncomp_size += self.output.len() as u64;
+        if self.output.is_empty() {
+            Ok(State::NeedsInput { unparsed_input })
+        } else {
+            Ok(State::HasOutput {
+                unparsed_input,
+                output: self.get_output(),
+            })
+        }
+    }
+
+    /// Skips the padding and buffers the bits of the next code.
+    /// Returns false if the input runs out first.
+    fn fill_bits(&mut self, bytes: &mut std::slice::Iter<u8>) -> bool {
+        while self.skip_bits > 0 || self.buffered_bits < self.code_bits {
+            if self.buffered_bits == 0 || self.skip_bits == 0 {
+                match bytes.next() {
+                    Some(&byte) => {
+                        self.bit_buffer |= u32::from(byte) << self.buffered_bits;
+                        self.buffered_bits += 8;
+                    }
+                    None => return false,
+                }
+            }
+            let skipped = std::cmp::min(self.skip_bits, self.buffered_bits);
+            self.bit_buffer >>= skipped;
+            self.buffered_bits -= skipped;
+            self.skip_bits -= skipped;
+        }
+        true
+    }
+
+    fn skip_to_next_group(&mut self) {
+        let group_size = self.code_bits * 8;
+        self.skip_bits = (group_size - self.group_bits) % group_size;
+        self.group_bits = 0;
+    }
+
+    fn decode(&mut self, code: u32) -> Result<(), InvalidStream> {
+        let previous_code = match self.previous_code {
+            Some(previous_code) => previous_code,
+            None => {
+                // The first code is always a literal byte.
+                if code >= 256 {
+                    return Err(InvalidStream);
+                }
+                self.first_byte = code as u8;
+                self.output.push(code as u8);
+                self.previous_code = Some(code);
+                return Ok(());
+            }
+        };
+
+        if code == CLEAR_CODE && self.block_mode {
+            self.skip_to_next_group();
+            self.code_bits = MIN_CODE_BITS;
+            self.max_code = (1 << MIN_CODE_BITS) - 1;
+            // The code after the clear code adds an entry in place of the clear code.
+            self.free_code = CLEAR_CODE;
+            return Ok(());
+        }
+
+        self.string.clear();
+        let mut current = code;
+        if code >= self.free_code {
+            // The code that is being defined: the previous string
+            // followed by its own first byte.
+            if code > self.free_code {
+                return Err(InvalidStream);
+            }
+            self.string.push(self.first_byte);
+            current = previous_code;
+        }
+        while current >= 256 {
+            self.string.push(self.suffixes[current as usize]);
+            current = u32::from(self.prefixes[current as usize]);
+        }
+        self.first_byte = current as u8;
+        self.string.push(self.first_byte);
+        self.output.extend(self.string.iter().rev());
+
+        if self.free_code < 1 << self.max_bits {
+            self.prefixes[self.free_code as usize] = previous_code as u16;
+            self.suffixes[self.free_code as usize] = self.first_byte;
+            self.free_code += 1;
+        }
+        self.previous_code = Some(code);
+        Ok(())
+    }
+
+    pub fn get_output(&self) -> &[u8] {
+        &self.output
+    }
+
+    pub fn uncompressed_size(&self) -> u64 {
+        self.uncomp_size
+    }
+
+    pub fn compressed_size(&self) -> u64 {
+        self.comp_size
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum LzwError {
+    InvalidHeader,
+    InvalidStream,
+    /// The data decompresses to more than `Options::max_size`.
+    StreamTooLarge,
+    /// The data decompresses to more than `Options::max_compression_ratio`
+    /// times its compressed size.
+    CompressionRatioExceeded,
+}
+
+impl std::error::Error for LzwError {
+    fn description(&self) -> &str {
+        "LZW uncompressing error"
+    }
+}
+
+impl std::fmt::Display for LzwError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        use self::LzwError::*;
+        match self {
+            InvalidHeader => write!(f, "invalid header"),
+            InvalidStream => write!(f, "invalid LZW stream"),
+            StreamTooLarge => write!(f, "stream exceeds the size limit"),
+            CompressionRatioExceeded => write!(f, "stream exceeds the compression ratio limit"),
+        }
+    }
+}
+
+/// Options for parsing a file made by `compress`, set with the builder methods.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Options {
+    pub(crate) limits: SizeLimits,
+}
+
+impl Options {
+    /// Limits the decompressed size of the file to `size` bytes. A file that
+    /// decompresses to more fails with `LzwError::StreamTooLarge` as soon as
+    /// the output that exceeds the limit has been decompressed, before it's returned.
+    pub fn max_size(mut self, size: u64) -> Self {
+        self.limits.max_size = Some(size);
+        self
+    }
+
+    /// Limits the ratio of the decompressed size of the file to its compressed size.
+    /// Once it has decompressed to more than `min_size` bytes, the file fails
+    /// with `LzwError::CompressionRatioExceeded` if its output so far is more than
+    /// `ratio` times the compressed data that produced it.
+    pub fn max_compression_ratio(mut self, ratio: u64, min_size: u64) -> Self {
+        self.limits.max_compression_ratio = Some(RatioLimit { ratio, min_size });
+        self
+    }
+}
+
+pub struct LzwFile {
+    state: InternalState,
+    unparsed: Vec<u8>,
+    decompressor: Option<Stream>,
+    options: Options,
+}
+
+impl std::fmt::Debug for LzwFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("LzwFile")
+            .field("state", &self.state)
+            .field("unparsed", &self.unparsed)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum InternalState {
+    Init,
+    HeaderParsed(StreamHeader),
+    Sentinel,
+    /// The stream failed with the error, which later reads return again.
+    Error(LzwError),
+}
+
+#[derive(Debug)]
+enum ParseResult {
+    Continue,
+    NeedsInput,
+    Output,
+    Error(LzwError),
+}
+
+impl LzwFile {
+    pub fn get_output(&self) -> &[u8] {
+        match self.decompressor {
+            Some(ref decompressor) => decompressor.get_output(),
+            None => &[],
+        }
+    }
+
+    /// The header of the file, once it has been parsed.
+    pub fn header(&self) -> Option<&StreamHeader> {
+        match &self.state {
+            InternalState::HeaderParsed(header) => Some(header),
+            _ => None,
+        }
+    }
+
+    pub fn read<'i, 's>(
+        &'s mut self,
+        input: &'i [u8],
+    ) -> Result<crate::State<'i, 's, LzwFile>, LzwError> {
+        let mut ihandler = InputHandler::take_storage(&mut self.unparsed, input);
+        let mut unparsed = ihandler.get_unparsed();
+
+        loop {
+            let mut state = InternalState::Sentinel;
+            std::mem::swap(&mut self.state, &mut state);
+            let (bytes_consumed, new_state, res) = self.parse_step(state, unparsed);
+            unparsed = ihandler.consumed(bytes_consumed);
+            self.state = new_state;
+            match res {
+                ParseResult::Continue => (),
+                ParseResult::NeedsInput => {
+                    let extended_len = ihandler.extend_input();
+                    // Nothing in input left to extend, so we need the user to provide more
+                    if extended_len == 0 {
+                        ihandler.return_storage(&mut self.unparsed);
+                        return Ok(crate::State::NeedsInput);
+                    }
+                    unparsed = ihandler.get_unparsed();
+                }
+                ParseResult::Output => {
+                    let unparsed_input = ihandler.take_rest(&mut self.unparsed);
+                    return Ok(crate::State::HasOutput {
+                        unparsed_input,
+                        output: self.get_output(),
+                    });
+                }
+                ParseResult::Error(err) => return Err(err),
+            };
+            if unparsed.is_empty() {
+                return Ok(crate::State::NeedsInput);
+            }
+        }
+    }
+
+    fn parse_step<'long, 'short>(
+        &'short mut self,
+        state: InternalState,
+        input: Input<'long, 'short>,
+    ) -> (usize, InternalState, ParseResult) {
+        match state {
+            InternalState::Init => self.parse_header(input),
+            InternalState::HeaderParsed(header) => self.decompress(input, header),
+            InternalState::Sentinel => unreachable!("parse_step is never called with Sentinel"),
+            InternalState::Error(error) => {
+                (0, InternalState::Error(error), ParseResult::Error(error))
+            }
+        }
+    }
+
+    fn parse_header<'long, 'short>(
+        &mut self,
+        input: Input<'long, 'short>,
+    ) -> (usize, InternalState, ParseResult) {
+        match StreamHeader::parse(*input) {
+            Ok((unparsed, header)) => {
+                self.decompressor = Some(Stream::new(&header));
+                let consumed = input.len() - unparsed.len();
+                (
+                    consumed,
+                    InternalState::HeaderParsed(header),
+                    ParseResult::Continue,
+                )
+            }
+            Err(nom::Err::Incomplete(_need)) => (0, InternalState::Init, ParseResult::NeedsInput),
+            Err(nom::Err::Error(_e)) | Err(nom::Err::Failure(_e)) => (
+                0,
+                InternalState::Error(LzwError::InvalidHeader),
+                ParseResult::Error(LzwError::InvalidHeader),
+            ),
+        }
+    }
+
+    fn decompress<'long, 'short>(
+        &mut self,
+        input: Input<'long, 'short>,
+        header: StreamHeader,
+    ) -> (usize, InternalState, ParseResult) {
+        let decompressor = self
+            .decompressor
+            .as_mut()
+            .expect("The decompressor is created with the header.");
+        match decompressor.feed_input(*input) {
+            Ok(deflate::State::NeedsInput { unparsed_input }) => (
+                input.len() - unparsed_input.len(),
+                InternalState::HeaderParsed(header),
+                ParseResult::Continue,
+            ),
+            Ok(deflate::State::HasOutput { unparsed_input, .. }) => {
+                let consumed_bytes = input.len() - unparsed_input.len();
+                if self
+                    .options
+                    .limits
+                    .exceeds_size(decompressor.uncompressed_size())
+                {
+                    return (
+                        consumed_bytes,
+                        InternalState::Error(LzwError::StreamTooLarge),
+                        ParseResult::Error(LzwError::StreamTooLarge),
+                    );
+                }
+                if self.options.limits.exceeds_ratio(
+                    decompressor.compressed_size(),
+                    decompressor.uncompressed_size(),
+                ) {
+                    return (
+                        consumed_bytes,
+                        InternalState::Error(LzwError::CompressionRatioExceeded),
+                        ParseResult::Error(LzwError::CompressionRatioExceeded),
+                    );
+                }
+                (
+                    consumed_bytes,
+                    InternalState::HeaderParsed(header),
+                    ParseResult::Output,
+                )
+            }
+            Ok(deflate::State::Stop { .. }) => unreachable!("LZW data has no end marker"),
+            Err(_) => (
+                0,
+                InternalState::Error(LzwError::InvalidStream),
+                ParseResult::Error(LzwError::InvalidStream),
+            ),
+        }
+    }
+
+    pub fn read_with<'i>(
+        &mut self,
+        mut input: &'i [u8],
+        mut callback: impl FnMut(&[u8]),
+    ) -> Result<crate::State<'i, 'i, LzwFile>, LzwError> {
+        loop {
+            match self.read(input)?.try_no_output() {
+                Err((unparsed_input, output)) => {
+                    input = unparsed_input;
+                    callback(output);
+                }
+                Ok(state) => return Ok(state),
+            }
+        }
+    }
+}
+
+/// Starts a stream of a file made by `compress`.
+pub fn start_stream() -> LzwFile {
+    start_stream_with(Options::default())
+}
+
+/// Starts a stream of a file made by `compress` that is parsed according to `options`.
+pub fn start_stream_with(options: Options) -> LzwFile {
+    LzwFile {
+        state: InternalState::Init,
+        unparsed: Vec::new(),
+        decompressor: None,
+        options,
+    }
+}
+
+/// Starts a stream whose first bytes, `prefix`, were already read from the input
+/// to detect the format. They are parsed together with the input of the next `read`.
+pub(crate) fn start_stream_with_options_and_prefix(options: Options, prefix: &[u8]) -> LzwFile {
+    let mut file = start_stream_with(options);
+    file.unparsed = prefix.to_vec();
+    file
+}
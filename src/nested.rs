@@ -0,0 +1,211 @@
+//! Streaming of nested archives: the entries whose content is itself a zip
+//! archive or a gzip file are decompressed in turn, down to a limited depth.
+//!
+//! The output of each entry is passed to a callback together with the path of
+//! the entry, which consists of the names of the archives it's in and its own
+//! name, separated by `!`, for example `outer.zip!inner.csv`. A nested stream
+//! that has no names, such as a gzip file without a filename, is named by
+//! the entry that contains it.
+//!
+//! The streams at every level are parsed according to the same `Options`,
+//! except that the format set with `Options::format` only applies at the top
+//! level, and the nested streams are autodetected.
+
+use crate::{start_stream_with as start_autodetected_stream_with, Error, File, Options, State};
+
+/// Separates the name of an entry from the name of the archive it's in.
+pub const PATH_SEPARATOR: u8 = b'!';
+
+/// The amount of output that is needed to tell whether an entry is an archive.
+const PROBE_LEN: usize = 4;
+
+/// Whether `output` starts a zip archive or a gzip file.
+fn is_nested_start(output: &[u8]) -> bool {
+    output.starts_with(b"PK\x03\x04") || output.starts_with(b"\x1f\x8b\x08")
+}
+
+/// Where a nested stream stopped.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum NestedState {
+    NeedsInput,
+    /// The stream may end here, or continue with more input.
+    NeedsInputOrEof,
+    EndOfFile,
+}
+
+enum Entry {
+    /// Buffering the output to tell whether the entry is an archive.
+    Probing(Vec<u8>),
+    /// The entry, whose path this is, isn't an archive, so its output is passed through.
+    Plain(Vec<u8>),
+    Nested(Box<NestedFile>),
+    /// The nested archive ended; the rest of the output is ignored.
+    Ended,
+}
+
+/// A stream whose entries are streamed in turn if they are archives themselves.
+pub struct NestedFile {
+    file: File,
+    /// The path of the entry this stream is in, followed by a separator,
+    /// or nothing at the top level.
+    prefix: Vec<u8>,
+    /// How many more levels of archives may be nested in this one.
+    depth_left: usize,
+    /// The options that the nested streams are started with.
+    options: Options,
+    entry: Entry,
+}
+
+/// Starts a stream whose format is autodetected, and that descends into
+/// entries that are zip archives or gzip files up to `max_depth` levels deep.
+/// With a `max_depth` of 0, no entry is descended into. The entries below
+/// the limit are passed through as they are, which protects against
+/// archives that contain themselves.
+pub fn start_stream(max_depth: usize) -> NestedFile {
+    start_stream_with(Options::default(), max_depth)
+}
+
+/// Starts a nested stream like `start_stream`, whose streams at every level
+/// are parsed according to `options`.
+pub fn start_stream_with(options: Options, max_depth: usize) -> NestedFile {
+    let file = start_autodetected_stream_with(options.clone());
+    let options = Options {
+        format: None,
+        ..options
+    };
+    NestedFile::new(file, Vec::new(), max_depth, options)
+}
+
+impl NestedFile {
+    fn new(file: File, prefix: Vec<u8>, depth_left: usize, options: Options) -> NestedFile {
+        NestedFile {
+            file,
+            prefix,
+            depth_left,
+            options,
+            entry: Entry::Probing(Vec::new()),
+        }
+    }
+
+    /// Consumes `input`, passing the path and the output of the entries
+    /// to `callback`. An error in a nested archive fails the whole stream,
+    /// as does a nested zip archive or gzip file that is truncated at the end
+    /// of its entry.
+    pub fn read(
+        &mut self,
+        mut input: &[u8],
+        callback: &mut dyn FnMut(&[u8], &[u8]),
+    ) -> Result<NestedState, Error> {
+        loop {
+            match self.file.read(input)? {
+                State::HasOutput { unparsed_input, .. } => {
+                    input = unparsed_input;
+                    self.pass_output(callback)?;
+                }
+                State::NeedsInput => return Ok(NestedState::NeedsInput),
+                State::NeedsInputOrEof(next_file) => {
+                    self.end_entry(callback)?;
+                    self.file = next_file;
+                    return Ok(NestedState::NeedsInputOrEof);
+                }
+                State::NextFile {
+                    unparsed_input,
+                    next_file,
+                }
+                | State::NextArchive {
+                    unparsed_input,
+                    next_file,
+                } => {
+                    self.end_entry(callback)?;
+                    self.file = next_file;
+                    input = unparsed_input;
+                }
+                State::EndOfFile | State::Stopped => {
+                    self.end_entry(callback)?;
+                    return Ok(NestedState::EndOfFile);
+                }
+            }
+        }
+    }
+
+    /// The path of the current entry.
+    fn path(file: &File, prefix: &[u8]) -> Vec<u8> {
+        match file.name() {
+            Some(name) => [prefix, name].concat(),
+            None => prefix
+                .strip_suffix(&[PATH_SEPARATOR])
+                .unwrap_or(prefix)
+                .to_vec(),
+        }
+    }
+
+    /// Passes the latest output of the stream to the callback or to the nested stream.
+    fn pass_output(&mut self, callback: &mut dyn FnMut(&[u8], &[u8])) -> Result<(), Error> {
+        let NestedFile {
+            file,
+            prefix,
+            depth_left,
+            options,
+            entry,
+        } = self;
+        let output = file.get_output();
+        let probe = match entry {
+            Entry::Probing(probe) => {
+                probe.extend_from_slice(output);
+                if probe.len() < PROBE_LEN {
+                    return Ok(());
+                }
+                std::mem::take(probe)
+            }
+            Entry::Plain(path) => {
+                callback(path, output);
+                return Ok(());
+            }
+            Entry::Nested(nested) => {
+                if nested.read(output, callback)? == NestedState::EndOfFile {
+                    *entry = Entry::Ended;
+                }
+                return Ok(());
+            }
+            Entry::Ended => return Ok(()),
+        };
+
+        let path = NestedFile::path(file, prefix);
+        if *depth_left > 0 && !file.is_dir() && is_nested_start(&probe) {
+            let mut nested_prefix = path;
+            nested_prefix.push(PATH_SEPARATOR);
+            let mut nested = NestedFile::new(
+                start_autodetected_stream_with(options.clone()),
+                nested_prefix,
+                *depth_left - 1,
+                options.clone(),
+            );
+            *entry = match nested.read(&probe, callback)? {
+                NestedState::EndOfFile => Entry::Ended,
+                _ => Entry::Nested(Box::new(nested)),
+            };
+        } else {
+            callback(&path, &probe);
+            *entry = Entry::Plain(path);
+        }
+        Ok(())
+    }
+
+    /// Passes what's left of the current entry to the callback once it has ended.
+    fn end_entry(&mut self, callback: &mut dyn FnMut(&[u8], &[u8])) -> Result<(), Error> {
+        match std::mem::replace(&mut self.entry, Entry::Probing(Vec::new())) {
+            // The entry was too short to tell whether it's an archive.
+            Entry::Probing(probe) if !probe.is_empty() => {
+                callback(&NestedFile::path(&self.file, &self.prefix), &probe)
+            }
+            // The last entry of a nested archive that didn't end may still have
+            // output left, but the archive fails unless it may end where it is.
+            Entry::Nested(mut nested) => {
+                nested.end_entry(callback)?;
+                nested.file.finish()?;
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,46 @@
+//! A pseudo-format for input that isn't compressed: the output is the input
+//! as it is, in a single file without a name that ends where the input ends.
+//! Autodetected streams fall back to it with `Options::passthrough_unknown`.
+
+use State;
+
+#[derive(Debug, Default)]
+pub struct PassthroughFile {
+    /// Input that was read to detect the format, returned before the next input.
+    unparsed: Vec<u8>,
+    output: Vec<u8>,
+}
+
+impl PassthroughFile {
+    pub fn get_output(&self) -> &[u8] {
+        &self.output
+    }
+
+    /// Returns all of `input` as output. The stream never ends by itself,
+    /// so it needs more input until the input ends.
+    pub fn read<'i, 's>(&'s mut self, input: &'i [u8]) -> State<'i, 's, PassthroughFile> {
+        if self.unparsed.is_empty() && input.is_empty() {
+            return State::NeedsInput;
+        }
+        self.output.clear();
+        self.output.append(&mut self.unparsed);
+        self.output.extend_from_slice(input);
+        State::HasOutput {
+            unparsed_input: &input[input.len()..],
+            output: &self.output,
+        }
+    }
+}
+
+/// Starts a stream that passes its input through.
+pub fn start_stream() -> PassthroughFile {
+    PassthroughFile::default()
+}
+
+/// Starts a stream whose first bytes, `prefix`, were already read from the input
+/// to detect the format. They are returned together with the input of the next `read`.
+pub(crate) fn start_stream_with_prefix(prefix: &[u8]) -> PassthroughFile {
+    let mut file = start_stream();
+    file.unparsed = prefix.to_vec();
+    file
+}
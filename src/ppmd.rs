@@ -0,0 +1,130 @@
+//! Decompression of PPMd Var.I Rev.1 data (zip compression method 98).
+//! WinZip uses PPMd for text-heavy files.
+//!
+//! The API mirrors `deflate::Stream` and returns the same `deflate::State`.
+//! The PPMd decoder can't be suspended in the middle of a symbol, so the stream
+//! buffers all of the compressed data before producing any output, and it needs
+//! to know both the compressed and the uncompressed length up front.
+
+use std::io::{Cursor, Read};
+
+use ppmd_rust::{Ppmd8Decoder, RestoreMethod};
+
+use crate::deflate::State;
+
+const OUTPUT_BUFFER_SIZE: usize = 32 * 1024;
+
+/// The length of the parameter word that precedes the PPMd data in zip entries.
+const PARAMETERS_SIZE: u64 = 2;
+
+/// The PPMd stream contained invalid data.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct InvalidStream;
+
+enum Phase {
+    Buffering(Vec<u8>),
+    Decoding(Box<Ppmd8Decoder<Cursor<Vec<u8>>>>),
+}
+
+pub struct Stream {
+    phase: Phase,
+    output: Vec<u8>,
+    output_len: usize,
+    remaining_input: u64,
+    remaining_output: u64,
+    uncomp_size: u64,
+    comp_size: u64,
+}
+
+impl Stream {
+    /// Starts a stream that is `compressed_size` bytes long,
+    /// including the parameter word, and decompresses to `uncompressed_size` bytes.
+    /// No input past that is consumed.
+    pub fn new(compressed_size: u64, uncompressed_size: u64) -> Stream {
+        Stream {
+            phase: Phase::Buffering(Vec::new()),
+            output: vec![0; OUTPUT_BUFFER_SIZE],
+            output_len: 0,
+            remaining_input: compressed_size,
+            remaining_output: uncompressed_size,
+            uncomp_size: 0,
+            comp_size: 0,
+        }
+    }
+
+    pub fn feed_input<'i, 'o>(
+        &'o mut self,
+        input: &'i [u8],
+    ) -> Result<State<'i, 'o>, InvalidStream> {
+        let mut unparsed_input = input;
+        if let Phase::Buffering(ref mut buffer) = self.phase {
+            let available = std::cmp::min(self.remaining_input, input.len() as u64) as usize;
+            buffer.extend_from_slice(&input[..available]);
+            self.comp_size += available as u64;
+            self.remaining_input -= available as u64;
+            unparsed_input = &input[available..];
+            if self.remaining_input > 0 {
+                self.output_len = 0;
+                return Ok(State::NeedsInput { unparsed_input });
+            }
+            let decoder = start_decoder(std::mem::take(buffer))?;
+            self.phase = Phase::Decoding(Box::new(decoder));
+        }
+
+        let decoder = match self.phase {
+            Phase::Decoding(ref mut decoder) => decoder,
+            Phase::Buffering(_) => unreachable!(),
+        };
+        let wanted = std::cmp::min(self.remaining_output, self.output.len() as u64) as usize;
+        let mut written = 0;
+        while written < wanted {
+            match decoder.read(&mut self.output[written..wanted]) {
+                Ok(0) | Err(_) => return Err(InvalidStream),
+                Ok(n) => written += n,
+            }
+        }
+        self.output_len = written;
+        self.uncomp_size += written as u64;
+        self.remaining_output -= written as u64;
+
+        if written > 0 {
+            Ok(State::HasOutput {
+                unparsed_input,
+                output: self.get_output(),
+            })
+        } else {
+            Ok(State::Stop { unparsed_input })
+        }
+    }
+
+    pub fn get_output(&self) -> &[u8] {
+        &self.output[..self.output_len]
+    }
+
+    pub fn uncompressed_size(&self) -> u64 {
+        self.uncomp_size
+    }
+
+    pub fn compressed_size(&self) -> u64 {
+        self.comp_size
+    }
+}
+
+/// Reads the parameter word at the start of `data` and starts decoding the rest.
+fn start_decoder(data: Vec<u8>) -> Result<Ppmd8Decoder<Cursor<Vec<u8>>>, InvalidStream> {
+    if (data.len() as u64) < PARAMETERS_SIZE {
+        return Err(InvalidStream);
+    }
+    let parameters = u16::from(data[0]) | u16::from(data[1]) << 8;
+    let order = u32::from(parameters & 0x0f) + 1;
+    let mem_size = (u32::from(parameters >> 4 & 0xff) + 1) << 20;
+    let restore_method = match parameters >> 12 {
+        0 => RestoreMethod::Restart,
+        1 => RestoreMethod::CutOff,
+        _ => return Err(InvalidStream),
+    };
+
+    let mut reader = Cursor::new(data);
+    reader.set_position(PARAMETERS_SIZE);
+    Ppmd8Decoder::new(reader, order, mem_size, restore_method).map_err(|_| InvalidStream)
+}
@@ -0,0 +1,71 @@
+//! Turning entry names into paths that are safe to write to.
+//!
+//! Archives can contain any bytes as entry names, including absolute paths,
+//! drive letters and `..` components that point outside the directory an archive
+//! is extracted to. These functions check and convert names for extraction code,
+//! like `extract::extract_to_dir` does. Both `/` and `\` are treated as separators,
+//! since some archivers write Windows-style paths.
+
+use std::ffi::OsStr;
+use std::path::{Component, PathBuf};
+
+fn is_separator(byte: u8) -> bool {
+    byte == b'/' || byte == b'\\'
+}
+
+/// The length of the drive letter, such as `C:`, at the start of `name`, if any.
+fn drive_len(name: &[u8]) -> usize {
+    match name {
+        [letter, b':', ..] if letter.is_ascii_alphabetic() => 2,
+        _ => 0,
+    }
+}
+
+/// Whether `name` is a relative path that stays inside the directory it's
+/// extracted to: it isn't empty, doesn't start with a separator or
+/// a drive letter, and has no `..` components. Components with a colon,
+/// which name alternate data streams on NTFS, and NUL bytes aren't allowed either.
+pub fn is_safe_name(name: &[u8]) -> bool {
+    !name.is_empty() && !is_separator(name[0]) && drive_len(name) == 0 && entry_path(name).is_some()
+}
+
+/// Converts the name of an entry into a relative path. Separators are normalized
+/// to those of the platform, and `.` and empty components are dropped.
+/// A leading drive letter and leading separators are stripped, so absolute names
+/// become relative. Returns `None` if the name has a `..` component, a component
+/// with a colon or a NUL byte, or if a component isn't valid on the platform.
+/// The path may be empty, for example for the name `./`.
+pub fn entry_path(name: &[u8]) -> Option<PathBuf> {
+    if name.contains(&0) {
+        return None;
+    }
+    let mut path = PathBuf::new();
+    for component in name[drive_len(name)..].split(|&byte| is_separator(byte)) {
+        match component {
+            b"" | b"." => continue,
+            b".." => return None,
+            _ if component.contains(&b':') => return None,
+            _ => path.push(component_to_os_str(component)?),
+        }
+    }
+    // The platform may parse a component differently, for example as a prefix.
+    if path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        Some(path)
+    } else {
+        None
+    }
+}
+
+#[cfg(unix)]
+fn component_to_os_str(component: &[u8]) -> Option<&OsStr> {
+    use std::os::unix::ffi::OsStrExt;
+    Some(OsStr::from_bytes(component))
+}
+
+#[cfg(not(unix))]
+fn component_to_os_str(component: &[u8]) -> Option<&OsStr> {
+    std::str::from_utf8(component).ok().map(OsStr::new)
+}
@@ -0,0 +1,727 @@
+//! Streaming decompression of 7z archives.
+//!
+//! The header of a 7z archive, which tells how the data is compressed
+//! and which files it contains, is at the end of the archive. The packed
+//! streams before it are buffered until the header has been read, so the
+//! whole compressed archive is held in memory. After that, each file of the
+//! archive is a file of its own: its data is returned as output, and the
+//! next file as `State::NextFile`, like the entries of a tar archive.
+//!
+//! Folders with a single copy, LZMA or LZMA2 coder are supported, whether
+//! the files are compressed together (solid) or one by one. Filters such
+//! as BCJ, other compression methods and encryption aren't. LZMA and LZMA2
+//! are decoded by liblzma.
+
+use xz2::stream::{Action, Status};
+
+use crate::input_helper::{Input, InputHandler};
+use crate::utils::update_crc32;
+use crate::{RatioLimit, SizeLimits, State};
+
+pub mod headers;
+
+use self::headers::{Entry, Folder, NextHeader, StartHeader, StreamsInfo, SIGNATURE_HEADER_SIZE};
+
+const OUTPUT_BUFFER_SIZE: usize = 32 * 1024;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum SevenZError {
+    InvalidSignature,
+    InvalidHeader,
+    /// The CRC-32 of a header or of the data of a file didn't match.
+    ChecksumMismatch,
+    /// A folder uses a coder other than copy, LZMA or LZMA2, or several coders.
+    UnsupportedCoder,
+    InvalidStream,
+    /// The size of a file in the header is more than `Options::max_entry_size`.
+    EntryTooLarge,
+    /// The unpacked size of a folder in the header is more than
+    /// `Options::max_compression_ratio` times its packed size.
+    CompressionRatioExceeded,
+}
+
+impl std::error::Error for SevenZError {
+    fn description(&self) -> &str {
+        "7z uncompressing error"
+    }
+}
+
+impl std::fmt::Display for SevenZError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        use self::SevenZError::*;
+        match self {
+            InvalidSignature => write!(f, "invalid signature header"),
+            InvalidHeader => write!(f, "invalid header"),
+            ChecksumMismatch => write!(f, "CRC-32 mismatch"),
+            UnsupportedCoder => write!(f, "unsupported coder"),
+            InvalidStream => write!(f, "invalid packed stream"),
+            EntryTooLarge => write!(f, "file exceeds the size limit"),
+            CompressionRatioExceeded => write!(f, "folder exceeds the compression ratio limit"),
+        }
+    }
+}
+
+/// Writes `value` as a variable-length integer of the xz format.
+fn write_xz_number(mut value: u64, out: &mut Vec<u8>) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// liblzma only decodes LZMA2 data inside xz streams, so the pack stream
+/// is wrapped into an xz stream of a single block without a check.
+/// Returns the bytes that go before and after it.
+fn xz_wrapping(dict_size: u8, pack_size: u64, unpack_size: u64) -> (Vec<u8>, Vec<u8>) {
+    // No check.
+    const STREAM_FLAGS: [u8; 2] = [0, 0];
+    const BLOCK_HEADER_SIZE: u64 = 12;
+
+    let mut prefix = b"\xfd7zXZ\x00".to_vec();
+    prefix.extend_from_slice(&STREAM_FLAGS);
+    prefix.extend_from_slice(&update_crc32(0, &STREAM_FLAGS).to_le_bytes());
+    // The size of the block header in units of four bytes, minus one,
+    // the block flags (one filter, no sizes), the LZMA2 filter and padding.
+    let block_header = [
+        (BLOCK_HEADER_SIZE / 4 - 1) as u8,
+        0,
+        0x21,
+        1,
+        dict_size,
+        0,
+        0,
+        0,
+    ];
+    prefix.extend_from_slice(&block_header);
+    prefix.extend_from_slice(&update_crc32(0, &block_header).to_le_bytes());
+
+    // The block is padded to a multiple of four bytes.
+    let mut suffix = vec![0; ((4 - pack_size % 4) % 4) as usize];
+    let mut index = vec![0, 1];
+    write_xz_number(BLOCK_HEADER_SIZE + pack_size, &mut index);
+    write_xz_number(unpack_size, &mut index);
+    index.resize(index.len().div_ceil(4) * 4, 0);
+    index.extend_from_slice(&update_crc32(0, &index).to_le_bytes());
+    suffix.extend_from_slice(&index);
+
+    let mut footer = ((index.len() / 4 - 1) as u32).to_le_bytes().to_vec();
+    footer.extend_from_slice(&STREAM_FLAGS);
+    suffix.extend_from_slice(&update_crc32(0, &footer).to_le_bytes());
+    suffix.extend_from_slice(&footer);
+    suffix.extend_from_slice(b"YZ");
+    (prefix, suffix)
+}
+
+/// The input of a folder decoder: the pack stream and the bytes that are
+/// fed before and after it to make it readable for liblzma.
+struct DecoderInput {
+    prefix: Vec<u8>,
+    suffix: Vec<u8>,
+    /// The start of the pack stream in the buffered packed streams.
+    pack_start: usize,
+    pack_size: usize,
+    /// How much of the prefix, the pack stream and the suffix has been fed.
+    fed: usize,
+}
+
+impl DecoderInput {
+    /// The part of the input that hasn't been fed yet.
+    fn next<'p>(&'p self, packed: &'p [u8]) -> &'p [u8] {
+        let pack_end = self.prefix.len() + self.pack_size;
+        if self.fed < self.prefix.len() {
+            &self.prefix[self.fed..]
+        } else if self.fed < pack_end {
+            let start = self.pack_start + self.fed - self.prefix.len();
+            &packed[start..self.pack_start + self.pack_size]
+        } else {
+            &self.suffix[self.fed - pack_end..]
+        }
+    }
+}
+
+/// Decodes the pack stream of a folder.
+struct FolderDecoder {
+    /// `None` with the copy coder.
+    decomp: Option<xz2::stream::Stream>,
+    input: DecoderInput,
+    /// How much unpacked data is left.
+    remaining: u64,
+}
+
+impl FolderDecoder {
+    fn new(
+        folder: &Folder,
+        pack_start: usize,
+        pack_size: usize,
+    ) -> Result<FolderDecoder, SevenZError> {
+        if folder.coders.len() != 1 || folder.packed_streams.len() != 1 {
+            return Err(SevenZError::UnsupportedCoder);
+        }
+        let coder = &folder.coders[0];
+        let unpack_size = folder.unpack_size();
+        let (decomp, prefix, suffix) = match &coder.method_id[..] {
+            [0x00] => (None, Vec::new(), Vec::new()),
+            // The properties are those of the header of the .lzma format,
+            // which only lacks the unpacked size.
+            [0x03, 0x01, 0x01] if coder.properties.len() == 5 => {
+                let mut prefix = coder.properties.clone();
+                prefix.extend_from_slice(&unpack_size.to_le_bytes());
+                let decomp = xz2::stream::Stream::new_lzma_decoder(u64::MAX)
+                    .expect("Starting a decoder without a memory limit doesn't fail.");
+                (Some(decomp), prefix, Vec::new())
+            }
+            [0x21] if coder.properties.len() == 1 => {
+                let (prefix, suffix) =
+                    xz_wrapping(coder.properties[0], pack_size as u64, unpack_size);
+                let decomp = xz2::stream::Stream::new_stream_decoder(u64::MAX, 0)
+                    .expect("Starting a decoder without a memory limit doesn't fail.");
+                (Some(decomp), prefix, suffix)
+            }
+            [0x03, 0x01, 0x01] | [0x21] => return Err(SevenZError::InvalidHeader),
+            _ => return Err(SevenZError::UnsupportedCoder),
+        };
+        Ok(FolderDecoder {
+            decomp,
+            input: DecoderInput {
+                prefix,
+                suffix,
+                pack_start,
+                pack_size,
+                fed: 0,
+            },
+            remaining: unpack_size,
+        })
+    }
+
+    /// Decodes data into `output`, returning how much was decoded.
+    /// Returns 0 only after all of the unpacked data has been decoded.
+    fn decode(&mut self, packed: &[u8], output: &mut [u8]) -> Result<usize, SevenZError> {
+        let wanted = std::cmp::min(output.len() as u64, self.remaining) as usize;
+        if wanted == 0 {
+            return Ok(0);
+        }
+        loop {
+            let input = self.input.next(packed);
+            let (consumed, produced, ended) = match self.decomp {
+                None => {
+                    let copied = std::cmp::min(wanted, input.len());
+                    output[..copied].copy_from_slice(&input[..copied]);
+                    (copied, copied, input.is_empty())
+                }
+                Some(ref mut decomp) => {
+                    let in_before = decomp.total_in();
+                    let out_before = decomp.total_out();
+                    let status = decomp
+                        .process(input, &mut output[..wanted], Action::Run)
+                        .map_err(|_| SevenZError::InvalidStream)?;
+                    let consumed = (decomp.total_in() - in_before) as usize;
+                    let produced = (decomp.total_out() - out_before) as usize;
+                    let stuck = input.is_empty() && produced == 0;
+                    (consumed, produced, status == Status::StreamEnd || stuck)
+                }
+            };
+            self.input.fed += consumed;
+            if produced > 0 {
+                self.remaining -= produced as u64;
+                return Ok(produced);
+            }
+            if ended {
+                // The stream ended before the unpacked size was reached.
+                return Err(SevenZError::InvalidStream);
+            }
+        }
+    }
+}
+
+/// The state that the files of an archive share. It's handed over
+/// from a file to the next one.
+#[derive(Default)]
+struct Archive {
+    /// The packed streams, buffered until the header has been read.
+    packed: Vec<u8>,
+    streams: StreamsInfo,
+    /// The entries that haven't been started yet.
+    entries: std::vec::IntoIter<Entry>,
+    /// The folder that is opened next.
+    next_folder: usize,
+    decoder: Option<FolderDecoder>,
+    /// The substreams of the current folder that haven't been started yet.
+    remaining_substreams: usize,
+    output: Vec<u8>,
+}
+
+impl Archive {
+    /// Opens the next folder that has substreams, checking its sizes against `limits`.
+    fn open_next_folder(&mut self, limits: &SizeLimits) -> Result<(), SevenZError> {
+        while self.remaining_substreams == 0 {
+            let folder = self
+                .streams
+                .folders
+                .get(self.next_folder)
+                .ok_or(SevenZError::InvalidHeader)?;
+            let pack_index: usize = self.streams.folders[..self.next_folder]
+                .iter()
+                .map(|folder| folder.packed_streams.len())
+                .sum();
+            let pack_start = self.streams.pack_sizes[..pack_index]
+                .iter()
+                .try_fold(self.streams.pack_pos, |start, &size| {
+                    start.checked_add(size)
+                });
+            let pack_size = self.streams.pack_sizes.get(pack_index).cloned();
+            let (pack_start, pack_size) = match (pack_start, pack_size) {
+                (Some(start), Some(size))
+                    if start
+                        .checked_add(size)
+                        .is_some_and(|end| end <= self.packed.len() as u64) =>
+                {
+                    (start as usize, size as usize)
+                }
+                _ => return Err(SevenZError::InvalidHeader),
+            };
+            if limits.exceeds_ratio(pack_size as u64, folder.unpack_size()) {
+                return Err(SevenZError::CompressionRatioExceeded);
+            }
+            self.decoder = Some(FolderDecoder::new(folder, pack_start, pack_size)?);
+            self.remaining_substreams = self.streams.substreams[self.next_folder].len();
+            self.next_folder += 1;
+        }
+        Ok(())
+    }
+
+    /// Decodes the next chunk of the current folder into the output buffer,
+    /// but not more than `limit` bytes.
+    fn decode(&mut self, limit: u64) -> Result<&[u8], SevenZError> {
+        let len = std::cmp::min(limit, OUTPUT_BUFFER_SIZE as u64) as usize;
+        self.output.resize(len, 0);
+        let decoder = self
+            .decoder
+            .as_mut()
+            .expect("A folder is opened before its data is decoded");
+        let decoded = decoder.decode(&self.packed, &mut self.output)?;
+        if decoded == 0 {
+            // The substreams take more than the folder holds.
+            return Err(SevenZError::InvalidHeader);
+        }
+        Ok(&self.output[..decoded])
+    }
+
+    /// Decodes the header that is compressed in the single folder of `streams`.
+    fn decode_header(
+        &mut self,
+        streams: StreamsInfo,
+        limits: &SizeLimits,
+    ) -> Result<Vec<u8>, SevenZError> {
+        if streams.folders.len() != 1 {
+            return Err(SevenZError::InvalidHeader);
+        }
+        let size = streams.folders[0].unpack_size();
+        let crc = streams.folders[0].crc;
+        self.streams = streams;
+        self.open_next_folder(limits)?;
+
+        let mut data = Vec::new();
+        while (data.len() as u64) < size {
+            let chunk = self.decode(size - data.len() as u64)?;
+            data.extend_from_slice(chunk);
+        }
+        if crc.is_some_and(|crc| crc != update_crc32(0, &data)) {
+            return Err(SevenZError::ChecksumMismatch);
+        }
+        *self = Archive {
+            packed: std::mem::take(&mut self.packed),
+            ..Archive::default()
+        };
+        Ok(data)
+    }
+}
+
+/// Options for parsing a 7z archive, set with the builder methods.
+/// The options are inherited by the files returned in `State::NextFile`.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Options {
+    pub(crate) limits: SizeLimits,
+}
+
+impl Options {
+    /// Limits the decompressed size of each file to `size` bytes. The sizes are
+    /// in the header of the archive, so a file whose size is more fails the stream
+    /// with `SevenZError::EntryTooLarge` before any of its data is decompressed.
+    pub fn max_entry_size(mut self, size: u64) -> Self {
+        self.limits.max_size = Some(size);
+        self
+    }
+
+    /// Limits the ratio of the unpacked size of each folder, the data of the files
+    /// that are compressed together, to its packed size. The sizes are in the header
+    /// of the archive, so a folder that unpacks to more than `min_size` bytes and more
+    /// than `ratio` times its packed size fails the stream with
+    /// `SevenZError::CompressionRatioExceeded` before any of it is decompressed.
+    pub fn max_compression_ratio(mut self, ratio: u64, min_size: u64) -> Self {
+        self.limits.max_compression_ratio = Some(RatioLimit { ratio, min_size });
+        self
+    }
+}
+
+pub struct SevenZFile {
+    state: InternalState,
+    unparsed: Vec<u8>,
+    entry: Option<Entry>,
+    archive: Box<Archive>,
+    output_len: usize,
+    options: Options,
+}
+
+impl std::fmt::Debug for SevenZFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("SevenZFile")
+            .field("state", &self.state)
+            .field("unparsed", &self.unparsed)
+            .field("entry", &self.entry)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum InternalState {
+    SignatureHeader,
+    PackedStreams {
+        remaining: u64,
+        start_header: StartHeader,
+    },
+    NextHeader {
+        remaining: u64,
+        data: Vec<u8>,
+        crc: u32,
+    },
+    EntryStart,
+    Data {
+        remaining: u64,
+        crc: u32,
+    },
+    EntryEnd,
+    End,
+    Sentinel,
+    /// The stream failed with the error, which later reads return again.
+    Error(SevenZError),
+}
+
+#[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
+enum ParseResult {
+    Continue,
+    NeedsInput,
+    Output,
+    Error(SevenZError),
+    NextFile(SevenZFile),
+    EndOfFile,
+}
+
+impl SevenZFile {
+    pub fn get_output(&self) -> &[u8] {
+        &self.archive.output[..self.output_len]
+    }
+
+    /// The entry, once the header of the archive has been read.
+    pub fn entry(&self) -> Option<&Entry> {
+        self.entry.as_ref()
+    }
+
+    pub fn filename(&self) -> Option<&[u8]> {
+        self.entry.as_ref().map(|entry| &entry.name[..])
+    }
+
+    pub fn read<'i, 's>(
+        &'s mut self,
+        input: &'i [u8],
+    ) -> Result<State<'i, 's, SevenZFile>, SevenZError> {
+        let mut ihandler = InputHandler::take_storage(&mut self.unparsed, input);
+        let mut unparsed = ihandler.get_unparsed();
+
+        loop {
+            let mut state = InternalState::Sentinel;
+            std::mem::swap(&mut self.state, &mut state);
+            let (bytes_consumed, new_state, res) = self.parse_step(state, unparsed);
+            unparsed = ihandler.consumed(bytes_consumed);
+            self.state = new_state;
+            match res {
+                ParseResult::Continue => (),
+                ParseResult::NeedsInput => {
+                    let extended_len = ihandler.extend_input();
+                    // Nothing in input left to extend, so we need the user to provide more
+                    if extended_len == 0 {
+                        ihandler.return_storage(&mut self.unparsed);
+                        return Ok(State::NeedsInput);
+                    }
+                    unparsed = ihandler.get_unparsed();
+                }
+                ParseResult::Output => {
+                    let unparsed_input = ihandler.take_rest(&mut self.unparsed);
+                    return Ok(State::HasOutput {
+                        unparsed_input,
+                        output: self.get_output(),
+                    });
+                }
+                ParseResult::NextFile(next_file) => {
+                    let unparsed_input = ihandler.take_rest(&mut self.unparsed);
+                    return Ok(State::NextFile {
+                        unparsed_input,
+                        next_file,
+                    });
+                }
+                ParseResult::EndOfFile => return Ok(State::EndOfFile),
+                ParseResult::Error(err) => return Err(err),
+            };
+            // The data of the files is decoded from the buffered
+            // packed streams, so only the archive itself needs input.
+            if unparsed.is_empty() && self.state.reads_input() {
+                ihandler.return_storage(&mut self.unparsed);
+                return Ok(State::NeedsInput);
+            }
+        }
+    }
+
+    pub fn read_with<'i>(
+        &mut self,
+        mut input: &'i [u8],
+        mut callback: impl FnMut(&[u8]),
+    ) -> Result<State<'i, 'i, SevenZFile>, SevenZError> {
+        loop {
+            match self.read(input)?.try_no_output() {
+                Err((unparsed_input, output)) => {
+                    input = unparsed_input;
+                    callback(output);
+                }
+                Ok(state) => return Ok(state),
+            }
+        }
+    }
+
+    fn parse_step<'long, 'short>(
+        &'short mut self,
+        state: InternalState,
+        input: Input<'long, 'short>,
+    ) -> (usize, InternalState, ParseResult) {
+        self.output_len = 0;
+        match state {
+            InternalState::SignatureHeader => SevenZFile::parse_signature_header(input),
+            InternalState::PackedStreams {
+                remaining,
+                start_header,
+            } => self.read_packed_streams(input, remaining, start_header),
+            InternalState::NextHeader {
+                remaining,
+                data,
+                crc,
+            } => self.read_next_header(input, remaining, data, crc),
+            InternalState::EntryStart => self.start_entry(),
+            InternalState::Data { remaining, crc } => self.read_data(remaining, crc),
+            InternalState::EntryEnd => self.end(),
+            InternalState::End => (0, InternalState::End, ParseResult::EndOfFile),
+            InternalState::Sentinel => unreachable!("parse_step is never called with Sentinel"),
+            InternalState::Error(error) => {
+                (0, InternalState::Error(error), ParseResult::Error(error))
+            }
+        }
+    }
+
+    fn parse_signature_header<'long, 'short>(
+        input: Input<'long, 'short>,
+    ) -> (usize, InternalState, ParseResult) {
+        if input.len() < SIGNATURE_HEADER_SIZE {
+            return (0, InternalState::SignatureHeader, ParseResult::NeedsInput);
+        }
+        match StartHeader::parse(&input[..SIGNATURE_HEADER_SIZE]) {
+            // An archive without files has no header.
+            Ok(start_header) if start_header.next_header_size == 0 => (
+                SIGNATURE_HEADER_SIZE,
+                InternalState::End,
+                ParseResult::EndOfFile,
+            ),
+            Ok(start_header) => (
+                SIGNATURE_HEADER_SIZE,
+                InternalState::PackedStreams {
+                    remaining: start_header.next_header_offset,
+                    start_header,
+                },
+                ParseResult::Continue,
+            ),
+            Err(e) => (0, InternalState::Error(e), ParseResult::Error(e)),
+        }
+    }
+
+    fn read_packed_streams<'long, 'short>(
+        &mut self,
+        input: Input<'long, 'short>,
+        remaining: u64,
+        start_header: StartHeader,
+    ) -> (usize, InternalState, ParseResult) {
+        let available = std::cmp::min(remaining, input.len() as u64) as usize;
+        self.archive.packed.extend_from_slice(&input[..available]);
+        let remaining = remaining - available as u64;
+        let state = if remaining > 0 {
+            InternalState::PackedStreams {
+                remaining,
+                start_header,
+            }
+        } else {
+            InternalState::NextHeader {
+                remaining: start_header.next_header_size,
+                data: Vec::new(),
+                crc: start_header.next_header_crc,
+            }
+        };
+        (available, state, ParseResult::Continue)
+    }
+
+    fn read_next_header<'long, 'short>(
+        &mut self,
+        input: Input<'long, 'short>,
+        remaining: u64,
+        mut data: Vec<u8>,
+        crc: u32,
+    ) -> (usize, InternalState, ParseResult) {
+        let available = std::cmp::min(remaining, input.len() as u64) as usize;
+        data.extend_from_slice(&input[..available]);
+        let remaining = remaining - available as u64;
+        if remaining > 0 {
+            let state = InternalState::NextHeader {
+                remaining,
+                data,
+                crc,
+            };
+            return (available, state, ParseResult::Continue);
+        }
+
+        match self.parse_next_header(&data, crc) {
+            Ok(()) => (available, InternalState::EntryStart, ParseResult::Continue),
+            Err(e) => (available, InternalState::Error(e), ParseResult::Error(e)),
+        }
+    }
+
+    fn parse_next_header(&mut self, data: &[u8], crc: u32) -> Result<(), SevenZError> {
+        if update_crc32(0, data) != crc {
+            return Err(SevenZError::ChecksumMismatch);
+        }
+        let header = match NextHeader::parse(data)? {
+            NextHeader::Header(header) => header,
+            NextHeader::Encoded(streams) => {
+                let data = self.archive.decode_header(streams, &self.options.limits)?;
+                match NextHeader::parse(&data)? {
+                    NextHeader::Header(header) => header,
+                    NextHeader::Encoded(_) => return Err(SevenZError::InvalidHeader),
+                }
+            }
+        };
+        self.archive.streams = header.streams;
+        self.archive.entries = header.entries.into_iter();
+        Ok(())
+    }
+
+    fn start_entry(&mut self) -> (usize, InternalState, ParseResult) {
+        let entry = match self.archive.entries.next() {
+            Some(entry) => entry,
+            // An archive without files.
+            None => return (0, InternalState::End, ParseResult::EndOfFile),
+        };
+        if self.options.limits.exceeds_size(entry.size) {
+            return (
+                0,
+                InternalState::Error(SevenZError::EntryTooLarge),
+                ParseResult::Error(SevenZError::EntryTooLarge),
+            );
+        }
+        if entry.has_stream {
+            if let Err(e) = self.archive.open_next_folder(&self.options.limits) {
+                return (0, InternalState::Error(e), ParseResult::Error(e));
+            }
+            self.archive.remaining_substreams -= 1;
+        }
+        let state = InternalState::Data {
+            remaining: entry.size,
+            crc: 0,
+        };
+        self.entry = Some(entry);
+        (0, state, ParseResult::Continue)
+    }
+
+    fn read_data(&mut self, remaining: u64, crc: u32) -> (usize, InternalState, ParseResult) {
+        if remaining == 0 {
+            let expected = self.entry.as_ref().and_then(|entry| entry.crc);
+            if expected.is_some_and(|expected| expected != crc) {
+                return (
+                    0,
+                    InternalState::Error(SevenZError::ChecksumMismatch),
+                    ParseResult::Error(SevenZError::ChecksumMismatch),
+                );
+            }
+            return (0, InternalState::EntryEnd, ParseResult::Continue);
+        }
+
+        match self.archive.decode(remaining) {
+            Ok(output) => {
+                let crc = update_crc32(crc, output);
+                self.output_len = output.len();
+                let state = InternalState::Data {
+                    remaining: remaining - output.len() as u64,
+                    crc,
+                };
+                (0, state, ParseResult::Output)
+            }
+            Err(e) => (0, InternalState::Error(e), ParseResult::Error(e)),
+        }
+    }
+
+    fn end(&mut self) -> (usize, InternalState, ParseResult) {
+        if self.archive.entries.len() == 0 {
+            return (0, InternalState::End, ParseResult::EndOfFile);
+        }
+        let next_file = SevenZFile {
+            state: InternalState::EntryStart,
+            unparsed: Vec::new(),
+            entry: None,
+            archive: std::mem::take(&mut self.archive),
+            output_len: 0,
+            options: self.options.clone(),
+        };
+        (0, InternalState::End, ParseResult::NextFile(next_file))
+    }
+}
+
+impl InternalState {
+    /// Whether the state reads the archive from the input.
+    fn reads_input(&self) -> bool {
+        matches!(
+            self,
+            InternalState::SignatureHeader
+                | InternalState::PackedStreams { .. }
+                | InternalState::NextHeader { .. }
+        )
+    }
+}
+
+/// Starts a stream of a 7z archive. The returned file is the first file of the archive.
+pub fn start_stream() -> SevenZFile {
+    start_stream_with(Options::default())
+}
+
+/// Starts a stream of a 7z archive that is parsed according to `options`.
+pub fn start_stream_with(options: Options) -> SevenZFile {
+    SevenZFile {
+        state: InternalState::SignatureHeader,
+        unparsed: Vec::new(),
+        entry: None,
+        archive: Box::default(),
+        output_len: 0,
+        options,
+    }
+}
+
+/// Starts a stream whose first bytes, `prefix`, were already read from the input
+/// to detect the format. They are parsed together with the input of the next `read`.
+pub(crate) fn start_stream_with_options_and_prefix(options: Options, prefix: &[u8]) -> SevenZFile {
+    let mut file = start_stream_with(options);
+    file.unparsed = prefix.to_vec();
+    file
+}
@@ -0,0 +1,632 @@
+use super::SevenZError;
+use crate::utils::update_crc32;
+
+/// The magic bytes that start a 7z archive.
+pub const SIGNATURE: &[u8] = b"7z\xbc\xaf\x27\x1c";
+/// The size of the signature header, which is followed by the packed streams.
+pub const SIGNATURE_HEADER_SIZE: usize = 32;
+
+/// Whether `input` starts with the 7z signature.
+pub fn is_7z_header(input: &[u8]) -> bool {
+    input.starts_with(SIGNATURE)
+}
+
+/// The property IDs that structure the header.
+mod id {
+    pub const END: u8 = 0x00;
+    pub const HEADER: u8 = 0x01;
+    pub const ARCHIVE_PROPERTIES: u8 = 0x02;
+    pub const ADDITIONAL_STREAMS_INFO: u8 = 0x03;
+    pub const MAIN_STREAMS_INFO: u8 = 0x04;
+    pub const FILES_INFO: u8 = 0x05;
+    pub const PACK_INFO: u8 = 0x06;
+    pub const UNPACK_INFO: u8 = 0x07;
+    pub const SUBSTREAMS_INFO: u8 = 0x08;
+    pub const SIZE: u8 = 0x09;
+    pub const CRC: u8 = 0x0a;
+    pub const FOLDER: u8 = 0x0b;
+    pub const CODERS_UNPACK_SIZE: u8 = 0x0c;
+    pub const NUM_UNPACK_STREAM: u8 = 0x0d;
+    pub const EMPTY_STREAM: u8 = 0x0e;
+    pub const EMPTY_FILE: u8 = 0x0f;
+    pub const ANTI: u8 = 0x10;
+    pub const NAME: u8 = 0x11;
+    pub const MTIME: u8 = 0x14;
+    pub const ATTRIBUTES: u8 = 0x15;
+    pub const ENCODED_HEADER: u8 = 0x17;
+}
+
+/// The part of the signature header that locates the header at the end of the archive.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct StartHeader {
+    /// The offset of the header from the end of the signature header.
+    pub next_header_offset: u64,
+    pub next_header_size: u64,
+    pub next_header_crc: u32,
+}
+
+impl StartHeader {
+    /// Parses the signature header. `input` must be `SIGNATURE_HEADER_SIZE` bytes long.
+    pub fn parse(input: &[u8]) -> Result<StartHeader, SevenZError> {
+        debug_assert_eq!(input.len(), SIGNATURE_HEADER_SIZE);
+        // Only the major version, which is 0, changes the format.
+        if !is_7z_header(input) || input[6] != 0 {
+            return Err(SevenZError::InvalidSignature);
+        }
+        let mut reader = Reader::new(&input[8..]);
+        let start_header_crc = reader.u32()?;
+        if update_crc32(0, &input[12..]) != start_header_crc {
+            return Err(SevenZError::ChecksumMismatch);
+        }
+        Ok(StartHeader {
+            next_header_offset: reader.u64()?,
+            next_header_size: reader.u64()?,
+            next_header_crc: reader.u32()?,
+        })
+    }
+}
+
+/// Reads the fields of the header.
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Reader<'a> {
+        Reader { data }
+    }
+
+    fn bytes(&mut self, len: u64) -> Result<&'a [u8], SevenZError> {
+        if len > self.data.len() as u64 {
+            return Err(SevenZError::InvalidHeader);
+        }
+        let (bytes, rest) = self.data.split_at(len as usize);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, SevenZError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, SevenZError> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn u64(&mut self) -> Result<u64, SevenZError> {
+        let bytes = self.bytes(8)?;
+        let mut value = [0; 8];
+        value.copy_from_slice(bytes);
+        Ok(u64::from_le_bytes(value))
+    }
+
+    /// Reads a variable-length number. The count of the leading one bits
+    /// of the first byte is the count of the little-endian bytes that follow.
+    /// The rest of the first byte holds the highest bits of the number.
+    fn number(&mut self) -> Result<u64, SevenZError> {
+        let first = self.byte()?;
+        let mut value = 0;
+        for i in 0..8 {
+            let mask = 0x80 >> i;
+            if first & mask == 0 {
+                return Ok(value | u64::from(first & (mask - 1)) << (8 * i));
+            }
+            value |= u64::from(self.byte()?) << (8 * i);
+        }
+        Ok(value)
+    }
+
+    /// Reads a count of items. Every item takes at least a byte,
+    /// so counts that exceed the rest of the header are rejected
+    /// before anything is allocated for them.
+    fn count(&mut self) -> Result<usize, SevenZError> {
+        let count = self.number()?;
+        if count > self.data.len() as u64 {
+            return Err(SevenZError::InvalidHeader);
+        }
+        Ok(count as usize)
+    }
+
+    fn expect(&mut self, property_id: u8) -> Result<(), SevenZError> {
+        if self.byte()? == property_id {
+            Ok(())
+        } else {
+            Err(SevenZError::InvalidHeader)
+        }
+    }
+
+    /// Reads `len` bits, most significant bit first.
+    fn bit_vector(&mut self, len: usize) -> Result<Vec<bool>, SevenZError> {
+        let bytes = self.bytes((len as u64).div_ceil(8))?;
+        Ok((0..len)
+            .map(|i| bytes[i / 8] & (0x80 >> (i % 8)) != 0)
+            .collect())
+    }
+
+    /// Reads which of `len` items are defined. A leading non-zero byte means all of them.
+    fn defined_vector(&mut self, len: usize) -> Result<Vec<bool>, SevenZError> {
+        if self.byte()? != 0 {
+            Ok(vec![true; len])
+        } else {
+            self.bit_vector(len)
+        }
+    }
+
+    fn digests(&mut self, len: usize) -> Result<Vec<Option<u32>>, SevenZError> {
+        self.defined_vector(len)?
+            .into_iter()
+            .map(|defined| {
+                if defined {
+                    self.u32().map(Some)
+                } else {
+                    Ok(None)
+                }
+            })
+            .collect()
+    }
+
+    /// Reads the "external" byte of a property. Data stored in the
+    /// additional streams isn't supported.
+    fn not_external(&mut self) -> Result<(), SevenZError> {
+        if self.byte()? == 0 {
+            Ok(())
+        } else {
+            Err(SevenZError::InvalidHeader)
+        }
+    }
+}
+
+/// A compression method, filter or encryption of a folder.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Coder {
+    pub method_id: Vec<u8>,
+    pub num_in_streams: u64,
+    pub num_out_streams: u64,
+    pub properties: Vec<u8>,
+}
+
+/// A unit of packed data that is decoded as a whole. The data of several
+/// files, the substreams, may be compressed together in a folder.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Folder {
+    pub coders: Vec<Coder>,
+    /// Pairs of coder input and output stream indices that are connected.
+    pub bind_pairs: Vec<(u64, u64)>,
+    /// The coder input streams that are read from pack streams.
+    pub packed_streams: Vec<u64>,
+    /// The sizes of all of the coder output streams.
+    pub unpack_sizes: Vec<u64>,
+    /// The CRC-32 of the unpacked data.
+    pub crc: Option<u32>,
+}
+
+impl Folder {
+    /// The size of the unpacked data: that of the output stream
+    /// that isn't connected to another coder.
+    pub fn unpack_size(&self) -> u64 {
+        (0..self.unpack_sizes.len() as u64)
+            .find(|&out_index| self.bind_pairs.iter().all(|&(_, out)| out != out_index))
+            .map_or(0, |out_index| self.unpack_sizes[out_index as usize])
+    }
+}
+
+/// The data of a file in a folder.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct SubStream {
+    pub size: u64,
+    pub crc: Option<u32>,
+}
+
+/// Describes the packed streams and how they are decoded.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct StreamsInfo {
+    /// The offset of the first pack stream from the end of the signature header.
+    pub pack_pos: u64,
+    pub pack_sizes: Vec<u64>,
+    pub folders: Vec<Folder>,
+    /// The substreams of each folder.
+    pub substreams: Vec<Vec<SubStream>>,
+}
+
+/// A file or a directory in the archive.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Entry {
+    /// The name, converted from UTF-16 to UTF-8.
+    pub name: Vec<u8>,
+    /// Whether the entry has data. Directories and empty files don't.
+    pub has_stream: bool,
+    pub is_dir: bool,
+    /// Whether the entry marks a file for deletion in an update archive.
+    pub is_anti: bool,
+    pub size: u64,
+    pub crc: Option<u32>,
+    /// The last modification time, as a Windows `FILETIME`: 100-nanosecond
+    /// intervals since the start of 1601.
+    pub modified: Option<u64>,
+    /// The Windows file attributes. If bit 15 is set, the high 16 bits
+    /// hold the Unix mode.
+    pub attributes: Option<u32>,
+}
+
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Header {
+    pub streams: StreamsInfo,
+    pub entries: Vec<Entry>,
+}
+
+/// The header at the end of the archive, which may be itself compressed.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum NextHeader {
+    Header(Header),
+    /// The header is the unpacked data of the single folder of these streams.
+    Encoded(StreamsInfo),
+}
+
+impl NextHeader {
+    pub fn parse(data: &[u8]) -> Result<NextHeader, SevenZError> {
+        let mut reader = Reader::new(data);
+        match reader.byte()? {
+            id::HEADER => Ok(NextHeader::Header(parse_header(&mut reader)?)),
+            id::ENCODED_HEADER => Ok(NextHeader::Encoded(parse_streams_info(&mut reader)?)),
+            _ => Err(SevenZError::InvalidHeader),
+        }
+    }
+}
+
+fn parse_header(reader: &mut Reader) -> Result<Header, SevenZError> {
+    let mut property_id = reader.byte()?;
+    if property_id == id::ARCHIVE_PROPERTIES {
+        loop {
+            if reader.byte()? == id::END {
+                break;
+            }
+            let size = reader.number()?;
+            reader.bytes(size)?;
+        }
+        property_id = reader.byte()?;
+    }
+    if property_id == id::ADDITIONAL_STREAMS_INFO {
+        parse_streams_info(reader)?;
+        property_id = reader.byte()?;
+    }
+    let mut header = Header::default();
+    if property_id == id::MAIN_STREAMS_INFO {
+        header.streams = parse_streams_info(reader)?;
+        property_id = reader.byte()?;
+    }
+    if property_id == id::FILES_INFO {
+        header.entries = parse_files_info(reader, &header.streams)?;
+        property_id = reader.byte()?;
+    }
+    if property_id != id::END {
+        return Err(SevenZError::InvalidHeader);
+    }
+    Ok(header)
+}
+
+fn parse_streams_info(reader: &mut Reader) -> Result<StreamsInfo, SevenZError> {
+    let mut streams = StreamsInfo::default();
+    let mut property_id = reader.byte()?;
+
+    if property_id == id::PACK_INFO {
+        streams.pack_pos = reader.number()?;
+        let num_pack_streams = reader.count()?;
+        property_id = reader.byte()?;
+        if property_id == id::SIZE {
+            streams.pack_sizes = (0..num_pack_streams)
+                .map(|_| reader.number())
+                .collect::<Result<_, _>>()?;
+            property_id = reader.byte()?;
+        }
+        if property_id == id::CRC {
+            // The CRCs of the packed data are redundant with those of the unpacked data.
+            reader.digests(num_pack_streams)?;
+            property_id = reader.byte()?;
+        }
+        if property_id != id::END || streams.pack_sizes.len() != num_pack_streams {
+            return Err(SevenZError::InvalidHeader);
+        }
+        property_id = reader.byte()?;
+    }
+
+    if property_id == id::UNPACK_INFO {
+        reader.expect(id::FOLDER)?;
+        let num_folders = reader.count()?;
+        reader.not_external()?;
+        streams.folders = (0..num_folders)
+            .map(|_| parse_folder(reader))
+            .collect::<Result<_, _>>()?;
+        reader.expect(id::CODERS_UNPACK_SIZE)?;
+        for folder in &mut streams.folders {
+            for size in &mut folder.unpack_sizes {
+                *size = reader.number()?;
+            }
+        }
+        property_id = reader.byte()?;
+        if property_id == id::CRC {
+            let crcs = reader.digests(num_folders)?;
+            for (folder, crc) in streams.folders.iter_mut().zip(crcs) {
+                folder.crc = crc;
+            }
+            property_id = reader.byte()?;
+        }
+        if property_id != id::END {
+            return Err(SevenZError::InvalidHeader);
+        }
+        property_id = reader.byte()?;
+    }
+
+    if property_id == id::SUBSTREAMS_INFO {
+        streams.substreams = parse_substreams_info(reader, &streams.folders)?;
+        property_id = reader.byte()?;
+    } else {
+        streams.substreams = streams
+            .folders
+            .iter()
+            .map(|folder| {
+                vec![SubStream {
+                    size: folder.unpack_size(),
+                    crc: folder.crc,
+                }]
+            })
+            .collect();
+    }
+
+    if property_id != id::END {
+        return Err(SevenZError::InvalidHeader);
+    }
+    Ok(streams)
+}
+
+fn parse_folder(reader: &mut Reader) -> Result<Folder, SevenZError> {
+    let num_coders = reader.count()?;
+    let mut coders = Vec::new();
+    for _ in 0..num_coders {
+        let flags = reader.byte()?;
+        // The flag of alternative methods was never used.
+        if flags & 0x80 != 0 {
+            return Err(SevenZError::InvalidHeader);
+        }
+        let method_id = reader.bytes(u64::from(flags & 0x0f))?.to_vec();
+        let (num_in_streams, num_out_streams) = if flags & 0x10 != 0 {
+            (reader.number()?, reader.number()?)
+        } else {
+            (1, 1)
+        };
+        let properties = if flags & 0x20 != 0 {
+            let size = reader.number()?;
+            reader.bytes(size)?.to_vec()
+        } else {
+            Vec::new()
+        };
+        coders.push(Coder {
+            method_id,
+            num_in_streams,
+            num_out_streams,
+            properties,
+        });
+    }
+
+    // The stream counts of the coders are limited by the rest of the header.
+    let total = |count: fn(&Coder) -> u64| {
+        coders
+            .iter()
+            .try_fold(0u64, |total, coder| total.checked_add(count(coder)))
+            .filter(|&total| total <= reader.data.len() as u64 + 1)
+            .ok_or(SevenZError::InvalidHeader)
+    };
+    let total_in = total(|coder| coder.num_in_streams)?;
+    let total_out = total(|coder| coder.num_out_streams)?;
+    if coders.is_empty() || total_out == 0 {
+        return Err(SevenZError::InvalidHeader);
+    }
+
+    let bind_pairs = (0..total_out - 1)
+        .map(|_| Ok((reader.number()?, reader.number()?)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let num_packed_streams = total_in
+        .checked_sub(total_out - 1)
+        .filter(|&num| num > 0)
+        .ok_or(SevenZError::InvalidHeader)?;
+    let packed_streams = if num_packed_streams == 1 {
+        let unbound = (0..total_in)
+            .find(|&in_index| bind_pairs.iter().all(|&(bound, _)| bound != in_index))
+            .ok_or(SevenZError::InvalidHeader)?;
+        vec![unbound]
+    } else {
+        (0..num_packed_streams)
+            .map(|_| reader.number())
+            .collect::<Result<_, _>>()?
+    };
+
+    Ok(Folder {
+        coders,
+        bind_pairs,
+        packed_streams,
+        unpack_sizes: vec![0; total_out as usize],
+        crc: None,
+    })
+}
+
+fn parse_substreams_info(
+    reader: &mut Reader,
+    folders: &[Folder],
+) -> Result<Vec<Vec<SubStream>>, SevenZError> {
+    let mut property_id = reader.byte()?;
+    let mut counts = vec![1; folders.len()];
+    if property_id == id::NUM_UNPACK_STREAM {
+        for count in &mut counts {
+            *count = reader.count()?;
+        }
+        property_id = reader.byte()?;
+    }
+
+    // The size of the last substream is what's left of the folder.
+    let has_sizes = property_id == id::SIZE;
+    let mut substreams = Vec::new();
+    for (folder, &count) in folders.iter().zip(&counts) {
+        let mut sizes = Vec::new();
+        if count > 1 {
+            if !has_sizes {
+                return Err(SevenZError::InvalidHeader);
+            }
+            for _ in 0..count - 1 {
+                sizes.push(reader.number()?);
+            }
+        }
+        if count > 0 {
+            let total = sizes
+                .iter()
+                .try_fold(0u64, |total, &size| total.checked_add(size));
+            let last = total
+                .and_then(|total| folder.unpack_size().checked_sub(total))
+                .ok_or(SevenZError::InvalidHeader)?;
+            sizes.push(last);
+        }
+        substreams.push(sizes);
+    }
+    if has_sizes {
+        property_id = reader.byte()?;
+    }
+
+    // The CRCs are only listed for the substreams whose CRC isn't known
+    // from the folder already.
+    let known = |folder: &Folder, count| count == 1 && folder.crc.is_some();
+    let num_unknown = folders
+        .iter()
+        .zip(&counts)
+        .filter(|&(folder, &count)| !known(folder, count))
+        .map(|(_, &count)| count)
+        .sum();
+    let mut crcs = Vec::new();
+    if property_id == id::CRC {
+        crcs = reader.digests(num_unknown)?;
+        property_id = reader.byte()?;
+    }
+    if property_id != id::END {
+        return Err(SevenZError::InvalidHeader);
+    }
+
+    let mut crcs = crcs.into_iter();
+    Ok(folders
+        .iter()
+        .zip(&counts)
+        .zip(substreams)
+        .map(|((folder, &count), sizes)| {
+            let is_known = known(folder, count);
+            sizes
+                .into_iter()
+                .map(|size| SubStream {
+                    size,
+                    crc: if is_known {
+                        folder.crc
+                    } else {
+                        crcs.next().flatten()
+                    },
+                })
+                .collect()
+        })
+        .collect())
+}
+
+/// Reads the `defined_vector`, the external byte and the values of a property.
+fn parse_optional_values<T>(
+    reader: &mut Reader,
+    len: usize,
+    mut value: impl FnMut(&mut Reader) -> Result<T, SevenZError>,
+) -> Result<Vec<Option<T>>, SevenZError> {
+    let defined = reader.defined_vector(len)?;
+    reader.not_external()?;
+    defined
+        .into_iter()
+        .map(|defined| {
+            if defined {
+                value(reader).map(Some)
+            } else {
+                Ok(None)
+            }
+        })
+        .collect()
+}
+
+fn parse_names(reader: &mut Reader, len: usize) -> Result<Vec<Vec<u8>>, SevenZError> {
+    reader.not_external()?;
+    let mut names = Vec::new();
+    let mut units = Vec::new();
+    while names.len() < len {
+        let bytes = reader.bytes(2)?;
+        match u16::from_le_bytes([bytes[0], bytes[1]]) {
+            0 => {
+                names.push(String::from_utf16_lossy(&units).into_bytes());
+                units.clear();
+            }
+            unit => units.push(unit),
+        }
+    }
+    Ok(names)
+}
+
+fn parse_files_info(reader: &mut Reader, streams: &StreamsInfo) -> Result<Vec<Entry>, SevenZError> {
+    let num_files = reader.count()?;
+    let mut empty_stream = vec![false; num_files];
+    let mut empty_file = Vec::new();
+    let mut anti = Vec::new();
+    let mut names = Vec::new();
+    let mut modified = Vec::new();
+    let mut attributes = Vec::new();
+
+    loop {
+        let property_type = reader.byte()?;
+        if property_type == id::END {
+            break;
+        }
+        let size = reader.number()?;
+        let mut property = Reader::new(reader.bytes(size)?);
+        let num_empty_streams = empty_stream.iter().filter(|&&empty| empty).count();
+        match property_type {
+            id::EMPTY_STREAM => empty_stream = property.bit_vector(num_files)?,
+            id::EMPTY_FILE => empty_file = property.bit_vector(num_empty_streams)?,
+            id::ANTI => anti = property.bit_vector(num_empty_streams)?,
+            id::NAME => names = parse_names(&mut property, num_files)?,
+            id::MTIME => {
+                modified = parse_optional_values(&mut property, num_files, |reader| reader.u64())?
+            }
+            id::ATTRIBUTES => {
+                attributes = parse_optional_values(&mut property, num_files, |reader| reader.u32())?
+            }
+            // The creation and access times, comments and padding are skipped.
+            _ => (),
+        }
+    }
+
+    let mut substreams = streams.substreams.iter().flatten();
+    let mut empty_index = 0;
+    let mut entries = Vec::new();
+    for (i, &empty) in empty_stream.iter().enumerate() {
+        let mut entry = Entry {
+            name: names.get(i).cloned().unwrap_or_default(),
+            has_stream: !empty,
+            is_dir: false,
+            is_anti: false,
+            size: 0,
+            crc: None,
+            modified: modified.get(i).cloned().flatten(),
+            attributes: attributes.get(i).cloned().flatten(),
+        };
+        if entry.has_stream {
+            let substream = substreams.next().ok_or(SevenZError::InvalidHeader)?;
+            entry.size = substream.size;
+            entry.crc = substream.crc;
+        } else {
+            entry.is_dir = !empty_file.get(empty_index).cloned().unwrap_or(false);
+            entry.is_anti = anti.get(empty_index).cloned().unwrap_or(false);
+            empty_index += 1;
+        }
+        entries.push(entry);
+    }
+    if substreams.next().is_some() {
+        return Err(SevenZError::InvalidHeader);
+    }
+    Ok(entries)
+}
@@ -0,0 +1,540 @@
+//! Streaming parsing of tar archives in the ustar, GNU and pax formats.
+//!
+//! Each entry of the archive is a file of its own: its data is returned
+//! as output, and the next entry as `State::NextFile`, like the entries
+//! of a zip archive. The data isn't compressed, so it's copied from the
+//! input to the output buffer as it streams by.
+//!
+//! GNU long name and long link entries and pax extended headers aren't
+//! returned as entries. They are applied to the header of the entry that
+//! follows them. Pax global headers are skipped.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::chain::{self, Chain};
+use crate::gzip::{self, GZipFile};
+use crate::input_helper::{Input, InputHandler};
+use crate::{State, Summary};
+
+pub mod headers;
+
+use self::headers::{EntryType, Header, PaxRecord, BLOCK_SIZE};
+
+const OUTPUT_BUFFER_SIZE: usize = 32 * 1024;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum TarError {
+    InvalidHeader,
+    /// The checksum of a header didn't match its contents.
+    ChecksumMismatch,
+    InvalidPaxHeader,
+}
+
+impl std::error::Error for TarError {
+    fn description(&self) -> &str {
+        "tar parsing error"
+    }
+}
+
+impl std::fmt::Display for TarError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        use self::TarError::*;
+        match self {
+            InvalidHeader => write!(f, "invalid header"),
+            ChecksumMismatch => write!(f, "header checksum mismatch"),
+            InvalidPaxHeader => write!(f, "invalid pax extended header"),
+        }
+    }
+}
+
+pub struct TarFile {
+    state: InternalState,
+    unparsed: Vec<u8>,
+    header: Option<Header>,
+    output: Vec<u8>,
+}
+
+impl std::fmt::Debug for TarFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("TarFile")
+            .field("state", &self.state)
+            .field("unparsed", &self.unparsed)
+            .finish()
+    }
+}
+
+/// The metadata that the entries before a header set for it.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+struct Extensions {
+    long_name: Option<Vec<u8>>,
+    long_link_name: Option<Vec<u8>>,
+    pax_records: Vec<PaxRecord>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum InternalState {
+    Init(Extensions),
+    /// Reading the data of an entry that describes the next one.
+    Metadata {
+        entry_type: EntryType,
+        remaining: u64,
+        data: Vec<u8>,
+        extensions: Extensions,
+    },
+    Data {
+        remaining: u64,
+    },
+    EntryEnd,
+    End,
+    Skip {
+        remaining: u64,
+        then: Box<InternalState>,
+    },
+    Sentinel,
+    /// The stream failed with the error, which later reads return again.
+    Error(TarError),
+}
+
+impl InternalState {
+    /// Returns a state that skips `bytes` bytes of input before continuing with `then`.
+    fn skip_then(bytes: u64, then: InternalState) -> InternalState {
+        if bytes == 0 {
+            then
+        } else {
+            InternalState::Skip {
+                remaining: bytes,
+                then: Box::new(then),
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
+enum ParseResult {
+    Continue,
+    NeedsInput,
+    Output,
+    Error(TarError),
+    NextFile(TarFile),
+    EndOfFile,
+}
+
+impl TarFile {
+    pub fn get_output(&self) -> &[u8] {
+        &self.output
+    }
+
+    /// The header of the entry, once it has been parsed.
+    pub fn header(&self) -> Option<&Header> {
+        self.header.as_ref()
+    }
+
+    pub fn filename(&self) -> Option<&[u8]> {
+        self.header.as_ref().map(|header| &header.name[..])
+    }
+
+    pub fn read<'i, 's>(&'s mut self, input: &'i [u8]) -> Result<State<'i, 's, TarFile>, TarError> {
+        let mut ihandler = InputHandler::take_storage(&mut self.unparsed, input);
+        let mut unparsed = ihandler.get_unparsed();
+
+        loop {
+            let mut state = InternalState::Sentinel;
+            std::mem::swap(&mut self.state, &mut state);
+            let (bytes_consumed, new_state, res) = self.parse_step(state, unparsed);
+            unparsed = ihandler.consumed(bytes_consumed);
+            self.state = new_state;
+            match res {
+                ParseResult::Continue => (),
+                ParseResult::NeedsInput => {
+                    let extended_len = ihandler.extend_input();
+                    // Nothing in input left to extend, so we need the user to provide more
+                    if extended_len == 0 {
+                        ihandler.return_storage(&mut self.unparsed);
+                        return Ok(State::NeedsInput);
+                    }
+                    unparsed = ihandler.get_unparsed();
+                }
+                ParseResult::Output => {
+                    let unparsed_input = ihandler.take_rest(&mut self.unparsed);
+                    return Ok(State::HasOutput {
+                        unparsed_input,
+                        output: &self.output,
+                    });
+                }
+                ParseResult::NextFile(next_file) => {
+                    let unparsed_input = ihandler.take_rest(&mut self.unparsed);
+                    return Ok(State::NextFile {
+                        unparsed_input,
+                        next_file,
+                    });
+                }
+                ParseResult::EndOfFile => return Ok(State::EndOfFile),
+                ParseResult::Error(err) => return Err(err),
+            };
+            if unparsed.is_empty() {
+                ihandler.return_storage(&mut self.unparsed);
+                return Ok(State::NeedsInput);
+            }
+        }
+    }
+
+    pub fn read_with<'i>(
+        &mut self,
+        mut input: &'i [u8],
+        mut callback: impl FnMut(&[u8]),
+    ) -> Result<State<'i, 'i, TarFile>, TarError> {
+        loop {
+            match self.read(input)?.try_no_output() {
+                Err((unparsed_input, output)) => {
+                    input = unparsed_input;
+                    callback(output);
+                }
+                Ok(state) => return Ok(state),
+            }
+        }
+    }
+
+    fn parse_step<'long, 'short>(
+        &'short mut self,
+        state: InternalState,
+        input: Input<'long, 'short>,
+    ) -> (usize, InternalState, ParseResult) {
+        match state {
+            InternalState::Init(extensions) => self.parse_header(input, extensions),
+            InternalState::Metadata {
+                entry_type,
+                remaining,
+                data,
+                extensions,
+            } => TarFile::read_metadata(input, entry_type, remaining, data, extensions),
+            InternalState::Data { remaining } => self.read_data(input, remaining),
+            InternalState::EntryEnd => TarFile::end(input),
+            InternalState::End => (0, InternalState::End, ParseResult::EndOfFile),
+            InternalState::Skip { remaining, then } => TarFile::skip(input, remaining, then),
+            InternalState::Sentinel => unreachable!("parse_step is never called with Sentinel"),
+            InternalState::Error(error) => {
+                (0, InternalState::Error(error), ParseResult::Error(error))
+            }
+        }
+    }
+
+    fn parse_header<'long, 'short>(
+        &mut self,
+        input: Input<'long, 'short>,
+        extensions: Extensions,
+    ) -> (usize, InternalState, ParseResult) {
+        if input.len() < BLOCK_SIZE {
+            return (0, InternalState::Init(extensions), ParseResult::NeedsInput);
+        }
+        let block = &input[..BLOCK_SIZE];
+        if headers::is_end_block(block) {
+            // An archive without entries.
+            return (BLOCK_SIZE, InternalState::End, ParseResult::EndOfFile);
+        }
+
+        let mut header = match Header::parse(block) {
+            Ok(header) => header,
+            Err(e) => return (0, InternalState::Error(e), ParseResult::Error(e)),
+        };
+        if header.entry_type.is_metadata() {
+            let state = InternalState::Metadata {
+                entry_type: header.entry_type,
+                remaining: header.size,
+                data: Vec::new(),
+                extensions,
+            };
+            return (BLOCK_SIZE, state, ParseResult::Continue);
+        }
+
+        if let Some(name) = extensions.long_name {
+            header.name = name;
+        }
+        if let Some(link_name) = extensions.long_link_name {
+            header.link_name = link_name;
+        }
+        if let Err(e) = header.apply_pax_records(&extensions.pax_records) {
+            return (0, InternalState::Error(e), ParseResult::Error(e));
+        }
+        let size = header.data_size();
+        self.header = Some(header);
+        (
+            BLOCK_SIZE,
+            InternalState::Data { remaining: size },
+            ParseResult::Continue,
+        )
+    }
+
+    /// Buffers the data of a metadata entry and applies it to the extensions
+    /// of the next header.
+    fn read_metadata<'long, 'short>(
+        input: Input<'long, 'short>,
+        entry_type: EntryType,
+        remaining: u64,
+        mut data: Vec<u8>,
+        mut extensions: Extensions,
+    ) -> (usize, InternalState, ParseResult) {
+        let available = std::cmp::min(remaining, input.len() as u64) as usize;
+        data.extend_from_slice(&input[..available]);
+        let remaining = remaining - available as u64;
+        if remaining > 0 {
+            let state = InternalState::Metadata {
+                entry_type,
+                remaining,
+                data,
+                extensions,
+            };
+            return (available, state, ParseResult::Continue);
+        }
+
+        let padding = headers::padding(data.len() as u64);
+        match entry_type {
+            EntryType::GnuLongName => extensions.long_name = Some(without_nul(data)),
+            EntryType::GnuLongLink => extensions.long_link_name = Some(without_nul(data)),
+            EntryType::PaxExtended => match headers::parse_pax_records(&data) {
+                Ok(records) => extensions.pax_records.extend(records),
+                Err(e) => return (0, InternalState::Error(e), ParseResult::Error(e)),
+            },
+            _ => (),
+        }
+        (
+            available,
+            InternalState::skip_then(padding, InternalState::Init(extensions)),
+            ParseResult::Continue,
+        )
+    }
+
+    fn read_data<'long, 'short>(
+        &mut self,
+        input: Input<'long, 'short>,
+        remaining: u64,
+    ) -> (usize, InternalState, ParseResult) {
+        if remaining == 0 {
+            let size = self.header.as_ref().map_or(0, Header::data_size);
+            return (
+                0,
+                InternalState::skip_then(headers::padding(size), InternalState::EntryEnd),
+                ParseResult::Continue,
+            );
+        }
+        if input.is_empty() {
+            return (
+                0,
+                InternalState::Data { remaining },
+                ParseResult::NeedsInput,
+            );
+        }
+
+        let available = std::cmp::min(remaining, input.len() as u64) as usize;
+        let available = std::cmp::min(available, OUTPUT_BUFFER_SIZE);
+        self.output.clear();
+        self.output.extend_from_slice(&input[..available]);
+        (
+            available,
+            InternalState::Data {
+                remaining: remaining - available as u64,
+            },
+            ParseResult::Output,
+        )
+    }
+
+    /// Checks whether the next block starts another entry or ends the archive.
+    fn end<'long, 'short>(input: Input<'long, 'short>) -> (usize, InternalState, ParseResult) {
+        if input.len() < BLOCK_SIZE {
+            return (0, InternalState::EntryEnd, ParseResult::NeedsInput);
+        }
+        if headers::is_end_block(&input[..BLOCK_SIZE]) {
+            // The rest of the end marker and the padding of the last record are ignored.
+            return (BLOCK_SIZE, InternalState::End, ParseResult::EndOfFile);
+        }
+        // The next entry parses its own header. The block may have been
+        // buffered from several inputs, so it's handed over with the entry.
+        let mut next_file = start_stream();
+        next_file.unparsed = input[..BLOCK_SIZE].to_vec();
+        (
+            BLOCK_SIZE,
+            InternalState::End,
+            ParseResult::NextFile(next_file),
+        )
+    }
+
+    /// Consumes input until `remaining` bytes are skipped, then continues with `then`.
+    fn skip<'long, 'short>(
+        input: Input<'long, 'short>,
+        remaining: u64,
+        then: Box<InternalState>,
+    ) -> (usize, InternalState, ParseResult) {
+        let skipped = std::cmp::min(remaining, input.len() as u64);
+        let remaining = remaining - skipped;
+        let state = if remaining == 0 {
+            *then
+        } else {
+            InternalState::Skip { remaining, then }
+        };
+        (skipped as usize, state, ParseResult::Continue)
+    }
+}
+
+impl chain::Stream for TarFile {
+    type Error = TarError;
+
+    fn read<'i, 's>(&'s mut self, input: &'i [u8]) -> Result<State<'i, 's, TarFile>, TarError> {
+        TarFile::read(self, input)
+    }
+
+    fn get_output(&self) -> &[u8] {
+        TarFile::get_output(self)
+    }
+
+    fn name(&self) -> Option<&[u8]> {
+        self.filename()
+    }
+}
+
+impl chain::Container for TarFile {
+    type Options = ();
+
+    const PROBE_LEN: usize = BLOCK_SIZE;
+
+    fn is_start(output: &[u8]) -> bool {
+        headers::is_tar_header(output)
+    }
+
+    fn start(_probe: &[u8], _options: &()) -> TarFile {
+        start_stream()
+    }
+}
+
+/// A gzip stream that may contain a tar archive. If the decompressed data
+/// starts with a tar header, each entry of the archive is a file of its own,
+/// like with `TarFile`. Otherwise the gzip members are returned as they are,
+/// like with `GZipFile`.
+pub struct TarGzFile(Box<Chain<GZipFile, TarFile>>);
+
+impl std::fmt::Debug for TarGzFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("TarGzFile")
+            .field("gzip", &self.0.outer())
+            .field("tar", &self.0.inner())
+            .finish()
+    }
+}
+
+impl TarGzFile {
+    pub fn get_output(&self) -> &[u8] {
+        self.0.get_output()
+    }
+
+    /// Whether the gzip stream contains a tar archive. This is known
+    /// once the first output of the stream has been returned.
+    pub fn is_tar(&self) -> bool {
+        self.0.is_piped()
+    }
+
+    /// The header of the tar entry, once it has been parsed.
+    pub fn header(&self) -> Option<&Header> {
+        self.0.inner().and_then(TarFile::header)
+    }
+
+    /// The name of the tar entry or, if the stream doesn't contain
+    /// a tar archive, the name of the gzip member.
+    pub fn filename(&self) -> Option<&[u8]> {
+        self.0.name()
+    }
+
+    /// The modification time of the tar entry or, if the stream doesn't contain
+    /// a tar archive, that of the gzip member.
+    pub fn last_modified(&self) -> Option<SystemTime> {
+        match self.0.inner() {
+            Some(tar) => UNIX_EPOCH.checked_add(Duration::from_secs(tar.header()?.mtime)),
+            None => self.0.outer()?.last_modified(),
+        }
+    }
+
+    /// The size of the tar entry, once its header has been parsed. The size of
+    /// a gzip member isn't known before its end, so it's `None` if the stream
+    /// doesn't contain a tar archive.
+    pub fn size(&self) -> Option<u64> {
+        self.header().map(|header| header.size)
+    }
+
+    /// Signals that the input has ended. Returns an error if the gzip stream
+    /// was cut short, like `GZipFile::finish`.
+    /// The progress of the gzip stream around the tar archive. See `GZipFile::progress`.
+    pub fn gzip_progress(&self) -> Option<Summary> {
+        self.0.outer().map(GZipFile::progress)
+    }
+
+    pub fn finish(&self) -> Result<(), crate::Error> {
+        match self.0.outer() {
+            Some(gzip) => Ok(gzip.finish()?),
+            None => Ok(()),
+        }
+    }
+
+    pub fn read<'i, 's>(
+        &'s mut self,
+        input: &'i [u8],
+    ) -> Result<State<'i, 's, TarGzFile>, crate::Error> {
+        Ok(self
+            .0
+            .read(input)?
+            .map_file(|next| TarGzFile(Box::new(next))))
+    }
+
+    pub fn read_with<'i>(
+        &mut self,
+        mut input: &'i [u8],
+        mut callback: impl FnMut(&[u8]),
+    ) -> Result<State<'i, 'i, TarGzFile>, crate::Error> {
+        loop {
+            match self.read(input)?.try_no_output() {
+                Err((unparsed_input, output)) => {
+                    input = unparsed_input;
+                    callback(output);
+                }
+                Ok(state) => return Ok(state),
+            }
+        }
+    }
+}
+
+/// GNU long names are NUL-terminated.
+fn without_nul(mut data: Vec<u8>) -> Vec<u8> {
+    if let Some(end) = data.iter().position(|&byte| byte == 0) {
+        data.truncate(end);
+    }
+    data
+}
+
+/// Starts a stream of a tar archive. The returned file is the first entry.
+pub fn start_stream() -> TarFile {
+    TarFile {
+        state: InternalState::Init(Extensions::default()),
+        unparsed: Vec::new(),
+        header: None,
+        output: Vec::new(),
+    }
+}
+
+/// Starts a gzip stream that may contain a tar archive.
+pub fn start_tar_gz_stream() -> TarGzFile {
+    TarGzFile(Box::new(Chain::new(gzip::start_stream())))
+}
+
+/// Starts a gzip stream that may contain a tar archive, and that is parsed
+/// according to `options`.
+pub fn start_tar_gz_stream_with(options: gzip::Options) -> TarGzFile {
+    TarGzFile(Box::new(Chain::new(gzip::start_stream_with(options))))
+}
+
+/// Starts a gzip stream whose first bytes, `prefix`, were already read from the input
+/// to detect the format. They are parsed together with the input of the next `read`.
+pub(crate) fn start_tar_gz_stream_with_options_and_prefix(
+    options: gzip::Options,
+    prefix: &[u8],
+) -> TarGzFile {
+    TarGzFile(Box::new(Chain::new(
+        gzip::start_stream_with_options_and_prefix(options, prefix),
+    )))
+}
@@ -0,0 +1,304 @@
+use super::TarError;
+
+/// Headers and the data of the entries are stored in blocks of this size.
+pub const BLOCK_SIZE: usize = 512;
+
+/// The magic and version of a POSIX ustar header.
+const USTAR_MAGIC: &[u8] = b"ustar\x0000";
+/// The magic and version of a GNU header, which has no name prefix field.
+const GNU_MAGIC: &[u8] = b"ustar  \x00";
+
+/// Whether `input` starts with a tar header block in the ustar or the GNU format.
+pub fn is_tar_header(input: &[u8]) -> bool {
+    input.len() >= BLOCK_SIZE
+        && (input[257..265] == *USTAR_MAGIC || input[257..265] == *GNU_MAGIC)
+        && parse_numeric(&input[148..156]) == Some(checksum(&input[..BLOCK_SIZE]))
+}
+
+/// Whether `block` is one of the zero blocks that end an archive.
+pub fn is_end_block(block: &[u8]) -> bool {
+    block.iter().all(|&byte| byte == 0)
+}
+
+/// The number of padding bytes after `size` bytes of entry data.
+pub fn padding(size: u64) -> u64 {
+    (BLOCK_SIZE as u64 - size % BLOCK_SIZE as u64) % BLOCK_SIZE as u64
+}
+
+/// A key and a value of a pax extended header.
+pub type PaxRecord = (Vec<u8>, Vec<u8>);
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Format {
+    /// The original format, which has no magic.
+    V7,
+    Ustar,
+    Gnu,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum EntryType {
+    Regular,
+    HardLink,
+    Symlink,
+    CharDevice,
+    BlockDevice,
+    Directory,
+    Fifo,
+    Contiguous,
+    /// The data is the name of the next entry (GNU).
+    GnuLongName,
+    /// The data is the link name of the next entry (GNU).
+    GnuLongLink,
+    /// The data holds pax records for the next entry.
+    PaxExtended,
+    /// The data holds pax records for all the following entries.
+    PaxGlobal,
+    Other(u8),
+}
+
+impl EntryType {
+    fn from_flag(flag: u8) -> EntryType {
+        use self::EntryType::*;
+        match flag {
+            b'0' | b'\0' => Regular,
+            b'1' => HardLink,
+            b'2' => Symlink,
+            b'3' => CharDevice,
+            b'4' => BlockDevice,
+            b'5' => Directory,
+            b'6' => Fifo,
+            b'7' => Contiguous,
+            b'L' => GnuLongName,
+            b'K' => GnuLongLink,
+            b'x' => PaxExtended,
+            b'g' => PaxGlobal,
+            other => Other(other),
+        }
+    }
+
+    /// Whether the entry describes the entry that follows it instead of a file.
+    pub fn is_metadata(self) -> bool {
+        use self::EntryType::*;
+        matches!(self, GnuLongName | GnuLongLink | PaxExtended | PaxGlobal)
+    }
+
+    /// Whether the header is followed by data. The size field of links,
+    /// devices, directories and fifos doesn't count any data blocks.
+    pub fn has_data(self) -> bool {
+        use self::EntryType::*;
+        !matches!(
+            self,
+            HardLink | Symlink | CharDevice | BlockDevice | Directory | Fifo
+        )
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Header {
+    pub format: Format,
+    pub entry_type: EntryType,
+    pub name: Vec<u8>,
+    pub link_name: Vec<u8>,
+    pub mode: u32,
+    pub uid: u64,
+    pub gid: u64,
+    pub user_name: Vec<u8>,
+    pub group_name: Vec<u8>,
+    /// The size of the data of the entry.
+    pub size: u64,
+    /// The last modification time, in seconds since the Unix epoch.
+    pub mtime: u64,
+    pub device_major: u32,
+    pub device_minor: u32,
+}
+
+/// Returns the part of a field before the first NUL byte.
+fn until_nul(field: &[u8]) -> &[u8] {
+    match field.iter().position(|&byte| byte == 0) {
+        Some(end) => &field[..end],
+        None => field,
+    }
+}
+
+/// Parses a numeric field. They are octal numbers padded with spaces or NULs,
+/// or, as a GNU extension for values that don't fit, big-endian binary numbers
+/// marked by the high bit of the first byte.
+fn parse_numeric(field: &[u8]) -> Option<u64> {
+    if field.first().is_some_and(|&byte| byte & 0x80 != 0) {
+        // A set second-highest bit would make the number negative.
+        if field[0] & 0x40 != 0 {
+            return None;
+        }
+        let mut value = u64::from(field[0] & 0x3f);
+        for &byte in &field[1..] {
+            if value >> 56 != 0 {
+                return None;
+            }
+            value = value << 8 | u64::from(byte);
+        }
+        return Some(value);
+    }
+
+    let digits = until_nul(field);
+    let digits = digits
+        .iter()
+        .position(|&byte| byte != b' ')
+        .map_or(&[][..], |start| &digits[start..]);
+    let end = digits
+        .iter()
+        .position(|&byte| byte == b' ')
+        .unwrap_or(digits.len());
+    if digits[end..].iter().any(|&byte| byte != b' ') {
+        return None;
+    }
+    digits[..end].iter().try_fold(0u64, |value, &digit| {
+        if !(b'0'..=b'7').contains(&digit) || value >> 61 != 0 {
+            return None;
+        }
+        Some(value << 3 | u64::from(digit - b'0'))
+    })
+}
+
+/// Sums the bytes of a header block, counting the checksum field as spaces.
+fn checksum(block: &[u8]) -> u64 {
+    block
+        .iter()
+        .enumerate()
+        .map(|(i, &byte)| {
+            if (148..156).contains(&i) {
+                u64::from(b' ')
+            } else {
+                u64::from(byte)
+            }
+        })
+        .sum()
+}
+
+impl Header {
+    /// Parses a header block. `block` must be `BLOCK_SIZE` bytes long.
+    pub fn parse(block: &[u8]) -> Result<Header, TarError> {
+        debug_assert_eq!(block.len(), BLOCK_SIZE);
+        let numeric = |range: std::ops::Range<usize>| {
+            parse_numeric(&block[range]).ok_or(TarError::InvalidHeader)
+        };
+        let small_numeric = |range: std::ops::Range<usize>| {
+            numeric(range).and_then(|value| {
+                if value > u64::from(u32::MAX) {
+                    Err(TarError::InvalidHeader)
+                } else {
+                    Ok(value as u32)
+                }
+            })
+        };
+
+        if numeric(148..156)? != checksum(block) {
+            return Err(TarError::ChecksumMismatch);
+        }
+
+        let format = match &block[257..265] {
+            magic if magic == USTAR_MAGIC => Format::Ustar,
+            magic if magic == GNU_MAGIC => Format::Gnu,
+            _ => Format::V7,
+        };
+        let mut name = until_nul(&block[..100]).to_vec();
+        if format == Format::Ustar {
+            let prefix = until_nul(&block[345..500]);
+            if !prefix.is_empty() {
+                name = [prefix, b"/", &name].concat();
+            }
+        }
+        let (user_name, group_name, device_major, device_minor) = if format == Format::V7 {
+            (Vec::new(), Vec::new(), 0, 0)
+        } else {
+            (
+                until_nul(&block[265..297]).to_vec(),
+                until_nul(&block[297..329]).to_vec(),
+                small_numeric(329..337)?,
+                small_numeric(337..345)?,
+            )
+        };
+
+        Ok(Header {
+            format,
+            entry_type: EntryType::from_flag(block[156]),
+            name,
+            link_name: until_nul(&block[157..257]).to_vec(),
+            mode: small_numeric(100..108)?,
+            uid: numeric(108..116)?,
+            gid: numeric(116..124)?,
+            user_name,
+            group_name,
+            size: numeric(124..136)?,
+            mtime: numeric(136..148)?,
+            device_major,
+            device_minor,
+        })
+    }
+
+    /// The number of data bytes that follow the header, not counting padding.
+    pub fn data_size(&self) -> u64 {
+        if self.entry_type.has_data() {
+            self.size
+        } else {
+            0
+        }
+    }
+
+    /// Overrides the fields of the header with the values of pax records.
+    /// Unknown keys are ignored.
+    pub fn apply_pax_records(&mut self, records: &[PaxRecord]) -> Result<(), TarError> {
+        let decimal = |value: &[u8]| {
+            // Times may have a fractional part, which is dropped.
+            let integer = value.split(|&byte| byte == b'.').next().unwrap_or(value);
+            std::str::from_utf8(integer)
+                .ok()
+                .and_then(|integer| integer.parse::<u64>().ok())
+                .ok_or(TarError::InvalidPaxHeader)
+        };
+        for (key, value) in records {
+            match &key[..] {
+                b"path" => self.name = value.clone(),
+                b"linkpath" => self.link_name = value.clone(),
+                b"uname" => self.user_name = value.clone(),
+                b"gname" => self.group_name = value.clone(),
+                b"size" => self.size = decimal(value)?,
+                b"uid" => self.uid = decimal(value)?,
+                b"gid" => self.gid = decimal(value)?,
+                b"mtime" => self.mtime = decimal(value)?,
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parses the records of a pax extended header. Each record is
+/// `"<length> <key>=<value>\n"`, where the length counts the whole record.
+pub fn parse_pax_records(mut data: &[u8]) -> Result<Vec<PaxRecord>, TarError> {
+    let mut records = Vec::new();
+    // Some writers pad the data with NULs.
+    while !until_nul(data).is_empty() {
+        let space = data
+            .iter()
+            .position(|&byte| byte == b' ')
+            .ok_or(TarError::InvalidPaxHeader)?;
+        let length = std::str::from_utf8(&data[..space])
+            .ok()
+            .and_then(|length| length.parse::<usize>().ok())
+            .filter(|&length| length > space + 1 && length <= data.len())
+            .ok_or(TarError::InvalidPaxHeader)?;
+        let record = &data[space + 1..length];
+        let record = match record.split_last() {
+            Some((b'\n', record)) => record,
+            _ => return Err(TarError::InvalidPaxHeader),
+        };
+        let equals = record
+            .iter()
+            .position(|&byte| byte == b'=')
+            .ok_or(TarError::InvalidPaxHeader)?;
+        records.push((record[..equals].to_vec(), record[equals + 1..].to_vec()));
+        data = &data[length..];
+    }
+    Ok(records)
+}
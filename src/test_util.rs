@@ -0,0 +1,223 @@
+//! A harness for testing that streaming drivers behave identically
+//! regardless of where the input is split into chunks.
+//!
+//! Boundary bugs tend to show up only with specific split positions,
+//! so the harness replays an input through a driver with every possible
+//! split position, or with a reproducible random set of splits,
+//! and asserts that the driver's output matches that of the unsplit input.
+//!
+//! A driver is any function that takes the input as a slice of chunks,
+//! feeds them to a stream in order and returns something comparable.
+//! `read_chunks` is a driver that uses the autodetecting `File` API.
+
+use std::fmt::Debug;
+
+use crate::zip::{self, ZipError};
+use crate::{start_stream_with, Error, Options, State};
+
+/// A file (a zip entry or a gzip member) read by `read_chunks`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Entry {
+    pub name: Option<Vec<u8>>,
+    pub data: Vec<u8>,
+}
+
+/// Feeds `chunks` in order to an autodetecting stream and collects
+/// the names and the decompressed contents of the files in it.
+/// If the chunks run out before the stream ends, the files read so far,
+/// including the incomplete one, are returned.
+pub fn read_chunks(chunks: &[&[u8]]) -> Result<Vec<Entry>, Error> {
+    read_chunks_with(Options::default(), chunks)
+}
+
+/// Like `read_chunks`, but the stream is parsed according to `options`.
+pub fn read_chunks_with(options: Options, chunks: &[&[u8]]) -> Result<Vec<Entry>, Error> {
+    let mut entries = Vec::new();
+    let mut data = Vec::new();
+    let mut file = start_stream_with(options);
+
+    for chunk in chunks {
+        let mut input = *chunk;
+        loop {
+            match file.read(input)? {
+                State::HasOutput {
+                    unparsed_input,
+                    output,
+                } => {
+                    data.extend_from_slice(output);
+                    input = unparsed_input;
+                }
+                State::NeedsInput => break,
+                State::NeedsInputOrEof(next_file) => {
+                    entries.push(Entry {
+                        name: file.name().map(<[u8]>::to_vec),
+                        data: std::mem::take(&mut data),
+                    });
+                    file = next_file;
+                    break;
+                }
+                State::NextFile {
+                    unparsed_input,
+                    next_file,
+                }
+                | State::NextArchive {
+                    unparsed_input,
+                    next_file,
+                } => {
+                    entries.push(Entry {
+                        name: file.name().map(<[u8]>::to_vec),
+                        data: std::mem::take(&mut data),
+                    });
+                    file = next_file;
+                    input = unparsed_input;
+                }
+                State::EndOfFile | State::Stopped => {
+                    entries.push(Entry {
+                        name: file.name().map(<[u8]>::to_vec),
+                        data: std::mem::take(&mut data),
+                    });
+                    return Ok(entries);
+                }
+            }
+        }
+    }
+
+    if !data.is_empty() {
+        entries.push(Entry {
+            name: file.name().map(<[u8]>::to_vec),
+            data,
+        });
+    }
+    Ok(entries)
+}
+
+/// Feeds `chunks` in order to a zip stream started with `options`, and collects
+/// the names and the decompressed contents of its entries. Unlike `read_chunks`,
+/// this panics if the chunks run out before the stream ends.
+pub fn read_zip_entries(chunks: &[&[u8]], options: zip::Options) -> Result<Vec<Entry>, ZipError> {
+    let mut zip_file = zip::start_stream_with(options);
+    let mut entries = Vec::new();
+    let mut data = Vec::new();
+    for chunk in chunks {
+        let mut input = *chunk;
+        loop {
+            match zip_file.read(input)? {
+                State::HasOutput {
+                    unparsed_input,
+                    output,
+                } => {
+                    data.extend_from_slice(output);
+                    input = unparsed_input;
+                }
+                State::NeedsInput => break,
+                State::NextFile {
+                    unparsed_input,
+                    next_file,
+                } => {
+                    entries.push(Entry {
+                        name: zip_file.filename().map(<[u8]>::to_vec),
+                        data: std::mem::take(&mut data),
+                    });
+                    zip_file = next_file;
+                    input = unparsed_input;
+                }
+                State::EndOfFile => {
+                    entries.push(Entry {
+                        name: zip_file.filename().map(<[u8]>::to_vec),
+                        data,
+                    });
+                    return Ok(entries);
+                }
+                state => panic!("Unexpected state: {:?}", state),
+            }
+        }
+    }
+    panic!("The stream should have ended");
+}
+
+/// The offset of the `nth` occurrence of `pattern` in `data`, counted from 0,
+/// such as that of a header signature. Panics if there are fewer occurrences.
+pub fn find_nth(data: &[u8], pattern: &[u8], nth: usize) -> usize {
+    data.windows(pattern.len())
+        .enumerate()
+        .filter(|(_, window)| *window == pattern)
+        .nth(nth)
+        .unwrap()
+        .0
+}
+
+/// Replays `input` through `driver` split in two at every possible position,
+/// and asserts that the output equals the output of the unsplit input.
+pub fn assert_every_split<O, D>(input: &[u8], mut driver: D)
+where
+    O: Debug + PartialEq,
+    D: FnMut(&[&[u8]]) -> O,
+{
+    let expected = driver(&[input]);
+    for split in 0..=input.len() {
+        let (first, second) = input.split_at(split);
+        let output = driver(&[first, second]);
+        assert_eq!(
+            output, expected,
+            "the output differs when the input is split at {}",
+            split
+        );
+    }
+}
+
+/// Replays `input` through `driver` `rounds` times, each time split into chunks
+/// of random lengths between 1 and `max_chunk` bytes, and asserts that the output
+/// equals the output of the unsplit input. The splits are derived from `seed`,
+/// so a failing round can be reproduced.
+pub fn assert_random_splits<O, D>(
+    input: &[u8],
+    seed: u64,
+    rounds: usize,
+    max_chunk: usize,
+    mut driver: D,
+) where
+    O: Debug + PartialEq,
+    D: FnMut(&[&[u8]]) -> O,
+{
+    assert!(max_chunk > 0, "max_chunk must be at least 1");
+
+    let expected = driver(&[input]);
+    let mut rng = XorShift::new(seed);
+    for round in 0..rounds {
+        let mut chunks = Vec::new();
+        let mut rest = input;
+        while !rest.is_empty() {
+            let len = 1 + (rng.next() % max_chunk as u64) as usize;
+            let (chunk, tail) = rest.split_at(std::cmp::min(len, rest.len()));
+            chunks.push(chunk);
+            rest = tail;
+        }
+        let output = driver(&chunks);
+        assert_eq!(
+            output,
+            expected,
+            "the output differs in round {} with chunk lengths {:?}",
+            round,
+            chunks.iter().map(|c| c.len()).collect::<Vec<_>>()
+        );
+    }
+}
+
+/// A tiny PRNG, good enough for picking split positions reproducibly.
+struct XorShift(u64);
+
+impl XorShift {
+    fn new(seed: u64) -> XorShift {
+        // The state must not be zero.
+        XorShift(seed ^ 0x9e37_79b9_7f4a_7c15)
+    }
+
+    fn next(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.0 = x;
+        x
+    }
+}
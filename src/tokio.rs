@@ -0,0 +1,256 @@
+//! Asynchronous reading of archives and compressed files from a
+//! `tokio::io::AsyncRead`, such as a socket.
+//!
+//! `Archive` is the asynchronous counterpart of `archive::Archive`: its entries
+//! are returned one at a time by `Archive::next_entry`, and their decompressed
+//! content is read through the `AsyncRead` and `AsyncBufRead` implementations
+//! of `Entry`. The state machines are driven from the poll functions, so nothing
+//! is spawned and any runtime that polls the futures will do.
+//!
+//! `extract_to_dir` extracts an archive like `extract::extract_to_dir`. As writing
+//! the files blocks, it's done with `tokio::task::spawn_blocking`, so that future
+//! needs a tokio runtime.
+
+use std::future::Future;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio_rt::io::{AsyncBufRead, AsyncRead, ReadBuf};
+use tokio_rt::task::{spawn_blocking, JoinHandle};
+
+use crate::archive::Core;
+use crate::extract::{ExtractError, Extractor};
+use crate::{DriveError, EntryMetadata, Options, Summary};
+
+/// The size of the input buffer of `extract_to_dir`.
+const INPUT_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Reads from `reader` into `buf` like `io::Read::read`.
+fn poll_read_into<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    cx: &mut Context,
+    buf: &mut [u8],
+) -> Poll<io::Result<usize>> {
+    let mut buf = ReadBuf::new(buf);
+    match Pin::new(reader).poll_read(cx, &mut buf) {
+        Poll::Ready(Ok(())) => Poll::Ready(Ok(buf.filled().len())),
+        Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
+        Poll::Pending => Poll::Pending,
+    }
+}
+
+/// An archive or a compressed file read from `R`. See the module documentation.
+pub struct Archive<R> {
+    reader: R,
+    core: Core,
+}
+
+impl<R: AsyncRead + Unpin> Archive<R> {
+    /// Reads the archive in `reader`, which is parsed with the default options.
+    pub fn new(reader: R) -> Archive<R> {
+        Archive::with_options(reader, Options::default())
+    }
+
+    /// Reads the archive in `reader`, which is parsed according to `options`.
+    pub fn with_options(reader: R, options: Options) -> Archive<R> {
+        Archive {
+            reader,
+            core: Core::new(options),
+        }
+    }
+
+    /// Returns a future that resolves to the next entry, skipping what's left of
+    /// the content of the current one, or to `None` if the archive has ended.
+    /// See `archive::Archive::next_entry`.
+    pub fn next_entry(&mut self) -> NextEntry<'_, R> {
+        NextEntry {
+            archive: Some(self),
+        }
+    }
+
+    fn poll_next_entry(&mut self, cx: &mut Context) -> Poll<Result<bool, DriveError>> {
+        let reader = &mut self.reader;
+        self.core
+            .poll_next_entry(&mut |buf: &mut [u8]| poll_read_into(reader, cx, buf))
+    }
+
+    fn poll_fill(&mut self, cx: &mut Context) -> Poll<Result<bool, DriveError>> {
+        let reader = &mut self.reader;
+        self.core
+            .poll_fill(&mut |buf: &mut [u8]| poll_read_into(reader, cx, buf))
+    }
+}
+
+/// The future returned by `Archive::next_entry`.
+pub struct NextEntry<'a, R> {
+    /// The archive, until the future has resolved.
+    archive: Option<&'a mut Archive<R>>,
+}
+
+impl<'a, R: AsyncRead + Unpin> Future for NextEntry<'a, R> {
+    type Output = Result<Option<Entry<'a, R>>, DriveError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let archive = match self.archive.take() {
+            Some(archive) => archive,
+            None => panic!("NextEntry polled after it resolved."),
+        };
+        match archive.poll_next_entry(cx) {
+            Poll::Ready(Ok(true)) => Poll::Ready(Ok(Some(Entry { archive }))),
+            Poll::Ready(Ok(false)) => Poll::Ready(Ok(None)),
+            Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
+            Poll::Pending => {
+                self.archive = Some(archive);
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// An entry of an `Archive`, returned by `Archive::next_entry`.
+/// Its decompressed content is read with `AsyncRead` or `AsyncBufRead`.
+pub struct Entry<'a, R> {
+    archive: &'a mut Archive<R>,
+}
+
+impl<R: AsyncRead + Unpin> Entry<'_, R> {
+    /// The metadata of the entry, as it was when the entry was returned.
+    pub fn metadata(&self) -> &EntryMetadata {
+        self.archive.core.metadata()
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for Entry<'_, R> {
+    /// Errors of the stream are returned as `io::ErrorKind::InvalidData`.
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut ReadBuf,
+    ) -> Poll<io::Result<()>> {
+        let pending = std::task::ready!(self.as_mut().poll_fill_buf(cx))?;
+        let len = pending.len().min(buf.remaining());
+        buf.put_slice(&pending[..len]);
+        self.consume(len);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncBufRead for Entry<'_, R> {
+    /// Returns the unread part of the latest output of the stream, without copying it.
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<&[u8]>> {
+        let archive = &mut *self.get_mut().archive;
+        std::task::ready!(archive.poll_fill(cx))?;
+        Poll::Ready(Ok(archive.core.pending()))
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        self.get_mut().archive.core.consume(amt);
+    }
+}
+
+/// Extracts everything `reader` provides into the directory `dest`, like
+/// `extract::extract_to_dir`. The input is read asynchronously, and the files
+/// are written on the blocking thread pool of tokio, so unlike the rest of the
+/// module, the future must be polled within a tokio runtime.
+pub fn extract_to_dir<R: AsyncRead + Unpin>(reader: R, dest: impl AsRef<Path>) -> ExtractToDir<R> {
+    ExtractToDir {
+        reader,
+        state: ExtractState::Start(dest.as_ref().to_path_buf()),
+    }
+}
+
+/// The future returned by `extract_to_dir`.
+pub struct ExtractToDir<R> {
+    reader: R,
+    state: ExtractState,
+}
+
+#[allow(clippy::large_enum_variant)]
+enum ExtractState {
+    /// The future hasn't been polled yet.
+    Start(PathBuf),
+    /// Creating the destination directory.
+    Creating(JoinHandle<Result<Extractor, ExtractError>>),
+    /// Reading the next input into the buffer.
+    Reading(Extractor, Vec<u8>),
+    /// Writing the output of the input in the buffer, which is returned with the extractor.
+    Writing(JoinHandle<(Extractor, Vec<u8>, Result<(), ExtractError>)>),
+    /// Closing the last file.
+    Finishing(JoinHandle<Result<Summary, ExtractError>>),
+    Done,
+}
+
+/// Polls a task spawned with `spawn_blocking`, resuming its panic if it panicked.
+fn poll_task<T>(task: &mut JoinHandle<T>, cx: &mut Context) -> Poll<Result<T, ExtractError>> {
+    match std::task::ready!(Pin::new(task).poll(cx)) {
+        Ok(value) => Poll::Ready(Ok(value)),
+        Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+        // The runtime is shutting down.
+        Err(e) => Poll::Ready(Err(ExtractError::Io(io::Error::other(e)))),
+    }
+}
+
+impl<R: AsyncRead + Unpin> Future for ExtractToDir<R> {
+    type Output = Result<Summary, ExtractError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        loop {
+            this.state = match std::mem::replace(&mut this.state, ExtractState::Done) {
+                ExtractState::Start(dest) => {
+                    ExtractState::Creating(spawn_blocking(move || Extractor::new(&dest)))
+                }
+                ExtractState::Creating(mut task) => match poll_task(&mut task, cx) {
+                    Poll::Ready(extractor) => {
+                        ExtractState::Reading(extractor??, vec![0; INPUT_BUFFER_SIZE])
+                    }
+                    Poll::Pending => {
+                        this.state = ExtractState::Creating(task);
+                        return Poll::Pending;
+                    }
+                },
+                ExtractState::Reading(extractor, mut buf) if !extractor.has_ended() => {
+                    match poll_read_into(&mut this.reader, cx, &mut buf) {
+                        Poll::Ready(Ok(0)) => {
+                            ExtractState::Finishing(spawn_blocking(move || extractor.finish()))
+                        }
+                        Poll::Ready(Ok(len)) => ExtractState::Writing(spawn_blocking(move || {
+                            let mut extractor = extractor;
+                            let result = extractor.read(&buf[..len]);
+                            (extractor, buf, result)
+                        })),
+                        Poll::Ready(Err(e)) => return Poll::Ready(Err(e.into())),
+                        Poll::Pending => {
+                            this.state = ExtractState::Reading(extractor, buf);
+                            return Poll::Pending;
+                        }
+                    }
+                }
+                ExtractState::Reading(extractor, _) => {
+                    ExtractState::Finishing(spawn_blocking(move || extractor.finish()))
+                }
+                ExtractState::Writing(mut task) => match poll_task(&mut task, cx) {
+                    Poll::Ready(written) => {
+                        let (extractor, buf, result) = written?;
+                        result?;
+                        ExtractState::Reading(extractor, buf)
+                    }
+                    Poll::Pending => {
+                        this.state = ExtractState::Writing(task);
+                        return Poll::Pending;
+                    }
+                },
+                ExtractState::Finishing(mut task) => match poll_task(&mut task, cx) {
+                    Poll::Ready(summary) => return Poll::Ready(summary?),
+                    Poll::Pending => {
+                        this.state = ExtractState::Finishing(task);
+                        return Poll::Pending;
+                    }
+                },
+                ExtractState::Done => panic!("ExtractToDir polled after it resolved."),
+            };
+        }
+    }
+}
@@ -34,8 +34,8 @@ where
 }
 
 pub trait NomErrorExt2<T, E1> {
-    fn nom_fail<E2>(self, impl Fn(E1) -> E2) -> Result<T, nom::Err<E2>>;
-    fn nom_err<E2>(self, impl Fn(E1) -> E2) -> Result<T, nom::Err<E2>>;
+    fn nom_fail<E2>(self, map: impl Fn(E1) -> E2) -> Result<T, nom::Err<E2>>;
+    fn nom_err<E2>(self, map: impl Fn(E1) -> E2) -> Result<T, nom::Err<E2>>;
 }
 
 impl<T, E1> NomErrorExt2<T, E1> for Result<T, E1> {
@@ -54,7 +54,7 @@ impl<T, E1> NomErrorExt2<T, E1> for Result<T, E1> {
 }
 
 pub trait NomErrorExt<T, E1> {
-    fn map_nom_err<E2>(self, impl Fn(E1) -> E2) -> Result<T, nom::Err<E2>>;
+    fn map_nom_err<E2>(self, map: impl Fn(E1) -> E2) -> Result<T, nom::Err<E2>>;
 }
 
 impl<T, E1> NomErrorExt<T, E1> for Result<T, nom::Err<E1>> {
@@ -121,3 +121,67 @@ where
         Err(e) => Err(e),
     }
 }
+
+/// The lookup tables of the CRC-32 that zip, gzip, xz and 7z use. The first one
+/// shifts in a byte, and table `n` shifts in a byte followed by `n` zero bytes,
+/// which lets `update_crc32` process eight bytes at a time.
+const CRC32_TABLES: [[u32; 256]; 8] = crc32_tables();
+
+const fn crc32_tables() -> [[u32; 256]; 8] {
+    let mut tables = [[0; 256]; 8];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                0xedb8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        tables[0][i] = crc;
+        i += 1;
+    }
+    let mut n = 1;
+    while n < 8 {
+        let mut i = 0;
+        while i < 256 {
+            let previous = tables[n - 1][i];
+            tables[n][i] = tables[0][(previous & 0xff) as usize] ^ (previous >> 8);
+            i += 1;
+        }
+        n += 1;
+    }
+    tables
+}
+
+/// Updates `crc`, the CRC-32 of the preceding data, with `data`.
+/// The CRC-32 of empty data is 0.
+pub fn update_crc32(crc: u32, data: &[u8]) -> u32 {
+    let mut crc = !crc;
+    let mut chunks = data.chunks_exact(8);
+    for chunk in &mut chunks {
+        let low = crc ^ u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        let t = &CRC32_TABLES;
+        crc = t[7][(low & 0xff) as usize]
+            ^ t[6][((low >> 8) & 0xff) as usize]
+            ^ t[5][((low >> 16) & 0xff) as usize]
+            ^ t[4][(low >> 24) as usize]
+            ^ t[3][chunk[4] as usize]
+            ^ t[2][chunk[5] as usize]
+            ^ t[1][chunk[6] as usize]
+            ^ t[0][chunk[7] as usize];
+    }
+    !chunks
+        .remainder()
+        .iter()
+        .fold(crc, |crc, &byte| crc32_step(crc, byte))
+}
+
+/// Shifts `byte` into the CRC-32 register `crc`,
+/// without the inversions before and after the data.
+pub(crate) fn crc32_step(crc: u32, byte: u8) -> u32 {
+    CRC32_TABLES[0][((crc ^ u32::from(byte)) & 0xff) as usize] ^ (crc >> 8)
+}
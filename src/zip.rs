@@ -1,16 +1,376 @@
-use std::ops::Not;
+use std::borrow::Cow;
+use std::sync::Arc;
+use std::time::SystemTime;
 
 use nom;
 use nom::error::ErrorKind;
+use nom::IResult;
 
 use crate::deflate;
 use crate::input_helper::{Input, InputHandler};
-use crate::{CompressedStream, ReadHeadersResult, State};
+use crate::utils::update_crc32;
+use crate::zstd;
+use crate::{
+    CompressedStream, Compression, EntryMetadata, EntryTotals, ErrorContext, FieldLimits,
+    HeaderField, HookProgress, Hooks, RatioLimit, ReadHeadersResult, State, StopConditions,
+    Summary, TryReadError,
+};
 
 pub struct ZipFile {
     state: InternalState,
-    inflater: deflate::Stream,
+    inflater: Option<Decompressor>,
     unparsed: Vec<u8>,
+    options: Options,
+    /// Set by `skip_entry`.
+    skip_data: bool,
+    /// The CRC-32 of the output of the entry so far.
+    crc_32: u32,
+    /// The amount of the compressed data of the entry that hasn't been passed
+    /// to the decompressor yet, if the local file header has the sizes.
+    data_left: Option<u64>,
+    /// The error the entry was skipped after, with `Options::recover_damaged_entries`.
+    damage: Option<ZipError>,
+    /// The index of the entry in the archive.
+    index: u64,
+    /// The offset in the archive of the next byte of input to parse.
+    position: u64,
+    /// The offset in the archive of the local file header of the entry.
+    header_offset: u64,
+    /// The entries of the archive before this one, if `Options::validate_central_dir` is enabled.
+    streamed_entries: Vec<StreamedEntry>,
+    hook_progress: HookProgress,
+    /// The progress of the stream, counted from the start of its first file.
+    progress: Summary,
+    /// Whether a stop condition of the options has stopped the stream.
+    stopped: bool,
+}
+
+/// Options that affect how a zip stream is parsed.
+/// The options are inherited by the files returned in
+/// `State::NextFile` and `State::NextArchive`.
+#[derive(Clone, Default)]
+pub struct Options {
+    central_dir_callback: Option<CentralDirCallback>,
+    concatenated_archives: bool,
+    entry_filter: Option<EntryFilter>,
+    field_limits: FieldLimits,
+    pub(crate) hooks: Hooks,
+    ignore_checksums: bool,
+    keep_central_dir: bool,
+    lazy_field_threshold: Option<usize>,
+    max_compression_ratio: Option<RatioLimit>,
+    max_entry_size: Option<u64>,
+    max_skip: Option<u64>,
+    output_buffer_size: Option<usize>,
+    password: Option<Vec<u8>>,
+    recover_damaged_entries: bool,
+    skip_encrypted_entries: bool,
+    pub(crate) stop: StopConditions,
+    validate_central_dir: bool,
+    zstd_backend: Option<ZstdBackendFactory>,
+}
+
+type CentralDirCallback = Arc<dyn Fn(&CentralDirHeader) + Send + Sync>;
+type EntryFilter = Arc<dyn Fn(&LocalFileHeader) -> bool + Send + Sync>;
+type ZstdBackendFactory = Arc<dyn Fn() -> Box<dyn zstd::Backend> + Send + Sync>;
+
+impl std::fmt::Debug for Options {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Options")
+            .field(
+                "central_dir_callback",
+                &self.central_dir_callback.as_ref().map(|_| ".."),
+            )
+            .field("concatenated_archives", &self.concatenated_archives)
+            .field("entry_filter", &self.entry_filter.as_ref().map(|_| ".."))
+            .field("field_limits", &self.field_limits)
+            .field("hooks", &self.hooks)
+            .field("ignore_checksums", &self.ignore_checksums)
+            .field("keep_central_dir", &self.keep_central_dir)
+            .field("lazy_field_threshold", &self.lazy_field_threshold)
+            .field("max_compression_ratio", &self.max_compression_ratio)
+            .field("max_entry_size", &self.max_entry_size)
+            .field("max_skip", &self.max_skip)
+            .field("output_buffer_size", &self.output_buffer_size)
+            .field("password", &self.password.as_ref().map(|_| ".."))
+            .field("recover_damaged_entries", &self.recover_damaged_entries)
+            .field("skip_encrypted_entries", &self.skip_encrypted_entries)
+            .field("stop", &self.stop)
+            .field("validate_central_dir", &self.validate_central_dir)
+            .field("zstd_backend", &self.zstd_backend.as_ref().map(|_| ".."))
+            .finish()
+    }
+}
+
+impl Options {
+    /// Some producers ship multiple complete zip archives back-to-back
+    /// in one stream. When enabled, a local file header following the
+    /// end of central directory record of an archive is reported as
+    /// `State::NextArchive` instead of the stream ending at `State::EndOfFile`.
+    /// If the input runs out right after the end of the archive,
+    /// `State::NeedsInputOrEof` is returned.
+    pub fn concatenated_archives(mut self, enabled: bool) -> Self {
+        self.concatenated_archives = enabled;
+        self
+    }
+
+    /// Sets a filter that selects the entries to decompress: `filter` is called
+    /// with the local file header of each entry, and the entries it rejects are
+    /// skipped like with `ZipFile::skip_entry`, producing no output. Note that
+    /// with `lazy_header_fields`, the filename of a header may be left empty.
+    pub fn entry_filter(
+        mut self,
+        filter: impl Fn(&LocalFileHeader) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.entry_filter = Some(Arc::new(filter));
+        self
+    }
+
+    /// Sets a hook that is called with the metadata of each entry once its
+    /// local file header has been parsed, before any of its output is returned.
+    pub fn on_entry_start(mut self, hook: impl Fn(&EntryMetadata) + Send + Sync + 'static) -> Self {
+        self.hooks.on_entry_start(Arc::new(hook));
+        self
+    }
+
+    /// Sets a hook that is called with the metadata, the sizes and the CRC-32
+    /// of each entry once its data has ended and the data descriptor, if any,
+    /// has been checked. Entries that fail the checks don't reach it.
+    pub fn on_entry_end(
+        mut self,
+        hook: impl Fn(&EntryMetadata, &EntryTotals) + Send + Sync + 'static,
+    ) -> Self {
+        self.hooks.on_entry_end(Arc::new(hook));
+        self
+    }
+
+    /// Sets a hook that is called with `ZipFile::progress` each time another
+    /// `interval` bytes of input have been consumed.
+    pub fn on_progress(
+        mut self,
+        interval: u64,
+        hook: impl Fn(&Summary) + Send + Sync + 'static,
+    ) -> Self {
+        self.hooks.on_progress(interval, Arc::new(hook));
+        self
+    }
+
+    /// Stops the stream with `State::Stopped` once `entries` entries have ended,
+    /// counted from the start of the stream.
+    pub fn stop_after_entries(mut self, entries: usize) -> Self {
+        self.stop.entries = Some(entries);
+        self
+    }
+
+    /// Stops the stream with `State::Stopped` once the entry named `name` has ended.
+    pub fn stop_after_entry(mut self, name: impl Into<Vec<u8>>) -> Self {
+        self.stop.entry_name = Some(name.into());
+        self
+    }
+
+    /// Stops the stream with `State::Stopped` once it has output `bytes` bytes,
+    /// counted from the start of the stream. The output that reaches the limit
+    /// is returned in full.
+    pub fn stop_after_bytes(mut self, bytes: u64) -> Self {
+        self.stop.uncompressed_bytes = Some(bytes);
+        self
+    }
+
+    /// Normally the central directory headers are parsed and dropped.
+    /// When enabled, they are kept, and are available from `ZipFile::central_dir`
+    /// of the last file of the archive once the archive has ended. The memory
+    /// this takes grows with the number of entries in the archive.
+    pub fn keep_central_dir(mut self, enabled: bool) -> Self {
+        self.keep_central_dir = enabled;
+        self
+    }
+
+    /// Sets a callback that is called with each central directory header
+    /// as it's parsed, which makes the comments, attributes and offsets of the
+    /// entries available at the end of the stream without keeping the whole
+    /// central directory in memory. Note that with `lazy_header_fields`,
+    /// the variable-length fields of a header may be left empty.
+    pub fn on_central_directory(
+        mut self,
+        callback: impl Fn(&CentralDirHeader) + Send + Sync + 'static,
+    ) -> Self {
+        self.central_dir_callback = Some(Arc::new(callback));
+        self
+    }
+
+    /// Normally the variable-length fields of headers (filenames, extra fields
+    /// and comments) are buffered until the whole header is available.
+    /// With a threshold set, a header whose variable-length fields are together
+    /// longer than `threshold` bytes is parsed from its fixed-size part only,
+    /// and the variable-length fields are skipped as they stream by.
+    /// This keeps memory use and latency bounded with headers that carry
+    /// huge comments or extra fields, but the skipped fields of such
+    /// headers are left empty; this includes the filename.
+    pub fn lazy_header_fields(mut self, threshold: usize) -> Self {
+        self.lazy_field_threshold = Some(threshold);
+        self
+    }
+
+    /// Limits the length of the filenames of the local file headers and
+    /// the central directory headers to `len` bytes. A longer filename fails
+    /// the stream with `ZipError::FieldTooLong`, unless `truncate_long_fields`
+    /// is enabled. The lengths are checked before the fields are buffered.
+    pub fn max_filename_len(mut self, len: usize) -> Self {
+        self.field_limits.filename = Some(len);
+        self
+    }
+
+    /// Limits the total length of the extra fields of each header, including
+    /// the ids and the lengths of the fields, to `len` bytes. See `max_filename_len`.
+    pub fn max_extra_fields_len(mut self, len: usize) -> Self {
+        self.field_limits.extra_fields = Some(len);
+        self
+    }
+
+    /// Limits the length of the comments of the central directory headers and
+    /// of the end of central directory record to `len` bytes. See `max_filename_len`.
+    pub fn max_comment_len(mut self, len: usize) -> Self {
+        self.field_limits.comment = Some(len);
+        self
+    }
+
+    /// Normally a header field that is longer than its limit fails the stream.
+    /// When enabled, such fields are parsed and then truncated to their limits:
+    /// filenames and comments are cut short, and the extra fields that don't fit
+    /// are dropped whole, along with what they carry. Only the truncated fields
+    /// are kept, but the whole header is buffered while it's parsed.
+    pub fn truncate_long_fields(mut self, enabled: bool) -> Self {
+        self.field_limits.truncate = enabled;
+        self
+    }
+
+    /// Normally the stream must start with a local file header.
+    /// When enabled, up to `max_skip` bytes of data before the first valid
+    /// local file header are skipped, such as the executable stub of
+    /// a self-extracting archive, or leftovers of the transport the archive
+    /// was extracted from. If the header isn't found within `max_skip` bytes,
+    /// the stream fails with `ZipError::NotLocalFileHeader`.
+    pub fn scan_for_start(mut self, max_skip: u64) -> Self {
+        self.max_skip = Some(max_skip);
+        self
+    }
+
+    /// Limits the decompressed size of each entry to `size` bytes. An entry whose data
+    /// decompresses to more fails the stream with `ZipError::EntryTooLarge` as soon
+    /// as the output that exceeds the limit has been decompressed, before it's returned,
+    /// whatever sizes the headers claim. This bounds the output of a zip bomb.
+    pub fn max_entry_size(mut self, size: u64) -> Self {
+        self.max_entry_size = Some(size);
+        self
+    }
+
+    /// Limits the ratio of the decompressed size of each entry to its compressed size.
+    /// Once an entry has decompressed to more than `min_size` bytes, the stream fails
+    /// with `ZipError::CompressionRatioExceeded` if its output so far is more than
+    /// `ratio` times the compressed data that produced it. Crafted data that
+    /// decompresses to a lot of output is caught early, while small, highly
+    /// compressible entries are let through.
+    pub fn max_compression_ratio(mut self, ratio: u64, min_size: u64) -> Self {
+        self.max_compression_ratio = Some(RatioLimit { ratio, min_size });
+        self
+    }
+
+    /// Sets the size of the output buffer of the Deflate decompressor, which is
+    /// the most output a single `State::HasOutput` can have. A larger buffer
+    /// means fewer, larger chunks of output. The buffer is at least 32 KiB,
+    /// the size of the Deflate window, which is also the default.
+    pub fn output_buffer_size(mut self, size: usize) -> Self {
+        self.output_buffer_size = Some(size);
+        self
+    }
+
+    /// Sets the password for decrypting encrypted entries. The traditional
+    /// PKWARE encryption, also known as ZipCrypto, is always supported, and
+    /// WinZip AES encryption with the `aes` feature. An entry whose encryption
+    /// header doesn't match the password fails with `ZipError::InvalidPassword`.
+    /// With ZipCrypto, the check catches 255 out of 256 wrong passwords;
+    /// the rest fail while decompressing the data.
+    pub fn password(mut self, password: impl AsRef<[u8]>) -> Self {
+        self.password = Some(password.as_ref().to_vec());
+        self
+    }
+
+    /// Normally an error in the data of an entry fails the stream. When enabled,
+    /// the rest of a damaged entry is skipped instead: the input is scanned for
+    /// the next valid local file header or the central directory, and the stream
+    /// continues from there. The damaged entry ends with `State::NextFile` or
+    /// `State::EndOfFile` as usual, and its error is available from `ZipFile::damage`.
+    /// The output of the entry up to the error has already been produced.
+    pub fn recover_damaged_entries(mut self, enabled: bool) -> Self {
+        self.recover_damaged_entries = enabled;
+        self
+    }
+
+    /// Without a password, encrypted entries fail with `ZipError::EncryptedEntry`.
+    /// When enabled, encrypted entries whose sizes are in the local header
+    /// are skipped instead, producing no output. An entry whose sizes are
+    /// deferred to the data descriptor can't be skipped, so it still fails.
+    pub fn skip_encrypted_entries(mut self, enabled: bool) -> Self {
+        self.skip_encrypted_entries = enabled;
+        self
+    }
+
+    /// Sets the backend used for decompressing Zstandard entries:
+    /// `new_backend` is called to start a decoder for each entry.
+    /// With the `zstd` feature, libzstd is used by default.
+    /// Without a backend, Zstandard entries fail with
+    /// `ZipError::UnsupportedCompressionMethod`.
+    pub fn zstd_backend(
+        mut self,
+        new_backend: impl Fn() -> Box<dyn zstd::Backend> + Send + Sync + 'static,
+    ) -> Self {
+        self.zstd_backend = Some(Arc::new(new_backend));
+        self
+    }
+
+    /// The CRC-32 of the decompressed data of each entry is verified against
+    /// the one in its headers, and a mismatch fails with `ZipError::ChecksumMismatch`.
+    /// Enabled by default. Disabling it makes it possible to salvage the data
+    /// of corrupted entries.
+    pub fn verify_checksums(mut self, enabled: bool) -> Self {
+        self.ignore_checksums = !enabled;
+        self
+    }
+
+    /// When enabled, the central directory is checked against the entries that
+    /// were streamed before it: the number of entries, and the offsets of their
+    /// local file headers, their sizes and their CRC-32s. The differences are
+    /// collected into a `CentralDirReport`, available from `ZipFile::central_dir_report`
+    /// of the last file of the archive once the archive has ended. Offsets are counted
+    /// from the start of the stream, including any data skipped with `scan_for_start`.
+    /// The memory this takes grows with the number of entries in the archive.
+    pub fn validate_central_dir(mut self, enabled: bool) -> Self {
+        self.validate_central_dir = enabled;
+        self
+    }
+
+    /// Whether the CRC-32 of the entry described by `header` is verified.
+    /// Entries encrypted with AE-2 don't store it, since the authentication
+    /// code already covers the data.
+    fn verifies_checksum(&self, header: &LocalFileHeader) -> bool {
+        let ae_2 = header.compression_method == CompressionMethod::AesEncrypted
+            && header.aes().map(|field| field.vendor_version) == Some(2);
+        !self.ignore_checksums && !ae_2
+    }
+
+    fn new_deflate_stream(&self) -> deflate::Stream {
+        deflate::Stream::with(self.output_buffer_size.unwrap_or(0), 0)
+    }
+
+    fn new_zstd_backend(&self) -> Option<Box<dyn zstd::Backend>> {
+        match &self.zstd_backend {
+            Some(new_backend) => Some(new_backend()),
+            #[cfg(feature = "zstd")]
+            None => Some(Box::new(zstd::LibZstd::default())),
+            #[cfg(not(feature = "zstd"))]
+            None => None,
+        }
+    }
 }
 
 impl std::fmt::Debug for ZipFile {
@@ -23,35 +383,185 @@ impl std::fmt::Debug for ZipFile {
 }
 
 impl CompressedStream for ZipFile {
-    fn feed_input(&mut self, input: &[u8]) -> State<Self> {
-        unimplemented!();
+    type Error = ZipError;
+
+    fn read<'i, 's>(&'s mut self, input: &'i [u8]) -> Result<State<'i, 's, ZipFile>, ZipError> {
+        ZipFile::read(self, input)
+    }
+
+    fn get_output(&self) -> &[u8] {
+        ZipFile::get_output(self)
+    }
+
+    fn metadata(&self) -> EntryMetadata {
+        EntryMetadata {
+            name: self.filename().map(<[u8]>::to_vec),
+            size_hint: self
+                .header()
+                .and_then(|header| header.sizes())
+                .map(|(_, uncompressed_size)| uncompressed_size),
+            mtime: self.last_modified(),
+            is_dir: self.is_dir(),
+            compression: self
+                .header()
+                .map_or(Compression::Other, crate::zip_compression),
+        }
+    }
+
+    fn finish(&self) -> Result<(), ZipError> {
+        ZipFile::finish(self)
     }
 }
 
+#[cfg(feature = "aes")]
+pub mod aes;
+pub mod cp437;
 pub mod datetime;
+mod decompressor;
 pub mod headers;
+mod matcher;
+mod seek;
+mod writer;
+pub mod zipcrypto;
 
-use self::headers::{CentralDirHeader, DataDescriptor, LocalFileHeader};
+pub use self::matcher::EntryMatcher;
+pub use self::seek::{list_entries, SeekArchive, SeekError};
+pub use self::writer::{merge, recompress, AppendPoint, EntryOptions, Recompression, Writer};
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+use self::decompressor::Decompressor;
+use self::headers::{
+    CentralDirEnd, CentralDirHeader, CompressionMethod, DataDescriptor, HeaderId, LocalFileHeader,
+    Zip64CentralDirEnd, Zip64CentralDirEndLocator,
+};
+
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub enum ZipError {
     InvalidDateOrTime,
     InvalidVersionMadeBy,
     InvalidCompressionMethod,
     InvalidHeaderId,
-    NotLocalFileHeader,
+    /// The input doesn't have a local file header where one should start.
+    NotLocalFileHeader(ErrorContext),
     InvalidLocalFileHeaderBasicStructure,
     InvalidLocalFileHeaderFilename,
     InvalidLocalFileHeaderExtraFields,
+    /// A variable-length field of a header is longer than its limit.
+    /// See `Options::max_filename_len`.
+    FieldTooLong(HeaderField),
     InvalidDeflateStream,
+    InvalidBzip2Stream,
+    InvalidZstdStream,
+    InvalidPpmdStream,
+    InvalidLegacyStream,
+    /// The entry is encrypted, but no password is set.
+    EncryptedEntry,
+    /// The password doesn't match the encryption header of an encrypted entry.
+    InvalidPassword,
+    /// The authentication code after the data of an AES encrypted entry
+    /// doesn't match the data, so the data was corrupted or tampered with.
+    InvalidAuthenticationCode,
+    /// The entry is compressed with a method that isn't supported,
+    /// or whose support isn't enabled with a crate feature.
+    UnsupportedCompressionMethod(CompressionMethod),
     InvalidDataDescriptor,
-    NotCentralDirHeader,
+    /// The data of an entry decompresses to more than `Options::max_entry_size`.
+    EntryTooLarge,
+    /// The data of an entry decompresses to more than `Options::max_compression_ratio`
+    /// allows for its compressed size.
+    CompressionRatioExceeded,
+    /// The compressed data of an entry doesn't end where the sizes
+    /// in its local file header say it does.
+    EntrySizeMismatch,
+    /// The CRC-32 of the decompressed data of an entry doesn't match
+    /// the one in its headers, so the data is corrupted.
+    ChecksumMismatch {
+        expected: u32,
+        actual: u32,
+    },
+    /// The input ended before the end of the archive. `entry` is the index
+    /// of the entry that was in progress, if any, and `decompressed` the amount
+    /// of its data that was decompressed. See `ZipFile::finish`.
+    TruncatedArchive {
+        entry: Option<u64>,
+        decompressed: u64,
+    },
+    /// The target of a symbolic link entry is longer than the maximum length of a path.
+    InvalidSymlinkTarget,
+    /// The sizes of an entry are in a data descriptor, and the end of its data
+    /// can't be found, because it can't be decompressed, for example since
+    /// it's encrypted and no password is set. See `Writer::copy_archive`.
+    UnknownEntrySize,
+    /// The input doesn't have a central directory header where one should start.
+    NotCentralDirHeader(ErrorContext),
     InvalidCentralDirHeader,
+    /// The input doesn't have the end of central directory record, or one of the
+    /// records that precede it, where one should start.
+    NotCentralDirEnd(ErrorContext),
+    InvalidCentralDirEnd,
+    /// An error in the data or the data descriptor of an entry, with the entry it's in.
+    InEntry(Box<EntryError>),
     NomError(ErrorKind),
     OtherError,
 }
 
+/// An error that happened while an entry was decompressed or its data descriptor
+/// was checked, with the index and the filename of the entry, so that the entry
+/// can be told apart in an archive of many.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct EntryError {
+    /// The index of the entry in the archive.
+    pub index: u64,
+    /// The filename of the entry, as it is in its local file header.
+    pub name: Vec<u8>,
+    /// The error itself.
+    pub error: ZipError,
+}
+
 impl ZipError {
+    /// Where in the input the error was detected, and what was there,
+    /// if the error is about a header that isn't where it should be.
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            ZipError::NotLocalFileHeader(context)
+            | ZipError::NotCentralDirHeader(context)
+            | ZipError::NotCentralDirEnd(context) => Some(context),
+            ZipError::InEntry(entry_error) => entry_error.error.context(),
+            _ => None,
+        }
+    }
+
+    /// The index and the filename of the entry the error happened in,
+    /// if it's an error in the data of an entry.
+    pub fn entry(&self) -> Option<(u64, &[u8])> {
+        match self {
+            ZipError::InEntry(entry_error) => Some((entry_error.index, &entry_error.name)),
+            _ => None,
+        }
+    }
+
+    /// The error without the entry it happened in.
+    pub fn without_entry(&self) -> &ZipError {
+        match self {
+            ZipError::InEntry(entry_error) => &entry_error.error,
+            _ => self,
+        }
+    }
+
+    /// Moves the context of the error `by` bytes further in the input,
+    /// from the start of a parse step to the start of the stream.
+    fn shifted(self, by: u64) -> Self {
+        match self {
+            ZipError::NotLocalFileHeader(context) => {
+                ZipError::NotLocalFileHeader(context.shifted(by))
+            }
+            ZipError::NotCentralDirHeader(context) => {
+                ZipError::NotCentralDirHeader(context.shifted(by))
+            }
+            ZipError::NotCentralDirEnd(context) => ZipError::NotCentralDirEnd(context.shifted(by)),
+            _ => self,
+        }
+    }
+
     fn replace_external(self, with: ZipError) -> Self {
         match self {
             Self::NomError(_) => with,
@@ -101,10 +611,159 @@ impl std::error::Error for ZipError {
 
 impl std::fmt::Display for ZipError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "zip uncompressing error: {:?}", self)
+        match self {
+            ZipError::NotLocalFileHeader(context) => {
+                write!(f, "zip uncompressing error: NotLocalFileHeader {}", context)
+            }
+            ZipError::NotCentralDirHeader(context) => {
+                write!(
+                    f,
+                    "zip uncompressing error: NotCentralDirHeader {}",
+                    context
+                )
+            }
+            ZipError::NotCentralDirEnd(context) => {
+                write!(f, "zip uncompressing error: NotCentralDirEnd {}", context)
+            }
+            ZipError::InEntry(entry_error) => write!(
+                f,
+                "{} in entry {} ({})",
+                entry_error.error,
+                entry_error.index,
+                String::from_utf8_lossy(&entry_error.name)
+            ),
+            _ => write!(f, "zip uncompressing error: {:?}", self),
+        }
+    }
+}
+
+/// A difference between the central directory of an archive and the entries
+/// that were streamed before it. The entries are indexed in the order of the archive.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Discrepancy {
+    /// The number of central directory headers differs from the number of streamed entries.
+    EntryCount { streamed: u64, central_dir: u64 },
+    /// The end of central directory record counts a different number of entries
+    /// than there are central directory headers.
+    RecordedEntryCount {
+        central_dir: u64,
+        central_dir_end: u64,
+    },
+    /// The central directory doesn't start where the end of central directory record says.
+    CentralDirOffset { streamed: u64, central_dir_end: u64 },
+    LocalHeaderOffset {
+        index: u64,
+        streamed: u64,
+        central_dir: u64,
+    },
+    CompressedSize {
+        index: u64,
+        streamed: u64,
+        central_dir: u64,
+    },
+    UncompressedSize {
+        index: u64,
+        streamed: u64,
+        central_dir: u64,
+    },
+    Crc32 {
+        index: u64,
+        streamed: u32,
+        central_dir: u32,
+    },
+}
+
+/// The result of checking the central directory of an archive against
+/// the entries that were streamed before it. See `Options::validate_central_dir`.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct CentralDirReport {
+    pub streamed_entries: u64,
+    pub central_dir_entries: u64,
+    pub discrepancies: Vec<Discrepancy>,
+}
+
+impl CentralDirReport {
+    /// Whether the central directory matches the streamed entries.
+    pub fn is_consistent(&self) -> bool {
+        self.discrepancies.is_empty()
+    }
+
+    /// Compares `header`, the next central directory header, to the streamed entry at its index.
+    /// The values that are saturated and whose Zip64 extra field was skipped aren't compared.
+    fn check_header(&mut self, header: &CentralDirHeader, streamed_entries: &[StreamedEntry]) {
+        let index = self.central_dir_entries;
+        self.central_dir_entries += 1;
+        let streamed = match streamed_entries.get(index as usize) {
+            Some(streamed) => streamed,
+            None => return,
+        };
+        if let Some(central_dir) = header.local_header_offset() {
+            if central_dir != streamed.header_offset {
+                self.discrepancies.push(Discrepancy::LocalHeaderOffset {
+                    index,
+                    streamed: streamed.header_offset,
+                    central_dir,
+                });
+            }
+        }
+        if let Some((compressed_size, uncompressed_size)) = header.sizes() {
+            if compressed_size != streamed.comp_size {
+                self.discrepancies.push(Discrepancy::CompressedSize {
+                    index,
+                    streamed: streamed.comp_size,
+                    central_dir: compressed_size,
+                });
+            }
+            if uncompressed_size != streamed.uncomp_size {
+                self.discrepancies.push(Discrepancy::UncompressedSize {
+                    index,
+                    streamed: streamed.uncomp_size,
+                    central_dir: uncompressed_size,
+                });
+            }
+        }
+        if header.crc_32 != streamed.crc_32 {
+            self.discrepancies.push(Discrepancy::Crc32 {
+                index,
+                streamed: streamed.crc_32,
+                central_dir: header.crc_32,
+            });
+        }
+    }
+
+    /// Compares the counts and the offset recorded at the end of the central directory,
+    /// which started at `central_dir_offset`, to what was streamed.
+    fn check_end(&mut self, central_dir_offset: u64, num_entries: u64, start_offset: u64) {
+        if self.central_dir_entries != self.streamed_entries {
+            self.discrepancies.push(Discrepancy::EntryCount {
+                streamed: self.streamed_entries,
+                central_dir: self.central_dir_entries,
+            });
+        }
+        if num_entries != self.central_dir_entries {
+            self.discrepancies.push(Discrepancy::RecordedEntryCount {
+                central_dir: self.central_dir_entries,
+                central_dir_end: num_entries,
+            });
+        }
+        if start_offset != central_dir_offset {
+            self.discrepancies.push(Discrepancy::CentralDirOffset {
+                streamed: central_dir_offset,
+                central_dir_end: start_offset,
+            });
+        }
     }
 }
 
+/// What was streamed of an entry, for checking it against the central directory.
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct StreamedEntry {
+    header_offset: u64,
+    comp_size: u64,
+    uncomp_size: u64,
+    crc_32: u32,
+}
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 struct HeaderParsed {
     header: LocalFileHeader,
@@ -112,59 +771,293 @@ struct HeaderParsed {
 #[derive(Debug, Clone, Eq, PartialEq)]
 struct Inflated {
     header: LocalFileHeader,
-    comp_size: usize,
-    uncomp_size: usize,
+    comp_size: u64,
+    uncomp_size: u64,
+    /// The CRC-32 of the decompressed data, if the data was decompressed instead of skipped.
+    crc_32: Option<u32>,
+}
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct ScanningData {
+    header: LocalFileHeader,
+    /// The amount of data before the input.
+    comp_size: u64,
+}
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct Resyncing {
+    header: LocalFileHeader,
+    /// The amount of data before the input.
+    comp_size: u64,
+    uncomp_size: u64,
 }
 #[derive(Debug, Clone, Eq, PartialEq)]
 struct DescriptorParsed {
     header: LocalFileHeader,
-    comp_size: usize,
-    uncomp_size: usize,
+    comp_size: u64,
+    uncomp_size: u64,
+    /// The CRC-32 of the data: the computed one if the data was decompressed,
+    /// and the one in the headers otherwise.
+    crc_32: u32,
+    /// The data descriptor after the data, if the entry has one.
+    descriptor: Option<DataDescriptor>,
+    /// What was parsed after the entry, if it's the last entry of the archive.
+    /// It's boxed because only one entry of each archive has it.
+    archive_end: Option<Box<ArchiveEnd>>,
+}
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+struct ArchiveEnd {
+    /// The central directory headers, if `Options::keep_central_dir` is enabled.
+    central_dir: Vec<CentralDirHeader>,
+    central_dir_end: Option<CentralDirEnd>,
+    /// The check of the central directory, if `Options::validate_central_dir` is enabled.
+    report: Option<CentralDirReport>,
+    /// The offset in the archive where the central directory starts.
+    central_dir_offset: u64,
+    /// The Zip64 end of central directory record, if `Options::validate_central_dir`
+    /// is enabled and the archive has one.
+    zip64_central_dir_end: Option<Zip64CentralDirEnd>,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 enum InternalState {
+    /// Skipping the data before the first local file header.
+    LeadingData {
+        skipped: u64,
+    },
     Init,
     HeaderParsed(HeaderParsed),
+    /// Skipping data whose size is deferred to the data descriptor.
+    ScanningData(ScanningData),
+    /// Skipping the rest of a damaged entry, up to the next header.
+    Resyncing(Resyncing),
     Inflated(Inflated),
     DescriptorParsed(DescriptorParsed),
+    CentralDir(DescriptorParsed),
+    ArchiveEnd(DescriptorParsed),
     End(DescriptorParsed),
+    Skip {
+        remaining: u64,
+        then: Box<InternalState>,
+    },
     Sentinel,
-    Error,
+    /// The stream failed with the error, which later reads return again.
+    Error(ZipError),
+}
+
+impl InternalState {
+    /// Returns a state that skips `bytes` bytes of input before continuing with `then`.
+    fn skip_then(bytes: u64, then: InternalState) -> InternalState {
+        if bytes == 0 {
+            then
+        } else {
+            InternalState::Skip {
+                remaining: bytes,
+                then: Box::new(then),
+            }
+        }
+    }
+
+    fn header(&self) -> Option<&LocalFileHeader> {
+        match self {
+            InternalState::LeadingData { .. } | InternalState::Init => None,
+            InternalState::HeaderParsed(state) => Some(&state.header),
+            InternalState::ScanningData(state) => Some(&state.header),
+            InternalState::Resyncing(state) => Some(&state.header),
+            InternalState::Inflated(state) => Some(&state.header),
+            InternalState::DescriptorParsed(state) => Some(&state.header),
+            InternalState::CentralDir(state) => Some(&state.header),
+            InternalState::ArchiveEnd(state) => Some(&state.header),
+            InternalState::End(state) => Some(&state.header),
+            InternalState::Skip { then, .. } => then.header(),
+            InternalState::Error(_) => None,
+            InternalState::Sentinel => unreachable!("header is never called with this"),
+        }
+    }
+
+    /// The data descriptor of the entry, once the entry has ended.
+    /// The sizes and the CRC-32 of the entry, once it has ended.
+    fn totals(&self) -> Option<EntryTotals> {
+        match self {
+            InternalState::DescriptorParsed(state)
+            | InternalState::CentralDir(state)
+            | InternalState::ArchiveEnd(state)
+            | InternalState::End(state) => Some(EntryTotals {
+                compressed_size: state.comp_size,
+                uncompressed_size: state.uncomp_size,
+                crc_32: state.crc_32,
+            }),
+            InternalState::Skip { then, .. } => then.totals(),
+            _ => None,
+        }
+    }
+
+    fn descriptor(&self) -> Option<&DataDescriptor> {
+        match self {
+            InternalState::DescriptorParsed(state)
+            | InternalState::CentralDir(state)
+            | InternalState::ArchiveEnd(state)
+            | InternalState::End(state) => state.descriptor.as_ref(),
+            InternalState::Skip { then, .. } => then.descriptor(),
+            _ => None,
+        }
+    }
+
+    /// What was parsed after the last entry of the archive, once the central directory is reached.
+    fn archive_end(&self) -> Option<&ArchiveEnd> {
+        match self {
+            InternalState::CentralDir(state)
+            | InternalState::ArchiveEnd(state)
+            | InternalState::End(state) => state.archive_end.as_deref(),
+            InternalState::Skip { then, .. } => then.archive_end(),
+            _ => None,
+        }
+    }
+}
+
+/// Parses a header fully or, if its variable-length fields are longer
+/// than `threshold`, only its fixed-size part. Returns the header
+/// and the amount of variable-length data left to skip.
+fn parse_lazily<'i, H, E>(
+    input: &'i [u8],
+    threshold: Option<usize>,
+    parse: impl Fn(&'i [u8]) -> IResult<&'i [u8], H, E>,
+    parse_fixed: impl Fn(&'i [u8]) -> IResult<&'i [u8], (H, u64), E>,
+) -> IResult<&'i [u8], (H, u64), E> {
+    if let Some(threshold) = threshold {
+        let (unparsed, (header, variable_len)) = parse_fixed(input)?;
+        if variable_len > threshold as u64 {
+            return Ok((unparsed, (header, variable_len)));
+        }
+    }
+    let (unparsed, header) = parse(input)?;
+    Ok((unparsed, (header, 0)))
+}
+
+/// The offsets of the lengths of the variable-length fields of the headers,
+/// counted from the signature, and the fields they are the lengths of.
+const LOCAL_FILE_HEADER_FIELDS: &[(usize, HeaderField)] =
+    &[(26, HeaderField::Filename), (28, HeaderField::ExtraFields)];
+const CENTRAL_DIR_HEADER_FIELDS: &[(usize, HeaderField)] = &[
+    (28, HeaderField::Filename),
+    (30, HeaderField::ExtraFields),
+    (32, HeaderField::Comment),
+];
+const CENTRAL_DIR_END_FIELDS: &[(usize, HeaderField)] = &[(20, HeaderField::Comment)];
+
+/// Checks the lengths of the variable-length fields of the header that starts
+/// `input` with `tag` against `limits`, before the fields are buffered.
+/// Returns the first field that is too long, if it isn't truncated.
+fn field_too_long(
+    input: &[u8],
+    tag: &[u8],
+    fields: &[(usize, HeaderField)],
+    limits: &FieldLimits,
+) -> Option<HeaderField> {
+    if !input.starts_with(tag) {
+        return None;
+    }
+    fields.iter().find_map(|&(offset, field)| {
+        let len = input.get(offset..offset + 2)?;
+        let len = u16::from_le_bytes([len[0], len[1]]) as usize;
+        Some(field).filter(|&field| limits.rejects(field, len))
+    })
+}
+
+/// Drops the extra fields that don't fit in the limit, once the ones before them are counted.
+fn truncate_extra_fields(extra_fields: &mut Vec<(HeaderId, Vec<u8>)>, limits: &FieldLimits) {
+    if let Some(limit) = limits.extra_fields {
+        let mut len = 0;
+        extra_fields.retain(|(_, data)| {
+            len += 4 + data.len();
+            len <= limit
+        });
+    }
+}
+
+/// Parses a local file header with `parse_lazily`, checking and truncating
+/// its fields according to the limits of `options`.
+fn parse_local_header<'i>(
+    input: &'i [u8],
+    options: &Options,
+) -> IResult<&'i [u8], (LocalFileHeader, u64), ZipError> {
+    let limits = &options.field_limits;
+    if let Some(field) = field_too_long(
+        input,
+        headers::LOCAL_FILE_HEADER_TAG,
+        LOCAL_FILE_HEADER_FIELDS,
+        limits,
+    ) {
+        return Err(nom::Err::Failure(ZipError::FieldTooLong(field)));
+    }
+    let (unparsed, (mut header, skip)) = parse_lazily(
+        input,
+        options.lazy_field_threshold,
+        LocalFileHeader::parse,
+        LocalFileHeader::parse_fixed,
+    )?;
+    limits.truncate(HeaderField::Filename, &mut header.filename);
+    truncate_extra_fields(&mut header.extra_fields, limits);
+    Ok((unparsed, (header, skip)))
 }
 
 #[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
 enum ParseResult {
     Continue,
     NeedsInput,
     Output,
     Error(ZipError),
     NextFile(ZipFile),
+    NextArchive(ZipFile),
+    NeedsInputOrEof(ZipFile),
     EndOfFile,
 }
 
 impl ZipFile {
     pub fn get_output(&self) -> &[u8] {
-        self.inflater.get_output()
+        match &self.inflater {
+            Some(inflater) => inflater.get_output(),
+            None => &[],
+        }
     }
 
     pub fn read_headers<'i>(&mut self, input: &'i [u8]) -> Result<ReadHeadersResult<'i>, ZipError> {
-        if let InternalState::Init = self.state {
-        } else {
-            return Ok(ReadHeadersResult::Done { unparsed: input });
+        match &self.state {
+            InternalState::LeadingData { .. } | InternalState::Init => (),
+            InternalState::Error(error) => return Err(error.clone()),
+            InternalState::Skip { then, .. }
+                if matches!(**then, InternalState::HeaderParsed(_)) => {}
+            _ => return Ok(ReadHeadersResult::Done { unparsed: input }),
         }
 
         let mut ihandler = InputHandler::take_storage(&mut self.unparsed, input);
         let mut unparsed = ihandler.get_unparsed();
         let res = loop {
-            let (bytes_consumed, new_state, res) = ZipFile::parse_header(unparsed);
+            let mut state = InternalState::Sentinel;
+            std::mem::swap(&mut self.state, &mut state);
+            // The offset of the input of the step from the start of the stream.
+            let step_offset = self.progress.compressed_bytes;
+            let (bytes_consumed, new_state, res) = match state {
+                InternalState::LeadingData { skipped } => self.scan_for_start(unparsed, skipped),
+                InternalState::Init => self.parse_header(unparsed),
+                InternalState::Skip { remaining, then } => ZipFile::skip(unparsed, remaining, then),
+                state => {
+                    self.state = state;
+                    break Ok(ReadHeadersResult::Done {
+                        unparsed: unparsed.assert_take_long(),
+                    });
+                }
+            };
             unparsed = ihandler.consumed(bytes_consumed);
+            self.position += bytes_consumed as u64;
             self.state = new_state;
+            self.count_progress(bytes_consumed, false, &res);
             match res {
                 ParseResult::Continue => {
-                    break Ok(ReadHeadersResult::Done {
-                        unparsed: unparsed.assert_take_long(),
-                    });
+                    if unparsed.is_empty() {
+                        if let InternalState::Skip { .. } = self.state {
+                            break Ok(ReadHeadersResult::NeedsInput);
+                        }
+                    }
                 }
                 ParseResult::NeedsInput => {
                     let ext_len = ihandler.extend_input();
@@ -173,7 +1066,7 @@ impl ZipFile {
                     }
                     unparsed = ihandler.get_unparsed();
                 }
-                ParseResult::Error(err) => break Err(err),
+                ParseResult::Error(err) => break Err(self.fail(err, step_offset)),
                 _ => {
                     unreachable!();
                 }
@@ -183,18 +1076,105 @@ impl ZipFile {
         res
     }
 
+    /// The input consumed, the output produced and the entries completed so far,
+    /// counted from the start of the stream over all the files that it has
+    /// continued in. The output of the latest `State::HasOutput` is included.
+    pub fn progress(&self) -> Summary {
+        self.progress
+    }
+
+    /// Counts a parse step that consumed `bytes_consumed` bytes and resulted
+    /// in `res`, and the entry if the step ended it, and reports the progress.
+    /// Returns whether the step ended the entry.
+    fn count_progress(&mut self, bytes_consumed: usize, ended: bool, res: &ParseResult) -> bool {
+        let before = self.progress;
+        self.progress.compressed_bytes += bytes_consumed as u64;
+        if let ParseResult::Output = res {
+            self.progress.uncompressed_bytes += self.get_output().len() as u64;
+        }
+        let entry_ended = !ended && self.state.totals().is_some();
+        if entry_ended {
+            self.progress.files += 1;
+        }
+        self.options.hooks.report_progress(&before, &self.progress);
+        entry_ended
+    }
+
+    /// Whether a stop condition of the options is met after a parse step that
+    /// resulted in `res`. The output of a step is returned before stopping.
+    fn should_stop(&self, entry_ended: bool, res: &ParseResult) -> bool {
+        if let ParseResult::Output | ParseResult::Error(_) = res {
+            return false;
+        }
+        let ended_entry = if entry_ended {
+            Some(self.filename())
+        } else {
+            None
+        };
+        self.options.stop.are_met(&self.progress, ended_entry)
+    }
+
+    /// Continues the progress of this file in `next_file`, which parses
+    /// its unparsed input again.
+    fn pass_progress(&self, mut next_file: ZipFile) -> ZipFile {
+        next_file.progress = Summary {
+            compressed_bytes: self.progress.compressed_bytes - next_file.unparsed.len() as u64,
+            ..self.progress
+        };
+        next_file
+    }
+
+    /// Fails the stream with `error` of a parse step whose input started at `step_offset`.
+    /// The error is kept in the state, so that later reads return it again.
+    fn fail(&mut self, error: ZipError, step_offset: u64) -> ZipError {
+        let error = error.shifted(step_offset);
+        self.state = InternalState::Error(error.clone());
+        error
+    }
+
+    /// Calls the hooks of the options that are due. See `Options::on_entry_start`.
+    fn call_hooks(&mut self) {
+        if let InternalState::Error(_) = self.state {
+            return;
+        }
+        let mut progress = self.hook_progress;
+        self.options.hooks.call(
+            &mut progress,
+            self.header().is_some(),
+            self.state.totals(),
+            || CompressedStream::metadata(self),
+        );
+        self.hook_progress = progress;
+    }
+
     pub fn read<'i, 's>(&'s mut self, input: &'i [u8]) -> Result<State<'i, 's, ZipFile>, ZipError>
     where
         'i: 's,
     {
+        if let InternalState::Error(error) = &self.state {
+            return Err(error.clone());
+        }
+        if self.stopped || self.options.stop.are_met(&self.progress, None) {
+            self.stopped = true;
+            return Ok(State::Stopped);
+        }
         let mut ihandler = InputHandler::take_storage(&mut self.unparsed, input);
         let mut unparsed = ihandler.get_unparsed();
         let res = loop {
+            let ended = self.state.totals().is_some();
             let mut state = InternalState::Sentinel;
             std::mem::swap(&mut self.state, &mut state);
+            let step_offset = self.progress.compressed_bytes;
             let (bytes_consumed, new_state, res) = self.parse_step(state, unparsed);
             unparsed = ihandler.consumed(bytes_consumed);
+            self.position += bytes_consumed as u64;
             self.state = new_state;
+            let entry_ended = self.count_progress(bytes_consumed, ended, &res);
+            self.call_hooks();
+            if self.should_stop(entry_ended, &res) {
+                self.stopped = true;
+                break Ok(State::Stopped);
+            }
             match res {
                 ParseResult::Continue => (),
                 ParseResult::NeedsInput => {
@@ -209,22 +1189,42 @@ impl ZipFile {
                     let unparsed_input = unparsed.assert_take_long();
                     break Ok(State::HasOutput {
                         unparsed_input,
-                        output: self.inflater.get_output(),
+                        output: self
+                            .inflater
+                            .as_ref()
+                            .expect("Output is only produced by a started decompressor")
+                            .get_output(),
                     });
                 }
                 ParseResult::NextFile(next_file) => {
                     let unparsed_input = unparsed.assert_take_long();
                     break Ok(State::NextFile {
                         unparsed_input,
-                        next_file,
+                        next_file: self.pass_progress(next_file),
+                    });
+                }
+                ParseResult::NextArchive(next_file) => {
+                    let unparsed_input = unparsed.assert_take_long();
+                    break Ok(State::NextArchive {
+                        unparsed_input,
+                        next_file: self.pass_progress(next_file),
                     });
                 }
+                ParseResult::NeedsInputOrEof(next_file) => {
+                    break Ok(State::NeedsInputOrEof(self.pass_progress(next_file)));
+                }
                 ParseResult::EndOfFile => {
                     break Ok(State::EndOfFile);
                 }
-                ParseResult::Error(err) => break Err(err),
+                ParseResult::Error(err) => break Err(self.fail(err, step_offset)),
             };
             if unparsed.is_empty() {
+                if let InternalState::ArchiveEnd(_) = self.state {
+                    // Running out of input right after an archive is
+                    // a valid place for the stream to end, so
+                    // we let the parser report that.
+                    continue;
+                }
                 break Ok(State::NeedsInput);
             }
         };
@@ -238,71 +1238,155 @@ impl ZipFile {
         mut callback: impl FnMut(&[u8]),
     ) -> Result<crate::State<'i, 'i, ZipFile>, ZipError> {
         loop {
-            let state = self.read(input)?;
-            if let State::HasOutput {
-                unparsed_input,
-                output,
-            } = state
-            {
-                input = unparsed_input;
-                callback(output);
-            } else {
-                return Ok(state.assert_no_output());
+            match self.read(input)?.try_no_output() {
+                Err((unparsed_input, output)) => {
+                    input = unparsed_input;
+                    callback(output);
+                }
+                Ok(state) => return Ok(state),
             }
         }
     }
 
+    /// Like `read_with`, but `callback` may fail, which stops
+    /// the reading and returns its error as `TryReadError::Callback`.
+    pub fn try_read_with<'i, E>(
+        &mut self,
+        input: &'i [u8],
+        callback: impl FnMut(&[u8]) -> Result<(), E>,
+    ) -> Result<State<'i, 'i, ZipFile>, TryReadError<ZipError, E>> {
+        crate::try_read_with(self, input, callback)
+    }
+
     fn parse_step<'long, 'short>(
         &'short mut self,
         state: InternalState,
         input: Input<'long, 'short>,
     ) -> (usize, InternalState, ParseResult) {
         match state {
-            InternalState::Init => ZipFile::parse_header(input),
+            InternalState::LeadingData { skipped } => self.scan_for_start(input, skipped),
+            InternalState::Init => self.parse_header(input),
             InternalState::HeaderParsed(state) => self.inflate(input, state),
-            InternalState::Inflated(state) => ZipFile::parse_descriptor(input, state),
-            InternalState::DescriptorParsed(state) => ZipFile::end(input, state),
+            InternalState::ScanningData(state) => ZipFile::scan_data(input, state),
+            InternalState::Resyncing(state) => self.resync(input, state),
+            InternalState::Inflated(state) => self.parse_descriptor(input, state),
+            InternalState::DescriptorParsed(state) => self.end(input, state),
+            InternalState::CentralDir(state) => self.parse_central_dir(input, state),
+            InternalState::ArchiveEnd(state) => self.after_archive(input, state),
             end_state @ InternalState::End { .. } => (0, end_state, ParseResult::EndOfFile),
+            InternalState::Skip { remaining, then } => ZipFile::skip(input, remaining, then),
             InternalState::Sentinel => unreachable!("parse_step is never called with Sentinel"),
-            InternalState::Error => panic!("Don't call read with Error"),
+            InternalState::Error(error) => (
+                0,
+                InternalState::Error(error.clone()),
+                ParseResult::Error(error),
+            ),
         }
     }
 
-    fn parse_header<'long, 'short>(
+    /// Consumes the data before the first local file header. The data may contain
+    /// the signature itself, for example if it's the stub of a self-extracting archive
+    /// that looks for it, so a signature without a valid header after it is skipped.
+    fn scan_for_start<'long, 'short>(
+        &self,
         input: Input<'long, 'short>,
+        skipped: u64,
     ) -> (usize, InternalState, ParseResult) {
-        match LocalFileHeader::parse(*input) {
-            Ok((unparsed, header)) => {
-                let bytes_parsed = input.len() - unparsed.len();
-                let inflater = deflate::Stream::new();
-                (
-                    bytes_parsed,
-                    InternalState::HeaderParsed(HeaderParsed { header }),
-                    ParseResult::Continue,
+        let tag = headers::LOCAL_FILE_HEADER_TAG;
+        let found = input.windows(tag.len()).position(|window| window == tag);
+        // The last bytes may be the start of a signature that continues in the next input.
+        let skip_len = found.unwrap_or_else(|| input.len().saturating_sub(tag.len() - 1));
+        let skipped = skipped + skip_len as u64;
+        if skipped > self.options.max_skip.unwrap_or(0) {
+            let error = ZipError::NotLocalFileHeader(ErrorContext::new(tag, *input));
+            return (
+                0,
+                InternalState::Error(error.clone()),
+                ParseResult::Error(error),
+            );
+        }
+
+        // More input is asked for only once nothing more can be skipped,
+        // that is, once the possible signature is at the start of the input.
+        let needs_input = if skip_len == 0 {
+            ParseResult::NeedsInput
+        } else {
+            ParseResult::Continue
+        };
+        let start = match found {
+            Some(start) => start,
+            None => {
+                return (
+                    skip_len,
+                    InternalState::LeadingData { skipped },
+                    needs_input,
                 )
             }
-            Err(nom::Err::Incomplete(_need)) => (0, InternalState::Init, ParseResult::NeedsInput),
-            Err(nom::Err::Error(_e)) => (
-                0,
-                InternalState::Error,
-                ParseResult::Error(_e),
-            ),
-            Err(nom::Err::Failure(_e)) => (
-                0,
-                InternalState::Error,
-                ParseResult::Error(_e),
+        };
+        match LocalFileHeader::parse_fixed(&input[start..]) {
+            Ok(_) => (start, InternalState::Init, ParseResult::Continue),
+            Err(nom::Err::Incomplete(_)) => {
+                (start, InternalState::LeadingData { skipped }, needs_input)
+            }
+            Err(_) => (
+                start + 1,
+                InternalState::LeadingData {
+                    skipped: skipped + 1,
+                },
+                ParseResult::Continue,
             ),
         }
     }
 
+    fn parse_header<'long, 'short>(
+        &mut self,
+        input: Input<'long, 'short>,
+    ) -> (usize, InternalState, ParseResult) {
+        match parse_local_header(*input, &self.options) {
+            Ok((unparsed, (header, skip))) => {
+                let bytes_parsed = input.len() - unparsed.len();
+                self.header_offset = self.position;
+                (
+                    bytes_parsed,
+                    InternalState::skip_then(
+                        skip,
+                        InternalState::HeaderParsed(HeaderParsed { header }),
+                    ),
+                    ParseResult::Continue,
+                )
+            }
+            Err(nom::Err::Incomplete(_need)) => (0, InternalState::Init, ParseResult::NeedsInput),
+            Err(nom::Err::Error(_e)) => {
+                (0, InternalState::Error(_e.clone()), ParseResult::Error(_e))
+            }
+            Err(nom::Err::Failure(_e)) => {
+                (0, InternalState::Error(_e.clone()), ParseResult::Error(_e))
+            }
+        }
+    }
+
+    /// Consumes input until `remaining` bytes are skipped, then continues with `then`.
+    fn skip<'long, 'short>(
+        input: Input<'long, 'short>,
+        remaining: u64,
+        then: Box<InternalState>,
+    ) -> (usize, InternalState, ParseResult) {
+        let skipped = std::cmp::min(remaining, input.len() as u64);
+        let remaining = remaining - skipped;
+        let state = if remaining == 0 {
+            *then
+        } else {
+            InternalState::Skip { remaining, then }
+        };
+        (skipped as usize, state, ParseResult::Continue)
+    }
+
+    #[allow(clippy::result_large_err)]
     fn detect_empty_stream(
         input: &[u8],
         state: HeaderParsed,
     ) -> Result<HeaderParsed, (usize, InternalState, ParseResult)> {
-        if state.header.compressed_size == 0
-            && state.header.uncompressed_size == 0
-            && state.header.deferred_sizes.not()
-        {
+        if state.header.sizes() == Some((0, 0)) {
             if input.len() < 4 {
                 return Err((
                     0,
@@ -319,6 +1403,7 @@ impl ZipFile {
                         header: state.header,
                         uncomp_size: 0,
                         comp_size: 0,
+                        crc_32: Some(0),
                     }),
                     ParseResult::Continue,
                 ));
@@ -342,41 +1427,394 @@ impl ZipFile {
 
         let HeaderParsed { header } = state;
 
-        match self.inflater.feed_input(*input) {
-            Ok(deflate::State::NeedsInput { unparsed_input }) => (
-                input.len() - unparsed_input.len(),
+        if self.inflater.is_none() {
+            if let Some(filter) = &self.options.entry_filter {
+                if !filter(&header) {
+                    self.skip_data = true;
+                }
+            }
+        }
+        if self.skip_data {
+            return self.start_skipping_data(header);
+        }
+
+        if self.inflater.is_none()
+            && header.encrypted
+            && self.options.password.is_none()
+            && self.options.skip_encrypted_entries
+        {
+            if let Some((compressed_size, uncompressed_size)) = header.sizes() {
+                // The sizes are the real ones, so a data descriptor still matches them.
+                return (
+                    0,
+                    InternalState::skip_then(
+                        compressed_size,
+                        InternalState::Inflated(Inflated {
+                            header,
+                            comp_size: compressed_size,
+                            uncomp_size: uncompressed_size,
+                            crc_32: None,
+                        }),
+                    ),
+                    ParseResult::Continue,
+                );
+            }
+        }
+
+        if self.inflater.is_none() {
+            match Decompressor::for_header(&header, &self.options) {
+                Ok(inflater) => {
+                    self.inflater = Some(inflater);
+                    self.data_left = header.sizes().map(|(compressed_size, _)| compressed_size);
+                }
+                Err(e) if self.options.recover_damaged_entries => {
+                    return self.start_resyncing(header, e, 0, 0)
+                }
+                Err(e) => {
+                    let error = self.entry_error(&header, e);
+                    return (
+                        0,
+                        InternalState::Error(error.clone()),
+                        ParseResult::Error(error),
+                    );
+                }
+            }
+        }
+        let inflater = self
+            .inflater
+            .as_mut()
+            .expect("The decompressor was just started");
+
+        // When the sizes are known, the decompressor gets no more than the compressed
+        // data, so that it can't consume what follows if the data is invalid.
+        let data = match self.data_left {
+            Some(data_left) if data_left < input.len() as u64 => &input[..data_left as usize],
+            _ => *input,
+        };
+        let result = inflater.feed_input(data).map(|state| {
+            let consumed = match &state {
+                deflate::State::HasOutput { unparsed_input, .. }
+                | deflate::State::NeedsInput { unparsed_input }
+                | deflate::State::Stop { unparsed_input } => data.len() - unparsed_input.len(),
+            };
+            (state, consumed)
+        });
+        if let Ok((_, consumed)) = &result {
+            self.data_left = self.data_left.map(|data_left| data_left - *consumed as u64);
+        }
+        match result {
+            // The data must end exactly where the sizes in the header say it does.
+            Ok((deflate::State::NeedsInput { .. }, consumed)) if self.data_left == Some(0) => {
+                self.fail_entry(header, ZipError::EntrySizeMismatch, consumed)
+            }
+            Ok((deflate::State::NeedsInput { .. }, consumed)) => (
+                consumed,
                 InternalState::HeaderParsed(HeaderParsed { header }),
                 ParseResult::Continue,
             ),
-            Ok(deflate::State::HasOutput {
-                unparsed_input,
-                output,
-            }) => {
-                let consumed_bytes = input.len() - unparsed_input.len();
+            Ok((deflate::State::HasOutput { output, .. }, consumed)) => {
+                self.crc_32 = update_crc32(self.crc_32, output);
+                let uncomp_size = inflater.uncompressed_size();
+                if self
+                    .options
+                    .max_entry_size
+                    .is_some_and(|max| uncomp_size > max)
+                {
+                    let error = self.entry_error(&header, ZipError::EntryTooLarge);
+                    return (
+                        consumed,
+                        InternalState::Error(error.clone()),
+                        ParseResult::Error(error),
+                    );
+                }
+                if let Some(limit) = &self.options.max_compression_ratio {
+                    if limit.is_exceeded(inflater.compressed_size(), uncomp_size) {
+                        let error = self.entry_error(&header, ZipError::CompressionRatioExceeded);
+                        return (
+                            consumed,
+                            InternalState::Error(error.clone()),
+                            ParseResult::Error(error),
+                        );
+                    }
+                }
                 (
-                    consumed_bytes,
+                    consumed,
                     InternalState::HeaderParsed(HeaderParsed { header }),
                     ParseResult::Output,
                 )
             }
-            Ok(deflate::State::Stop { unparsed_input }) => (
-                input.len() - unparsed_input.len(),
-                InternalState::Inflated(Inflated {
+            Ok((deflate::State::Stop { .. }, consumed)) => {
+                let comp_size = inflater.compressed_size();
+                let uncomp_size = inflater.uncompressed_size();
+                let sizes_match = match (self.data_left, header.sizes()) {
+                    (Some(data_left), Some((_, expected))) => {
+                        data_left == 0 && uncomp_size == expected
+                    }
+                    _ => true,
+                };
+                if !sizes_match {
+                    return self.fail_entry(header, ZipError::EntrySizeMismatch, consumed);
+                }
+                if let Some(descriptor) = inflater.descriptor() {
+                    // The descriptor was matched with the data when it was found.
+                    return (
+                        consumed,
+                        InternalState::DescriptorParsed(DescriptorParsed {
+                            header,
+                            comp_size,
+                            uncomp_size,
+                            crc_32: self.crc_32,
+                            descriptor: Some(descriptor.clone()),
+                            archive_end: None,
+                        }),
+                        ParseResult::Continue,
+                    );
+                }
+                (
+                    consumed,
+                    InternalState::Inflated(Inflated {
+                        header,
+                        comp_size,
+                        uncomp_size,
+                        crc_32: Some(self.crc_32),
+                    }),
+                    ParseResult::Continue,
+                )
+            }
+            Err(e) => self.fail_entry(header, e, 0),
+        }
+    }
+
+    /// Wraps `error`, which happened in the entry of `header`, in `ZipError::InEntry`.
+    fn entry_error(&self, header: &LocalFileHeader, error: ZipError) -> ZipError {
+        ZipError::InEntry(Box::new(EntryError {
+            index: self.index,
+            name: header.filename.clone(),
+            error,
+        }))
+    }
+
+    /// Fails the entry that is being decompressed with `error`, or skips
+    /// the rest of it with `Options::recover_damaged_entries`. `consumed`
+    /// is the amount of input the decompressor consumed before failing.
+    fn fail_entry(
+        &mut self,
+        header: LocalFileHeader,
+        error: ZipError,
+        consumed: usize,
+    ) -> (usize, InternalState, ParseResult) {
+        if !self.options.recover_damaged_entries {
+            let error = self.entry_error(&header, error);
+            return (
+                0,
+                InternalState::HeaderParsed(HeaderParsed { header }),
+                ParseResult::Error(error),
+            );
+        }
+        let (comp_size, uncomp_size) = self.inflater.as_ref().map_or((0, 0), |inflater| {
+            (inflater.compressed_size(), inflater.uncompressed_size())
+        });
+        let (_, state, result) = self.start_resyncing(header, error, comp_size, uncomp_size);
+        (consumed, state, result)
+    }
+
+    /// With `Options::recover_damaged_entries`, skips the rest of an entry that
+    /// failed with `error`, after `comp_size` bytes of its data were consumed.
+    fn start_resyncing(
+        &mut self,
+        header: LocalFileHeader,
+        error: ZipError,
+        comp_size: u64,
+        uncomp_size: u64,
+    ) -> (usize, InternalState, ParseResult) {
+        self.inflater = None;
+        self.damage = Some(error);
+        (
+            0,
+            InternalState::Resyncing(Resyncing {
+                header,
+                comp_size,
+                uncomp_size,
+            }),
+            ParseResult::Continue,
+        )
+    }
+
+    /// Consumes the rest of a damaged entry, until the start of a valid local
+    /// file header or of the central directory.
+    fn resync(
+        &self,
+        input: Input<'_, '_>,
+        state: Resyncing,
+    ) -> (usize, InternalState, ParseResult) {
+        let tag_len = headers::LOCAL_FILE_HEADER_TAG.len();
+        for (start, window) in input.windows(tag_len).enumerate() {
+            let parsed = if window == headers::LOCAL_FILE_HEADER_TAG {
+                LocalFileHeader::parse_fixed(&input[start..]).map(|_| ())
+            } else if window == headers::CENTRAL_DIR_HEADER_TAG {
+                CentralDirHeader::parse_fixed(&input[start..]).map(|_| ())
+            } else if window == headers::CENTRAL_DIR_END_TAG {
+                CentralDirEnd::parse_fixed(&input[start..])
+                    .map(|_| ())
+                    .map_err(|e| e.map(|_: (&[u8], ErrorKind)| ZipError::InvalidCentralDirEnd))
+            } else {
+                continue;
+            };
+            let comp_size = state.comp_size + start as u64;
+            match parsed {
+                Ok(()) => {
+                    return (
+                        start,
+                        InternalState::DescriptorParsed(DescriptorParsed {
+                            header: state.header,
+                            comp_size,
+                            uncomp_size: state.uncomp_size,
+                            crc_32: self.crc_32,
+                            descriptor: None,
+                            archive_end: None,
+                        }),
+                        ParseResult::Continue,
+                    )
+                }
+                // The header continues in the next input.
+                Err(nom::Err::Incomplete(_)) => {
+                    let result = if start == 0 {
+                        ParseResult::NeedsInput
+                    } else {
+                        ParseResult::Continue
+                    };
+                    return (
+                        start,
+                        InternalState::Resyncing(Resyncing { comp_size, ..state }),
+                        result,
+                    );
+                }
+                Err(_) => (),
+            }
+        }
+
+        // The last bytes may be the start of a signature that continues in the next input.
+        let consumed = input.len().saturating_sub(tag_len - 1);
+        if consumed == 0 {
+            return (0, InternalState::Resyncing(state), ParseResult::NeedsInput);
+        }
+        (
+            consumed,
+            InternalState::Resyncing(Resyncing {
+                comp_size: state.comp_size + consumed as u64,
+                ..state
+            }),
+            ParseResult::Continue,
+        )
+    }
+
+    /// Skips the rest of the data of the entry instead of decompressing it.
+    fn start_skipping_data(
+        &mut self,
+        header: LocalFileHeader,
+    ) -> (usize, InternalState, ParseResult) {
+        // The decompressor may have been started before the entry was skipped.
+        let consumed = self
+            .inflater
+            .take()
+            .map_or(0, |inflater| inflater.compressed_size());
+        match header.sizes() {
+            Some((compressed_size, uncompressed_size)) => (
+                0,
+                InternalState::skip_then(
+                    compressed_size.saturating_sub(consumed),
+                    InternalState::Inflated(Inflated {
+                        header,
+                        comp_size: compressed_size,
+                        uncomp_size: uncompressed_size,
+                        crc_32: None,
+                    }),
+                ),
+                ParseResult::Continue,
+            ),
+            None => (
+                0,
+                InternalState::ScanningData(ScanningData {
                     header,
-                    comp_size: self.inflater.compressed_size(),
-                    uncomp_size: self.inflater.uncompressed_size(),
+                    comp_size: consumed,
                 }),
                 ParseResult::Continue,
             ),
-            Err(_) => (
+        }
+    }
+
+    /// Consumes data whose size is deferred to the data descriptor, until a data descriptor
+    /// whose compressed size matches the data is followed by the next header.
+    fn scan_data(input: Input<'_, '_>, state: ScanningData) -> (usize, InternalState, ParseResult) {
+        let is_zip64 = state.header.is_zip64;
+        let (descriptor_len, size_mask) = if is_zip64 {
+            (20, u64::MAX)
+        } else {
+            (12, u64::from(u32::MAX))
+        };
+        let tag_len = headers::DATA_DESCRIPTOR_TAG.len();
+
+        let next_header = input.windows(tag_len).enumerate().filter(|(_, window)| {
+            *window == headers::LOCAL_FILE_HEADER_TAG || *window == headers::CENTRAL_DIR_HEADER_TAG
+        });
+        for (end, _) in next_header {
+            // The descriptor may or may not start with its signature.
+            for &len in &[descriptor_len + tag_len, descriptor_len] {
+                let start = match end.checked_sub(len) {
+                    Some(start) => start,
+                    None => continue,
+                };
+                let comp_size = state.comp_size + start as u64;
+                let descriptor = &input[start..end];
+                let parsed = if is_zip64 {
+                    DataDescriptor::parse_zip64(descriptor)
+                } else {
+                    DataDescriptor::parse_zip(descriptor)
+                };
+                match parsed {
+                    Ok((rest, desc))
+                        if rest.is_empty()
+                            && desc.tag == (len > descriptor_len)
+                            && desc.compressed_size == comp_size & size_mask =>
+                    {
+                        return (
+                            start,
+                            InternalState::Inflated(Inflated {
+                                header: state.header,
+                                comp_size,
+                                uncomp_size: desc.uncompressed_size,
+                                crc_32: None,
+                            }),
+                            ParseResult::Continue,
+                        );
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        // The end of the input may be the start of a descriptor and a signature
+        // that continue in the next input.
+        let consumed = input.len().saturating_sub(descriptor_len + 2 * tag_len - 1);
+        if consumed == 0 {
+            return (
                 0,
-                InternalState::HeaderParsed(HeaderParsed { header }),
-                ParseResult::Error(ZipError::InvalidDeflateStream),
-            ),
+                InternalState::ScanningData(state),
+                ParseResult::NeedsInput,
+            );
         }
+        (
+            consumed,
+            InternalState::ScanningData(ScanningData {
+                header: state.header,
+                comp_size: state.comp_size + consumed as u64,
+            }),
+            ParseResult::Continue,
+        )
     }
 
     fn parse_descriptor(
+        &mut self,
         input: Input<'_, '_>,
         state: Inflated,
     ) -> (usize, InternalState, ParseResult) {
@@ -396,47 +1834,84 @@ impl ZipFile {
 
                 if state.header.is_zip64 {
                     // The sizes are actual sizes, not moduluses
-                    actual_uncomp_size = state.uncomp_size as u64;
-                    actual_comp_size = state.comp_size as u64;
+                    actual_uncomp_size = state.uncomp_size;
+                    actual_comp_size = state.comp_size;
                 } else {
                     // Some archivers store the file size as a modulus of 2^32 if it's over 4 GiB
-                    actual_uncomp_size = (state.uncomp_size as u64) % (std::u32::MAX as u64 + 1);
-                    actual_comp_size = (state.comp_size as u64) % (std::u32::MAX as u64 + 1);
+                    actual_uncomp_size = state.uncomp_size % (u32::MAX as u64 + 1);
+                    actual_comp_size = state.comp_size % (u32::MAX as u64 + 1);
                 }
 
                 let data_matches = actual_uncomp_size == desc.uncompressed_size
-                    && actual_comp_size as u64 == desc.compressed_size;
+                    && actual_comp_size == desc.compressed_size;
+
+                // The CRC-32 is in the data descriptor if the sizes are.
+                let expected_crc = if state.header.deferred_sizes {
+                    desc.crc_32
+                } else {
+                    state.header.crc_32
+                };
+                let crc_matches = match state.crc_32 {
+                    Some(actual) if self.options.verifies_checksum(&state.header) => {
+                        if actual == expected_crc {
+                            Ok(())
+                        } else {
+                            Err(ZipError::ChecksumMismatch {
+                                expected: expected_crc,
+                                actual,
+                            })
+                        }
+                    }
+                    _ => Ok(()),
+                };
 
                 let dparsed = DescriptorParsed {
                     header: state.header,
                     comp_size: state.comp_size,
                     uncomp_size: state.uncomp_size,
+                    crc_32: state.crc_32.unwrap_or(expected_crc),
+                    descriptor: if data_matches { Some(desc) } else { None },
+                    archive_end: None,
                 };
-                if data_matches {
-                    return (
-                        input.len() - unparsed.len(),
-                        InternalState::DescriptorParsed(dparsed),
-                        ParseResult::Continue,
-                    );
+                let error = if !data_matches && desc_must_exist {
+                    Some(ZipError::InvalidDataDescriptor)
                 } else {
-                    if desc_must_exist {
-                        return (
-                            0,
-                            InternalState::Error,
-                            ParseResult::Error(ZipError::InvalidDataDescriptor),
-                        );
+                    crc_matches.err()
+                };
+                if let Some(e) = error {
+                    if self.options.recover_damaged_entries {
+                        let DescriptorParsed {
+                            header,
+                            comp_size,
+                            uncomp_size,
+                            ..
+                        } = dparsed;
+                        self.start_resyncing(header, e, comp_size, uncomp_size)
                     } else {
-                        // Data was garbage, but the descriptor wasn't required to exist so it's good.
-                        return (
+                        let error = self.entry_error(&dparsed.header, e);
+                        (
                             0,
-                            InternalState::DescriptorParsed(dparsed),
-                            ParseResult::Continue,
-                        );
+                            InternalState::Error(error.clone()),
+                            ParseResult::Error(error),
+                        )
                     }
+                } else if data_matches {
+                    (
+                        input.len() - unparsed.len(),
+                        InternalState::DescriptorParsed(dparsed),
+                        ParseResult::Continue,
+                    )
+                } else {
+                    // Data was garbage, but the descriptor wasn't required to exist so it's good.
+                    (
+                        0,
+                        InternalState::DescriptorParsed(dparsed),
+                        ParseResult::Continue,
+                    )
                 }
             }
             Err(nom::Err::Incomplete(_)) => {
-                return (0, InternalState::Inflated(state), ParseResult::NeedsInput);
+                (0, InternalState::Inflated(state), ParseResult::NeedsInput)
             }
             Err(_) => {
                 unreachable!(
@@ -447,84 +1922,489 @@ impl ZipFile {
     }
 
     fn end<'long, 'short>(
+        &mut self,
         input: Input<'long, 'short>,
-        state: DescriptorParsed,
+        mut state: DescriptorParsed,
     ) -> (usize, InternalState, ParseResult) {
-        match peek_stream(*input) {
-            Ok((unparsed, next_file)) => {
+        // We need the full tag to tell a local file header apart from the central directory
+        if input.len() < headers::LOCAL_FILE_HEADER_TAG.len() {
+            return (
+                0,
+                InternalState::DescriptorParsed(state),
+                ParseResult::NeedsInput,
+            );
+        }
+
+        if self.options.validate_central_dir {
+            self.streamed_entries.push(StreamedEntry {
+                header_offset: self.header_offset,
+                comp_size: state.comp_size,
+                uncomp_size: state.uncomp_size,
+                crc_32: state.crc_32,
+            });
+        }
+
+        if !input.starts_with(headers::LOCAL_FILE_HEADER_TAG) {
+            if self.options.validate_central_dir {
+                let archive_end = state.archive_end.get_or_insert_with(Default::default);
+                archive_end.central_dir_offset = self.position;
+                archive_end.report = Some(CentralDirReport {
+                    streamed_entries: self.streamed_entries.len() as u64,
+                    ..CentralDirReport::default()
+                });
+            }
+            return (0, InternalState::CentralDir(state), ParseResult::Continue);
+        }
+
+        match peek_stream_with(*input, &self.options) {
+            Ok((unparsed, mut next_file)) => {
                 let bytes_parsed = input.len() - unparsed.len();
-                return (
+                next_file.continue_after(self, bytes_parsed);
+                (
                     bytes_parsed,
                     InternalState::End(state),
                     ParseResult::NextFile(next_file),
-                );
+                )
             }
-            Err(ZipError::NotLocalFileHeader) => (),
-            Err(e) => return (0, InternalState::Error, ParseResult::Error(e)),
+            Err(e) => (0, InternalState::Error(e.clone()), ParseResult::Error(e)),
+        }
+    }
+
+    /// Consumes the central directory one record at a time,
+    /// until the end of central directory record is reached.
+    fn parse_central_dir<'long, 'short>(
+        &self,
+        input: Input<'long, 'short>,
+        mut state: DescriptorParsed,
+    ) -> (usize, InternalState, ParseResult) {
+        if input.len() < headers::CENTRAL_DIR_HEADER_TAG.len() {
+            return (0, InternalState::CentralDir(state), ParseResult::NeedsInput);
+        }
+
+        let threshold = self.options.lazy_field_threshold;
+        let limits = &self.options.field_limits;
+        let tag = &input[..headers::CENTRAL_DIR_HEADER_TAG.len()];
+        if let Some(field) = field_too_long(
+            *input,
+            headers::CENTRAL_DIR_HEADER_TAG,
+            CENTRAL_DIR_HEADER_FIELDS,
+            limits,
+        ) {
+            return (
+                0,
+                InternalState::Error(ZipError::FieldTooLong(field)),
+                ParseResult::Error(ZipError::FieldTooLong(field)),
+            );
+        }
+        let record = if tag == headers::CENTRAL_DIR_HEADER_TAG {
+            parse_lazily(
+                *input,
+                threshold,
+                CentralDirHeader::parse,
+                CentralDirHeader::parse_fixed,
+            )
+            .map(|(unparsed, (mut header, skip))| {
+                limits.truncate(HeaderField::Filename, &mut header.filename);
+                truncate_extra_fields(&mut header.extra_fields, limits);
+                limits.truncate(HeaderField::Comment, &mut header.comment);
+                let archive_end = state.archive_end.get_or_insert_with(Default::default);
+                if let Some(report) = &mut archive_end.report {
+                    report.check_header(&header, &self.streamed_entries);
+                }
+                if let Some(callback) = &self.options.central_dir_callback {
+                    callback(&header);
+                }
+                if self.options.keep_central_dir {
+                    state
+                        .archive_end
+                        .get_or_insert_with(Default::default)
+                        .central_dir
+                        .push(header);
+                }
+                (unparsed, skip)
+            })
+        } else if tag == headers::ZIP64_CENTRAL_DIR_END_TAG {
+            Zip64CentralDirEnd::parse(*input).map(|(unparsed, end)| {
+                if self.options.validate_central_dir {
+                    state
+                        .archive_end
+                        .get_or_insert_with(Default::default)
+                        .zip64_central_dir_end = Some(end);
+                }
+                (unparsed, 0)
+            })
+        } else if tag == headers::ZIP64_CENTRAL_DIR_END_LOCATOR_TAG {
+            Zip64CentralDirEndLocator::parse(*input).map(|(unparsed, _locator)| (unparsed, 0))
+        } else if tag == headers::DIGITAL_SIGNATURE_TAG {
+            parse_lazily(
+                *input,
+                threshold,
+                |i| {
+                    headers::parse_digital_signature(i).map(|(unparsed, _signature)| (unparsed, ()))
+                },
+                |i| {
+                    headers::parse_digital_signature_fixed(i)
+                        .map(|(unparsed, len)| (unparsed, ((), len)))
+                },
+            )
+            .map(|(unparsed, ((), skip))| (unparsed, skip))
+        } else if tag == headers::CENTRAL_DIR_END_TAG {
+            return self.parse_central_dir_end(input, state);
+        } else {
+            let context = ErrorContext::new(headers::CENTRAL_DIR_HEADER_TAG, *input);
+            return (
+                0,
+                InternalState::Error(ZipError::NotCentralDirHeader(context)),
+                ParseResult::Error(ZipError::NotCentralDirHeader(context)),
+            );
         };
 
-        match CentralDirHeader::parse(*input) {
-            Ok(_header) => {
-                return (0, InternalState::End(state), ParseResult::Continue);
-            }
+        match record {
+            Ok((unparsed, skip)) => (
+                input.len() - unparsed.len(),
+                InternalState::skip_then(skip, InternalState::CentralDir(state)),
+                ParseResult::Continue,
+            ),
             Err(nom::Err::Incomplete(_)) => {
-                return (
+                (0, InternalState::CentralDir(state), ParseResult::NeedsInput)
+            }
+            Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
+                let error = e.replace_external(ZipError::InvalidCentralDirHeader);
+                (
                     0,
-                    InternalState::DescriptorParsed(state),
-                    ParseResult::NeedsInput,
+                    InternalState::Error(error.clone()),
+                    ParseResult::Error(error),
                 )
             }
-            Err(_) => {
-                return (
-                    0,
-                    InternalState::Error,
-                    ParseResult::Error(ZipError::InvalidCentralDirHeader),
+        }
+    }
+
+    fn parse_central_dir_end<'long, 'short>(
+        &self,
+        input: Input<'long, 'short>,
+        mut state: DescriptorParsed,
+    ) -> (usize, InternalState, ParseResult) {
+        let limits = &self.options.field_limits;
+        if let Some(field) = field_too_long(
+            *input,
+            headers::CENTRAL_DIR_END_TAG,
+            CENTRAL_DIR_END_FIELDS,
+            limits,
+        ) {
+            return (
+                0,
+                InternalState::Error(ZipError::FieldTooLong(field)),
+                ParseResult::Error(ZipError::FieldTooLong(field)),
+            );
+        }
+        match parse_lazily(
+            *input,
+            self.options.lazy_field_threshold,
+            CentralDirEnd::parse,
+            CentralDirEnd::parse_fixed,
+        ) {
+            Ok((unparsed, (mut end, skip))) => {
+                limits.truncate(HeaderField::Comment, &mut end.comment);
+                let bytes_parsed = input.len() - unparsed.len();
+                let archive_end = state.archive_end.get_or_insert_with(Default::default);
+                if let Some(report) = &mut archive_end.report {
+                    let (num_entries, start_offset) = match &archive_end.zip64_central_dir_end {
+                        Some(zip64_end) => (
+                            zip64_end.central_dir_num_entries_total,
+                            zip64_end.central_dir_start_offset,
+                        ),
+                        None => (
+                            u64::from(end.central_dir_num_entries_total),
+                            u64::from(end.central_dir_start_offset),
+                        ),
+                    };
+                    report.check_end(archive_end.central_dir_offset, num_entries, start_offset);
+                }
+                archive_end.central_dir_end = Some(end);
+                if self.options.concatenated_archives {
+                    (
+                        bytes_parsed,
+                        InternalState::skip_then(skip, InternalState::ArchiveEnd(state)),
+                        ParseResult::Continue,
+                    )
+                } else {
+                    // The comment is the last thing in the archive,
+                    // so there's no need to skip it.
+                    (
+                        bytes_parsed,
+                        InternalState::End(state),
+                        ParseResult::EndOfFile,
+                    )
+                }
+            }
+            Err(nom::Err::Incomplete(_)) => {
+                (0, InternalState::CentralDir(state), ParseResult::NeedsInput)
+            }
+            Err(_) => (
+                0,
+                InternalState::Error(ZipError::InvalidCentralDirEnd),
+                ParseResult::Error(ZipError::InvalidCentralDirEnd),
+            ),
+        }
+    }
+
+    /// In the concatenated archives mode, checks whether
+    /// another archive follows the one that just ended.
+    fn after_archive<'long, 'short>(
+        &self,
+        input: Input<'long, 'short>,
+        state: DescriptorParsed,
+    ) -> (usize, InternalState, ParseResult) {
+        if input.is_empty() {
+            // Only the first archive may have data before it.
+            let next_file = ZipFile {
+                state: InternalState::Init,
+                ..start_stream_with(self.options.clone())
+            };
+            return (
+                0,
+                InternalState::End(state),
+                ParseResult::NeedsInputOrEof(next_file),
+            );
+        }
+
+        if input.len() < headers::LOCAL_FILE_HEADER_TAG.len() {
+            return (0, InternalState::ArchiveEnd(state), ParseResult::NeedsInput);
+        }
+
+        if !input.starts_with(headers::LOCAL_FILE_HEADER_TAG) {
+            // Whatever trails the archive isn't another archive,
+            // so it's ignored just like in the non-concatenated mode.
+            return (0, InternalState::End(state), ParseResult::EndOfFile);
+        }
+
+        match peek_stream_with(*input, &self.options) {
+            Ok((unparsed, mut next_file)) => {
+                let bytes_parsed = input.len() - unparsed.len();
+                // The offsets of the next archive are counted from its start.
+                next_file.position = (bytes_parsed - next_file.unparsed.len()) as u64;
+                (
+                    bytes_parsed,
+                    InternalState::End(state),
+                    ParseResult::NextArchive(next_file),
                 )
             }
+            Err(e) => (0, InternalState::Error(e.clone()), ParseResult::Error(e)),
         }
     }
 
-    pub fn filename(&self) -> Option<&[u8]> {
-        match &self.state {
-            InternalState::Init => None,
-            InternalState::HeaderParsed(state) => Some(&state.header.filename),
-            InternalState::Inflated(state) => Some(&state.header.filename),
-            InternalState::DescriptorParsed(state) => Some(&state.header.filename),
-            InternalState::End(state) => Some(&state.header.filename),
-            InternalState::Sentinel => unreachable!("filename is never called with this"),
-            InternalState::Error => panic!("this shouldn't be called after an error"),
+    /// Continues the offsets and the streamed entries of `previous`, whose input
+    /// ended with the local file header of this file, `bytes_parsed` bytes long.
+    fn continue_after(&mut self, previous: &mut ZipFile, bytes_parsed: usize) {
+        // What was peeked, but not parsed, is parsed again from the unparsed input.
+        self.header_offset = previous.position;
+        self.index = previous.index + 1;
+        self.position = previous.position + (bytes_parsed - self.unparsed.len()) as u64;
+        self.streamed_entries = std::mem::take(&mut previous.streamed_entries);
+    }
+
+    /// Signals that the input has ended. Returns `ZipError::TruncatedArchive`
+    /// unless the stream has reached the end of the archive. Without this,
+    /// a truncated archive would just keep returning `State::NeedsInput`.
+    /// After `State::NeedsInputOrEof` or `State::Stopped`, the input may end
+    /// without calling this.
+    pub fn finish(&self) -> Result<(), ZipError> {
+        if self.stopped {
+            return Ok(());
         }
-        .map(|n| &**n)
+        let in_progress = match &self.state {
+            InternalState::Skip { then, .. } => then,
+            state => state,
+        };
+        let (entry, decompressed) = match in_progress {
+            InternalState::End(_) | InternalState::ArchiveEnd(_) => return Ok(()),
+            InternalState::DescriptorParsed(_) | InternalState::CentralDir(_) => (None, 0),
+            InternalState::Resyncing(state) => (Some(self.index), state.uncomp_size),
+            _ => (
+                Some(self.index),
+                self.inflater
+                    .as_ref()
+                    .map_or(0, Decompressor::uncompressed_size),
+            ),
+        };
+        Err(ZipError::TruncatedArchive {
+            entry,
+            decompressed,
+        })
+    }
+
+    /// The name of the entry, once its header has been parsed.
+    /// See `LocalFileHeader::name`.
+    pub fn filename(&self) -> Option<&[u8]> {
+        self.header().map(LocalFileHeader::name)
+    }
+
+    /// The modification time of the entry, once its header has been parsed:
+    /// the one in the extended timestamp or the NTFS extra field if the header
+    /// has either, since they are precise and in UTC, and otherwise the MS-DOS
+    /// time, which is in the unknown time zone of the archiver.
+    pub fn last_modified(&self) -> Option<SystemTime> {
+        self.header().map(LocalFileHeader::last_modified)
+    }
+
+    /// The index of the entry in its archive, counting from 0. Unlike the name,
+    /// which may be duplicated, it identifies the entry, for example to correlate it
+    /// with the central directory. With `Options::concatenated_archives`,
+    /// the count restarts with each archive.
+    pub fn entry_index(&self) -> u64 {
+        self.index
+    }
+
+    /// Whether the entry is a directory, once its header has been parsed.
+    /// See `LocalFileHeader::is_dir`.
+    pub fn is_dir(&self) -> bool {
+        self.header().is_some_and(LocalFileHeader::is_dir)
+    }
+
+    /// The name of the entry decoded as a string, once its header has been parsed.
+    /// See `LocalFileHeader::name_str`.
+    pub fn name_str(&self) -> Option<Cow<'_, str>> {
+        self.header().map(LocalFileHeader::name_str)
+    }
+
+    /// The local file header of the entry, once it has been parsed.
+    pub fn header(&self) -> Option<&LocalFileHeader> {
+        self.state.header()
+    }
+
+    /// The data descriptor after the data of the entry, with the CRC-32 and
+    /// the sizes of the entry, once the entry has ended. `None` if the entry
+    /// has no data descriptor.
+    pub fn data_descriptor(&self) -> Option<&DataDescriptor> {
+        self.state.descriptor()
+    }
+
+    /// The error that damaged the entry, if the rest of the entry was skipped
+    /// because of it. See `Options::recover_damaged_entries`.
+    pub fn damage(&self) -> Option<ZipError> {
+        self.damage.clone()
+    }
+
+    /// Skips the rest of the entry without decompressing it: the following reads
+    /// produce no output until the next file or the end of the stream is reached.
+    /// If the sizes of the entry are in its local header, its data is skipped
+    /// as is. Otherwise the data is scanned for the data descriptor that ends it.
+    pub fn skip_entry(&mut self) {
+        self.skip_data = true;
+    }
+
+    /// The central directory headers of the archive, if this is its last entry
+    /// and `Options::keep_central_dir` is enabled. The headers are available
+    /// once the stream has reached the end of the archive.
+    pub fn central_dir(&self) -> &[CentralDirHeader] {
+        self.state
+            .archive_end()
+            .map_or(&[], |archive_end| &archive_end.central_dir[..])
+    }
+
+    /// The end of central directory record of the archive, if this is its last entry
+    /// and the stream has reached the end of the archive.
+    pub fn central_dir_end(&self) -> Option<&CentralDirEnd> {
+        self.state
+            .archive_end()
+            .and_then(|archive_end| archive_end.central_dir_end.as_ref())
+    }
+
+    /// The check of the central directory against the streamed entries, if this is
+    /// the last entry of the archive, `Options::validate_central_dir` is enabled
+    /// and the stream has reached the end of the archive.
+    pub fn central_dir_report(&self) -> Option<&CentralDirReport> {
+        self.state
+            .archive_end()
+            .filter(|archive_end| archive_end.central_dir_end.is_some())
+            .and_then(|archive_end| archive_end.report.as_ref())
+    }
+
+    /// The comment of the archive, if this is its last entry
+    /// and the stream has reached the end of the archive.
+    pub fn archive_comment(&self) -> Option<&[u8]> {
+        self.central_dir_end().map(|end| &end.comment[..])
     }
 }
 
+/// The longest stub that is skipped when an autodetected stream
+/// starts like an executable, in case it's a self-extracting archive.
+pub const MAX_AUTODETECTED_STUB_LEN: u64 = 16 * 1024 * 1024;
+
+/// Whether `input` starts with the magic number of a Windows (MZ)
+/// or an ELF executable, which self-extracting archives start with.
+pub fn is_executable_header(input: &[u8]) -> bool {
+    input.starts_with(b"MZ") || input.starts_with(b"\x7fELF")
+}
+
 pub fn start_stream() -> ZipFile {
+    start_stream_with(Options::default())
+}
+
+/// Starts a zip stream that is parsed according to `options`.
+pub fn start_stream_with(options: Options) -> ZipFile {
+    let state = match options.max_skip {
+        Some(_) => InternalState::LeadingData { skipped: 0 },
+        None => InternalState::Init,
+    };
     ZipFile {
-        state: InternalState::Init,
+        state,
         unparsed: Vec::new(),
-        inflater: deflate::Stream::new(),
+        inflater: None,
+        options,
+        skip_data: false,
+        crc_32: 0,
+        data_left: None,
+        damage: None,
+        index: 0,
+        position: 0,
+        header_offset: 0,
+        streamed_entries: Vec::new(),
+        hook_progress: HookProgress::default(),
+        progress: Summary::default(),
+        stopped: false,
     }
 }
 
+/// Starts a stream whose first bytes, `prefix`, were already read from the input
+/// to detect the format. They are parsed together with the input of the next `read`.
+pub(crate) fn start_stream_with_options_and_prefix(options: Options, prefix: &[u8]) -> ZipFile {
+    let mut file = start_stream_with(options);
+    file.unparsed = prefix.to_vec();
+    file
+}
+
 pub fn peek_stream(input: &[u8]) -> Result<(&[u8], ZipFile), ZipError> {
-    match LocalFileHeader::parse(input) {
-        Ok((unparsed, header)) => Ok((
-            unparsed,
-            ZipFile {
-                state: InternalState::HeaderParsed(HeaderParsed { header }),
-                unparsed: Vec::new(),
-                inflater: deflate::Stream::new(),
-            },
-        )),
+    peek_stream_with(input, &Options::default())
+}
+
+fn peek_stream_with<'i>(
+    input: &'i [u8],
+    options: &Options,
+) -> Result<(&'i [u8], ZipFile), ZipError> {
+    match parse_local_header(input, options) {
+        Ok((unparsed, (header, skip))) => {
+            // Skip what we can right away, the rest is skipped by the next file.
+            let skipped_now = std::cmp::min(skip, unparsed.len() as u64);
+            Ok((
+                &unparsed[skipped_now as usize..],
+                ZipFile {
+                    state: InternalState::skip_then(
+                        skip - skipped_now,
+                        InternalState::HeaderParsed(HeaderParsed { header }),
+                    ),
+                    ..start_stream_with(options.clone())
+                },
+            ))
+        }
         Err(nom::Err::Incomplete(_need)) => Ok((
             &[],
             ZipFile {
                 state: InternalState::Init,
                 unparsed: input.to_vec(),
-                inflater: deflate::Stream::new(),
+                ..start_stream_with(options.clone())
             },
         )),
-        Err(nom::Err::Error(ZipError::NotLocalFileHeader)) => Err(ZipError::NotLocalFileHeader),
         Err(nom::Err::Error(_e)) => Err(_e),
         Err(nom::Err::Failure(_e)) => Err(_e),
     }
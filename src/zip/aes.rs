@@ -0,0 +1,180 @@
+//! Decryption of WinZip AES encryption (AE-1 and AE-2).
+//!
+//! The encrypted data is preceded by a salt and a password verifier,
+//! and followed by an authentication code. The keys are derived from
+//! the password and the salt with PBKDF2-HMAC-SHA1, and the data is
+//! encrypted with AES in counter mode, with a little-endian counter
+//! that starts from 1. The authentication code is the start of
+//! the HMAC-SHA1 of the encrypted data.
+
+use aes_cipher::cipher::generic_array::GenericArray;
+use aes_cipher::cipher::{BlockEncrypt, KeyInit};
+use aes_cipher::{Aes128, Aes192, Aes256};
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+
+use super::headers::AesStrength;
+use super::ZipError;
+
+/// The length of the password verifier that follows the salt.
+pub const VERIFIER_LEN: usize = 2;
+/// The length of the authentication code that follows the encrypted data.
+pub const AUTH_CODE_LEN: usize = 10;
+
+const KEY_DERIVATION_ITERATIONS: u32 = 1000;
+const BLOCK_SIZE: usize = 16;
+
+enum BlockCipher {
+    Aes128(Aes128),
+    Aes192(Aes192),
+    Aes256(Aes256),
+}
+
+impl BlockCipher {
+    /// `key` must be as long as the key length of `strength`.
+    fn new(strength: AesStrength, key: &[u8]) -> BlockCipher {
+        match strength {
+            AesStrength::Aes128 => BlockCipher::Aes128(Aes128::new(GenericArray::from_slice(key))),
+            AesStrength::Aes192 => BlockCipher::Aes192(Aes192::new(GenericArray::from_slice(key))),
+            AesStrength::Aes256 => BlockCipher::Aes256(Aes256::new(GenericArray::from_slice(key))),
+        }
+    }
+
+    /// The key stream block for the block at `index` of the data.
+    fn key_stream(&self, index: u64) -> [u8; BLOCK_SIZE] {
+        let counter = u128::from(index) + 1;
+        let mut block = GenericArray::from(counter.to_le_bytes());
+        match self {
+            BlockCipher::Aes128(cipher) => cipher.encrypt_block(&mut block),
+            BlockCipher::Aes192(cipher) => cipher.encrypt_block(&mut block),
+            BlockCipher::Aes256(cipher) => cipher.encrypt_block(&mut block),
+        }
+        block.into()
+    }
+}
+
+struct Keys {
+    cipher: BlockCipher,
+    mac: Hmac<Sha1>,
+}
+
+/// Checks the password against the verifier, decrypts the data
+/// and checks the authentication code after it.
+pub struct Decryptor {
+    password: Vec<u8>,
+    strength: AesStrength,
+    header: Vec<u8>,
+    keys: Option<Keys>,
+    /// The amount of data decrypted so far.
+    position: u64,
+    trailer: Vec<u8>,
+}
+
+impl Decryptor {
+    pub fn new(password: &[u8], strength: AesStrength) -> Decryptor {
+        Decryptor {
+            password: password.to_vec(),
+            strength,
+            header: Vec::new(),
+            keys: None,
+            position: 0,
+            trailer: Vec::new(),
+        }
+    }
+
+    /// The length of the salt and the password verifier.
+    fn header_len(&self) -> usize {
+        self.strength.salt_len() + VERIFIER_LEN
+    }
+
+    /// The amount of the compressed size that isn't encrypted data.
+    pub fn overhead(&self) -> usize {
+        self.header_len() + AUTH_CODE_LEN
+    }
+
+    pub fn header_done(&self) -> bool {
+        self.keys.is_some()
+    }
+
+    /// Reads as much of the salt and the password verifier from the start of `input`
+    /// as there is, and derives the keys once they are complete. Returns the number
+    /// of bytes consumed, or an error if the password doesn't match the verifier.
+    pub fn read_header(&mut self, input: &[u8]) -> Result<usize, ZipError> {
+        if self.header_done() {
+            return Ok(0);
+        }
+        let len = std::cmp::min(self.header_len() - self.header.len(), input.len());
+        self.header.extend_from_slice(&input[..len]);
+        if self.header.len() < self.header_len() {
+            return Ok(len);
+        }
+
+        let key_len = self.strength.key_len();
+        let (salt, verifier) = self.header.split_at(self.strength.salt_len());
+        let mut derived = vec![0; 2 * key_len + VERIFIER_LEN];
+        pbkdf2::pbkdf2_hmac::<Sha1>(
+            &self.password,
+            salt,
+            KEY_DERIVATION_ITERATIONS,
+            &mut derived,
+        );
+        if &derived[2 * key_len..] != verifier {
+            return Err(ZipError::InvalidPassword);
+        }
+        self.keys = Some(Keys {
+            cipher: BlockCipher::new(self.strength, &derived[..key_len]),
+            mac: <Hmac<Sha1> as Mac>::new_from_slice(&derived[key_len..2 * key_len])
+                .expect("HMAC accepts keys of any length"),
+        });
+        self.password.clear();
+        Ok(len)
+    }
+
+    /// Decrypts `input` to `output` without advancing the position,
+    /// so that the ciphertext that isn't used can be decrypted again later.
+    pub fn peek_decrypt(&self, input: &[u8], output: &mut Vec<u8>) {
+        let keys = self.keys.as_ref().expect("The header is read first");
+        let mut position = self.position;
+        let mut input = input;
+        while !input.is_empty() {
+            let offset = (position % BLOCK_SIZE as u64) as usize;
+            let key_stream = keys.cipher.key_stream(position / BLOCK_SIZE as u64);
+            let len = std::cmp::min(BLOCK_SIZE - offset, input.len());
+            let (block, rest) = input.split_at(len);
+            output.extend(
+                block
+                    .iter()
+                    .zip(&key_stream[offset..])
+                    .map(|(byte, key)| byte ^ key),
+            );
+            position += len as u64;
+            input = rest;
+        }
+    }
+
+    /// Advances the position over the ciphertext that was used.
+    pub fn advance(&mut self, cipher: &[u8]) {
+        let keys = self.keys.as_mut().expect("The header is read first");
+        keys.mac.update(cipher);
+        self.position += cipher.len() as u64;
+    }
+
+    pub fn trailer_done(&self) -> bool {
+        self.trailer.len() == AUTH_CODE_LEN
+    }
+
+    /// Reads as much of the authentication code from the start of `input` as there is.
+    /// Returns the number of bytes consumed, or an error if the code doesn't match the data.
+    pub fn read_trailer(&mut self, input: &[u8]) -> Result<usize, ZipError> {
+        let len = std::cmp::min(AUTH_CODE_LEN - self.trailer.len(), input.len());
+        self.trailer.extend_from_slice(&input[..len]);
+        if self.trailer_done() {
+            let keys = self.keys.as_ref().expect("The header is read first");
+            let code = keys.mac.clone().finalize().into_bytes();
+            if code[..AUTH_CODE_LEN] != self.trailer[..] {
+                return Err(ZipError::InvalidAuthenticationCode);
+            }
+        }
+        Ok(len)
+    }
+}
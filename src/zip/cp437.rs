@@ -0,0 +1,31 @@
+//! Decoding of code page 437, the original IBM PC character set,
+//! which zip archives use for names that aren't flagged as UTF-8.
+
+use std::borrow::Cow;
+
+/// The characters of the bytes 0x80 to 0xff. The lower half is the same as in ASCII.
+const HIGH_HALF: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å', 'É', 'æ', 'Æ',
+    'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ', 'á', 'í', 'ó', 'ú', 'ñ', 'Ñ',
+    'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»', '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕',
+    '╣', '║', '╗', '╝', '╜', '╛', '┐', '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦',
+    '╠', '═', '╬', '╧', '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐',
+    '▀', 'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩', '≡', '±',
+    '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{a0}',
+];
+
+/// Decodes `bytes` from code page 437. ASCII input is borrowed as is.
+pub fn decode(bytes: &[u8]) -> Cow<'_, str> {
+    if bytes.is_ascii() {
+        return Cow::Borrowed(std::str::from_utf8(bytes).expect("ASCII is UTF-8"));
+    }
+    Cow::Owned(
+        bytes
+            .iter()
+            .map(|&byte| match byte {
+                0x00..=0x7f => char::from(byte),
+                _ => HIGH_HALF[usize::from(byte - 0x80)],
+            })
+            .collect(),
+    )
+}
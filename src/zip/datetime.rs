@@ -45,7 +45,7 @@ fn days_since_msdos_epoch(msdos_year: u16, month: u16, day: u16) -> Result<u16,
     let year = 1980 + msdos_year;
 
     // As a base rule, the leap day happens on 29th of February, every 4th year.
-    let is_leap_day = (year % 4) == 0 && month == 1 && day == 28;
+    let is_leap_day = year.is_multiple_of(4) && month == 1 && day == 28;
 
     // Every 100th year is exceptionally a non-leap year,
     // but year 2000, even more exceptionally, IS a leap year.
@@ -71,7 +71,7 @@ fn days_since_msdos_epoch(msdos_year: u16, month: u16, day: u16) -> Result<u16,
 
     // Check if the leap has already happened
     // during the current 4 year cycle.
-    let is_after_leap = (year % 4) > 0 || month > 1;
+    let is_after_leap = !year.is_multiple_of(4) || month > 1;
     let after_leap_correction = if is_after_leap { 1 } else { 0 };
 
     // Check if the year 2100 skip has already happened
@@ -274,6 +274,71 @@ fn test_parse_msdos_time_bits() {
     test(0b11111111_11111111_u16, 31, 63, 62); // Max representable invalid time
 }
 
+/// Returns the MS-DOS time field of `time`, the inverse of the time part of `parse_msdos_datetime`.
+pub fn msdos_time(time: SystemTime) -> u16 {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map(|since_epoch| since_epoch.as_secs())
+        .unwrap_or(0)
+        % DAY.as_secs();
+    let (hours, minutes, seconds) = (seconds / 3600, seconds % 3600 / 60, seconds % 60);
+    ((hours << 11) | (minutes << 5) | (seconds / 2)) as u16
+}
+
+#[test]
+fn test_msdos_time() {
+    fn test(input: &[u8; 4]) {
+        let (_, time) = parse_msdos_datetime(input).unwrap();
+        assert_eq!(msdos_time(time), u16::from_le_bytes([input[0], input[1]]));
+    }
+    test(b"\x00\x00\x21\x00");
+    test(b"\x7d\xbf\x21\x00"); // 23:59:58
+    test(b"\x21\x08\x4f\x51"); // 01:01:02 on 2020-10-15
+}
+
+/// Returns the MS-DOS date field of `time`, the inverse of the date part of
+/// `parse_msdos_datetime`. Dates outside of the years 1980 - 2107 are clamped
+/// to the first or the last day that the field can represent.
+pub fn msdos_date(time: SystemTime) -> u16 {
+    fn date_bits(msdos_year: u16, month: u16, day: u16) -> u16 {
+        (msdos_year << 9) | (month << 5) | day
+    }
+    let first_of = |msdos_year, month| days_since_msdos_epoch(msdos_year, month, 1).unwrap_or(0);
+
+    let days = match time.duration_since(UNIX_EPOCH + EPOCH_DIFF) {
+        Ok(since_epoch) => since_epoch.as_secs() / DAY.as_secs(),
+        Err(_) => return date_bits(0, 1, 1),
+    };
+    if days > u64::from(first_of(127, 12) + 30) {
+        return date_bits(127, 12, 31);
+    }
+    let days = days as u16;
+    let msdos_year = (0..128)
+        .rev()
+        .find(|&y| first_of(y, 1) <= days)
+        .unwrap_or(0);
+    let month = (1..=12)
+        .rev()
+        .find(|&m| first_of(msdos_year, m) <= days)
+        .unwrap_or(1);
+    date_bits(msdos_year, month, days - first_of(msdos_year, month) + 1)
+}
+
+#[test]
+fn test_msdos_date() {
+    fn test(input: &[u8; 4]) {
+        let (_, time) = parse_msdos_datetime(input).unwrap();
+        assert_eq!(msdos_date(time), u16::from_le_bytes([input[2], input[3]]));
+    }
+    test(b"\x00\x00\x21\x00"); // 1980-01-01
+    test(b"\x21\x08\x4f\x51"); // 2020-10-15
+    test(b"\x00\x00\x5d\x28"); // 2000-02-29
+    test(b"\x00\x00\x9f\xff"); // 2107-12-31
+
+    assert_eq!(msdos_date(UNIX_EPOCH), 0x21);
+    assert_eq!(msdos_date(UNIX_EPOCH + DAY * 60_000), 0xff9f);
+}
+
 pub fn parse_msdos_datetime(i: &[u8]) -> IResult<&[u8], SystemTime, ZipError> {
     let (i, (msdos_time, msdos_date)) =
         pair(le_u16, le_u16)(i).map_nom_err(|()| ZipError::InvalidDateOrTime)?;
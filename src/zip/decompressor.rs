@@ -0,0 +1,573 @@
+//! Dispatches the compressed data of a zip entry
+//! to a decompressor according to its compression method.
+
+#[cfg(feature = "bzip2")]
+use crate::bzip2;
+use crate::deflate;
+#[cfg(feature = "deflate64")]
+use crate::deflate64;
+use crate::legacy;
+#[cfg(feature = "ppmd")]
+use crate::ppmd;
+use crate::utils::update_crc32;
+use crate::zstd;
+
+#[cfg(feature = "aes")]
+use super::aes;
+use super::headers::{
+    CompressionMethod, DataDescriptor, DeflateMode, LocalFileHeader, DATA_DESCRIPTOR_TAG,
+};
+use super::zipcrypto;
+use super::{Options, ZipError};
+
+/// How much encrypted data is decrypted ahead for the decompressor at a time.
+const DECRYPTED_CHUNK_SIZE: usize = 64 * 1024;
+/// How much stored data is copied to the output at a time.
+const STORED_CHUNK_SIZE: usize = 64 * 1024;
+
+pub enum Decompressor {
+    Stored(Stored),
+    ScanningStored(ScanningStored),
+    Deflate(deflate::Stream),
+    #[cfg(feature = "deflate64")]
+    Deflate64(deflate64::Stream),
+    #[cfg(feature = "bzip2")]
+    Bzip2(bzip2::Stream),
+    Zstd(zstd::Stream),
+    Legacy(legacy::Stream),
+    #[cfg(feature = "ppmd")]
+    Ppmd(ppmd::Stream),
+    Decrypting(Box<Decrypting>),
+}
+
+impl Decompressor {
+    /// Starts a decompressor for the entry described by `header`.
+    pub fn for_header(
+        header: &LocalFileHeader,
+        options: &Options,
+    ) -> Result<Decompressor, ZipError> {
+        Decompressor::for_header_with_sizes(header, header.sizes(), options)
+    }
+
+    /// Starts a decompressor for the entry described by `header`, whose compressed
+    /// and uncompressed sizes are `sizes` if known, for example from the central directory.
+    pub fn for_header_with_sizes(
+        header: &LocalFileHeader,
+        sizes: Option<(u64, u64)>,
+        options: &Options,
+    ) -> Result<Decompressor, ZipError> {
+        match (header.encrypted, &options.password) {
+            (true, Some(password)) => Decompressor::decrypting(header, sizes, password, options),
+            (true, None) => Err(ZipError::EncryptedEntry),
+            _ => Decompressor::for_method(header.compression_method, header, sizes, options),
+        }
+    }
+
+    /// Starts a decompressor that decrypts the data of an encrypted entry first.
+    fn decrypting(
+        header: &LocalFileHeader,
+        sizes: Option<(u64, u64)>,
+        password: &[u8],
+        options: &Options,
+    ) -> Result<Decompressor, ZipError> {
+        let (cipher, method) = match header.compression_method {
+            #[cfg(feature = "aes")]
+            CompressionMethod::AesEncrypted => {
+                let field = header
+                    .aes()
+                    .ok_or(ZipError::InvalidLocalFileHeaderExtraFields)?;
+                (
+                    Cipher::Aes(Box::new(aes::Decryptor::new(password, field.strength))),
+                    field.compression_method,
+                )
+            }
+            method => (
+                Cipher::ZipCrypto(zipcrypto::Decryptor::new(password, header)),
+                method,
+            ),
+        };
+        // The compressed size includes what the encryption adds to the data.
+        let sizes = sizes.map(|(compressed_size, uncompressed_size)| {
+            (
+                compressed_size.saturating_sub(cipher.overhead() as u64),
+                uncompressed_size,
+            )
+        });
+        let inner = Decompressor::for_method(method, header, sizes, options)?;
+        Ok(Decompressor::Decrypting(Box::new(Decrypting {
+            cipher,
+            plaintext: Vec::new(),
+            inner,
+            inner_done: false,
+        })))
+    }
+
+    /// Starts a decompressor for `method`, the compression method of the entry.
+    /// `sizes` are the compressed and the uncompressed size of the data, if known.
+    fn for_method(
+        method: CompressionMethod,
+        header: &LocalFileHeader,
+        sizes: Option<(u64, u64)>,
+        options: &Options,
+    ) -> Result<Decompressor, ZipError> {
+        match (method, sizes) {
+            // Stored data doesn't mark its end either.
+            (CompressionMethod::Stored, Some((compressed_size, _))) => {
+                Ok(Decompressor::Stored(Stored::new(compressed_size)))
+            }
+            // Without the sizes, the data ends where a data descriptor that matches
+            // it starts. Encrypted data can't be matched with the descriptor.
+            (CompressionMethod::Stored, None) if !header.encrypted => Ok(
+                Decompressor::ScanningStored(ScanningStored::new(header.is_zip64)),
+            ),
+            (CompressionMethod::Deflated, _) => {
+                Ok(Decompressor::Deflate(options.new_deflate_stream()))
+            }
+            // Deflate64 may read past the end of the compressed data,
+            // so it needs to know where the data ends.
+            #[cfg(feature = "deflate64")]
+            (CompressionMethod::EnhancedDeflated, Some((compressed_size, _))) => Ok(
+                Decompressor::Deflate64(deflate64::Stream::with_compressed_size(compressed_size)),
+            ),
+            #[cfg(feature = "bzip2")]
+            (CompressionMethod::Bzip2, _) => Ok(Decompressor::Bzip2(bzip2::Stream::new())),
+            (CompressionMethod::Zstd, _) => match options.new_zstd_backend() {
+                Some(backend) => Ok(Decompressor::Zstd(zstd::Stream::new(backend))),
+                None => Err(ZipError::UnsupportedCompressionMethod(
+                    CompressionMethod::Zstd,
+                )),
+            },
+            // PPMd buffers the whole compressed data and decodes
+            // exactly as many bytes as the header says.
+            #[cfg(feature = "ppmd")]
+            (CompressionMethod::PpmdVer1Rev1, Some((compressed_size, uncompressed_size))) => Ok(
+                Decompressor::Ppmd(ppmd::Stream::new(compressed_size, uncompressed_size)),
+            ),
+            // The legacy methods don't mark the end of the data.
+            (method, Some((compressed_size, uncompressed_size))) => {
+                match legacy_method(method, header) {
+                    Some(legacy_method) => Ok(Decompressor::Legacy(legacy::Stream::new(
+                        legacy_method,
+                        compressed_size,
+                        uncompressed_size,
+                    ))),
+                    None => Err(ZipError::UnsupportedCompressionMethod(method)),
+                }
+            }
+            (method, None) => Err(ZipError::UnsupportedCompressionMethod(method)),
+        }
+    }
+
+    pub fn feed_input<'i, 'o>(
+        &'o mut self,
+        input: &'i [u8],
+    ) -> Result<deflate::State<'i, 'o>, ZipError> {
+        match self {
+            Decompressor::Stored(stream) => Ok(stream.feed_input(input)),
+            Decompressor::ScanningStored(stream) => Ok(stream.feed_input(input)),
+            Decompressor::Deflate(stream) => stream
+                .feed_input(input)
+                .map_err(|_| ZipError::InvalidDeflateStream),
+            #[cfg(feature = "deflate64")]
+            Decompressor::Deflate64(stream) => stream
+                .feed_input(input)
+                .map_err(|_| ZipError::InvalidDeflateStream),
+            #[cfg(feature = "bzip2")]
+            Decompressor::Bzip2(stream) => stream
+                .feed_input(input)
+                .map_err(|_| ZipError::InvalidBzip2Stream),
+            Decompressor::Zstd(stream) => stream
+                .feed_input(input)
+                .map_err(|_| ZipError::InvalidZstdStream),
+            Decompressor::Legacy(stream) => stream
+                .feed_input(input)
+                .map_err(|_| ZipError::InvalidLegacyStream),
+            #[cfg(feature = "ppmd")]
+            Decompressor::Ppmd(stream) => stream
+                .feed_input(input)
+                .map_err(|_| ZipError::InvalidPpmdStream),
+            Decompressor::Decrypting(stream) => stream.feed_input(input),
+        }
+    }
+
+    pub fn get_output(&self) -> &[u8] {
+        match self {
+            Decompressor::Stored(stream) => stream.get_output(),
+            Decompressor::ScanningStored(stream) => &stream.output,
+            Decompressor::Deflate(stream) => stream.get_output(),
+            #[cfg(feature = "deflate64")]
+            Decompressor::Deflate64(stream) => stream.get_output(),
+            #[cfg(feature = "bzip2")]
+            Decompressor::Bzip2(stream) => stream.get_output(),
+            Decompressor::Zstd(stream) => stream.get_output(),
+            Decompressor::Legacy(stream) => stream.get_output(),
+            #[cfg(feature = "ppmd")]
+            Decompressor::Ppmd(stream) => stream.get_output(),
+            Decompressor::Decrypting(stream) => stream.inner.get_output(),
+        }
+    }
+
+    pub fn compressed_size(&self) -> u64 {
+        match self {
+            Decompressor::Stored(stream) => stream.size(),
+            Decompressor::ScanningStored(stream) => stream.size,
+            Decompressor::Deflate(stream) => stream.compressed_size(),
+            #[cfg(feature = "deflate64")]
+            Decompressor::Deflate64(stream) => stream.compressed_size(),
+            #[cfg(feature = "bzip2")]
+            Decompressor::Bzip2(stream) => stream.compressed_size(),
+            Decompressor::Zstd(stream) => stream.compressed_size(),
+            Decompressor::Legacy(stream) => stream.compressed_size(),
+            #[cfg(feature = "ppmd")]
+            Decompressor::Ppmd(stream) => stream.compressed_size(),
+            Decompressor::Decrypting(stream) => {
+                stream.cipher.overhead() as u64 + stream.inner.compressed_size()
+            }
+        }
+    }
+
+    pub fn uncompressed_size(&self) -> u64 {
+        match self {
+            Decompressor::Stored(stream) => stream.size(),
+            Decompressor::ScanningStored(stream) => stream.size,
+            Decompressor::Deflate(stream) => stream.uncompressed_size(),
+            #[cfg(feature = "deflate64")]
+            Decompressor::Deflate64(stream) => stream.uncompressed_size(),
+            #[cfg(feature = "bzip2")]
+            Decompressor::Bzip2(stream) => stream.uncompressed_size(),
+            Decompressor::Zstd(stream) => stream.uncompressed_size(),
+            Decompressor::Legacy(stream) => stream.uncompressed_size(),
+            #[cfg(feature = "ppmd")]
+            Decompressor::Ppmd(stream) => stream.uncompressed_size(),
+            Decompressor::Decrypting(stream) => stream.inner.uncompressed_size(),
+        }
+    }
+
+    /// The data descriptor after the data, if it was consumed with the data.
+    /// Only Stored data without sizes does that, since its end is found by
+    /// scanning for the descriptor.
+    pub fn descriptor(&self) -> Option<&DataDescriptor> {
+        match self {
+            Decompressor::ScanningStored(stream) => stream.descriptor.as_ref(),
+            _ => None,
+        }
+    }
+}
+
+/// Copies the data of an entry that is stored without compression.
+pub struct Stored {
+    remaining: u64,
+    output: Vec<u8>,
+    size: u64,
+}
+
+impl Stored {
+    fn new(size: u64) -> Stored {
+        Stored {
+            remaining: size,
+            output: Vec::new(),
+            size: 0,
+        }
+    }
+
+    fn feed_input<'i, 'o>(&'o mut self, input: &'i [u8]) -> deflate::State<'i, 'o> {
+        let len = std::cmp::min(self.remaining, input.len().min(STORED_CHUNK_SIZE) as u64) as usize;
+        let (data, unparsed_input) = input.split_at(len);
+        self.output.clear();
+        self.output.extend_from_slice(data);
+        self.remaining -= len as u64;
+        self.size += len as u64;
+        if len > 0 {
+            deflate::State::HasOutput {
+                unparsed_input,
+                output: &self.output,
+            }
+        } else if self.remaining == 0 {
+            deflate::State::Stop { unparsed_input }
+        } else {
+            deflate::State::NeedsInput { unparsed_input }
+        }
+    }
+
+    fn get_output(&self) -> &[u8] {
+        &self.output
+    }
+
+    /// The amount of data copied so far, which is both
+    /// the compressed and the uncompressed size.
+    fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+/// Copies the data of an entry that is stored without compression, and whose
+/// sizes are only in the data descriptor after it. The data ends at the first
+/// data descriptor with a signature whose sizes and CRC-32 match the data before it.
+/// Since a descriptor may continue in the next input, the end of the input
+/// is held back until it's known not to start one.
+pub struct ScanningStored {
+    is_zip64: bool,
+    /// The end of the previous input, which may be the start of a descriptor.
+    pending: Vec<u8>,
+    output: Vec<u8>,
+    size: u64,
+    crc_32: u32,
+    descriptor: Option<DataDescriptor>,
+}
+
+impl ScanningStored {
+    fn new(is_zip64: bool) -> ScanningStored {
+        ScanningStored {
+            is_zip64,
+            pending: Vec::new(),
+            output: Vec::new(),
+            size: 0,
+            crc_32: 0,
+            descriptor: None,
+        }
+    }
+
+    fn descriptor_len(&self) -> usize {
+        if self.is_zip64 {
+            24
+        } else {
+            16
+        }
+    }
+
+    fn feed_input<'i, 'o>(&'o mut self, input: &'i [u8]) -> deflate::State<'i, 'o> {
+        if self.descriptor.is_some() {
+            return deflate::State::Stop {
+                unparsed_input: input,
+            };
+        }
+        let chunk = &input[..std::cmp::min(input.len(), STORED_CHUNK_SIZE)];
+        let mut data = std::mem::take(&mut self.pending);
+        data.extend_from_slice(chunk);
+        let pending_len = data.len() - chunk.len();
+
+        let (end, descriptor) = self.find_descriptor(&data);
+        self.output.clear();
+        self.output.extend_from_slice(&data[..end]);
+        self.size += end as u64;
+        self.crc_32 = update_crc32(self.crc_32, &data[..end]);
+        let found = descriptor.is_some();
+        let unparsed_input = if found {
+            self.descriptor = descriptor;
+            &input[end + self.descriptor_len() - pending_len..]
+        } else {
+            self.pending = data.split_off(end);
+            &input[chunk.len()..]
+        };
+        if end > 0 {
+            deflate::State::HasOutput {
+                unparsed_input,
+                output: &self.output,
+            }
+        } else if found {
+            deflate::State::Stop { unparsed_input }
+        } else {
+            deflate::State::NeedsInput { unparsed_input }
+        }
+    }
+
+    /// Finds the first descriptor in `data`, which continues the data so far,
+    /// that matches the data before it. Returns the length of the data before
+    /// the descriptor and the descriptor, if it was found. If it wasn't, the data
+    /// that may be the start of a descriptor isn't included in the length.
+    fn find_descriptor(&self, data: &[u8]) -> (usize, Option<DataDescriptor>) {
+        let size_mask = if self.is_zip64 {
+            u64::MAX
+        } else {
+            u64::from(u32::MAX)
+        };
+        let searched_len = (data.len() + 1).saturating_sub(self.descriptor_len());
+        let mut crc_32 = self.crc_32;
+        let mut crc_len = 0;
+        for end in 0..searched_len {
+            if !data[end..].starts_with(DATA_DESCRIPTOR_TAG) {
+                continue;
+            }
+            let parsed = if self.is_zip64 {
+                DataDescriptor::parse_zip64(&data[end..])
+            } else {
+                DataDescriptor::parse_zip(&data[end..])
+            };
+            let desc = match parsed {
+                Ok((_, desc)) => desc,
+                Err(_) => continue,
+            };
+            let size = (self.size + end as u64) & size_mask;
+            if desc.compressed_size != size || desc.uncompressed_size != size {
+                continue;
+            }
+            crc_32 = update_crc32(crc_32, &data[crc_len..end]);
+            crc_len = end;
+            if desc.crc_32 == crc_32 {
+                return (end, Some(desc));
+            }
+        }
+        (searched_len, None)
+    }
+}
+
+/// The encryption of an entry.
+enum Cipher {
+    ZipCrypto(zipcrypto::Decryptor),
+    #[cfg(feature = "aes")]
+    Aes(Box<aes::Decryptor>),
+}
+
+impl Cipher {
+    /// The amount of the compressed size that isn't encrypted data.
+    fn overhead(&self) -> usize {
+        match self {
+            Cipher::ZipCrypto(_) => zipcrypto::HEADER_SIZE,
+            #[cfg(feature = "aes")]
+            Cipher::Aes(decryptor) => decryptor.overhead(),
+        }
+    }
+
+    fn header_done(&self) -> bool {
+        match self {
+            Cipher::ZipCrypto(decryptor) => decryptor.header_done(),
+            #[cfg(feature = "aes")]
+            Cipher::Aes(decryptor) => decryptor.header_done(),
+        }
+    }
+
+    fn read_header(&mut self, input: &[u8]) -> Result<usize, ZipError> {
+        match self {
+            Cipher::ZipCrypto(decryptor) => decryptor.read_header(input),
+            #[cfg(feature = "aes")]
+            Cipher::Aes(decryptor) => decryptor.read_header(input),
+        }
+    }
+
+    fn peek_decrypt(&self, input: &[u8], output: &mut Vec<u8>) {
+        match self {
+            Cipher::ZipCrypto(decryptor) => decryptor.peek_decrypt(input, output),
+            #[cfg(feature = "aes")]
+            Cipher::Aes(decryptor) => decryptor.peek_decrypt(input, output),
+        }
+    }
+
+    /// Advances over the ciphertext that was used and its plaintext.
+    #[cfg_attr(not(feature = "aes"), allow(unused_variables))]
+    fn advance(&mut self, cipher: &[u8], plain: &[u8]) {
+        match self {
+            Cipher::ZipCrypto(decryptor) => decryptor.advance(plain),
+            #[cfg(feature = "aes")]
+            Cipher::Aes(decryptor) => decryptor.advance(cipher),
+        }
+    }
+
+    fn trailer_done(&self) -> bool {
+        match self {
+            Cipher::ZipCrypto(_) => true,
+            #[cfg(feature = "aes")]
+            Cipher::Aes(decryptor) => decryptor.trailer_done(),
+        }
+    }
+
+    /// Reads what follows the encrypted data.
+    #[cfg_attr(not(feature = "aes"), allow(unused_variables))]
+    fn read_trailer(&mut self, input: &[u8]) -> Result<usize, ZipError> {
+        match self {
+            Cipher::ZipCrypto(_) => Ok(0),
+            #[cfg(feature = "aes")]
+            Cipher::Aes(decryptor) => decryptor.read_trailer(input),
+        }
+    }
+}
+
+/// Decrypts the data of an encrypted entry for the decompressor of its compression method.
+pub struct Decrypting {
+    cipher: Cipher,
+    plaintext: Vec<u8>,
+    inner: Decompressor,
+    inner_done: bool,
+}
+
+impl Decrypting {
+    fn feed_input<'i, 'o>(
+        &'o mut self,
+        input: &'i [u8],
+    ) -> Result<deflate::State<'i, 'o>, ZipError> {
+        let header_len = self.cipher.read_header(input)?;
+        let mut input = &input[header_len..];
+        if !self.cipher.header_done() {
+            return Ok(deflate::State::NeedsInput {
+                unparsed_input: input,
+            });
+        }
+
+        if !self.inner_done {
+            // The data may end before the input does, and what follows it
+            // mustn't be decrypted, so the cipher is advanced only over
+            // the plaintext that the decompressor consumes.
+            let chunk = &input[..std::cmp::min(input.len(), DECRYPTED_CHUNK_SIZE)];
+            self.plaintext.clear();
+            self.cipher.peek_decrypt(chunk, &mut self.plaintext);
+            let state = self.inner.feed_input(&self.plaintext)?;
+            let unparsed_len = match &state {
+                deflate::State::HasOutput { unparsed_input, .. }
+                | deflate::State::NeedsInput { unparsed_input }
+                | deflate::State::Stop { unparsed_input } => unparsed_input.len(),
+            };
+            let consumed = self.plaintext.len() - unparsed_len;
+            self.cipher
+                .advance(&chunk[..consumed], &self.plaintext[..consumed]);
+            let unparsed_input = &input[consumed..];
+            match state {
+                deflate::State::HasOutput { output, .. } => {
+                    return Ok(deflate::State::HasOutput {
+                        unparsed_input,
+                        output,
+                    })
+                }
+                deflate::State::NeedsInput { .. } => {
+                    return Ok(deflate::State::NeedsInput { unparsed_input })
+                }
+                deflate::State::Stop { .. } => {
+                    self.inner_done = true;
+                    input = unparsed_input;
+                }
+            }
+        }
+
+        let trailer_len = self.cipher.read_trailer(input)?;
+        let unparsed_input = &input[trailer_len..];
+        if self.cipher.trailer_done() {
+            Ok(deflate::State::Stop { unparsed_input })
+        } else {
+            Ok(deflate::State::NeedsInput { unparsed_input })
+        }
+    }
+}
+
+/// Picks the legacy method and its parameters for `method`, if it is one.
+fn legacy_method(method: CompressionMethod, header: &LocalFileHeader) -> Option<legacy::Method> {
+    match method {
+        CompressionMethod::Shrunk => Some(legacy::Method::Shrink),
+        CompressionMethod::ReducedX1 => Some(legacy::Method::Reduce { factor: 1 }),
+        CompressionMethod::ReducedX2 => Some(legacy::Method::Reduce { factor: 2 }),
+        CompressionMethod::ReducedX3 => Some(legacy::Method::Reduce { factor: 3 }),
+        CompressionMethod::ReducedX4 => Some(legacy::Method::Reduce { factor: 4 }),
+        // For Implode, the flag bits that Deflate uses for its mode
+        // select the dictionary size (bit 1) and the number of trees (bit 2).
+        CompressionMethod::Imploded => {
+            let (large_window, literal_tree) = match header.deflate_mode {
+                DeflateMode::Normal => (false, false),
+                DeflateMode::Max => (true, false),
+                DeflateMode::Fast => (false, true),
+                DeflateMode::SuperFast => (true, true),
+            };
+            Some(legacy::Method::Implode {
+                large_window,
+                literal_tree,
+            })
+        }
+        _ => None,
+    }
+}
@@ -1,4 +1,6 @@
-use std::time::SystemTime;
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use nom::bits::{bits, streaming::take as take_bits};
 use nom::bytes::streaming::tag as btag;
@@ -8,8 +10,12 @@ use nom::sequence::{pair, tuple};
 use nom::IResult;
 use nom::{call, do_parse, length_value, many0, opt, tag, take, value};
 
-use crate::utils::{fail, flat_map, map_err, parse_bit_to_bool, NomErrorExt};
+use crate::utils::{
+    fail, fail_wrap, flat_map, map_err, parse_bit_to_bool, update_crc32, NomErrorExt,
+};
+use crate::ErrorContext;
 
+use super::cp437;
 use super::datetime::parse_msdos_datetime;
 use super::ZipError;
 
@@ -17,6 +23,23 @@ pub const LOCAL_FILE_HEADER_TAG: &[u8] = b"\x50\x4b\x03\x04";
 pub const DATA_DESCRIPTOR_TAG: &[u8] = b"\x50\x4b\x07\x08";
 pub const CENTRAL_DIR_HEADER_TAG: &[u8] = b"\x50\x4b\x01\x02";
 pub const CENTRAL_DIR_END_TAG: &[u8] = b"\x50\x4b\x05\x06";
+pub const ZIP64_CENTRAL_DIR_END_TAG: &[u8] = b"\x50\x4b\x06\x06";
+pub const ZIP64_CENTRAL_DIR_END_LOCATOR_TAG: &[u8] = b"\x50\x4b\x06\x07";
+pub const DIGITAL_SIGNATURE_TAG: &[u8] = b"\x50\x4b\x05\x05";
+
+/// Size of the fixed-size fields of the Zip64 end of central directory record,
+/// not counting the tag and the record size field.
+const ZIP64_CENTRAL_DIR_END_FIXED_SIZE: u64 = 44;
+/// The record size is attacker-controlled and we'd have to buffer
+/// the whole record before parsing it, so the extensible data is capped.
+const ZIP64_CENTRAL_DIR_END_MAX_EXTENSIBLE_DATA: u64 = 64 * 1024;
+
+pub const LOCAL_FILE_HEADER_FIXED_SIZE: usize = 30;
+pub const CENTRAL_DIR_HEADER_FIXED_SIZE: usize = 46;
+pub(crate) const CENTRAL_DIR_END_FIXED_SIZE: usize = 22;
+/// The end of central directory record is at most this far from the end of an archive:
+/// its fixed-size part followed by a comment of the maximum length.
+pub const MAX_CENTRAL_DIR_END_LEN: usize = CENTRAL_DIR_END_FIXED_SIZE + u16::MAX as usize;
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct LocalFileHeader {
@@ -24,14 +47,20 @@ pub struct LocalFileHeader {
     pub encrypted: bool,
     pub deflate_mode: DeflateMode,
     pub deferred_sizes: bool,
+    /// Whether the filename is in UTF-8 instead of code page 437.
+    pub utf8_name: bool,
     pub compression_method: CompressionMethod,
     pub last_mod: SystemTime,
     pub crc_32: u32,
     pub compressed_size: u32,
     pub uncompressed_size: u32,
     pub filename: Vec<u8>,
+    /// Whether the sizes in the data descriptor are 64-bit. They are if the sizes
+    /// of the header are saturated or the header has a Zip64 extended information field.
     pub is_zip64: bool,
     pub extra_fields: Vec<(HeaderId, Vec<u8>)>,
+    /// The Zip64 extended information extra field, if the header has one.
+    pub zip64: Option<Zip64ExtendedInfo>,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -56,6 +85,9 @@ pub enum VersionMadeBy {
     Tandem,
     Os400,
     OsXDarwin,
+    /// A host system that isn't assigned in the specification, or that was
+    /// assigned after this list was written.
+    Unknown(u8),
 }
 
 fn parse_version_made_by(input: &[u8]) -> IResult<&[u8], (u8, VersionMadeBy), ZipError> {
@@ -66,16 +98,16 @@ fn parse_version_made_by(input: &[u8]) -> IResult<&[u8], (u8, VersionMadeBy), Zi
         Ok((
             zip_ver,
             match tag {
-                00 => MsDos,
-                01 => Amiga,
-                02 => OpenVms,
-                03 => Unix,
-                04 => VmCms,
-                05 => AtariSt,
-                06 => Os2Hpfs,
-                07 => Macintosh,
-                08 => ZSystem,
-                09 => Cpm,
+                0 => MsDos,
+                1 => Amiga,
+                2 => OpenVms,
+                3 => Unix,
+                4 => VmCms,
+                5 => AtariSt,
+                6 => Os2Hpfs,
+                7 => Macintosh,
+                8 => ZSystem,
+                9 => Cpm,
                 10 => WindowsNtfs,
                 11 => Mvs,
                 12 => Vse,
@@ -86,13 +118,13 @@ fn parse_version_made_by(input: &[u8]) -> IResult<&[u8], (u8, VersionMadeBy), Zi
                 17 => Tandem,
                 18 => Os400,
                 19 => OsXDarwin,
-                _ => return fail(ZipError::InvalidVersionMadeBy),
+                _ => Unknown(tag),
             },
         ))
     })(input)
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum CompressionMethod {
     Stored,
     Shrunk,
@@ -114,8 +146,15 @@ pub enum CompressionMethod {
     Reserved6,
     IbmTerse,
     IbmLz77,
+    Zstd,
     WavPack,
     PpmdVer1Rev1,
+    /// WinZip AES encryption. The actual compression method
+    /// is in the AES extra field.
+    AesEncrypted,
+    /// A method that isn't assigned in the specification. Entries compressed
+    /// with it can be listed and skipped, but not decompressed.
+    Unknown(u16),
 }
 
 fn parse_compression_method(input: &[u8]) -> IResult<&[u8], CompressionMethod, ZipError> {
@@ -147,9 +186,11 @@ fn parse_compression_method(input: &[u8]) -> IResult<&[u8], CompressionMethod, Z
             17 => Reserved6,
             18 => IbmTerse,
             19 => IbmLz77,
+            93 => Zstd,
             97 => WavPack,
             98 => PpmdVer1Rev1,
-            _ => return fail(ZipError::InvalidCompressionMethod),
+            99 => AesEncrypted,
+            _ => Unknown(tag),
         },
     ))
 }
@@ -193,11 +234,15 @@ pub enum HeaderId {
     InfoZipUnixOriginal,
     InfoZipUnicodeComment,
     BeOsBeBox,
+    InfoZipUnicodePath,
     AsiUnix,
     InfoZipUnixNew,
+    InfoZipUnixUidGid,
     MicrosoftOpenPackagingGrowthHint,
     SmsQDos,
-    UnknownHeader(u8, u8)
+    WinZipAes,
+    /// An ID that isn't assigned in the specification, such as that of a vendor-specific field.
+    Unknown(u16),
 }
 
 fn parse_header_id(input: &[u8]) -> IResult<&[u8], HeaderId, ZipError> {
@@ -249,15 +294,20 @@ fn parse_header_id(input: &[u8]) -> IResult<&[u8], HeaderId, ZipError> {
             0x5855 => InfoZipUnixOriginal,
             0x6375 => InfoZipUnicodeComment,
             0x6542 => BeOsBeBox,
+            0x7075 => InfoZipUnicodePath,
             0x756e => AsiUnix,
             0x7855 => InfoZipUnixNew,
+            0x7875 => InfoZipUnixUidGid,
             0xa220 => MicrosoftOpenPackagingGrowthHint,
             0xfd4a => SmsQDos,
-            _ => UnknownHeader(input[0], input[1]),
+            0x9901 => WinZipAes,
+            _ => Unknown(tag),
         },
     ))
 }
 
+type ExtraFields = Vec<(HeaderId, Vec<u8>)>;
+
 fn parse_one_extra_field(i: &[u8]) -> IResult<&[u8], (HeaderId, Vec<u8>), ZipError> {
     do_parse!(
         i,
@@ -268,17 +318,477 @@ fn parse_one_extra_field(i: &[u8]) -> IResult<&[u8], (HeaderId, Vec<u8>), ZipErr
     )
 }
 
-fn parse_extra_fields(
-    input: &[u8],
-    len: u16,
-) -> IResult<&[u8], Vec<(HeaderId, Vec<u8>)>, ZipError> {
+fn parse_extra_fields(input: &[u8], len: u16) -> IResult<&[u8], ExtraFields, ZipError> {
     length_value!(input, value!(len), many0!(parse_one_extra_field))
 }
 
+/// The 32-bit value of a size or an offset that is stored in
+/// the Zip64 extended information extra field instead.
+const ZIP64_SATURATED: u32 = u32::MAX;
+
+/// The Zip64 extended information extra field, which stores the sizes
+/// and the offset of an entry that don't fit in the 32-bit fields of its headers.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct Zip64ExtendedInfo {
+    pub uncompressed_size: Option<u64>,
+    pub compressed_size: Option<u64>,
+    pub local_header_offset: Option<u64>,
+    pub disk_start: Option<u32>,
+}
+
+impl Zip64ExtendedInfo {
+    /// Parses the field of a central directory header. A value is only
+    /// stored if the corresponding 32-bit field of the header is saturated,
+    /// so the flags tell which values the field has, in order.
+    /// Returns `None` if the field is too short for the values.
+    pub fn parse(
+        data: &[u8],
+        uncompressed_size: bool,
+        compressed_size: bool,
+        local_header_offset: bool,
+        disk_start: bool,
+    ) -> Option<Zip64ExtendedInfo> {
+        let mut data = data;
+        let mut take = |present: bool, len: usize| -> Option<Option<u64>> {
+            if !present {
+                return Some(None);
+            }
+            if data.len() < len {
+                return None;
+            }
+            let (value, rest) = data.split_at(len);
+            data = rest;
+            let mut bytes = [0; 8];
+            bytes[..len].copy_from_slice(value);
+            Some(Some(u64::from_le_bytes(bytes)))
+        };
+        Some(Zip64ExtendedInfo {
+            uncompressed_size: take(uncompressed_size, 8)?,
+            compressed_size: take(compressed_size, 8)?,
+            local_header_offset: take(local_header_offset, 8)?,
+            disk_start: take(disk_start, 4)?.map(|disk| disk as u32),
+        })
+    }
+
+    /// Parses the field of a local file header. The field must have
+    /// both sizes there, but some archivers only store the saturated ones,
+    /// so the sizes are read from as much of the field as there is.
+    pub fn parse_local(data: &[u8]) -> Option<Zip64ExtendedInfo> {
+        Zip64ExtendedInfo::parse(data, data.len() >= 8, data.len() >= 16, false, false)
+    }
+}
+
+/// The key length of WinZip AES encryption.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum AesStrength {
+    Aes128,
+    Aes192,
+    Aes256,
+}
+
+impl AesStrength {
+    /// The length of the key in bytes.
+    pub fn key_len(self) -> usize {
+        match self {
+            AesStrength::Aes128 => 16,
+            AesStrength::Aes192 => 24,
+            AesStrength::Aes256 => 32,
+        }
+    }
+
+    /// The length of the salt that precedes the encrypted data.
+    pub fn salt_len(self) -> usize {
+        self.key_len() / 2
+    }
+}
+
+/// The WinZip AES extra field of an entry whose compression method is `AesEncrypted`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct AesExtraField {
+    /// 1 for AE-1, which stores the CRC-32 of the data,
+    /// and 2 for AE-2, which doesn't.
+    pub vendor_version: u16,
+    pub strength: AesStrength,
+    /// The method that the data is compressed with before the encryption.
+    pub compression_method: CompressionMethod,
+}
+
+impl AesExtraField {
+    /// Returns `None` if the field isn't valid.
+    pub fn parse(data: &[u8]) -> Option<AesExtraField> {
+        if data.len() != 7 || &data[2..4] != b"AE" {
+            return None;
+        }
+        let vendor_version = u16::from_le_bytes([data[0], data[1]]);
+        let strength = match data[4] {
+            1 => AesStrength::Aes128,
+            2 => AesStrength::Aes192,
+            3 => AesStrength::Aes256,
+            _ => return None,
+        };
+        let (_, compression_method) = parse_compression_method(&data[5..7]).ok()?;
+        if !(vendor_version == 1 || vendor_version == 2)
+            || compression_method == CompressionMethod::AesEncrypted
+        {
+            return None;
+        }
+        Some(AesExtraField {
+            vendor_version,
+            strength,
+            compression_method,
+        })
+    }
+}
+
+/// The Info-ZIP Unicode Path extra field, which stores the UTF-8 name of an entry
+/// whose name in the header is in another encoding.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct UnicodePathExtraField<'a> {
+    /// The CRC-32 of the name in the header when the field was written.
+    pub name_crc_32: u32,
+    pub name: &'a [u8],
+}
+
+impl<'a> UnicodePathExtraField<'a> {
+    /// Returns `None` if the field isn't valid.
+    pub fn parse(data: &'a [u8]) -> Option<UnicodePathExtraField<'a>> {
+        if data.len() < 5 || data[0] != 1 {
+            return None;
+        }
+        Some(UnicodePathExtraField {
+            name_crc_32: u32::from_le_bytes([data[1], data[2], data[3], data[4]]),
+            name: &data[5..],
+        })
+    }
+}
+
+/// The Extended Timestamp extra field, which stores the times of an entry
+/// as Unix timestamps in UTC, unlike the MS-DOS time of the headers, which has
+/// a two-second resolution and no time zone.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct ExtendedTimestamp {
+    pub modified: Option<SystemTime>,
+    pub accessed: Option<SystemTime>,
+    pub created: Option<SystemTime>,
+}
+
+impl ExtendedTimestamp {
+    /// Returns `None` if the field isn't valid.
+    ///
+    /// The flags tell which times the entry has, but in central directory headers
+    /// the field only stores the modification time, so the times that are flagged
+    /// are read from as much of the field as there is.
+    pub fn parse(data: &[u8]) -> Option<ExtendedTimestamp> {
+        let (&flags, mut data) = data.split_first()?;
+        let mut take = |bit: u8| -> Option<SystemTime> {
+            if flags & bit == 0 || data.len() < 4 {
+                return None;
+            }
+            let (time, rest) = data.split_at(4);
+            data = rest;
+            unix_time(time)
+        };
+        Some(ExtendedTimestamp {
+            modified: take(0b001),
+            accessed: take(0b010),
+            created: take(0b100),
+        })
+    }
+}
+
+/// Converts a 32-bit Unix timestamp, which is signed, to a `SystemTime`.
+fn unix_time(time: &[u8]) -> Option<SystemTime> {
+    let time = i32::from_le_bytes([time[0], time[1], time[2], time[3]]);
+    let since_epoch = Duration::from_secs(u64::from(time.unsigned_abs()));
+    if time < 0 {
+        UNIX_EPOCH.checked_sub(since_epoch)
+    } else {
+        UNIX_EPOCH.checked_add(since_epoch)
+    }
+}
+
+/// The times and the owner of an entry in one of the Info-ZIP Unix extra fields.
+/// The fields have different subsets of the values, and the values may also
+/// be missing from the copy of a field in the central directory.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct UnixExtraField {
+    pub accessed: Option<SystemTime>,
+    pub modified: Option<SystemTime>,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+}
+
+impl UnixExtraField {
+    /// Parses the original Info-ZIP Unix field (0x5855), which has the access and
+    /// the modification time, followed by the 16-bit user and group IDs in local file
+    /// headers. Returns `None` if the field isn't valid.
+    pub fn parse_original(data: &[u8]) -> Option<UnixExtraField> {
+        if data.len() != 8 && data.len() != 12 {
+            return None;
+        }
+        let ids = data.get(8..12);
+        Some(UnixExtraField {
+            accessed: unix_time(&data[0..4]),
+            modified: unix_time(&data[4..8]),
+            uid: ids.map(|ids| u32::from(u16::from_le_bytes([ids[0], ids[1]]))),
+            gid: ids.map(|ids| u32::from(u16::from_le_bytes([ids[2], ids[3]]))),
+        })
+    }
+
+    /// Parses the second Info-ZIP Unix field (0x7855), which has the 16-bit user
+    /// and group IDs in local file headers and is empty in central directory headers.
+    /// Returns `None` if the field isn't valid.
+    pub fn parse_new(data: &[u8]) -> Option<UnixExtraField> {
+        match data.len() {
+            0 => Some(UnixExtraField::default()),
+            4 => Some(UnixExtraField {
+                uid: Some(u32::from(u16::from_le_bytes([data[0], data[1]]))),
+                gid: Some(u32::from(u16::from_le_bytes([data[2], data[3]]))),
+                ..UnixExtraField::default()
+            }),
+            _ => None,
+        }
+    }
+
+    /// Parses the third Info-ZIP Unix field (0x7875), which has a version
+    /// and the user and group IDs, each preceded by its size. Returns `None`
+    /// if the field isn't valid or an ID doesn't fit in 32 bits.
+    pub fn parse_uid_gid(data: &[u8]) -> Option<UnixExtraField> {
+        let (&version, data) = data.split_first()?;
+        if version != 1 {
+            return None;
+        }
+        let take_id = |data: &[u8]| -> Option<(u32, usize)> {
+            let size = usize::from(*data.first()?);
+            let id = data.get(1..1 + size)?;
+            if size > 4 && id[4..].iter().any(|&byte| byte != 0) {
+                return None;
+            }
+            let mut bytes = [0; 4];
+            let len = size.min(4);
+            bytes[..len].copy_from_slice(&id[..len]);
+            Some((u32::from_le_bytes(bytes), 1 + size))
+        };
+        let (uid, uid_len) = take_id(data)?;
+        let (gid, _) = take_id(&data[uid_len..])?;
+        Some(UnixExtraField {
+            uid: Some(uid),
+            gid: Some(gid),
+            ..UnixExtraField::default()
+        })
+    }
+}
+
+/// Seconds from the Windows epoch, 1601-01-01, to the Unix epoch.
+const WINDOWS_EPOCH_OFFSET: u64 = 11_644_473_600;
+
+/// The times of an entry in the NTFS extra field, which has a 100-nanosecond
+/// resolution. Archivers on Windows store the precise times only here.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct NtfsTimestamps {
+    pub modified: SystemTime,
+    pub accessed: SystemTime,
+    pub created: SystemTime,
+}
+
+impl NtfsTimestamps {
+    /// Returns `None` if the field isn't valid or doesn't have the timestamp attribute.
+    ///
+    /// The field consists of four reserved bytes followed by attributes,
+    /// each with a tag and a size. The timestamps are attribute 1.
+    pub fn parse(data: &[u8]) -> Option<NtfsTimestamps> {
+        let mut attributes = data.get(4..)?;
+        while attributes.len() >= 4 {
+            let tag = u16::from_le_bytes([attributes[0], attributes[1]]);
+            let size = usize::from(u16::from_le_bytes([attributes[2], attributes[3]]));
+            let value = attributes.get(4..4 + size)?;
+            if tag == 1 && size == 24 {
+                let time = |i: usize| {
+                    let mut bytes = [0; 8];
+                    bytes.copy_from_slice(&value[i * 8..i * 8 + 8]);
+                    windows_time(u64::from_le_bytes(bytes))
+                };
+                return Some(NtfsTimestamps {
+                    modified: time(0)?,
+                    accessed: time(1)?,
+                    created: time(2)?,
+                });
+            }
+            attributes = &attributes[4 + size..];
+        }
+        None
+    }
+}
+
+/// Converts a Windows FILETIME, in 100-nanosecond intervals since 1601-01-01, to a `SystemTime`.
+pub(crate) fn windows_time(ticks: u64) -> Option<SystemTime> {
+    let since_windows_epoch = Duration::new(ticks / 10_000_000, (ticks % 10_000_000) as u32 * 100);
+    let windows_epoch = UNIX_EPOCH.checked_sub(Duration::from_secs(WINDOWS_EPOCH_OFFSET))?;
+    windows_epoch.checked_add(since_windows_epoch)
+}
+
+/// An extra field of a header, parsed according to its header ID.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ExtraField<'a> {
+    ExtendedTimestamp(ExtendedTimestamp),
+    Ntfs(NtfsTimestamps),
+    UnixOriginal(UnixExtraField),
+    UnixNew(UnixExtraField),
+    UnixUidGid(UnixExtraField),
+    UnicodePath(UnicodePathExtraField<'a>),
+    WinZipAes(AesExtraField),
+    /// A field of a kind that isn't parsed, or that isn't valid. The Zip64
+    /// extended information field is also here, since parsing it needs
+    /// the header; it's available as the `zip64` field of the headers instead.
+    Other(&'a HeaderId, &'a [u8]),
+}
+
+impl<'a> ExtraField<'a> {
+    pub fn parse(id: &'a HeaderId, data: &'a [u8]) -> ExtraField<'a> {
+        let field = match id {
+            HeaderId::ExtendedTimestamp => {
+                ExtendedTimestamp::parse(data).map(ExtraField::ExtendedTimestamp)
+            }
+            HeaderId::Ntfs => NtfsTimestamps::parse(data).map(ExtraField::Ntfs),
+            HeaderId::InfoZipUnixOriginal => {
+                UnixExtraField::parse_original(data).map(ExtraField::UnixOriginal)
+            }
+            HeaderId::InfoZipUnixNew => UnixExtraField::parse_new(data).map(ExtraField::UnixNew),
+            HeaderId::InfoZipUnixUidGid => {
+                UnixExtraField::parse_uid_gid(data).map(ExtraField::UnixUidGid)
+            }
+            HeaderId::InfoZipUnicodePath => {
+                UnicodePathExtraField::parse(data).map(ExtraField::UnicodePath)
+            }
+            HeaderId::WinZipAes => AesExtraField::parse(data).map(ExtraField::WinZipAes),
+            _ => None,
+        };
+        field.unwrap_or(ExtraField::Other(id, data))
+    }
+}
+
+/// Parses each of `extra_fields` according to its header ID.
+fn typed_extra_fields(extra_fields: &[(HeaderId, Vec<u8>)]) -> Vec<ExtraField<'_>> {
+    extra_fields
+        .iter()
+        .map(|(id, data)| ExtraField::parse(id, data))
+        .collect()
+}
+
+/// The first valid Extended Timestamp extra field among `extra_fields`.
+fn extended_timestamp(extra_fields: &[(HeaderId, Vec<u8>)]) -> Option<ExtendedTimestamp> {
+    typed_extra_fields(extra_fields)
+        .into_iter()
+        .find_map(|field| match field {
+            ExtraField::ExtendedTimestamp(timestamp) => Some(timestamp),
+            _ => None,
+        })
+}
+
+/// The first valid NTFS extra field with timestamps among `extra_fields`.
+fn ntfs_timestamps(extra_fields: &[(HeaderId, Vec<u8>)]) -> Option<NtfsTimestamps> {
+    typed_extra_fields(extra_fields)
+        .into_iter()
+        .find_map(|field| match field {
+            ExtraField::Ntfs(timestamps) => Some(timestamps),
+            _ => None,
+        })
+}
+
+/// The user and group IDs of the entry from the Info-ZIP Unix extra fields among
+/// `extra_fields`. The newer fields are preferred, since they can store 32-bit IDs.
+fn unix_owner(extra_fields: &[(HeaderId, Vec<u8>)]) -> Option<(u32, u32)> {
+    let fields = typed_extra_fields(extra_fields);
+    let owner = |field: &UnixExtraField| Some((field.uid?, field.gid?));
+    let uid_gid = fields.iter().find_map(|field| match field {
+        ExtraField::UnixUidGid(field) => owner(field),
+        _ => None,
+    });
+    let older = || {
+        fields.iter().find_map(|field| match field {
+            ExtraField::UnixNew(field) | ExtraField::UnixOriginal(field) => owner(field),
+            _ => None,
+        })
+    };
+    uid_gid.or_else(older)
+}
+
+/// The name in the Unicode Path extra field among `extra_fields`, if there's a valid one.
+/// A field whose CRC-32 doesn't match `filename` is ignored, since the name was
+/// changed without updating the field by a tool that doesn't know about it.
+fn unicode_path<'a>(filename: &[u8], extra_fields: &'a [(HeaderId, Vec<u8>)]) -> Option<&'a [u8]> {
+    extra_fields
+        .iter()
+        .find(|(id, _)| *id == HeaderId::InfoZipUnicodePath)
+        .and_then(|(_, data)| UnicodePathExtraField::parse(data))
+        .filter(|field| field.name_crc_32 == update_crc32(0, filename))
+        .map(|field| field.name)
+}
+
+/// Decodes the name of an entry: the name in the Unicode Path extra field if there's
+/// a valid one, and otherwise `filename`, in UTF-8 if `utf8` is set and in code page 437
+/// if it isn't. Invalid UTF-8 is replaced with replacement characters.
+fn decode_name<'a>(
+    filename: &'a [u8],
+    utf8: bool,
+    extra_fields: &'a [(HeaderId, Vec<u8>)],
+) -> Cow<'a, str> {
+    match unicode_path(filename, extra_fields) {
+        Some(name) => String::from_utf8_lossy(name),
+        None if utf8 => String::from_utf8_lossy(filename),
+        None => cp437::decode(filename),
+    }
+}
+
+/// Whether `name` ends with a separator. It should be a slash,
+/// but some archivers on Windows write a backslash.
+fn ends_with_separator(name: &[u8]) -> bool {
+    name.ends_with(b"/") || name.ends_with(b"\\")
+}
+
+/// Finds the Zip64 extended information extra field among `extra_fields`.
+fn zip64_extra_field(extra_fields: &[(HeaderId, Vec<u8>)]) -> Option<&[u8]> {
+    extra_fields
+        .iter()
+        .find(|(id, _)| *id == HeaderId::Zip64Extended)
+        .map(|(_, data)| &data[..])
+}
+
+/// Picks the 64-bit value of a field that may be saturated.
+fn zip64_value(value: u32, zip64_value: Option<u64>) -> Option<u64> {
+    if value == ZIP64_SATURATED {
+        zip64_value
+    } else {
+        Some(u64::from(value))
+    }
+}
+
+/// Takes the fixed-size part of a record and returns a copy of it with
+/// the length fields at `len_offsets` zeroed, so that the copy can be parsed
+/// as a record without variable-length fields. The sum of the original lengths,
+/// that is, the amount of variable-length data that follows the fixed-size part,
+/// is returned alongside.
+fn without_variable_fields<'i, E: nom::error::ParseError<&'i [u8]>>(
+    i: &'i [u8],
+    fixed_size: usize,
+    len_offsets: &[usize],
+) -> IResult<&'i [u8], (Vec<u8>, u64), E> {
+    let (i, fixed) = nom::bytes::streaming::take(fixed_size)(i)?;
+    let mut fixed = fixed.to_vec();
+    let mut variable_len = 0;
+    for &offset in len_offsets {
+        variable_len += u64::from(u16::from_le_bytes([fixed[offset], fixed[offset + 1]]));
+        fixed[offset] = 0;
+        fixed[offset + 1] = 0;
+    }
+    Ok((i, (fixed, variable_len)))
+}
+
 impl LocalFileHeader {
     pub fn parse(i: &[u8]) -> IResult<&[u8], LocalFileHeader, ZipError> {
-        let (i, _) =
-            btag(LOCAL_FILE_HEADER_TAG)(i).map_nom_err(|_: ()| ZipError::NotLocalFileHeader)?;
+        let (i, _) = btag(LOCAL_FILE_HEADER_TAG)(i).map_nom_err(|_: ()| {
+            ZipError::NotLocalFileHeader(ErrorContext::new(LOCAL_FILE_HEADER_TAG, i))
+        })?;
         let (
             i,
             (
@@ -315,6 +825,13 @@ impl LocalFileHeader {
             .map_nom_err(|e| e.replace_external(ZipError::InvalidLocalFileHeaderFilename))?;
         let (i, extra_fields) = parse_extra_fields(i, extra_field_len)
             .map_nom_err(|e| e.replace_external(ZipError::InvalidLocalFileHeaderExtraFields))?;
+        let zip64 = match zip64_extra_field(&extra_fields) {
+            Some(data) => Some(fail_wrap(
+                Zip64ExtendedInfo::parse_local(data)
+                    .ok_or(ZipError::InvalidLocalFileHeaderExtraFields),
+            )?),
+            None => None,
+        };
         Ok((
             i,
             LocalFileHeader {
@@ -322,17 +839,108 @@ impl LocalFileHeader {
                 encrypted: bit_flags.0,
                 deflate_mode: bit_flags.1,
                 deferred_sizes: bit_flags.2,
+                utf8_name: bit_flags.3,
                 compression_method,
                 last_mod,
                 crc_32,
-                is_zip64: (compressed_size == std::u32::MAX || uncompressed_size == std::u32::MAX),
+                is_zip64: compressed_size == ZIP64_SATURATED
+                    || uncompressed_size == ZIP64_SATURATED
+                    || zip64.is_some(),
                 compressed_size,
                 uncompressed_size,
                 filename: filename.to_vec(),
                 extra_fields: extra_fields.to_vec(),
+                zip64,
             },
         ))
     }
+
+    /// The compressed and the uncompressed size of the data, taking saturated
+    /// sizes from the Zip64 extended information extra field. `None` if
+    /// the sizes are deferred to the data descriptor, or if they are saturated
+    /// and the extra field was skipped or doesn't have them.
+    pub fn sizes(&self) -> Option<(u64, u64)> {
+        if self.deferred_sizes {
+            return None;
+        }
+        let zip64 = self.zip64.unwrap_or_default();
+        Some((
+            zip64_value(self.compressed_size, zip64.compressed_size)?,
+            zip64_value(self.uncompressed_size, zip64.uncompressed_size)?,
+        ))
+    }
+
+    /// The name of the entry: the UTF-8 name in the Unicode Path extra field
+    /// if the header has a valid one, and the filename of the header otherwise.
+    pub fn name(&self) -> &[u8] {
+        unicode_path(&self.filename, &self.extra_fields).unwrap_or(&self.filename)
+    }
+
+    /// The name of the entry decoded as a string, according to the UTF-8 flag
+    /// and the Unicode Path extra field.
+    pub fn name_str(&self) -> Cow<'_, str> {
+        decode_name(&self.filename, self.utf8_name, &self.extra_fields)
+    }
+
+    /// Whether the entry is a directory, which its name marks with a trailing slash.
+    /// The attributes that also mark directories are only in the central directory.
+    pub fn is_dir(&self) -> bool {
+        ends_with_separator(self.name())
+    }
+
+    /// The extra fields of the header, parsed according to their header IDs.
+    pub fn typed_extra_fields(&self) -> Vec<ExtraField<'_>> {
+        typed_extra_fields(&self.extra_fields)
+    }
+
+    /// The modification time of the entry: the one in the extended timestamp
+    /// or the NTFS extra field if the header has either, since they are precise
+    /// and in UTC, and otherwise the MS-DOS time, which is in the unknown
+    /// time zone of the archiver.
+    pub fn last_modified(&self) -> SystemTime {
+        self.extended_timestamp()
+            .and_then(|timestamp| timestamp.modified)
+            .or_else(|| self.ntfs_timestamps().map(|timestamps| timestamps.modified))
+            .unwrap_or(self.last_mod)
+    }
+
+    /// The Extended Timestamp extra field, if the header has a valid one.
+    /// Local file headers have all the times that the field is flagged to have.
+    pub fn extended_timestamp(&self) -> Option<ExtendedTimestamp> {
+        extended_timestamp(&self.extra_fields)
+    }
+
+    /// The timestamps in the NTFS extra field, if the header has a valid one.
+    pub fn ntfs_timestamps(&self) -> Option<NtfsTimestamps> {
+        ntfs_timestamps(&self.extra_fields)
+    }
+
+    /// The user and group IDs of the owner of the entry, if the header has
+    /// an Info-ZIP Unix extra field with them.
+    pub fn unix_owner(&self) -> Option<(u32, u32)> {
+        unix_owner(&self.extra_fields)
+    }
+
+    /// The WinZip AES extra field, if the header has a valid one.
+    pub fn aes(&self) -> Option<AesExtraField> {
+        self.extra_fields
+            .iter()
+            .find(|(id, _)| *id == HeaderId::WinZipAes)
+            .and_then(|(_, data)| AesExtraField::parse(data))
+    }
+
+    /// Parses only the fixed-size part of the header, leaving the filename
+    /// and the extra fields empty. Returns the header and the length of the
+    /// variable-length fields that follow the fixed-size part in the input.
+    pub fn parse_fixed(i: &[u8]) -> IResult<&[u8], (LocalFileHeader, u64), ZipError> {
+        btag(LOCAL_FILE_HEADER_TAG)(i).map_nom_err(|_: ()| {
+            ZipError::NotLocalFileHeader(ErrorContext::new(LOCAL_FILE_HEADER_TAG, i))
+        })?;
+        let (i, (fixed, variable_len)) =
+            without_variable_fields(i, LOCAL_FILE_HEADER_FIXED_SIZE, &[26, 28])?;
+        let (_, header) = LocalFileHeader::parse(&fixed)?;
+        Ok((i, (header, variable_len)))
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -407,6 +1015,8 @@ pub struct CentralDirHeader {
     pub encrypted: bool,
     pub deflate_mode: DeflateMode,
     pub deferred_sizes: bool,
+    /// Whether the filename is in UTF-8 instead of code page 437.
+    pub utf8_name: bool,
     pub compression_method: CompressionMethod,
     pub last_mod_time: u16,
     pub last_mod_date: u16,
@@ -420,25 +1030,93 @@ pub struct CentralDirHeader {
     pub filename: Vec<u8>,
     pub extra_fields: Vec<(HeaderId, Vec<u8>)>,
     pub comment: Vec<u8>,
+    /// The Zip64 extended information extra field, if the header has one.
+    pub zip64: Option<Zip64ExtendedInfo>,
+}
+
+const UNIX_FILE_TYPE_MASK: u32 = 0o170000;
+const UNIX_DIRECTORY: u32 = 0o040000;
+const UNIX_SYMLINK: u32 = 0o120000;
+const FAT_READ_ONLY: u8 = 0x01;
+const FAT_DIRECTORY: u8 = 0x10;
+
+/// The permissions of an entry, from the external file attributes of its central
+/// directory header.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Permissions {
+    /// The Unix mode, with the file type bits if the archiver stored them.
+    Unix(u32),
+    /// The MS-DOS attributes, such as read-only, hidden and directory.
+    Fat(u8),
+}
+
+impl Permissions {
+    pub fn is_dir(self) -> bool {
+        match self {
+            Permissions::Unix(mode) => mode & UNIX_FILE_TYPE_MASK == UNIX_DIRECTORY,
+            Permissions::Fat(attributes) => attributes & FAT_DIRECTORY != 0,
+        }
+    }
+
+    /// Whether the entry is a symbolic link. MS-DOS attributes can't mark one.
+    pub fn is_symlink(self) -> bool {
+        match self {
+            Permissions::Unix(mode) => mode & UNIX_FILE_TYPE_MASK == UNIX_SYMLINK,
+            Permissions::Fat(_) => false,
+        }
+    }
+
+    /// Whether nobody may write to the entry.
+    pub fn is_readonly(self) -> bool {
+        match self {
+            Permissions::Unix(mode) => mode & 0o222 == 0,
+            Permissions::Fat(attributes) => attributes & FAT_READ_ONLY != 0,
+        }
+    }
+
+    /// The Unix permission bits to extract the entry with. MS-DOS attributes
+    /// are mapped the way Info-ZIP does it, with the execute bits for directories.
+    pub fn unix_mode(self) -> u32 {
+        match self {
+            Permissions::Unix(mode) => mode & 0o7777,
+            Permissions::Fat(_) => {
+                let mode = if self.is_dir() { 0o755 } else { 0o644 };
+                if self.is_readonly() {
+                    mode & !0o222
+                } else {
+                    mode
+                }
+            }
+        }
+    }
 }
 
-pub fn parse_bitflags(input: &[u8]) -> IResult<&[u8], (bool, DeflateMode, bool), ZipError> {
-    let (input, (_pad1, deferred_sizes, deflate_mode, encrypted, _pad2)) = bits(tuple((
-        take_bits(4_usize),
-        parse_bit_to_bool,
-        parse_deflate_mode,
-        parse_bit_to_bool,
-        take_bits(4_usize),
-    )))(input)?;
+/// Parses the general purpose bit flags into whether the entry is encrypted,
+/// its Deflate mode, whether its sizes are deferred, and whether its name is in UTF-8.
+pub fn parse_bitflags(input: &[u8]) -> IResult<&[u8], (bool, DeflateMode, bool, bool), ZipError> {
+    // The flags are little-endian, so the first byte has the bits 7 to 0
+    // and the second one the bits 15 to 8.
+    let (input, (_pad1, deferred_sizes, deflate_mode, encrypted, _pad2, utf8_name, _pad3)) =
+        bits(tuple((
+            take_bits(4_usize),
+            parse_bit_to_bool,
+            parse_deflate_mode,
+            parse_bit_to_bool,
+            take_bits(4_usize),
+            parse_bit_to_bool,
+            take_bits(3_usize),
+        )))(input)?;
     let _: u8 = _pad1;
     let _: u8 = _pad2;
-    Ok((input, (encrypted, deflate_mode, deferred_sizes)))
+    let _: u8 = _pad3;
+    Ok((input, (encrypted, deflate_mode, deferred_sizes, utf8_name)))
 }
 
 impl CentralDirHeader {
     pub fn parse(i: &[u8]) -> IResult<&[u8], CentralDirHeader, ZipError> {
-        let (i, _) =
-            btag(CENTRAL_DIR_HEADER_TAG)(i).map_nom_err(|_: ()| ZipError::NotCentralDirHeader)?;
+        let (i, _) = btag(CENTRAL_DIR_HEADER_TAG)(i).map_nom_err(|_: ()| {
+            ZipError::NotCentralDirHeader(ErrorContext::new(CENTRAL_DIR_HEADER_TAG, i))
+        })?;
         do_parse!(
             i,
             version_made_by: parse_version_made_by
@@ -466,6 +1144,7 @@ impl CentralDirHeader {
                     encrypted: bit_flags.0,
                     deflate_mode: bit_flags.1,
                     deferred_sizes: bit_flags.2,
+                    utf8_name: bit_flags.3,
                     compression_method,
                     last_mod_time,
                     last_mod_date,
@@ -479,8 +1158,113 @@ impl CentralDirHeader {
                     filename: filename.to_vec(),
                     extra_fields,
                     comment: comment.to_vec(),
+                    zip64: None,
                 })
         )
+        .and_then(|(i, mut header)| {
+            if let Some(data) = zip64_extra_field(&header.extra_fields) {
+                let zip64 = Zip64ExtendedInfo::parse(
+                    data,
+                    header.uncompressed_size == ZIP64_SATURATED,
+                    header.compressed_size == ZIP64_SATURATED,
+                    header.rel_offset_loc_header == ZIP64_SATURATED,
+                    header.disk_no_start == u16::MAX,
+                );
+                header.zip64 = Some(fail_wrap(zip64.ok_or(ZipError::InvalidCentralDirHeader))?);
+            }
+            Ok((i, header))
+        })
+    }
+
+    /// The compressed and the uncompressed size of the data, taking saturated
+    /// sizes from the Zip64 extended information extra field. `None` if
+    /// the sizes are saturated and the extra field was skipped.
+    pub fn sizes(&self) -> Option<(u64, u64)> {
+        let zip64 = self.zip64.unwrap_or_default();
+        Some((
+            zip64_value(self.compressed_size, zip64.compressed_size)?,
+            zip64_value(self.uncompressed_size, zip64.uncompressed_size)?,
+        ))
+    }
+
+    /// The name of the entry: the UTF-8 name in the Unicode Path extra field
+    /// if the header has a valid one, and the filename of the header otherwise.
+    pub fn name(&self) -> &[u8] {
+        unicode_path(&self.filename, &self.extra_fields).unwrap_or(&self.filename)
+    }
+
+    /// The name of the entry decoded as a string, according to the UTF-8 flag
+    /// and the Unicode Path extra field.
+    pub fn name_str(&self) -> Cow<'_, str> {
+        decode_name(&self.filename, self.utf8_name, &self.extra_fields)
+    }
+
+    /// Whether the entry is a directory, according to a trailing slash
+    /// in its name or its external file attributes.
+    pub fn is_dir(&self) -> bool {
+        ends_with_separator(self.name()) || self.permissions().is_dir()
+    }
+
+    /// The extra fields of the header, parsed according to their header IDs.
+    pub fn typed_extra_fields(&self) -> Vec<ExtraField<'_>> {
+        typed_extra_fields(&self.extra_fields)
+    }
+
+    /// The Extended Timestamp extra field, if the header has a valid one.
+    /// In central directory headers, it usually only has the modification time.
+    pub fn extended_timestamp(&self) -> Option<ExtendedTimestamp> {
+        extended_timestamp(&self.extra_fields)
+    }
+
+    /// The timestamps in the NTFS extra field, if the header has a valid one.
+    pub fn ntfs_timestamps(&self) -> Option<NtfsTimestamps> {
+        ntfs_timestamps(&self.extra_fields)
+    }
+
+    /// The user and group IDs of the owner of the entry, if the header has
+    /// an Info-ZIP Unix extra field with them.
+    pub fn unix_owner(&self) -> Option<(u32, u32)> {
+        unix_owner(&self.extra_fields)
+    }
+
+    /// The offset of the local file header of the entry, taking a saturated
+    /// offset from the Zip64 extended information extra field.
+    pub fn local_header_offset(&self) -> Option<u64> {
+        let zip64 = self.zip64.unwrap_or_default();
+        zip64_value(self.rel_offset_loc_header, zip64.local_header_offset)
+    }
+
+    /// Whether the entry is a symbolic link, whose data is the path it links to.
+    /// Only archivers on Unix-like systems mark symbolic links.
+    pub fn is_symlink(&self) -> bool {
+        self.permissions().is_symlink()
+    }
+
+    /// The permissions of the entry, interpreting the external file attributes
+    /// according to the system that made the archive: archivers on Unix-like
+    /// systems store the mode in the high 16 bits and the MS-DOS attributes
+    /// in the low ones, and other archivers store only the MS-DOS attributes.
+    pub fn permissions(&self) -> Permissions {
+        let mode = self.ext_file_attrib >> 16;
+        match self.version_made_by.1 {
+            VersionMadeBy::Unix | VersionMadeBy::OsXDarwin | VersionMadeBy::BeOs if mode != 0 => {
+                Permissions::Unix(mode)
+            }
+            _ => Permissions::Fat(self.ext_file_attrib as u8),
+        }
+    }
+
+    /// Parses only the fixed-size part of the header, leaving the filename,
+    /// the extra fields and the comment empty. Returns the header and the length
+    /// of the variable-length fields that follow the fixed-size part in the input.
+    pub fn parse_fixed(i: &[u8]) -> IResult<&[u8], (CentralDirHeader, u64), ZipError> {
+        btag(CENTRAL_DIR_HEADER_TAG)(i).map_nom_err(|_: ()| {
+            ZipError::NotCentralDirHeader(ErrorContext::new(CENTRAL_DIR_HEADER_TAG, i))
+        })?;
+        let (i, (fixed, variable_len)) =
+            without_variable_fields(i, CENTRAL_DIR_HEADER_FIXED_SIZE, &[28, 30, 32])?;
+        let (_, header) = CentralDirHeader::parse(&fixed)?;
+        Ok((i, (header, variable_len)))
     }
 }
 
@@ -519,4 +1303,155 @@ impl CentralDirEnd {
                 })
         )
     }
+
+    /// Parses only the fixed-size part of the record, leaving the comment empty.
+    /// Returns the record and the length of the comment that follows
+    /// the fixed-size part in the input.
+    pub fn parse_fixed(i: &[u8]) -> IResult<&[u8], (CentralDirEnd, u64)> {
+        btag(CENTRAL_DIR_END_TAG)(i)?;
+        let (rest, (fixed, comment_len)) =
+            without_variable_fields(i, CENTRAL_DIR_END_FIXED_SIZE, &[20])?;
+        let (_, end) = CentralDirEnd::parse(&fixed).map_err(|e| e.map(|(_, kind)| (i, kind)))?;
+        Ok((rest, (end, comment_len)))
+    }
+
+    /// Searches `tail`, the last `MAX_CENTRAL_DIR_END_LEN` bytes of an archive
+    /// (or all of it, if it's shorter), backward for the end of central directory record.
+    /// Returns the record and its offset in `tail`.
+    ///
+    /// The comment may contain the signature of the record, so the record is the one
+    /// whose comment reaches exactly the end of `tail`. If there's no such record,
+    /// data after the archive is tolerated, and the last record that parses is returned.
+    pub fn find(tail: &[u8]) -> Option<(CentralDirEnd, usize)> {
+        let candidates = || {
+            (0..tail.len())
+                .rev()
+                .filter(move |&pos| tail[pos..].starts_with(CENTRAL_DIR_END_TAG))
+                .filter_map(move |pos| {
+                    CentralDirEnd::parse(&tail[pos..])
+                        .ok()
+                        .map(|(rest, end)| (rest.is_empty(), end, pos))
+                })
+        };
+        candidates()
+            .find(|&(exact, _, _)| exact)
+            .or_else(|| candidates().next())
+            .map(|(_, end, pos)| (end, pos))
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Zip64CentralDirEnd {
+    pub version_made_by: (u8, VersionMadeBy),
+    pub version_needed: u16,
+    pub this_disk_num: u32,
+    pub central_dir_start_disk_num: u32,
+    pub central_dir_num_entries_this_disk: u64,
+    pub central_dir_num_entries_total: u64,
+    pub central_dir_size: u64,
+    pub central_dir_start_offset: u64,
+    pub extensible_data: Vec<u8>,
+}
+
+impl Zip64CentralDirEnd {
+    pub fn parse(i: &[u8]) -> IResult<&[u8], Zip64CentralDirEnd, ZipError> {
+        let (i, _) = btag(ZIP64_CENTRAL_DIR_END_TAG)(i).map_nom_err(|_: ()| {
+            ZipError::NotCentralDirEnd(ErrorContext::new(ZIP64_CENTRAL_DIR_END_TAG, i))
+        })?;
+        let (i, record_size) = le_u64(i)?;
+        // The record size doesn't include the leading 12 bytes (the tag and the size itself)
+        // but it includes everything after those: the fixed-size fields
+        // and the variable-size extensible data.
+        if record_size < ZIP64_CENTRAL_DIR_END_FIXED_SIZE
+            || record_size - ZIP64_CENTRAL_DIR_END_FIXED_SIZE
+                > ZIP64_CENTRAL_DIR_END_MAX_EXTENSIBLE_DATA
+        {
+            return fail(ZipError::InvalidCentralDirEnd);
+        }
+        let record_size =
+            fail_wrap(usize::try_from(record_size).map_err(|_| ZipError::InvalidCentralDirEnd))?;
+        let (i, record) = nom::bytes::streaming::take(record_size)(i)?;
+        let (
+            extensible_data,
+            (
+                version_made_by,
+                version_needed,
+                this_disk_num,
+                central_dir_start_disk_num,
+                central_dir_num_entries_this_disk,
+                central_dir_num_entries_total,
+                central_dir_size,
+                central_dir_start_offset,
+            ),
+        ) = tuple((
+            parse_version_made_by,
+            le_u16,
+            le_u32,
+            le_u32,
+            le_u64,
+            le_u64,
+            le_u64,
+            le_u64,
+        ))(record)
+        .map_err(|_| nom::Err::Failure(ZipError::InvalidCentralDirEnd))?;
+        Ok((
+            i,
+            Zip64CentralDirEnd {
+                version_made_by,
+                version_needed,
+                this_disk_num,
+                central_dir_start_disk_num,
+                central_dir_num_entries_this_disk,
+                central_dir_num_entries_total,
+                central_dir_size,
+                central_dir_start_offset,
+                extensible_data: extensible_data.to_vec(),
+            },
+        ))
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Zip64CentralDirEndLocator {
+    pub central_dir_end_disk_num: u32,
+    pub central_dir_end_offset: u64,
+    pub total_disks: u32,
+}
+
+impl Zip64CentralDirEndLocator {
+    pub fn parse(i: &[u8]) -> IResult<&[u8], Zip64CentralDirEndLocator, ZipError> {
+        let (i, _) = btag(ZIP64_CENTRAL_DIR_END_LOCATOR_TAG)(i).map_nom_err(|_: ()| {
+            ZipError::NotCentralDirEnd(ErrorContext::new(ZIP64_CENTRAL_DIR_END_LOCATOR_TAG, i))
+        })?;
+        let (i, (central_dir_end_disk_num, central_dir_end_offset, total_disks)) =
+            tuple((le_u32, le_u64, le_u32))(i)?;
+        Ok((
+            i,
+            Zip64CentralDirEndLocator {
+                central_dir_end_disk_num,
+                central_dir_end_offset,
+                total_disks,
+            },
+        ))
+    }
+}
+
+/// Parses the digital signature record that may follow
+/// the central directory headers, returning the signature data.
+pub fn parse_digital_signature(i: &[u8]) -> IResult<&[u8], &[u8], ZipError> {
+    let (i, _) = btag(DIGITAL_SIGNATURE_TAG)(i).map_nom_err(|_: ()| {
+        ZipError::NotCentralDirEnd(ErrorContext::new(DIGITAL_SIGNATURE_TAG, i))
+    })?;
+    let (i, len) = le_u16(i)?;
+    nom::bytes::streaming::take(len)(i)
+}
+
+/// Parses only the fixed-size part of the digital signature record.
+/// Returns the length of the signature data that follows it in the input.
+pub fn parse_digital_signature_fixed(i: &[u8]) -> IResult<&[u8], u64, ZipError> {
+    let (i, _) = btag(DIGITAL_SIGNATURE_TAG)(i).map_nom_err(|_: ()| {
+        ZipError::NotCentralDirEnd(ErrorContext::new(DIGITAL_SIGNATURE_TAG, i))
+    })?;
+    let (i, len) = le_u16(i)?;
+    Ok((i, u64::from(len)))
 }
@@ -0,0 +1,192 @@
+//! Matching entry names against glob patterns.
+//!
+//! Names are matched as bytes, since zip archives don't guarantee any encoding.
+//! Both `/` and `\` are treated as path separators, in the names as well as
+//! in the patterns, because some archivers write Windows-style paths.
+
+use super::headers::{CentralDirHeader, LocalFileHeader};
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Token {
+    Literal(u8),
+    /// `?`, any byte except a separator.
+    AnyByte,
+    /// `[...]`, a byte in (or, if negated, not in) one of the inclusive ranges.
+    Class {
+        negated: bool,
+        ranges: Vec<(u8, u8)>,
+    },
+    /// `*`, any bytes except separators.
+    Star,
+    /// `**` followed by a separator, any number of whole path segments.
+    Segments,
+    /// `**` elsewhere, any bytes.
+    Globstar,
+}
+
+/// Matches entry names against a glob pattern, for example to select the entries
+/// to decompress with `Options::entry_filter`, or to filter the result of `list_entries`.
+///
+/// In the pattern, `?` matches any character and `*` any characters, except
+/// separators, and `[abc]`, `[a-z]` and `[!a-z]` match a character in or not in
+/// the set. `**/` matches any number of directories, including none, so `**/*.csv`
+/// matches CSV files at any depth, and `**` elsewhere matches anything.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct EntryMatcher {
+    tokens: Vec<Token>,
+    case_insensitive: bool,
+}
+
+impl EntryMatcher {
+    /// A matcher for the glob `pattern`. A `[` without a matching `]` matches itself.
+    pub fn glob(pattern: &str) -> EntryMatcher {
+        EntryMatcher {
+            tokens: tokenize(pattern.as_bytes()),
+            case_insensitive: false,
+        }
+    }
+
+    /// When enabled, ASCII letters match regardless of their case.
+    pub fn case_insensitive(mut self, enabled: bool) -> Self {
+        self.case_insensitive = enabled;
+        self
+    }
+
+    /// Whether `name`, the name of an entry, matches the pattern.
+    pub fn matches(&self, name: &[u8]) -> bool {
+        let name: Vec<u8> = name.iter().map(|&byte| self.normalize(byte)).collect();
+        // matched[j] is whether the tokens so far match the first j bytes of the name.
+        let mut matched = vec![false; name.len() + 1];
+        matched[0] = true;
+        for token in &self.tokens {
+            let mut next = vec![false; name.len() + 1];
+            match token {
+                Token::Star | Token::Globstar => {
+                    next[0] = matched[0];
+                    for j in 1..=name.len() {
+                        let extends =
+                            next[j - 1] && (*token == Token::Globstar || name[j - 1] != b'/');
+                        next[j] = matched[j] || extends;
+                    }
+                }
+                Token::Segments => {
+                    // Either nothing, or anything that ends with a separator.
+                    let mut matched_before = false;
+                    next[0] = matched[0];
+                    for j in 1..=name.len() {
+                        matched_before |= matched[j - 1];
+                        next[j] = matched[j] || (matched_before && name[j - 1] == b'/');
+                    }
+                }
+                _ => {
+                    for j in 0..name.len() {
+                        next[j + 1] = matched[j] && self.matches_byte(token, name[j]);
+                    }
+                }
+            }
+            matched = next;
+        }
+        matched[name.len()]
+    }
+
+    /// Whether the name of `header` matches the pattern.
+    pub fn matches_header(&self, header: &LocalFileHeader) -> bool {
+        self.matches(header.name())
+    }
+
+    /// Whether the name of `header` matches the pattern.
+    pub fn matches_central_dir_header(&self, header: &CentralDirHeader) -> bool {
+        self.matches(header.name())
+    }
+
+    fn normalize(&self, byte: u8) -> u8 {
+        match byte {
+            b'\\' => b'/',
+            _ if self.case_insensitive => byte.to_ascii_lowercase(),
+            _ => byte,
+        }
+    }
+
+    /// Whether `byte` of a normalized name matches a token that matches a single byte.
+    fn matches_byte(&self, token: &Token, byte: u8) -> bool {
+        match token {
+            Token::Literal(literal) => self.normalize(*literal) == byte,
+            Token::AnyByte => byte != b'/',
+            Token::Class { negated, ranges } => {
+                let in_ranges = ranges.iter().any(|&(start, end)| {
+                    let (start, end) = (self.normalize(start), self.normalize(end));
+                    start <= byte && byte <= end
+                });
+                byte != b'/' && in_ranges != *negated
+            }
+            Token::Star | Token::Segments | Token::Globstar => {
+                unreachable!("These don't match single bytes")
+            }
+        }
+    }
+}
+
+fn is_separator(byte: u8) -> bool {
+    byte == b'/' || byte == b'\\'
+}
+
+fn tokenize(pattern: &[u8]) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < pattern.len() {
+        match pattern[i] {
+            b'*' if pattern[i..].starts_with(b"**") => {
+                i += 2;
+                if i < pattern.len() && is_separator(pattern[i]) {
+                    i += 1;
+                    tokens.push(Token::Segments);
+                } else {
+                    tokens.push(Token::Globstar);
+                }
+                continue;
+            }
+            b'*' => tokens.push(Token::Star),
+            b'?' => tokens.push(Token::AnyByte),
+            b'[' => {
+                if let Some((token, len)) = parse_class(&pattern[i..]) {
+                    tokens.push(token);
+                    i += len;
+                    continue;
+                }
+                tokens.push(Token::Literal(b'['));
+            }
+            byte if is_separator(byte) => tokens.push(Token::Literal(b'/')),
+            byte => tokens.push(Token::Literal(byte)),
+        }
+        i += 1;
+    }
+    tokens
+}
+
+/// Parses a class at the start of `pattern`, returning it and its length in the pattern.
+fn parse_class(pattern: &[u8]) -> Option<(Token, usize)> {
+    let mut i = 1;
+    let negated = pattern.get(i) == Some(&b'!');
+    if negated {
+        i += 1;
+    }
+    let mut ranges = Vec::new();
+    // A `]` right at the start is a member, not the end of the class.
+    let start = i;
+    loop {
+        let byte = *pattern.get(i)?;
+        if byte == b']' && i > start {
+            return Some((Token::Class { negated, ranges }, i + 1));
+        }
+        match pattern.get(i + 1..i + 3) {
+            Some(&[b'-', end]) if end != b']' => {
+                ranges.push((byte, end));
+                i += 3;
+            }
+            _ => {
+                ranges.push((byte, byte));
+                i += 1;
+            }
+        }
+    }
+}
@@ -0,0 +1,501 @@
+//! Random access to the entries of a zip archive in a seekable source.
+//!
+//! Unlike the streaming `ZipFile`, `SeekArchive` starts from the end of the archive:
+//! it reads the central directory first, so the metadata of every entry is known
+//! up front, and then decompresses entries by seeking to their local headers.
+
+use std::convert::TryFrom;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::time::UNIX_EPOCH;
+
+use crate::deflate;
+use crate::gzip::headers::{self as gzip_headers, MemberHeader, Os};
+use crate::utils::update_crc32;
+
+use super::decompressor::Decompressor;
+use super::headers::{
+    self, CentralDirEnd, CentralDirHeader, CompressionMethod, LocalFileHeader, VersionMadeBy,
+    Zip64CentralDirEnd, Zip64CentralDirEndLocator,
+};
+use super::{Options, ZipError};
+use crate::ErrorContext;
+
+const ZIP64_CENTRAL_DIR_END_LOCATOR_LEN: u64 = 20;
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+/// Symbolic link targets are paths, so they are at most as long as the longest
+/// path that Linux allows.
+const MAX_SYMLINK_TARGET_LEN: u64 = 4096;
+
+/// An error returned by `SeekArchive`: either the reader or the sink
+/// failed, the archive itself was invalid, or there was no entry at the index.
+#[derive(Debug)]
+pub enum SeekError {
+    Io(io::Error),
+    Zip(ZipError),
+    /// The index was out of bounds of `entries`.
+    NoSuchEntry(usize),
+}
+
+impl std::fmt::Display for SeekError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SeekError::Io(e) => write!(f, "i/o error: {}", e),
+            SeekError::Zip(e) => write!(f, "zip error: {}", e),
+            SeekError::NoSuchEntry(index) => write!(f, "no entry at index {}", index),
+        }
+    }
+}
+
+impl std::error::Error for SeekError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SeekError::Io(e) => Some(e),
+            SeekError::Zip(e) => Some(e),
+            SeekError::NoSuchEntry(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for SeekError {
+    fn from(err: io::Error) -> SeekError {
+        SeekError::Io(err)
+    }
+}
+
+impl From<ZipError> for SeekError {
+    fn from(err: ZipError) -> SeekError {
+        SeekError::Zip(err)
+    }
+}
+
+/// A zip archive read from a seekable source, such as a local file.
+pub struct SeekArchive<R> {
+    reader: R,
+    options: Options,
+    /// Where the archive starts in the source. The offsets in the archive are relative
+    /// to this, which matters if something, like the stub of a self-extracting archive,
+    /// precedes the archive without the offsets accounting for it.
+    archive_start: u64,
+    end: CentralDirEnd,
+    entries: Vec<CentralDirHeader>,
+}
+
+impl<R: Read + Seek> SeekArchive<R> {
+    /// Reads the central directory of the archive in `reader`.
+    pub fn new(reader: R) -> Result<SeekArchive<R>, SeekError> {
+        SeekArchive::with_options(reader, Options::default())
+    }
+
+    /// Reads the central directory of the archive in `reader`.
+    /// The entries are decompressed according to `options`.
+    pub fn with_options(mut reader: R, options: Options) -> Result<SeekArchive<R>, SeekError> {
+        let central_dir = read_central_dir(&mut reader)?;
+        let mut input = &central_dir.headers[..];
+        let mut entries = Vec::new();
+        for _ in 0..central_dir.num_entries {
+            let (rest, entry) = CentralDirHeader::parse(input).map_err(|e| match e {
+                nom::Err::Error(e) | nom::Err::Failure(e) => e,
+                nom::Err::Incomplete(_) => ZipError::InvalidCentralDirHeader,
+            })?;
+            entries.push(entry);
+            input = rest;
+        }
+
+        Ok(SeekArchive {
+            reader,
+            options,
+            archive_start: central_dir.archive_start,
+            end: central_dir.end,
+            entries,
+        })
+    }
+
+    /// The central directory headers of the entries, in the order of the central directory.
+    pub fn entries(&self) -> &[CentralDirHeader] {
+        &self.entries
+    }
+
+    /// The end of central directory record of the archive.
+    pub fn central_dir_end(&self) -> &CentralDirEnd {
+        &self.end
+    }
+
+    /// The central directory header of the entry at `index` of `entries`.
+    fn entry(&self, index: usize) -> Result<&CentralDirHeader, SeekError> {
+        self.entries.get(index).ok_or(SeekError::NoSuchEntry(index))
+    }
+
+    /// Decompresses the entry at `index` of `entries`, passing the decompressed
+    /// output to `sink`. Returns the number of decompressed bytes.
+    ///
+    /// Fails with `SeekError::NoSuchEntry` if `index` is out of bounds.
+    pub fn read_entry(
+        &mut self,
+        index: usize,
+        mut sink: impl FnMut(&[u8]) -> io::Result<()>,
+    ) -> Result<u64, SeekError> {
+        let entry = self.entry(index)?;
+        let expected_crc = entry.crc_32;
+        let (compressed_size, uncompressed_size) =
+            entry.sizes().ok_or(ZipError::InvalidCentralDirHeader)?;
+        let header = self.read_local_header(index)?;
+
+        // Like in the stream, entries without data, such as directories,
+        // are empty whatever their compression method.
+        if compressed_size == 0 {
+            if uncompressed_size > 0 {
+                return Err(ZipError::InvalidCentralDirHeader.into());
+            }
+            return Ok(0);
+        }
+        let mut decompressor = Decompressor::for_header_with_sizes(
+            &header,
+            Some((compressed_size, uncompressed_size)),
+            &self.options,
+        )?;
+        let mut remaining = compressed_size;
+        let mut input = Vec::new();
+        // Where the unparsed part of `input` starts. The parsed part
+        // is dropped only when more input is read, not after every output.
+        let mut position = 0;
+        let mut written = 0;
+        let mut crc_32 = 0;
+        loop {
+            let unparsed = &input[position..];
+            let (consumed, needs_input) = match decompressor.feed_input(unparsed)? {
+                deflate::State::HasOutput {
+                    unparsed_input,
+                    output,
+                } => {
+                    sink(output)?;
+                    written += output.len() as u64;
+                    crc_32 = update_crc32(crc_32, output);
+                    (unparsed.len() - unparsed_input.len(), false)
+                }
+                deflate::State::NeedsInput { unparsed_input } => {
+                    (unparsed.len() - unparsed_input.len(), true)
+                }
+                deflate::State::Stop { .. } => break,
+            };
+            position += consumed;
+            if needs_input {
+                if remaining == 0 && consumed == 0 {
+                    // The compressed data ended before the decompressed data did.
+                    return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+                }
+                input.drain(..position);
+                position = 0;
+                let len = std::cmp::min(remaining, READ_BUFFER_SIZE as u64);
+                let read = (&mut self.reader).take(len).read_to_end(&mut input)?;
+                if read as u64 != len {
+                    return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+                }
+                remaining -= len;
+            }
+        }
+
+        if written != uncompressed_size {
+            return Err(ZipError::InvalidCentralDirHeader.into());
+        }
+        if self.options.verifies_checksum(&header) && crc_32 != expected_crc {
+            return Err(ZipError::ChecksumMismatch {
+                expected: expected_crc,
+                actual: crc_32,
+            }
+            .into());
+        }
+        Ok(written)
+    }
+
+    /// Copies the data of the entry at `index` of `entries` to `sink` as it is
+    /// in the archive: compressed, and possibly encrypted. Returns the number
+    /// of bytes copied.
+    ///
+    /// Fails with `SeekError::NoSuchEntry` if `index` is out of bounds.
+    pub fn read_raw_entry(
+        &mut self,
+        index: usize,
+        mut sink: impl FnMut(&[u8]) -> io::Result<()>,
+    ) -> Result<u64, SeekError> {
+        let (compressed_size, _) = self
+            .entry(index)?
+            .sizes()
+            .ok_or(ZipError::InvalidCentralDirHeader)?;
+        self.read_local_header(index)?;
+
+        let mut buffer = vec![0; std::cmp::min(compressed_size, READ_BUFFER_SIZE as u64) as usize];
+        let mut remaining = compressed_size;
+        while remaining > 0 {
+            let len = std::cmp::min(remaining, buffer.len() as u64) as usize;
+            self.reader.read_exact(&mut buffer[..len])?;
+            sink(&buffer[..len])?;
+            remaining -= len as u64;
+        }
+        Ok(compressed_size)
+    }
+
+    /// Writes the entry at `index` of `entries` to `sink` as a gzip member.
+    /// Both formats carry raw Deflate, so the data of a Deflate-compressed entry
+    /// is copied without decompressing it, and the CRC-32 and the size in the footer
+    /// are taken from the central directory, to be checked when the member is
+    /// decompressed. Stored entries are wrapped in stored Deflate blocks.
+    ///
+    /// Fails with `ZipError::EncryptedEntry` if the entry is encrypted, and with
+    /// `ZipError::UnsupportedCompressionMethod` if it's compressed with another method.
+    ///
+    /// Fails with `SeekError::NoSuchEntry` if `index` is out of bounds.
+    pub fn transcode_to_gzip(
+        &mut self,
+        index: usize,
+        mut sink: impl FnMut(&[u8]) -> io::Result<()>,
+    ) -> Result<(), SeekError> {
+        let entry = self.entry(index)?;
+        if entry.encrypted {
+            return Err(ZipError::EncryptedEntry.into());
+        }
+        let (compressed_size, uncompressed_size) =
+            entry.sizes().ok_or(ZipError::InvalidCentralDirHeader)?;
+        // Entries without data may have no Deflate stream at all.
+        let copy_raw = match entry.compression_method {
+            CompressionMethod::Deflated => compressed_size > 0,
+            CompressionMethod::Stored => false,
+            method => return Err(ZipError::UnsupportedCompressionMethod(method).into()),
+        };
+        let crc_32 = entry.crc_32;
+        let os = gzip_os(&entry.version_made_by.1);
+        // The gzip header has the name of the file without its directory.
+        let filename = entry
+            .name()
+            .rsplit(|&byte| byte == b'/')
+            .next()
+            .filter(|name| !name.is_empty() && !name.contains(&0))
+            .map(<[u8]>::to_vec);
+
+        let mtime = self
+            .read_local_header(index)?
+            .last_modified()
+            .duration_since(UNIX_EPOCH)
+            .ok()
+            .and_then(|since_epoch| u32::try_from(since_epoch.as_secs()).ok())
+            .unwrap_or(0);
+        let header = MemberHeader {
+            mtime,
+            os: os.into(),
+            filename,
+            ..MemberHeader::default()
+        };
+        let header = header.to_bytes().ok_or(ZipError::InvalidCentralDirHeader)?;
+        sink(&header)?;
+
+        if copy_raw {
+            self.read_raw_entry(index, &mut sink)?;
+        } else {
+            let mut encoder = deflate::Encoder::with(0, 0);
+            let mut output = Vec::new();
+            let compression_error = |status| io::Error::other(format!("{:?}", status));
+            self.read_raw_entry(index, |data| {
+                output.clear();
+                encoder
+                    .inner_iter(data, |out| output.extend_from_slice(out))
+                    .map_err(compression_error)?;
+                sink(&output)
+            })?;
+            output.clear();
+            encoder
+                .finish_iter(|out| output.extend_from_slice(out))
+                .map_err(compression_error)?;
+            sink(&output)?;
+        }
+        sink(&gzip_headers::footer(crc_32, uncompressed_size))?;
+        Ok(())
+    }
+
+    /// Reads the local file header of the entry at `index` of `entries`,
+    /// leaving the reader at the start of the data of the entry.
+    fn read_local_header(&mut self, index: usize) -> Result<LocalFileHeader, SeekError> {
+        let offset = self
+            .entry(index)?
+            .local_header_offset()
+            .ok_or(ZipError::InvalidCentralDirHeader)?;
+
+        // The local header has the extra fields that decryption needs, and its length
+        // tells where the data starts, so it's read before the data.
+        self.reader
+            .seek(SeekFrom::Start(self.archive_start + offset))?;
+        let mut header = vec![0; headers::LOCAL_FILE_HEADER_FIXED_SIZE];
+        self.reader.read_exact(&mut header)?;
+        let variable_len = match LocalFileHeader::parse_fixed(&header) {
+            Ok((_, (_, variable_len))) => variable_len,
+            Err(_) => {
+                let context = ErrorContext::new(headers::LOCAL_FILE_HEADER_TAG, &header);
+                let context = context.shifted(self.archive_start + offset);
+                return Err(ZipError::NotLocalFileHeader(context).into());
+            }
+        };
+        (&mut self.reader)
+            .take(variable_len)
+            .read_to_end(&mut header)?;
+        match LocalFileHeader::parse(&header) {
+            Ok((_, header)) => Ok(header),
+            Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => Err(e.into()),
+            Err(nom::Err::Incomplete(_)) => {
+                Err(io::Error::from(io::ErrorKind::UnexpectedEof).into())
+            }
+        }
+    }
+
+    /// Reads the path that the entry at `index` of `entries` links to,
+    /// or returns `None` if the entry isn't a symbolic link.
+    ///
+    /// The target is taken from the archive as is, so it may be absolute
+    /// or point outside the directory the archive is extracted to.
+    ///
+    /// Fails with `SeekError::NoSuchEntry` if `index` is out of bounds.
+    pub fn read_symlink_target(&mut self, index: usize) -> Result<Option<Vec<u8>>, SeekError> {
+        let entry = self.entry(index)?;
+        if !entry.is_symlink() {
+            return Ok(None);
+        }
+        match entry.sizes() {
+            Some((_, uncompressed_size)) if uncompressed_size <= MAX_SYMLINK_TARGET_LEN => (),
+            _ => return Err(ZipError::InvalidSymlinkTarget.into()),
+        }
+        let mut target = Vec::new();
+        self.read_entry(index, |output| {
+            target.extend_from_slice(output);
+            Ok(())
+        })?;
+        Ok(Some(target))
+    }
+
+    /// Returns the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+/// The gzip equivalent of the host system of a zip entry.
+fn gzip_os(host: &VersionMadeBy) -> Os {
+    use self::VersionMadeBy::*;
+    match host {
+        MsDos | VFat => Os::Fat,
+        Amiga => Os::Amiga,
+        OpenVms => Os::Vms,
+        Unix | OsXDarwin => Os::Unix,
+        VmCms => Os::VmCms,
+        AtariSt => Os::AtariTos,
+        Os2Hpfs => Os::Hpfs,
+        Macintosh => Os::Macintosh,
+        ZSystem => Os::ZSystem,
+        Cpm => Os::CpM,
+        WindowsNtfs => Os::Ntfs,
+        AcornRisc => Os::AcornRiscos,
+        _ => Os::Unknown(255),
+    }
+}
+
+/// The central directory of an archive, unparsed, and where it is.
+pub(crate) struct CentralDir {
+    /// See `SeekArchive::archive_start`.
+    pub archive_start: u64,
+    pub end: CentralDirEnd,
+    /// Whether the archive has a Zip64 end of central directory record.
+    pub is_zip64: bool,
+    pub num_entries: u64,
+    /// The offset of the central directory, relative to `archive_start`.
+    pub offset: u64,
+    pub headers: Vec<u8>,
+}
+
+/// Finds the end of central directory record of the archive in `reader`,
+/// and reads the central directory that it points to.
+pub(crate) fn read_central_dir<R: Read + Seek>(reader: &mut R) -> Result<CentralDir, SeekError> {
+    let len = reader.seek(SeekFrom::End(0))?;
+    let tail_start = len.saturating_sub(headers::MAX_CENTRAL_DIR_END_LEN as u64);
+    let tail = read_at(reader, tail_start, len - tail_start)?;
+    let (end, end_pos) = CentralDirEnd::find(&tail).ok_or_else(|| {
+        // Where the record would be, if the archive had no comment.
+        let pos = tail
+            .len()
+            .saturating_sub(headers::CENTRAL_DIR_END_FIXED_SIZE);
+        let context = ErrorContext::new(headers::CENTRAL_DIR_END_TAG, &tail[pos..]);
+        ZipError::NotCentralDirEnd(context.shifted(tail_start + pos as u64))
+    })?;
+    let end_offset = tail_start + end_pos as u64;
+
+    let is_zip64 = end.central_dir_num_entries_total == u16::MAX
+        || end.central_dir_size == u32::MAX
+        || end.central_dir_start_offset == u32::MAX;
+    let (num_entries, central_dir_size, central_dir_offset, central_dir_end) = if is_zip64 {
+        let locator_offset = end_offset
+            .checked_sub(ZIP64_CENTRAL_DIR_END_LOCATOR_LEN)
+            .ok_or_else(|| {
+                let context = ErrorContext::new(headers::ZIP64_CENTRAL_DIR_END_LOCATOR_TAG, &[]);
+                ZipError::NotCentralDirEnd(context)
+            })?;
+        let locator = read_at(reader, locator_offset, ZIP64_CENTRAL_DIR_END_LOCATOR_LEN)?;
+        let (_, locator) = Zip64CentralDirEndLocator::parse(&locator).map_err(|_| {
+            let context = ErrorContext::new(headers::ZIP64_CENTRAL_DIR_END_LOCATOR_TAG, &locator);
+            ZipError::NotCentralDirEnd(context.shifted(locator_offset))
+        })?;
+        let zip64_end_offset = locator.central_dir_end_offset;
+        let zip64_end = read_at(
+            reader,
+            zip64_end_offset,
+            locator_offset.saturating_sub(zip64_end_offset),
+        )?;
+        let (_, zip64_end) = Zip64CentralDirEnd::parse(&zip64_end).map_err(|_| {
+            let context = ErrorContext::new(headers::ZIP64_CENTRAL_DIR_END_TAG, &zip64_end);
+            ZipError::NotCentralDirEnd(context.shifted(zip64_end_offset))
+        })?;
+        (
+            zip64_end.central_dir_num_entries_total,
+            zip64_end.central_dir_size,
+            zip64_end.central_dir_start_offset,
+            zip64_end_offset,
+        )
+    } else {
+        (
+            u64::from(end.central_dir_num_entries_total),
+            u64::from(end.central_dir_size),
+            u64::from(end.central_dir_start_offset),
+            end_offset,
+        )
+    };
+    let archive_start = central_dir_end
+        .checked_sub(central_dir_size)
+        .and_then(|central_dir_start| central_dir_start.checked_sub(central_dir_offset))
+        .ok_or(ZipError::InvalidCentralDirEnd)?;
+
+    let headers = read_at(reader, archive_start + central_dir_offset, central_dir_size)?;
+    Ok(CentralDir {
+        archive_start,
+        end,
+        is_zip64,
+        num_entries,
+        offset: central_dir_offset,
+        headers,
+    })
+}
+
+/// Lists the entries of `data`, a complete zip archive, by reading its central directory.
+/// The compressed data of the entries isn't read.
+pub fn list_entries(data: &[u8]) -> Result<Vec<CentralDirHeader>, ZipError> {
+    match SeekArchive::new(io::Cursor::new(data)) {
+        Ok(archive) => Ok(archive.entries),
+        Err(SeekError::Zip(e)) => Err(e),
+        // Reading from memory fails only if the end of central directory
+        // record points past the end of the archive.
+        Err(SeekError::Io(_)) => Err(ZipError::InvalidCentralDirEnd),
+        Err(SeekError::NoSuchEntry(_)) => unreachable!("no entry is read by index"),
+    }
+}
+
+/// Reads `len` bytes at `offset` of `reader`.
+fn read_at<R: Read + Seek>(reader: &mut R, offset: u64, len: u64) -> io::Result<Vec<u8>> {
+    let len = usize::try_from(len).map_err(|_| io::Error::from(io::ErrorKind::InvalidData))?;
+    let mut data = vec![0; len];
+    reader.seek(SeekFrom::Start(offset))?;
+    reader.read_exact(&mut data)?;
+    Ok(data)
+}
@@ -0,0 +1,650 @@
+//! Writing zip archives to an `io::Write` sink.
+//!
+//! Entries are either added whole with `Writer::add_entry`, or streamed with
+//! `Writer::start_entry` followed by writes to the `Writer` itself, in which case
+//! their CRC-32 and sizes follow the data in a data descriptor. Zip64 records
+//! are written where the sizes, the offsets or the number of entries
+//! don't fit in the classic fields.
+//!
+//! Entries can also be appended to an existing archive, starting from its
+//! `AppendPoint`: the new entries overwrite its central directory, which
+//! is then rewritten with the new entries added.
+//!
+//! The entries of other archives can be copied in with `Writer::copy_archive`
+//! without decompressing them, which `merge` uses to combine several archives,
+//! or with `Writer::recompress_archive`, which changes how they are compressed.
+
+use std::borrow::Cow;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::deflate;
+use crate::utils::update_crc32;
+
+use super::datetime::{msdos_date, msdos_time};
+use super::headers::{
+    CENTRAL_DIR_END_TAG, CENTRAL_DIR_HEADER_TAG, DATA_DESCRIPTOR_TAG, LOCAL_FILE_HEADER_TAG,
+    ZIP64_CENTRAL_DIR_END_LOCATOR_TAG, ZIP64_CENTRAL_DIR_END_TAG,
+};
+use super::seek::{read_central_dir, SeekError};
+
+mod recompress;
+
+pub use self::recompress::{merge, recompress, Recompression};
+
+const VERSION_DEFAULT: u16 = 20;
+const VERSION_ZIP64: u16 = 45;
+/// Entries are marked as made on Unix, because archivers decode
+/// the names of entries made on MS-DOS as code page 437.
+const HOST_UNIX: u16 = 3;
+const DEFAULT_FILE_MODE: u32 = 0o100644;
+const DEFAULT_DIR_MODE: u32 = 0o040755;
+const FLAG_DEFERRED_SIZES: u16 = 1 << 3;
+const FLAG_UTF8_NAME: u16 = 1 << 11;
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATED: u16 = 8;
+const ZIP64_EXTENDED_INFO_ID: u16 = 0x0001;
+const FAT_DIRECTORY: u32 = 0x10;
+/// The value of a 32-bit field whose value is in the Zip64 extended
+/// information extra field instead.
+const ZIP64_SATURATED: u32 = u32::MAX;
+/// Streamed entries with a smaller size hint get classic headers.
+/// The margin covers the overhead of Deflate on incompressible data.
+const ZIP64_SIZE_HINT_LIMIT: u64 = 0xF000_0000;
+
+/// Options for an entry written with `Writer`, set with the builder methods.
+#[derive(Debug, Clone)]
+pub struct EntryOptions {
+    compression_level: Option<u8>,
+    last_modified: SystemTime,
+    size_hint: Option<u64>,
+    unix_mode: Option<u32>,
+}
+
+impl Default for EntryOptions {
+    fn default() -> EntryOptions {
+        EntryOptions {
+            compression_level: Some(6),
+            last_modified: UNIX_EPOCH,
+            size_hint: None,
+            unix_mode: None,
+        }
+    }
+}
+
+impl EntryOptions {
+    pub fn new() -> EntryOptions {
+        EntryOptions::default()
+    }
+
+    /// The Deflate compression level, from 0 to 10, or `None` to store
+    /// the data uncompressed. The default is 6.
+    pub fn compression_level(mut self, level: Option<u8>) -> Self {
+        self.compression_level = level;
+        self
+    }
+
+    /// The modification time of the entry. MS-DOS times have a resolution
+    /// of two seconds and start from 1980, which is also the default.
+    pub fn last_modified(mut self, time: SystemTime) -> Self {
+        self.last_modified = time;
+        self
+    }
+
+    /// The expected size of a streamed entry. Streamed entries get Zip64
+    /// headers in case their data grows over 4 GiB, unless the hint shows
+    /// that it won't. Finishing an entry that outgrew its hint fails.
+    pub fn size_hint(mut self, size: u64) -> Self {
+        self.size_hint = Some(size);
+        self
+    }
+
+    /// The Unix mode of the entry, with the file type bits. The default is
+    /// `0o100644` for files, and `0o40755` for directories, whose names end with a slash.
+    pub fn unix_mode(mut self, mode: u32) -> Self {
+        self.unix_mode = Some(mode);
+        self
+    }
+}
+
+trait PutLe {
+    fn put_u16(&mut self, value: u16);
+    fn put_u32(&mut self, value: u32);
+    fn put_u64(&mut self, value: u64);
+}
+
+impl PutLe for Vec<u8> {
+    fn put_u16(&mut self, value: u16) {
+        self.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn put_u32(&mut self, value: u32) {
+        self.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn put_u64(&mut self, value: u64) {
+        self.extend_from_slice(&value.to_le_bytes());
+    }
+}
+
+/// A 32-bit field of a header, saturated if the value needs the Zip64 field.
+fn field_u32(value: u64) -> u32 {
+    if value >= u64::from(ZIP64_SATURATED) {
+        ZIP64_SATURATED
+    } else {
+        value as u32
+    }
+}
+
+fn needs_zip64(value: u64) -> bool {
+    value >= u64::from(ZIP64_SATURATED)
+}
+
+/// What the central directory header of an entry needs to know.
+#[derive(Debug)]
+struct Record {
+    name: Vec<u8>,
+    flags: u16,
+    method: u16,
+    time: u16,
+    date: u16,
+    crc_32: u32,
+    compressed_size: u64,
+    uncompressed_size: u64,
+    offset: u64,
+    unix_mode: Option<u32>,
+    /// Whether the local file header has the Zip64 extended information field,
+    /// which makes the data descriptor 64-bit too.
+    local_zip64: bool,
+}
+
+impl Record {
+    fn new(name: &str, options: &EntryOptions, offset: u64) -> io::Result<Record> {
+        if name.len() > usize::from(u16::MAX) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the name of the entry is too long",
+            ));
+        }
+        Ok(Record {
+            name: name.as_bytes().to_vec(),
+            flags: if name.is_ascii() { 0 } else { FLAG_UTF8_NAME },
+            method: match options.compression_level {
+                Some(_) => METHOD_DEFLATED,
+                None => METHOD_STORED,
+            },
+            time: msdos_time(options.last_modified),
+            date: msdos_date(options.last_modified),
+            crc_32: 0,
+            compressed_size: 0,
+            uncompressed_size: 0,
+            offset,
+            unix_mode: options.unix_mode,
+            local_zip64: false,
+        })
+    }
+
+    fn central_zip64(&self) -> bool {
+        needs_zip64(self.uncompressed_size)
+            || needs_zip64(self.compressed_size)
+            || needs_zip64(self.offset)
+    }
+
+    fn version_needed(&self) -> u16 {
+        if self.local_zip64 || self.central_zip64() {
+            VERSION_ZIP64
+        } else {
+            VERSION_DEFAULT
+        }
+    }
+
+    fn local_header(&self) -> Vec<u8> {
+        let mut extra = Vec::new();
+        let (compressed_size, uncompressed_size) = if self.local_zip64 {
+            // The local field has both sizes, even if only one of them is large.
+            extra.put_u16(ZIP64_EXTENDED_INFO_ID);
+            extra.put_u16(16);
+            extra.put_u64(self.uncompressed_size);
+            extra.put_u64(self.compressed_size);
+            (ZIP64_SATURATED, ZIP64_SATURATED)
+        } else {
+            (self.compressed_size as u32, self.uncompressed_size as u32)
+        };
+
+        let mut header = LOCAL_FILE_HEADER_TAG.to_vec();
+        header.put_u16(self.version_needed());
+        header.put_u16(self.flags);
+        header.put_u16(self.method);
+        header.put_u16(self.time);
+        header.put_u16(self.date);
+        header.put_u32(self.crc_32);
+        header.put_u32(compressed_size);
+        header.put_u32(uncompressed_size);
+        header.put_u16(self.name.len() as u16);
+        header.put_u16(extra.len() as u16);
+        header.extend_from_slice(&self.name);
+        header.extend_from_slice(&extra);
+        header
+    }
+
+    fn data_descriptor(&self) -> Vec<u8> {
+        let mut descriptor = DATA_DESCRIPTOR_TAG.to_vec();
+        descriptor.put_u32(self.crc_32);
+        if self.local_zip64 {
+            descriptor.put_u64(self.compressed_size);
+            descriptor.put_u64(self.uncompressed_size);
+        } else {
+            descriptor.put_u32(self.compressed_size as u32);
+            descriptor.put_u32(self.uncompressed_size as u32);
+        }
+        descriptor
+    }
+
+    fn central_dir_header(&self) -> Vec<u8> {
+        // Only the values that don't fit are in the field, in this order.
+        let mut zip64 = Vec::new();
+        for &value in &[self.uncompressed_size, self.compressed_size, self.offset] {
+            if needs_zip64(value) {
+                zip64.put_u64(value);
+            }
+        }
+        let mut extra = Vec::new();
+        if !zip64.is_empty() {
+            extra.put_u16(ZIP64_EXTENDED_INFO_ID);
+            extra.put_u16(zip64.len() as u16);
+            extra.extend_from_slice(&zip64);
+        }
+
+        let (mode, fat_attributes) = if self.name.ends_with(b"/") {
+            (self.unix_mode.unwrap_or(DEFAULT_DIR_MODE), FAT_DIRECTORY)
+        } else {
+            (self.unix_mode.unwrap_or(DEFAULT_FILE_MODE), 0)
+        };
+
+        let mut header = CENTRAL_DIR_HEADER_TAG.to_vec();
+        header.put_u16(HOST_UNIX << 8 | VERSION_ZIP64);
+        header.put_u16(self.version_needed());
+        header.put_u16(self.flags);
+        header.put_u16(self.method);
+        header.put_u16(self.time);
+        header.put_u16(self.date);
+        header.put_u32(self.crc_32);
+        header.put_u32(field_u32(self.compressed_size));
+        header.put_u32(field_u32(self.uncompressed_size));
+        header.put_u16(self.name.len() as u16);
+        header.put_u16(extra.len() as u16);
+        // The comment, the disk number and the internal attributes.
+        header.put_u16(0);
+        header.put_u16(0);
+        header.put_u16(0);
+        header.put_u32(mode << 16 | fat_attributes);
+        header.put_u32(field_u32(self.offset));
+        header.extend_from_slice(&self.name);
+        header.extend_from_slice(&extra);
+        header
+    }
+}
+
+/// The sink, with the count of the bytes written to it,
+/// which is the offset of the next header.
+struct Sink<W> {
+    inner: W,
+    offset: u64,
+}
+
+impl<W: Write> Sink<W> {
+    fn write_all(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.inner.write_all(bytes)?;
+        self.offset += bytes.len() as u64;
+        Ok(())
+    }
+}
+
+/// The end of a source, `data`, which starts at `start`. Reading
+/// before the start fails, as if the source were truncated.
+struct Tail<'a> {
+    data: &'a [u8],
+    start: u64,
+    pos: u64,
+}
+
+impl Read for Tail<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let pos = self
+            .pos
+            .checked_sub(self.start)
+            .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+        let data = self.data.get(pos as usize..).unwrap_or(&[]);
+        let len = std::cmp::min(data.len(), buf.len());
+        buf[..len].copy_from_slice(&data[..len]);
+        self.pos += len as u64;
+        Ok(len)
+    }
+}
+
+impl Seek for Tail<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(pos) => Some(pos),
+            SeekFrom::End(delta) => (self.start + self.data.len() as u64).checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.pos.checked_add_signed(delta),
+        };
+        self.pos = pos.ok_or_else(|| io::Error::from(io::ErrorKind::InvalidInput))?;
+        Ok(self.pos)
+    }
+}
+
+/// Where new entries are appended to an existing archive: the start of its
+/// central directory. The headers of the central directory are kept,
+/// so the entries that are already in the archive stay in it,
+/// and their data isn't touched.
+#[derive(Debug, Clone)]
+pub struct AppendPoint {
+    position: u64,
+    offset: u64,
+    num_entries: u64,
+    central_dir: Vec<u8>,
+    comment: Vec<u8>,
+    is_zip64: bool,
+}
+
+impl AppendPoint {
+    /// Finds the append point of the archive in `reader`.
+    pub fn find<R: Read + Seek>(reader: &mut R) -> Result<AppendPoint, SeekError> {
+        let central_dir = read_central_dir(reader)?;
+        Ok(AppendPoint {
+            position: central_dir.archive_start + central_dir.offset,
+            offset: central_dir.offset,
+            num_entries: central_dir.num_entries,
+            central_dir: central_dir.headers,
+            comment: central_dir.end.comment,
+            is_zip64: central_dir.is_zip64,
+        })
+    }
+
+    /// Finds the append point from `tail`, the end of an archive of `archive_len`
+    /// bytes, for sinks that can't be read back. The tail must reach back to
+    /// the start of the central directory and be at least
+    /// `headers::MAX_CENTRAL_DIR_END_LEN` bytes long, unless it's the whole archive,
+    /// or this fails with an `io::Error` of the kind `UnexpectedEof`.
+    pub fn from_tail(tail: &[u8], archive_len: u64) -> Result<AppendPoint, SeekError> {
+        let start = archive_len
+            .checked_sub(tail.len() as u64)
+            .ok_or_else(|| io::Error::from(io::ErrorKind::InvalidInput))?;
+        AppendPoint::find(&mut Tail {
+            data: tail,
+            start,
+            pos: start,
+        })
+    }
+
+    /// The position in the source where the new entries start.
+    /// The source may have to be truncated there before appending.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+}
+
+/// An entry that is being streamed.
+struct Streamed {
+    record: Record,
+    encoder: Option<deflate::Encoder>,
+}
+
+impl Streamed {
+    /// Writes `data` to `sink`, compressing it into `buffer` first if the entry is compressed.
+    fn write<W: Write>(
+        &mut self,
+        data: &[u8],
+        sink: &mut Sink<W>,
+        buffer: &mut Vec<u8>,
+    ) -> io::Result<()> {
+        let record = &mut self.record;
+        record.crc_32 = update_crc32(record.crc_32, data);
+        record.uncompressed_size += data.len() as u64;
+
+        match &mut self.encoder {
+            Some(encoder) => {
+                buffer.clear();
+                encoder
+                    .inner_iter(data, |out| buffer.extend_from_slice(out))
+                    .map_err(compression_error)?;
+                sink.write_all(buffer)?;
+                record.compressed_size += buffer.len() as u64;
+            }
+            None => {
+                sink.write_all(data)?;
+                record.compressed_size += data.len() as u64;
+            }
+        }
+        Ok(())
+    }
+
+    /// Ends the entry with its data descriptor, returning its record.
+    fn finish<W: Write>(self, sink: &mut Sink<W>, buffer: &mut Vec<u8>) -> io::Result<Record> {
+        let Streamed {
+            mut record,
+            encoder,
+        } = self;
+
+        if let Some(mut encoder) = encoder {
+            buffer.clear();
+            encoder
+                .finish_iter(|out| buffer.extend_from_slice(out))
+                .map_err(compression_error)?;
+            sink.write_all(buffer)?;
+            record.compressed_size += buffer.len() as u64;
+        }
+
+        if !record.local_zip64
+            && (needs_zip64(record.compressed_size) || needs_zip64(record.uncompressed_size))
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the entry is larger than its size hint",
+            ));
+        }
+        sink.write_all(&record.data_descriptor())?;
+        Ok(record)
+    }
+}
+
+fn compression_error(status: impl std::fmt::Debug) -> io::Error {
+    io::Error::other(format!("compression failed: {:?}", status))
+}
+
+/// Writes a zip archive to `W`. See the module documentation.
+pub struct Writer<W: Write> {
+    sink: Sink<W>,
+    records: Vec<Record>,
+    current: Option<Streamed>,
+    buffer: Vec<u8>,
+    /// The central directory headers of the entries of an archive
+    /// that is appended to, and their number.
+    central_dir: Vec<u8>,
+    num_entries: u64,
+    comment: Vec<u8>,
+    /// Whether the end of the central directory must have the Zip64 record.
+    is_zip64: bool,
+}
+
+impl<W: Write> Writer<W> {
+    pub fn new(inner: W) -> Writer<W> {
+        Writer {
+            sink: Sink { inner, offset: 0 },
+            records: Vec::new(),
+            current: None,
+            buffer: Vec::new(),
+            central_dir: Vec::new(),
+            num_entries: 0,
+            comment: Vec::new(),
+            is_zip64: false,
+        }
+    }
+
+    /// Appends entries to the archive of `point`. `inner` must continue
+    /// the source of the archive at `point.position()`. The archive comment
+    /// is kept, and so is the Zip64 end of central directory record if there's one,
+    /// so the archive doesn't get shorter, even if no entries are added.
+    pub fn append(inner: W, point: AppendPoint) -> Writer<W> {
+        Writer {
+            sink: Sink {
+                inner,
+                offset: point.offset,
+            },
+            records: Vec::new(),
+            current: None,
+            buffer: Vec::new(),
+            central_dir: point.central_dir,
+            num_entries: point.num_entries,
+            comment: point.comment,
+            is_zip64: point.is_zip64,
+        }
+    }
+
+    /// Adds an entry whose data is known up front, so its sizes
+    /// are in its local file header and it has no data descriptor.
+    pub fn add_entry(&mut self, name: &str, options: &EntryOptions, data: &[u8]) -> io::Result<()> {
+        self.finish_entry()?;
+
+        let mut record = Record::new(name, options, self.sink.offset)?;
+        let compressed = match options.compression_level {
+            Some(level) => {
+                let mut compressed = Vec::new();
+                let mut encoder = deflate::Encoder::with(0, level);
+                encoder
+                    .inner_iter(data, |out| compressed.extend_from_slice(out))
+                    .map_err(compression_error)?;
+                encoder
+                    .finish_iter(|out| compressed.extend_from_slice(out))
+                    .map_err(compression_error)?;
+                Cow::Owned(compressed)
+            }
+            None => Cow::Borrowed(data),
+        };
+        record.crc_32 = update_crc32(0, data);
+        record.compressed_size = compressed.len() as u64;
+        record.uncompressed_size = data.len() as u64;
+        record.local_zip64 =
+            needs_zip64(record.compressed_size) || needs_zip64(record.uncompressed_size);
+
+        self.sink.write_all(&record.local_header())?;
+        self.sink.write_all(&compressed)?;
+        self.records.push(record);
+        Ok(())
+    }
+
+    /// Starts an entry whose data is then written to the `Writer`.
+    /// An entry that is in progress is finished first.
+    pub fn start_entry(&mut self, name: &str, options: &EntryOptions) -> io::Result<()> {
+        self.finish_entry()?;
+
+        let mut record = Record::new(name, options, self.sink.offset)?;
+        record.flags |= FLAG_DEFERRED_SIZES;
+        record.local_zip64 = options
+            .size_hint
+            .is_none_or(|size| size >= ZIP64_SIZE_HINT_LIMIT);
+        self.sink.write_all(&record.local_header())?;
+        self.current = Some(Streamed {
+            record,
+            encoder: options
+                .compression_level
+                .map(|level| deflate::Encoder::with(0, level)),
+        });
+        Ok(())
+    }
+
+    /// Ends the entry that is in progress with its data descriptor.
+    /// Does nothing if no entry is in progress.
+    pub fn finish_entry(&mut self) -> io::Result<()> {
+        if let Some(streamed) = self.current.take() {
+            let record = streamed.finish(&mut self.sink, &mut self.buffer)?;
+            self.records.push(record);
+        }
+        Ok(())
+    }
+
+    /// Finishes the entry that is in progress and writes the central directory,
+    /// returning the sink.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.finish_entry()?;
+
+        let central_dir_offset = self.sink.offset;
+        self.sink.write_all(&self.central_dir)?;
+        for record in &self.records {
+            self.sink.write_all(&record.central_dir_header())?;
+        }
+        let central_dir_size = self.sink.offset - central_dir_offset;
+        let entries = self.num_entries + self.records.len() as u64;
+
+        let mut end = Vec::new();
+        if self.is_zip64
+            || entries >= u64::from(u16::MAX)
+            || needs_zip64(central_dir_size)
+            || needs_zip64(central_dir_offset)
+        {
+            let zip64_end_offset = self.sink.offset;
+            end.extend_from_slice(ZIP64_CENTRAL_DIR_END_TAG);
+            // The size of the rest of the record.
+            end.put_u64(44);
+            end.put_u16(VERSION_ZIP64);
+            end.put_u16(VERSION_ZIP64);
+            // This disk and the disk where the central directory starts.
+            end.put_u32(0);
+            end.put_u32(0);
+            end.put_u64(entries);
+            end.put_u64(entries);
+            end.put_u64(central_dir_size);
+            end.put_u64(central_dir_offset);
+
+            end.extend_from_slice(ZIP64_CENTRAL_DIR_END_LOCATOR_TAG);
+            end.put_u32(0);
+            end.put_u64(zip64_end_offset);
+            end.put_u32(1);
+        }
+
+        let entries = if entries >= u64::from(u16::MAX) {
+            u16::MAX
+        } else {
+            entries as u16
+        };
+        end.extend_from_slice(CENTRAL_DIR_END_TAG);
+        end.put_u16(0);
+        end.put_u16(0);
+        end.put_u16(entries);
+        end.put_u16(entries);
+        end.put_u32(field_u32(central_dir_size));
+        end.put_u32(field_u32(central_dir_offset));
+        end.put_u16(self.comment.len() as u16);
+        end.extend_from_slice(&self.comment);
+        self.sink.write_all(&end)?;
+
+        self.sink.inner.flush()?;
+        Ok(self.sink.inner)
+    }
+}
+
+impl<W: Read + Write + Seek> Writer<W> {
+    /// Appends entries to the archive in `inner`, which is both read
+    /// and written, such as a file opened for reading and writing.
+    pub fn append_seekable(mut inner: W) -> Result<Writer<W>, SeekError> {
+        let point = AppendPoint::find(&mut inner)?;
+        inner.seek(SeekFrom::Start(point.position()))?;
+        Ok(Writer::append(inner, point))
+    }
+}
+
+/// Writes the data of the entry that was started with `Writer::start_entry`.
+impl<W: Write> Write for Writer<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let streamed = self.current.as_mut().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "no entry is in progress")
+        })?;
+        streamed.write(data, &mut self.sink, &mut self.buffer)?;
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.sink.inner.flush()
+    }
+}
@@ -0,0 +1,480 @@
+//! Copying the entries of other archives into a `Writer` in one pass over them,
+//! either as they are, or decompressed and compressed again.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+use crate::deflate;
+use crate::ErrorContext;
+
+use super::super::decompressor::Decompressor;
+use super::super::headers::{
+    CentralDirHeader, DataDescriptor, LocalFileHeader, CENTRAL_DIR_END_TAG,
+    CENTRAL_DIR_HEADER_FIXED_SIZE, CENTRAL_DIR_HEADER_TAG, DATA_DESCRIPTOR_TAG,
+    DIGITAL_SIGNATURE_TAG, LOCAL_FILE_HEADER_FIXED_SIZE, LOCAL_FILE_HEADER_TAG,
+    ZIP64_CENTRAL_DIR_END_TAG,
+};
+use super::super::{Options, SeekError, ZipError};
+use super::{
+    field_u32, needs_zip64, PutLe, Record, Sink, Streamed, Writer, FLAG_DEFERRED_SIZES,
+    FLAG_UTF8_NAME, METHOD_DEFLATED, METHOD_STORED, VERSION_ZIP64, ZIP64_EXTENDED_INFO_ID,
+    ZIP64_SIZE_HINT_LIMIT,
+};
+
+const WINZIP_AES_ID: u16 = 0x9901;
+
+/// What `Writer::recompress_archive` does with the data of an entry.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Recompression {
+    /// Copies the data as it is, without decompressing it.
+    Keep,
+    /// Compresses the data with Deflate at the level, from 0 to 10.
+    Deflate(u8),
+    /// Stores the data uncompressed.
+    Store,
+}
+
+/// The central directory header `raw`, parsed as `header`, of an entry whose local
+/// file header is moved to `offset`. If the entry was re-encoded, the values that
+/// describe its data are taken from its `record`. The Zip64 extended information
+/// extra field is rewritten to have the values that don't fit the classic fields.
+fn rewrite_central_dir_header(
+    raw: &[u8],
+    header: &CentralDirHeader,
+    offset: u64,
+    record: Option<&Record>,
+) -> Result<Vec<u8>, ZipError> {
+    let (compressed_size, uncompressed_size) = match record {
+        Some(record) => (record.compressed_size, record.uncompressed_size),
+        None => header.sizes().ok_or(ZipError::InvalidCentralDirHeader)?,
+    };
+    let name_end = CENTRAL_DIR_HEADER_FIXED_SIZE + header.filename.len();
+    let extra_end = raw.len() - header.comment.len();
+
+    let mut zip64 = Vec::new();
+    for &value in &[uncompressed_size, compressed_size, offset] {
+        if needs_zip64(value) {
+            zip64.put_u64(value);
+        }
+    }
+    let mut extra = Vec::new();
+    if !zip64.is_empty() {
+        extra.put_u16(ZIP64_EXTENDED_INFO_ID);
+        extra.put_u16(zip64.len() as u16);
+        extra.extend_from_slice(&zip64);
+    }
+    let mut fields = &raw[name_end..extra_end];
+    while fields.len() >= 4 {
+        let id = u16::from_le_bytes([fields[0], fields[1]]);
+        let len = 4 + usize::from(u16::from_le_bytes([fields[2], fields[3]]));
+        let len = std::cmp::min(len, fields.len());
+        // Re-encoded entries aren't encrypted anymore.
+        let dropped = id == ZIP64_EXTENDED_INFO_ID || (record.is_some() && id == WINZIP_AES_ID);
+        if !dropped {
+            extra.extend_from_slice(&fields[..len]);
+        }
+        fields = &fields[len..];
+    }
+    if extra.len() > usize::from(u16::MAX) {
+        return Err(ZipError::InvalidCentralDirHeader);
+    }
+
+    let mut rewritten = raw[..CENTRAL_DIR_HEADER_FIXED_SIZE].to_vec();
+    let mut version_needed = header.version_needed;
+    if let Some(record) = record {
+        version_needed = record.version_needed();
+        rewritten[8..10].copy_from_slice(&record.flags.to_le_bytes());
+        rewritten[10..12].copy_from_slice(&record.method.to_le_bytes());
+        rewritten[16..20].copy_from_slice(&record.crc_32.to_le_bytes());
+    }
+    if !zip64.is_empty() {
+        version_needed = std::cmp::max(version_needed, VERSION_ZIP64);
+    }
+    rewritten[6..8].copy_from_slice(&version_needed.to_le_bytes());
+    rewritten[20..24].copy_from_slice(&field_u32(compressed_size).to_le_bytes());
+    rewritten[24..28].copy_from_slice(&field_u32(uncompressed_size).to_le_bytes());
+    rewritten[30..32].copy_from_slice(&(extra.len() as u16).to_le_bytes());
+    // The disk number, as the new archive is on a single disk.
+    rewritten[34..36].copy_from_slice(&[0, 0]);
+    rewritten[42..46].copy_from_slice(&field_u32(offset).to_le_bytes());
+    rewritten.extend_from_slice(&raw[CENTRAL_DIR_HEADER_FIXED_SIZE..name_end]);
+    rewritten.extend_from_slice(&extra);
+    rewritten.extend_from_slice(&header.comment);
+    Ok(rewritten)
+}
+
+fn parse_error(err: nom::Err<ZipError>) -> SeekError {
+    match err {
+        nom::Err::Error(e) | nom::Err::Failure(e) => e.into(),
+        nom::Err::Incomplete(_) => io::Error::from(io::ErrorKind::UnexpectedEof).into(),
+    }
+}
+
+/// An archive that is copied into a `Writer`, with the count of the bytes
+/// read from it, which is the offset of the next header.
+struct Source<R> {
+    inner: BufReader<R>,
+    offset: u64,
+}
+
+impl<R: Read> Source<R> {
+    /// Reads `len` more bytes to the end of `buf`.
+    fn read_to(&mut self, buf: &mut Vec<u8>, len: u64) -> io::Result<()> {
+        let read = (&mut self.inner).take(len).read_to_end(buf)?;
+        self.offset += read as u64;
+        if (read as u64) < len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(())
+    }
+
+    /// Reads a header that has a fixed-size part of `fixed_len` bytes,
+    /// the first four of which are already in `buf`, and that `parse_fixed` parses
+    /// to the length of the variable-length fields that follow.
+    fn read_header(
+        &mut self,
+        buf: &mut Vec<u8>,
+        fixed_len: usize,
+        parse_fixed: impl Fn(&[u8]) -> Result<u64, nom::Err<ZipError>>,
+    ) -> Result<(), SeekError> {
+        self.read_to(buf, (fixed_len - buf.len()) as u64)?;
+        let variable_len = parse_fixed(buf).map_err(parse_error)?;
+        self.read_to(buf, variable_len)?;
+        Ok(())
+    }
+
+    /// Reads the data descriptor after the data of the entry of `header`,
+    /// returning it both as it is in the source and parsed.
+    fn read_descriptor(
+        &mut self,
+        header: &LocalFileHeader,
+    ) -> Result<(Vec<u8>, DataDescriptor), SeekError> {
+        // The signature of the data descriptor is optional.
+        let mut raw = Vec::new();
+        self.read_to(&mut raw, 4)?;
+        let len = if header.is_zip64 { 16 } else { 8 };
+        if raw == DATA_DESCRIPTOR_TAG {
+            self.read_to(&mut raw, len + 4)?;
+        } else {
+            self.read_to(&mut raw, len)?;
+        }
+        let parsed = if header.is_zip64 {
+            DataDescriptor::parse_zip64(&raw)
+        } else {
+            DataDescriptor::parse_zip(&raw)
+        };
+        let (_, descriptor) = parsed.map_err(parse_error)?;
+        Ok((raw, descriptor))
+    }
+
+    fn copy_to<W: Write>(&mut self, len: u64, sink: &mut Sink<W>) -> io::Result<()> {
+        let mut left = len;
+        while left > 0 {
+            let input = self.inner.fill_buf()?;
+            if input.is_empty() {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            let len = std::cmp::min(left, input.len() as u64) as usize;
+            sink.write_all(&input[..len])?;
+            self.inner.consume(len);
+            self.offset += len as u64;
+            left -= len as u64;
+        }
+        Ok(())
+    }
+
+    /// Feeds the data of an entry to `decompressor` until it stops, passing the input
+    /// it consumes to `on_input` and its output to `on_output`. The input is limited
+    /// to `compressed_size` if it's known, and the data must end there.
+    fn decompress(
+        &mut self,
+        decompressor: &mut Decompressor,
+        compressed_size: Option<u64>,
+        mut on_input: impl FnMut(&[u8]) -> io::Result<()>,
+        mut on_output: impl FnMut(&[u8]) -> io::Result<()>,
+    ) -> Result<(), SeekError> {
+        let mut remaining = compressed_size;
+        loop {
+            let buf = self.inner.fill_buf()?;
+            let len = match remaining {
+                Some(remaining) => std::cmp::min(remaining, buf.len() as u64) as usize,
+                None => buf.len(),
+            };
+            let input = &buf[..len];
+            let (unparsed_len, done) = match decompressor.feed_input(input)? {
+                deflate::State::HasOutput {
+                    unparsed_input,
+                    output,
+                } => {
+                    on_output(output)?;
+                    (unparsed_input.len(), false)
+                }
+                deflate::State::NeedsInput { .. } if input.is_empty() => {
+                    return Err(match remaining {
+                        Some(0) => ZipError::EntrySizeMismatch.into(),
+                        _ => io::Error::from(io::ErrorKind::UnexpectedEof).into(),
+                    });
+                }
+                deflate::State::NeedsInput { unparsed_input } => (unparsed_input.len(), false),
+                deflate::State::Stop { unparsed_input } => (unparsed_input.len(), true),
+            };
+            let consumed = len - unparsed_len;
+            on_input(&input[..consumed])?;
+            self.inner.consume(consumed);
+            self.offset += consumed as u64;
+            remaining = remaining.map(|remaining| remaining - consumed as u64);
+            if done {
+                break;
+            }
+        }
+        if remaining.is_some_and(|remaining| remaining > 0) {
+            return Err(ZipError::EntrySizeMismatch.into());
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Writer<W> {
+    /// Copies the entries of the archive read from `source`, without decompressing
+    /// them, and returns their number. An entry that is in progress is finished first.
+    ///
+    /// The archive is read in one pass: the local file headers and the data of
+    /// the entries are copied as they are, and the central directory headers
+    /// are copied with the new offsets of the entries. Entries whose sizes are
+    /// in a data descriptor are decompressed to find the end of their data,
+    /// so they can't be encrypted, or this fails with `ZipError::UnknownEntrySize`.
+    /// Entries that aren't in the central directory are copied but not listed
+    /// in the new one, as in the source. The source must start with the first
+    /// entry, and its comment is dropped.
+    ///
+    /// The names of the entries aren't checked against the other entries,
+    /// so copying archives that have the same names results in duplicate entries.
+    pub fn copy_archive<R: Read>(&mut self, source: R) -> Result<u64, SeekError> {
+        self.recompress_archive(source, &Options::default(), |_| Recompression::Keep)
+    }
+
+    /// Copies the entries of the archive read from `source` like `copy_archive`,
+    /// but the data of each entry is copied or compressed again as `policy`
+    /// decides from its local file header.
+    ///
+    /// The entries that are compressed again are decompressed according to `options`,
+    /// so encrypted entries need its password, and are written unencrypted. Their
+    /// CRC-32s are verified, unless `options` disables that, and their sizes follow
+    /// their data in a data descriptor. Their central directory headers keep
+    /// the attributes, the comments and the extra fields of the source. Entries
+    /// that are kept whose sizes are in a data descriptor are also decompressed
+    /// according to `options`, to find the end of their data.
+    pub fn recompress_archive<R: Read>(
+        &mut self,
+        source: R,
+        options: &Options,
+        mut policy: impl FnMut(&LocalFileHeader) -> Recompression,
+    ) -> Result<u64, SeekError> {
+        self.finish_entry()?;
+        // The central directory headers of the copied entries follow those of
+        // the entries that were written so far, keeping them in the order of the data.
+        for record in self.records.drain(..) {
+            self.central_dir
+                .extend_from_slice(&record.central_dir_header());
+            self.num_entries += 1;
+        }
+
+        let mut source = Source {
+            inner: BufReader::new(source),
+            offset: 0,
+        };
+        // The entries by the offsets of their local file headers in the source:
+        // their offsets in the sink, and the records of those that were re-encoded.
+        let mut entries = HashMap::new();
+        let mut copied = 0;
+        loop {
+            let source_offset = source.offset;
+            let mut header = Vec::new();
+            source.read_to(&mut header, 4)?;
+            if header == LOCAL_FILE_HEADER_TAG {
+                source.read_header(&mut header, LOCAL_FILE_HEADER_FIXED_SIZE, |fixed| {
+                    LocalFileHeader::parse_fixed(fixed).map(|(_, (_, len))| len)
+                })?;
+                let (_, local) = LocalFileHeader::parse(&header).map_err(parse_error)?;
+                let offset = self.sink.offset;
+                let record = match policy(&local) {
+                    Recompression::Keep => {
+                        self.copy_entry(&mut source, &header, &local, options)?;
+                        None
+                    }
+                    Recompression::Deflate(level) => Some(self.reencode_entry(
+                        &mut source,
+                        &header,
+                        &local,
+                        options,
+                        Some(level),
+                    )?),
+                    Recompression::Store => {
+                        Some(self.reencode_entry(&mut source, &header, &local, options, None)?)
+                    }
+                };
+                entries.insert(source_offset, (offset, record));
+            } else if header == CENTRAL_DIR_HEADER_TAG {
+                source.read_header(&mut header, CENTRAL_DIR_HEADER_FIXED_SIZE, |fixed| {
+                    CentralDirHeader::parse_fixed(fixed).map(|(_, (_, len))| len)
+                })?;
+                let (_, central) = CentralDirHeader::parse(&header).map_err(parse_error)?;
+                let (offset, record) = central
+                    .local_header_offset()
+                    .and_then(|offset| entries.remove(&offset))
+                    .ok_or(ZipError::InvalidCentralDirHeader)?;
+                self.central_dir
+                    .extend_from_slice(&rewrite_central_dir_header(
+                        &header,
+                        &central,
+                        offset,
+                        record.as_ref(),
+                    )?);
+                self.num_entries += 1;
+                copied += 1;
+            } else if header == DIGITAL_SIGNATURE_TAG
+                || header == ZIP64_CENTRAL_DIR_END_TAG
+                || header == CENTRAL_DIR_END_TAG
+            {
+                // The rest of the source describes only its own central directory.
+                return Ok(copied);
+            } else {
+                let context = ErrorContext::new(LOCAL_FILE_HEADER_TAG, &header);
+                return Err(ZipError::NotLocalFileHeader(context.shifted(source_offset)).into());
+            }
+        }
+    }
+
+    /// Copies the local file header `raw_header`, parsed as `header`, and the data
+    /// of its entry as they are, and the data descriptor if the entry has one.
+    fn copy_entry<R: Read>(
+        &mut self,
+        source: &mut Source<R>,
+        raw_header: &[u8],
+        header: &LocalFileHeader,
+        options: &Options,
+    ) -> Result<(), SeekError> {
+        self.sink.write_all(raw_header)?;
+        if !header.deferred_sizes {
+            let (compressed_size, _) = header
+                .sizes()
+                .ok_or(ZipError::InvalidLocalFileHeaderExtraFields)?;
+            source.copy_to(compressed_size, &mut self.sink)?;
+            return Ok(());
+        }
+
+        let mut decompressor =
+            Decompressor::for_header(header, options).map_err(|_| ZipError::UnknownEntrySize)?;
+        let sink = &mut self.sink;
+        source.decompress(
+            &mut decompressor,
+            None,
+            |input| sink.write_all(input),
+            |_| Ok(()),
+        )?;
+        // Stored data without sizes is found to end where its data descriptor
+        // starts, so the descriptor was consumed with it.
+        if decompressor.descriptor().is_none() {
+            let (descriptor, _) = source.read_descriptor(header)?;
+            self.sink.write_all(&descriptor)?;
+        }
+        Ok(())
+    }
+
+    /// Decompresses the data of the entry of `header`, whose local file header is
+    /// `raw_header`, and compresses it again at `level`, or stores it if that's `None`.
+    fn reencode_entry<R: Read>(
+        &mut self,
+        source: &mut Source<R>,
+        raw_header: &[u8],
+        header: &LocalFileHeader,
+        options: &Options,
+        level: Option<u8>,
+    ) -> Result<Record, SeekError> {
+        let sizes = header.sizes();
+        let mut streamed = Streamed {
+            record: Record {
+                name: header.filename.clone(),
+                flags: if header.utf8_name {
+                    FLAG_DEFERRED_SIZES | FLAG_UTF8_NAME
+                } else {
+                    FLAG_DEFERRED_SIZES
+                },
+                method: match level {
+                    Some(_) => METHOD_DEFLATED,
+                    None => METHOD_STORED,
+                },
+                time: u16::from_le_bytes([raw_header[10], raw_header[11]]),
+                date: u16::from_le_bytes([raw_header[12], raw_header[13]]),
+                crc_32: 0,
+                compressed_size: 0,
+                uncompressed_size: 0,
+                offset: self.sink.offset,
+                unix_mode: None,
+                local_zip64: sizes.is_none_or(|(_, size)| size >= ZIP64_SIZE_HINT_LIMIT),
+            },
+            encoder: level.map(|level| deflate::Encoder::with(0, level)),
+        };
+        self.sink.write_all(&streamed.record.local_header())?;
+
+        let expected_crc = match sizes {
+            // Like in the stream, entries without data, such as directories,
+            // are empty whatever their compression method.
+            Some((0, _)) => header.crc_32,
+            _ => {
+                let mut decompressor = Decompressor::for_header(header, options)?;
+                let (sink, buffer) = (&mut self.sink, &mut self.buffer);
+                source.decompress(
+                    &mut decompressor,
+                    sizes.map(|(compressed_size, _)| compressed_size),
+                    |_| Ok(()),
+                    |output| streamed.write(output, sink, buffer),
+                )?;
+                if !header.deferred_sizes {
+                    header.crc_32
+                } else if let Some(descriptor) = decompressor.descriptor() {
+                    descriptor.crc_32
+                } else {
+                    source.read_descriptor(header)?.1.crc_32
+                }
+            }
+        };
+        let actual = streamed.record.crc_32;
+        if options.verifies_checksum(header) && actual != expected_crc {
+            return Err(ZipError::ChecksumMismatch {
+                expected: expected_crc,
+                actual,
+            }
+            .into());
+        }
+        Ok(streamed.finish(&mut self.sink, &mut self.buffer)?)
+    }
+}
+
+/// Merges the archives read from `sources` into a single archive written to
+/// `sink`, copying their entries without decompressing them, and returns the sink.
+/// See `Writer::copy_archive`.
+pub fn merge<R: Read, W: Write>(
+    sources: impl IntoIterator<Item = R>,
+    sink: W,
+) -> Result<W, SeekError> {
+    let mut writer = Writer::new(sink);
+    for source in sources {
+        writer.copy_archive(source)?;
+    }
+    Ok(writer.finish()?)
+}
+
+/// Copies the archive read from `source` to `sink`, compressing the data
+/// of each entry again as `policy` decides, and returns the sink.
+/// See `Writer::recompress_archive`.
+pub fn recompress<R: Read, W: Write>(
+    source: R,
+    sink: W,
+    options: &Options,
+    policy: impl FnMut(&LocalFileHeader) -> Recompression,
+) -> Result<W, SeekError> {
+    let mut writer = Writer::new(sink);
+    writer.recompress_archive(source, options, policy)?;
+    Ok(writer.finish()?)
+}
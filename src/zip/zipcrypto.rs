@@ -0,0 +1,109 @@
+//! Decryption of the traditional PKWARE encryption, also known as ZipCrypto.
+//!
+//! The data of an encrypted entry starts with a 12-byte encryption header
+//! that randomizes the keys. The last byte of the decrypted header is
+//! a check byte that tells a wrong password apart in 255 cases out of 256.
+
+use crate::utils::crc32_step;
+
+use super::datetime::msdos_time;
+use super::headers::LocalFileHeader;
+use super::ZipError;
+
+/// The size of the encryption header, which the compressed size includes.
+pub const HEADER_SIZE: usize = 12;
+
+#[derive(Debug, Copy, Clone)]
+struct Keys {
+    key0: u32,
+    key1: u32,
+    key2: u32,
+}
+
+impl Keys {
+    fn new(password: &[u8]) -> Keys {
+        let mut keys = Keys {
+            key0: 0x1234_5678,
+            key1: 0x2345_6789,
+            key2: 0x3456_7890,
+        };
+        for &byte in password {
+            keys.update(byte);
+        }
+        keys
+    }
+
+    fn update(&mut self, plain: u8) {
+        self.key0 = crc32_step(self.key0, plain);
+        self.key1 = (self.key1.wrapping_add(self.key0 & 0xff))
+            .wrapping_mul(134_775_813)
+            .wrapping_add(1);
+        self.key2 = crc32_step(self.key2, (self.key1 >> 24) as u8);
+    }
+
+    fn decrypt(&mut self, cipher: u8) -> u8 {
+        let temp = (self.key2 | 2) & 0xffff;
+        let plain = cipher ^ (temp.wrapping_mul(temp ^ 1) >> 8) as u8;
+        self.update(plain);
+        plain
+    }
+}
+
+/// Decrypts the encryption header and then the data of an entry.
+#[derive(Debug, Clone)]
+pub struct Decryptor {
+    keys: Keys,
+    header_read: usize,
+    check_byte: u8,
+    /// Writers that defer the CRC-32 to the data descriptor check
+    /// the high byte of the modification time instead.
+    time_check_byte: Option<u8>,
+}
+
+impl Decryptor {
+    pub fn new(password: &[u8], header: &LocalFileHeader) -> Decryptor {
+        Decryptor {
+            keys: Keys::new(password),
+            header_read: 0,
+            check_byte: (header.crc_32 >> 24) as u8,
+            time_check_byte: if header.deferred_sizes {
+                Some((msdos_time(header.last_mod) >> 8) as u8)
+            } else {
+                None
+            },
+        }
+    }
+
+    pub fn header_done(&self) -> bool {
+        self.header_read == HEADER_SIZE
+    }
+
+    /// Decrypts as much of the encryption header from the start of `input` as there is.
+    /// Returns the number of bytes consumed, or an error if the check byte doesn't match.
+    pub fn read_header(&mut self, input: &[u8]) -> Result<usize, ZipError> {
+        let len = std::cmp::min(HEADER_SIZE - self.header_read, input.len());
+        for &byte in &input[..len] {
+            let plain = self.keys.decrypt(byte);
+            self.header_read += 1;
+            if self.header_done() && plain != self.check_byte && Some(plain) != self.time_check_byte
+            {
+                return Err(ZipError::InvalidPassword);
+            }
+        }
+        Ok(len)
+    }
+
+    /// Decrypts `input` to `output` without advancing the keys,
+    /// so that the ciphertext that isn't used can be decrypted again later.
+    pub fn peek_decrypt(&self, input: &[u8], output: &mut Vec<u8>) {
+        let mut keys = self.keys;
+        output.extend(input.iter().map(|&byte| keys.decrypt(byte)));
+    }
+
+    /// Advances the keys over the plaintext that was used.
+    pub fn advance(&mut self, plain: &[u8]) {
+        for &byte in plain {
+            self.keys.update(byte);
+        }
+    }
+}
@@ -0,0 +1,372 @@
+//! Streaming decompression of the zlib format (RFC 1950):
+//! a deflate stream wrapped between a two-byte header and
+//! an Adler-32 checksum of the uncompressed data.
+//!
+//! A zlib stream holds a single file and has no name.
+//! Any input after the checksum is ignored.
+
+use deflate;
+
+use std;
+
+use nom;
+
+use zlib::headers::StreamHeader;
+use State;
+
+use crate::input_helper::{Input, InputHandler};
+use crate::{RatioLimit, SizeLimits};
+
+pub mod headers;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum ZlibError {
+    InvalidHeader,
+    /// The stream needs a preset dictionary, which isn't supported.
+    PresetDictionary,
+    InvalidDeflateStream,
+    InvalidFooter,
+    /// The Adler-32 checksum didn't match the uncompressed data.
+    ChecksumMismatch,
+    /// The data decompresses to more than `Options::max_size`.
+    StreamTooLarge,
+    /// The data decompresses to more than `Options::max_compression_ratio`
+    /// times its compressed size.
+    CompressionRatioExceeded,
+}
+
+impl std::error::Error for ZlibError {
+    fn description(&self) -> &str {
+        "zlib uncompressing error"
+    }
+}
+
+impl std::fmt::Display for ZlibError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        use self::ZlibError::*;
+        match self {
+            InvalidHeader => write!(f, "invalid header"),
+            PresetDictionary => write!(f, "preset dictionaries aren't supported"),
+            InvalidDeflateStream => write!(f, "invalid deflate stream"),
+            InvalidFooter => write!(f, "invalid footer"),
+            ChecksumMismatch => write!(f, "Adler-32 checksum mismatch"),
+            StreamTooLarge => write!(f, "stream exceeds the size limit"),
+            CompressionRatioExceeded => write!(f, "stream exceeds the compression ratio limit"),
+        }
+    }
+}
+
+/// Options for parsing a zlib stream, set with the builder methods.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Options {
+    pub(crate) limits: SizeLimits,
+}
+
+impl Options {
+    /// Limits the decompressed size of the stream to `size` bytes. A stream that
+    /// decompresses to more fails with `ZlibError::StreamTooLarge` as soon as
+    /// the output that exceeds the limit has been decompressed, before it's returned.
+    pub fn max_size(mut self, size: u64) -> Self {
+        self.limits.max_size = Some(size);
+        self
+    }
+
+    /// Limits the ratio of the decompressed size of the stream to its compressed size.
+    /// Once it has decompressed to more than `min_size` bytes, the stream fails
+    /// with `ZlibError::CompressionRatioExceeded` if its output so far is more than
+    /// `ratio` times the compressed data that produced it.
+    pub fn max_compression_ratio(mut self, ratio: u64, min_size: u64) -> Self {
+        self.limits.max_compression_ratio = Some(RatioLimit { ratio, min_size });
+        self
+    }
+}
+
+pub struct ZlibFile {
+    state: InternalState,
+    unparsed: Vec<u8>,
+    inflater: deflate::Stream,
+    checksum: Adler32,
+    options: Options,
+}
+
+impl std::fmt::Debug for ZlibFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("ZlibFile")
+            .field("state", &self.state)
+            .field("unparsed", &self.unparsed)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum InternalState {
+    Init,
+    HeaderParsed(StreamHeader),
+    Inflated(StreamHeader),
+    Eof,
+    Sentinel,
+    /// The stream failed with the error, which later reads return again.
+    Error(ZlibError),
+}
+
+#[derive(Debug)]
+enum ParseResult {
+    Continue,
+    NeedsInput,
+    Output,
+    Error(ZlibError),
+    EndOfFile,
+}
+
+impl ZlibFile {
+    pub fn get_output(&self) -> &[u8] {
+        self.inflater.get_output()
+    }
+
+    /// The header of the stream, once it has been parsed.
+    pub fn header(&self) -> Option<&StreamHeader> {
+        match &self.state {
+            InternalState::HeaderParsed(header) | InternalState::Inflated(header) => Some(header),
+            _ => None,
+        }
+    }
+
+    pub fn read<'i, 's>(
+        &'s mut self,
+        input: &'i [u8],
+    ) -> Result<State<'i, 's, ZlibFile>, ZlibError> {
+        let mut ihandler = InputHandler::take_storage(&mut self.unparsed, input);
+        let mut unparsed = ihandler.get_unparsed();
+
+        loop {
+            let mut state = InternalState::Sentinel;
+            std::mem::swap(&mut self.state, &mut state);
+            let (bytes_consumed, new_state, res) = self.parse_step(state, unparsed);
+            unparsed = ihandler.consumed(bytes_consumed);
+            self.state = new_state;
+            match res {
+                ParseResult::Continue => (),
+                ParseResult::NeedsInput => {
+                    let extended_len = ihandler.extend_input();
+                    // Nothing in input left to extend, so we need the user to provide more
+                    if extended_len == 0 {
+                        ihandler.return_storage(&mut self.unparsed);
+                        return Ok(State::NeedsInput);
+                    }
+                    unparsed = ihandler.get_unparsed();
+                }
+                ParseResult::Output => {
+                    let unparsed_input = ihandler.take_rest(&mut self.unparsed);
+                    return Ok(State::HasOutput {
+                        unparsed_input,
+                        output: self.inflater.get_output(),
+                    });
+                }
+                ParseResult::EndOfFile => return Ok(State::EndOfFile),
+                ParseResult::Error(err) => return Err(err),
+            };
+            if unparsed.is_empty() {
+                return Ok(State::NeedsInput);
+            }
+        }
+    }
+
+    fn parse_step<'long, 'short>(
+        &'short mut self,
+        state: InternalState,
+        input: Input<'long, 'short>,
+    ) -> (usize, InternalState, ParseResult) {
+        match state {
+            InternalState::Init => ZlibFile::parse_header(input),
+            InternalState::HeaderParsed(header) => self.inflate(input, header),
+            InternalState::Inflated(header) => self.parse_footer(input, header),
+            InternalState::Eof => (0, InternalState::Eof, ParseResult::EndOfFile),
+            InternalState::Sentinel => unreachable!("parse_step is never called with Sentinel"),
+            InternalState::Error(error) => {
+                (0, InternalState::Error(error), ParseResult::Error(error))
+            }
+        }
+    }
+
+    fn parse_header<'long, 'short>(
+        input: Input<'long, 'short>,
+    ) -> (usize, InternalState, ParseResult) {
+        match StreamHeader::parse(*input) {
+            Ok((_, ref header)) if header.preset_dictionary.is_some() => (
+                0,
+                InternalState::Error(ZlibError::PresetDictionary),
+                ParseResult::Error(ZlibError::PresetDictionary),
+            ),
+            Ok((unparsed, header)) => {
+                let consumed = input.len() - unparsed.len();
+                (
+                    consumed,
+                    InternalState::HeaderParsed(header),
+                    ParseResult::Continue,
+                )
+            }
+            Err(nom::Err::Incomplete(_need)) => (0, InternalState::Init, ParseResult::NeedsInput),
+            Err(nom::Err::Error(_e)) | Err(nom::Err::Failure(_e)) => (
+                0,
+                InternalState::Error(ZlibError::InvalidHeader),
+                ParseResult::Error(ZlibError::InvalidHeader),
+            ),
+        }
+    }
+
+    fn inflate<'long, 'short>(
+        &mut self,
+        input: Input<'long, 'short>,
+        header: StreamHeader,
+    ) -> (usize, InternalState, ParseResult) {
+        match self.inflater.feed_input(*input) {
+            Ok(deflate::State::NeedsInput { unparsed_input }) => (
+                input.len() - unparsed_input.len(),
+                InternalState::HeaderParsed(header),
+                ParseResult::Continue,
+            ),
+            Ok(deflate::State::HasOutput {
+                unparsed_input,
+                output,
+            }) => {
+                self.checksum.update(output);
+                let consumed_bytes = input.len() - unparsed_input.len();
+                if self
+                    .options
+                    .limits
+                    .exceeds_size(self.inflater.uncompressed_size())
+                {
+                    return (
+                        consumed_bytes,
+                        InternalState::Error(ZlibError::StreamTooLarge),
+                        ParseResult::Error(ZlibError::StreamTooLarge),
+                    );
+                }
+                if self.options.limits.exceeds_ratio(
+                    self.inflater.compressed_size(),
+                    self.inflater.uncompressed_size(),
+                ) {
+                    return (
+                        consumed_bytes,
+                        InternalState::Error(ZlibError::CompressionRatioExceeded),
+                        ParseResult::Error(ZlibError::CompressionRatioExceeded),
+                    );
+                }
+                (
+                    consumed_bytes,
+                    InternalState::HeaderParsed(header),
+                    ParseResult::Output,
+                )
+            }
+            Ok(deflate::State::Stop { unparsed_input }) => (
+                input.len() - unparsed_input.len(),
+                InternalState::Inflated(header),
+                ParseResult::Continue,
+            ),
+            Err(_) => (
+                0,
+                InternalState::Error(ZlibError::InvalidDeflateStream),
+                ParseResult::Error(ZlibError::InvalidDeflateStream),
+            ),
+        }
+    }
+
+    fn parse_footer<'long, 'short>(
+        &self,
+        input: Input<'long, 'short>,
+        header: StreamHeader,
+    ) -> (usize, InternalState, ParseResult) {
+        match headers::parse_footer(*input) {
+            Ok((unparsed, checksum)) => {
+                if checksum != self.checksum.value() {
+                    return (
+                        0,
+                        InternalState::Error(ZlibError::ChecksumMismatch),
+                        ParseResult::Error(ZlibError::ChecksumMismatch),
+                    );
+                }
+                let consumed = input.len() - unparsed.len();
+                (consumed, InternalState::Eof, ParseResult::EndOfFile)
+            }
+            Err(nom::Err::Incomplete(_need)) => {
+                (0, InternalState::Inflated(header), ParseResult::NeedsInput)
+            }
+            Err(nom::Err::Error(_e)) | Err(nom::Err::Failure(_e)) => (
+                0,
+                InternalState::Error(ZlibError::InvalidFooter),
+                ParseResult::Error(ZlibError::InvalidFooter),
+            ),
+        }
+    }
+
+    pub fn read_with<'i>(
+        &mut self,
+        mut input: &'i [u8],
+        mut callback: impl FnMut(&[u8]),
+    ) -> Result<crate::State<'i, 'i, ZlibFile>, ZlibError> {
+        loop {
+            match self.read(input)?.try_no_output() {
+                Err((unparsed_input, output)) => {
+                    input = unparsed_input;
+                    callback(output);
+                }
+                Ok(state) => return Ok(state),
+            }
+        }
+    }
+}
+
+/// The running Adler-32 checksum of the uncompressed data.
+struct Adler32 {
+    a: u32,
+    b: u32,
+}
+
+impl Adler32 {
+    const MODULUS: u32 = 65521;
+    /// The number of bytes that can be summed before `b` may overflow a `u32`.
+    const CHUNK_SIZE: usize = 5552;
+
+    fn new() -> Adler32 {
+        Adler32 { a: 1, b: 0 }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        for chunk in data.chunks(Self::CHUNK_SIZE) {
+            for &byte in chunk {
+                self.a += u32::from(byte);
+                self.b += self.a;
+            }
+            self.a %= Self::MODULUS;
+            self.b %= Self::MODULUS;
+        }
+    }
+
+    fn value(&self) -> u32 {
+        self.b << 16 | self.a
+    }
+}
+
+/// Starts a zlib stream.
+pub fn start_stream() -> ZlibFile {
+    start_stream_with(Options::default())
+}
+
+/// Starts a zlib stream that is parsed according to `options`.
+pub fn start_stream_with(options: Options) -> ZlibFile {
+    ZlibFile {
+        state: InternalState::Init,
+        unparsed: Vec::new(),
+        inflater: deflate::Stream::new(),
+        checksum: Adler32::new(),
+        options,
+    }
+}
+
+/// Starts a stream whose first bytes, `prefix`, were already read from the input
+/// to detect the format. They are parsed together with the input of the next `read`.
+pub(crate) fn start_stream_with_options_and_prefix(options: Options, prefix: &[u8]) -> ZlibFile {
+    let mut file = start_stream_with(options);
+    file.unparsed = prefix.to_vec();
+    file
+}
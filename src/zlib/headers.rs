@@ -0,0 +1,58 @@
+use nom::combinator::verify;
+use nom::number::streaming::{be_u32, be_u8};
+use nom::sequence::pair;
+use nom::IResult;
+
+/// The deflate compression method, the only one defined for zlib.
+const CM_DEFLATE: u8 = 8;
+/// The largest window size, as a base-2 logarithm minus 8.
+const MAX_CINFO: u8 = 7;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct StreamHeader {
+    /// The base-2 logarithm of the window size used by the compressor.
+    pub window_bits: u8,
+    /// The compression level hint (0 is the fastest, 3 the best compression).
+    pub level: u8,
+    pub preset_dictionary: Option<u32>,
+}
+
+fn is_valid_header(&(cmf, flg): &(u8, u8)) -> bool {
+    cmf & 0x0f == CM_DEFLATE
+        && cmf >> 4 <= MAX_CINFO
+        && (u16::from(cmf) << 8 | u16::from(flg)) % 31 == 0
+}
+
+/// Whether `input` starts like a zlib stream.
+pub fn is_zlib_header(input: &[u8]) -> bool {
+    match input {
+        [cmf, flg, ..] => is_valid_header(&(*cmf, *flg)),
+        _ => false,
+    }
+}
+
+impl StreamHeader {
+    pub fn parse(i: &[u8]) -> IResult<&[u8], StreamHeader> {
+        let (i, (cmf, flg)) = verify(pair(be_u8, be_u8), is_valid_header)(i)?;
+        let (i, preset_dictionary) = if flg & 0x20 != 0 {
+            let (i, dict_id) = be_u32(i)?;
+            (i, Some(dict_id))
+        } else {
+            (i, None)
+        };
+
+        Ok((
+            i,
+            StreamHeader {
+                window_bits: (cmf >> 4) + 8,
+                level: flg >> 6,
+                preset_dictionary,
+            },
+        ))
+    }
+}
+
+/// Parses the Adler-32 checksum of the uncompressed data.
+pub fn parse_footer(i: &[u8]) -> IResult<&[u8], u32> {
+    be_u32(i)
+}
@@ -0,0 +1,130 @@
+//! Streaming decompression of Zstandard data (zip compression method 93).
+//!
+//! The decoding itself is done by a pluggable `Backend`; with the `zstd` feature,
+//! `LibZstd` provides one that uses libzstd. `Stream` drives a backend with
+//! the same API as `deflate::Stream` and returns the same `deflate::State`.
+
+use crate::deflate::State;
+
+const OUTPUT_BUFFER_SIZE: usize = 32 * 1024;
+
+/// The zstd stream contained invalid data.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct InvalidStream;
+
+/// The result of a single `Backend::decompress` call.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Progress {
+    /// The number of input bytes consumed.
+    pub consumed: usize,
+    /// The number of bytes written to the output buffer.
+    pub written: usize,
+    /// Whether the frame was fully decoded and all of its output written.
+    pub frame_ended: bool,
+}
+
+/// A zstd decoder that `Stream` feeds input to.
+pub trait Backend: Send {
+    /// Decompresses from `input` to `output`, buffering any input it can't
+    /// make progress with yet. Must not consume input past the end of the frame,
+    /// because the input that follows belongs to the next zip record.
+    fn decompress(&mut self, input: &[u8], output: &mut [u8]) -> Result<Progress, InvalidStream>;
+}
+
+/// A backend that uses libzstd through the `zstd-safe` crate.
+#[cfg(feature = "zstd")]
+pub struct LibZstd {
+    dctx: zstd_safe::DCtx<'static>,
+}
+
+#[cfg(feature = "zstd")]
+impl Default for LibZstd {
+    fn default() -> LibZstd {
+        LibZstd {
+            dctx: zstd_safe::DCtx::create(),
+        }
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl Backend for LibZstd {
+    fn decompress(&mut self, input: &[u8], output: &mut [u8]) -> Result<Progress, InvalidStream> {
+        let mut in_buf = zstd_safe::InBuffer::around(input);
+        let mut out_buf = zstd_safe::OutBuffer::around(output);
+        let hint = self
+            .dctx
+            .decompress_stream(&mut out_buf, &mut in_buf)
+            .map_err(|_| InvalidStream)?;
+        Ok(Progress {
+            consumed: in_buf.pos(),
+            written: out_buf.pos(),
+            frame_ended: hint == 0,
+        })
+    }
+}
+
+pub struct Stream {
+    backend: Box<dyn Backend>,
+    output: Vec<u8>,
+    output_len: usize,
+    ended: bool,
+    uncomp_size: u64,
+    comp_size: u64,
+}
+
+impl Stream {
+    pub fn new(backend: Box<dyn Backend>) -> Stream {
+        Stream {
+            backend,
+            output: vec![0; OUTPUT_BUFFER_SIZE],
+            output_len: 0,
+            ended: false,
+            uncomp_size: 0,
+            comp_size: 0,
+        }
+    }
+
+    pub fn feed_input<'i, 'o>(
+        &'o mut self,
+        input: &'i [u8],
+    ) -> Result<State<'i, 'o>, InvalidStream> {
+        if self.ended {
+            // The end of the frame was reached during the previous call,
+            // which also returned the last output.
+            self.output_len = 0;
+            return Ok(State::Stop {
+                unparsed_input: input,
+            });
+        }
+
+        let progress = self.backend.decompress(input, &mut self.output)?;
+        self.comp_size += progress.consumed as u64;
+        self.uncomp_size += progress.written as u64;
+        self.output_len = progress.written;
+        self.ended = progress.frame_ended;
+        let unparsed_input = &input[progress.consumed..];
+
+        if self.output_len > 0 {
+            Ok(State::HasOutput {
+                unparsed_input,
+                output: self.get_output(),
+            })
+        } else if self.ended {
+            Ok(State::Stop { unparsed_input })
+        } else {
+            Ok(State::NeedsInput { unparsed_input })
+        }
+    }
+
+    pub fn get_output(&self) -> &[u8] {
+        &self.output[..self.output_len]
+    }
+
+    pub fn uncompressed_size(&self) -> u64 {
+        self.uncomp_size
+    }
+
+    pub fn compressed_size(&self) -> u64 {
+        self.comp_size
+    }
+}
@@ -0,0 +1,238 @@
+extern crate stream_zipper;
+
+use std::fs;
+
+use stream_zipper::ar::{self, ArError};
+use stream_zipper::test_util::{assert_every_split, assert_random_splits, read_chunks, Entry};
+use stream_zipper::{Error, State};
+
+const CONTROL: &[u8] = b"Package: example\nVersion: 1.0\nArchitecture: all\n\
+    Maintainer: Nobody <nobody@example.com>\nDescription: An example package\n";
+
+fn entry(name: &str, data: Vec<u8>) -> Entry {
+    Entry {
+        name: Some(name.as_bytes().to_vec()),
+        data,
+    }
+}
+
+fn expected_members() -> Vec<Entry> {
+    vec![
+        entry(
+            "short_data.txt",
+            fs::read("tests/assets/uncompressed/short_data.txt").unwrap(),
+        ),
+        entry(
+            "a_member_with_a_rather_long_name.txt",
+            fs::read("tests/assets/uncompressed/zipped_a.txt").unwrap(),
+        ),
+        entry("odd.txt", b"x".to_vec()),
+    ]
+}
+
+fn expected_deb_entries() -> Vec<Entry> {
+    vec![
+        entry("debian-binary", b"2.0\n".to_vec()),
+        entry("./", Vec::new()),
+        entry("./control", CONTROL.to_vec()),
+        entry("./", Vec::new()),
+        entry("./usr/", Vec::new()),
+        entry("./usr/share/", Vec::new()),
+        entry("./usr/share/doc/", Vec::new()),
+        entry("./usr/share/doc/example/", Vec::new()),
+        entry(
+            "./usr/share/doc/example/short_data.txt",
+            fs::read("tests/assets/uncompressed/short_data.txt").unwrap(),
+        ),
+        entry(
+            "./usr/share/doc/example/zipped_a.txt",
+            fs::read("tests/assets/uncompressed/zipped_a.txt").unwrap(),
+        ),
+    ]
+}
+
+#[test]
+fn test_gnu() {
+    // The long name is stored in a table of long names.
+    let data_ar = fs::read("tests/assets/ar/gnu.a").unwrap();
+
+    assert_eq!(read_chunks(&[&data_ar]), Ok(expected_members()));
+    assert_every_split(&data_ar, read_chunks);
+}
+
+#[test]
+fn test_bsd() {
+    // The long name is stored before the data.
+    let data_ar = fs::read("tests/assets/ar/bsd.a").unwrap();
+
+    let mut file = ar::start_stream();
+    let state = file.read_with(&data_ar, |_| ()).unwrap();
+    let header = file.header().unwrap();
+    assert_eq!(header.name, b"short_data.txt");
+    assert_eq!(header.mtime, 1_580_000_000);
+    assert_eq!((header.uid, header.gid), (501, 20));
+    assert_eq!(header.mode, 0o100_644);
+    assert_eq!(header.size, 60);
+    let mut file = match state {
+        State::NextFile { next_file, .. } => next_file,
+        state => panic!("unexpected state: {:?}", state),
+    };
+    file.read_with(&[], |_| ()).unwrap();
+    assert_eq!(file.header().unwrap().size, 9505);
+
+    assert_eq!(read_chunks(&[&data_ar]), Ok(expected_members()));
+    assert_every_split(&data_ar, read_chunks);
+}
+
+#[test]
+fn test_ends_with_input() {
+    let data_ar = fs::read("tests/assets/ar/gnu.a").unwrap();
+
+    let mut names = Vec::new();
+    let mut file = ar::start_stream();
+    let mut input = &data_ar[..];
+    loop {
+        match file.read_with(input, |_| ()).unwrap() {
+            State::NextFile {
+                unparsed_input,
+                next_file,
+            } => {
+                names.push(file.filename().unwrap().to_vec());
+                file = next_file;
+                input = unparsed_input;
+            }
+            // The archive has no end marker.
+            State::NeedsInputOrEof(_) => break,
+            state => panic!("unexpected state: {:?}", state),
+        }
+    }
+    names.push(file.filename().unwrap().to_vec());
+
+    let expected: Vec<_> = expected_members()
+        .into_iter()
+        .map(|e| e.name.unwrap())
+        .collect();
+    assert_eq!(names, expected);
+}
+
+#[test]
+fn test_symbol_table() {
+    // A symbol table is skipped like the table of long names.
+    let data_ar = fs::read("tests/assets/ar/gnu.a").unwrap();
+    let symbols = b"\0\0\0\x01\0\0\0\x08main\0";
+    let header = format!(
+        "{:<16}{:<12}{:<6}{:<6}{:<8}{:<10}`\n",
+        "/", 0, "", "", 0, 13
+    );
+    let data_ar = [
+        &data_ar[..8],
+        header.as_bytes(),
+        symbols,
+        b"\n",
+        &data_ar[8..],
+    ]
+    .concat();
+
+    assert_eq!(read_chunks(&[&data_ar]), Ok(expected_members()));
+}
+
+#[test]
+fn test_invalid_long_name() {
+    let data_ar = fs::read("tests/assets/ar/gnu.a").unwrap();
+    // Refer to an offset past the end of the table of long names.
+    let data_ar = String::from_utf8_lossy(&data_ar).replacen("/0   ", "/99  ", 1);
+
+    assert_eq!(
+        read_chunks(&[data_ar.as_bytes()]),
+        Err(Error::Ar(ArError::InvalidName))
+    );
+}
+
+#[test]
+fn test_read_after_error() {
+    let data_ar = fs::read("tests/assets/ar/gnu.a").unwrap();
+    let data_ar = String::from_utf8_lossy(&data_ar).replacen("/0   ", "/99  ", 1);
+
+    let mut ar_file = ar::start_stream();
+    let mut input = data_ar.as_bytes();
+    let error = loop {
+        match ar_file.read_with(input, |_| ()) {
+            Ok(State::NextFile {
+                unparsed_input,
+                next_file,
+            }) => {
+                input = unparsed_input;
+                ar_file = next_file;
+            }
+            Ok(state) => panic!("unexpected state: {:?}", state),
+            Err(e) => break e,
+        }
+    };
+    assert_eq!(error, ArError::InvalidName);
+    assert_eq!(ar_file.read(&[]).unwrap_err(), ArError::InvalidName);
+    assert_eq!(
+        ar_file.read(data_ar.as_bytes()).unwrap_err(),
+        ArError::InvalidName
+    );
+}
+
+#[test]
+fn test_read_after_next_file() {
+    let data_ar = fs::read("tests/assets/ar/gnu.a").unwrap();
+
+    let mut ar_file = ar::start_stream();
+    match ar_file.read_with(&data_ar, |_| ()).unwrap() {
+        State::NextFile { .. } => (),
+        state => panic!("unexpected state: {:?}", state),
+    }
+    // The member has ended; the rest of the archive belongs to the next one.
+    match ar_file.read(&data_ar).unwrap() {
+        State::EndOfFile => (),
+        state => panic!("unexpected state: {:?}", state),
+    }
+}
+
+#[test]
+fn test_deb_gz() {
+    let data_deb = fs::read("tests/assets/ar/example_gz.deb").unwrap();
+
+    assert_eq!(read_chunks(&[&data_deb]), Ok(expected_deb_entries()));
+    assert_every_split(&data_deb, read_chunks);
+}
+
+#[test]
+fn test_deb_xz() {
+    let data_deb = fs::read("tests/assets/ar/example_xz.deb").unwrap();
+
+    let mut tar_entries = 0;
+    let mut file = ar::start_deb_stream();
+    let mut input = &data_deb[..];
+    loop {
+        match file.read_with(input, |_| ()).unwrap() {
+            State::NextFile {
+                unparsed_input,
+                next_file,
+            } => {
+                if file.is_tar() {
+                    tar_entries += 1;
+                    assert!(file.tar_header().is_some());
+                }
+                // The ar member is handed over with the next entry of its tar archive.
+                if next_file.is_tar() {
+                    let ar_name = &next_file.ar_header().unwrap().name;
+                    assert!(ar_name == b"control.tar.xz" || ar_name == b"data.tar.xz");
+                }
+                file = next_file;
+                input = unparsed_input;
+            }
+            State::NeedsInputOrEof(_) => break,
+            state => panic!("unexpected state: {:?}", state),
+        }
+    }
+    assert!(file.is_tar());
+    // All but "debian-binary" and the last entry.
+    assert_eq!(tar_entries, expected_deb_entries().len() - 2);
+
+    assert_eq!(read_chunks(&[&data_deb]), Ok(expected_deb_entries()));
+    assert_random_splits(&data_deb, 1521, 20, 50, read_chunks);
+}
@@ -0,0 +1,204 @@
+extern crate stream_zipper;
+
+use std::fs;
+use std::io::{self, BufRead, Read};
+
+use stream_zipper::archive::Archive;
+use stream_zipper::test_util::{read_chunks, Entry};
+use stream_zipper::{DriveError, Error, Options};
+
+const ASSETS: &[&str] = &[
+    "tests/assets/zip/comments.zip",
+    "tests/assets/zip/stored_descriptors.zip",
+    "tests/assets/zip/unicode_names.zip",
+    "tests/assets/tar/ustar.tar.gz",
+    "tests/assets/gzip/repetitive_data.txt.gz",
+    "tests/assets/cpio/newc.cpio",
+    "tests/assets/ar/gnu.a",
+    "tests/assets/bzip2/concatenated.bz2",
+];
+
+/// A reader that returns at most `chunk_size` bytes at a time.
+struct Chunked<'a> {
+    data: &'a [u8],
+    chunk_size: usize,
+}
+
+impl Read for Chunked<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.data.len().min(self.chunk_size).min(buf.len());
+        buf[..len].copy_from_slice(&self.data[..len]);
+        self.data = &self.data[len..];
+        Ok(len)
+    }
+}
+
+fn read_archive<R: Read>(mut archive: Archive<R>) -> Result<Vec<Entry>, DriveError> {
+    let mut entries = Vec::new();
+    while let Some(mut entry) = archive.next_entry()? {
+        let name = entry.metadata().name.clone();
+        let mut data = Vec::new();
+        while let Some(chunk) = entry.next_chunk()? {
+            data.extend_from_slice(chunk);
+        }
+        entries.push(Entry { name, data });
+    }
+    Ok(entries)
+}
+
+#[test]
+fn test_archive() {
+    for path in ASSETS {
+        let input = fs::read(path).unwrap();
+        let expected = read_chunks(&[&input]).unwrap();
+        assert_eq!(
+            read_archive(Archive::new(&input[..])).expect(path),
+            expected,
+            "{}",
+            path
+        );
+        let chunked = Chunked {
+            data: &input,
+            chunk_size: 7,
+        };
+        assert_eq!(
+            read_archive(Archive::new(chunked)).unwrap(),
+            expected,
+            "{}",
+            path
+        );
+    }
+}
+
+#[test]
+fn test_archive_read() {
+    for path in ASSETS {
+        let input = fs::read(path).unwrap();
+        let mut archive = Archive::new(&input[..]);
+        let mut entries = Vec::new();
+        while let Some(mut entry) = archive.next_entry().unwrap() {
+            let name = entry.metadata().name.clone();
+            let mut data = Vec::new();
+            // Read in small pieces, so that the chunks are split.
+            let mut buf = [0; 5];
+            loop {
+                match entry.read(&mut buf).unwrap() {
+                    0 => break,
+                    len => data.extend_from_slice(&buf[..len]),
+                }
+            }
+            entries.push(Entry { name, data });
+        }
+        assert_eq!(entries, read_chunks(&[&input]).unwrap(), "{}", path);
+    }
+}
+
+#[test]
+fn test_archive_read_lines() {
+    let input = fs::read("tests/assets/zip/kyushu.tsv.zip").unwrap();
+    let expected = read_chunks(&[&input]).unwrap();
+
+    let mut archive = Archive::new(&input[..]);
+    let entry = archive.next_entry().unwrap().unwrap();
+    assert_eq!(entry.metadata().name, Some(b"kyushu.tsv".to_vec()));
+    let lines: Vec<String> = entry.lines().map(Result::unwrap).collect();
+    assert!(lines.len() > 100);
+    assert!(lines.iter().all(|line| line.contains('\t')));
+    let expected = String::from_utf8(expected[0].data.clone()).unwrap();
+    assert!(lines.iter().map(String::as_str).eq(expected.lines()));
+}
+
+/// Reads the first entry of the input to the end, expecting an error.
+fn read_error(input: &[u8]) -> io::Error {
+    let mut archive = Archive::new(input);
+    match archive.next_entry() {
+        Ok(Some(mut entry)) => io::copy(&mut entry, &mut io::sink()).unwrap_err(),
+        Ok(None) => panic!("No entry"),
+        Err(err) => io::Error::from(err),
+    }
+}
+
+#[test]
+fn test_archive_read_errors() {
+    let input = fs::read("tests/assets/gzip/repetitive_data.txt.gz").unwrap();
+    assert_eq!(
+        read_error(&input[..input.len() / 2]).kind(),
+        io::ErrorKind::UnexpectedEof
+    );
+    assert_eq!(
+        read_error(b"not an archive").kind(),
+        io::ErrorKind::InvalidData
+    );
+}
+
+#[test]
+fn test_archive_skip_entries() {
+    let input = fs::read("tests/assets/zip/comments.zip").unwrap();
+    let expected = read_chunks(&[&input]).unwrap();
+    assert!(expected.len() > 1);
+
+    // Only the content of every other entry is read, and that only partly.
+    let mut archive = Archive::new(Chunked {
+        data: &input,
+        chunk_size: 7,
+    });
+    let mut names = Vec::new();
+    let mut index = 0;
+    while let Some(mut entry) = archive.next_entry().unwrap() {
+        names.push(entry.metadata().name.clone());
+        if index % 2 == 0 {
+            let chunk = entry.next_chunk().unwrap().unwrap();
+            assert!(expected[index].data.starts_with(chunk));
+        }
+        index += 1;
+    }
+    let expected_names: Vec<_> = expected.into_iter().map(|entry| entry.name).collect();
+    assert_eq!(names, expected_names);
+    assert!(archive.next_entry().unwrap().is_none());
+}
+
+#[test]
+fn test_archive_truncated() {
+    let input = fs::read("tests/assets/zip/comments.zip").unwrap();
+    match read_archive(Archive::new(&input[..input.len() / 2])) {
+        Err(DriveError::Io(ref e)) if e.kind() == io::ErrorKind::UnexpectedEof => (),
+        Err(DriveError::Stream(Error::Zip(_))) => (),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+
+    match read_archive(Archive::new(&b"not an archive"[..])) {
+        Err(DriveError::Stream(Error::UnknownFileFormat)) => (),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+}
+
+#[test]
+fn test_archive_passthrough() {
+    let options = Options::default().passthrough_unknown(true);
+    let plain = fs::read("tests/assets/uncompressed/numbers.txt").unwrap();
+    for input in &[&plain[..], &plain[..3], b""] {
+        for chunk_size in &[1, 100, plain.len()] {
+            let reader = Chunked {
+                data: input,
+                chunk_size: *chunk_size,
+            };
+            let entries = read_archive(Archive::with_options(reader, options.clone())).unwrap();
+            let expected: &[Entry] = &[Entry {
+                name: None,
+                data: input.to_vec(),
+            }];
+            // Empty input has no entries.
+            assert_eq!(entries, &expected[..input.len().min(1)]);
+        }
+    }
+
+    // Input in a known format is decompressed as usual.
+    let input = fs::read("tests/assets/gzip/repetitive_data.txt.gz").unwrap();
+    let entries = read_archive(Archive::with_options(&input[..], options)).unwrap();
+    assert_eq!(entries, read_chunks(&[&input]).unwrap());
+
+    match read_archive(Archive::new(&plain[..])) {
+        Err(DriveError::Stream(Error::UnknownFileFormat)) => (),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+}
@@ -0,0 +1,151 @@
+extern crate bytes;
+extern crate futures;
+extern crate stream_zipper;
+
+use std::fs;
+use std::io;
+
+use bytes::{Buf, Bytes, BytesMut};
+use futures::executor::block_on_stream;
+use futures::stream;
+use stream_zipper::bytes::{BodyError, BytesStream};
+use stream_zipper::futures::OutputChunk;
+use stream_zipper::test_util::{read_chunks, Entry};
+use stream_zipper::{start_stream, zip, DriveError, State};
+
+/// The body of a response, split into chunks of `chunk_size`.
+fn body(input: &[u8], chunk_size: usize) -> Vec<Result<Bytes, io::Error>> {
+    input
+        .chunks(chunk_size)
+        .map(|chunk| Ok(Bytes::copy_from_slice(chunk)))
+        .collect()
+}
+
+#[test]
+fn test_bytes_stream() {
+    for path in &[
+        "tests/assets/zip/comments.zip",
+        "tests/assets/tar/ustar.tar.gz",
+        "tests/assets/gzip/repetitive_data.txt.gz",
+    ] {
+        let input = fs::read(path).unwrap();
+        let expected = read_chunks(&[&input]).unwrap();
+
+        let body = stream::iter(body(&input, 1000));
+        let mut entries = Vec::new();
+        for frame in block_on_stream(BytesStream::new(start_stream(), body)) {
+            match frame.unwrap() {
+                OutputChunk::EntryStart(metadata) => entries.push(Entry {
+                    name: metadata.name,
+                    data: Vec::new(),
+                }),
+                OutputChunk::Data(data) => entries.last_mut().unwrap().data.extend(&data[..]),
+                OutputChunk::EntryEnd => (),
+            }
+        }
+        assert_eq!(entries, expected, "{}", path);
+    }
+}
+
+#[test]
+fn test_bytes_stream_errors() {
+    let input = fs::read("tests/assets/gzip/repetitive_data.txt.gz").unwrap();
+
+    // The error of the body is returned even though the gzip file could end before it.
+    let mut chunks = body(&input, 1000);
+    chunks.push(Err(io::Error::other("connection reset")));
+    let frames: Vec<_> =
+        block_on_stream(BytesStream::new(start_stream(), stream::iter(chunks))).collect();
+    match frames.last() {
+        Some(Err(BodyError::Body(e))) => assert_eq!(e.to_string(), "connection reset"),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+    assert!(frames[..frames.len() - 1].iter().all(Result::is_ok));
+
+    let chunks = body(&input[..input.len() / 2], 1000);
+    let frames: Vec<_> =
+        block_on_stream(BytesStream::new(start_stream(), stream::iter(chunks))).collect();
+    match frames.last() {
+        Some(Err(BodyError::Stream(DriveError::Io(e)))) => {
+            assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof)
+        }
+        result => panic!("Unexpected result: {:?}", result),
+    }
+}
+
+#[test]
+fn test_read_buf() {
+    let input = fs::read("tests/assets/zip/zipped_ab.zip").unwrap();
+    let expected = read_chunks(&[&input]).unwrap();
+
+    for split in &[1, 30, input.len() / 2, input.len() - 1] {
+        // A non-contiguous buffer, whose first part ends at `split`.
+        let (first, second) = input.split_at(*split);
+        let mut buf = Bytes::copy_from_slice(first).chain(BytesMut::from(second));
+
+        let mut entries = Vec::new();
+        let mut data = Vec::new();
+        let mut file = start_stream();
+        loop {
+            match file.read_buf(&mut buf).unwrap() {
+                State::HasOutput {
+                    unparsed_input,
+                    output,
+                } => {
+                    assert!(unparsed_input.is_empty());
+                    data.extend_from_slice(output);
+                }
+                State::NextFile { next_file, .. } => {
+                    entries.push(Entry {
+                        name: file.name().map(<[u8]>::to_vec),
+                        data: std::mem::take(&mut data),
+                    });
+                    file = next_file;
+                }
+                State::EndOfFile => {
+                    entries.push(Entry {
+                        name: file.name().map(<[u8]>::to_vec),
+                        data: std::mem::take(&mut data),
+                    });
+                    break;
+                }
+                state => panic!("Unexpected state: {:?}", state),
+            }
+        }
+        assert_eq!(entries, expected, "{}", split);
+    }
+}
+
+#[test]
+fn test_read_buf_needs_input() {
+    let input = fs::read("tests/assets/zip/zipped_ab.zip").unwrap();
+
+    // The buffer is consumed as a whole when more input is needed.
+    let mut buf = &input[..20];
+    let mut file = zip::start_stream();
+    assert!(matches!(file.read_buf(&mut buf), Ok(State::NeedsInput)));
+    assert!(!buf.has_remaining());
+
+    // The input of the next entry is left in the buffer.
+    let expected = read_chunks(&[&input]).unwrap();
+    let mut buf = &input[20..];
+    for (index, entry) in expected.iter().enumerate() {
+        let mut data = Vec::new();
+        loop {
+            match file.read_buf(&mut buf).unwrap() {
+                State::HasOutput { output, .. } => data.extend_from_slice(output),
+                State::NextFile { next_file, .. } => {
+                    assert!(buf.has_remaining());
+                    file = next_file;
+                    break;
+                }
+                State::EndOfFile => {
+                    assert_eq!(index, expected.len() - 1);
+                    break;
+                }
+                state => panic!("Unexpected state: {:?}", state),
+            }
+        }
+        assert_eq!(data, entry.data);
+    }
+}
@@ -0,0 +1,82 @@
+extern crate stream_zipper;
+
+use std::fs;
+
+use stream_zipper::bzip2::{self, Bzip2Error};
+use stream_zipper::test_util::{assert_every_split, read_chunks, Entry};
+use stream_zipper::{Error, State};
+
+#[test]
+fn test_decompression() {
+    for name in &["short_data.txt", "repetitive_data.txt"] {
+        let data_bz2 = fs::read(format!("tests/assets/bzip2/{}.bz2", name)).unwrap();
+        let data = fs::read(format!("tests/assets/uncompressed/{}", name)).unwrap();
+
+        let mut output = Vec::new();
+        let mut bz2_file = bzip2::start_stream();
+        let state = bz2_file
+            .read_with(&data_bz2, |out| output.extend_from_slice(out))
+            .unwrap();
+        // Another stream may follow in the input.
+        assert!(matches!(state, State::NeedsInputOrEof(_)));
+        assert_eq!(output, data);
+    }
+}
+
+#[test]
+fn test_autodetect_concatenated() {
+    let data_bz2 = fs::read("tests/assets/bzip2/concatenated.bz2").unwrap();
+    let data_short = fs::read("tests/assets/uncompressed/short_data.txt").unwrap();
+    let data_a = fs::read("tests/assets/uncompressed/zipped_a.txt").unwrap();
+
+    assert_eq!(
+        read_chunks(&[&data_bz2]),
+        Ok(vec![
+            Entry {
+                name: None,
+                data: data_short,
+            },
+            Entry {
+                name: None,
+                data: data_a,
+            },
+        ])
+    );
+    assert_every_split(&data_bz2, read_chunks);
+}
+
+#[test]
+fn test_invalid_stream() {
+    let mut data_bz2 = fs::read("tests/assets/bzip2/repetitive_data.txt.bz2").unwrap();
+    // Corrupt the block magic that follows the stream header.
+    data_bz2[5] ^= 0xff;
+
+    assert_eq!(
+        read_chunks(&[&data_bz2]),
+        Err(Error::Bzip2(Bzip2Error::InvalidStream))
+    );
+}
+
+#[test]
+fn test_trailing_garbage() {
+    let mut data_bz2 = fs::read("tests/assets/bzip2/short_data.txt.bz2").unwrap();
+    data_bz2.extend_from_slice(b"garbage");
+
+    let res = bzip2::start_stream().read_with(&data_bz2, |_| ());
+    assert_eq!(res.unwrap_err(), Bzip2Error::InvalidHeader);
+}
+
+#[test]
+fn test_read_after_error() {
+    let mut data_bz2 = fs::read("tests/assets/bzip2/repetitive_data.txt.bz2").unwrap();
+    data_bz2[5] ^= 0xff;
+
+    let mut bzip2_file = bzip2::start_stream();
+    let res = bzip2_file.read_with(&data_bz2, |_| ());
+    assert_eq!(res.unwrap_err(), Bzip2Error::InvalidStream);
+    assert_eq!(bzip2_file.read(&[]).unwrap_err(), Bzip2Error::InvalidStream);
+    assert_eq!(
+        bzip2_file.read(&data_bz2).unwrap_err(),
+        Bzip2Error::InvalidStream
+    );
+}
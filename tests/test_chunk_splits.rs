@@ -0,0 +1,41 @@
+extern crate stream_zipper;
+
+use std::fs;
+
+use stream_zipper::test_util::{assert_every_split, assert_random_splits, read_chunks};
+
+#[test]
+fn test_every_split_zip() {
+    for path in &[
+        "tests/assets/zip/short_data.txt.zip",
+        "tests/assets/zip/zipped_ab.zip",
+        "tests/assets/zip/test_zip_bug_20210407.zip",
+    ] {
+        let data_zip = fs::read(path).unwrap();
+        assert_every_split(&data_zip, read_chunks);
+    }
+}
+
+#[test]
+fn test_every_split_gzip() {
+    for path in &[
+        "tests/assets/gzip/short_data.txt.gz",
+        "tests/assets/gzip/zipped_a.txt.gz",
+    ] {
+        let data_gz = fs::read(path).unwrap();
+        assert_every_split(&data_gz, read_chunks);
+    }
+}
+
+#[test]
+fn test_random_splits() {
+    for path in &[
+        "tests/assets/zip/rand_data_abc.zip",
+        "tests/assets/zip/repetitive_data.txt.zip",
+        "tests/assets/gzip/rand_data.bin.gz",
+    ] {
+        let data = fs::read(path).unwrap();
+        assert_random_splits(&data, 20200131, 20, 1, read_chunks);
+        assert_random_splits(&data, 20200131, 20, 200, read_chunks);
+    }
+}
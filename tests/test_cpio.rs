@@ -0,0 +1,160 @@
+extern crate stream_zipper;
+
+use std::fs;
+
+use stream_zipper::cpio::headers::{Format, Header, HEADER_SIZE};
+use stream_zipper::cpio::{self, CpioError};
+use stream_zipper::test_util::{assert_every_split, assert_random_splits, read_chunks, Entry};
+use stream_zipper::{Error, State};
+
+fn expected_entries() -> Vec<Entry> {
+    let data_short = fs::read("tests/assets/uncompressed/short_data.txt").unwrap();
+    let data_a = fs::read("tests/assets/uncompressed/zipped_a.txt").unwrap();
+    let long_name = [
+        &b"a_directory_with_a_rather_long_name/".repeat(3)[..],
+        b"zipped_a.txt",
+    ]
+    .concat();
+    let entry = |name: &[u8], data| Entry {
+        name: Some(name.to_vec()),
+        data,
+    };
+
+    vec![
+        entry(b"dir", Vec::new()),
+        entry(b"dir/short_data.txt", data_short),
+        entry(&long_name, data_a),
+        // The data of a symlink is its target.
+        entry(b"link", b"dir/short_data.txt".to_vec()),
+        entry(b"empty.txt", Vec::new()),
+    ]
+}
+
+#[test]
+fn test_parsing_header() {
+    let data_cpio = fs::read("tests/assets/cpio/crc.cpio").unwrap();
+
+    let (header, name_size) = Header::parse(&data_cpio[..HEADER_SIZE]).unwrap();
+    assert_eq!(header.format, Format::Crc);
+    assert!(header.is_dir());
+    assert_eq!(header.mode & 0o7777, 0o755);
+    assert_eq!(header.nlink, 2);
+    assert_eq!(header.mtime, 1_580_000_000);
+    assert_eq!(name_size, 4);
+    assert_eq!(Header::len_with_name(name_size), 116);
+}
+
+#[test]
+fn test_newc() {
+    let data_cpio = fs::read("tests/assets/cpio/newc.cpio").unwrap();
+
+    assert_eq!(read_chunks(&[&data_cpio]), Ok(expected_entries()));
+    assert_every_split(&data_cpio, read_chunks);
+}
+
+#[test]
+fn test_crc() {
+    let data_cpio = fs::read("tests/assets/cpio/crc.cpio").unwrap();
+
+    let mut headers = Vec::new();
+    let mut file = cpio::start_stream();
+    let mut input = &data_cpio[..];
+    loop {
+        match file.read_with(input, |_| ()).unwrap() {
+            State::NextFile {
+                unparsed_input,
+                next_file,
+            } => {
+                headers.push(file.header().unwrap().clone());
+                file = next_file;
+                input = unparsed_input;
+            }
+            State::EndOfFile => break,
+            state => panic!("unexpected state: {:?}", state),
+        }
+    }
+    headers.push(file.header().unwrap().clone());
+
+    assert_eq!(headers.len(), 5);
+    assert!(headers[3].is_symlink());
+    assert_eq!(headers[2].size, 9505);
+    assert_eq!(read_chunks(&[&data_cpio]), Ok(expected_entries()));
+    assert_random_splits(&data_cpio, 1520, 10, 200, read_chunks);
+}
+
+#[test]
+fn test_checksum_mismatch() {
+    let mut data_cpio = fs::read("tests/assets/cpio/crc.cpio").unwrap();
+    // Change the data of "dir/short_data.txt", which follows the first
+    // entry and its own header with the padded name.
+    data_cpio[116 + 132] ^= 1;
+
+    assert_eq!(
+        read_chunks(&[&data_cpio]),
+        Err(Error::Cpio(CpioError::ChecksumMismatch))
+    );
+}
+
+#[test]
+fn test_invalid_header() {
+    let mut data_cpio = fs::read("tests/assets/cpio/newc.cpio").unwrap();
+    // The size of the first entry isn't a hexadecimal number.
+    data_cpio[54] = b'x';
+
+    assert_eq!(
+        read_chunks(&[&data_cpio]),
+        Err(Error::Cpio(CpioError::InvalidHeader))
+    );
+}
+
+#[test]
+fn test_cpio_gz() {
+    let data_gz = fs::read("tests/assets/cpio/newc.cpio.gz").unwrap();
+
+    let mut entries = Vec::new();
+    let mut data = Vec::new();
+    let mut file = cpio::start_cpio_gz_stream();
+    let mut input = &data_gz[..];
+    loop {
+        match file
+            .read_with(input, |out| data.extend_from_slice(out))
+            .unwrap()
+        {
+            State::NextFile {
+                unparsed_input,
+                next_file,
+            } => {
+                assert!(file.is_cpio());
+                entries.push(Entry {
+                    name: file.filename().map(<[u8]>::to_vec),
+                    data: std::mem::take(&mut data),
+                });
+                file = next_file;
+                input = unparsed_input;
+            }
+            State::NeedsInputOrEof(_) => break,
+            state => panic!("unexpected state: {:?}", state),
+        }
+    }
+    entries.push(Entry {
+        name: file.filename().map(<[u8]>::to_vec),
+        data,
+    });
+
+    assert_eq!(entries, expected_entries());
+}
+
+#[test]
+fn test_read_after_error() {
+    let mut data_cpio = fs::read("tests/assets/cpio/newc.cpio").unwrap();
+    data_cpio[54] = b'x';
+
+    let mut cpio_file = cpio::start_stream();
+    let res = cpio_file.read_with(&data_cpio, |_| ());
+    assert_eq!(res.unwrap_err(), CpioError::InvalidHeader);
+    assert_eq!(cpio_file.read(&[]).unwrap_err(), CpioError::InvalidHeader);
+    assert_eq!(
+        cpio_file.read(&data_cpio).unwrap_err(),
+        CpioError::InvalidHeader
+    );
+}
@@ -0,0 +1,91 @@
+extern crate stream_zipper;
+
+use std::fs;
+
+use stream_zipper::gzip::GZipError;
+use stream_zipper::test_util::{read_chunks, read_chunks_with};
+use stream_zipper::zip::{self, ZipError};
+use stream_zipper::{Error, Options};
+
+#[test]
+fn test_zip_error_context() {
+    // The signature of the central directory header at 204 is damaged.
+    let mut data_zip = fs::read("tests/assets/zip/zipped_ab.zip").unwrap();
+    data_zip[204..208].copy_from_slice(b"PK??");
+
+    // The offset is from the start of the stream, however the input is split,
+    // but only the bytes that had been read when the error was detected are kept.
+    for chunk_size in &[7, data_zip.len()] {
+        let chunks: Vec<_> = data_zip.chunks(*chunk_size).collect();
+        match read_chunks(&chunks) {
+            Err(Error::Zip(ZipError::NotCentralDirHeader(context))) => {
+                assert_eq!(context.offset(), 204);
+                assert_eq!(context.expected_magic(), b"PK\x01\x02");
+                assert!(data_zip[204..].starts_with(context.found()));
+                assert!(context.found().len() >= 4);
+            }
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
+    let error = read_chunks(&[&data_zip]).unwrap_err();
+    assert!(error.to_string().ends_with(
+        "NotCentralDirHeader at offset 204: \
+         expected 50 4b 01 02, found 50 4b 3f 3f 15 03 14 00 08 00 08 00 2f 92 90 4c"
+    ));
+
+    // Parsing a slice counts the offset from its start.
+    match zip::peek_stream(b"not a zip") {
+        Err(ZipError::NotLocalFileHeader(context)) => {
+            assert_eq!(context.offset(), 0);
+            assert_eq!(context.expected_magic(), b"PK\x03\x04");
+            assert_eq!(context.found(), b"not a zip");
+        }
+        result => panic!("Unexpected result: {:?}", result.map(|_| ())),
+    }
+}
+
+#[test]
+fn test_zip_entry_error() {
+    // Only the second entry is larger than the limit.
+    let data_zip = fs::read("tests/assets/zip/comments.zip").unwrap();
+    let options = Options::default().max_entry_size(1000);
+    match read_chunks_with(options, &[&data_zip]) {
+        Err(Error::Zip(error)) => {
+            assert_eq!(error.entry(), Some((1, &b"repetitive_data.txt"[..])));
+            assert_eq!(error.without_entry(), &ZipError::EntryTooLarge);
+            assert_eq!(
+                error.to_string(),
+                "zip uncompressing error: EntryTooLarge in entry 1 (repetitive_data.txt)"
+            );
+        }
+        result => panic!("Unexpected result: {:?}", result),
+    }
+
+    // Errors outside of the data of an entry aren't wrapped.
+    match zip::peek_stream(b"not a zip") {
+        Err(error) => assert_eq!(error.entry(), None),
+        result => panic!("Unexpected result: {:?}", result.map(|_| ())),
+    }
+}
+
+#[test]
+fn test_gzip_error_context() {
+    let data_gz = fs::read("tests/assets/gzip/short_data.txt.gz").unwrap();
+    let data = [&data_gz[..], &data_gz[..], b"\x1f\x8b\x09\x00"].concat();
+    match read_chunks(&[&data]) {
+        Err(Error::GZip(error @ GZipError::InvalidMemberHeader(_))) => {
+            let context = error.context().unwrap();
+            assert_eq!(context.offset(), 2 * data_gz.len() as u64);
+            assert_eq!(context.expected_magic(), b"\x1f\x8b\x08");
+            assert_eq!(context.found(), b"\x1f\x8b\x09\x00");
+            assert_eq!(
+                error.to_string(),
+                format!(
+                    "invalid member header at offset {}: expected 1f 8b 08, found 1f 8b 09 00",
+                    2 * data_gz.len()
+                )
+            );
+        }
+        result => panic!("Unexpected result: {:?}", result),
+    }
+}
@@ -0,0 +1,154 @@
+extern crate stream_zipper;
+
+use std::fs;
+use std::path::PathBuf;
+
+use stream_zipper::extract::{extract_to_dir, ExtractError, Extractor};
+use stream_zipper::zip::ZipError;
+use stream_zipper::Error;
+
+/// A directory for a test to extract into, removed when dropped.
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(test: &str) -> TempDir {
+        let path =
+            std::env::temp_dir().join(format!("stream_zipper_{}_{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// The names of the files and directories in `dir`, sorted.
+fn list_dir(dir: &PathBuf) -> Vec<String> {
+    let mut names: Vec<_> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn test_extract_tar_gz() {
+    let temp = TempDir::new("extract_tar_gz");
+    let data_tgz = fs::read("tests/assets/tar/ustar.tar.gz").unwrap();
+
+    let summary = extract_to_dir(&data_tgz[..], &temp.0).unwrap();
+    assert_eq!(summary.files, 5);
+
+    // The symbolic link isn't created.
+    assert_eq!(
+        list_dir(&temp.0),
+        ["a_directory_with_a_rather_long_name", "dir", "empty.txt"]
+    );
+    assert_eq!(
+        fs::read(temp.0.join("dir/short_data.txt")).unwrap(),
+        fs::read("tests/assets/uncompressed/short_data.txt").unwrap()
+    );
+    let long_dir = "a_directory_with_a_rather_long_name";
+    let long_name = format!("{0}/{0}/{0}/zipped_a.txt", long_dir);
+    assert_eq!(
+        fs::read(temp.0.join(long_name)).unwrap(),
+        fs::read("tests/assets/uncompressed/zipped_a.txt").unwrap()
+    );
+    assert_eq!(fs::read(temp.0.join("empty.txt")).unwrap(), b"");
+}
+
+#[test]
+fn test_extract_zip_in_chunks() {
+    let temp = TempDir::new("extract_zip_in_chunks");
+    let data_zip = fs::read("tests/assets/zip/zipped_ab.zip").unwrap();
+
+    let mut extractor = Extractor::new(&temp.0).unwrap();
+    for chunk in data_zip.chunks(7) {
+        extractor.read(chunk).unwrap();
+    }
+    assert!(extractor.has_ended());
+    let summary = extractor.finish().unwrap();
+    assert_eq!(summary.files, 2);
+    assert_eq!(summary.compressed_bytes, data_zip.len() as u64);
+
+    assert_eq!(list_dir(&temp.0), ["zipped_a.txt", "zipped_b.txt"]);
+    for name in &["zipped_a.txt", "zipped_b.txt"] {
+        assert_eq!(
+            fs::read(temp.0.join(name)).unwrap(),
+            fs::read(format!("tests/assets/uncompressed/{}", name)).unwrap()
+        );
+    }
+}
+
+#[test]
+fn test_unsafe_paths() {
+    let temp = TempDir::new("unsafe_paths");
+    let dest = temp.0.join("dest");
+    let data_zip = fs::read("tests/assets/zip/zipped_ab.zip").unwrap();
+
+    for name in &[
+        "../evil.txtx",
+        "/tmp/evil.tx",
+        "\\tmp\\evil.tx",
+        "C:/evil.txtx",
+        "C:evil.txtxx",
+        "a/../../evil",
+        "a\\..\\..\\evil",
+        "./../evil.tx",
+    ] {
+        // The names are as long as the original, zipped_a.txt.
+        assert_eq!(name.len(), 12);
+        let mut data_zip = data_zip.clone();
+        for i in 0..data_zip.len() - 12 {
+            if &data_zip[i..i + 12] == b"zipped_a.txt" {
+                data_zip[i..i + 12].copy_from_slice(name.as_bytes());
+            }
+        }
+
+        match extract_to_dir(&data_zip[..], &dest) {
+            Err(ExtractError::UnsafePath(path)) => assert_eq!(path, name.as_bytes()),
+            result => panic!("Unexpected result for {}: {:?}", name, result),
+        }
+        assert_eq!(list_dir(&temp.0), ["dest"]);
+        assert!(list_dir(&dest).is_empty());
+    }
+}
+
+#[test]
+fn test_safe_unusual_paths() {
+    let temp = TempDir::new("safe_unusual_paths");
+    let data_zip = fs::read("tests/assets/zip/zipped_ab.zip").unwrap();
+
+    // Backslashes are separators, and `.` and empty components are dropped.
+    let mut data_zip = data_zip.clone();
+    for i in 0..data_zip.len() - 12 {
+        if &data_zip[i..i + 12] == b"zipped_a.txt" {
+            data_zip[i..i + 12].copy_from_slice(b"./a\\b//c.txt");
+        }
+    }
+    extract_to_dir(&data_zip[..], &temp.0).unwrap();
+    assert_eq!(
+        fs::read(temp.0.join("a/b/c.txt")).unwrap(),
+        fs::read("tests/assets/uncompressed/zipped_a.txt").unwrap()
+    );
+}
+
+#[test]
+fn test_truncated_archive() {
+    let temp = TempDir::new("truncated_archive");
+    let data_zip = fs::read("tests/assets/zip/zipped_ab.zip").unwrap();
+
+    match extract_to_dir(&data_zip[..150], &temp.0) {
+        Err(ExtractError::Stream(Error::Zip(ZipError::TruncatedArchive { entry, .. }))) => {
+            assert_eq!(entry, Some(1))
+        }
+        result => panic!("Unexpected result: {:?}", result),
+    }
+    // The entries before the truncation are extracted.
+    assert_eq!(list_dir(&temp.0), ["zipped_a.txt"]);
+}
@@ -0,0 +1,94 @@
+extern crate futures;
+extern crate stream_zipper;
+
+use std::fs;
+use std::io;
+
+use futures::executor::block_on_stream;
+use futures::stream::{self, StreamExt};
+use stream_zipper::futures::{DecompressStream, OutputChunk};
+use stream_zipper::test_util::{read_chunks, Entry};
+use stream_zipper::{start_stream, DriveError};
+
+/// Collects the entries of the output of a `DecompressStream`,
+/// checking that the chunks are in order.
+fn collect_entries(chunks: Vec<Result<OutputChunk, DriveError>>) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    let mut current: Option<Entry> = None;
+    for chunk in chunks {
+        match chunk.unwrap() {
+            OutputChunk::EntryStart(metadata) => {
+                assert!(current.is_none());
+                current = Some(Entry {
+                    name: metadata.name,
+                    data: Vec::new(),
+                });
+            }
+            OutputChunk::Data(data) => current.as_mut().unwrap().data.extend(data),
+            OutputChunk::EntryEnd => entries.push(current.take().unwrap()),
+        }
+    }
+    assert!(current.is_none());
+    entries
+}
+
+#[test]
+fn test_decompress_stream() {
+    for path in &[
+        "tests/assets/zip/comments.zip",
+        "tests/assets/zip/stored_descriptors.zip",
+        "tests/assets/tar/ustar.tar.gz",
+        "tests/assets/gzip/repetitive_data.txt.gz",
+        "tests/assets/cpio/newc.cpio",
+        "tests/assets/bzip2/concatenated.bz2",
+    ] {
+        let input = fs::read(path).unwrap();
+        let expected = read_chunks(&[&input]).unwrap();
+        for chunk_size in &[7, 1000, input.len()] {
+            let chunks = stream::iter(input.chunks(*chunk_size));
+            let output = block_on_stream(DecompressStream::new(start_stream(), chunks)).collect();
+            assert_eq!(collect_entries(output), expected, "{}", path);
+        }
+    }
+}
+
+#[test]
+fn test_decompress_stream_combinators() {
+    let input = fs::read("tests/assets/zip/zipped_ab.zip").unwrap();
+    let expected = read_chunks(&[&input]).unwrap();
+
+    // The input may be any chunks that can be viewed as bytes.
+    let chunks = stream::iter(input.chunks(10).map(<[u8]>::to_vec));
+    let names: Vec<_> =
+        block_on_stream(DecompressStream::new(start_stream(), chunks).filter_map(entry_start_name))
+            .collect();
+    let expected_names: Vec<_> = expected.into_iter().map(|entry| entry.name).collect();
+    assert_eq!(names, expected_names);
+}
+
+/// The name of the entry that starts, if the chunk starts an entry.
+fn entry_start_name(
+    chunk: Result<OutputChunk, DriveError>,
+) -> futures::future::Ready<Option<Option<Vec<u8>>>> {
+    futures::future::ready(match chunk.unwrap() {
+        OutputChunk::EntryStart(metadata) => Some(metadata.name),
+        _ => None,
+    })
+}
+
+#[test]
+fn test_decompress_stream_errors() {
+    let input = fs::read("tests/assets/gzip/repetitive_data.txt.gz").unwrap();
+    let chunks = stream::iter(input[..input.len() / 2].chunks(100));
+    let output: Vec<_> = block_on_stream(DecompressStream::new(start_stream(), chunks)).collect();
+    // The stream ends after the error.
+    match output.last() {
+        Some(Err(DriveError::Io(e))) => assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+    assert!(output[..output.len() - 1].iter().all(Result::is_ok));
+
+    let chunks = stream::iter(vec![&b"not an archive"[..]]);
+    let output: Vec<_> = block_on_stream(DecompressStream::new(start_stream(), chunks)).collect();
+    assert!(matches!(output[..], [Err(DriveError::Stream(_))]));
+}
@@ -1,10 +1,12 @@
 extern crate stream_zipper;
 
 use std::fs;
+use std::time::{Duration, UNIX_EPOCH};
 
-use stream_zipper::deflate::{self, *};
-use stream_zipper::gzip::headers::*;
-use stream_zipper::gzip::*;
+use stream_zipper::deflate;
+use stream_zipper::gzip::{self, headers::*, GZipError};
+use stream_zipper::test_util::{assert_every_split, read_chunks};
+use stream_zipper::{Error, State};
 
 #[test]
 fn test_parsing_member_header() {
@@ -17,6 +19,8 @@ fn test_parsing_member_header() {
         parsed_header,
         MemberHeader {
             os: 3,
+            extra_flags: 0,
+            text: false,
             mtime: 1523430128,
             filename: Some(b"rand_data.bin".to_vec()),
             fcomment: None,
@@ -36,6 +40,8 @@ fn test_decompression_rand_tiny() {
         parsed_header,
         MemberHeader {
             os: 3,
+            extra_flags: 0,
+            text: false,
             mtime: 1523857589,
             filename: Some(b"short_data.txt".to_vec()),
             fcomment: None,
@@ -45,7 +51,7 @@ fn test_decompression_rand_tiny() {
     let mut def = deflate::Stream::new();
     let mut out_pos = 0;
 
-    let unparsed_2 = if let deflate::State::Stop { unparsed_input } = def
+    let _unparsed_2 = if let deflate::State::Stop { unparsed_input } = def
         .inner_iter(unparsed_1, |out| {
             assert_eq!(out, &data_txt[out_pos..out_pos + out.len()]);
             out_pos += out.len();
@@ -70,6 +76,8 @@ fn test_decompression_rand_small() {
         parsed_header,
         MemberHeader {
             os: 3,
+            extra_flags: 0,
+            text: false,
             mtime: 1523430128,
             filename: Some(b"rand_data.bin".to_vec()),
             fcomment: None,
@@ -86,7 +94,7 @@ fn test_decompression_rand_small() {
         })
         .expect("Should be able to deflate");
 
-    let unparsed_2 = if let deflate::State::Stop { unparsed_input } = deflate_state {
+    let _unparsed_2 = if let deflate::State::Stop { unparsed_input } = deflate_state {
         unparsed_input
     } else {
         panic!("That should have been a full, complete stream!");
@@ -95,8 +103,6 @@ fn test_decompression_rand_small() {
 
 #[test]
 fn test_decompression_rand_big() {
-    use std::cmp::min;
-
     let data_zip = fs::read("tests/assets/gzip/big_rand_data.bin.gz").unwrap();
     let data_txt = fs::read("tests/assets/uncompressed/big_rand_data.bin").unwrap();
 
@@ -107,6 +113,8 @@ fn test_decompression_rand_big() {
         parsed_header,
         MemberHeader {
             os: 3,
+            extra_flags: 0,
+            text: false,
             mtime: 1523596293,
             filename: Some(b"big_rand_data.bin".to_vec()),
             fcomment: None,
@@ -116,7 +124,7 @@ fn test_decompression_rand_big() {
     let mut def = deflate::Stream::new();
     let mut out_pos = 0;
 
-    let unparsed_2 = if let deflate::State::Stop { unparsed_input } = def
+    let _unparsed_2 = if let deflate::State::Stop { unparsed_input } = def
         .inner_iter(unparsed_1, |out| {
             assert_eq!(out, &data_txt[out_pos..out_pos + out.len()]);
             out_pos += out.len();
@@ -131,8 +139,6 @@ fn test_decompression_rand_big() {
 #[test]
 #[ignore]
 fn test_decompression_huge() {
-    use std::cmp::min;
-
     let data_zip = fs::read("tests/assets/gzip/huge_repeat.bin.gz").unwrap();
 
     let (unparsed_1, parsed_header) =
@@ -142,6 +148,8 @@ fn test_decompression_huge() {
         parsed_header,
         MemberHeader {
             os: 3,
+            extra_flags: 0,
+            text: false,
             mtime: 1523863915,
             filename: Some(b"huge_repeat.bin".to_vec()),
             fcomment: None,
@@ -161,7 +169,7 @@ fn test_decompression_huge() {
 
     let end = std::time::Instant::now() - start;
 
-    let unparsed_2 = if let deflate::State::Stop { unparsed_input } = result {
+    let _unparsed_2 = if let deflate::State::Stop { unparsed_input } = result {
         unparsed_input
     } else {
         panic!("That should have been a full, complete stream!");
@@ -175,3 +183,265 @@ fn test_decompression_huge() {
         end.subsec_millis()
     );
 }
+
+#[test]
+fn test_last_modified() {
+    let data_gz = fs::read("tests/assets/gzip/short_data.txt.gz").unwrap();
+
+    let mut gzip_file = gzip::start_stream();
+    match gzip_file.read(&data_gz) {
+        Ok(State::HasOutput { .. }) => (),
+        state => panic!("Unexpected state: {:?}", state),
+    }
+    assert_eq!(
+        gzip_file.last_modified(),
+        Some(UNIX_EPOCH + Duration::from_secs(1523857589))
+    );
+
+    // A time of 0 means that there's none.
+    let header = MemberHeader {
+        os: 3,
+        extra_flags: 0,
+        text: false,
+        mtime: 0,
+        filename: None,
+        fcomment: None,
+    };
+    assert_eq!(header.last_modified(), None);
+}
+
+#[test]
+fn test_operating_system() {
+    let data_gz = fs::read("tests/assets/gzip/short_data.txt.gz").unwrap();
+    let (_, header) = MemberHeader::parse(&data_gz).unwrap();
+    assert_eq!(header.operating_system(), Os::Unix);
+
+    assert_eq!(Os::from(0), Os::Fat);
+    assert_eq!(Os::from(11), Os::Ntfs);
+    assert_eq!(Os::from(13), Os::AcornRiscos);
+    assert_eq!(Os::from(14), Os::Unknown(14));
+    assert_eq!(Os::from(255), Os::Unknown(255));
+}
+
+#[test]
+fn test_text_and_compression_hint() {
+    let data_gz = fs::read("tests/assets/gzip/short_data.txt.gz").unwrap();
+    let (_, header) = MemberHeader::parse(&data_gz).unwrap();
+    assert!(!header.text);
+    assert_eq!(header.compression_hint(), None);
+
+    // The header of ustar.tar.gz was written with the best compression.
+    let data_tgz = fs::read("tests/assets/tar/ustar.tar.gz").unwrap();
+    let (_, header) = MemberHeader::parse(&data_tgz).unwrap();
+    assert_eq!(header.compression_hint(), Some(CompressionHint::Best));
+
+    // The FTEXT flag is the lowest bit of the flags.
+    let mut data_gz = data_gz;
+    data_gz[3] |= 1;
+    data_gz[8] = 4;
+    let (_, header) = MemberHeader::parse(&data_gz).unwrap();
+    assert!(header.text);
+    assert_eq!(header.compression_hint(), Some(CompressionHint::Fastest));
+}
+
+#[test]
+fn test_header() {
+    let data_gz = fs::read("tests/assets/gzip/short_data.txt.gz").unwrap();
+    let expected = MemberHeader {
+        os: 3,
+        extra_flags: 0,
+        text: false,
+        mtime: 1523857589,
+        filename: Some(b"short_data.txt".to_vec()),
+        fcomment: None,
+    };
+
+    let mut gzip_file = gzip::start_stream();
+    assert_eq!(gzip_file.header(), None);
+    let mut input = &data_gz[..];
+    loop {
+        match gzip_file.read(input) {
+            Ok(State::HasOutput { unparsed_input, .. }) => {
+                assert_eq!(gzip_file.header(), Some(&expected));
+                input = unparsed_input;
+            }
+            Ok(State::NeedsInputOrEof(_)) => break,
+            state => panic!("Unexpected state: {:?}", state),
+        }
+    }
+    // The header is still there once the member has ended.
+    assert_eq!(gzip_file.header(), Some(&expected));
+}
+
+/// Reads short_data.txt.gz with the byte at `offset` from the end flipped.
+/// The footer is the CRC-32 followed by the size, 4 bytes each.
+fn with_footer_byte_flipped(offset_from_end: usize) -> Vec<u8> {
+    let mut data_gz = fs::read("tests/assets/gzip/short_data.txt.gz").unwrap();
+    let len = data_gz.len();
+    data_gz[len - offset_from_end] ^= 0xff;
+    data_gz
+}
+
+#[test]
+fn test_checksum_mismatch() {
+    let data_gz = with_footer_byte_flipped(8);
+    let error = GZipError::ChecksumMismatch {
+        expected: 0xa45f_a555,
+        actual: 0xa45f_a5aa,
+    };
+
+    assert_eq!(read_chunks(&[&data_gz]), Err(Error::GZip(error)));
+    assert_eq!(
+        error.to_string(),
+        "data checksum mismatch: expected a45fa555, got a45fa5aa"
+    );
+    assert_every_split(&data_gz, |chunks| {
+        read_chunks(chunks).map_err(|e| e == Error::GZip(error))
+    });
+}
+
+#[test]
+fn test_size_mismatch() {
+    let data_gz = with_footer_byte_flipped(4);
+
+    assert_eq!(
+        read_chunks(&[&data_gz]),
+        Err(Error::GZip(GZipError::SizeMismatch {
+            expected: 60 ^ 0xff,
+            actual: 60,
+        }))
+    );
+}
+
+#[test]
+fn test_concatenate_members() {
+    let data_gz = [
+        fs::read("tests/assets/gzip/short_data.txt.gz").unwrap(),
+        fs::read("tests/assets/gzip/zipped_a.txt.gz").unwrap(),
+    ]
+    .concat();
+    let data = [
+        fs::read("tests/assets/uncompressed/short_data.txt").unwrap(),
+        fs::read("tests/assets/uncompressed/zipped_a.txt").unwrap(),
+    ]
+    .concat();
+
+    let options = gzip::Options::default().concatenate_members(true);
+    let mut gzip_file = gzip::start_stream_with(options);
+    let mut output = Vec::new();
+    let state = gzip_file
+        .read_with(&data_gz, |out| output.extend_from_slice(out))
+        .unwrap();
+    assert!(matches!(state, State::NeedsInputOrEof(_)));
+    assert_eq!(output, data);
+    // The header is that of the last member.
+    assert_eq!(gzip_file.filename(), Some(&b"zipped_a.txt"[..]));
+
+    // By default, the second member is a file of its own.
+    let mut gzip_file = gzip::start_stream();
+    let state = gzip_file.read_with(&data_gz, |_| ()).unwrap();
+    assert!(matches!(state, State::NextFile { .. }));
+}
+
+#[test]
+fn test_trailing_data() {
+    let data_gz = fs::read("tests/assets/gzip/short_data.txt.gz").unwrap();
+    let data = fs::read("tests/assets/uncompressed/short_data.txt").unwrap();
+    // Padded with zeros to a block, and with bytes that start like a member at first.
+    let padding = [&[0; 512][..], &b"\x1f\x8bgarbage"[..]];
+
+    for padding in &padding {
+        let padded = [&data_gz[..], padding].concat();
+        let read_padded = |chunks: &[&[u8]]| {
+            let options = gzip::Options::default().allow_trailing_data(true);
+            let mut gzip_file = gzip::start_stream_with(options);
+            let mut output = Vec::new();
+            for (i, chunk) in chunks.iter().enumerate() {
+                match gzip_file.read_with(chunk, |out| output.extend_from_slice(out)) {
+                    Ok(State::NeedsInput) => (),
+                    // The member ended, and the trailing data starts in the next chunk.
+                    Ok(State::NeedsInputOrEof(next_file)) => gzip_file = next_file,
+                    Ok(State::EndOfFile) => {
+                        // The chunks after the end aren't read.
+                        let trailing =
+                            [gzip_file.trailing_data(), &chunks[i + 1..].concat()].concat();
+                        return Ok((output, trailing));
+                    }
+                    Ok(state) => panic!("Unexpected state: {:?}", state),
+                    Err(e) => return Err(e),
+                }
+            }
+            panic!("The stream didn't end");
+        };
+
+        assert_eq!(
+            read_padded(&[&padded]),
+            Ok((data.clone(), padding.to_vec()))
+        );
+        assert_every_split(&padded, read_padded);
+
+        // By default, the padding is taken for the header of another member.
+        match read_chunks(&[&padded]) {
+            Err(Error::GZip(GZipError::InvalidMemberHeader(context))) => {
+                assert_eq!(context.offset(), data_gz.len() as u64);
+                assert!(padding.starts_with(context.found()));
+            }
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
+}
+
+#[test]
+fn test_encoder() {
+    let data_gz = fs::read("tests/assets/gzip/short_data.txt.gz").unwrap();
+    let (unparsed, header) = MemberHeader::parse(&data_gz).unwrap();
+    assert_eq!(
+        header.to_bytes().unwrap(),
+        &data_gz[..data_gz.len() - unparsed.len()]
+    );
+
+    let data = fs::read("tests/assets/uncompressed/ultra_repetitive_data.txt").unwrap();
+    let header = MemberHeader {
+        mtime: 1523857589,
+        os: Os::Unix.into(),
+        filename: Some(b"ultra_repetitive_data.txt".to_vec()),
+        fcomment: Some(b"comment".to_vec()),
+        ..MemberHeader::default()
+    };
+    for &level in &[0, 6] {
+        let mut encoder = gzip::Encoder::with_level(&header, level).unwrap();
+        let mut compressed = Vec::new();
+        for chunk in data.chunks(10_000) {
+            encoder
+                .write(chunk, |out| compressed.extend_from_slice(out))
+                .unwrap();
+        }
+        assert_eq!(encoder.uncompressed_size(), data.len() as u64);
+        encoder
+            .finish(|out| compressed.extend_from_slice(out))
+            .unwrap();
+
+        let (_, parsed_header) = MemberHeader::parse(&compressed).unwrap();
+        assert_eq!(parsed_header, header);
+        let entries = read_chunks(&[&compressed]).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].data, data);
+    }
+
+    // An empty member.
+    let mut compressed = Vec::new();
+    gzip::Encoder::new(&MemberHeader::default())
+        .unwrap()
+        .finish(|out| compressed.extend_from_slice(out))
+        .unwrap();
+    let entries = read_chunks(&[&compressed]).unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].data, b"");
+
+    let header = MemberHeader {
+        filename: Some(b"a\0b".to_vec()),
+        ..MemberHeader::default()
+    };
+    assert_eq!(header.to_bytes(), None);
+    assert!(gzip::Encoder::new(&header).is_err());
+}
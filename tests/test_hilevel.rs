@@ -1,10 +1,18 @@
 extern crate stream_zipper;
 
 use std::fs;
+use std::io::{self, IoSlice, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, UNIX_EPOCH};
 
 use stream_zipper::gzip;
+use stream_zipper::test_util::{read_chunks, read_chunks_with, Entry};
 use stream_zipper::zip;
-use stream_zipper::{start_stream, State};
+use stream_zipper::{
+    start_stream, start_stream_with, start_stream_with_format, CompressedStream, Compression,
+    DriveError, EntryMetadata, Error, Event, Format, Options, ReadHeadersResult, State,
+    TryReadError,
+};
 
 #[test]
 fn test_hilevel_api_zip() {
@@ -155,7 +163,7 @@ fn test_local_huge_zip() {
 
     let mut zip_file_1 = zip::start_stream();
 
-    let res = zip_file_1
+    let _res = zip_file_1
         .read_with(&data_zip, |bytes| {
             println!("Yay. {}", bytes.len());
         })
@@ -179,11 +187,8 @@ fn test_with_zip_tsv_file() {
     let mut zipfile = stream_zipper::start_stream();
 
     let mut chunk_uncomp_bytes = 0;
-    let mut chunk_comp_bytes = 0;
 
     for chunk in file.chunks(64 * 1024) {
-        chunk_comp_bytes += chunk.len();
-
         let result = zipfile.read_internal_iter(chunk, |bytes| {
             chunk_uncomp_bytes += bytes.len();
         });
@@ -195,31 +200,28 @@ fn test_with_zip_tsv_file() {
         let (unparsed_input, mut next_file) = match result {
             Ok(State::NextFile {
                 unparsed_input,
-                mut next_file,
+                next_file,
             }) => (unparsed_input, next_file),
             _ => panic!("Expected the next file but got {:?}", result),
         };
 
         println!("Done? {:?}", unparsed_input);
 
-        assert_eq!(next_file.name().map(|b| b), Some(&b"__MACOSX/"[..]));
+        assert_eq!(next_file.name(), Some(&b"__MACOSX/"[..]));
         let result = next_file.read_internal_iter(unparsed_input, |input| println!("{:?}", input));
 
         let (unparsed_input, mut next_file) = match result {
             Ok(State::NextFile {
                 unparsed_input,
-                mut next_file,
+                next_file,
             }) => (unparsed_input, next_file),
             _ => panic!("Expected the next file but got {:?}", result),
         };
 
-        assert_eq!(
-            next_file.name().map(|b| b),
-            Some(&b"__MACOSX/._kyushu.tsv"[..])
-        );
+        assert_eq!(next_file.name(), Some(&b"__MACOSX/._kyushu.tsv"[..]));
         let result = next_file.read_internal_iter(unparsed_input, |input| println!("{:?}", input));
 
-        let result = match result {
+        match result {
             Ok(State::EndOfFile) => {
                 eprintln!("Success!");
             }
@@ -227,3 +229,990 @@ fn test_with_zip_tsv_file() {
         };
     }
 }
+
+#[test]
+fn test_concatenated_zip_archives() {
+    let mut data_zip = fs::read("tests/assets/zip/zipped_ab.zip").unwrap();
+    data_zip.extend(fs::read("tests/assets/zip/short_data.txt.zip").unwrap());
+
+    // Without the option, the stream ends after the first archive.
+    let mut zip_file = zip::start_stream();
+    let mut input = &data_zip[..];
+    loop {
+        match zip_file.read_with(input, |_| ()).expect("Should succeed") {
+            State::NextFile {
+                unparsed_input,
+                next_file,
+            } => {
+                input = unparsed_input;
+                zip_file = next_file;
+            }
+            State::EndOfFile => break,
+            state => panic!("Unexpected state: {:?}", state),
+        }
+    }
+
+    let options = zip::Options::default().concatenated_archives(true);
+    let mut zip_file = zip::start_stream_with(options);
+    let mut input = &data_zip[..];
+    let mut names = Vec::new();
+    let mut indices = Vec::new();
+    let mut archives = 1;
+    loop {
+        let state = zip_file.read_with(input, |_| ()).expect("Should succeed");
+        names.push(zip_file.filename().unwrap().to_vec());
+        indices.push(zip_file.entry_index());
+        match state {
+            State::NextFile {
+                unparsed_input,
+                next_file,
+            } => {
+                input = unparsed_input;
+                zip_file = next_file;
+            }
+            State::NextArchive {
+                unparsed_input,
+                next_file,
+            } => {
+                archives += 1;
+                input = unparsed_input;
+                zip_file = next_file;
+            }
+            State::NeedsInputOrEof(_) => break,
+            state => panic!("Unexpected state: {:?}", state),
+        }
+    }
+
+    assert_eq!(archives, 2);
+    assert_eq!(
+        names,
+        vec![
+            b"zipped_a.txt".to_vec(),
+            b"zipped_b.txt".to_vec(),
+            b"short_data.txt".to_vec()
+        ]
+    );
+    // The indices restart with the second archive.
+    assert_eq!(indices, vec![0, 1, 0]);
+}
+
+#[test]
+fn test_entry_index() {
+    let data_zip = fs::read("tests/assets/zip/rand_data_abc.zip").unwrap();
+
+    let mut file = start_stream();
+    let mut input = &data_zip[..];
+    let mut indices = Vec::new();
+    loop {
+        match file.read(input).expect("Should succeed") {
+            State::HasOutput { unparsed_input, .. } => input = unparsed_input,
+            State::NextFile {
+                unparsed_input,
+                next_file,
+            } => {
+                indices.push(file.entry_index());
+                input = unparsed_input;
+                file = next_file;
+            }
+            State::EndOfFile => {
+                indices.push(file.entry_index());
+                break;
+            }
+            state => panic!("Unexpected state: {:?}", state),
+        }
+    }
+    assert_eq!(indices, vec![Some(0), Some(1), Some(2)]);
+
+    // Other formats don't track it.
+    let data_gz = fs::read("tests/assets/gzip/short_data.txt.gz").unwrap();
+    let mut file = start_stream();
+    file.read(&data_gz).expect("Should succeed");
+    assert_eq!(file.entry_index(), None);
+}
+
+#[test]
+fn test_drive() {
+    let data_zip = fs::File::open("tests/assets/zip/zipped_ab.zip").unwrap();
+    let mut expected = fs::read("tests/assets/uncompressed/zipped_a.txt").unwrap();
+    expected.extend(fs::read("tests/assets/uncompressed/zipped_b.txt").unwrap());
+
+    let mut buf = [0; 7];
+    let mut output = Vec::new();
+    let summary = stream_zipper::drive(data_zip, &mut buf, |chunk| {
+        output.extend_from_slice(chunk);
+        Ok(())
+    })
+    .expect("Should succeed");
+
+    assert_eq!(output, expected);
+    assert_eq!(summary.files, 2);
+    assert_eq!(summary.compressed_bytes, 366);
+    assert_eq!(summary.uncompressed_bytes, expected.len() as u64);
+
+    let data_gzip = fs::read("tests/assets/gzip/zipped_a.txt.gz").unwrap();
+    let mut buf = [0; 1024];
+    let mut output = Vec::new();
+    let summary = stream_zipper::drive(&data_gzip[..], &mut buf, |chunk| {
+        output.extend_from_slice(chunk);
+        Ok(())
+    })
+    .expect("Should succeed");
+
+    assert_eq!(
+        output,
+        fs::read("tests/assets/uncompressed/zipped_a.txt").unwrap()
+    );
+    assert_eq!(summary.files, 1);
+
+    // Truncated input can't end the stream
+    let err =
+        stream_zipper::drive(&data_gzip[..30], &mut buf, |_| Ok(())).expect_err("Should fail");
+    match err {
+        stream_zipper::DriveError::Io(e) => {
+            assert_eq!(e.kind(), std::io::ErrorKind::UnexpectedEof)
+        }
+        e => panic!("Unexpected error: {:?}", e),
+    }
+}
+
+#[test]
+fn test_last_modified() {
+    // The modification time of the first entry, or of the file.
+    let first_last_modified = |path: &str| {
+        let data = fs::read(path).unwrap();
+        let mut file = start_stream();
+        match file.read(&data) {
+            Ok(State::HasOutput { .. }) | Ok(State::NextFile { .. }) => (),
+            state => panic!("Unexpected state: {:?}", state.map(|_| ())),
+        }
+        file.last_modified()
+    };
+    let unix_time = |secs| Some(UNIX_EPOCH + Duration::from_secs(secs));
+
+    assert_eq!(
+        first_last_modified("tests/assets/gzip/short_data.txt.gz"),
+        unix_time(1_523_857_589)
+    );
+    assert_eq!(
+        first_last_modified("tests/assets/tar/ustar.tar.gz"),
+        unix_time(1_580_000_000)
+    );
+    assert_eq!(
+        first_last_modified("tests/assets/cpio/newc.cpio"),
+        unix_time(1_580_000_000)
+    );
+    assert_eq!(
+        first_last_modified("tests/assets/zlib/short_data.txt.zlib"),
+        None
+    );
+}
+
+#[test]
+fn test_metadata() {
+    // The metadata of the first entry, or of the file.
+    let first_metadata = |path: &str| {
+        let data = fs::read(path).unwrap();
+        let mut file = start_stream();
+        match file.read(&data) {
+            Ok(State::HasOutput { .. }) | Ok(State::NextFile { .. }) => (),
+            state => panic!("Unexpected state: {:?}", state.map(|_| ())),
+        }
+        file.metadata()
+    };
+
+    let zip = first_metadata("tests/assets/zip/comments.zip");
+    assert_eq!(zip.size_hint, Some(60));
+    assert_eq!(zip.compression, Compression::Deflate);
+    assert!(!zip.is_dir);
+    assert!(zip.name.is_some());
+
+    let tar = first_metadata("tests/assets/tar/ustar.tar.gz");
+    assert_eq!(tar.compression, Compression::Deflate);
+    assert_eq!(
+        tar.mtime,
+        Some(UNIX_EPOCH + Duration::from_secs(1_580_000_000))
+    );
+    assert!(tar.size_hint.is_some());
+
+    let cpio = first_metadata("tests/assets/cpio/newc.cpio");
+    assert_eq!(cpio.compression, Compression::None);
+    assert!(cpio.size_hint.is_some());
+
+    let zlib = first_metadata("tests/assets/zlib/short_data.txt.zlib");
+    assert_eq!(zlib.compression, Compression::Deflate);
+    assert_eq!(zlib.name, None);
+    assert_eq!(zlib.size_hint, None);
+    assert_eq!(zlib.mtime, None);
+}
+
+#[test]
+fn test_output_buffer_size() {
+    // The sizes of the chunks of output of the first entry or file.
+    let chunk_sizes = |path: &str, options: Options| {
+        let data = fs::read(path).unwrap();
+        let mut file = start_stream_with(options);
+        let mut input = &data[..];
+        let mut sizes = Vec::new();
+        while let State::HasOutput {
+            unparsed_input,
+            output,
+        } = file.read(input).unwrap()
+        {
+            sizes.push(output.len());
+            input = unparsed_input;
+        }
+        sizes
+    };
+    const SIZE: usize = 1024 * 1024;
+
+    for path in &[
+        "tests/assets/zip/ultra_repetitive_data.txt.zip",
+        "tests/assets/gzip/repetitive_data.txt.gz",
+    ] {
+        let default_sizes = chunk_sizes(path, Options::default());
+        let sizes = chunk_sizes(path, Options::default().output_buffer_size(SIZE));
+        assert_eq!(
+            sizes.iter().sum::<usize>(),
+            default_sizes.iter().sum::<usize>()
+        );
+        assert!(sizes.len() < default_sizes.len());
+        assert!(default_sizes.iter().all(|&size| size <= 32 * 1024));
+        assert!(sizes.iter().any(|&size| size > 32 * 1024));
+    }
+
+    // The size of the format options is used as well.
+    let options = Options::default().gzip(gzip::Options::default().output_buffer_size(SIZE));
+    let sizes = chunk_sizes("tests/assets/gzip/repetitive_data.txt.gz", options);
+    assert_eq!(sizes, vec![65536]);
+
+    // The size is kept when the options of the formats are set after it.
+    let options = Options::default()
+        .output_buffer_size(SIZE)
+        .gzip(gzip::Options::default());
+    let sizes = chunk_sizes("tests/assets/gzip/repetitive_data.txt.gz", options);
+    assert_eq!(sizes, vec![65536]);
+}
+
+/// A writer that accepts one byte at a time and fails after `limit` bytes.
+struct ShortWriter {
+    data: Vec<u8>,
+    limit: usize,
+}
+
+impl Write for ShortWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.data.len() == self.limit {
+            return Err(io::Error::other("full"));
+        }
+        self.data.extend_from_slice(&buf[..buf.len().min(1)]);
+        Ok(buf.len().min(1))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_copy_to() {
+    let data_zip = fs::read("tests/assets/zip/zipped_ab.zip").unwrap();
+    let expected = read_chunks(&[&data_zip]).unwrap();
+
+    let mut file = start_stream();
+    let mut input = &data_zip[..];
+    let mut contents = Vec::new();
+    loop {
+        let mut writer = ShortWriter {
+            data: Vec::new(),
+            limit: usize::MAX,
+        };
+        match file.copy_to(input, &mut writer).unwrap() {
+            State::NextFile {
+                unparsed_input,
+                next_file,
+            } => {
+                contents.push(writer.data);
+                file = next_file;
+                input = unparsed_input;
+            }
+            State::EndOfFile => {
+                contents.push(writer.data);
+                break;
+            }
+            state => panic!("Unexpected state: {:?}", state),
+        }
+    }
+    let expected: Vec<_> = expected.into_iter().map(|entry| entry.data).collect();
+    assert_eq!(contents, expected);
+
+    // The errors of the writer are passed through.
+    let mut writer = ShortWriter {
+        data: Vec::new(),
+        limit: 3,
+    };
+    let err = start_stream().copy_to(&data_zip, &mut writer).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::Other);
+    assert_eq!(writer.data, expected[0][..3]);
+
+    let err = start_stream()
+        .copy_to(b"not an archive", &mut io::sink())
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert!(matches!(
+        err.get_ref().and_then(|err| err.downcast_ref::<Error>()),
+        Some(Error::UnknownFileFormat)
+    ));
+}
+
+#[test]
+fn test_read_vectored() {
+    for path in &[
+        "tests/assets/zip/zipped_ab.zip",
+        "tests/assets/tar/ustar.tar.gz",
+    ] {
+        let input = fs::read(path).unwrap();
+        let expected = read_chunks(&[&input]).unwrap();
+
+        for split in &[1, 30, input.len() / 2] {
+            // The input wraps around in a ring buffer, and an empty slice is in between.
+            let (first, second) = input.split_at(*split);
+            let (second, third) = second.split_at(second.len() / 3);
+            let mut slices = [
+                IoSlice::new(first),
+                IoSlice::new(&[]),
+                IoSlice::new(second),
+                IoSlice::new(third),
+            ];
+            let mut bufs = &mut slices[..];
+
+            let mut entries = Vec::new();
+            let mut data = Vec::new();
+            let mut file = start_stream();
+            loop {
+                match file.read_vectored(&mut bufs).unwrap() {
+                    State::HasOutput {
+                        unparsed_input,
+                        output,
+                    } => {
+                        assert!(unparsed_input.is_empty());
+                        data.extend_from_slice(output);
+                    }
+                    State::NextFile { next_file, .. } => {
+                        entries.push(Entry {
+                            name: file.name().map(<[u8]>::to_vec),
+                            data: std::mem::take(&mut data),
+                        });
+                        file = next_file;
+                    }
+                    State::EndOfFile | State::NeedsInputOrEof(_) => {
+                        entries.push(Entry {
+                            name: file.name().map(<[u8]>::to_vec),
+                            data: std::mem::take(&mut data),
+                        });
+                        break;
+                    }
+                    state => panic!("Unexpected state: {:?}", state),
+                }
+            }
+            assert_eq!(entries, expected, "{} {}", path, split);
+        }
+    }
+
+    // The slices are consumed as a whole when more input is needed.
+    let input = fs::read("tests/assets/gzip/short_data.txt.gz").unwrap();
+    let mut slices = [IoSlice::new(&input[..3]), IoSlice::new(&input[3..6])];
+    let mut bufs = &mut slices[..];
+    assert!(matches!(
+        gzip::start_stream().read_vectored(&mut bufs),
+        Ok(State::NeedsInput)
+    ));
+    assert!(bufs.iter().all(|buf| buf.is_empty()));
+}
+
+#[test]
+fn test_read_from() {
+    for path in &[
+        "tests/assets/zip/zipped_ab.zip",
+        "tests/assets/tar/ustar.tar.gz",
+        "tests/assets/gzip/repetitive_data.txt.gz",
+    ] {
+        let input = fs::read(path).unwrap();
+        let expected = read_chunks(&[&input]).unwrap();
+
+        let mut entries = Vec::new();
+        let summary = start_stream()
+            .read_from(&mut &input[..], |event| match event {
+                Event::EntryStart(metadata) => entries.push(Entry {
+                    name: metadata.name.clone(),
+                    data: Vec::new(),
+                }),
+                Event::Data(data) => entries.last_mut().unwrap().data.extend_from_slice(data),
+                Event::EntryEnd => (),
+            })
+            .unwrap();
+        assert_eq!(entries, expected, "{}", path);
+        assert_eq!(summary.files, expected.len());
+        assert_eq!(summary.compressed_bytes, input.len() as u64);
+        let uncompressed: usize = expected.iter().map(|entry| entry.data.len()).sum();
+        assert_eq!(summary.uncompressed_bytes, uncompressed as u64);
+    }
+
+    let input = fs::read("tests/assets/gzip/repetitive_data.txt.gz").unwrap();
+    match start_stream().read_from(&mut &input[..input.len() / 2], |_| ()) {
+        Err(DriveError::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+}
+
+#[test]
+fn test_state_combinators() {
+    let input = fs::read("tests/assets/zip/zipped_ab.zip").unwrap();
+    let expected = read_chunks(&[&input]).unwrap();
+
+    let mut file = start_stream();
+    let mut input = &input[..];
+    let mut entries = Vec::new();
+    let mut data = Vec::new();
+    loop {
+        match file.read(input).unwrap().try_no_output() {
+            Err((unparsed_input, output)) => {
+                data.extend_from_slice(output);
+                input = unparsed_input;
+            }
+            Ok(state) => {
+                entries.push(Entry {
+                    name: file.name().map(<[u8]>::to_vec),
+                    data: std::mem::take(&mut data),
+                });
+                if state.is_end() {
+                    break;
+                }
+                if let State::NextFile { unparsed_input, .. } = state {
+                    input = unparsed_input;
+                }
+                file = state.into_next_file().unwrap();
+            }
+        }
+    }
+    assert_eq!(entries, expected);
+
+    let input = fs::read("tests/assets/gzip/short_data.txt.gz").unwrap();
+    let mut file = gzip::start_stream();
+    let (_, output) = file.read(&input).unwrap().into_output().unwrap();
+    assert!(!output.is_empty());
+    assert!(State::<()>::EndOfFile.into_output().is_none());
+    assert!(!State::<()>::NeedsInput.is_end());
+    assert_eq!(
+        State::<u8>::NeedsInputOrEof(1)
+            .map_file(u16::from)
+            .into_next_file(),
+        Some(1)
+    );
+}
+
+/// Decompresses the first file of `stream`, returning its metadata and output.
+fn read_first_file<S: CompressedStream>(
+    mut stream: S,
+    mut input: &[u8],
+) -> Result<(EntryMetadata, Vec<u8>), S::Error> {
+    let mut output = Vec::new();
+    loop {
+        match stream.read(input)? {
+            State::HasOutput {
+                unparsed_input,
+                output: out,
+            } => {
+                output.extend_from_slice(out);
+                input = unparsed_input;
+            }
+            State::NeedsInput => {
+                stream.finish()?;
+                break;
+            }
+            _ => break,
+        }
+    }
+    Ok((stream.metadata(), output))
+}
+
+#[test]
+fn test_compressed_stream_trait() {
+    let input = fs::read("tests/assets/zip/zipped_ab.zip").unwrap();
+    let expected = read_chunks(&[&input]).unwrap();
+    let (metadata, output) = read_first_file(zip::start_stream(), &input).unwrap();
+    assert_eq!(metadata.name, expected[0].name);
+    assert_eq!(output, expected[0].data);
+    let (metadata, output) = read_first_file(start_stream(), &input).unwrap();
+    assert_eq!(metadata.name, expected[0].name);
+    assert_eq!(output, expected[0].data);
+
+    let input = fs::read("tests/assets/gzip/short_data.txt.gz").unwrap();
+    let expected = read_chunks(&[&input]).unwrap();
+    let (metadata, output) = read_first_file(gzip::start_stream(), &input).unwrap();
+    assert_eq!(metadata.name, expected[0].name);
+    assert_eq!(output, expected[0].data);
+
+    // A truncated stream fails once the input ends.
+    let truncated = &input[..input.len() - 4];
+    assert_eq!(
+        read_first_file(gzip::start_stream(), truncated).unwrap_err(),
+        gzip::GZipError::TruncatedMember
+    );
+    assert!(matches!(
+        read_first_file(start_stream(), truncated),
+        Err(Error::GZip(gzip::GZipError::TruncatedMember))
+    ));
+    assert!(matches!(
+        read_first_file(start_stream(), &input[..3]),
+        Err(Error::UnknownFileFormat)
+    ));
+}
+
+#[test]
+fn test_format() {
+    let mut file = start_stream();
+    assert_eq!(file.format(), None);
+    assert!(matches!(file.read(b"PK"), Ok(State::NeedsInput)));
+    assert_eq!(file.format(), None);
+
+    for (path, format) in &[
+        ("tests/assets/zip/zipped_ab.zip", Format::Zip),
+        ("tests/assets/gzip/short_data.txt.gz", Format::GZip),
+        ("tests/assets/tar/ustar.tar.gz", Format::TarGz),
+        ("tests/assets/lzw/short_data.txt.Z", Format::Lzw),
+        ("tests/assets/cpio/newc.cpio", Format::Cpio),
+        ("tests/assets/ar/gnu.a", Format::Ar),
+    ] {
+        let input = fs::read(path).unwrap();
+        let mut file = start_stream();
+        file.read(&input).unwrap();
+        assert_eq!(file.format(), Some(*format), "{}", path);
+    }
+    assert_eq!(Format::TarGz.to_string(), "tar.gz");
+}
+
+#[test]
+fn test_start_stream_with_format() {
+    for path in &[
+        "tests/assets/zip/zipped_ab.zip",
+        "tests/assets/tar/ustar.tar.gz",
+        "tests/assets/cpio/newc.cpio",
+    ] {
+        let input = fs::read(path).unwrap();
+        let expected = read_chunks(&[&input]).unwrap();
+        let format = Format::from_extension(path).unwrap();
+        let options = Options::default().format(format);
+        assert_eq!(
+            read_chunks_with(options, &[&input]).unwrap(),
+            expected,
+            "{}",
+            path
+        );
+    }
+
+    // The first bytes aren't buffered, so input in another format fails right away.
+    let mut file = start_stream_with_format(Format::Zip);
+    assert_eq!(file.format(), Some(Format::Zip));
+    assert!(matches!(file.read(b"\x1f\x8b"), Err(Error::Zip(_))));
+
+    // Gzip streams are read as they are, even if they contain a tar archive.
+    let input = fs::read("tests/assets/tar/ustar.tar.gz").unwrap();
+    let mut file = start_stream_with_format(Format::GZip);
+    let mut output = Vec::new();
+    file.read_internal_iter(&input, |data| output.extend_from_slice(data))
+        .unwrap();
+    assert_eq!(output, fs::read("tests/assets/tar/ustar.tar").unwrap(),);
+    assert_eq!(file.format(), Some(Format::GZip));
+}
+
+#[test]
+fn test_format_from_extension() {
+    assert_eq!(Format::from_extension(".gz"), Some(Format::GZip));
+    assert_eq!(Format::from_extension("gz"), Some(Format::GZip));
+    assert_eq!(
+        Format::from_extension("logs/app.log.GZ"),
+        Some(Format::GZip)
+    );
+    assert_eq!(Format::from_extension("backup.tar.gz"), Some(Format::TarGz));
+    assert_eq!(Format::from_extension(".tar.gz"), Some(Format::TarGz));
+    assert_eq!(Format::from_extension("tar.gz"), Some(Format::TarGz));
+    assert_eq!(Format::from_extension("guitar.gz"), Some(Format::GZip));
+    assert_eq!(Format::from_extension("footar.gz"), Some(Format::GZip));
+    assert_eq!(Format::from_extension("backup.tgz"), Some(Format::TarGz));
+    assert_eq!(Format::from_extension("numbers.txt.Z"), Some(Format::Lzw));
+    assert_eq!(Format::from_extension("package.deb"), Some(Format::Ar));
+    assert_eq!(Format::from_extension("notes.txt"), None);
+    assert_eq!(Format::from_extension(""), None);
+}
+
+#[test]
+fn test_passthrough() {
+    let plain = fs::read("tests/assets/uncompressed/numbers.txt").unwrap();
+    let options = Options::default().passthrough_unknown(true);
+    let chunks: Vec<_> = plain.chunks(7).collect();
+    let entries = read_chunks_with(options.clone(), &chunks).unwrap();
+    assert_eq!(
+        entries,
+        vec![Entry {
+            name: None,
+            data: plain.clone(),
+        }]
+    );
+
+    let mut file = start_stream_with(options);
+    file.read(&plain).unwrap();
+    assert_eq!(file.format(), Some(Format::Passthrough));
+
+    assert!(matches!(
+        read_chunks(&chunks),
+        Err(Error::UnknownFileFormat)
+    ));
+}
+
+#[test]
+fn test_read_entries() {
+    let input = fs::read("tests/assets/zip/zipped_ab.zip").unwrap();
+    let expected = read_chunks(&[&input]).unwrap();
+
+    for chunk_size in &[10, input.len()] {
+        let mut entries: Vec<Entry> = Vec::new();
+        let mut file = start_stream();
+        for chunk in input.chunks(*chunk_size) {
+            let state = file
+                .read_entries(chunk, |file, output| {
+                    let name = file.name().map(<[u8]>::to_vec);
+                    match entries.last_mut() {
+                        Some(entry) if entry.name == name => entry.data.extend_from_slice(output),
+                        _ => entries.push(Entry {
+                            name,
+                            data: output.to_vec(),
+                        }),
+                    }
+                })
+                .unwrap();
+            match state {
+                State::NeedsInput => (),
+                State::EndOfFile => break,
+                state => panic!("Unexpected state: {:?}", state),
+            }
+        }
+        assert_eq!(entries, expected, "{}", chunk_size);
+    }
+}
+
+#[test]
+fn test_try_read_with() {
+    /// A sink that fails once it has been given more than `limit` bytes.
+    fn sink<'a>(
+        output: &'a mut Vec<u8>,
+        limit: usize,
+    ) -> impl FnMut(&[u8]) -> Result<(), io::Error> + 'a {
+        move |data| {
+            output.extend_from_slice(data);
+            if output.len() > limit {
+                Err(io::Error::other("sink full"))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    let input = fs::read("tests/assets/gzip/repetitive_data.txt.gz").unwrap();
+    let expected = read_chunks(&[&input]).unwrap().remove(0).data;
+
+    let mut output = Vec::new();
+    let state = gzip::start_stream()
+        .try_read_with(&input, sink(&mut output, usize::MAX))
+        .unwrap();
+    assert!(matches!(state, State::NeedsInputOrEof(_)));
+    assert_eq!(output, expected);
+
+    let mut output = Vec::new();
+    match gzip::start_stream().try_read_with(&input, sink(&mut output, 10)) {
+        Err(TryReadError::Callback(e)) => assert_eq!(e.to_string(), "sink full"),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+    assert!(output.len() < expected.len());
+
+    let mut output = Vec::new();
+    match start_stream().try_read_internal_iter(&input, sink(&mut output, 10)) {
+        Err(TryReadError::Callback(_)) => (),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+
+    let input = fs::read("tests/assets/zip/zipped_ab.zip").unwrap();
+    let mut output = Vec::new();
+    match zip::start_stream().try_read_with(&input[..100], sink(&mut output, usize::MAX)) {
+        Ok(State::NeedsInput) => (),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+    match gzip::start_stream().try_read_with(b"not a gzip member", sink(&mut output, 0)) {
+        Err(TryReadError::Stream(gzip::GZipError::InvalidMemberHeader(_))) => (),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+}
+
+#[test]
+fn test_entry_hooks() {
+    #[derive(Debug, PartialEq)]
+    enum Hook {
+        Start(Option<Vec<u8>>),
+        End(Option<Vec<u8>>, u64),
+    }
+
+    for path in &[
+        "tests/assets/zip/zipped_ab.zip",
+        "tests/assets/zip/stored_descriptors.zip",
+        "tests/assets/gzip/short_data.txt.gz",
+    ] {
+        let input = fs::read(path).unwrap();
+        let expected = read_chunks(&[&input]).unwrap();
+
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let (start_calls, end_calls) = (calls.clone(), calls.clone());
+        // The hooks are kept when the options of the formats are set after them.
+        let options = Options::default()
+            .on_entry_start(move |metadata| {
+                start_calls
+                    .lock()
+                    .unwrap()
+                    .push(Hook::Start(metadata.name.clone()))
+            })
+            .on_entry_end(move |metadata, totals| {
+                end_calls
+                    .lock()
+                    .unwrap()
+                    .push(Hook::End(metadata.name.clone(), totals.uncompressed_size))
+            })
+            .zip(zip::Options::default())
+            .gzip(gzip::Options::default());
+        let chunks: Vec<_> = input.chunks(10).collect();
+        assert_eq!(read_chunks_with(options, &chunks).unwrap(), expected);
+
+        let expected_calls: Vec<_> = expected
+            .iter()
+            .flat_map(|entry| {
+                vec![
+                    Hook::Start(entry.name.clone()),
+                    Hook::End(entry.name.clone(), entry.data.len() as u64),
+                ]
+            })
+            .collect();
+        assert_eq!(*calls.lock().unwrap(), expected_calls, "{}", path);
+    }
+
+    // The CRC-32 is the one that was checked.
+    let input = fs::read("tests/assets/gzip/short_data.txt.gz").unwrap();
+    let crc = Arc::new(Mutex::new(None));
+    let hook_crc = crc.clone();
+    let options = gzip::Options::default()
+        .on_entry_end(move |_, totals| *hook_crc.lock().unwrap() = Some(totals.crc_32));
+    let mut file = gzip::start_stream_with(options);
+    file.read_with(&input, |_| ()).unwrap();
+    let footer = &input[input.len() - 8..input.len() - 4];
+    assert_eq!(
+        *crc.lock().unwrap(),
+        Some(u32::from_le_bytes([
+            footer[0], footer[1], footer[2], footer[3]
+        ]))
+    );
+}
+
+#[test]
+fn test_progress() {
+    for path in &[
+        "tests/assets/zip/zipped_ab.zip",
+        "tests/assets/zip/stored_descriptors.zip",
+        "tests/assets/gzip/short_data.txt.gz",
+        "tests/assets/tar/ustar.tar.gz",
+    ] {
+        let input = fs::read(path).unwrap();
+        let expected = read_chunks(&[&input]).unwrap();
+
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let hook_reports = reports.clone();
+        // The hook is kept when the options of the formats are set after it.
+        let options = Options::default()
+            .on_progress(100, move |summary| {
+                hook_reports.lock().unwrap().push(*summary)
+            })
+            .zip(zip::Options::default())
+            .gzip(gzip::Options::default());
+        let mut file = start_stream_with(options);
+        let mut uncompressed_bytes = 0;
+        for chunk in input.chunks(10) {
+            let state = file
+                .read_entries(chunk, |file, output| {
+                    uncompressed_bytes += output.len() as u64;
+                    let progress = file.progress().unwrap();
+                    assert!(progress.uncompressed_bytes >= uncompressed_bytes);
+                })
+                .unwrap();
+            match state {
+                State::NeedsInput => (),
+                State::NeedsInputOrEof(next_file) => file = next_file,
+                State::EndOfFile => break,
+                state => panic!("Unexpected state: {:?}", state),
+            }
+        }
+
+        let progress = file.progress().unwrap();
+        assert_eq!(progress.compressed_bytes, input.len() as u64, "{}", path);
+        if path.ends_with(".tar.gz") {
+            // The gzip stream around the archive is a single member.
+            assert_eq!(progress.files, 1);
+            continue;
+        }
+        assert_eq!(progress.uncompressed_bytes, uncompressed_bytes, "{}", path);
+        assert_eq!(progress.files, expected.len(), "{}", path);
+
+        // The hook is called once for every 100 bytes of input.
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports.len(), input.len() / 100, "{}", path);
+        for (i, report) in reports.iter().enumerate() {
+            assert!(report.compressed_bytes >= (i as u64 + 1) * 100);
+            assert!(report.compressed_bytes < (i as u64 + 2) * 100);
+        }
+    }
+
+    // The input consumed by the headers is counted as well.
+    let input = fs::read("tests/assets/zip/zipped_ab.zip").unwrap();
+    let mut file = zip::start_stream();
+    match file.read_headers(&input).unwrap() {
+        ReadHeadersResult::Done { unparsed } => {
+            assert_eq!(
+                file.progress().compressed_bytes,
+                (input.len() - unparsed.len()) as u64
+            );
+            assert!(unparsed.len() < input.len());
+        }
+        ReadHeadersResult::NeedsInput => panic!("The headers are in the input"),
+    }
+}
+
+#[test]
+fn test_stop_conditions() {
+    let input = fs::read("tests/assets/zip/zipped_ab.zip").unwrap();
+    let expected = read_chunks(&[&input]).unwrap();
+    assert_eq!(expected.len(), 2);
+
+    let name = expected[0].name.clone().unwrap();
+    for options in [
+        Options::default().stop_after_entries(1),
+        Options::default().stop_after_entry(name),
+        // The condition is kept when the options of the formats are set after it.
+        Options::default()
+            .stop_after_entries(1)
+            .zip(zip::Options::default()),
+    ] {
+        let chunks: Vec<_> = input.chunks(10).collect();
+        assert_eq!(
+            read_chunks_with(options.clone(), &chunks).unwrap(),
+            expected[..1]
+        );
+
+        // The stream stops right after the entry, and stays stopped.
+        let mut file = start_stream_with(options);
+        let mut rest = &input[..];
+        loop {
+            match file.read_entries(rest, |_, _| ()).unwrap() {
+                State::Stopped => break,
+                State::NeedsInput => rest = &[],
+                state => panic!("Unexpected state: {:?}", state),
+            }
+        }
+        assert!(file.read(&[]).unwrap().is_end());
+        assert!(matches!(file.read(b"more input").unwrap(), State::Stopped));
+        assert!(file.progress().unwrap().compressed_bytes < input.len() as u64);
+        assert!(file.finish().is_ok());
+    }
+
+    // An entry that isn't there doesn't stop the stream.
+    let options = Options::default().stop_after_entry(&b"zipped_c.txt"[..]);
+    assert_eq!(read_chunks_with(options, &[&input]).unwrap(), expected);
+
+    // The output that reaches the limit is returned before stopping.
+    let input = fs::read("tests/assets/gzip/repetitive_data.txt.gz").unwrap();
+    let expected = read_chunks(&[&input]).unwrap();
+    let options = Options::default()
+        .gzip(gzip::Options::default().output_buffer_size(1024))
+        .stop_after_bytes(1);
+    let entries = read_chunks_with(options, &[&input]).unwrap();
+    assert_eq!(entries.len(), 1);
+    assert!(!entries[0].data.is_empty());
+    assert!(entries[0].data.len() < expected[0].data.len());
+    assert!(expected[0].data.starts_with(&entries[0].data));
+}
+
+#[test]
+fn test_misuse_doesnt_panic() {
+    // There's no output before the format has been detected.
+    let mut file = start_stream();
+    assert!(file.get_output().is_empty());
+    assert!(matches!(file.read(&[0x50]), Ok(State::NeedsInput)));
+    assert!(file.get_output().is_empty());
+
+    // The entry of a failed stream has no name.
+    let input = fs::read("tests/assets/zip/zipped_ab.zip").unwrap();
+    let mut zip_file = zip::start_stream_with(zip::Options::default().max_entry_size(1));
+    assert!(zip_file.read_with(&input, |_| ()).is_err());
+    assert_eq!(zip_file.filename(), None);
+    assert!(zip_file.header().is_none());
+
+    // Reading after the end returns the end again.
+    let input = fs::read("tests/assets/gzip/repetitive_data.txt.gz").unwrap();
+    let mut gzip_file = gzip::start_stream();
+    match gzip_file.read_with(&input, |_| ()).unwrap() {
+        State::NeedsInputOrEof(_) => (),
+        state => panic!("Unexpected state: {:?}", state),
+    }
+    assert!(matches!(gzip_file.read(&[]), Ok(State::EndOfFile)));
+    assert!(matches!(gzip_file.read(&[]), Ok(State::EndOfFile)));
+    assert!(matches!(gzip_file.read(&input), Ok(State::EndOfFile)));
+
+    // The state of a `HasOutput` is returned as an error.
+    let mut file = start_stream();
+    let (unparsed_input, output) = file.read(&input).unwrap().try_no_output().unwrap_err();
+    assert!(!output.is_empty());
+    assert!(unparsed_input.len() < input.len());
+}
+
+#[test]
+fn test_read_headers() {
+    let data_zip = fs::read("tests/assets/zip/zipped_ab.zip").unwrap();
+    let mut file = start_stream();
+    match file.read_headers(&data_zip).unwrap() {
+        ReadHeadersResult::Done { unparsed } => assert!(unparsed.len() < data_zip.len()),
+        ReadHeadersResult::NeedsInput => panic!("The headers are in the input"),
+    }
+
+    // The other formats fail instead of reading their headers.
+    let data_gz = fs::read("tests/assets/gzip/short_data.txt.gz").unwrap();
+    let mut file = start_stream();
+    assert_eq!(
+        file.read_headers(&data_gz).err(),
+        Some(Error::ReadHeadersUnsupported)
+    );
+}
+
+#[test]
+fn test_read_after_error() {
+    // The error is returned again, however the stream is read after it.
+    let mut data_zip = fs::read("tests/assets/zip/zipped_ab.zip").unwrap();
+    // The first extra field claims to be longer than the extra fields.
+    data_zip[44..46].copy_from_slice(&[0xff, 0x00]);
+    let mut zip_file = zip::start_stream();
+    let error = zip_file.read_with(&data_zip, |_| ()).unwrap_err();
+    assert_eq!(error, zip::ZipError::InvalidLocalFileHeaderExtraFields);
+    assert_eq!(zip_file.read(&data_zip).unwrap_err(), error);
+    assert_eq!(zip_file.read(&[]).unwrap_err(), error);
+    assert_eq!(zip_file.read_headers(&data_zip).err(), Some(error));
+
+    let data_gz = fs::read("tests/assets/gzip/short_data.txt.gz").unwrap();
+    let data = [&data_gz[..], b"\x1f\x8b\x09\x00"].concat();
+    let mut gzip_file = gzip::start_stream();
+    let error = gzip_file.read_with(&data, |_| ()).unwrap_err();
+    assert!(matches!(error, gzip::GZipError::InvalidMemberHeader(_)));
+    assert_eq!(gzip_file.read(&data).unwrap_err(), error);
+    assert_eq!(gzip_file.read(&[]).unwrap_err(), error);
+}
@@ -0,0 +1,408 @@
+extern crate stream_zipper;
+
+use std::fs;
+
+use stream_zipper::ar::ArError;
+use stream_zipper::bzip2::Bzip2Error;
+use stream_zipper::cpio::CpioError;
+use stream_zipper::gzip::headers::MemberHeader;
+use stream_zipper::gzip::{self, GZipError};
+use stream_zipper::lzw::LzwError;
+use stream_zipper::sevenz::SevenZError;
+use stream_zipper::test_util::{read_chunks, read_chunks_with};
+use stream_zipper::xz::XzError;
+use stream_zipper::zip::{self, ZipError};
+use stream_zipper::zlib::ZlibError;
+use stream_zipper::{Error, HeaderField, Options, ReadHeadersResult, State};
+
+/// A member of an ar archive named `name`, with its padding.
+fn ar_member(name: &str, data: &[u8]) -> Vec<u8> {
+    let header = format!(
+        "{:<16}{:<12}{:<6}{:<6}{:<8}{:<10}`\n",
+        name,
+        0,
+        0,
+        0,
+        644,
+        data.len()
+    );
+    let padding: &[u8] = if data.len() % 2 == 1 { b"\n" } else { b"" };
+    [header.as_bytes(), data, padding].concat()
+}
+
+#[test]
+fn test_max_entry_size() {
+    let data_zip = fs::read("tests/assets/zip/zipped_ab.zip").unwrap();
+    let expected = read_chunks(&[&data_zip]).unwrap();
+    let largest = expected.iter().map(|entry| entry.data.len()).max().unwrap() as u64;
+
+    // Entries of exactly the limit pass.
+    let options = Options::default().max_entry_size(largest);
+    assert_eq!(read_chunks_with(options, &[&data_zip]).unwrap(), expected);
+
+    let options = Options::default().max_entry_size(largest - 1);
+    match read_chunks_with(options, &[&data_zip]) {
+        Err(Error::Zip(e)) => assert_eq!(e.without_entry(), &ZipError::EntryTooLarge),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+    // The limit is kept when the options of the format are set after it.
+    let options = Options::default()
+        .max_entry_size(largest - 1)
+        .zip(zip::Options::default());
+    match read_chunks_with(options, &[&data_zip]) {
+        Err(Error::Zip(e)) => assert_eq!(e.without_entry(), &ZipError::EntryTooLarge),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+
+    let data_gz = fs::read("tests/assets/gzip/repetitive_data.txt.gz").unwrap();
+    let expected = read_chunks(&[&data_gz]).unwrap();
+    let size = expected[0].data.len() as u64;
+    let options = Options::default().max_entry_size(size);
+    assert_eq!(read_chunks_with(options, &[&data_gz]).unwrap(), expected);
+    let options = Options::default().max_entry_size(size - 1);
+    assert_eq!(
+        read_chunks_with(options, &[&data_gz]),
+        Err(Error::GZip(GZipError::MemberTooLarge))
+    );
+    let options = Options::default()
+        .max_entry_size(size - 1)
+        .gzip(gzip::Options::default());
+    assert_eq!(
+        read_chunks_with(options, &[&data_gz]),
+        Err(Error::GZip(GZipError::MemberTooLarge))
+    );
+}
+
+#[test]
+fn test_max_entry_size_bomb() {
+    // The output that exceeds the limit is never returned, and the rest
+    // of the data isn't decompressed.
+    const LIMIT: u64 = 1 << 20;
+
+    let data_zip = fs::read("tests/assets/zip/huge_repeat.bin.zip").unwrap();
+    let mut zip_file = zip::start_stream_with(zip::Options::default().max_entry_size(LIMIT));
+    let mut input = &data_zip[..];
+    let mut output_len = 0;
+    let error = loop {
+        match zip_file.read(input) {
+            Ok(State::HasOutput {
+                unparsed_input,
+                output,
+            }) => {
+                output_len += output.len() as u64;
+                input = unparsed_input;
+            }
+            Ok(state) => panic!("Unexpected state: {:?}", state),
+            Err(e) => break e,
+        }
+    };
+    assert_eq!(error.without_entry(), &ZipError::EntryTooLarge);
+    assert!(output_len <= LIMIT);
+    assert!(!input.is_empty());
+
+    let data_gz = fs::read("tests/assets/gzip/huge_repeat.bin.gz").unwrap();
+    let mut gzip_file = gzip::start_stream_with(gzip::Options::default().max_member_size(LIMIT));
+    let mut output_len = 0;
+    let result = gzip_file.read_with(&data_gz, |output| output_len += output.len() as u64);
+    assert_eq!(result.unwrap_err(), GZipError::MemberTooLarge);
+    assert!(output_len <= LIMIT);
+}
+
+#[test]
+fn test_max_entry_size_other_formats() {
+    let cases: Vec<(&str, Error)> = vec![
+        (
+            "tests/assets/zlib/repetitive_data.txt.zlib",
+            Error::Zlib(ZlibError::StreamTooLarge),
+        ),
+        (
+            "tests/assets/lzw/numbers.txt.Z",
+            Error::Lzw(LzwError::StreamTooLarge),
+        ),
+        (
+            "tests/assets/bzip2/repetitive_data.txt.bz2",
+            Error::Bzip2(Bzip2Error::StreamTooLarge),
+        ),
+        (
+            "tests/assets/xz/repetitive_data.txt.xz",
+            Error::Xz(XzError::StreamTooLarge),
+        ),
+        (
+            "tests/assets/cpio/newc.cpio",
+            Error::Cpio(CpioError::EntryTooLarge),
+        ),
+        ("tests/assets/ar/gnu.a", Error::Ar(ArError::MemberTooLarge)),
+        (
+            "tests/assets/sevenz/lzma2_solid.7z",
+            Error::SevenZ(SevenZError::EntryTooLarge),
+        ),
+    ];
+    for (path, error) in cases {
+        let data = fs::read(path).unwrap();
+        let expected = read_chunks(&[&data]).unwrap();
+        let largest = expected.iter().map(|entry| entry.data.len()).max().unwrap() as u64;
+
+        let options = Options::default().max_entry_size(largest);
+        assert_eq!(read_chunks_with(options, &[&data]).unwrap(), expected);
+        let options = Options::default().max_entry_size(largest - 1);
+        assert_eq!(
+            read_chunks_with(options, &[&data]),
+            Err(error.clone()),
+            "{}",
+            path
+        );
+        // The limit applies to the streams started in a given format as well.
+        let format = stream_zipper::Format::from_extension(path).unwrap();
+        let options = Options::default()
+            .format(format)
+            .max_entry_size(largest - 1);
+        assert_eq!(read_chunks_with(options, &[&data]), Err(error), "{}", path);
+    }
+}
+
+#[test]
+fn test_max_entry_size_zlib_and_deb_bombs() {
+    const LIMIT: u64 = 1 << 20;
+
+    // The Deflate data of the gzip member in a zlib stream.
+    let data_gz = fs::read("tests/assets/gzip/huge_repeat.bin.gz").unwrap();
+    let (deflate_data, _) = MemberHeader::parse(&data_gz).unwrap();
+    let data_zlib = [&b"\x78\x9c"[..], deflate_data].concat();
+    let options = Options::default().max_entry_size(LIMIT);
+    assert_eq!(
+        read_chunks_with(options, &[&data_zlib]),
+        Err(Error::Zlib(ZlibError::StreamTooLarge))
+    );
+
+    // A Debian package whose data.tar.gz decompresses to more than the limit.
+    let data_deb = [
+        &b"!<arch>\n"[..],
+        &ar_member("debian-binary", b"2.0\n"),
+        &ar_member("data.tar.gz", &data_gz),
+    ]
+    .concat();
+    let options = Options::default().max_entry_size(data_gz.len() as u64);
+    assert_eq!(
+        read_chunks_with(options, &[&data_deb]),
+        Err(Error::GZip(GZipError::MemberTooLarge))
+    );
+}
+
+#[test]
+fn test_max_compression_ratio() {
+    // The entry decompresses to about 505 times its compressed size.
+    let data_zip = fs::read("tests/assets/zip/ultra_repetitive_data.txt.zip").unwrap();
+    let expected = read_chunks(&[&data_zip]).unwrap();
+    let options = Options::default().max_compression_ratio(1000, 1 << 16);
+    assert_eq!(read_chunks_with(options, &[&data_zip]).unwrap(), expected);
+    let options = Options::default().max_compression_ratio(100, 1 << 16);
+    match read_chunks_with(options, &[&data_zip]) {
+        Err(Error::Zip(e)) => assert_eq!(e.without_entry(), &ZipError::CompressionRatioExceeded),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+    // The limit is kept when the options of the format are set after it.
+    let options = Options::default()
+        .max_compression_ratio(100, 1 << 16)
+        .zip(zip::Options::default());
+    match read_chunks_with(options, &[&data_zip]) {
+        Err(Error::Zip(e)) => assert_eq!(e.without_entry(), &ZipError::CompressionRatioExceeded),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+    // Entries up to the minimum size aren't checked.
+    let options = Options::default().max_compression_ratio(100, 1 << 20);
+    assert_eq!(read_chunks_with(options, &[&data_zip]).unwrap(), expected);
+
+    // The member decompresses to about 100 times its compressed size.
+    let data_gz = fs::read("tests/assets/gzip/repetitive_data.txt.gz").unwrap();
+    let expected = read_chunks(&[&data_gz]).unwrap();
+    let options = Options::default().max_compression_ratio(1000, 1 << 15);
+    assert_eq!(read_chunks_with(options, &[&data_gz]).unwrap(), expected);
+    let options = Options::default().max_compression_ratio(10, 1 << 15);
+    assert_eq!(
+        read_chunks_with(options, &[&data_gz]),
+        Err(Error::GZip(GZipError::CompressionRatioExceeded))
+    );
+}
+
+#[test]
+fn test_max_compression_ratio_other_formats() {
+    // The data decompresses to between 2 and 10 000 times its compressed size.
+    let cases: Vec<(&str, u64, Error)> = vec![
+        (
+            "tests/assets/zlib/repetitive_data.txt.zlib",
+            10,
+            Error::Zlib(ZlibError::CompressionRatioExceeded),
+        ),
+        (
+            "tests/assets/lzw/numbers.txt.Z",
+            1,
+            Error::Lzw(LzwError::CompressionRatioExceeded),
+        ),
+        (
+            "tests/assets/bzip2/repetitive_data.txt.bz2",
+            10,
+            Error::Bzip2(Bzip2Error::CompressionRatioExceeded),
+        ),
+        (
+            "tests/assets/xz/repetitive_data.txt.xz",
+            10,
+            Error::Xz(XzError::CompressionRatioExceeded),
+        ),
+        (
+            "tests/assets/sevenz/lzma2_solid.7z",
+            10,
+            Error::SevenZ(SevenZError::CompressionRatioExceeded),
+        ),
+        (
+            "tests/assets/ar/example_gz.deb",
+            1,
+            Error::GZip(GZipError::CompressionRatioExceeded),
+        ),
+        (
+            "tests/assets/ar/example_xz.deb",
+            1,
+            Error::Xz(XzError::CompressionRatioExceeded),
+        ),
+    ];
+    for (path, ratio, error) in cases {
+        let data = fs::read(path).unwrap();
+        let expected = read_chunks(&[&data]).unwrap();
+
+        let options = Options::default().max_compression_ratio(10_000, 1 << 10);
+        assert_eq!(read_chunks_with(options, &[&data]).unwrap(), expected);
+        let options = Options::default().max_compression_ratio(ratio, 1 << 10);
+        assert_eq!(
+            read_chunks_with(options, &[&data]),
+            Err(error.clone()),
+            "{}",
+            path
+        );
+        let format = stream_zipper::Format::from_extension(path).unwrap();
+        let options = Options::default()
+            .format(format)
+            .max_compression_ratio(ratio, 1 << 10);
+        assert_eq!(read_chunks_with(options, &[&data]), Err(error), "{}", path);
+    }
+
+    // Stored data never exceeds the ratio. The header of the 7z archive
+    // is compressed, but it's smaller than the minimum size.
+    for path in &["tests/assets/sevenz/copy.7z", "tests/assets/cpio/newc.cpio"] {
+        let data = fs::read(path).unwrap();
+        let options = Options::default().max_compression_ratio(1, 1 << 10);
+        assert_eq!(
+            read_chunks_with(options, &[&data]).unwrap(),
+            read_chunks(&[&data]).unwrap()
+        );
+    }
+}
+
+#[test]
+fn test_max_field_lengths() {
+    let data_zip = fs::read("tests/assets/zip/comments.zip").unwrap();
+    let expected = read_chunks(&[&data_zip]).unwrap();
+    let longest = expected
+        .iter()
+        .map(|entry| entry.name.as_ref().unwrap().len())
+        .max()
+        .unwrap();
+    let options = Options::default().max_filename_len(longest);
+    assert_eq!(read_chunks_with(options, &[&data_zip]).unwrap(), expected);
+    let options = Options::default().max_filename_len(longest - 1);
+    assert_eq!(
+        read_chunks_with(options, &[&data_zip]),
+        Err(Error::Zip(ZipError::FieldTooLong(HeaderField::Filename)))
+    );
+
+    // The longest comment is that of the central directory header of the second entry.
+    let options = Options::default().max_comment_len(31);
+    assert_eq!(read_chunks_with(options, &[&data_zip]).unwrap(), expected);
+    let options = Options::default().max_comment_len(30);
+    assert_eq!(
+        read_chunks_with(options, &[&data_zip]),
+        Err(Error::Zip(ZipError::FieldTooLong(HeaderField::Comment)))
+    );
+
+    let data_zip = fs::read("tests/assets/zip/zipped_ab.zip").unwrap();
+    let options = Options::default().max_extra_fields_len(0);
+    assert_eq!(
+        read_chunks_with(options, &[&data_zip]),
+        Err(Error::Zip(ZipError::FieldTooLong(HeaderField::ExtraFields)))
+    );
+    // The limits are kept when the options of the format are set after them.
+    let options = Options::default()
+        .max_extra_fields_len(0)
+        .zip(zip::Options::default());
+    assert_eq!(
+        read_chunks_with(options, &[&data_zip]),
+        Err(Error::Zip(ZipError::FieldTooLong(HeaderField::ExtraFields)))
+    );
+
+    let data_gz = fs::read("tests/assets/gzip/repetitive_data.txt.gz").unwrap();
+    let expected = read_chunks(&[&data_gz]).unwrap();
+    let len = expected[0].name.as_ref().unwrap().len();
+    // The header is parsed one byte at a time.
+    let chunks: Vec<_> = data_gz.chunks(1).collect();
+    let options = Options::default().max_filename_len(len);
+    assert_eq!(read_chunks_with(options, &chunks).unwrap(), expected);
+    let options = Options::default().max_filename_len(len - 1);
+    assert_eq!(
+        read_chunks_with(options, &chunks),
+        Err(Error::GZip(GZipError::FieldTooLong(HeaderField::Filename)))
+    );
+    let options = Options::default()
+        .max_filename_len(len - 1)
+        .gzip(gzip::Options::default());
+    assert_eq!(
+        read_chunks_with(options, &chunks),
+        Err(Error::GZip(GZipError::FieldTooLong(HeaderField::Filename)))
+    );
+}
+
+#[test]
+fn test_truncate_long_fields() {
+    let data_zip = fs::read("tests/assets/zip/comments.zip").unwrap();
+    let mut expected = read_chunks(&[&data_zip]).unwrap();
+    for entry in &mut expected {
+        entry.name.as_mut().unwrap().truncate(5);
+    }
+    let options = Options::default()
+        .max_filename_len(5)
+        .max_comment_len(5)
+        .truncate_long_fields(true);
+    assert_eq!(read_chunks_with(options, &[&data_zip]).unwrap(), expected);
+
+    // The extra fields that don't fit are dropped.
+    let data_zip = fs::read("tests/assets/zip/zipped_ab.zip").unwrap();
+    let mut zip_file = zip::start_stream_with(
+        zip::Options::default()
+            .max_extra_fields_len(0)
+            .truncate_long_fields(true),
+    );
+    assert!(matches!(
+        zip_file.read_headers(&data_zip),
+        Ok(ReadHeadersResult::Done { .. })
+    ));
+    assert!(zip_file.header().unwrap().extra_fields.is_empty());
+    let expected = read_chunks(&[&data_zip]).unwrap();
+    let options = Options::default()
+        .max_extra_fields_len(0)
+        .truncate_long_fields(true);
+    assert_eq!(read_chunks_with(options, &[&data_zip]).unwrap(), expected);
+
+    // The rest of a truncated gzip filename is skipped as it arrives.
+    let data_gz = fs::read("tests/assets/gzip/repetitive_data.txt.gz").unwrap();
+    let mut expected = read_chunks(&[&data_gz]).unwrap();
+    expected[0].name.as_mut().unwrap().truncate(5);
+    let chunks: Vec<_> = data_gz.chunks(3).collect();
+    let options = Options::default()
+        .max_filename_len(5)
+        .truncate_long_fields(true);
+    assert_eq!(read_chunks_with(options, &chunks).unwrap(), expected);
+    let mut gzip_file = gzip::start_stream_with(
+        gzip::Options::default()
+            .max_filename_len(5)
+            .truncate_long_fields(true),
+    );
+    gzip_file.read(&data_gz[..40]).unwrap();
+    assert_eq!(gzip_file.filename(), Some(&b"repet"[..]));
+}
@@ -0,0 +1,98 @@
+extern crate stream_zipper;
+
+use std::fs;
+
+use stream_zipper::lzw::{self, LzwError, StreamHeader};
+use stream_zipper::test_util::{assert_every_split, assert_random_splits, read_chunks, Entry};
+use stream_zipper::{Error, State};
+
+#[test]
+fn test_parsing_stream_header() {
+    let data_z = fs::read("tests/assets/lzw/numbers.txt.Z").unwrap();
+
+    let (_, header) = StreamHeader::parse(&data_z).unwrap();
+    assert_eq!(
+        header,
+        StreamHeader {
+            max_bits: 16,
+            block_mode: true,
+        }
+    );
+}
+
+#[test]
+fn test_decompression() {
+    let data_numbers = fs::read("tests/assets/uncompressed/numbers.txt").unwrap();
+    let data_short = fs::read("tests/assets/uncompressed/short_data.txt").unwrap();
+    let mut data_clears = fs::read("tests/assets/uncompressed/repetitive_data.txt").unwrap();
+    data_clears.extend(fs::read("tests/assets/uncompressed/zipped_a.txt").unwrap());
+
+    for &(name, ref data) in &[
+        ("numbers.txt.Z", data_numbers),
+        // Made without block mode.
+        ("short_data.txt.Z", data_short),
+        // Made with 10-bit codes, clearing the table whenever it fills up.
+        ("table_clears.Z", data_clears),
+    ] {
+        let data_z = fs::read(format!("tests/assets/lzw/{}", name)).unwrap();
+
+        let mut output = Vec::new();
+        let mut z_file = lzw::start_stream();
+        let state = z_file
+            .read_with(&data_z, |out| output.extend_from_slice(out))
+            .unwrap();
+        // The end of the input is the end of the file.
+        assert!(matches!(state, State::NeedsInput));
+        assert_eq!(&output, data, "{}", name);
+    }
+}
+
+#[test]
+fn test_autodetect() {
+    let data_z = fs::read("tests/assets/lzw/short_data.txt.Z").unwrap();
+    let data = fs::read("tests/assets/uncompressed/short_data.txt").unwrap();
+
+    assert_eq!(
+        read_chunks(&[&data_z]),
+        Ok(vec![Entry { name: None, data }])
+    );
+    assert_every_split(&data_z, read_chunks);
+
+    let data_z = fs::read("tests/assets/lzw/table_clears.Z").unwrap();
+    assert_random_splits(&data_z, 1516, 3, 1, read_chunks);
+    assert_random_splits(&data_z, 1516, 10, 300, read_chunks);
+}
+
+#[test]
+fn test_invalid_header() {
+    let mut data_z = fs::read("tests/assets/lzw/numbers.txt.Z").unwrap();
+    // Set a reserved flag.
+    data_z[2] |= 0x20;
+
+    assert_eq!(
+        read_chunks(&[&data_z]),
+        Err(Error::Lzw(LzwError::InvalidHeader))
+    );
+}
+
+#[test]
+fn test_invalid_code() {
+    let mut data_z = fs::read("tests/assets/lzw/numbers.txt.Z").unwrap();
+    // The first code must be a literal byte.
+    data_z[4] |= 0x01;
+
+    let res = lzw::start_stream().read_with(&data_z, |_| ());
+    assert_eq!(res.unwrap_err(), LzwError::InvalidStream);
+}
+
+#[test]
+fn test_read_after_error() {
+    let mut data_z = fs::read("tests/assets/lzw/numbers.txt.Z").unwrap();
+    data_z[4] |= 0x01;
+
+    let mut lzw_file = lzw::start_stream();
+    let res = lzw_file.read_with(&data_z, |_| ());
+    assert_eq!(res.unwrap_err(), LzwError::InvalidStream);
+    assert_eq!(lzw_file.read(&[]).unwrap_err(), LzwError::InvalidStream);
+    assert_eq!(lzw_file.read(&data_z).unwrap_err(), LzwError::InvalidStream);
+}
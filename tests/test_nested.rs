@@ -0,0 +1,150 @@
+extern crate stream_zipper;
+
+use std::fs;
+use std::sync::{Arc, Mutex};
+
+use stream_zipper::nested::{self, NestedState};
+use stream_zipper::test_util::{assert_every_split, assert_random_splits, Entry};
+use stream_zipper::{Error, Format, Options};
+
+/// Reads the stream with a nested stream limited to `max_depth`, returning
+/// the paths and the output of the entries. Consecutive output with
+/// the same path is counted as one entry.
+fn read_nested(chunks: &[&[u8]], max_depth: usize) -> Result<Vec<Entry>, Error> {
+    let mut file = nested::start_stream(max_depth);
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut callback = |path: &[u8], output: &[u8]| match entries.last_mut() {
+        Some(entry) if entry.name.as_deref() == Some(path) => entry.data.extend_from_slice(output),
+        _ => entries.push(Entry {
+            name: Some(path.to_vec()),
+            data: output.to_vec(),
+        }),
+    };
+    let mut state = NestedState::NeedsInput;
+    for chunk in chunks {
+        state = file.read(chunk, &mut callback)?;
+    }
+    assert_eq!(state, NestedState::EndOfFile);
+    Ok(entries)
+}
+
+fn entry(path: &str, data: &[u8]) -> Entry {
+    Entry {
+        name: Some(path.as_bytes().to_vec()),
+        data: data.to_vec(),
+    }
+}
+
+#[test]
+fn test_nested_archives() {
+    let data_zip = fs::read("tests/assets/zip/nested.zip").unwrap();
+
+    let expected = vec![
+        entry("readme.txt", b"read me\n"),
+        entry("inner.zip!inner.csv", b"a,b\n1,2\n"),
+        entry("inner.zip!deeper.zip!deep.txt", b"deep down\n"),
+        entry("data.csv.gz!data.csv", b"x,y\n3,4\n"),
+        // Without a filename, the gzip file is named by its entry.
+        entry("noname.gz", b"no name\n"),
+    ];
+    assert_eq!(read_nested(&[&data_zip], 2), Ok(expected));
+    assert_every_split(&data_zip, |chunks| read_nested(chunks, 2));
+    assert_random_splits(&data_zip, 1, 20, 7, |chunks| read_nested(chunks, 2));
+}
+
+#[test]
+fn test_depth_limit() {
+    let data_zip = fs::read("tests/assets/zip/nested.zip").unwrap();
+
+    // The archives below the limit are passed through as they are.
+    let entries = read_nested(&[&data_zip], 1).unwrap();
+    let paths: Vec<_> = entries
+        .iter()
+        .map(|entry| String::from_utf8(entry.name.clone().unwrap()).unwrap())
+        .collect();
+    assert_eq!(
+        paths,
+        [
+            "readme.txt",
+            "inner.zip!inner.csv",
+            "inner.zip!deeper.zip",
+            "data.csv.gz!data.csv",
+            "noname.gz",
+        ]
+    );
+    assert!(entries[2].data.starts_with(b"PK\x03\x04"));
+
+    let entries = read_nested(&[&data_zip], 0).unwrap();
+    assert_eq!(entries.len(), 4);
+    assert_eq!(entries[1].name.as_deref(), Some(&b"inner.zip"[..]));
+    assert!(entries[1].data.starts_with(b"PK\x03\x04"));
+    assert!(entries[2].data.starts_with(b"\x1f\x8b\x08"));
+}
+
+#[test]
+fn test_plain_archive() {
+    let data_zip = fs::read("tests/assets/zip/zipped_ab.zip").unwrap();
+    let data_a = fs::read("tests/assets/uncompressed/zipped_a.txt").unwrap();
+    let data_b = fs::read("tests/assets/uncompressed/zipped_b.txt").unwrap();
+
+    assert_eq!(
+        read_nested(&[&data_zip], 2),
+        Ok(vec![
+            entry("zipped_a.txt", &data_a),
+            entry("zipped_b.txt", &data_b),
+        ])
+    );
+}
+
+#[test]
+fn test_truncated_nested_archive() {
+    // The entry holds the first half of zipped_ab.zip.
+    let data_zip = fs::read("tests/assets/zip/nested_truncated.zip").unwrap();
+
+    match read_nested(&[&data_zip], 2) {
+        Err(Error::Zip(_)) => (),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+    // Below the limit, the entry is passed through as it is.
+    let entries = read_nested(&[&data_zip], 0).unwrap();
+    assert_eq!(entries.len(), 1);
+}
+
+#[test]
+fn test_options() {
+    let data_zip = fs::read("tests/assets/zip/nested.zip").unwrap();
+
+    // The hook is called for the entries at every level.
+    let names = Arc::new(Mutex::new(Vec::new()));
+    let hook_names = names.clone();
+    let options = Options::default()
+        .format(Format::Zip)
+        .on_entry_start(move |metadata| hook_names.lock().unwrap().push(metadata.name.clone()));
+    let mut file = nested::start_stream_with(options, 2);
+    assert_eq!(
+        file.read(&data_zip, &mut |_, _| ()),
+        Ok(NestedState::EndOfFile)
+    );
+    let names: Vec<_> = names
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|name| {
+            name.as_ref()
+                .map(|name| String::from_utf8_lossy(name).into_owned())
+        })
+        .collect();
+    let expected = [
+        Some("readme.txt"),
+        Some("inner.zip"),
+        Some("inner.csv"),
+        Some("deeper.zip"),
+        Some("deep.txt"),
+        Some("data.csv.gz"),
+        Some("data.csv"),
+        Some("noname.gz"),
+        // The gzip file in noname.gz has no filename.
+        None,
+    ];
+    assert_eq!(names, expected.map(|name| name.map(String::from)));
+}
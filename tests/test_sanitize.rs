@@ -0,0 +1,66 @@
+extern crate stream_zipper;
+
+use std::path::PathBuf;
+
+use stream_zipper::sanitize::{entry_path, is_safe_name};
+
+fn path(components: &[&str]) -> Option<PathBuf> {
+    Some(components.iter().collect())
+}
+
+#[test]
+fn test_entry_path() {
+    assert_eq!(entry_path(b"dir/file.txt"), path(&["dir", "file.txt"]));
+    // Windows-style separators, and `.` and empty components.
+    assert_eq!(
+        entry_path(b".\\dir\\\\sub/./file.txt"),
+        path(&["dir", "sub", "file.txt"])
+    );
+    assert_eq!(entry_path(b"dir/"), path(&["dir"]));
+    assert_eq!(entry_path(b"./"), path(&[]));
+    // Dots that aren't whole components.
+    assert_eq!(
+        entry_path(b"..dir/file..txt"),
+        path(&["..dir", "file..txt"])
+    );
+}
+
+#[test]
+fn test_absolute_names_are_made_relative() {
+    assert_eq!(entry_path(b"/etc/passwd"), path(&["etc", "passwd"]));
+    assert_eq!(
+        entry_path(b"C:\\Windows\\win.ini"),
+        path(&["Windows", "win.ini"])
+    );
+    assert_eq!(entry_path(b"c:file.txt"), path(&["file.txt"]));
+    assert_eq!(
+        entry_path(b"\\\\server\\share\\file.txt"),
+        path(&["server", "share", "file.txt"])
+    );
+}
+
+#[test]
+fn test_traversal_is_rejected() {
+    assert_eq!(entry_path(b"../file.txt"), None);
+    assert_eq!(entry_path(b"dir/../../file.txt"), None);
+    assert_eq!(entry_path(b"dir\\..\\file.txt"), None);
+    assert_eq!(entry_path(b"C:..\\file.txt"), None);
+    // Alternate data streams and drive letters after the start.
+    assert_eq!(entry_path(b"file.txt:stream"), None);
+    assert_eq!(entry_path(b"dir/C:/file.txt"), None);
+    assert_eq!(entry_path(b"file\0.txt"), None);
+}
+
+#[test]
+fn test_is_safe_name() {
+    assert!(is_safe_name(b"dir/file.txt"));
+    assert!(is_safe_name(b"./dir\\file.txt"));
+
+    assert!(!is_safe_name(b""));
+    assert!(!is_safe_name(b"/etc/passwd"));
+    assert!(!is_safe_name(b"\\file.txt"));
+    assert!(!is_safe_name(b"C:/file.txt"));
+    assert!(!is_safe_name(b"c:file.txt"));
+    assert!(!is_safe_name(b"dir/../../file.txt"));
+    assert!(!is_safe_name(b"file.txt:stream"));
+}
@@ -0,0 +1,183 @@
+extern crate stream_zipper;
+
+use std::fs;
+
+use stream_zipper::sevenz::headers::{NextHeader, StartHeader, SIGNATURE_HEADER_SIZE};
+use stream_zipper::sevenz::{self, SevenZError};
+use stream_zipper::test_util::{assert_every_split, assert_random_splits, read_chunks, Entry};
+use stream_zipper::utils::update_crc32;
+use stream_zipper::{Error, State};
+
+fn expected_entries() -> Vec<Entry> {
+    let entry = |name: &str, path: Option<&str>| Entry {
+        name: Some(name.as_bytes().to_vec()),
+        data: path.map_or(Vec::new(), |path| fs::read(path).unwrap()),
+    };
+
+    vec![
+        entry("dir", None),
+        entry(
+            "dir/short_data.txt",
+            Some("tests/assets/uncompressed/short_data.txt"),
+        ),
+        entry(
+            "repetitive_data.txt",
+            Some("tests/assets/uncompressed/repetitive_data.txt"),
+        ),
+        entry("empty.txt", None),
+        entry(
+            "zipped_ä.txt",
+            Some("tests/assets/uncompressed/zipped_a.txt"),
+        ),
+    ]
+}
+
+/// Recomputes the CRC-32 of the header after it has been modified.
+fn fix_header_crc(data: &mut [u8]) {
+    let start_header = StartHeader::parse(&data[..SIGNATURE_HEADER_SIZE]).unwrap();
+    let header_start = SIGNATURE_HEADER_SIZE + start_header.next_header_offset as usize;
+    let crc = update_crc32(0, &data[header_start..]);
+    data[28..32].copy_from_slice(&crc.to_le_bytes());
+    let start_header_crc = update_crc32(0, &data[12..32]);
+    data[8..12].copy_from_slice(&start_header_crc.to_le_bytes());
+}
+
+#[test]
+fn test_parsing_header() {
+    let data_7z = fs::read("tests/assets/sevenz/lzma.7z").unwrap();
+
+    let start_header = StartHeader::parse(&data_7z[..SIGNATURE_HEADER_SIZE]).unwrap();
+    let header_start = SIGNATURE_HEADER_SIZE + start_header.next_header_offset as usize;
+    assert_eq!(
+        header_start + start_header.next_header_size as usize,
+        data_7z.len()
+    );
+
+    let header = match NextHeader::parse(&data_7z[header_start..]).unwrap() {
+        NextHeader::Header(header) => header,
+        NextHeader::Encoded(_) => panic!("the header isn't encoded"),
+    };
+    // Not solid: each file with data is a folder of its own.
+    assert_eq!(header.streams.folders.len(), 3);
+    let dir = &header.entries[0];
+    assert!(dir.is_dir && !dir.has_stream);
+    assert_eq!(dir.attributes, Some(0x10));
+    let empty = &header.entries[3];
+    assert!(!empty.is_dir && !empty.has_stream);
+    let short = &header.entries[1];
+    assert_eq!(short.name, b"dir/short_data.txt");
+    assert_eq!(short.size, 60);
+    assert_eq!(short.modified, Some(132_223_104_010_000_000));
+}
+
+#[test]
+fn test_lzma2_solid() {
+    // The files are compressed together and the header is compressed too.
+    let data_7z = fs::read("tests/assets/sevenz/lzma2_solid.7z").unwrap();
+
+    assert_eq!(read_chunks(&[&data_7z]), Ok(expected_entries()));
+    assert_every_split(&data_7z, read_chunks);
+}
+
+#[test]
+fn test_lzma() {
+    let data_7z = fs::read("tests/assets/sevenz/lzma.7z").unwrap();
+
+    assert_eq!(read_chunks(&[&data_7z]), Ok(expected_entries()));
+    assert_random_splits(&data_7z, 1519, 20, 100, read_chunks);
+}
+
+#[test]
+fn test_copy() {
+    let data_7z = fs::read("tests/assets/sevenz/copy.7z").unwrap();
+
+    let mut names = Vec::new();
+    let mut file = sevenz::start_stream();
+    let mut input = &data_7z[..];
+    loop {
+        match file.read_with(input, |_| ()).unwrap() {
+            State::NextFile {
+                unparsed_input,
+                next_file,
+            } => {
+                names.push(file.filename().unwrap().to_vec());
+                file = next_file;
+                input = unparsed_input;
+            }
+            State::EndOfFile => break,
+            state => panic!("unexpected state: {:?}", state),
+        }
+    }
+    names.push(file.filename().unwrap().to_vec());
+    assert_eq!(file.entry().unwrap().size, 9505);
+
+    let expected: Vec<_> = expected_entries()
+        .into_iter()
+        .map(|entry| entry.name.unwrap())
+        .collect();
+    assert_eq!(names, expected);
+    assert_random_splits(&data_7z, 1519, 10, 5000, read_chunks);
+}
+
+#[test]
+fn test_checksum_mismatch() {
+    let mut data_7z = fs::read("tests/assets/sevenz/copy.7z").unwrap();
+    // Change the data of the first file.
+    data_7z[SIGNATURE_HEADER_SIZE] ^= 1;
+
+    assert_eq!(
+        read_chunks(&[&data_7z]),
+        Err(Error::SevenZ(SevenZError::ChecksumMismatch))
+    );
+}
+
+#[test]
+fn test_unsupported_coder() {
+    let mut data_7z = fs::read("tests/assets/sevenz/lzma.7z").unwrap();
+    // Change the LZMA coders to PPMd.
+    let lzma_coder = b"\x23\x03\x01\x01";
+    let mut i = 0;
+    while i + lzma_coder.len() <= data_7z.len() {
+        if &data_7z[i..i + lzma_coder.len()] == lzma_coder {
+            data_7z[i + 2..i + 4].copy_from_slice(b"\x04\x01");
+        }
+        i += 1;
+    }
+    fix_header_crc(&mut data_7z);
+
+    assert_eq!(
+        read_chunks(&[&data_7z]),
+        Err(Error::SevenZ(SevenZError::UnsupportedCoder))
+    );
+}
+
+#[test]
+fn test_read_after_error() {
+    let mut data_7z = fs::read("tests/assets/sevenz/copy.7z").unwrap();
+    data_7z[SIGNATURE_HEADER_SIZE] ^= 1;
+
+    let mut sevenz_file = sevenz::start_stream();
+    let mut input = &data_7z[..];
+    let error = loop {
+        match sevenz_file.read_with(input, |_| ()) {
+            Ok(State::NextFile {
+                unparsed_input,
+                next_file,
+            }) => {
+                input = unparsed_input;
+                sevenz_file = next_file;
+            }
+            Ok(state) => panic!("Unexpected state: {:?}", state),
+            Err(e) => break e,
+        }
+    };
+    assert_eq!(error, SevenZError::ChecksumMismatch);
+    assert_eq!(
+        sevenz_file.read(&[]).unwrap_err(),
+        SevenZError::ChecksumMismatch
+    );
+    assert_eq!(
+        sevenz_file.read(&data_7z).unwrap_err(),
+        SevenZError::ChecksumMismatch
+    );
+}
@@ -0,0 +1,180 @@
+extern crate stream_zipper;
+
+use std::fs;
+
+use stream_zipper::tar::headers::{EntryType, Format, Header};
+use stream_zipper::tar::{self, TarError};
+use stream_zipper::test_util::{assert_every_split, assert_random_splits};
+use stream_zipper::State;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct TarEntry {
+    name: Vec<u8>,
+    entry_type: EntryType,
+    link_name: Vec<u8>,
+    data: Vec<u8>,
+}
+
+/// Feeds `chunks` in order to a tar stream and collects the entries.
+fn read_entries(chunks: &[&[u8]]) -> Result<Vec<TarEntry>, TarError> {
+    let mut entries = Vec::new();
+    let mut data = Vec::new();
+    let mut file = tar::start_stream();
+
+    let mut push_entry = |file: &tar::TarFile, data: &mut Vec<u8>| {
+        if let Some(header) = file.header() {
+            entries.push(TarEntry {
+                name: header.name.clone(),
+                entry_type: header.entry_type,
+                link_name: header.link_name.clone(),
+                data: std::mem::take(data),
+            });
+        }
+    };
+
+    for chunk in chunks {
+        let mut input = *chunk;
+        loop {
+            match file.read(input)? {
+                State::HasOutput {
+                    unparsed_input,
+                    output,
+                } => {
+                    data.extend_from_slice(output);
+                    input = unparsed_input;
+                }
+                State::NeedsInput => break,
+                State::NextFile {
+                    unparsed_input,
+                    next_file,
+                } => {
+                    push_entry(&file, &mut data);
+                    file = next_file;
+                    input = unparsed_input;
+                }
+                State::EndOfFile => {
+                    push_entry(&file, &mut data);
+                    return Ok(entries);
+                }
+                State::NeedsInputOrEof(_) | State::NextArchive { .. } | State::Stopped => {
+                    unreachable!()
+                }
+            }
+        }
+    }
+    panic!("the archive didn't end");
+}
+
+fn expected_entries(long_name: &[u8]) -> Vec<TarEntry> {
+    let data_short = fs::read("tests/assets/uncompressed/short_data.txt").unwrap();
+    let data_a = fs::read("tests/assets/uncompressed/zipped_a.txt").unwrap();
+    let entry = |name: &[u8], entry_type, link_name: &[u8], data| TarEntry {
+        name: name.to_vec(),
+        entry_type,
+        link_name: link_name.to_vec(),
+        data,
+    };
+
+    vec![
+        entry(b"dir/", EntryType::Directory, b"", Vec::new()),
+        entry(b"dir/short_data.txt", EntryType::Regular, b"", data_short),
+        entry(long_name, EntryType::Regular, b"", data_a),
+        entry(
+            b"link",
+            EntryType::Symlink,
+            b"dir/short_data.txt",
+            Vec::new(),
+        ),
+        entry(b"empty.txt", EntryType::Regular, b"", Vec::new()),
+    ]
+}
+
+#[test]
+fn test_parsing_header() {
+    let data_tar = fs::read("tests/assets/tar/ustar.tar").unwrap();
+
+    let header = Header::parse(&data_tar[512..1024]).unwrap();
+    assert_eq!(
+        header,
+        Header {
+            format: Format::Ustar,
+            entry_type: EntryType::Regular,
+            name: b"dir/short_data.txt".to_vec(),
+            link_name: Vec::new(),
+            mode: 0o644,
+            uid: 1000,
+            gid: 1000,
+            user_name: b"user".to_vec(),
+            group_name: b"group".to_vec(),
+            size: 60,
+            mtime: 1_580_000_000,
+            device_major: 0,
+            device_minor: 0,
+        }
+    );
+}
+
+#[test]
+fn test_ustar() {
+    let data_tar = fs::read("tests/assets/tar/ustar.tar").unwrap();
+    // The directories of the long name are stored in the prefix field.
+    let long_name = [
+        &b"a_directory_with_a_rather_long_name/".repeat(3)[..],
+        b"zipped_a.txt",
+    ]
+    .concat();
+
+    assert_eq!(read_entries(&[&data_tar]), Ok(expected_entries(&long_name)));
+    assert_every_split(&data_tar, read_entries);
+}
+
+#[test]
+fn test_gnu_long_name() {
+    let data_tar = fs::read("tests/assets/tar/gnu.tar").unwrap();
+    let long_name = [
+        &b"a_directory_with_a_rather_long_name/".repeat(6)[..],
+        b"zipped_a.txt",
+    ]
+    .concat();
+
+    assert_eq!(read_entries(&[&data_tar]), Ok(expected_entries(&long_name)));
+    assert_random_splits(&data_tar, 1517, 20, 700, read_entries);
+}
+
+#[test]
+fn test_pax_path() {
+    let data_tar = fs::read("tests/assets/tar/pax.tar").unwrap();
+    let long_name = [
+        &b"a_directory_with_a_rather_long_name/".repeat(6)[..],
+        "zipped_ä.txt".as_bytes(),
+    ]
+    .concat();
+
+    assert_eq!(read_entries(&[&data_tar]), Ok(expected_entries(&long_name)));
+    assert_random_splits(&data_tar, 1517, 20, 700, read_entries);
+}
+
+#[test]
+fn test_checksum_mismatch() {
+    let mut data_tar = fs::read("tests/assets/tar/ustar.tar").unwrap();
+    // Change the name of the second entry.
+    data_tar[512] = b'D';
+
+    assert_eq!(read_entries(&[&data_tar]), Err(TarError::ChecksumMismatch));
+}
+
+#[test]
+fn test_read_after_error() {
+    let mut data_tar = fs::read("tests/assets/tar/ustar.tar").unwrap();
+    // Change the name of the first entry.
+    data_tar[0] = b'D';
+
+    let mut tar_file = tar::start_stream();
+    let res = tar_file.read_with(&data_tar, |_| ());
+    assert_eq!(res.unwrap_err(), TarError::ChecksumMismatch);
+    assert_eq!(tar_file.read(&[]).unwrap_err(), TarError::ChecksumMismatch);
+    assert_eq!(
+        tar_file.read(&data_tar).unwrap_err(),
+        TarError::ChecksumMismatch
+    );
+}
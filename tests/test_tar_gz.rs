@@ -0,0 +1,104 @@
+extern crate stream_zipper;
+
+use std::fs;
+
+use stream_zipper::test_util::{
+    assert_every_split, assert_random_splits, read_chunks, read_chunks_with, Entry,
+};
+use stream_zipper::{gzip, tar};
+use stream_zipper::{Options, State};
+
+fn expected_entries() -> Vec<Entry> {
+    let data_short = fs::read("tests/assets/uncompressed/short_data.txt").unwrap();
+    let data_a = fs::read("tests/assets/uncompressed/zipped_a.txt").unwrap();
+    let long_name = [
+        &b"a_directory_with_a_rather_long_name/".repeat(3)[..],
+        b"zipped_a.txt",
+    ]
+    .concat();
+    let entry = |name: &[u8], data| Entry {
+        name: Some(name.to_vec()),
+        data,
+    };
+
+    vec![
+        entry(b"dir/", Vec::new()),
+        entry(b"dir/short_data.txt", data_short),
+        entry(&long_name, data_a),
+        entry(b"link", Vec::new()),
+        entry(b"empty.txt", Vec::new()),
+    ]
+}
+
+#[test]
+fn test_tar_gz() {
+    let data_tgz = fs::read("tests/assets/tar/ustar.tar.gz").unwrap();
+
+    let mut names = Vec::new();
+    let mut file = tar::start_tar_gz_stream();
+    let mut input = &data_tgz[..];
+    loop {
+        match file.read_with(input, |_| ()).unwrap() {
+            State::NextFile {
+                unparsed_input,
+                next_file,
+            } => {
+                assert!(file.is_tar());
+                names.push(file.header().unwrap().name.clone());
+                file = next_file;
+                input = unparsed_input;
+            }
+            State::NeedsInputOrEof(_) => break,
+            state => panic!("unexpected state: {:?}", state),
+        }
+    }
+    names.push(file.filename().unwrap().to_vec());
+
+    let expected: Vec<_> = expected_entries()
+        .into_iter()
+        .map(|e| e.name.unwrap())
+        .collect();
+    assert_eq!(names, expected);
+}
+
+#[test]
+fn test_autodetect() {
+    let data_tgz = fs::read("tests/assets/tar/ustar.tar.gz").unwrap();
+
+    assert_eq!(read_chunks(&[&data_tgz]), Ok(expected_entries()));
+    assert_every_split(&data_tgz, read_chunks);
+    assert_random_splits(&data_tgz, 1518, 10, 50, read_chunks);
+}
+
+#[test]
+fn test_plain_gzip() {
+    // Not a tar archive: the content is returned as it is.
+    let data_gz = fs::read("tests/assets/gzip/repetitive_data.txt.gz").unwrap();
+    let data = fs::read("tests/assets/uncompressed/repetitive_data.txt").unwrap();
+
+    let mut output = Vec::new();
+    let mut file = tar::start_tar_gz_stream();
+    let state = file
+        .read_with(&data_gz, |out| output.extend_from_slice(out))
+        .unwrap();
+    assert!(matches!(state, State::NeedsInputOrEof(_)));
+    assert!(!file.is_tar());
+    assert_eq!(file.filename(), Some(&b"repetitive_data.txt"[..]));
+    assert_eq!(output, data);
+}
+
+#[test]
+fn test_concatenated_members() {
+    // The tar archive is split into three gzip members in the middle of entries.
+    let data_tgz = fs::read("tests/assets/tar/ustar_members.tar.gz").unwrap();
+    let options = Options::default().gzip(gzip::Options::default().concatenate_members(true));
+    let read_members = |chunks: &[&[u8]]| read_chunks_with(options.clone(), chunks);
+
+    assert_eq!(read_members(&[&data_tgz]), Ok(expected_entries()));
+    assert_every_split(&data_tgz, read_members);
+    assert_random_splits(&data_tgz, 1569, 10, 50, read_members);
+
+    // By default, each member is a file of its own.
+    let entries = read_chunks(&[&data_tgz]).unwrap();
+    assert_ne!(entries, expected_entries());
+}
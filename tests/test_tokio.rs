@@ -0,0 +1,141 @@
+extern crate stream_zipper;
+extern crate tokio;
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use stream_zipper::test_util::{read_chunks, Entry};
+use stream_zipper::tokio::{extract_to_dir, Archive};
+use stream_zipper::DriveError;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, ReadBuf};
+use tokio::runtime::{Builder, Runtime};
+
+fn runtime() -> Runtime {
+    Builder::new_current_thread().build().unwrap()
+}
+
+/// A reader that returns at most `chunk_size` bytes at a time,
+/// and is pending on every other poll, like a slow socket.
+struct Trickle<'a> {
+    data: &'a [u8],
+    chunk_size: usize,
+    pending: bool,
+}
+
+impl<'a> Trickle<'a> {
+    fn new(data: &'a [u8]) -> Trickle<'a> {
+        Trickle {
+            data,
+            chunk_size: 7,
+            pending: true,
+        }
+    }
+}
+
+impl AsyncRead for Trickle<'_> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut ReadBuf,
+    ) -> Poll<io::Result<()>> {
+        self.pending = !self.pending;
+        if !self.pending {
+            cx.waker().wake_by_ref();
+            return Poll::Pending;
+        }
+        let len = self.data.len().min(self.chunk_size).min(buf.remaining());
+        buf.put_slice(&self.data[..len]);
+        self.data = &self.data[len..];
+        Poll::Ready(Ok(()))
+    }
+}
+
+fn read_archive<R: AsyncRead + Unpin>(
+    runtime: &Runtime,
+    mut archive: Archive<R>,
+) -> Result<Vec<Entry>, DriveError> {
+    let mut entries = Vec::new();
+    while let Some(mut entry) = runtime.block_on(archive.next_entry())? {
+        let name = entry.metadata().name.clone();
+        let mut data = Vec::new();
+        runtime.block_on(entry.read_to_end(&mut data))?;
+        entries.push(Entry { name, data });
+    }
+    Ok(entries)
+}
+
+#[test]
+fn test_archive() {
+    let runtime = runtime();
+    for path in &[
+        "tests/assets/zip/comments.zip",
+        "tests/assets/zip/stored_descriptors.zip",
+        "tests/assets/tar/ustar.tar.gz",
+        "tests/assets/gzip/repetitive_data.txt.gz",
+        "tests/assets/bzip2/concatenated.bz2",
+    ] {
+        let input = fs::read(path).unwrap();
+        let expected = read_chunks(&[&input]).unwrap();
+        assert_eq!(
+            read_archive(&runtime, Archive::new(&input[..])).unwrap(),
+            expected,
+            "{}",
+            path
+        );
+        assert_eq!(
+            read_archive(&runtime, Archive::new(Trickle::new(&input))).unwrap(),
+            expected,
+            "{}",
+            path
+        );
+    }
+}
+
+#[test]
+fn test_archive_lines_and_errors() {
+    let runtime = runtime();
+    let input = fs::read("tests/assets/zip/kyushu.tsv.zip").unwrap();
+    let expected = read_chunks(&[&input]).unwrap();
+
+    // Skipped entries are read past.
+    let mut archive = Archive::new(Trickle::new(&input));
+    let mut entry = runtime.block_on(archive.next_entry()).unwrap().unwrap();
+    let mut line = String::new();
+    runtime.block_on(entry.read_line(&mut line)).unwrap();
+    let expected_data = String::from_utf8(expected[0].data.clone()).unwrap();
+    assert_eq!(Some(line.trim_end()), expected_data.lines().next());
+    let mut count = 1;
+    while runtime.block_on(archive.next_entry()).unwrap().is_some() {
+        count += 1;
+    }
+    assert_eq!(count, expected.len());
+
+    // The truncation is reported by the zip stream as an error of the stream.
+    let mut archive = Archive::new(&input[..input.len() / 2]);
+    let mut entry = runtime.block_on(archive.next_entry()).unwrap().unwrap();
+    let err = runtime
+        .block_on(entry.read_to_end(&mut Vec::new()))
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn test_extract_to_dir() {
+    let dest: PathBuf =
+        std::env::temp_dir().join(format!("stream_zipper_tokio_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dest);
+    let input = fs::read("tests/assets/tar/ustar.tar.gz").unwrap();
+
+    let summary = runtime()
+        .block_on(extract_to_dir(Trickle::new(&input), &dest))
+        .unwrap();
+    assert_eq!(summary.files, 5);
+    assert_eq!(
+        fs::read(dest.join("dir/short_data.txt")).unwrap(),
+        fs::read("tests/assets/uncompressed/short_data.txt").unwrap()
+    );
+    fs::remove_dir_all(&dest).unwrap();
+}
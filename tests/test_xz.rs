@@ -0,0 +1,79 @@
+extern crate stream_zipper;
+
+use std::fs;
+
+use stream_zipper::test_util::{assert_every_split, read_chunks, Entry};
+use stream_zipper::xz::{self, XzError};
+use stream_zipper::{Error, State};
+
+#[test]
+fn test_decompression() {
+    for name in &["short_data.txt", "repetitive_data.txt"] {
+        let data_xz = fs::read(format!("tests/assets/xz/{}.xz", name)).unwrap();
+        let data = fs::read(format!("tests/assets/uncompressed/{}", name)).unwrap();
+
+        let mut output = Vec::new();
+        let mut xz_file = xz::start_stream();
+        let state = xz_file
+            .read_with(&data_xz, |out| output.extend_from_slice(out))
+            .unwrap();
+        // Another stream may follow in the input.
+        assert!(matches!(state, State::NeedsInputOrEof(_)));
+        assert_eq!(output, data);
+    }
+}
+
+#[test]
+fn test_autodetect_concatenated() {
+    let data_xz = fs::read("tests/assets/xz/concatenated.xz").unwrap();
+    let data_short = fs::read("tests/assets/uncompressed/short_data.txt").unwrap();
+    let data_a = fs::read("tests/assets/uncompressed/zipped_a.txt").unwrap();
+
+    assert_eq!(
+        read_chunks(&[&data_xz]),
+        Ok(vec![
+            Entry {
+                name: None,
+                data: data_short,
+            },
+            Entry {
+                name: None,
+                data: data_a,
+            },
+        ])
+    );
+    assert_every_split(&data_xz, read_chunks);
+}
+
+#[test]
+fn test_invalid_stream() {
+    let mut data_xz = fs::read("tests/assets/xz/repetitive_data.txt.xz").unwrap();
+    // Corrupt the stream flags, which are covered by a checksum.
+    data_xz[7] ^= 0xff;
+
+    assert_eq!(
+        read_chunks(&[&data_xz]),
+        Err(Error::Xz(XzError::InvalidStream))
+    );
+}
+
+#[test]
+fn test_trailing_garbage() {
+    let mut data_xz = fs::read("tests/assets/xz/short_data.txt.xz").unwrap();
+    data_xz.extend_from_slice(b"garbage");
+
+    let res = xz::start_stream().read_with(&data_xz, |_| ());
+    assert_eq!(res.unwrap_err(), XzError::InvalidHeader);
+}
+
+#[test]
+fn test_read_after_error() {
+    let mut data_xz = fs::read("tests/assets/xz/short_data.txt.xz").unwrap();
+    data_xz.extend_from_slice(b"garbage");
+
+    let mut xz_file = xz::start_stream();
+    let res = xz_file.read_with(&data_xz, |_| ());
+    assert_eq!(res.unwrap_err(), XzError::InvalidHeader);
+    assert_eq!(xz_file.read(&[]).unwrap_err(), XzError::InvalidHeader);
+    assert_eq!(xz_file.read(&data_xz).unwrap_err(), XzError::InvalidHeader);
+}
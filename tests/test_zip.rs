@@ -27,7 +27,7 @@ pub fn generate_systime(
     let month = (month - 1) as usize;
     let day = day - 1;
     let years_since = year - 1970;
-    let is_after_leap = (year % 4) > 0 || month > 1;
+    let is_after_leap = !year.is_multiple_of(4) || month > 1;
     let leap_days_since = ((years_since - 2) / 4) + is_after_leap as u32;
     let days_since = 365 * years_since + DAY_OF_YEAR[month] as u32 + day + leap_days_since;
     let mins_since = hours * 60 + minutes;
@@ -70,6 +70,7 @@ fn test_parsing_local_header() {
             encrypted: false,
             deflate_mode: DeflateMode::Normal,
             deferred_sizes: true,
+            utf8_name: false,
             compression_method: CompressionMethod::Deflated,
             last_mod: generate_systime(2018, 4, 10, 0, 45, 58),
             crc_32: 0,
@@ -81,6 +82,7 @@ fn test_parsing_local_header() {
                 HeaderId::InfoZipUnixOriginal,
                 [222, 138, 203, 90, 182, 138, 203, 90, 245, 1, 20, 0][..].into(),
             )],
+            zip64: None,
         }
     );
 }
@@ -100,6 +102,7 @@ fn test_inner_iterion_rand_small() {
             encrypted: false,
             deflate_mode: DeflateMode::Normal,
             deferred_sizes: true,
+            utf8_name: false,
             compression_method: CompressionMethod::Deflated,
             last_mod: generate_systime(2018, 4, 10, 0, 45, 58),
             crc_32: 0,
@@ -111,6 +114,7 @@ fn test_inner_iterion_rand_small() {
                 HeaderId::InfoZipUnixOriginal,
                 [222, 138, 203, 90, 182, 138, 203, 90, 245, 1, 20, 0][..].into(),
             )],
+            zip64: None,
         }
     );
 
@@ -130,7 +134,7 @@ fn test_inner_iterion_rand_small() {
     };
 
     let (unparsed_3, parsed_data_desc) =
-        DataDescriptor::parse_zip(&unparsed_2).expect("Should be able to parse");
+        DataDescriptor::parse_zip(unparsed_2).expect("Should be able to parse");
 
     assert_eq!(
         parsed_data_desc,
@@ -142,10 +146,10 @@ fn test_inner_iterion_rand_small() {
         }
     );
 
-    let (unparsed_4, parsed_data_desc) =
-        CentralDirHeader::parse(&unparsed_3).expect("Should be able to parse");
-    let (unparsed_5, parsed_central_dir_end) =
-        CentralDirEnd::parse(&unparsed_4).expect("Should be able to parse");
+    let (unparsed_4, _parsed_data_desc) =
+        CentralDirHeader::parse(unparsed_3).expect("Should be able to parse");
+    let (unparsed_5, _parsed_central_dir_end) =
+        CentralDirEnd::parse(unparsed_4).expect("Should be able to parse");
 
     assert!(unparsed_5.is_empty());
 }
@@ -165,6 +169,7 @@ fn test_inner_iterion_rand_big() {
             encrypted: false,
             deflate_mode: DeflateMode::Normal,
             deferred_sizes: true,
+            utf8_name: false,
             compression_method: CompressionMethod::Deflated,
             last_mod: generate_systime(2018, 4, 13, 13, 11, 58),
             crc_32: 0,
@@ -176,6 +181,7 @@ fn test_inner_iterion_rand_big() {
                 HeaderId::InfoZipUnixOriginal,
                 [31, 46, 208, 90, 14, 46, 208, 90, 245, 1, 20, 0][..].to_vec(),
             )],
+            zip64: None,
         }
     );
 
@@ -195,7 +201,7 @@ fn test_inner_iterion_rand_big() {
     };
 
     let (unparsed_3, parsed_data_desc) =
-        DataDescriptor::parse_zip(&unparsed_2).expect("Should be able to parse");
+        DataDescriptor::parse_zip(unparsed_2).expect("Should be able to parse");
 
     assert_eq!(
         parsed_data_desc,
@@ -208,7 +214,7 @@ fn test_inner_iterion_rand_big() {
     );
 
     let (unparsed_4, parsed_central_dir_entry) =
-        CentralDirHeader::parse(&unparsed_3).expect("Should be able to parse");
+        CentralDirHeader::parse(unparsed_3).expect("Should be able to parse");
 
     assert_eq!(
         parsed_central_dir_entry,
@@ -218,6 +224,7 @@ fn test_inner_iterion_rand_big() {
             encrypted: false,
             deflate_mode: DeflateMode::Normal,
             deferred_sizes: true,
+            utf8_name: false,
             compression_method: CompressionMethod::Deflated,
             last_mod_time: 27005,
             last_mod_date: 19597,
@@ -234,11 +241,12 @@ fn test_inner_iterion_rand_big() {
                 [31, 46, 208, 90, 14, 46, 208, 90][..].to_vec(),
             )],
             comment: b""[..].to_vec(),
+            zip64: None,
         }
     );
 
     let (unparsed_5, parsed_central_dir_end) =
-        CentralDirEnd::parse(&unparsed_4).expect("Should be able to parse");
+        CentralDirEnd::parse(unparsed_4).expect("Should be able to parse");
 
     assert_eq!(
         parsed_central_dir_end,
@@ -271,6 +279,7 @@ fn test_inner_iterion_huge() {
             encrypted: false,
             deflate_mode: DeflateMode::Normal,
             deferred_sizes: true,
+            utf8_name: false,
             compression_method: CompressionMethod::Deflated,
             last_mod: generate_systime(2018, 4, 16, 18, 17, 30),
             crc_32: 0,
@@ -282,6 +291,7 @@ fn test_inner_iterion_huge() {
                 HeaderId::InfoZipUnixOriginal,
                 [219, 81, 212, 90, 107, 81, 212, 90, 53, 50, 50, 131][..].to_vec(),
             )],
+            zip64: None,
         }
     );
 
@@ -305,7 +315,7 @@ fn test_inner_iterion_huge() {
     };
 
     let (unparsed_3, parsed_data_desc) =
-        DataDescriptor::parse_zip(&unparsed_2).expect("Should be able to parse");
+        DataDescriptor::parse_zip(unparsed_2).expect("Should be able to parse");
 
     assert_eq!(
         parsed_data_desc,
@@ -326,7 +336,7 @@ fn test_inner_iterion_huge() {
     );
 
     let (unparsed_4, parsed_central_dir_entry) =
-        CentralDirHeader::parse(&unparsed_3).expect("Should be able to parse");
+        CentralDirHeader::parse(unparsed_3).expect("Should be able to parse");
 
     assert_eq!(
         parsed_central_dir_entry,
@@ -336,6 +346,7 @@ fn test_inner_iterion_huge() {
             encrypted: false,
             deflate_mode: DeflateMode::Normal,
             deferred_sizes: true,
+            utf8_name: false,
             compression_method: CompressionMethod::Deflated,
             last_mod_time: 33788,
             last_mod_date: 19600,
@@ -352,11 +363,12 @@ fn test_inner_iterion_huge() {
                 [219, 81, 212, 90, 107, 81, 212, 90][..].to_vec(),
             )],
             comment: b""[..].to_vec(),
+            zip64: None,
         }
     );
 
     let (unparsed_5, parsed_central_dir_end) =
-        CentralDirEnd::parse(&unparsed_4).expect("Should be able to parse");
+        CentralDirEnd::parse(unparsed_4).expect("Should be able to parse");
 
     assert_eq!(
         parsed_central_dir_end,
@@ -388,6 +400,7 @@ fn test_inner_iterion_multi() {
             encrypted: false,
             deflate_mode: DeflateMode::Normal,
             deferred_sizes: true,
+            utf8_name: false,
             compression_method: CompressionMethod::Deflated,
             last_mod: generate_systime(2018, 4, 16, 18, 17, 30),
             crc_32: 0,
@@ -399,6 +412,7 @@ fn test_inner_iterion_multi() {
                 HeaderId::InfoZipUnixOriginal,
                 [48, 106, 212, 90, 41, 106, 212, 90, 53, 50, 50, 131][..].to_vec(),
             )],
+            zip64: None,
         }
     );
 
@@ -413,7 +427,7 @@ fn test_inner_iterion_multi() {
         })
         .expect("Should be able to deflate");
 
-    let end = std::time::Instant::now() - start;
+    let _end = std::time::Instant::now() - start;
 
     let unparsed_2 = if let deflate::State::Stop { unparsed_input } = result {
         unparsed_input
@@ -422,7 +436,7 @@ fn test_inner_iterion_multi() {
     };
 
     let (unparsed_3, parsed_data_desc) =
-        DataDescriptor::parse_zip(&unparsed_2).expect("Should be able to parse");
+        DataDescriptor::parse_zip(unparsed_2).expect("Should be able to parse");
 
     assert_eq!(
         parsed_data_desc,
@@ -437,7 +451,7 @@ fn test_inner_iterion_multi() {
     println!("Uncompressed {} bytes", out_pos);
 
     let (unparsed_4, parsed_header) =
-        LocalFileHeader::parse(&unparsed_3).expect("Should be able to parse");
+        LocalFileHeader::parse(unparsed_3).expect("Should be able to parse");
 
     assert_eq!(
         parsed_header,
@@ -446,6 +460,7 @@ fn test_inner_iterion_multi() {
             encrypted: false,
             deflate_mode: DeflateMode::Normal,
             deferred_sizes: true,
+            utf8_name: false,
             compression_method: CompressionMethod::Deflated,
             last_mod: generate_systime(2018, 4, 16, 18, 17, 58),
             crc_32: 0,
@@ -457,6 +472,7 @@ fn test_inner_iterion_multi() {
                 HeaderId::InfoZipUnixOriginal,
                 [72, 106, 212, 90, 70, 106, 212, 90, 53, 50, 50, 131][..].to_vec(),
             )],
+            zip64: None,
         }
     );
 
@@ -471,7 +487,7 @@ fn test_inner_iterion_multi() {
         })
         .expect("Should be able to deflate");
 
-    let end = std::time::Instant::now() - start;
+    let _end = std::time::Instant::now() - start;
 
     let unparsed_5 = if let deflate::State::Stop { unparsed_input } = result {
         unparsed_input
@@ -480,7 +496,7 @@ fn test_inner_iterion_multi() {
     };
 
     let (unparsed_6, parsed_data_desc) =
-        DataDescriptor::parse_zip(&unparsed_5).expect("Should be able to parse");
+        DataDescriptor::parse_zip(unparsed_5).expect("Should be able to parse");
 
     assert_eq!(
         parsed_data_desc,
@@ -495,7 +511,7 @@ fn test_inner_iterion_multi() {
     println!("Uncompressed {} bytes", out_pos);
 
     let (unparsed_7, parsed_central_dir_entry) =
-        CentralDirHeader::parse(&unparsed_6).expect("Should be able to parse");
+        CentralDirHeader::parse(unparsed_6).expect("Should be able to parse");
 
     assert_eq!(
         parsed_central_dir_entry,
@@ -505,6 +521,7 @@ fn test_inner_iterion_multi() {
             encrypted: false,
             deflate_mode: DeflateMode::Normal,
             deferred_sizes: true,
+            utf8_name: false,
             compression_method: CompressionMethod::Deflated,
             last_mod_time: 37423,
             last_mod_date: 19600,
@@ -521,11 +538,12 @@ fn test_inner_iterion_multi() {
                 [48, 106, 212, 90, 41, 106, 212, 90][..].to_vec(),
             )],
             comment: b""[..].to_vec(),
+            zip64: None,
         }
     );
 
     let (unparsed_8, parsed_central_dir_entry) =
-        CentralDirHeader::parse(&unparsed_7).expect("Should be able to parse");
+        CentralDirHeader::parse(unparsed_7).expect("Should be able to parse");
 
     assert_eq!(
         parsed_central_dir_entry,
@@ -535,6 +553,7 @@ fn test_inner_iterion_multi() {
             encrypted: false,
             deflate_mode: DeflateMode::Normal,
             deferred_sizes: true,
+            utf8_name: false,
             compression_method: CompressionMethod::Deflated,
             last_mod_time: 37437,
             last_mod_date: 19600,
@@ -551,11 +570,12 @@ fn test_inner_iterion_multi() {
                 [72, 106, 212, 90, 70, 106, 212, 90][..].into(),
             )],
             comment: b""[..].into(),
+            zip64: None,
         }
     );
 
     let (unparsed_9, parsed_central_dir_end) =
-        CentralDirEnd::parse(&unparsed_8).expect("Should be able to parse");
+        CentralDirEnd::parse(unparsed_8).expect("Should be able to parse");
 
     assert_eq!(
         parsed_central_dir_end,
@@ -589,16 +609,16 @@ fn test_multifile() {
 
     if let Some(first_chunk) = chunks.next() {
         comp_accu += first_chunk.len();
-        let r = file
-            .read_internal_iter(&first_chunk, |uncomp| {
+        let _r = file
+            .read_internal_iter(first_chunk, |uncomp| {
                 uncomp_accu += uncomp.len();
             })
             .unwrap();
         println!(
             "File name: {}",
-            String::from_utf8_lossy(file.name().as_ref().map(|s| &**s).unwrap_or(b""))
+            String::from_utf8_lossy(file.name().unwrap_or(b""))
         );
-        while let Some(chunk) = chunks.next() {
+        for chunk in chunks {
             let mut input = chunk;
             println!(
                 "{} KB compressed. {} KB uncompressed.",
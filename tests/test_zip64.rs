@@ -0,0 +1,167 @@
+extern crate stream_zipper;
+
+use std::fs;
+
+use stream_zipper::test_util::{assert_every_split, assert_random_splits, read_chunks, Entry};
+use stream_zipper::zip::headers::{LocalFileHeader, Zip64ExtendedInfo, LOCAL_FILE_HEADER_TAG};
+use stream_zipper::zip::{self, ZipError};
+use stream_zipper::Error;
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+/// Moves the sizes of the local file headers of an archive without data descriptors
+/// to Zip64 extended information extra fields, saturating the 32-bit sizes.
+fn with_zip64_sizes(data: &[u8]) -> Vec<u8> {
+    let mut converted = Vec::new();
+    let mut pos = 0;
+    while data[pos..].starts_with(LOCAL_FILE_HEADER_TAG) {
+        let compressed_size = read_u32(data, pos + 18);
+        let uncompressed_size = read_u32(data, pos + 22);
+        let name_len = usize::from(read_u16(data, pos + 26));
+        let extra_len = read_u16(data, pos + 28);
+
+        let mut header = data[pos..pos + 30].to_vec();
+        header[18..26].copy_from_slice(&[0xff; 8]);
+        header[28..30].copy_from_slice(&(extra_len + 20).to_le_bytes());
+        converted.extend_from_slice(&header);
+        let fields_end = pos + 30 + name_len + usize::from(extra_len);
+        converted.extend_from_slice(&data[pos + 30..fields_end]);
+        converted.extend_from_slice(b"\x01\x00\x10\x00");
+        converted.extend_from_slice(&u64::from(uncompressed_size).to_le_bytes());
+        converted.extend_from_slice(&u64::from(compressed_size).to_le_bytes());
+        pos = fields_end + compressed_size as usize;
+        converted.extend_from_slice(&data[fields_end..pos]);
+    }
+    converted.extend_from_slice(&data[pos..]);
+    converted
+}
+
+fn expected_entries() -> Vec<Entry> {
+    vec![
+        Entry {
+            name: Some(b"short_data.txt".to_vec()),
+            data: fs::read("tests/assets/uncompressed/short_data.txt").unwrap(),
+        },
+        Entry {
+            name: Some(b"repetitive_data.txt".to_vec()),
+            data: fs::read("tests/assets/uncompressed/repetitive_data.txt").unwrap(),
+        },
+    ]
+}
+
+#[test]
+fn test_zip64_data_descriptor() {
+    // The sizes are deferred to 64-bit data descriptors.
+    let data_zip = fs::read("tests/assets/zip/zip64_descriptor.zip").unwrap();
+
+    let (_, header) = LocalFileHeader::parse(&data_zip).unwrap();
+    assert!(header.is_zip64);
+    assert_eq!(header.sizes(), None);
+
+    assert_eq!(read_chunks(&[&data_zip]), Ok(expected_entries()));
+    assert_every_split(&data_zip, read_chunks);
+}
+
+#[test]
+fn test_zip64_extra_field_without_saturated_sizes() {
+    // Some archivers leave the deferred sizes at zero, but the extra field
+    // still tells that the data descriptor has 64-bit sizes.
+    let mut data_zip = fs::read("tests/assets/zip/zip64_descriptor.zip").unwrap();
+    let mut pos = 0;
+    while let Some(offset) = data_zip[pos..]
+        .windows(4)
+        .position(|window| window == LOCAL_FILE_HEADER_TAG)
+    {
+        pos += offset;
+        data_zip[pos + 18..pos + 26].copy_from_slice(&[0; 8]);
+        pos += 4;
+    }
+
+    let (_, header) = LocalFileHeader::parse(&data_zip).unwrap();
+    assert!(header.is_zip64);
+
+    assert_eq!(read_chunks(&[&data_zip]), Ok(expected_entries()));
+}
+
+#[test]
+fn test_zip64_sizes_in_local_header() {
+    // Shrink doesn't mark the end of the data, so the entries are only
+    // readable with the real sizes from the extra fields.
+    let data_zip = with_zip64_sizes(&fs::read("tests/assets/zip/shrunk.zip").unwrap());
+    let data_numbers = fs::read("tests/assets/uncompressed/numbers.txt").unwrap();
+
+    let (_, header) = LocalFileHeader::parse(&data_zip).unwrap();
+    assert_eq!(header.compressed_size, u32::MAX);
+    assert_eq!(
+        header.zip64,
+        Some(Zip64ExtendedInfo {
+            uncompressed_size: Some(50027),
+            compressed_size: Some(23229),
+            local_header_offset: None,
+            disk_start: None,
+        })
+    );
+    assert_eq!(header.sizes(), Some((23229, 50027)));
+
+    let entries = read_chunks(&[&data_zip]).unwrap();
+    assert_eq!(entries[0].data, data_numbers);
+    assert_eq!(entries.len(), 2);
+    assert_random_splits(&data_zip, 1523, 10, 3000, read_chunks);
+}
+
+#[test]
+fn test_saturated_sizes_without_extra_field() {
+    let mut data_zip = with_zip64_sizes(&fs::read("tests/assets/zip/shrunk.zip").unwrap());
+    // Rename the extra field, so that the sizes are unknown.
+    let name_len = usize::from(read_u16(&data_zip, 26));
+    data_zip[30 + name_len] = 0xff;
+
+    assert_eq!(
+        zip::start_stream()
+            .read_with(&data_zip, |_| ())
+            .unwrap_err()
+            .without_entry(),
+        &ZipError::UnsupportedCompressionMethod(zip::headers::CompressionMethod::Shrunk)
+    );
+    match read_chunks(&[&data_zip]) {
+        Err(Error::Zip(e)) => assert!(matches!(
+            e.without_entry(),
+            ZipError::UnsupportedCompressionMethod(_)
+        )),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+}
+
+#[test]
+fn test_parsing_zip64_extended_info() {
+    let mut data = Vec::new();
+    data.extend_from_slice(&0x1_0000_0000_u64.to_le_bytes());
+    data.extend_from_slice(&0x2_0000_0000_u64.to_le_bytes());
+    data.extend_from_slice(&7_u32.to_le_bytes());
+
+    // Only the values of the saturated fields are stored, in order.
+    assert_eq!(
+        Zip64ExtendedInfo::parse(&data, false, true, true, true),
+        Some(Zip64ExtendedInfo {
+            uncompressed_size: None,
+            compressed_size: Some(0x1_0000_0000),
+            local_header_offset: Some(0x2_0000_0000),
+            disk_start: Some(7),
+        })
+    );
+    assert_eq!(
+        Zip64ExtendedInfo::parse(&data[..16], true, true, true, false),
+        None
+    );
+}
@@ -0,0 +1,82 @@
+extern crate stream_zipper;
+
+use std::fs;
+
+use stream_zipper::test_util::{assert_random_splits, read_zip_entries, Entry};
+use stream_zipper::zip::headers::{AesExtraField, AesStrength, CompressionMethod};
+use stream_zipper::zip::{self, ZipError};
+
+#[test]
+fn test_winzip_aes() {
+    // The first entry is AE-1 with AES-128 and its sizes in the local header,
+    // the second one AE-2 with AES-256 and a data descriptor.
+    let data_zip = fs::read("tests/assets/zip/winzip_aes.zip").unwrap();
+    let data_short = fs::read("tests/assets/uncompressed/short_data.txt").unwrap();
+    let data_repetitive = fs::read("tests/assets/uncompressed/repetitive_data.txt").unwrap();
+
+    let options = zip::Options::default().password("secret");
+    assert_eq!(
+        read_zip_entries(&[&data_zip], options.clone()),
+        Ok(vec![
+            Entry {
+                name: Some(b"short_data.txt".to_vec()),
+                data: data_short,
+            },
+            Entry {
+                name: Some(b"repetitive_data.txt".to_vec()),
+                data: data_repetitive,
+            },
+        ])
+    );
+    // Deriving the keys is slow on purpose, so not every split is tried.
+    assert_random_splits(&data_zip, 1529, 20, 64, |chunks| {
+        read_zip_entries(chunks, options.clone())
+    });
+}
+
+#[test]
+fn test_winzip_aes_wrong_password() {
+    let data_zip = fs::read("tests/assets/zip/winzip_aes.zip").unwrap();
+
+    let options = zip::Options::default().password("wrong");
+    assert_eq!(
+        read_zip_entries(&[&data_zip], options).map_err(|e| e.without_entry().clone()),
+        Err(ZipError::InvalidPassword)
+    );
+}
+
+#[test]
+fn test_winzip_aes_tampered() {
+    let mut data_zip = fs::read("tests/assets/zip/winzip_aes.zip").unwrap();
+    // The authentication code of the first entry ends right before the second entry.
+    let second_entry = data_zip[1..]
+        .windows(4)
+        .position(|window| window == b"PK\x03\x04")
+        .unwrap()
+        + 1;
+    data_zip[second_entry - 1] ^= 1;
+
+    let options = zip::Options::default().password("secret");
+    assert_eq!(
+        read_zip_entries(&[&data_zip], options).map_err(|e| e.without_entry().clone()),
+        Err(ZipError::InvalidAuthenticationCode)
+    );
+}
+
+#[test]
+fn test_parsing_aes_extra_field() {
+    assert_eq!(
+        AesExtraField::parse(b"\x02\x00AE\x03\x08\x00"),
+        Some(AesExtraField {
+            vendor_version: 2,
+            strength: AesStrength::Aes256,
+            compression_method: CompressionMethod::Deflated,
+        })
+    );
+    // An unknown key strength
+    assert_eq!(AesExtraField::parse(b"\x02\x00AE\x04\x08\x00"), None);
+    // The vendor ID isn't "AE"
+    assert_eq!(AesExtraField::parse(b"\x02\x00XY\x03\x08\x00"), None);
+    // The actual compression method can't be AES again
+    assert_eq!(AesExtraField::parse(b"\x01\x00AE\x01\x63\x00"), None);
+}
@@ -0,0 +1,200 @@
+extern crate stream_zipper;
+
+use std::fs;
+use std::io::Cursor;
+
+use stream_zipper::test_util::{assert_every_split, read_chunks, Entry};
+use stream_zipper::zip::headers::Permissions;
+use stream_zipper::zip::{self, SeekArchive};
+use stream_zipper::State;
+
+/// The names and the permissions of the entries of the archive at `path`.
+fn permissions(path: &str) -> Vec<(Vec<u8>, Permissions)> {
+    let data_zip = fs::read(path).unwrap();
+    zip::list_entries(&data_zip)
+        .unwrap()
+        .iter()
+        .map(|header| (header.filename.clone(), header.permissions()))
+        .collect()
+}
+
+#[test]
+fn test_unix_permissions() {
+    assert_eq!(
+        permissions("tests/assets/zip/kyushu.tsv.zip"),
+        vec![
+            (b"kyushu.tsv".to_vec(), Permissions::Unix(0o100755)),
+            (b"__MACOSX/".to_vec(), Permissions::Unix(0o40775)),
+            (
+                b"__MACOSX/._kyushu.tsv".to_vec(),
+                Permissions::Unix(0o100644)
+            ),
+        ]
+    );
+
+    let file = Permissions::Unix(0o100755);
+    assert!(!file.is_dir());
+    assert!(!file.is_symlink());
+    assert!(!file.is_readonly());
+    assert_eq!(file.unix_mode(), 0o755);
+
+    let dir = Permissions::Unix(0o40775);
+    assert!(dir.is_dir());
+    assert_eq!(dir.unix_mode(), 0o775);
+
+    let symlink = Permissions::Unix(0o120777);
+    assert!(symlink.is_symlink());
+    assert!(!symlink.is_dir());
+
+    assert!(Permissions::Unix(0o100444).is_readonly());
+}
+
+#[test]
+fn test_unix_permissions_without_file_type() {
+    // Python's zipfile stores only the permission bits for entries added from bytes.
+    assert_eq!(
+        permissions("tests/assets/zip/comments.zip"),
+        vec![
+            (b"short_data.txt".to_vec(), Permissions::Unix(0o600)),
+            (b"repetitive_data.txt".to_vec(), Permissions::Unix(0o600)),
+        ]
+    );
+    assert!(!Permissions::Unix(0o600).is_dir());
+}
+
+#[test]
+fn test_fat_attributes() {
+    // Made on Windows, with the archive attribute.
+    assert_eq!(
+        permissions("tests/assets/zip/ntfs_timestamps.zip"),
+        vec![(b"short_data.txt".to_vec(), Permissions::Fat(0x20))]
+    );
+    assert_eq!(Permissions::Fat(0x20).unix_mode(), 0o644);
+    assert!(!Permissions::Fat(0x20).is_readonly());
+
+    let dir = Permissions::Fat(0x10);
+    assert!(dir.is_dir());
+    assert_eq!(dir.unix_mode(), 0o755);
+
+    let readonly = Permissions::Fat(0x01);
+    assert!(readonly.is_readonly());
+    assert_eq!(readonly.unix_mode(), 0o444);
+}
+
+#[test]
+fn test_symlinks() {
+    let data_zip = fs::read("tests/assets/zip/symlinks.zip").unwrap();
+    let data_repetitive = fs::read("tests/assets/uncompressed/repetitive_data.txt").unwrap();
+
+    let mut archive = SeekArchive::new(Cursor::new(&data_zip)).unwrap();
+    let symlinks: Vec<bool> = archive
+        .entries()
+        .iter()
+        .map(|header| header.is_symlink())
+        .collect();
+    assert_eq!(symlinks, vec![false, true, true]);
+
+    assert_eq!(archive.read_symlink_target(0).unwrap(), None);
+    assert_eq!(
+        archive.read_symlink_target(1).unwrap(),
+        Some(b"repetitive_data.txt".to_vec())
+    );
+    assert_eq!(
+        archive.read_symlink_target(2).unwrap(),
+        Some(b"/etc/passwd".to_vec())
+    );
+
+    let mut data = Vec::new();
+    archive
+        .read_entry(0, |output| {
+            data.extend_from_slice(output);
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(data, data_repetitive);
+}
+
+#[test]
+fn test_symlink_targets_while_streaming() {
+    // The targets are stored uncompressed, and they are the data of the entries.
+    let data_zip = fs::read("tests/assets/zip/symlinks.zip").unwrap();
+
+    let targets = |chunks: &[&[u8]]| read_chunks(chunks).map(|entries| entries[1..].to_vec());
+    assert_eq!(
+        targets(&[&data_zip]),
+        Ok(vec![
+            Entry {
+                name: Some(b"link.txt".to_vec()),
+                data: b"repetitive_data.txt".to_vec(),
+            },
+            Entry {
+                name: Some(b"absolute_link".to_vec()),
+                data: b"/etc/passwd".to_vec(),
+            },
+        ])
+    );
+    assert_every_split(&data_zip, targets);
+}
+
+/// Reads the stream with an autodetecting stream,
+/// returning the names of the files and whether they are directories.
+fn directories(chunks: &[&[u8]]) -> Result<Vec<(Vec<u8>, bool)>, stream_zipper::Error> {
+    let mut file = stream_zipper::start_stream();
+    let mut files = Vec::new();
+    for chunk in chunks {
+        let mut input = *chunk;
+        loop {
+            match file.read(input)? {
+                State::HasOutput { unparsed_input, .. } => input = unparsed_input,
+                State::NeedsInput => break,
+                State::NextFile {
+                    unparsed_input,
+                    next_file,
+                } => {
+                    files.push((file.name().unwrap().to_vec(), file.is_dir()));
+                    file = next_file;
+                    input = unparsed_input;
+                }
+                State::NeedsInputOrEof(_) | State::EndOfFile => {
+                    files.push((file.name().unwrap().to_vec(), file.is_dir()));
+                    return Ok(files);
+                }
+                state => panic!("Unexpected state: {:?}", state),
+            }
+        }
+    }
+    panic!("The stream should have ended");
+}
+
+#[test]
+fn test_is_dir() {
+    let data_zip = fs::read("tests/assets/zip/kyushu.tsv.zip").unwrap();
+
+    let expected = vec![
+        (b"kyushu.tsv".to_vec(), false),
+        (b"__MACOSX/".to_vec(), true),
+        (b"__MACOSX/._kyushu.tsv".to_vec(), false),
+    ];
+    assert_eq!(directories(&[&data_zip]), Ok(expected.clone()));
+    let chunks: Vec<&[u8]> = data_zip.chunks(4096).collect();
+    assert_eq!(directories(&chunks), Ok(expected.clone()));
+
+    let dirs: Vec<(Vec<u8>, bool)> = zip::list_entries(&data_zip)
+        .unwrap()
+        .iter()
+        .map(|header| (header.filename.clone(), header.is_dir()))
+        .collect();
+    assert_eq!(dirs, expected);
+}
+
+#[test]
+fn test_is_dir_in_tar() {
+    let data_tar_gz = fs::read("tests/assets/tar/ustar.tar.gz").unwrap();
+
+    let dirs: Vec<bool> = directories(&[&data_tar_gz])
+        .unwrap()
+        .into_iter()
+        .map(|(_, is_dir)| is_dir)
+        .collect();
+    assert_eq!(dirs, vec![true, false, false, false, false]);
+}
@@ -1,9 +1,8 @@
 extern crate stream_zipper;
 
 use std::fs;
-use std::ops::Not;
 
-use stream_zipper::{zip, State};
+use stream_zipper::zip;
 
 #[test]
 fn test_bug_20200131() {
@@ -19,17 +18,17 @@ fn test_bug_20200131() {
     // at the same time. See: https://github.com/Frommi/miniz_oxide/pull/68
     let critical_len = 13750 + zip_header_len;
 
-    let mut data_zip_a = &data_zip[..critical_len];
-    let mut data_zip_b = &data_zip[critical_len..];
+    let data_zip_a = &data_zip[..critical_len];
+    let data_zip_b = &data_zip[critical_len..];
 
     zip_file
-        .read_with(&data_zip_a, |unzipped_bytes| {
+        .read_with(data_zip_a, |unzipped_bytes| {
             assert_eq!(unzipped_bytes, &data_uncompressed[..unzipped_bytes.len()]);
             data_uncompressed = &data_uncompressed[unzipped_bytes.len()..]
         })
         .expect("Should succeed");
     zip_file
-        .read_with(&data_zip_b, |unzipped_bytes| {
+        .read_with(data_zip_b, |unzipped_bytes| {
             assert_eq!(unzipped_bytes, data_uncompressed);
         })
         .expect("Should succeed");
@@ -0,0 +1,30 @@
+extern crate stream_zipper;
+
+use std::fs;
+
+use stream_zipper::test_util::{assert_every_split, read_chunks, Entry};
+
+#[test]
+fn test_bzip2() {
+    // The entries have data descriptors, so the end of each bzip2 stream
+    // must be found from the stream itself.
+    let data_zip = fs::read("tests/assets/zip/bzip2.zip").unwrap();
+    let data_repetitive = fs::read("tests/assets/uncompressed/repetitive_data.txt").unwrap();
+    let data_short = fs::read("tests/assets/uncompressed/short_data.txt").unwrap();
+
+    assert_eq!(
+        read_chunks(&[&data_zip]),
+        Ok(vec![
+            Entry {
+                name: Some(b"repetitive_data.txt".to_vec()),
+                data: data_repetitive,
+            },
+            Entry {
+                name: Some(b"short_data.txt".to_vec()),
+                data: data_short,
+            },
+        ])
+    );
+
+    assert_every_split(&data_zip, read_chunks);
+}
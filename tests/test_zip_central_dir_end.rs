@@ -0,0 +1,71 @@
+extern crate stream_zipper;
+
+use std::fs;
+
+use stream_zipper::zip::headers::{CentralDirEnd, MAX_CENTRAL_DIR_END_LEN};
+
+/// Sets the archive comment of `data_zip`, which must not have one.
+fn with_comment(mut data_zip: Vec<u8>, comment: &[u8]) -> Vec<u8> {
+    let len = data_zip.len();
+    assert_eq!(data_zip[len - 2..], [0, 0]);
+    data_zip[len - 2..].copy_from_slice(&(comment.len() as u16).to_le_bytes());
+    data_zip.extend_from_slice(comment);
+    data_zip
+}
+
+#[test]
+fn test_find_central_dir_end() {
+    let data_zip = fs::read("tests/assets/zip/zipped_ab.zip").unwrap();
+
+    let (end, offset) = CentralDirEnd::find(&data_zip).unwrap();
+    assert_eq!(offset, data_zip.len() - 22);
+    assert_eq!(end.central_dir_num_entries_total, 2);
+    assert_eq!(end.comment, b"");
+}
+
+#[test]
+fn test_find_central_dir_end_in_tail() {
+    let data_zip = fs::read("tests/assets/zip/kyushu.tsv.zip").unwrap();
+    assert!(data_zip.len() > MAX_CENTRAL_DIR_END_LEN);
+    let tail = &data_zip[data_zip.len() - MAX_CENTRAL_DIR_END_LEN..];
+
+    let (end, offset) = CentralDirEnd::find(tail).unwrap();
+    assert_eq!(offset, tail.len() - 22);
+    assert_eq!(end.central_dir_num_entries_total, 3);
+}
+
+#[test]
+fn test_find_central_dir_end_with_comment() {
+    let data_zip = fs::read("tests/assets/zip/zipped_ab.zip").unwrap();
+    let original_len = data_zip.len();
+
+    // The comment contains the signature of the record.
+    let comment = b"The signature PK\x05\x06 starts the end of central directory record.";
+    let data_zip = with_comment(data_zip, comment);
+
+    let (end, offset) = CentralDirEnd::find(&data_zip).unwrap();
+    assert_eq!(offset, original_len - 22);
+    assert_eq!(end.central_dir_num_entries_total, 2);
+    assert_eq!(end.comment, &comment[..]);
+}
+
+#[test]
+fn test_find_central_dir_end_with_trailing_data() {
+    let mut data_zip = fs::read("tests/assets/zip/zipped_ab.zip").unwrap();
+    let original_len = data_zip.len();
+    data_zip.extend_from_slice(b"trailing data");
+
+    let (end, offset) = CentralDirEnd::find(&data_zip).unwrap();
+    assert_eq!(offset, original_len - 22);
+    assert_eq!(end.central_dir_num_entries_total, 2);
+}
+
+#[test]
+fn test_find_central_dir_end_missing() {
+    let data = fs::read("tests/assets/uncompressed/short_data.txt").unwrap();
+    assert_eq!(CentralDirEnd::find(&data), None);
+
+    // Truncated in the middle of the record.
+    let data_zip = fs::read("tests/assets/zip/zipped_ab.zip").unwrap();
+    assert_eq!(CentralDirEnd::find(&data_zip[..data_zip.len() - 1]), None);
+}
@@ -0,0 +1,205 @@
+extern crate stream_zipper;
+
+use std::fs;
+
+use stream_zipper::test_util::{assert_every_split, find_nth};
+use stream_zipper::zip::{self, CentralDirReport, Discrepancy, ZipError};
+use stream_zipper::State;
+
+/// Reads the archives with a zip stream that validates the central directory,
+/// returning the report of each archive once the stream has ended.
+fn read_reports(chunks: &[&[u8]]) -> Result<Vec<Option<CentralDirReport>>, ZipError> {
+    let options = zip::Options::default()
+        .validate_central_dir(true)
+        .concatenated_archives(true);
+    let mut zip_file = zip::start_stream_with(options);
+    let mut reports = Vec::new();
+    for chunk in chunks {
+        let mut input = *chunk;
+        loop {
+            match zip_file.read(input)? {
+                State::HasOutput { unparsed_input, .. } => input = unparsed_input,
+                State::NeedsInput => break,
+                State::NextFile {
+                    unparsed_input,
+                    next_file,
+                } => {
+                    zip_file = next_file;
+                    input = unparsed_input;
+                }
+                State::NextArchive {
+                    unparsed_input,
+                    next_file,
+                } => {
+                    reports.push(zip_file.central_dir_report().cloned());
+                    zip_file = next_file;
+                    input = unparsed_input;
+                }
+                State::NeedsInputOrEof(_) => {
+                    reports.push(zip_file.central_dir_report().cloned());
+                    return Ok(reports);
+                }
+                state => panic!("Unexpected state: {:?}", state),
+            }
+        }
+    }
+    panic!("The stream should have ended");
+}
+
+fn consistent(entries: u64) -> CentralDirReport {
+    CentralDirReport {
+        streamed_entries: entries,
+        central_dir_entries: entries,
+        discrepancies: Vec::new(),
+    }
+}
+
+#[test]
+fn test_consistent_archives() {
+    for (name, entries) in &[
+        ("comments.zip", 2),
+        ("zipped_ab.zip", 2),
+        ("zip64_descriptor.zip", 2),
+        ("symlinks.zip", 3),
+    ] {
+        let data_zip = fs::read(format!("tests/assets/zip/{}", name)).unwrap();
+        let report = read_reports(&[&data_zip]).unwrap().remove(0).unwrap();
+        assert_eq!(report, consistent(*entries), "{}", name);
+        assert!(report.is_consistent());
+    }
+
+    let data_zip = fs::read("tests/assets/zip/zipped_ab.zip").unwrap();
+    assert_every_split(&data_zip, read_reports);
+}
+
+#[test]
+fn test_concatenated_archives() {
+    let mut data_zip = fs::read("tests/assets/zip/comments.zip").unwrap();
+    data_zip.extend(fs::read("tests/assets/zip/zipped_ab.zip").unwrap());
+
+    // The offsets of each archive are counted from its start.
+    assert_eq!(
+        read_reports(&[&data_zip]),
+        Ok(vec![Some(consistent(2)), Some(consistent(2))])
+    );
+}
+
+#[test]
+fn test_not_validated_by_default() {
+    let data_zip = fs::read("tests/assets/zip/zipped_ab.zip").unwrap();
+
+    let mut zip_file = zip::start_stream();
+    let mut input = &data_zip[..];
+    loop {
+        match zip_file.read(input).unwrap() {
+            State::HasOutput { unparsed_input, .. } => input = unparsed_input,
+            State::NextFile {
+                unparsed_input,
+                next_file,
+            } => {
+                zip_file = next_file;
+                input = unparsed_input;
+            }
+            State::EndOfFile => break,
+            state => panic!("Unexpected state: {:?}", state),
+        }
+    }
+    assert!(zip_file.central_dir_end().is_some());
+    assert_eq!(zip_file.central_dir_report(), None);
+}
+
+#[test]
+fn test_mismatching_headers() {
+    let mut data_zip = fs::read("tests/assets/zip/comments.zip").unwrap();
+    let first = find_nth(&data_zip, b"PK\x01\x02", 0);
+    let second = find_nth(&data_zip, b"PK\x01\x02", 1);
+    data_zip[first + 16] ^= 1;
+    data_zip[first + 20] += 1;
+    data_zip[second + 42] += 1;
+
+    let report = read_reports(&[&data_zip]).unwrap().remove(0).unwrap();
+    let crc_32 = u32::from_le_bytes([
+        data_zip[first + 16],
+        data_zip[first + 17],
+        data_zip[first + 18],
+        data_zip[first + 19],
+    ]);
+    let compressed_size = u64::from(u32::from_le_bytes([
+        data_zip[first + 20],
+        data_zip[first + 21],
+        data_zip[first + 22],
+        data_zip[first + 23],
+    ]));
+    let second_offset = find_nth(&data_zip, b"PK\x03\x04", 1) as u64;
+    assert_eq!(
+        report.discrepancies,
+        vec![
+            Discrepancy::CompressedSize {
+                index: 0,
+                streamed: compressed_size - 1,
+                central_dir: compressed_size,
+            },
+            Discrepancy::Crc32 {
+                index: 0,
+                streamed: crc_32 ^ 1,
+                central_dir: crc_32,
+            },
+            Discrepancy::LocalHeaderOffset {
+                index: 1,
+                streamed: second_offset,
+                central_dir: second_offset + 1,
+            },
+        ]
+    );
+    assert!(!report.is_consistent());
+}
+
+#[test]
+fn test_missing_central_dir_header() {
+    let data_zip = fs::read("tests/assets/zip/zipped_ab.zip").unwrap();
+    // The central directory ends right after its first header, but
+    // the end of central directory record still counts two entries.
+    let second = find_nth(&data_zip, b"PK\x01\x02", 1);
+    let end = find_nth(&data_zip, b"PK\x05\x06", 0);
+    let mut truncated = data_zip[..second].to_vec();
+    truncated.extend_from_slice(&data_zip[end..]);
+
+    let report = read_reports(&[&truncated]).unwrap().remove(0).unwrap();
+    assert_eq!(
+        report.discrepancies,
+        vec![
+            Discrepancy::EntryCount {
+                streamed: 2,
+                central_dir: 1,
+            },
+            Discrepancy::RecordedEntryCount {
+                central_dir: 1,
+                central_dir_end: 2,
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_mismatching_central_dir_end() {
+    let mut data_zip = fs::read("tests/assets/zip/zipped_ab.zip").unwrap();
+    let end = find_nth(&data_zip, b"PK\x05\x06", 0);
+    let central_dir = find_nth(&data_zip, b"PK\x01\x02", 0) as u64;
+    data_zip[end + 10] = 3;
+    data_zip[end + 16] += 2;
+
+    let report = read_reports(&[&data_zip]).unwrap().remove(0).unwrap();
+    assert_eq!(
+        report.discrepancies,
+        vec![
+            Discrepancy::RecordedEntryCount {
+                central_dir: 2,
+                central_dir_end: 3,
+            },
+            Discrepancy::CentralDirOffset {
+                streamed: central_dir,
+                central_dir_end: central_dir + 2,
+            },
+        ]
+    );
+}
@@ -0,0 +1,116 @@
+extern crate stream_zipper;
+
+use std::fs;
+use std::io::Cursor;
+
+use stream_zipper::test_util::{assert_every_split, read_zip_entries};
+use stream_zipper::utils::update_crc32;
+use stream_zipper::zip::{self, EntryError, SeekArchive, SeekError, ZipError};
+
+/// Flips the lowest bit of the little-endian CRC-32 at `offset`,
+/// returning the CRC-32 before and after.
+fn corrupt_crc(data: &mut [u8], offset: usize) -> (u32, u32) {
+    let crc = |data: &[u8]| u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+    let original = crc(&data[offset..]);
+    data[offset] ^= 1;
+    (original, crc(&data[offset..]))
+}
+
+#[test]
+fn test_crc32() {
+    assert_eq!(update_crc32(0, b""), 0);
+    assert_eq!(update_crc32(0, b"123456789"), 0xcbf4_3926);
+
+    let data_repetitive = fs::read("tests/assets/uncompressed/repetitive_data.txt").unwrap();
+    let whole = update_crc32(0, &data_repetitive);
+    for split in 0..20 {
+        let (first, second) = data_repetitive.split_at(split);
+        assert_eq!(update_crc32(update_crc32(0, first), second), whole);
+    }
+}
+
+#[test]
+fn test_checksum_in_local_header() {
+    let mut data_zip = fs::read("tests/assets/zip/comments.zip").unwrap();
+    let data_short = fs::read("tests/assets/uncompressed/short_data.txt").unwrap();
+    // The CRC-32 of the first entry.
+    let (actual, expected) = corrupt_crc(&mut data_zip, 14);
+
+    let error = Err(ZipError::InEntry(Box::new(EntryError {
+        index: 0,
+        name: b"short_data.txt".to_vec(),
+        error: ZipError::ChecksumMismatch { expected, actual },
+    })));
+    assert_eq!(
+        read_zip_entries(&[&data_zip], zip::Options::default()),
+        error
+    );
+    assert_every_split(&data_zip, |chunks| {
+        read_zip_entries(chunks, zip::Options::default())
+    });
+
+    let options = zip::Options::default().verify_checksums(false);
+    let entries = read_zip_entries(&[&data_zip], options).unwrap();
+    assert_eq!(entries[0].data, data_short);
+}
+
+#[test]
+fn test_checksum_in_data_descriptor() {
+    let mut data_zip = fs::read("tests/assets/zip/zipped_ab.zip").unwrap();
+    let descriptor = data_zip
+        .windows(4)
+        .position(|window| window == b"PK\x07\x08")
+        .unwrap();
+    let (actual, expected) = corrupt_crc(&mut data_zip, descriptor + 4);
+
+    assert_eq!(
+        read_zip_entries(&[&data_zip], zip::Options::default())
+            .map_err(|e| e.without_entry().clone()),
+        Err(ZipError::ChecksumMismatch { expected, actual })
+    );
+    assert_every_split(&data_zip, |chunks| {
+        read_zip_entries(chunks, zip::Options::default())
+    });
+
+    let options = zip::Options::default().verify_checksums(false);
+    assert_eq!(
+        read_zip_entries(&[&data_zip], options).map(|entries| entries.len()),
+        Ok(2)
+    );
+}
+
+#[test]
+fn test_skipped_entries_are_not_verified() {
+    let mut data_zip = fs::read("tests/assets/zip/comments.zip").unwrap();
+    corrupt_crc(&mut data_zip, 14);
+
+    let options =
+        zip::Options::default().entry_filter(|header| header.filename != b"short_data.txt");
+    assert_eq!(
+        read_zip_entries(&[&data_zip], options).map(|entries| entries.len()),
+        Ok(2)
+    );
+}
+
+#[test]
+fn test_checksum_with_seek_archive() {
+    let mut data_zip = fs::read("tests/assets/zip/comments.zip").unwrap();
+    let central_dir = data_zip
+        .windows(4)
+        .position(|window| window == b"PK\x01\x02")
+        .unwrap();
+    let (actual, expected) = corrupt_crc(&mut data_zip, central_dir + 16);
+
+    let mut archive = SeekArchive::new(Cursor::new(&data_zip)).unwrap();
+    match archive.read_entry(0, |_| Ok(())) {
+        Err(SeekError::Zip(error)) => {
+            assert_eq!(error, ZipError::ChecksumMismatch { expected, actual })
+        }
+        result => panic!("Unexpected result: {:?}", result),
+    }
+    assert!(archive.read_entry(1, |_| Ok(())).is_ok());
+
+    let options = zip::Options::default().verify_checksums(false);
+    let mut archive = SeekArchive::with_options(Cursor::new(&data_zip), options).unwrap();
+    assert!(archive.read_entry(0, |_| Ok(())).is_ok());
+}
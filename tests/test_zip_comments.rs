@@ -0,0 +1,172 @@
+extern crate stream_zipper;
+
+use std::fs;
+use std::sync::{Arc, Mutex};
+
+use stream_zipper::test_util::assert_every_split;
+use stream_zipper::zip::{self, ZipError, ZipFile};
+use stream_zipper::State;
+
+/// Reads the archive with a zip stream started with `options`,
+/// returning its last file once the stream has ended.
+fn read_to_end(chunks: &[&[u8]], options: zip::Options) -> Result<ZipFile, ZipError> {
+    let mut zip_file = zip::start_stream_with(options);
+    for chunk in chunks {
+        let mut input = *chunk;
+        loop {
+            match zip_file.read(input)? {
+                State::HasOutput { unparsed_input, .. } => input = unparsed_input,
+                State::NeedsInput => break,
+                State::NextFile {
+                    unparsed_input,
+                    next_file,
+                } => {
+                    zip_file = next_file;
+                    input = unparsed_input;
+                }
+                State::EndOfFile => return Ok(zip_file),
+                state => panic!("Unexpected state: {:?}", state),
+            }
+        }
+    }
+    panic!("The stream should have ended");
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Comments {
+    archive: Option<Vec<u8>>,
+    /// The names and the comments of the entries.
+    entries: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+fn comments(zip_file: &ZipFile) -> Comments {
+    Comments {
+        archive: zip_file.archive_comment().map(<[u8]>::to_vec),
+        entries: zip_file
+            .central_dir()
+            .iter()
+            .map(|header| (header.filename.clone(), header.comment.clone()))
+            .collect(),
+    }
+}
+
+#[test]
+fn test_comments() {
+    let data_zip = fs::read("tests/assets/zip/comments.zip").unwrap();
+
+    let options = zip::Options::default().keep_central_dir(true);
+    let expected = Comments {
+        archive: Some(b"archive comment".to_vec()),
+        entries: vec![
+            (
+                b"short_data.txt".to_vec(),
+                b"comment for short_data.txt".to_vec(),
+            ),
+            (
+                b"repetitive_data.txt".to_vec(),
+                b"comment for repetitive_data.txt".to_vec(),
+            ),
+        ],
+    };
+    assert_eq!(
+        read_to_end(&[&data_zip], options.clone()).map(|file| comments(&file)),
+        Ok(expected)
+    );
+    assert_every_split(&data_zip, |chunks| {
+        read_to_end(chunks, options.clone()).map(|file| comments(&file))
+    });
+}
+
+#[test]
+fn test_central_dir_callback() {
+    let data_zip = fs::read("tests/assets/zip/comments.zip").unwrap();
+
+    // The headers are passed to the callback without keeping them.
+    let read_comments = |chunks: &[&[u8]]| {
+        let headers = Arc::new(Mutex::new(Vec::new()));
+        let callback_headers = Arc::clone(&headers);
+        let options = zip::Options::default().on_central_directory(move |header| {
+            callback_headers
+                .lock()
+                .unwrap()
+                .push((header.filename.clone(), header.comment.clone()));
+        });
+        let zip_file = read_to_end(chunks, options).unwrap();
+        assert!(zip_file.central_dir().is_empty());
+        let entries = headers.lock().unwrap().clone();
+        entries
+    };
+    assert_eq!(
+        read_comments(&[&data_zip]),
+        vec![
+            (
+                b"short_data.txt".to_vec(),
+                b"comment for short_data.txt".to_vec(),
+            ),
+            (
+                b"repetitive_data.txt".to_vec(),
+                b"comment for repetitive_data.txt".to_vec(),
+            ),
+        ]
+    );
+    assert_every_split(&data_zip, read_comments);
+}
+
+#[test]
+fn test_archive_comment_without_central_dir() {
+    let data_zip = fs::read("tests/assets/zip/comments.zip").unwrap();
+
+    let zip_file = read_to_end(&[&data_zip], zip::Options::default()).unwrap();
+    assert_eq!(zip_file.archive_comment(), Some(&b"archive comment"[..]));
+    assert_eq!(
+        zip_file
+            .central_dir_end()
+            .map(|end| end.central_dir_num_entries_total),
+        Some(2)
+    );
+    assert!(zip_file.central_dir().is_empty());
+}
+
+#[test]
+fn test_comments_before_the_end() {
+    let data_zip = fs::read("tests/assets/zip/comments.zip").unwrap();
+
+    // The first entry isn't the last one, so it never has the comments.
+    let options = zip::Options::default().keep_central_dir(true);
+    let mut zip_file = zip::start_stream_with(options);
+    match zip_file.read(&data_zip) {
+        Ok(State::HasOutput { .. }) => (),
+        state => panic!("Unexpected state: {:?}", state),
+    }
+    assert_eq!(zip_file.archive_comment(), None);
+    assert!(zip_file.central_dir().is_empty());
+}
+
+#[test]
+fn test_comments_of_concatenated_archives() {
+    let data_zip = fs::read("tests/assets/zip/comments.zip").unwrap();
+
+    // The archive that has ended keeps its comments
+    // while the stream waits for another archive.
+    let options = zip::Options::default()
+        .keep_central_dir(true)
+        .concatenated_archives(true);
+    let mut zip_file = zip::start_stream_with(options);
+    let mut input = &data_zip[..];
+    loop {
+        match zip_file.read(input).unwrap() {
+            State::HasOutput { unparsed_input, .. } => input = unparsed_input,
+            State::NextFile {
+                unparsed_input,
+                next_file,
+            } => {
+                zip_file = next_file;
+                input = unparsed_input;
+            }
+            State::NeedsInputOrEof(_) => break,
+            state => panic!("Unexpected state: {:?}", state),
+        }
+    }
+    assert_eq!(zip_file.archive_comment(), Some(&b"archive comment"[..]));
+    assert_eq!(zip_file.central_dir().len(), 2);
+}
@@ -0,0 +1,100 @@
+extern crate stream_zipper;
+
+use std::fs;
+
+use stream_zipper::test_util::assert_every_split;
+use stream_zipper::utils::update_crc32;
+use stream_zipper::zip::headers::DataDescriptor;
+use stream_zipper::zip::{self, ZipError};
+use stream_zipper::State;
+
+/// Reads the archive, returning the data descriptor of each entry.
+fn read_descriptors(chunks: &[&[u8]]) -> Result<Vec<Option<DataDescriptor>>, ZipError> {
+    let mut zip_file = zip::start_stream();
+    let mut descriptors = Vec::new();
+    for chunk in chunks {
+        let mut input = *chunk;
+        loop {
+            match zip_file.read(input)? {
+                State::HasOutput { unparsed_input, .. } => input = unparsed_input,
+                State::NeedsInput => break,
+                State::NextFile {
+                    unparsed_input,
+                    next_file,
+                } => {
+                    descriptors.push(zip_file.data_descriptor().cloned());
+                    zip_file = next_file;
+                    input = unparsed_input;
+                }
+                State::EndOfFile => {
+                    descriptors.push(zip_file.data_descriptor().cloned());
+                    return Ok(descriptors);
+                }
+                state => panic!("Unexpected state: {:?}", state),
+            }
+        }
+    }
+    panic!("The stream should have ended");
+}
+
+#[test]
+fn test_data_descriptors() {
+    let data_zip = fs::read("tests/assets/zip/zipped_ab.zip").unwrap();
+    let data_a = fs::read("tests/assets/uncompressed/zipped_a.txt").unwrap();
+    let data_b = fs::read("tests/assets/uncompressed/zipped_b.txt").unwrap();
+
+    let descriptor = |data: &[u8]| DataDescriptor {
+        tag: true,
+        crc_32: update_crc32(0, data),
+        compressed_size: 28,
+        uncompressed_size: data.len() as u64,
+    };
+    assert_eq!(
+        read_descriptors(&[&data_zip]),
+        Ok(vec![Some(descriptor(&data_a)), Some(descriptor(&data_b))])
+    );
+    assert_every_split(&data_zip, read_descriptors);
+}
+
+#[test]
+fn test_data_descriptors_without_signature() {
+    let data_zip = fs::read("tests/assets/zip/unsigned_descriptors.zip").unwrap();
+    let data_short = fs::read("tests/assets/uncompressed/short_data.txt").unwrap();
+
+    let descriptors = read_descriptors(&[&data_zip]).unwrap();
+    assert_eq!(
+        descriptors[0],
+        Some(DataDescriptor {
+            tag: false,
+            crc_32: update_crc32(0, &data_short),
+            compressed_size: 12,
+            uncompressed_size: 60,
+        })
+    );
+}
+
+#[test]
+fn test_data_descriptor_of_stored_entry() {
+    // The descriptor is found by scanning the data.
+    let data_zip = fs::read("tests/assets/zip/stored_descriptors.zip").unwrap();
+    let data_short = fs::read("tests/assets/uncompressed/short_data.txt").unwrap();
+
+    let descriptors = read_descriptors(&[&data_zip]).unwrap();
+    assert_eq!(
+        descriptors[0],
+        Some(DataDescriptor {
+            tag: true,
+            crc_32: update_crc32(0, &data_short),
+            compressed_size: 60,
+            uncompressed_size: 60,
+        })
+    );
+}
+
+#[test]
+fn test_no_data_descriptors() {
+    // The sizes are in the local file headers.
+    let data_zip = fs::read("tests/assets/zip/comments.zip").unwrap();
+
+    assert_eq!(read_descriptors(&[&data_zip]), Ok(vec![None, None]));
+}
@@ -0,0 +1,53 @@
+extern crate stream_zipper;
+
+use std::fs;
+
+use stream_zipper::test_util::{assert_random_splits, read_chunks, Entry};
+use stream_zipper::zip::headers::CompressionMethod;
+use stream_zipper::zip::{self, ZipError};
+use stream_zipper::Error;
+
+#[test]
+fn test_deflate64() {
+    // The first entry uses distances over 32 KiB and lengths over 258 bytes,
+    // the second one is a plain Deflate entry.
+    let data_zip = fs::read("tests/assets/zip/deflate64.zip").unwrap();
+    let data_deflate64 = fs::read("tests/assets/uncompressed/deflate64.bin").unwrap();
+    let data_short = fs::read("tests/assets/uncompressed/short_data.txt").unwrap();
+
+    assert_eq!(
+        read_chunks(&[&data_zip]),
+        Ok(vec![
+            Entry {
+                name: Some(b"deflate64.bin".to_vec()),
+                data: data_deflate64,
+            },
+            Entry {
+                name: Some(b"short_data.txt".to_vec()),
+                data: data_short,
+            },
+        ])
+    );
+
+    assert_random_splits(&data_zip, 1502, 10, 3000, read_chunks);
+}
+
+#[test]
+fn test_unsupported_compression_method() {
+    let mut data_zip = fs::read("tests/assets/zip/short_data.txt.zip").unwrap();
+    // Patch the compression method of the local file header to IBM TERSE
+    data_zip[8] = 18;
+
+    let res = zip::start_stream().read_with(&data_zip, |_| ());
+    assert_eq!(
+        res.unwrap_err().without_entry(),
+        &ZipError::UnsupportedCompressionMethod(CompressionMethod::IbmTerse)
+    );
+    match read_chunks(&[&data_zip]) {
+        Err(Error::Zip(e)) => assert_eq!(
+            e.without_entry(),
+            &ZipError::UnsupportedCompressionMethod(CompressionMethod::IbmTerse)
+        ),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+}
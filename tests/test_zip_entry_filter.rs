@@ -0,0 +1,73 @@
+extern crate stream_zipper;
+
+use std::fs;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use stream_zipper::test_util::{assert_every_split, read_zip_entries, Entry};
+use stream_zipper::zip;
+
+#[test]
+fn test_entry_filter() {
+    let data_zip = fs::read("tests/assets/zip/zipped_ab.zip").unwrap();
+    let data_b = fs::read("tests/assets/uncompressed/zipped_b.txt").unwrap();
+
+    let options =
+        zip::Options::default().entry_filter(|header| header.filename.ends_with(b"_b.txt"));
+    assert_eq!(
+        read_zip_entries(&[&data_zip], options.clone()),
+        Ok(vec![
+            Entry {
+                name: Some(b"zipped_a.txt".to_vec()),
+                data: Vec::new(),
+            },
+            Entry {
+                name: Some(b"zipped_b.txt".to_vec()),
+                data: data_b,
+            },
+        ])
+    );
+    assert_every_split(&data_zip, |chunks| {
+        read_zip_entries(chunks, options.clone())
+    });
+}
+
+#[test]
+fn test_entry_filter_called_once_per_entry() {
+    let data_zip = fs::read("tests/assets/zip/zip64_descriptor.zip").unwrap();
+    let data_repetitive = fs::read("tests/assets/uncompressed/repetitive_data.txt").unwrap();
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    let filter_calls = calls.clone();
+    let options = zip::Options::default().entry_filter(move |header| {
+        filter_calls.fetch_add(1, Ordering::SeqCst);
+        header.filename != b"short_data.txt"
+    });
+    let chunks: Vec<&[u8]> = data_zip.chunks(16).collect();
+    assert_eq!(
+        read_zip_entries(&chunks, options),
+        Ok(vec![
+            Entry {
+                name: Some(b"short_data.txt".to_vec()),
+                data: Vec::new(),
+            },
+            Entry {
+                name: Some(b"repetitive_data.txt".to_vec()),
+                data: data_repetitive,
+            },
+        ])
+    );
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn test_entry_filter_skips_encrypted_entries() {
+    // Rejected entries aren't decrypted, so they need no password.
+    let data_zip = fs::read("tests/assets/zip/zipcrypto.zip").unwrap();
+
+    let options = zip::Options::default().entry_filter(|header| !header.encrypted);
+    assert_eq!(
+        read_zip_entries(&[&data_zip], options).map(|entries| entries.len()),
+        Ok(2)
+    );
+}
@@ -0,0 +1,101 @@
+extern crate stream_zipper;
+
+use std::fs;
+
+use stream_zipper::test_util::{assert_every_split, read_chunks};
+use stream_zipper::zip::{self, ZipError};
+use stream_zipper::{Error, State};
+
+/// The offset of the compressed size in the local file header of the first entry.
+const COMPRESSED_SIZE_OFFSET: usize = 18;
+/// The offset of the uncompressed size in the local file header of the first entry.
+const UNCOMPRESSED_SIZE_OFFSET: usize = 22;
+
+/// Reads comments.zip with the size at `offset` replaced by `size`. The first entry
+/// has its sizes in the local file header, 12 bytes compressed and 60 bytes uncompressed.
+fn with_size(offset: usize, size: u32) -> Vec<u8> {
+    let mut data_zip = fs::read("tests/assets/zip/comments.zip").unwrap();
+    data_zip[offset..offset + 4].copy_from_slice(&size.to_le_bytes());
+    data_zip
+}
+
+#[test]
+fn test_compressed_size_too_small() {
+    // The decompressor doesn't get the last byte of the data,
+    // so the Deflate stream doesn't end.
+    let data_zip = with_size(COMPRESSED_SIZE_OFFSET, 11);
+
+    let mut zip_file = zip::start_stream();
+    let mut input = &data_zip[..];
+    let error = loop {
+        match zip_file.read(input) {
+            Ok(State::HasOutput { unparsed_input, .. }) => input = unparsed_input,
+            Ok(state) => panic!("Unexpected state: {:?}", state),
+            Err(e) => break e,
+        }
+    };
+    assert_eq!(error.without_entry(), &ZipError::EntrySizeMismatch);
+    assert_every_split(&data_zip, |chunks| {
+        read_chunks(chunks).map_err(|e| match e {
+            Error::Zip(e) => e.without_entry() == &ZipError::EntrySizeMismatch,
+            _ => false,
+        })
+    });
+}
+
+#[test]
+fn test_compressed_size_too_large() {
+    // The Deflate stream ends before the data does.
+    let data_zip = with_size(COMPRESSED_SIZE_OFFSET, 13);
+
+    match read_chunks(&[&data_zip]) {
+        Err(Error::Zip(e)) => assert_eq!(e.without_entry(), &ZipError::EntrySizeMismatch),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+}
+
+#[test]
+fn test_uncompressed_size_mismatch() {
+    let data_zip = with_size(UNCOMPRESSED_SIZE_OFFSET, 59);
+
+    match read_chunks(&[&data_zip]) {
+        Err(Error::Zip(e)) => assert_eq!(e.without_entry(), &ZipError::EntrySizeMismatch),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+}
+
+#[test]
+fn test_size_mismatch_recovery() {
+    let data_zip = with_size(COMPRESSED_SIZE_OFFSET, 11);
+    let data_repetitive = fs::read("tests/assets/uncompressed/repetitive_data.txt").unwrap();
+
+    // The rest of the damaged entry is skipped up to the next local file header.
+    let options = zip::Options::default().recover_damaged_entries(true);
+    let mut zip_file = zip::start_stream_with(options);
+    let mut input = &data_zip[..];
+    let mut data = Vec::new();
+    loop {
+        match zip_file.read(input).unwrap() {
+            State::HasOutput {
+                unparsed_input,
+                output,
+            } => {
+                data.extend_from_slice(output);
+                input = unparsed_input;
+            }
+            State::NextFile {
+                unparsed_input,
+                next_file,
+            } => {
+                assert_eq!(zip_file.damage(), Some(ZipError::EntrySizeMismatch));
+                data.clear();
+                zip_file = next_file;
+                input = unparsed_input;
+            }
+            State::EndOfFile => break,
+            state => panic!("Unexpected state: {:?}", state),
+        }
+    }
+    assert_eq!(zip_file.damage(), None);
+    assert_eq!(data, data_repetitive);
+}
@@ -0,0 +1,271 @@
+extern crate stream_zipper;
+
+use std::fs;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use stream_zipper::zip;
+use stream_zipper::zip::headers::{
+    ExtendedTimestamp, ExtraField, HeaderId, LocalFileHeader, NtfsTimestamps, UnixExtraField,
+};
+use stream_zipper::State;
+
+fn unix_time(secs: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+#[test]
+fn test_extended_timestamp_local() {
+    let data_zip = fs::read("tests/assets/zip/timestamps.zip").unwrap();
+
+    let (_, header) = LocalFileHeader::parse(&data_zip).unwrap();
+    let timestamp = ExtendedTimestamp {
+        modified: Some(unix_time(1_614_834_367)),
+        accessed: Some(unix_time(1_641_092_645)),
+        created: None,
+    };
+    assert_eq!(header.extended_timestamp(), Some(timestamp));
+    assert_eq!(
+        header.typed_extra_fields()[0],
+        ExtraField::ExtendedTimestamp(timestamp)
+    );
+    // The MS-DOS time is rounded to even seconds.
+    assert_ne!(Some(header.last_mod), timestamp.modified);
+}
+
+#[test]
+fn test_extended_timestamp_central_dir() {
+    let data_zip = fs::read("tests/assets/zip/timestamps.zip").unwrap();
+
+    let entries = zip::list_entries(&data_zip).unwrap();
+    assert_eq!(
+        entries[0].extended_timestamp(),
+        Some(ExtendedTimestamp {
+            modified: Some(unix_time(1_614_834_367)),
+            accessed: None,
+            created: None,
+        })
+    );
+}
+
+#[test]
+fn test_extended_timestamp_while_streaming() {
+    let data_zip = fs::read("tests/assets/zip/timestamps.zip").unwrap();
+
+    let mut zip_file = zip::start_stream();
+    match zip_file.read(&data_zip) {
+        Ok(State::HasOutput { .. }) => (),
+        state => panic!("Unexpected state: {:?}", state),
+    }
+    let timestamp = zip_file
+        .header()
+        .and_then(|header| header.extended_timestamp());
+    assert_eq!(
+        timestamp.and_then(|timestamp| timestamp.modified),
+        Some(unix_time(1_614_834_367))
+    );
+    // The extended timestamp is preferred to the MS-DOS time.
+    assert_eq!(zip_file.last_modified(), Some(unix_time(1_614_834_367)));
+}
+
+#[test]
+fn test_last_modified_without_extra_fields() {
+    let data_zip = fs::read("tests/assets/zip/comments.zip").unwrap();
+
+    let (_, header) = LocalFileHeader::parse(&data_zip).unwrap();
+    let mut zip_file = zip::start_stream();
+    assert_eq!(zip_file.last_modified(), None);
+    match zip_file.read(&data_zip) {
+        Ok(State::HasOutput { .. }) => (),
+        state => panic!("Unexpected state: {:?}", state),
+    }
+    assert_eq!(zip_file.last_modified(), Some(header.last_mod));
+}
+
+#[test]
+fn test_extended_timestamp_parse() {
+    // All three times, the last one before the epoch.
+    let data = [0b111, 1, 0, 0, 0, 2, 0, 0, 0, 0xff, 0xff, 0xff, 0xff];
+    assert_eq!(
+        ExtendedTimestamp::parse(&data),
+        Some(ExtendedTimestamp {
+            modified: Some(unix_time(1)),
+            accessed: Some(unix_time(2)),
+            created: Some(UNIX_EPOCH - Duration::from_secs(1)),
+        })
+    );
+
+    // Only the flagged times are read, in order.
+    let data = [0b100, 3, 0, 0, 0];
+    assert_eq!(
+        ExtendedTimestamp::parse(&data),
+        Some(ExtendedTimestamp {
+            modified: None,
+            accessed: None,
+            created: Some(unix_time(3)),
+        })
+    );
+
+    assert_eq!(ExtendedTimestamp::parse(&[]), None);
+}
+
+#[test]
+fn test_untyped_extra_fields() {
+    let data = [1, 2, 3];
+    assert_eq!(
+        ExtraField::parse(&HeaderId::Os2, &data),
+        ExtraField::Other(&HeaderId::Os2, &data)
+    );
+    // A field that isn't valid is left unparsed.
+    assert_eq!(
+        ExtraField::parse(&HeaderId::WinZipAes, &data),
+        ExtraField::Other(&HeaderId::WinZipAes, &data)
+    );
+}
+
+#[test]
+fn test_ntfs_timestamps() {
+    let data_zip = fs::read("tests/assets/zip/ntfs_timestamps.zip").unwrap();
+
+    let timestamps = NtfsTimestamps {
+        modified: UNIX_EPOCH + Duration::new(1_614_834_367, 123_456_700),
+        accessed: unix_time(1_641_092_645),
+        created: UNIX_EPOCH + Duration::new(946_684_799, 500_000_000),
+    };
+    let (_, header) = LocalFileHeader::parse(&data_zip).unwrap();
+    assert_eq!(header.ntfs_timestamps(), Some(timestamps));
+    assert_eq!(
+        header.typed_extra_fields(),
+        vec![ExtraField::Ntfs(timestamps)]
+    );
+    let entries = zip::list_entries(&data_zip).unwrap();
+    assert_eq!(entries[0].ntfs_timestamps(), Some(timestamps));
+}
+
+#[test]
+fn test_ntfs_timestamps_parse() {
+    // An unknown attribute before the timestamps is skipped.
+    let mut data = vec![0, 0, 0, 0, 2, 0, 2, 0, 0xaa, 0xbb, 1, 0, 24, 0];
+    // The Windows epoch, 1601-01-01, and 1601-01-01 plus 100 ns and one second.
+    data.extend_from_slice(&0u64.to_le_bytes());
+    data.extend_from_slice(&1u64.to_le_bytes());
+    data.extend_from_slice(&10_000_000u64.to_le_bytes());
+    let windows_epoch = UNIX_EPOCH - Duration::from_secs(11_644_473_600);
+    assert_eq!(
+        NtfsTimestamps::parse(&data),
+        Some(NtfsTimestamps {
+            modified: windows_epoch,
+            accessed: windows_epoch + Duration::from_nanos(100),
+            created: windows_epoch + Duration::from_secs(1),
+        })
+    );
+
+    // Truncated attributes.
+    assert_eq!(NtfsTimestamps::parse(&data[..20]), None);
+    // No timestamp attribute.
+    assert_eq!(NtfsTimestamps::parse(&data[..10]), None);
+}
+
+#[test]
+fn test_unix_original() {
+    let data_zip = fs::read("tests/assets/zip/rand_data.bin.zip").unwrap();
+
+    let (_, header) = LocalFileHeader::parse(&data_zip).unwrap();
+    assert_eq!(
+        header.typed_extra_fields(),
+        vec![ExtraField::UnixOriginal(UnixExtraField {
+            accessed: Some(unix_time(1_523_288_798)),
+            modified: Some(unix_time(1_523_288_758)),
+            uid: Some(501),
+            gid: Some(20),
+        })]
+    );
+    assert_eq!(header.unix_owner(), Some((501, 20)));
+
+    // The central directory has only the times.
+    let entries = zip::list_entries(&data_zip).unwrap();
+    assert_eq!(entries[0].unix_owner(), None);
+    assert_eq!(
+        UnixExtraField::parse_original(&entries[0].extra_fields[0].1),
+        Some(UnixExtraField {
+            accessed: Some(unix_time(1_523_288_798)),
+            modified: Some(unix_time(1_523_288_758)),
+            uid: None,
+            gid: None,
+        })
+    );
+}
+
+#[test]
+fn test_unix_uid_gid() {
+    let data_zip = fs::read("tests/assets/zip/timestamps.zip").unwrap();
+
+    let (_, header) = LocalFileHeader::parse(&data_zip).unwrap();
+    assert_eq!(
+        header.typed_extra_fields()[1],
+        ExtraField::UnixUidGid(UnixExtraField {
+            uid: Some(0),
+            gid: Some(0),
+            ..UnixExtraField::default()
+        })
+    );
+    assert_eq!(header.unix_owner(), Some((0, 0)));
+
+    // 32-bit and 8-bit IDs.
+    let data = [1, 4, 0x40, 0x42, 0x0f, 0, 1, 100];
+    assert_eq!(
+        UnixExtraField::parse_uid_gid(&data),
+        Some(UnixExtraField {
+            uid: Some(1_000_000),
+            gid: Some(100),
+            ..UnixExtraField::default()
+        })
+    );
+    // An ID that doesn't fit in 32 bits.
+    assert_eq!(
+        UnixExtraField::parse_uid_gid(&[1, 8, 0, 0, 0, 0, 1, 0, 0, 0, 1, 0]),
+        None
+    );
+    assert_eq!(UnixExtraField::parse_uid_gid(&[2, 1, 0, 1, 0]), None);
+}
+
+#[test]
+fn test_unix_new() {
+    assert_eq!(
+        UnixExtraField::parse_new(&[0xe8, 0x03, 0x64, 0]),
+        Some(UnixExtraField {
+            uid: Some(1000),
+            gid: Some(100),
+            ..UnixExtraField::default()
+        })
+    );
+    // Central directory headers have an empty field.
+    assert_eq!(
+        UnixExtraField::parse_new(&[]),
+        Some(UnixExtraField::default())
+    );
+    assert_eq!(UnixExtraField::parse_new(&[1, 2]), None);
+}
+
+#[test]
+fn test_unknown_header_id() {
+    // A local file header with a vendor-specific extra field, 0xcafe, after a known one.
+    let mut data = b"PK\x03\x04\x14\x00\x00\x00\x08\x00\x00\x00\x21\x00".to_vec();
+    data.extend_from_slice(&[0; 12]);
+    data.extend_from_slice(&[1, 0, 15, 0]);
+    data.push(b'a');
+    data.extend_from_slice(&[0x55, 0x54, 5, 0, 1, 0, 0, 0, 0]);
+    data.extend_from_slice(&[0xfe, 0xca, 2, 0, 0xab, 0xcd]);
+
+    let (_, header) = LocalFileHeader::parse(&data).unwrap();
+    assert_eq!(
+        header.extra_fields,
+        vec![
+            (HeaderId::ExtendedTimestamp, vec![1, 0, 0, 0, 0]),
+            (HeaderId::Unknown(0xcafe), vec![0xab, 0xcd]),
+        ]
+    );
+    assert_eq!(
+        header.typed_extra_fields()[1],
+        ExtraField::Other(&HeaderId::Unknown(0xcafe), &[0xab, 0xcd])
+    );
+}
@@ -0,0 +1,96 @@
+extern crate stream_zipper;
+
+use std::fs;
+
+use stream_zipper::zip;
+use stream_zipper::State;
+
+/// Feeds the archive to the zip stream in chunks, returning
+/// the uncompressed data and the filename reported for the entry.
+fn read_in_chunks(data_zip: &[u8], chunk_size: usize, options: zip::Options) -> (Vec<u8>, Vec<u8>) {
+    let mut zip_file = zip::start_stream_with(options);
+    let mut output = Vec::new();
+    let mut filename = None;
+
+    for chunk in data_zip.chunks(chunk_size) {
+        let mut input = chunk;
+        loop {
+            match zip_file.read(input).unwrap() {
+                State::HasOutput {
+                    unparsed_input,
+                    output: out,
+                } => {
+                    output.extend_from_slice(out);
+                    input = unparsed_input;
+                }
+                State::NeedsInput => break,
+                State::EndOfFile => {
+                    filename = zip_file.filename().map(<[u8]>::to_vec);
+                    break;
+                }
+                state => panic!("Unexpected state: {:?}", state),
+            }
+        }
+    }
+
+    (output, filename.expect("The stream should have ended"))
+}
+
+#[test]
+fn test_lazy_header_fields() {
+    // The local header has a 30000 byte extra field, and the central directory
+    // has the same extra field, a 30000 byte file comment and a 30000 byte archive comment.
+    let data_zip = fs::read("tests/assets/zip/long_header_fields.zip").unwrap();
+    let data = fs::read("tests/assets/uncompressed/short_data.txt").unwrap();
+
+    let (output, filename) = read_in_chunks(&data_zip, 100, zip::Options::default());
+    assert_eq!(output, data);
+    assert_eq!(filename, b"short_data.txt");
+
+    let options = zip::Options::default().lazy_header_fields(1024);
+    for &chunk_size in &[7, 100, 4096, data_zip.len()] {
+        let (output, filename) = read_in_chunks(&data_zip, chunk_size, options.clone());
+        assert_eq!(output, data);
+        // The filename was skipped together with the oversized extra field.
+        assert_eq!(filename, b"");
+    }
+}
+
+#[test]
+fn test_lazy_header_fields_under_threshold() {
+    let data_zip = fs::read("tests/assets/zip/long_header_fields.zip").unwrap();
+    let data = fs::read("tests/assets/uncompressed/short_data.txt").unwrap();
+
+    let options = zip::Options::default().lazy_header_fields(100_000);
+    let (output, filename) = read_in_chunks(&data_zip, 100, options);
+    assert_eq!(output, data);
+    assert_eq!(filename, b"short_data.txt");
+}
+
+#[test]
+fn test_lazy_header_fields_peek_stream() {
+    let data_zip = fs::read("tests/assets/zip/zipped_ab.zip").unwrap();
+
+    // Neither filename fits under the threshold, so both are skipped
+    // but the entries are still read through.
+    let mut zip_file = zip::start_stream_with(zip::Options::default().lazy_header_fields(0));
+    let mut input = &data_zip[..];
+    let mut files = 1;
+    loop {
+        match zip_file.read(input).unwrap() {
+            State::HasOutput { unparsed_input, .. } => input = unparsed_input,
+            State::NextFile {
+                unparsed_input,
+                next_file,
+            } => {
+                assert_eq!(next_file.filename(), Some(&b""[..]));
+                input = unparsed_input;
+                zip_file = next_file;
+                files += 1;
+            }
+            State::EndOfFile => break,
+            state => panic!("Unexpected state: {:?}", state),
+        }
+    }
+    assert_eq!(files, 2);
+}
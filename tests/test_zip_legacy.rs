@@ -0,0 +1,89 @@
+extern crate stream_zipper;
+
+use std::fs;
+
+use stream_zipper::test_util::{assert_random_splits, read_chunks, Entry};
+use stream_zipper::zip::ZipError;
+use stream_zipper::Error;
+
+fn entry(name: &str, data: &[u8]) -> Entry {
+    Entry {
+        name: Some(name.as_bytes().to_vec()),
+        data: data.to_vec(),
+    }
+}
+
+#[test]
+fn test_shrunk() {
+    // The longer entry fills up the code table, so it has partial clears.
+    let data_zip = fs::read("tests/assets/zip/shrunk.zip").unwrap();
+    let data_numbers = fs::read("tests/assets/uncompressed/numbers.txt").unwrap();
+    let data_short = fs::read("tests/assets/uncompressed/short_data.txt").unwrap();
+
+    assert_eq!(
+        read_chunks(&[&data_zip]),
+        Ok(vec![
+            entry("numbers.txt", &data_numbers),
+            entry("short_data.txt", &data_short),
+        ])
+    );
+
+    assert_random_splits(&data_zip, 1508, 10, 3000, read_chunks);
+}
+
+#[test]
+fn test_reduced() {
+    // An entry for each compression factor from 1 to 4. The data has
+    // a run of DLE bytes, which the second stage of Reduce has to escape.
+    let data_zip = fs::read("tests/assets/zip/reduced.zip").unwrap();
+    let data_a = fs::read("tests/assets/uncompressed/zipped_a.txt").unwrap();
+    let mut data = data_a[..3000].to_vec();
+    data.extend_from_slice(&[144; 5]);
+    data.extend_from_slice(&data_a[..600]);
+
+    assert_eq!(
+        read_chunks(&[&data_zip]),
+        Ok(vec![
+            entry("zipped_a_1.txt", &data),
+            entry("zipped_a_2.txt", &data),
+            entry("zipped_a_3.txt", &data),
+            entry("zipped_a_4.txt", &data),
+        ])
+    );
+
+    assert_random_splits(&data_zip, 1508, 10, 300, read_chunks);
+}
+
+#[test]
+fn test_imploded() {
+    // The entries cover both dictionary sizes, with and without a literal tree.
+    let data_zip = fs::read("tests/assets/zip/imploded.zip").unwrap();
+    let data_numbers = fs::read("tests/assets/uncompressed/numbers.txt").unwrap();
+    let data_repetitive = fs::read("tests/assets/uncompressed/repetitive_data.txt").unwrap();
+    let data_short = fs::read("tests/assets/uncompressed/short_data.txt").unwrap();
+
+    assert_eq!(
+        read_chunks(&[&data_zip]),
+        Ok(vec![
+            entry("numbers_8k_3.txt", &data_numbers),
+            entry("numbers_4k_2.txt", &data_numbers),
+            entry("repetitive_8k_2.txt", &data_repetitive),
+            entry("short_data_4k_3.txt", &data_short),
+        ])
+    );
+
+    assert_random_splits(&data_zip, 1508, 10, 3000, read_chunks);
+}
+
+#[test]
+fn test_invalid_imploded() {
+    let mut data_zip = fs::read("tests/assets/zip/imploded.zip").unwrap();
+    // Clear the flag bits of the first entry, so that it's read
+    // as having two trees instead of three.
+    data_zip[6] = 0;
+
+    match read_chunks(&[&data_zip]) {
+        Err(Error::Zip(e)) => assert_eq!(e.without_entry(), &ZipError::InvalidLegacyStream),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+}
@@ -0,0 +1,149 @@
+extern crate stream_zipper;
+
+use std::fs;
+
+use stream_zipper::zip::{self, EntryMatcher};
+use stream_zipper::State;
+
+fn matching<'a>(pattern: &EntryMatcher, names: &[&'a str]) -> Vec<&'a str> {
+    names
+        .iter()
+        .cloned()
+        .filter(|name| pattern.matches(name.as_bytes()))
+        .collect()
+}
+
+const NAMES: &[&str] = &[
+    "a.csv",
+    "b.txt",
+    "data/c.csv",
+    "data/2020/d.csv",
+    "data\\2021\\e.CSV",
+    "data/",
+    "other/f.csv",
+];
+
+#[test]
+fn test_star() {
+    let matcher = EntryMatcher::glob("*.csv");
+    assert_eq!(matching(&matcher, NAMES), vec!["a.csv"]);
+
+    let matcher = EntryMatcher::glob("data/*");
+    assert_eq!(matching(&matcher, NAMES), vec!["data/c.csv", "data/"]);
+}
+
+#[test]
+fn test_globstar() {
+    let matcher = EntryMatcher::glob("**/*.csv");
+    assert_eq!(
+        matching(&matcher, NAMES),
+        vec!["a.csv", "data/c.csv", "data/2020/d.csv", "other/f.csv"]
+    );
+
+    let matcher = EntryMatcher::glob("data/**");
+    assert_eq!(
+        matching(&matcher, NAMES),
+        vec![
+            "data/c.csv",
+            "data/2020/d.csv",
+            "data\\2021\\e.CSV",
+            "data/"
+        ]
+    );
+
+    let matcher = EntryMatcher::glob("data/**/*.csv");
+    assert_eq!(
+        matching(&matcher, NAMES),
+        vec!["data/c.csv", "data/2020/d.csv"]
+    );
+}
+
+#[test]
+fn test_separators_and_case() {
+    let matcher = EntryMatcher::glob("data/20??/*.csv").case_insensitive(true);
+    assert_eq!(
+        matching(&matcher, NAMES),
+        vec!["data/2020/d.csv", "data\\2021\\e.CSV"]
+    );
+
+    let matcher = EntryMatcher::glob("DATA\\**\\*.CSV").case_insensitive(true);
+    assert_eq!(
+        matching(&matcher, NAMES),
+        vec!["data/c.csv", "data/2020/d.csv", "data\\2021\\e.CSV"]
+    );
+}
+
+#[test]
+fn test_classes() {
+    let matcher = EntryMatcher::glob("[ab].*");
+    assert_eq!(matching(&matcher, NAMES), vec!["a.csv", "b.txt"]);
+
+    let matcher = EntryMatcher::glob("**/[!a-c].csv");
+    assert_eq!(
+        matching(&matcher, NAMES),
+        vec!["data/2020/d.csv", "other/f.csv"]
+    );
+
+    // Without a closing bracket, the bracket is literal.
+    let matcher = EntryMatcher::glob("[a.csv");
+    assert!(matcher.matches(b"[a.csv"));
+    assert!(!matcher.matches(b"a.csv"));
+}
+
+#[test]
+fn test_matcher_with_list_entries() {
+    let data_zip = fs::read("tests/assets/zip/kyushu.tsv.zip").unwrap();
+
+    let matcher = EntryMatcher::glob("__MACOSX/**");
+    let entries = zip::list_entries(&data_zip).unwrap();
+    let names: Vec<&[u8]> = entries
+        .iter()
+        .filter(|entry| matcher.matches_central_dir_header(entry))
+        .map(|entry| &entry.filename[..])
+        .collect();
+    assert_eq!(
+        names,
+        vec![&b"__MACOSX/"[..], &b"__MACOSX/._kyushu.tsv"[..]]
+    );
+}
+
+#[test]
+fn test_matcher_with_entry_filter() {
+    let data_zip = fs::read("tests/assets/zip/zipped_ab.zip").unwrap();
+    let data_a = fs::read("tests/assets/uncompressed/zipped_a.txt").unwrap();
+
+    let matcher = EntryMatcher::glob("*_A.TXT").case_insensitive(true);
+    let options =
+        zip::Options::default().entry_filter(move |header| matcher.matches_header(header));
+    let mut zip_file = zip::start_stream_with(options);
+    let mut input = &data_zip[..];
+    let mut outputs: Vec<(Vec<u8>, Vec<u8>)> = vec![(b"zipped_a.txt".to_vec(), Vec::new())];
+    loop {
+        match zip_file.read(input).unwrap() {
+            State::HasOutput {
+                unparsed_input,
+                output,
+            } => {
+                outputs.last_mut().unwrap().1.extend_from_slice(output);
+                input = unparsed_input;
+            }
+            State::NextFile {
+                unparsed_input,
+                next_file,
+            } => {
+                zip_file = next_file;
+                outputs.push((zip_file.filename().unwrap().to_vec(), Vec::new()));
+                input = unparsed_input;
+            }
+            State::EndOfFile => break,
+            state => panic!("Unexpected state: {:?}", state),
+        }
+    }
+    assert_eq!(
+        outputs,
+        vec![
+            (b"zipped_a.txt".to_vec(), data_a),
+            (b"zipped_b.txt".to_vec(), Vec::new()),
+        ]
+    );
+}
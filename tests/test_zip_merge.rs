@@ -0,0 +1,137 @@
+extern crate stream_zipper;
+
+use std::fs;
+use std::io::{Cursor, Write};
+
+use stream_zipper::test_util::{read_chunks, Entry};
+use stream_zipper::zip::{self, EntryOptions, SeekArchive, SeekError, ZipError};
+
+const SOURCES: &[&str] = &[
+    "tests/assets/zip/comments.zip",
+    "tests/assets/zip/zipped_ab.zip",
+    "tests/assets/zip/unsigned_descriptors.zip",
+    "tests/assets/zip/zip64_descriptor.zip",
+    "tests/assets/zip/unicode_names.zip",
+    "tests/assets/zip/stored_descriptors.zip",
+];
+
+/// Reads every entry of the archive with `SeekArchive`, which finds
+/// the entries by the offsets in the central directory.
+fn read_entries(archive: Vec<u8>) -> Vec<Entry> {
+    let mut archive = SeekArchive::new(Cursor::new(archive)).unwrap();
+    (0..archive.entries().len())
+        .map(|index| {
+            let mut data = Vec::new();
+            archive
+                .read_entry(index, |out| {
+                    data.extend_from_slice(out);
+                    Ok(())
+                })
+                .unwrap();
+            Entry {
+                name: Some(archive.entries()[index].name().to_vec()),
+                data,
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn test_merge() {
+    let sources: Vec<Vec<u8>> = SOURCES.iter().map(|path| fs::read(path).unwrap()).collect();
+    let merged = zip::merge(sources.iter().map(|source| &source[..]), Vec::new()).unwrap();
+
+    let expected: Vec<Entry> = sources
+        .iter()
+        .flat_map(|source| read_chunks(&[source]).unwrap())
+        .collect();
+    assert_eq!(read_chunks(&[&merged]).unwrap(), expected);
+    assert_eq!(read_entries(merged.clone()), expected);
+
+    // The central directory headers keep their attributes and comments.
+    let entries = zip::list_entries(&merged).unwrap();
+    let source_entries: Vec<_> = sources
+        .iter()
+        .flat_map(|source| zip::list_entries(source).unwrap())
+        .collect();
+    assert_eq!(entries.len(), source_entries.len());
+    for (entry, source_entry) in entries.iter().zip(&source_entries) {
+        assert_eq!(entry.filename, source_entry.filename);
+        assert_eq!(entry.comment, source_entry.comment);
+        assert_eq!(entry.ext_file_attrib, source_entry.ext_file_attrib);
+        assert_eq!(entry.version_made_by, source_entry.version_made_by);
+        assert_eq!(entry.sizes(), source_entry.sizes());
+        assert_eq!(entry.crc_32, source_entry.crc_32);
+    }
+    assert_eq!(entries[0].comment, b"comment for short_data.txt");
+}
+
+#[test]
+fn test_copy_archive_between_entries() {
+    let short = fs::read("tests/assets/uncompressed/short_data.txt").unwrap();
+    let mut streamed = zip::Writer::new(Vec::new());
+    streamed
+        .start_entry("streamed.txt", &EntryOptions::new())
+        .unwrap();
+    streamed.write_all(&short).unwrap();
+    let streamed = streamed.finish().unwrap();
+
+    let mut writer = zip::Writer::new(Vec::new());
+    writer
+        .add_entry("first.txt", &EntryOptions::new(), b"first")
+        .unwrap();
+    writer
+        .start_entry("second.txt", &EntryOptions::new())
+        .unwrap();
+    writer.write_all(b"second").unwrap();
+    assert_eq!(writer.copy_archive(&streamed[..]).unwrap(), 1);
+    writer
+        .add_entry("last.txt", &EntryOptions::new(), b"last")
+        .unwrap();
+    let archive = writer.finish().unwrap();
+
+    let expected = vec![
+        Entry {
+            name: Some(b"first.txt".to_vec()),
+            data: b"first".to_vec(),
+        },
+        Entry {
+            name: Some(b"second.txt".to_vec()),
+            data: b"second".to_vec(),
+        },
+        Entry {
+            name: Some(b"streamed.txt".to_vec()),
+            data: short,
+        },
+        Entry {
+            name: Some(b"last.txt".to_vec()),
+            data: b"last".to_vec(),
+        },
+    ];
+    assert_eq!(read_chunks(&[&archive]).unwrap(), expected);
+    assert_eq!(read_entries(archive), expected);
+}
+
+#[test]
+fn test_merge_empty() {
+    let empty = zip::Writer::new(Vec::new()).finish().unwrap();
+    let merged = zip::merge(vec![&empty[..], &empty[..]], Vec::new()).unwrap();
+    assert_eq!(merged, empty);
+}
+
+#[test]
+fn test_merge_unknown_entry_size() {
+    // An encrypted entry whose sizes are in a data descriptor can't be decrypted
+    // to find the end of its data without the password.
+    let source = fs::read("tests/assets/zip/zipcrypto.zip").unwrap();
+    match zip::merge(vec![&source[..]], Vec::new()) {
+        Err(SeekError::Zip(ZipError::UnknownEntrySize)) => (),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+
+    let source = fs::read("tests/assets/uncompressed/short_data.txt").unwrap();
+    match zip::merge(vec![&source[..]], Vec::new()) {
+        Err(SeekError::Zip(ZipError::NotLocalFileHeader(_))) => (),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+}
@@ -0,0 +1,101 @@
+extern crate stream_zipper;
+
+use std::fs;
+
+use stream_zipper::test_util::assert_every_split;
+use stream_zipper::zip::{self, ZipError};
+use stream_zipper::State;
+
+/// Reads the archive with a zip stream, returning the names of its entries.
+fn read_names(chunks: &[&[u8]]) -> Result<Vec<Vec<u8>>, ZipError> {
+    let mut zip_file = zip::start_stream();
+    let mut names = Vec::new();
+    for chunk in chunks {
+        let mut input = *chunk;
+        loop {
+            match zip_file.read(input)? {
+                State::HasOutput { unparsed_input, .. } => input = unparsed_input,
+                State::NeedsInput => break,
+                State::NextFile {
+                    unparsed_input,
+                    next_file,
+                } => {
+                    names.push(zip_file.filename().unwrap().to_vec());
+                    zip_file = next_file;
+                    input = unparsed_input;
+                }
+                State::EndOfFile => {
+                    names.push(zip_file.filename().unwrap().to_vec());
+                    return Ok(names);
+                }
+                state => panic!("Unexpected state: {:?}", state),
+            }
+        }
+    }
+    panic!("The stream should have ended");
+}
+
+#[test]
+fn test_unicode_path() {
+    // The first name is in CP437 with a Unicode Path extra field, the second
+    // has a field whose CRC-32 doesn't match the name, so it's ignored,
+    // and the third is in UTF-8 without the field.
+    let data_zip = fs::read("tests/assets/zip/unicode_names.zip").unwrap();
+
+    let expected = vec![
+        "Übung.txt".as_bytes().to_vec(),
+        b"\x8erger.txt".to_vec(),
+        "日本語.txt".as_bytes().to_vec(),
+    ];
+    assert_eq!(read_names(&[&data_zip]), Ok(expected.clone()));
+    assert_every_split(&data_zip, read_names);
+
+    let entries = zip::list_entries(&data_zip).unwrap();
+    let names: Vec<Vec<u8>> = entries.iter().map(|entry| entry.name().to_vec()).collect();
+    assert_eq!(names, expected);
+    assert_eq!(entries[0].filename, b"\x9abung.txt");
+}
+
+#[test]
+fn test_name_str() {
+    let data_zip = fs::read("tests/assets/zip/unicode_names.zip").unwrap();
+
+    // The second name is decoded from code page 437,
+    // and the third one from UTF-8 according to the flag.
+    let expected = vec!["Übung.txt", "Ärger.txt", "日本語.txt"];
+    let entries = zip::list_entries(&data_zip).unwrap();
+    let names: Vec<_> = entries.iter().map(|entry| entry.name_str()).collect();
+    assert_eq!(names, expected);
+    assert!(!entries[1].utf8_name);
+    assert!(entries[2].utf8_name);
+
+    let mut zip_file = zip::start_stream();
+    let mut input = &data_zip[..];
+    let mut names = Vec::new();
+    loop {
+        match zip_file.read(input).unwrap() {
+            State::HasOutput { unparsed_input, .. } => input = unparsed_input,
+            State::NextFile {
+                unparsed_input,
+                next_file,
+            } => {
+                names.push(zip_file.name_str().unwrap().into_owned());
+                zip_file = next_file;
+                input = unparsed_input;
+            }
+            State::EndOfFile => break,
+            state => panic!("Unexpected state: {:?}", state),
+        }
+    }
+    names.push(zip_file.name_str().unwrap().into_owned());
+    assert_eq!(names, expected);
+}
+
+#[test]
+fn test_cp437() {
+    assert_eq!(zip::cp437::decode(b"plain.txt"), "plain.txt");
+    assert_eq!(
+        zip::cp437::decode(b"\x80\x9a\xe1\xb0\xff"),
+        "\u{c7}\u{dc}\u{df}\u{2591}\u{a0}"
+    );
+}
@@ -0,0 +1,73 @@
+extern crate stream_zipper;
+
+use std::fs;
+
+use stream_zipper::zip;
+use stream_zipper::zip::ZipError;
+
+/// Inserts a crafted Zip64 end of central directory record
+/// right before the end of central directory record.
+fn with_zip64_central_dir_end(record_size: u64, fixed_and_extensible: &[u8]) -> Vec<u8> {
+    let data_zip = fs::read("tests/assets/zip/short_data.txt.zip").unwrap();
+    let eocd_pos = data_zip
+        .windows(4)
+        .rposition(|w| w == b"PK\x05\x06")
+        .unwrap();
+
+    let mut crafted = data_zip[..eocd_pos].to_vec();
+    crafted.extend_from_slice(b"PK\x06\x06");
+    crafted.extend_from_slice(&record_size.to_le_bytes());
+    crafted.extend_from_slice(fixed_and_extensible);
+    crafted.extend_from_slice(&data_zip[eocd_pos..]);
+    crafted
+}
+
+fn zip64_fixed_fields() -> Vec<u8> {
+    let mut fields = Vec::new();
+    fields.extend_from_slice(&0x031e_u16.to_le_bytes()); // Version made by: Unix, 3.0
+    fields.extend_from_slice(&45_u16.to_le_bytes()); // Version needed
+    fields.extend_from_slice(&0_u32.to_le_bytes()); // This disk
+    fields.extend_from_slice(&0_u32.to_le_bytes()); // Central dir start disk
+    fields.extend_from_slice(&2_u64.to_le_bytes()); // Entries on this disk
+    fields.extend_from_slice(&2_u64.to_le_bytes()); // Entries in total
+    fields.extend_from_slice(&0_u64.to_le_bytes()); // Central dir size
+    fields.extend_from_slice(&0_u64.to_le_bytes()); // Central dir offset
+    assert_eq!(fields.len(), 44);
+    fields
+}
+
+#[test]
+fn test_zip64_central_dir_end_valid_size() {
+    let crafted = with_zip64_central_dir_end(44, &zip64_fixed_fields());
+
+    zip::start_stream()
+        .read_with(&crafted, |_| ())
+        .expect("Should succeed");
+}
+
+#[test]
+fn test_zip64_central_dir_end_huge_size() {
+    // A record size this large would make the parser wait for input forever
+    // and overflow when converted to usize on 32-bit targets.
+    let crafted = with_zip64_central_dir_end(u64::MAX, &zip64_fixed_fields());
+
+    let res = zip::start_stream().read_with(&crafted, |_| ());
+    assert_eq!(res.unwrap_err(), ZipError::InvalidCentralDirEnd);
+}
+
+#[test]
+fn test_zip64_central_dir_end_too_small_size() {
+    // The record size can't be smaller than the fixed-size fields it contains.
+    let crafted = with_zip64_central_dir_end(10, &zip64_fixed_fields());
+
+    let res = zip::start_stream().read_with(&crafted, |_| ());
+    assert_eq!(res.unwrap_err(), ZipError::InvalidCentralDirEnd);
+}
+
+#[test]
+fn test_zip64_central_dir_end_oversized_extensible_data() {
+    let crafted = with_zip64_central_dir_end(44 + 64 * 1024 + 1, &zip64_fixed_fields());
+
+    let res = zip::start_stream().read_with(&crafted, |_| ());
+    assert_eq!(res.unwrap_err(), ZipError::InvalidCentralDirEnd);
+}
@@ -0,0 +1,43 @@
+extern crate stream_zipper;
+
+use std::fs;
+
+use stream_zipper::test_util::{assert_random_splits, read_chunks, Entry};
+use stream_zipper::zip::ZipError;
+use stream_zipper::Error;
+
+#[test]
+fn test_ppmd() {
+    let data_zip = fs::read("tests/assets/zip/ppmd.zip").unwrap();
+    let data_numbers = fs::read("tests/assets/uncompressed/numbers.txt").unwrap();
+    let data_short = fs::read("tests/assets/uncompressed/short_data.txt").unwrap();
+
+    assert_eq!(
+        read_chunks(&[&data_zip]),
+        Ok(vec![
+            Entry {
+                name: Some(b"numbers.txt".to_vec()),
+                data: data_numbers,
+            },
+            Entry {
+                name: Some(b"short_data.txt".to_vec()),
+                data: data_short,
+            },
+        ])
+    );
+
+    assert_random_splits(&data_zip, 1507, 10, 3000, read_chunks);
+}
+
+#[test]
+fn test_invalid_ppmd_parameters() {
+    let mut data_zip = fs::read("tests/assets/zip/ppmd.zip").unwrap();
+    // The parameter word follows the 30 byte header and the 11 byte filename.
+    // Patch its restore method to an undefined one.
+    data_zip[42] |= 0xf0;
+
+    match read_chunks(&[&data_zip]) {
+        Err(Error::Zip(e)) => assert_eq!(e.without_entry(), &ZipError::InvalidPpmdStream),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+}
@@ -0,0 +1,194 @@
+extern crate stream_zipper;
+
+use std::fs;
+use std::io::Write;
+
+use stream_zipper::test_util::{read_chunks, read_chunks_with};
+use stream_zipper::zip::headers::{CompressionMethod, LocalFileHeader};
+use stream_zipper::zip::{self, EntryOptions, Recompression, SeekError, ZipError};
+use stream_zipper::Options;
+
+fn source_archive() -> Vec<u8> {
+    let short = fs::read("tests/assets/uncompressed/short_data.txt").unwrap();
+    let repetitive = fs::read("tests/assets/uncompressed/repetitive_data.txt").unwrap();
+    let stored = EntryOptions::new().compression_level(None);
+
+    let mut writer = zip::Writer::new(Vec::new());
+    writer
+        .add_entry("stored.txt", &stored, &repetitive)
+        .unwrap();
+    writer
+        .add_entry(
+            "fast.txt",
+            &EntryOptions::new().compression_level(Some(1)),
+            &repetitive,
+        )
+        .unwrap();
+    writer
+        .add_entry("dir/", &stored.clone().unix_mode(0o40700), b"")
+        .unwrap();
+    // Stored without sizes, so its end is found by scanning for its data descriptor.
+    writer.start_entry("dir/streamed.txt", &stored).unwrap();
+    writer.write_all(&short).unwrap();
+    writer.finish().unwrap()
+}
+
+#[test]
+fn test_recompress() {
+    let source = source_archive();
+    let mut methods = Vec::new();
+    let archive = zip::recompress(
+        &source[..],
+        Vec::new(),
+        &zip::Options::default(),
+        |header: &LocalFileHeader| {
+            methods.push(header.compression_method);
+            match header.compression_method {
+                CompressionMethod::Stored => Recompression::Deflate(9),
+                _ => Recompression::Keep,
+            }
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        methods,
+        [
+            CompressionMethod::Stored,
+            CompressionMethod::Deflated,
+            CompressionMethod::Stored,
+            CompressionMethod::Stored,
+        ]
+    );
+
+    assert_eq!(
+        read_chunks(&[&archive]).unwrap(),
+        read_chunks(&[&source]).unwrap()
+    );
+    let entries = zip::list_entries(&archive).unwrap();
+    let source_entries = zip::list_entries(&source).unwrap();
+    for (entry, source_entry) in entries.iter().zip(&source_entries) {
+        assert_eq!(entry.compression_method, CompressionMethod::Deflated);
+        assert_eq!(entry.filename, source_entry.filename);
+        assert_eq!(entry.crc_32, source_entry.crc_32);
+        assert_eq!(entry.ext_file_attrib, source_entry.ext_file_attrib);
+        assert_eq!(
+            (entry.last_mod_time, entry.last_mod_date),
+            (source_entry.last_mod_time, source_entry.last_mod_date)
+        );
+    }
+    // The kept entry is untouched, and the stored one is compressed.
+    assert_eq!(entries[1].sizes(), source_entries[1].sizes());
+    assert!(entries[0].sizes().unwrap().0 < source_entries[0].sizes().unwrap().0 / 10);
+}
+
+#[test]
+fn test_recompress_to_stored() {
+    // The comments and the attributes of the source are kept.
+    let source = fs::read("tests/assets/zip/comments.zip").unwrap();
+    let archive = zip::recompress(&source[..], Vec::new(), &zip::Options::default(), |_| {
+        Recompression::Store
+    })
+    .unwrap();
+
+    assert_eq!(
+        read_chunks(&[&archive]).unwrap(),
+        read_chunks(&[&source]).unwrap()
+    );
+    let entries = zip::list_entries(&archive).unwrap();
+    let source_entries = zip::list_entries(&source).unwrap();
+    for (entry, source_entry) in entries.iter().zip(&source_entries) {
+        assert_eq!(entry.compression_method, CompressionMethod::Stored);
+        assert_eq!(entry.comment, source_entry.comment);
+        assert_eq!(entry.version_made_by, source_entry.version_made_by);
+        assert_eq!(entry.extra_fields, source_entry.extra_fields);
+        assert_eq!(entry.sizes().unwrap().1, source_entry.sizes().unwrap().1);
+    }
+}
+
+#[test]
+fn test_recompress_other_methods() {
+    let source = fs::read("tests/assets/zip/bzip2.zip").unwrap();
+    let archive = zip::recompress(&source[..], Vec::new(), &zip::Options::default(), |_| {
+        Recompression::Deflate(6)
+    })
+    .unwrap();
+    assert_eq!(
+        read_chunks(&[&archive]).unwrap(),
+        read_chunks(&[&source]).unwrap()
+    );
+    for entry in zip::list_entries(&archive).unwrap() {
+        assert_eq!(entry.compression_method, CompressionMethod::Deflated);
+    }
+}
+
+#[test]
+fn test_recompress_encrypted() {
+    let password = zip::Options::default().password("secret");
+    for path in &[
+        "tests/assets/zip/zipcrypto.zip",
+        "tests/assets/zip/winzip_aes.zip",
+    ] {
+        let source = fs::read(path).unwrap();
+        let expected = read_chunks_with(Options::default().zip(password.clone()), &[&source]);
+
+        // Re-encoded entries are decrypted.
+        let archive = zip::recompress(&source[..], Vec::new(), &password, |_| {
+            Recompression::Deflate(6)
+        })
+        .unwrap();
+        assert_eq!(read_chunks(&[&archive]), expected);
+        for entry in zip::list_entries(&archive).unwrap() {
+            assert!(!entry.encrypted);
+            assert_eq!(entry.compression_method, CompressionMethod::Deflated);
+        }
+
+        // Kept entries stay encrypted.
+        let archive =
+            zip::recompress(&source[..], Vec::new(), &password, |_| Recompression::Keep).unwrap();
+        assert_eq!(
+            read_chunks_with(Options::default().zip(password.clone()), &[&archive]),
+            expected
+        );
+
+        match zip::recompress(&source[..], Vec::new(), &zip::Options::default(), |_| {
+            Recompression::Store
+        }) {
+            Err(SeekError::Zip(ZipError::EncryptedEntry)) => (),
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
+}
+
+#[test]
+fn test_recompress_checksum_mismatch() {
+    let mut source = zip::Writer::new(Vec::new());
+    source
+        .add_entry(
+            "stored.txt",
+            &EntryOptions::new().compression_level(None),
+            b"some stored data",
+        )
+        .unwrap();
+    let mut source = source.finish().unwrap();
+    // Corrupt the data, which follows the 40 bytes of the local file header.
+    source[40] ^= 1;
+
+    match zip::recompress(&source[..], Vec::new(), &zip::Options::default(), |_| {
+        Recompression::Deflate(6)
+    }) {
+        Err(SeekError::Zip(ZipError::ChecksumMismatch { .. })) => (),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+    // The data isn't decompressed if it's kept.
+    zip::recompress(&source[..], Vec::new(), &zip::Options::default(), |_| {
+        Recompression::Keep
+    })
+    .unwrap();
+    zip::recompress(
+        &source[..],
+        Vec::new(),
+        &zip::Options::default().verify_checksums(false),
+        |_| Recompression::Store,
+    )
+    .unwrap();
+}
@@ -0,0 +1,165 @@
+extern crate stream_zipper;
+
+use std::fs;
+
+use stream_zipper::test_util::{assert_every_split, Entry};
+use stream_zipper::zip::{self, ZipError};
+use stream_zipper::State;
+
+/// Reads the entries of the archive with a zip stream that recovers from damaged entries,
+/// returning their names, uncompressed data and damage.
+fn read_recovering(chunks: &[&[u8]]) -> Result<Vec<(Entry, Option<ZipError>)>, ZipError> {
+    let options = zip::Options::default().recover_damaged_entries(true);
+    let mut zip_file = zip::start_stream_with(options);
+    let mut entries = Vec::new();
+    let mut data = Vec::new();
+    for chunk in chunks {
+        let mut input = *chunk;
+        loop {
+            let state = zip_file.read(input)?;
+            let ended = match state {
+                State::HasOutput {
+                    unparsed_input,
+                    output,
+                } => {
+                    data.extend_from_slice(output);
+                    input = unparsed_input;
+                    continue;
+                }
+                State::NeedsInput => break,
+                State::NextFile {
+                    unparsed_input,
+                    next_file,
+                } => {
+                    input = unparsed_input;
+                    Some(next_file)
+                }
+                State::EndOfFile => None,
+                state => panic!("Unexpected state: {:?}", state),
+            };
+            let entry = Entry {
+                name: zip_file.filename().map(<[u8]>::to_vec),
+                data: std::mem::take(&mut data),
+            };
+            entries.push((entry, zip_file.damage()));
+            match ended {
+                Some(next_file) => zip_file = next_file,
+                None => return Ok(entries),
+            }
+        }
+    }
+    panic!("The stream should have ended");
+}
+
+fn entry(name: &str, data: Vec<u8>) -> Entry {
+    Entry {
+        name: Some(name.as_bytes().to_vec()),
+        data,
+    }
+}
+
+/// Makes the Deflate stream at `offset` start with a block of the reserved type.
+fn corrupt_deflate_stream(data_zip: &mut [u8], offset: usize) {
+    data_zip[offset] = 0b111;
+}
+
+#[test]
+fn test_damaged_entry_with_sizes() {
+    let mut data_zip = fs::read("tests/assets/zip/comments.zip").unwrap();
+    let data_repetitive = fs::read("tests/assets/uncompressed/repetitive_data.txt").unwrap();
+    corrupt_deflate_stream(&mut data_zip, 30 + b"short_data.txt".len());
+
+    let expected = vec![
+        (
+            entry("short_data.txt", Vec::new()),
+            Some(ZipError::InvalidDeflateStream),
+        ),
+        (entry("repetitive_data.txt", data_repetitive), None),
+    ];
+    assert_eq!(read_recovering(&[&data_zip]), Ok(expected));
+    assert_every_split(&data_zip, read_recovering);
+
+    // Without recovery, the error fails the stream.
+    let mut zip_file = zip::start_stream();
+    assert_eq!(
+        zip_file
+            .read(&data_zip)
+            .map(|_| ())
+            .map_err(|e| e.without_entry().clone()),
+        Err(ZipError::InvalidDeflateStream)
+    );
+}
+
+#[test]
+fn test_damaged_entry_with_data_descriptor() {
+    let mut data_zip = fs::read("tests/assets/zip/zipped_ab.zip").unwrap();
+    let data_b = fs::read("tests/assets/uncompressed/zipped_b.txt").unwrap();
+    corrupt_deflate_stream(&mut data_zip, 30 + b"zipped_a.txt".len() + 16);
+
+    let expected = vec![
+        (
+            entry("zipped_a.txt", Vec::new()),
+            Some(ZipError::InvalidDeflateStream),
+        ),
+        (entry("zipped_b.txt", data_b), None),
+    ];
+    assert_eq!(read_recovering(&[&data_zip]), Ok(expected));
+    assert_every_split(&data_zip, read_recovering);
+}
+
+#[test]
+fn test_damaged_last_entry() {
+    let mut data_zip = fs::read("tests/assets/zip/comments.zip").unwrap();
+    let data_short = fs::read("tests/assets/uncompressed/short_data.txt").unwrap();
+    corrupt_deflate_stream(&mut data_zip, 56 + 30 + b"repetitive_data.txt".len());
+
+    // The rest of the entry is skipped up to the central directory.
+    let expected = vec![
+        (entry("short_data.txt", data_short), None),
+        (
+            entry("repetitive_data.txt", Vec::new()),
+            Some(ZipError::InvalidDeflateStream),
+        ),
+    ];
+    assert_eq!(read_recovering(&[&data_zip]), Ok(expected));
+    assert_every_split(&data_zip, read_recovering);
+}
+
+#[test]
+fn test_checksum_mismatch() {
+    let mut data_zip = fs::read("tests/assets/zip/comments.zip").unwrap();
+    let data_short = fs::read("tests/assets/uncompressed/short_data.txt").unwrap();
+    let data_repetitive = fs::read("tests/assets/uncompressed/repetitive_data.txt").unwrap();
+    // The CRC-32 of the first entry.
+    data_zip[14] ^= 1;
+
+    let entries = read_recovering(&[&data_zip]).unwrap();
+    // The data was already produced when the mismatch was found.
+    assert_eq!(entries[0].0, entry("short_data.txt", data_short));
+    assert!(matches!(
+        entries[0].1,
+        Some(ZipError::ChecksumMismatch { .. })
+    ));
+    assert_eq!(
+        entries[1],
+        (entry("repetitive_data.txt", data_repetitive), None)
+    );
+}
+
+#[test]
+fn test_unsupported_compression_method() {
+    let mut data_zip = fs::read("tests/assets/zip/comments.zip").unwrap();
+    let data_repetitive = fs::read("tests/assets/uncompressed/repetitive_data.txt").unwrap();
+    // An unassigned compression method for the first entry.
+    data_zip[8] = 0xfe;
+
+    let entries = read_recovering(&[&data_zip]).unwrap();
+    assert!(matches!(
+        entries[0].1,
+        Some(ZipError::UnsupportedCompressionMethod(_))
+    ));
+    assert_eq!(
+        entries[1],
+        (entry("repetitive_data.txt", data_repetitive), None)
+    );
+}
@@ -0,0 +1,299 @@
+extern crate stream_zipper;
+
+use std::fs;
+use std::io::Cursor;
+use std::time::{Duration, UNIX_EPOCH};
+
+use stream_zipper::deflate;
+use stream_zipper::gzip::headers::{MemberHeader, Os};
+use stream_zipper::test_util::read_chunks;
+use stream_zipper::zip::{self, SeekArchive, SeekError, ZipError};
+
+/// Reads the entry at `index`, returning its uncompressed data.
+fn read_entry(archive: &mut SeekArchive<Cursor<Vec<u8>>>, index: usize) -> Vec<u8> {
+    let mut data = Vec::new();
+    let len = archive
+        .read_entry(index, |output| {
+            data.extend_from_slice(output);
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(len, data.len() as u64);
+    data
+}
+
+fn names(archive: &SeekArchive<Cursor<Vec<u8>>>) -> Vec<&[u8]> {
+    archive
+        .entries()
+        .iter()
+        .map(|entry| &entry.filename[..])
+        .collect()
+}
+
+#[test]
+fn test_seek_archive() {
+    let data_zip = fs::read("tests/assets/zip/zipped_ab.zip").unwrap();
+    let data_a = fs::read("tests/assets/uncompressed/zipped_a.txt").unwrap();
+    let data_b = fs::read("tests/assets/uncompressed/zipped_b.txt").unwrap();
+
+    let mut archive = SeekArchive::new(Cursor::new(data_zip)).unwrap();
+    assert_eq!(
+        names(&archive),
+        vec![&b"zipped_a.txt"[..], &b"zipped_b.txt"[..]]
+    );
+    for entry in archive.entries() {
+        assert_eq!(entry.sizes().map(|(_, size)| size), Some(9505));
+    }
+    // The entries can be read in any order, and more than once.
+    assert_eq!(read_entry(&mut archive, 1), data_b);
+    assert_eq!(read_entry(&mut archive, 0), data_a);
+    assert_eq!(read_entry(&mut archive, 1), data_b);
+}
+
+#[test]
+fn test_seek_archive_directory_entries() {
+    let data_zip = fs::read("tests/assets/zip/kyushu.tsv.zip").unwrap();
+
+    let mut archive = SeekArchive::new(Cursor::new(data_zip)).unwrap();
+    assert_eq!(
+        names(&archive),
+        vec![
+            &b"kyushu.tsv"[..],
+            &b"__MACOSX/"[..],
+            &b"__MACOSX/._kyushu.tsv"[..]
+        ]
+    );
+    assert_eq!(read_entry(&mut archive, 2).len(), 217);
+    assert_eq!(read_entry(&mut archive, 1), b"");
+    assert_eq!(read_entry(&mut archive, 0).len(), 2_760_454);
+}
+
+#[test]
+fn test_seek_archive_deferred_sizes() {
+    // The local headers don't have the sizes, but the central directory does.
+    let data_zip = fs::read("tests/assets/zip/zip64_descriptor.zip").unwrap();
+    let data_short = fs::read("tests/assets/uncompressed/short_data.txt").unwrap();
+    let data_repetitive = fs::read("tests/assets/uncompressed/repetitive_data.txt").unwrap();
+
+    let mut archive = SeekArchive::new(Cursor::new(data_zip)).unwrap();
+    assert_eq!(
+        names(&archive),
+        vec![&b"short_data.txt"[..], &b"repetitive_data.txt"[..]]
+    );
+    assert_eq!(read_entry(&mut archive, 1), data_repetitive);
+    assert_eq!(read_entry(&mut archive, 0), data_short);
+}
+
+#[test]
+fn test_seek_archive_with_prefix_and_comment() {
+    // A stub before the archive that the offsets don't account for,
+    // and an archive comment that contains the end of central directory signature.
+    let mut data_zip = b"MZ".to_vec();
+    data_zip.extend_from_slice(&[0x90; 300]);
+    data_zip.extend(fs::read("tests/assets/zip/short_data.txt.zip").unwrap());
+    let comment = b"a comment with PK\x05\x06 in it";
+    let len = data_zip.len();
+    data_zip[len - 2..].copy_from_slice(&(comment.len() as u16).to_le_bytes());
+    data_zip.extend_from_slice(comment);
+    let data_short = fs::read("tests/assets/uncompressed/short_data.txt").unwrap();
+
+    let mut archive = SeekArchive::new(Cursor::new(data_zip)).unwrap();
+    assert_eq!(archive.central_dir_end().comment, &comment[..]);
+    assert_eq!(names(&archive), vec![&b"short_data.txt"[..]]);
+    assert_eq!(read_entry(&mut archive, 0), data_short);
+}
+
+#[test]
+fn test_seek_archive_with_password() {
+    let data_zip = fs::read("tests/assets/zip/zipcrypto.zip").unwrap();
+    let data_short = fs::read("tests/assets/uncompressed/short_data.txt").unwrap();
+
+    let options = zip::Options::default().password("secret");
+    let mut archive = SeekArchive::with_options(Cursor::new(data_zip), options).unwrap();
+    assert_eq!(read_entry(&mut archive, 0), data_short);
+}
+
+#[test]
+fn test_seek_archive_not_zip() {
+    let data = fs::read("tests/assets/uncompressed/short_data.txt").unwrap();
+
+    assert!(matches!(
+        SeekArchive::new(Cursor::new(data)),
+        Err(SeekError::Zip(ZipError::NotCentralDirEnd(_)))
+    ));
+}
+
+#[test]
+fn test_list_entries() {
+    let data_zip = fs::read("tests/assets/zip/kyushu.tsv.zip").unwrap();
+
+    let entries = zip::list_entries(&data_zip).unwrap();
+    let listing: Vec<_> = entries
+        .iter()
+        .map(|entry| (&entry.filename[..], entry.sizes()))
+        .collect();
+    assert_eq!(
+        listing,
+        vec![
+            (&b"kyushu.tsv"[..], Some((405_206, 2_760_454))),
+            (&b"__MACOSX/"[..], Some((0, 0))),
+            (&b"__MACOSX/._kyushu.tsv"[..], Some((137, 217))),
+        ]
+    );
+
+    let data = fs::read("tests/assets/uncompressed/short_data.txt").unwrap();
+    assert!(matches!(
+        zip::list_entries(&data),
+        Err(ZipError::NotCentralDirEnd(_))
+    ));
+}
+
+#[test]
+fn test_list_entries_invalid_central_dir_end() {
+    // The central directory can't start before the start of the data.
+    let mut data_zip = fs::read("tests/assets/zip/zipped_ab.zip").unwrap();
+    let len = data_zip.len();
+    data_zip[len - 6..len - 2].copy_from_slice(&0xffff_fff0_u32.to_le_bytes());
+
+    assert_eq!(
+        zip::list_entries(&data_zip).map(|entries| entries.len()),
+        Err(ZipError::InvalidCentralDirEnd)
+    );
+}
+
+#[test]
+fn test_read_raw_entry() {
+    let data_zip = fs::read("tests/assets/zip/comments.zip").unwrap();
+    let mut archive = SeekArchive::new(Cursor::new(data_zip.clone())).unwrap();
+    let (compressed_size, _) = archive.entries()[1].sizes().unwrap();
+
+    let mut raw = Vec::new();
+    let len = archive
+        .read_raw_entry(1, |data| {
+            raw.extend_from_slice(data);
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(len, compressed_size);
+    assert_eq!(raw.len() as u64, compressed_size);
+    // The raw data of a Deflate entry is a Deflate stream.
+    let mut inflated = Vec::new();
+    deflate::Stream::new()
+        .inner_iter(&raw, |out| inflated.extend_from_slice(out))
+        .unwrap();
+    assert_eq!(
+        inflated,
+        fs::read("tests/assets/uncompressed/repetitive_data.txt").unwrap()
+    );
+}
+
+#[test]
+fn test_transcode_to_gzip() {
+    let short = fs::read("tests/assets/uncompressed/short_data.txt").unwrap();
+    let mtime = UNIX_EPOCH + Duration::from_secs(1_602_723_662);
+    let mut writer = zip::Writer::new(Vec::new());
+    let options = zip::EntryOptions::new().last_modified(mtime);
+    writer
+        .add_entry("dir/deflated.txt", &options, &short)
+        .unwrap();
+    writer
+        .add_entry(
+            "stored.txt",
+            &options.clone().compression_level(None),
+            &short,
+        )
+        .unwrap();
+    writer.add_entry("empty.txt", &options, b"").unwrap();
+    writer
+        .add_entry("dir/", &options.clone().compression_level(None), b"")
+        .unwrap();
+    let mut archive = SeekArchive::new(Cursor::new(writer.finish().unwrap())).unwrap();
+
+    let mut transcode = |index| {
+        let mut member = Vec::new();
+        archive
+            .transcode_to_gzip(index, |data| {
+                member.extend_from_slice(data);
+                Ok(())
+            })
+            .unwrap();
+        let (_, header) = MemberHeader::parse(&member).unwrap();
+        let entries = read_chunks(&[&member]).unwrap();
+        assert_eq!(entries.len(), 1);
+        (header, entries[0].data.clone())
+    };
+
+    let (header, data) = transcode(0);
+    assert_eq!(header.filename, Some(b"deflated.txt".to_vec()));
+    assert_eq!(header.last_modified(), Some(mtime));
+    assert_eq!(header.operating_system(), Os::Unix);
+    assert_eq!(data, short);
+
+    let (header, data) = transcode(1);
+    assert_eq!(header.filename, Some(b"stored.txt".to_vec()));
+    assert_eq!(data, short);
+
+    assert_eq!(transcode(2).1, b"");
+    let (header, data) = transcode(3);
+    assert_eq!(header.filename, None);
+    assert_eq!(data, b"");
+}
+
+#[test]
+fn test_transcode_to_gzip_unsupported() {
+    let data_zip = fs::read("tests/assets/zip/bzip2.zip").unwrap();
+    let mut archive = SeekArchive::new(Cursor::new(data_zip)).unwrap();
+    match archive.transcode_to_gzip(0, |_| Ok(())) {
+        Err(SeekError::Zip(ZipError::UnsupportedCompressionMethod(_))) => (),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+
+    let data_zip = fs::read("tests/assets/zip/zipcrypto.zip").unwrap();
+    let mut archive = SeekArchive::new(Cursor::new(data_zip)).unwrap();
+    match archive.transcode_to_gzip(0, |_| Ok(())) {
+        Err(SeekError::Zip(ZipError::EncryptedEntry)) => (),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+}
+
+#[test]
+fn test_seek_archive_no_such_entry() {
+    let data_zip = fs::read("tests/assets/zip/zipped_ab.zip").unwrap();
+    let mut archive = SeekArchive::new(Cursor::new(data_zip)).unwrap();
+    match archive.read_entry(2, |_| Ok(())) {
+        Err(SeekError::NoSuchEntry(2)) => (),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+    match archive.read_raw_entry(2, |_| Ok(())) {
+        Err(SeekError::NoSuchEntry(2)) => (),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+    match archive.transcode_to_gzip(usize::MAX, |_| Ok(())) {
+        Err(SeekError::NoSuchEntry(usize::MAX)) => (),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+    match archive.read_symlink_target(2) {
+        Err(SeekError::NoSuchEntry(2)) => (),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+}
+
+#[test]
+fn test_seek_archive_empty_data_with_size() {
+    let mut writer = zip::Writer::new(Vec::new());
+    let options = zip::EntryOptions::new().compression_level(None);
+    writer.add_entry("empty.txt", &options, b"").unwrap();
+    let mut data_zip = writer.finish().unwrap();
+    // The central directory claims a size for the entry that has no data.
+    let central_dir_header = data_zip
+        .windows(4)
+        .position(|window| window == b"PK\x01\x02")
+        .unwrap();
+    data_zip[central_dir_header + 24] = 1;
+
+    let mut archive = SeekArchive::new(Cursor::new(data_zip)).unwrap();
+    match archive.read_entry(0, |_| Ok(())) {
+        Err(SeekError::Zip(ZipError::InvalidCentralDirHeader)) => (),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+}
@@ -0,0 +1,132 @@
+extern crate stream_zipper;
+
+use std::fs;
+
+use stream_zipper::test_util::{assert_every_split, read_chunks, Entry};
+use stream_zipper::zip::{self, ZipError};
+use stream_zipper::State;
+
+/// A stub that looks like a Windows executable and, like real extractor stubs,
+/// contains the local file header signature without a valid header after it.
+fn stub() -> Vec<u8> {
+    let mut stub = b"MZ".to_vec();
+    stub.extend_from_slice(&[0x90; 300]);
+    stub.extend_from_slice(b"PK\x03\x04");
+    stub.extend_from_slice(&[0xff; 26]);
+    stub.extend_from_slice(&[0; 200]);
+    stub
+}
+
+fn sfx_archive() -> Vec<u8> {
+    let mut archive = stub();
+    archive.extend(fs::read("tests/assets/zip/short_data.txt.zip").unwrap());
+    archive
+}
+
+/// Reads the archive with a zip stream started with `options`,
+/// returning the uncompressed data of the single entry.
+fn read_zip(chunks: &[&[u8]], options: zip::Options) -> Result<Vec<u8>, ZipError> {
+    let mut zip_file = zip::start_stream_with(options);
+    let mut output = Vec::new();
+    for chunk in chunks {
+        let mut input = *chunk;
+        loop {
+            match zip_file.read(input)? {
+                State::HasOutput {
+                    unparsed_input,
+                    output: out,
+                } => {
+                    output.extend_from_slice(out);
+                    input = unparsed_input;
+                }
+                State::NeedsInput => break,
+                State::EndOfFile => return Ok(output),
+                state => panic!("Unexpected state: {:?}", state),
+            }
+        }
+    }
+    panic!("The stream should have ended");
+}
+
+#[test]
+fn test_self_extracting() {
+    let archive = sfx_archive();
+    let data = fs::read("tests/assets/uncompressed/short_data.txt").unwrap();
+
+    let options = zip::Options::default().scan_for_start(1024);
+    assert_eq!(read_zip(&[&archive], options.clone()).unwrap(), data);
+    assert_every_split(&archive, |chunks| read_zip(chunks, options.clone()));
+}
+
+#[test]
+fn test_self_extracting_without_stub() {
+    let archive = fs::read("tests/assets/zip/short_data.txt.zip").unwrap();
+    let data = fs::read("tests/assets/uncompressed/short_data.txt").unwrap();
+
+    let options = zip::Options::default().scan_for_start(1024);
+    assert_eq!(read_zip(&[&archive], options).unwrap(), data);
+}
+
+#[test]
+fn test_stub_longer_than_limit() {
+    let archive = sfx_archive();
+    let stub_len = stub().len() as u64;
+
+    let options = zip::Options::default().scan_for_start(stub_len - 1);
+    assert!(matches!(
+        read_zip(&[&archive], options),
+        Err(ZipError::NotLocalFileHeader(_))
+    ));
+    let options = zip::Options::default().scan_for_start(stub_len);
+    assert!(read_zip(&[&archive], options).is_ok());
+}
+
+#[test]
+fn test_self_extracting_autodetect() {
+    let archive = sfx_archive();
+    let data = fs::read("tests/assets/uncompressed/short_data.txt").unwrap();
+
+    assert_eq!(
+        read_chunks(&[&archive]).unwrap(),
+        vec![Entry {
+            name: Some(b"short_data.txt".to_vec()),
+            data,
+        }]
+    );
+    assert_every_split(&archive, read_chunks);
+}
+
+#[test]
+fn test_executable_without_archive() {
+    let mut executable = b"\x7fELF".to_vec();
+    executable.extend_from_slice(&[0; 1000]);
+
+    // Autodetection allows for long stubs, so the archive may still follow.
+    let mut file = stream_zipper::start_stream();
+    assert!(matches!(file.read(&executable), Ok(State::NeedsInput)));
+
+    let mut zip_file = zip::start_stream_with(zip::Options::default().scan_for_start(100));
+    assert!(matches!(
+        zip_file.read(&executable),
+        Err(ZipError::NotLocalFileHeader(_))
+    ));
+}
+
+#[test]
+fn test_scan_for_start_after_multipart_headers() {
+    let mut archive = b"--boundary\r\n\
+        Content-Disposition: form-data; name=\"file\"; filename=\"short_data.txt.zip\"\r\n\
+        Content-Type: application/zip\r\n\r\n"
+        .to_vec();
+    let junk_len = archive.len() as u64;
+    archive.extend(fs::read("tests/assets/zip/short_data.txt.zip").unwrap());
+    archive.extend_from_slice(b"\r\n--boundary--\r\n");
+    let data = fs::read("tests/assets/uncompressed/short_data.txt").unwrap();
+
+    // Without the option, the archive must start with a local file header.
+    assert!(read_zip(&[&archive], zip::Options::default()).is_err());
+
+    let options = zip::Options::default().scan_for_start(junk_len);
+    assert_eq!(read_zip(&[&archive], options.clone()).unwrap(), data);
+    assert_every_split(&archive, |chunks| read_zip(chunks, options.clone()));
+}
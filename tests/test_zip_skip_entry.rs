@@ -0,0 +1,169 @@
+extern crate stream_zipper;
+
+use std::fs;
+
+use stream_zipper::test_util::{assert_every_split, Entry};
+use stream_zipper::zip::{self, ZipError};
+use stream_zipper::State;
+
+/// Reads the entries of the archive, skipping the entries at the indices in `skipped`
+/// once they have produced `skip_after` outputs, and returning their names and data.
+fn read_entries(
+    chunks: &[&[u8]],
+    skipped: &[usize],
+    skip_after: usize,
+) -> Result<Vec<Entry>, ZipError> {
+    let mut zip_file = zip::start_stream();
+    let mut entries = Vec::new();
+    let mut data = Vec::new();
+    let mut outputs = 0;
+    for chunk in chunks {
+        let mut input = *chunk;
+        loop {
+            if skipped.contains(&entries.len()) && outputs == skip_after {
+                zip_file.skip_entry();
+            }
+            match zip_file.read(input)? {
+                State::HasOutput {
+                    unparsed_input,
+                    output,
+                } => {
+                    data.extend_from_slice(output);
+                    outputs += 1;
+                    input = unparsed_input;
+                }
+                State::NeedsInput => break,
+                State::NextFile {
+                    unparsed_input,
+                    next_file,
+                } => {
+                    entries.push(Entry {
+                        name: zip_file.filename().map(<[u8]>::to_vec),
+                        data: std::mem::take(&mut data),
+                    });
+                    outputs = 0;
+                    zip_file = next_file;
+                    input = unparsed_input;
+                }
+                State::EndOfFile => {
+                    entries.push(Entry {
+                        name: zip_file.filename().map(<[u8]>::to_vec),
+                        data,
+                    });
+                    return Ok(entries);
+                }
+                state => panic!("Unexpected state: {:?}", state),
+            }
+        }
+    }
+    panic!("The stream should have ended");
+}
+
+fn entry(name: &str, data: Vec<u8>) -> Entry {
+    Entry {
+        name: Some(name.as_bytes().to_vec()),
+        data,
+    }
+}
+
+#[test]
+fn test_skip_entry_with_sizes() {
+    let data_zip = fs::read("tests/assets/zip/comments.zip").unwrap();
+    let data_repetitive = fs::read("tests/assets/uncompressed/repetitive_data.txt").unwrap();
+
+    assert_eq!(
+        read_entries(&[&data_zip], &[0], 0),
+        Ok(vec![
+            entry("short_data.txt", Vec::new()),
+            entry("repetitive_data.txt", data_repetitive),
+        ])
+    );
+    assert_every_split(&data_zip, |chunks| read_entries(chunks, &[0], 0));
+    assert_every_split(&data_zip, |chunks| read_entries(chunks, &[1], 0));
+}
+
+#[test]
+fn test_skip_entry_with_descriptor() {
+    // The sizes are deferred to data descriptors, so the data is scanned for them.
+    let data_zip = fs::read("tests/assets/zip/zipped_ab.zip").unwrap();
+    let data_b = fs::read("tests/assets/uncompressed/zipped_b.txt").unwrap();
+
+    assert_eq!(
+        read_entries(&[&data_zip], &[0], 0),
+        Ok(vec![
+            entry("zipped_a.txt", Vec::new()),
+            entry("zipped_b.txt", data_b),
+        ])
+    );
+    assert_every_split(&data_zip, |chunks| read_entries(chunks, &[0], 0));
+    assert_every_split(&data_zip, |chunks| read_entries(chunks, &[0, 1], 0));
+}
+
+#[test]
+fn test_skip_entry_with_zip64_descriptor() {
+    let data_zip = fs::read("tests/assets/zip/zip64_descriptor.zip").unwrap();
+    let data_repetitive = fs::read("tests/assets/uncompressed/repetitive_data.txt").unwrap();
+
+    assert_eq!(
+        read_entries(&[&data_zip], &[0], 0),
+        Ok(vec![
+            entry("short_data.txt", Vec::new()),
+            entry("repetitive_data.txt", data_repetitive),
+        ])
+    );
+    assert_every_split(&data_zip, |chunks| read_entries(chunks, &[0, 1], 0));
+}
+
+#[test]
+fn test_skip_entry_with_unsigned_descriptor() {
+    let data_zip = fs::read("tests/assets/zip/unsigned_descriptors.zip").unwrap();
+    let data_short = fs::read("tests/assets/uncompressed/short_data.txt").unwrap();
+
+    assert_eq!(
+        read_entries(&[&data_zip], &[1], 0),
+        Ok(vec![
+            entry("short_data.txt", data_short),
+            entry("repetitive_data.txt", Vec::new()),
+        ])
+    );
+    assert_every_split(&data_zip, |chunks| read_entries(chunks, &[0, 1], 0));
+}
+
+#[test]
+fn test_skip_entry_after_output() {
+    // Whatever was decompressed before the skip is kept.
+    let data_zip = fs::read("tests/assets/zip/zip64_descriptor.zip").unwrap();
+    let data_short = fs::read("tests/assets/uncompressed/short_data.txt").unwrap();
+    let data_repetitive = fs::read("tests/assets/uncompressed/repetitive_data.txt").unwrap();
+
+    let chunks: Vec<&[u8]> = data_zip.chunks(16).collect();
+    let entries = read_entries(&chunks, &[1], 1).unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0], entry("short_data.txt", data_short));
+    assert_eq!(entries[1].name, Some(b"repetitive_data.txt".to_vec()));
+    assert!(!entries[1].data.is_empty());
+    assert!(entries[1].data.len() < data_repetitive.len());
+    assert!(data_repetitive.starts_with(&entries[1].data));
+
+    // The same with the sizes in the local headers.
+    let data_zip = fs::read("tests/assets/zip/comments.zip").unwrap();
+    let chunks: Vec<&[u8]> = data_zip.chunks(16).collect();
+    let entries = read_entries(&chunks, &[1], 1).unwrap();
+    assert_eq!(entries.len(), 2);
+    assert!(entries[1].data.len() < data_repetitive.len());
+    assert!(data_repetitive.starts_with(&entries[1].data));
+}
+
+#[test]
+fn test_skip_encrypted_entry() {
+    // The data is skipped without decrypting it, so no password is needed.
+    let data_zip = fs::read("tests/assets/zip/zipcrypto.zip").unwrap();
+
+    assert_eq!(
+        read_entries(&[&data_zip], &[0, 1], 0),
+        Ok(vec![
+            entry("short_data.txt", Vec::new()),
+            entry("repetitive_data.txt", Vec::new()),
+        ])
+    );
+}
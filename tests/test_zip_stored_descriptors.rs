@@ -0,0 +1,42 @@
+extern crate stream_zipper;
+
+use std::fs;
+
+use stream_zipper::test_util::{assert_every_split, assert_random_splits, read_chunks, Entry};
+
+fn entry(name: &str, data: Vec<u8>) -> Entry {
+    Entry {
+        name: Some(name.as_bytes().to_vec()),
+        data,
+    }
+}
+
+/// The data of decoy.bin, which has two data descriptor signatures in it:
+/// one whose sizes don't match the data before it, and one whose sizes
+/// match, but whose CRC-32 doesn't.
+fn decoy_data() -> Vec<u8> {
+    let mut data = b"abcPK\x07\x08".to_vec();
+    data.extend_from_slice(&[0, 0, 0, 0, 3, 0, 0, 0, 3, 0, 0, 0]);
+    data.extend_from_slice(b"defPK\x07\x08");
+    data.extend_from_slice(&[0, 0, 0, 0, 22, 0, 0, 0, 22, 0, 0, 0]);
+    data.extend_from_slice(b"ghi");
+    data
+}
+
+#[test]
+fn test_stored_entries_with_data_descriptors() {
+    // Written to an unseekable output, so the sizes of the Stored
+    // entries are only in the data descriptors.
+    let data_zip = fs::read("tests/assets/zip/stored_descriptors.zip").unwrap();
+    let data_short = fs::read("tests/assets/uncompressed/short_data.txt").unwrap();
+
+    let expected = vec![
+        entry("short_data.txt", data_short),
+        entry("decoy.bin", decoy_data()),
+        entry("empty.txt", Vec::new()),
+    ];
+    assert_eq!(read_chunks(&[&data_zip]), Ok(expected));
+    assert_every_split(&data_zip, read_chunks);
+    // Descriptors that are split over several inputs.
+    assert_random_splits(&data_zip, 1557, 20, 8, read_chunks);
+}
@@ -0,0 +1,125 @@
+extern crate stream_zipper;
+
+use std::fs;
+
+use stream_zipper::test_util::find_nth;
+use stream_zipper::zip::{self, ZipError, ZipFile};
+use stream_zipper::{DriveError, Error, State};
+
+/// Reads `input` with a zip stream until it needs more input or ends,
+/// returning the file that was in progress.
+fn read_until_end_of_input(input: &[u8]) -> ZipFile {
+    let mut zip_file = zip::start_stream();
+    let mut input = input;
+    loop {
+        match zip_file.read(input).unwrap() {
+            State::HasOutput { unparsed_input, .. } => input = unparsed_input,
+            State::NextFile {
+                unparsed_input,
+                next_file,
+            } => {
+                zip_file = next_file;
+                input = unparsed_input;
+            }
+            State::NeedsInput | State::EndOfFile => return zip_file,
+            state => panic!("Unexpected state: {:?}", state),
+        }
+    }
+}
+
+#[test]
+fn test_complete_archive() {
+    let data_zip = fs::read("tests/assets/zip/comments.zip").unwrap();
+    assert_eq!(read_until_end_of_input(&data_zip).finish(), Ok(()));
+}
+
+#[test]
+fn test_every_truncation() {
+    let data_zip = fs::read("tests/assets/zip/comments.zip").unwrap();
+    for len in 0..data_zip.len() {
+        let result = read_until_end_of_input(&data_zip[..len]).finish();
+        assert!(
+            matches!(result, Err(ZipError::TruncatedArchive { .. })),
+            "{}: {:?}",
+            len,
+            result
+        );
+    }
+}
+
+#[test]
+fn test_truncated_entries() {
+    let data_zip = fs::read("tests/assets/zip/comments.zip").unwrap();
+
+    // In the middle of the first local file header.
+    assert_eq!(
+        read_until_end_of_input(&data_zip[..20]).finish(),
+        Err(ZipError::TruncatedArchive {
+            entry: Some(0),
+            decompressed: 0,
+        })
+    );
+
+    // In the middle of the data of the second entry.
+    let second = find_nth(&data_zip, b"PK\x03\x04", 1);
+    let result = read_until_end_of_input(&data_zip[..second + 200]).finish();
+    match result {
+        Err(ZipError::TruncatedArchive {
+            entry: Some(1),
+            decompressed,
+        }) => assert!(0 < decompressed && decompressed < 65536),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+
+    // In the central directory. Right after the last entry, there isn't yet
+    // enough input to tell whether a data descriptor follows it.
+    let central_dir = find_nth(&data_zip, b"PK\x01\x02", 0);
+    assert_eq!(
+        read_until_end_of_input(&data_zip[..central_dir + 10]).finish(),
+        Err(ZipError::TruncatedArchive {
+            entry: Some(1),
+            decompressed: 65536,
+        })
+    );
+    assert_eq!(
+        read_until_end_of_input(&data_zip[..central_dir + 30]).finish(),
+        Err(ZipError::TruncatedArchive {
+            entry: None,
+            decompressed: 0,
+        })
+    );
+}
+
+#[test]
+fn test_truncated_entry_with_data_descriptor() {
+    let data_zip = fs::read("tests/assets/zip/zipped_ab.zip").unwrap();
+    let data_b = fs::read("tests/assets/uncompressed/zipped_b.txt").unwrap();
+
+    // Right before the data descriptor of the second entry.
+    let descriptor = find_nth(&data_zip, b"PK\x07\x08", 1);
+    assert_eq!(
+        read_until_end_of_input(&data_zip[..descriptor]).finish(),
+        Err(ZipError::TruncatedArchive {
+            entry: Some(1),
+            decompressed: data_b.len() as u64,
+        })
+    );
+}
+
+#[test]
+fn test_drive_truncated_archive() {
+    let data_zip = fs::read("tests/assets/zip/comments.zip").unwrap();
+    let mut buf = [0; 64];
+
+    let result = stream_zipper::drive(&data_zip[..20], &mut buf, |_| Ok(()));
+    match result {
+        Err(DriveError::Stream(Error::Zip(error))) => assert_eq!(
+            error,
+            ZipError::TruncatedArchive {
+                entry: Some(0),
+                decompressed: 0,
+            }
+        ),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+}
@@ -0,0 +1,89 @@
+extern crate stream_zipper;
+
+use std::fs;
+
+use stream_zipper::test_util::{assert_every_split, read_zip_entries, Entry};
+use stream_zipper::zip::headers::{CompressionMethod, Permissions, VersionMadeBy};
+use stream_zipper::zip::{self, ZipError};
+
+/// comments.zip with the compression method of its first entry, in both
+/// of its headers, replaced with one that isn't assigned.
+fn with_unknown_method() -> Vec<u8> {
+    let mut data_zip = fs::read("tests/assets/zip/comments.zip").unwrap();
+    data_zip[8..10].copy_from_slice(&0x1234_u16.to_le_bytes());
+    let central_dir = data_zip
+        .windows(4)
+        .position(|window| window == b"PK\x01\x02")
+        .unwrap();
+    data_zip[central_dir + 10..central_dir + 12].copy_from_slice(&0x1234_u16.to_le_bytes());
+    data_zip
+}
+
+#[test]
+fn test_unknown_compression_method() {
+    let data_zip = with_unknown_method();
+
+    // The entry can be listed...
+    let entries = zip::list_entries(&data_zip).unwrap();
+    assert_eq!(
+        entries[0].compression_method,
+        CompressionMethod::Unknown(0x1234)
+    );
+
+    // ...but not decompressed.
+    assert_eq!(
+        read_zip_entries(&[&data_zip], zip::Options::default())
+            .map_err(|e| e.without_entry().clone()),
+        Err(ZipError::UnsupportedCompressionMethod(
+            CompressionMethod::Unknown(0x1234)
+        ))
+    );
+}
+
+#[test]
+fn test_skip_unknown_compression_method() {
+    let data_zip = with_unknown_method();
+    let data_repetitive = fs::read("tests/assets/uncompressed/repetitive_data.txt").unwrap();
+
+    let options = zip::Options::default()
+        .entry_filter(|header| !matches!(header.compression_method, CompressionMethod::Unknown(_)));
+    assert_eq!(
+        read_zip_entries(&[&data_zip], options.clone()),
+        Ok(vec![
+            Entry {
+                name: Some(b"short_data.txt".to_vec()),
+                data: Vec::new(),
+            },
+            Entry {
+                name: Some(b"repetitive_data.txt".to_vec()),
+                data: data_repetitive,
+            },
+        ])
+    );
+    assert_every_split(&data_zip, |chunks| {
+        read_zip_entries(chunks, options.clone())
+    });
+}
+
+#[test]
+fn test_unknown_version_made_by() {
+    let mut data_zip = fs::read("tests/assets/zip/comments.zip").unwrap();
+    let central_dir = data_zip
+        .windows(4)
+        .position(|window| window == b"PK\x01\x02")
+        .unwrap();
+    data_zip[central_dir + 5] = 200;
+
+    let entries = zip::list_entries(&data_zip).unwrap();
+    assert_eq!(entries[0].version_made_by.1, VersionMadeBy::Unknown(200));
+    assert_eq!(entries[1].version_made_by.1, VersionMadeBy::Unix);
+    // The attributes of an unknown system are taken to be MS-DOS attributes.
+    assert_eq!(entries[0].permissions(), Permissions::Fat(0));
+
+    // The stream parses the central directory too.
+    let options = zip::Options::default().keep_central_dir(true);
+    assert_eq!(
+        read_zip_entries(&[&data_zip], options).map(|entries| entries.len()),
+        Ok(2)
+    );
+}
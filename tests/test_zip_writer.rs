@@ -0,0 +1,285 @@
+extern crate stream_zipper;
+
+use std::fs;
+use std::io::{self, Cursor, Write};
+use std::time::{Duration, UNIX_EPOCH};
+
+use stream_zipper::test_util::{assert_random_splits, read_chunks, Entry};
+use stream_zipper::zip::headers::{
+    CompressionMethod, LocalFileHeader, Permissions, ZIP64_CENTRAL_DIR_END_TAG,
+};
+use stream_zipper::zip::{self, AppendPoint, EntryOptions, SeekArchive, SeekError};
+use stream_zipper::{start_stream, State};
+
+fn entry(name: &str, data: &[u8]) -> Entry {
+    Entry {
+        name: Some(name.as_bytes().to_vec()),
+        data: data.to_vec(),
+    }
+}
+
+#[test]
+fn test_round_trip() {
+    let short = fs::read("tests/assets/uncompressed/short_data.txt").unwrap();
+    let repetitive = fs::read("tests/assets/uncompressed/repetitive_data.txt").unwrap();
+    let mtime = UNIX_EPOCH + Duration::from_secs(1_602_723_662);
+
+    let mut writer = zip::Writer::new(Vec::new());
+    writer
+        .add_entry(
+            "short_data.txt",
+            &EntryOptions::new().last_modified(mtime),
+            &short,
+        )
+        .unwrap();
+    writer
+        .add_entry(
+            "stored.txt",
+            &EntryOptions::new().compression_level(None),
+            &short,
+        )
+        .unwrap();
+    writer
+        .add_entry("dir/", &EntryOptions::new().unix_mode(0o40700), b"")
+        .unwrap();
+    writer
+        .start_entry(
+            "dir/repetitive_data.txt",
+            &EntryOptions::new().size_hint(repetitive.len() as u64),
+        )
+        .unwrap();
+    for chunk in repetitive.chunks(1000) {
+        writer.write_all(chunk).unwrap();
+    }
+    writer
+        .start_entry(
+            "dir/stored_streamed.txt",
+            &EntryOptions::new()
+                .compression_level(None)
+                .size_hint(short.len() as u64),
+        )
+        .unwrap();
+    writer.write_all(&short).unwrap();
+    writer
+        .start_entry("ünicode.txt", &EntryOptions::new())
+        .unwrap();
+    writer.write_all(&short).unwrap();
+    let archive = writer.finish().unwrap();
+
+    let expected = vec![
+        entry("short_data.txt", &short),
+        entry("stored.txt", &short),
+        entry("dir/", b""),
+        entry("dir/repetitive_data.txt", &repetitive),
+        entry("dir/stored_streamed.txt", &short),
+        entry("ünicode.txt", &short),
+    ];
+    assert_eq!(read_chunks(&[&archive]).unwrap(), expected);
+    assert_random_splits(&archive, 1582, 20, 500, |chunks| {
+        read_chunks(chunks).unwrap()
+    });
+
+    let entries = zip::list_entries(&archive).unwrap();
+    assert_eq!(entries.len(), 6);
+    assert_eq!(entries[0].compression_method, CompressionMethod::Deflated);
+    assert_eq!(entries[1].compression_method, CompressionMethod::Stored);
+    assert_eq!(entries[0].permissions(), Permissions::Unix(0o100644));
+    assert_eq!(entries[2].permissions(), Permissions::Unix(0o40700));
+    assert!(entries[2].is_dir());
+    assert!(entries[5].utf8_name);
+    assert_eq!(entries[5].name_str(), "ünicode.txt");
+    for entry in &entries {
+        assert_eq!(entry.zip64, None);
+    }
+    // Only the streamed entry without a size hint needs Zip64 local headers.
+    let versions: Vec<u16> = entries.iter().map(|e| e.version_needed).collect();
+    assert_eq!(versions, [20, 20, 20, 20, 20, 45]);
+
+    let (_, header) = LocalFileHeader::parse(&archive).unwrap();
+    assert_eq!(header.last_mod, mtime);
+    assert_eq!(header.sizes().unwrap().1, short.len() as u64);
+}
+
+#[test]
+fn test_zip64_streamed_entry() {
+    let data = fs::read("tests/assets/uncompressed/repetitive_data.txt").unwrap();
+    let mut writer = zip::Writer::new(Vec::new());
+    writer
+        .start_entry("data.txt", &EntryOptions::new())
+        .unwrap();
+    writer.write_all(&data).unwrap();
+    let archive = writer.finish().unwrap();
+
+    let (_, header) = LocalFileHeader::parse(&archive).unwrap();
+    assert!(header.is_zip64);
+    assert!(header.deferred_sizes);
+    assert_eq!(
+        read_chunks(&[&archive]).unwrap(),
+        [entry("data.txt", &data)]
+    );
+}
+
+#[test]
+fn test_size_hint() {
+    let mut writer = zip::Writer::new(Vec::new());
+    writer
+        .start_entry("data.txt", &EntryOptions::new().size_hint(10))
+        .unwrap();
+    writer.write_all(&[0; 1000]).unwrap();
+    // The hint only picks the headers, so a wrong one is fine
+    // as long as the sizes fit.
+    writer.finish_entry().unwrap();
+
+    assert!(zip::Writer::new(Vec::new()).write_all(b"data").is_err());
+}
+
+#[test]
+fn test_zip64_many_entries() {
+    let mut writer = zip::Writer::new(Vec::new());
+    let options = EntryOptions::new().compression_level(None);
+    for i in 0..70_000 {
+        writer.add_entry(&format!("{}", i), &options, b"").unwrap();
+    }
+    let archive = writer.finish().unwrap();
+
+    // The Zip64 end of central directory record and its locator
+    // precede the classic record.
+    let zip64_end = archive.len() - 22 - 20 - 56;
+    assert!(archive[zip64_end..].starts_with(ZIP64_CENTRAL_DIR_END_TAG));
+
+    let entries = zip::list_entries(&archive).unwrap();
+    assert_eq!(entries.len(), 70_000);
+    assert_eq!(entries[69_999].name(), b"69999");
+    assert_eq!(read_chunks(&[&archive]).unwrap().len(), 70_000);
+}
+
+/// Streams an entry of over 4 GiB through the writer and reads it back.
+/// Takes a while, so it's only run on request.
+#[test]
+#[ignore]
+fn test_zip64_large_entry() {
+    const SIZE: u64 = 4 * 1024 * 1024 * 1024 + 12345;
+
+    let mut writer = zip::Writer::new(Vec::new());
+    writer
+        .start_entry("zeros.bin", &EntryOptions::new().compression_level(Some(1)))
+        .unwrap();
+    let zeros = vec![0; 1024 * 1024];
+    let mut written = 0;
+    while written < SIZE {
+        let len = std::cmp::min(SIZE - written, zeros.len() as u64) as usize;
+        writer.write_all(&zeros[..len]).unwrap();
+        written += len as u64;
+    }
+    let archive = writer.finish().unwrap();
+
+    let entries = zip::list_entries(&archive).unwrap();
+    assert_eq!(entries[0].sizes().unwrap().1, SIZE);
+
+    let mut file = start_stream();
+    let mut input = &archive[..];
+    let mut read = 0;
+    loop {
+        match file.read(input).unwrap() {
+            State::HasOutput {
+                unparsed_input,
+                output,
+            } => {
+                assert!(output.iter().all(|&byte| byte == 0));
+                read += output.len() as u64;
+                input = unparsed_input;
+            }
+            State::NextFile { .. } | State::EndOfFile => break,
+            state => panic!("Unexpected state: {:?}", state),
+        }
+    }
+    assert_eq!(read, SIZE);
+}
+
+#[test]
+fn test_append() {
+    let original = fs::read("tests/assets/zip/comments.zip").unwrap();
+    let short = fs::read("tests/assets/uncompressed/short_data.txt").unwrap();
+    let repetitive = fs::read("tests/assets/uncompressed/repetitive_data.txt").unwrap();
+
+    let mut writer = zip::Writer::append_seekable(Cursor::new(original.clone())).unwrap();
+    writer
+        .add_entry("appended.txt", &EntryOptions::new(), &short)
+        .unwrap();
+    let archive = writer.finish().unwrap().into_inner();
+
+    // The data of the existing entries is untouched.
+    let point = AppendPoint::find(&mut Cursor::new(&original)).unwrap();
+    let position = point.position() as usize;
+    assert_eq!(archive[..position], original[..position]);
+
+    assert_eq!(
+        read_chunks(&[&archive]).unwrap(),
+        [
+            entry("short_data.txt", &short),
+            entry("repetitive_data.txt", &repetitive),
+            entry("appended.txt", &short),
+        ]
+    );
+    let archive = SeekArchive::new(Cursor::new(archive)).unwrap();
+    assert_eq!(archive.central_dir_end().comment, b"archive comment");
+    let entries = archive.entries();
+    assert_eq!(entries.len(), 3);
+    assert_eq!(entries[0].comment, b"comment for short_data.txt");
+    assert_eq!(entries[2].name(), b"appended.txt");
+    assert_eq!(entries[2].local_header_offset(), Some(point.position()));
+}
+
+#[test]
+fn test_append_with_prefix() {
+    let mut original = vec![0xff; 100];
+    original.extend(fs::read("tests/assets/zip/short_data.txt.zip").unwrap());
+    let short = fs::read("tests/assets/uncompressed/short_data.txt").unwrap();
+
+    let mut writer = zip::Writer::append_seekable(Cursor::new(original)).unwrap();
+    writer
+        .add_entry("appended.txt", &EntryOptions::new(), b"appended")
+        .unwrap();
+    let archive = writer.finish().unwrap();
+
+    let mut archive = SeekArchive::new(archive).unwrap();
+    assert_eq!(archive.entries().len(), 2);
+    for (index, expected) in [&short[..], b"appended"].iter().enumerate() {
+        let mut data = Vec::new();
+        archive
+            .read_entry(index, |out| {
+                data.extend_from_slice(out);
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(data, *expected);
+    }
+}
+
+#[test]
+fn test_append_from_tail() {
+    let original = fs::read("tests/assets/zip/comments.zip").unwrap();
+    let short = fs::read("tests/assets/uncompressed/short_data.txt").unwrap();
+
+    // A sink that can only be appended to, given the whole archive as its tail.
+    let point = AppendPoint::from_tail(&original, original.len() as u64).unwrap();
+    let mut archive = original[..point.position() as usize].to_vec();
+    let mut writer = zip::Writer::append(&mut archive, point);
+    writer
+        .start_entry("streamed.txt", &EntryOptions::new())
+        .unwrap();
+    writer.write_all(&short).unwrap();
+    writer.finish().unwrap();
+    assert_eq!(zip::list_entries(&archive).unwrap().len(), 3);
+    assert_eq!(
+        read_chunks(&[&archive]).unwrap()[2],
+        entry("streamed.txt", &short)
+    );
+
+    // The tail doesn't reach back to the central directory.
+    let tail = &original[original.len() - 50..];
+    match AppendPoint::from_tail(tail, original.len() as u64) {
+        Err(SeekError::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof),
+        result => panic!("Unexpected result: {:?}", result.map(|_| ())),
+    }
+}
@@ -0,0 +1,115 @@
+extern crate stream_zipper;
+
+use std::fs;
+
+use stream_zipper::test_util::{assert_every_split, read_zip_entries, Entry};
+use stream_zipper::zip::{self, ZipError};
+use stream_zipper::State;
+
+#[test]
+fn test_zipcrypto() {
+    // Made with Info-ZIP's `zip -P secret`, which defers the sizes
+    // to data descriptors and checks the password against the modification time.
+    let data_zip = fs::read("tests/assets/zip/zipcrypto.zip").unwrap();
+    let data_short = fs::read("tests/assets/uncompressed/short_data.txt").unwrap();
+    let data_repetitive = fs::read("tests/assets/uncompressed/repetitive_data.txt").unwrap();
+
+    let options = zip::Options::default().password("secret");
+    assert_eq!(
+        read_zip_entries(&[&data_zip], options.clone()),
+        Ok(vec![
+            Entry {
+                name: Some(b"short_data.txt".to_vec()),
+                data: data_short,
+            },
+            Entry {
+                name: Some(b"repetitive_data.txt".to_vec()),
+                data: data_repetitive,
+            },
+        ])
+    );
+    assert_every_split(&data_zip, |chunks| {
+        read_zip_entries(chunks, options.clone())
+    });
+}
+
+#[test]
+fn test_zipcrypto_wrong_password() {
+    let data_zip = fs::read("tests/assets/zip/zipcrypto.zip").unwrap();
+
+    let options = zip::Options::default().password("wrong");
+    assert_eq!(
+        read_zip_entries(&[&data_zip], options).map_err(|e| e.without_entry().clone()),
+        Err(ZipError::InvalidPassword)
+    );
+}
+
+#[test]
+fn test_zipcrypto_crc_check_byte() {
+    // The sizes are in the local header, so the password is checked
+    // against the CRC-32 instead of the modification time.
+    let data_zip = fs::read("tests/assets/zip/zipcrypto_crc_check.zip").unwrap();
+    let data_short = fs::read("tests/assets/uncompressed/short_data.txt").unwrap();
+
+    let options = zip::Options::default().password(b"secret");
+    assert_eq!(
+        read_zip_entries(&[&data_zip], options.clone()),
+        Ok(vec![Entry {
+            name: Some(b"short_data.txt".to_vec()),
+            data: data_short,
+        }])
+    );
+    assert_every_split(&data_zip, |chunks| {
+        read_zip_entries(chunks, options.clone())
+    });
+
+    let options = zip::Options::default().password(b"wrong");
+    assert_eq!(
+        read_zip_entries(&[&data_zip], options).map_err(|e| e.without_entry().clone()),
+        Err(ZipError::InvalidPassword)
+    );
+}
+
+#[test]
+fn test_encrypted_entry_without_password() {
+    let data_zip = fs::read("tests/assets/zip/zipcrypto_crc_check.zip").unwrap();
+
+    assert_eq!(
+        read_zip_entries(&[&data_zip], zip::Options::default())
+            .map_err(|e| e.without_entry().clone()),
+        Err(ZipError::EncryptedEntry)
+    );
+}
+
+#[test]
+fn test_skip_encrypted_entries() {
+    let data_zip = fs::read("tests/assets/zip/zipcrypto_crc_check.zip").unwrap();
+
+    let options = zip::Options::default().skip_encrypted_entries(true);
+    assert_eq!(
+        read_zip_entries(&[&data_zip], options.clone()),
+        Ok(vec![Entry {
+            name: Some(b"short_data.txt".to_vec()),
+            data: Vec::new(),
+        }])
+    );
+    assert_every_split(&data_zip, |chunks| {
+        read_zip_entries(chunks, options.clone())
+    });
+
+    let mut zip_file = zip::start_stream_with(options);
+    assert!(matches!(zip_file.read(&data_zip), Ok(State::EndOfFile)));
+    assert!(zip_file.header().unwrap().encrypted);
+}
+
+#[test]
+fn test_skip_encrypted_entries_with_deferred_sizes() {
+    // The end of the data can't be found without decrypting it.
+    let data_zip = fs::read("tests/assets/zip/zipcrypto.zip").unwrap();
+
+    let options = zip::Options::default().skip_encrypted_entries(true);
+    assert_eq!(
+        read_zip_entries(&[&data_zip], options).map_err(|e| e.without_entry().clone()),
+        Err(ZipError::EncryptedEntry)
+    );
+}
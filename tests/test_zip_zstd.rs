@@ -0,0 +1,85 @@
+extern crate stream_zipper;
+
+use std::fs;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use stream_zipper::test_util::{assert_every_split, read_chunks, Entry};
+use stream_zipper::zip::{self, ZipError};
+use stream_zipper::zstd::{Backend, InvalidStream, LibZstd, Progress};
+use stream_zipper::State;
+
+fn expected_entries() -> Vec<Entry> {
+    vec![
+        Entry {
+            name: Some(b"repetitive_data.txt".to_vec()),
+            data: fs::read("tests/assets/uncompressed/repetitive_data.txt").unwrap(),
+        },
+        Entry {
+            name: Some(b"short_data.txt".to_vec()),
+            data: fs::read("tests/assets/uncompressed/short_data.txt").unwrap(),
+        },
+    ]
+}
+
+#[test]
+fn test_zstd() {
+    // The entries have data descriptors, so the end of each zstd frame
+    // must be found from the frame itself.
+    let data_zip = fs::read("tests/assets/zip/zstd.zip").unwrap();
+
+    assert_eq!(read_chunks(&[&data_zip]), Ok(expected_entries()));
+    assert_every_split(&data_zip, read_chunks);
+}
+
+/// Delegates to libzstd, counting the calls.
+struct CountingBackend {
+    inner: LibZstd,
+    calls: Arc<AtomicUsize>,
+}
+
+impl Backend for CountingBackend {
+    fn decompress(&mut self, input: &[u8], output: &mut [u8]) -> Result<Progress, InvalidStream> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        self.inner.decompress(input, output)
+    }
+}
+
+/// Rejects everything.
+struct FailingBackend;
+
+impl Backend for FailingBackend {
+    fn decompress(&mut self, _: &[u8], _: &mut [u8]) -> Result<Progress, InvalidStream> {
+        Err(InvalidStream)
+    }
+}
+
+#[test]
+fn test_zstd_pluggable_backend() {
+    let data_zip = fs::read("tests/assets/zip/zstd.zip").unwrap();
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    let backend_calls = calls.clone();
+    let options = zip::Options::default().zstd_backend(move || {
+        Box::new(CountingBackend {
+            inner: LibZstd::default(),
+            calls: backend_calls.clone(),
+        })
+    });
+
+    let mut zip_file = zip::start_stream_with(options);
+    let mut output = Vec::new();
+    let state = zip_file
+        .read_with(&data_zip, |out| output.extend_from_slice(out))
+        .unwrap();
+    assert!(matches!(state, State::NextFile { .. }));
+    assert_eq!(output, expected_entries()[0].data);
+    assert!(calls.load(Ordering::SeqCst) > 0);
+
+    let options = zip::Options::default().zstd_backend(|| Box::new(FailingBackend));
+    let res = zip::start_stream_with(options).read_with(&data_zip, |_| ());
+    assert_eq!(
+        res.unwrap_err().without_entry(),
+        &ZipError::InvalidZstdStream
+    );
+}
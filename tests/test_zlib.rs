@@ -0,0 +1,94 @@
+extern crate stream_zipper;
+
+use std::fs;
+
+use stream_zipper::test_util::{assert_every_split, read_chunks, Entry};
+use stream_zipper::zlib::headers::StreamHeader;
+use stream_zipper::zlib::{self, ZlibError};
+use stream_zipper::{Error, State};
+
+#[test]
+fn test_parsing_stream_header() {
+    let data_zlib = fs::read("tests/assets/zlib/short_data.txt.zlib").unwrap();
+
+    let (unparsed, header) = StreamHeader::parse(&data_zlib).expect("Should be able to parse");
+    assert_eq!(unparsed.len(), data_zlib.len() - 2);
+    assert_eq!(
+        header,
+        StreamHeader {
+            window_bits: 15,
+            level: 3,
+            preset_dictionary: None,
+        }
+    );
+}
+
+#[test]
+fn test_decompression() {
+    for name in &["short_data.txt", "repetitive_data.txt", "numbers.txt"] {
+        let data_zlib = fs::read(format!("tests/assets/zlib/{}.zlib", name)).unwrap();
+        let data = fs::read(format!("tests/assets/uncompressed/{}", name)).unwrap();
+
+        let mut output = Vec::new();
+        let mut zlib_file = zlib::start_stream();
+        let state = zlib_file
+            .read_with(&data_zlib, |out| output.extend_from_slice(out))
+            .unwrap();
+        assert!(matches!(state, State::EndOfFile));
+        assert_eq!(output, data);
+    }
+}
+
+#[test]
+fn test_autodetect() {
+    // The output of short_data.txt starts within the bytes read to detect the format.
+    for name in &["short_data.txt", "repetitive_data.txt"] {
+        let data_zlib = fs::read(format!("tests/assets/zlib/{}.zlib", name)).unwrap();
+        let data = fs::read(format!("tests/assets/uncompressed/{}", name)).unwrap();
+
+        assert_eq!(
+            read_chunks(&[&data_zlib]),
+            Ok(vec![Entry { name: None, data }])
+        );
+        assert_every_split(&data_zlib, read_chunks);
+    }
+}
+
+#[test]
+fn test_checksum_mismatch() {
+    let mut data_zlib = fs::read("tests/assets/zlib/short_data.txt.zlib").unwrap();
+    *data_zlib.last_mut().unwrap() ^= 1;
+
+    let res = zlib::start_stream().read_with(&data_zlib, |_| ());
+    assert_eq!(res.unwrap_err(), ZlibError::ChecksumMismatch);
+    assert_eq!(
+        read_chunks(&[&data_zlib]),
+        Err(Error::Zlib(ZlibError::ChecksumMismatch))
+    );
+}
+
+#[test]
+fn test_preset_dictionary() {
+    let data_zlib = fs::read("tests/assets/zlib/preset_dictionary.zlib").unwrap();
+
+    let res = zlib::start_stream().read_with(&data_zlib, |_| ());
+    assert_eq!(res.unwrap_err(), ZlibError::PresetDictionary);
+}
+
+#[test]
+fn test_read_after_error() {
+    let mut data_zlib = fs::read("tests/assets/zlib/short_data.txt.zlib").unwrap();
+    *data_zlib.last_mut().unwrap() ^= 1;
+
+    let mut zlib_file = zlib::start_stream();
+    let res = zlib_file.read_with(&data_zlib, |_| ());
+    assert_eq!(res.unwrap_err(), ZlibError::ChecksumMismatch);
+    assert_eq!(
+        zlib_file.read(&[]).unwrap_err(),
+        ZlibError::ChecksumMismatch
+    );
+    assert_eq!(
+        zlib_file.read(&data_zlib).unwrap_err(),
+        ZlibError::ChecksumMismatch
+    );
+}